@@ -0,0 +1,76 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rs::account::Account;
+use rs::blockchain::block::Block;
+use rs::config::chain::ChainRules;
+use rs::config::DEFAULT_CHAIN_ID;
+use rs::transaction::tx::Transaction;
+use rs::util::clock::SystemClock;
+use rs::util::{keccak_bytes, keccak_hash, sort_characters};
+
+//keccak_hash() hashes the serialized value with its characters sorted. It does that from byte counts without
+// building the string, and sorted_string is the old way of doing it, kept here to compare against
+
+fn transfer() -> Transaction {
+    let to = Account::new(vec![]).public_account.address;
+    Transaction::create_transaction(
+        Some(Account::new(vec![])),
+        Some(to),
+        10,
+        None,
+        100,
+        DEFAULT_CHAIN_ID,
+    )
+}
+
+fn block_with(tx_count: usize) -> Block {
+    let tx_series = (0..tx_count).map(|_| transfer()).collect();
+    Block::mine_block(
+        &Block::genesis(&SystemClock, DEFAULT_CHAIN_ID),
+        Account::new(vec![]).public_account.address,
+        tx_series,
+        "",
+        &SystemClock,
+        &ChainRules::default(),
+    )
+}
+
+fn bench_structures(c: &mut Criterion) {
+    let tx = transfer();
+    let block = block_with(10);
+
+    let mut group = c.benchmark_group("keccak_hash");
+    group.bench_function("tx", |b| b.iter(|| keccak_hash(&tx)));
+    group.bench_function("block_headers", |b| {
+        b.iter(|| keccak_hash(&block.block_headers))
+    });
+    group.bench_function("block_10_txs", |b| b.iter(|| keccak_hash(&block)));
+    group.finish();
+
+    let mut group = c.benchmark_group("sorted_string");
+    group.bench_function("tx", |b| {
+        b.iter(|| keccak_bytes(sort_characters(&tx).as_bytes()))
+    });
+    group.bench_function("block_10_txs", |b| {
+        b.iter(|| keccak_bytes(sort_characters(&block).as_bytes()))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("sort_characters");
+    group.bench_function("tx", |b| b.iter(|| sort_characters(&tx)));
+    group.bench_function("block_10_txs", |b| b.iter(|| sort_characters(&block)));
+    group.finish();
+}
+
+fn bench_block_sizes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("keccak_hash_block");
+    for tx_count in [1, 10, 100].iter() {
+        let block = block_with(*tx_count);
+        group.bench_with_input(BenchmarkId::from_parameter(tx_count), tx_count, |b, _| {
+            b.iter(|| keccak_hash(&block))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_structures, bench_block_sizes);
+criterion_main!(benches);
@@ -0,0 +1,55 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rs::store::trie::Trie;
+
+//a put() only rehashes the nodes on its key's path, so these should grow about as the depth does
+const SIZES: [usize; 3] = [10, 100, 1000];
+
+fn trie_with(size: usize) -> Trie {
+    let mut trie = Trie::new();
+    for i in 0..size {
+        trie.put(i.to_string(), format!("value-{}", i));
+    }
+    trie
+}
+
+fn bench_put(c: &mut Criterion) {
+    let mut group = c.benchmark_group("trie_put");
+    for size in SIZES.iter() {
+        let trie = trie_with(*size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, &size| {
+            b.iter_batched(
+                || trie.clone(),
+                |mut trie| trie.put(size.to_string(), "new".into()),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_get(c: &mut Criterion) {
+    let mut group = c.benchmark_group("trie_get");
+    for size in SIZES.iter() {
+        let trie = trie_with(*size);
+        let key = (size / 2).to_string();
+        group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, _| {
+            b.iter(|| trie.get(key.clone()))
+        });
+    }
+    group.finish();
+}
+
+fn bench_proof(c: &mut Criterion) {
+    let mut group = c.benchmark_group("trie_proof");
+    for size in SIZES.iter() {
+        let trie = trie_with(*size);
+        let key = (size / 2).to_string();
+        group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, _| {
+            b.iter(|| Trie::verify_proof(&trie.root_hash, &key, &trie.get_proof(&key).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_put, bench_get, bench_proof);
+criterion_main!(benches);
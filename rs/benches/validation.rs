@@ -0,0 +1,71 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rs::account::Account;
+use rs::blockchain::block::Block;
+use rs::config::chain::ChainRules;
+use rs::config::DEFAULT_CHAIN_ID;
+use rs::store::state::State;
+use rs::store::trie::Trie;
+use rs::transaction::tx::Transaction;
+use rs::util::clock::SystemClock;
+
+/// a block on top of genesis with tx_count transfers, each from its own funded sender,
+/// and the state it validates against
+fn block_with(tx_count: usize) -> (Block, Block, State) {
+    let mut state = State::new();
+    let receiver = Account::new(vec![]).public_account.address;
+    let tx_series = (0..tx_count)
+        .map(|_| {
+            let sender = Account::new(vec![]);
+            state.allocate(sender.public_account.address, 1000);
+            Transaction::create_transaction(
+                Some(sender),
+                Some(receiver),
+                10,
+                None,
+                100,
+                DEFAULT_CHAIN_ID,
+            )
+        })
+        .collect();
+    let genesis = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
+    let state_root = state.get_state_root().clone();
+    let block = Block::mine_block(
+        &genesis,
+        Account::new(vec![]).public_account.address,
+        tx_series,
+        &state_root,
+        &SystemClock,
+        &ChainRules::default(),
+    );
+    (genesis, block, state)
+}
+
+fn bench_validate_block(c: &mut Criterion) {
+    let mut group = c.benchmark_group("validate_block");
+    group.sample_size(20);
+    let rules = ChainRules::default();
+    for tx_count in [1, 10, 100].iter() {
+        let (genesis, block, state) = block_with(*tx_count);
+        //validation runs contract code on an overlay, so the same state can be reused for every run.
+        // After the first run the signatures come out of the signature cache - a block whose tx went through our
+        // preflight or queue first is the same
+        group.bench_with_input(BenchmarkId::from_parameter(tx_count), tx_count, |b, _| {
+            b.iter(|| assert!(Block::validate_block(&genesis, &block, &state, &rules)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_build_tx_trie(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build_tx_trie");
+    for tx_count in [1, 10, 100].iter() {
+        let (_, block, _) = block_with(*tx_count);
+        group.bench_with_input(BenchmarkId::from_parameter(tx_count), tx_count, |b, _| {
+            b.iter(|| Trie::build_trie(block.tx_series.clone()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_validate_block, bench_build_tx_trie);
+criterion_main!(benches);
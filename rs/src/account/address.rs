@@ -0,0 +1,201 @@
+use secp256k1::PublicKey;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha3::{Digest, Keccak256};
+use std::fmt;
+use std::str::FromStr;
+
+pub const ADDRESS_LENGTH: usize = 20;
+
+/// what accounts are known by - in the state, in tx and over the api. The 20 byte ethereum style address of the
+/// account's public key, written as 0x-prefixed hex
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address([u8; ADDRESS_LENGTH]);
+
+impl Address {
+    /// last 20 bytes of the keccak hash of the uncompressed public key (without its 0x04 prefix), same as real ethereum
+    pub fn from_public_key(public_key: &PublicKey) -> Self {
+        let hash = Keccak256::digest(&public_key.serialize_uncompressed()[1..]);
+        let mut address = [0u8; ADDRESS_LENGTH];
+        address.copy_from_slice(&hash[12..]);
+        Address(address)
+    }
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() != ADDRESS_LENGTH {
+            return Err(format!(
+                "an address is {} bytes, not {}.",
+                ADDRESS_LENGTH,
+                bytes.len()
+            ));
+        }
+        let mut address = [0u8; ADDRESS_LENGTH];
+        address.copy_from_slice(bytes);
+        Ok(Address(address))
+    }
+    pub fn as_bytes(&self) -> &[u8; ADDRESS_LENGTH] {
+        &self.0
+    }
+    /// lowercase, without the 0x - the key the account is stored under in the state trie
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+    /// what contract code sees of the address, eg from CALLER - stack values are i32s, so it's the first 4 bytes
+    /// squeezed into 1..=i32::MAX. Positive so contracts can tell it apart from their own slots at 0 and below.
+    /// Not unique, only hard to collide on by accident
+    pub fn short(&self) -> i32 {
+        let mut first = [0u8; 4];
+        first.copy_from_slice(&self.0[..4]);
+        (u32::from_be_bytes(first) % i32::MAX as u32) as i32 + 1
+    }
+}
+
+//lowercase. to_checksum_address() for the EIP-55 form
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", self.to_hex())
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl FromStr for Address {
+    type Err = String;
+    fn from_str(address: &str) -> Result<Self, String> {
+        parse_address(address)
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let address = String::deserialize(deserializer)?;
+        parse_address(&address).map_err(serde::de::Error::custom)
+    }
+}
+
+/// EIP-55 - a hex letter is uppercased if the matching nibble of the hash of the lowercase address is >= 8
+pub fn to_checksum_address(address: &Address) -> String {
+    let lower = address.to_hex();
+    let hash = hex::encode(Keccak256::digest(lower.as_bytes()));
+    let checksummed: String = lower
+        .chars()
+        .zip(hash.chars())
+        .map(|(c, h)| {
+            if h.to_digit(16).unwrap() >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+    format!("0x{}", checksummed)
+}
+
+/// all lowercase or all uppercase input carries no checksum and is accepted as is. Mixed case has to match EIP-55
+pub fn parse_address(address: &str) -> Result<Address, String> {
+    let hex_part = address
+        .strip_prefix("0x")
+        .filter(|h| h.len() == ADDRESS_LENGTH * 2)
+        .ok_or_else(|| format!("invalid address {}: expected 0x + 40 hex chars.", address))?;
+    let bytes =
+        hex::decode(hex_part).map_err(|_| format!("invalid address {}: not hex.", address))?;
+    let parsed = Address::from_bytes(&bytes)?;
+
+    let is_mixed_case = hex_part.chars().any(|c| c.is_ascii_lowercase())
+        && hex_part.chars().any(|c| c.is_ascii_uppercase());
+    if is_mixed_case {
+        let expected = to_checksum_address(&parsed);
+        if expected != address {
+            return Err(format!(
+                "invalid address checksum for {}: expected {}.",
+                address, expected
+            ));
+        }
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::hd_wallet::HdWallet;
+
+    /// examples from https://eips.ethereum.org/EIPS/eip-55
+    const CHECKSUMMED: [&str; 4] = [
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ];
+
+    #[test]
+    fn test_checksum_roundtrip() {
+        for address in CHECKSUMMED.iter() {
+            let parsed = parse_address(address).unwrap();
+            assert_eq!(&to_checksum_address(&parsed), address);
+            //no checksum to verify
+            assert_eq!(parse_address(&address.to_lowercase()).unwrap(), parsed);
+        }
+    }
+
+    #[test]
+    fn test_display_and_serde() {
+        let address = parse_address(CHECKSUMMED[0]).unwrap();
+        assert_eq!(address.to_string(), CHECKSUMMED[0].to_lowercase());
+        let json = serde_json::to_string(&address).unwrap();
+        assert_eq!(json, format!("\"{}\"", CHECKSUMMED[0].to_lowercase()));
+        assert_eq!(serde_json::from_str::<Address>(&json).unwrap(), address);
+        assert!(serde_json::from_str::<Address>("\"0x1234\"").is_err());
+    }
+
+    #[test]
+    fn test_bad_checksum() {
+        //last letter's case flipped
+        let err = parse_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").unwrap_err();
+        assert!(err.contains("expected 0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"));
+        assert!(parse_address("5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").is_err());
+        assert!(parse_address("0x5aAeb6").is_err());
+        assert!(parse_address("0xzzAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").is_err());
+    }
+
+    #[test]
+    fn test_short() {
+        let address = |first: [u8; 4]| {
+            let mut bytes = [0xab; ADDRESS_LENGTH];
+            bytes[..4].copy_from_slice(&first);
+            Address(bytes)
+        };
+        assert_eq!(address([0, 0, 0, 0]).short(), 1);
+        assert_eq!(address([0, 0, 1, 0]).short(), 257);
+        assert_eq!(address([0x7f, 0xff, 0xff, 0xfe]).short(), i32::MAX);
+        //wraps around instead of going negative
+        assert_eq!(address([0x7f, 0xff, 0xff, 0xff]).short(), 1);
+        assert_eq!(address([0xff, 0xff, 0xff, 0xff]).short(), 2);
+    }
+
+    /// same address metamask shows for the first account of the test phrase
+    #[test]
+    fn test_eth_address_from_public_key() {
+        let wallet = HdWallet::from_mnemonic(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+        )
+        .unwrap();
+        let account = wallet.derive_account(0).unwrap();
+        assert_eq!(
+            to_checksum_address(&account.public_account.address),
+            "0x9858EfFD232B4033E47d90003D41EC34EcaEda94"
+        );
+        assert_eq!(
+            account.public_account.address,
+            Address::from_public_key(&account.public_key())
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use crate::account::address::Address;
+use crate::error::StoreError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+/// longest name the address book takes
+pub const MAX_NAME_LEN: usize = 64;
+
+/// an address as the api takes it: 0x hex, or a name from the address book for the chain the node is on. Left as
+/// it came in until the handler resolves it, see GlobalState::resolve_address()
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AddressInput(pub String);
+
+impl From<Address> for AddressInput {
+    fn from(address: Address) -> Self {
+        Self(address.to_string())
+    }
+}
+
+impl Deref for AddressInput {
+    type Target = str;
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct NamedAddress {
+    pub name: String,
+    #[schema(value_type = String)]
+    pub address: Address,
+}
+
+/// human readable names for addresses, local to this node - nothing about them goes on chain. Kept per chain id:
+/// the same name can stand for different addresses on different chains, and a datadir reused for another chain
+/// doesn't resolve names meant for the old one. With a datadir it's <datadir>/addressbook.json
+#[derive(Debug, Default)]
+pub struct AddressBook {
+    path: Option<PathBuf>,
+    chains: BTreeMap<u64, BTreeMap<String, Address>>,
+}
+
+impl AddressBook {
+    /// empty if the file isn't there yet - it's only written on the first change
+    pub fn open(path: &Path) -> Result<Self, StoreError> {
+        let chains = if path.exists() {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| StoreError::Io(format!("failed to read {:?}: {}", path, e)))?;
+            serde_json::from_str(&contents).map_err(|e| StoreError::Corrupt {
+                key: format!("{:?}", path),
+                reason: e.to_string(),
+            })?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            chains,
+        })
+    }
+
+    pub fn get(&self, chain_id: u64, name: &str) -> Option<Address> {
+        self.chains.get(&chain_id)?.get(name).copied()
+    }
+
+    /// by name
+    pub fn entries(&self, chain_id: u64) -> Vec<NamedAddress> {
+        self.chains
+            .get(&chain_id)
+            .into_iter()
+            .flatten()
+            .map(|(name, address)| NamedAddress {
+                name: name.clone(),
+                address: *address,
+            })
+            .collect()
+    }
+
+    /// adds the name, or points it somewhere else. Returns where it pointed before
+    pub fn set(
+        &mut self,
+        chain_id: u64,
+        name: &str,
+        address: Address,
+    ) -> Result<Option<Address>, String> {
+        validate_name(name)?;
+        let previous = self
+            .chains
+            .entry(chain_id)
+            .or_default()
+            .insert(name.to_string(), address);
+        if let Err(e) = self.save() {
+            self.restore(chain_id, name, previous);
+            return Err(e.to_string());
+        }
+        Ok(previous)
+    }
+
+    /// None if there was no such name
+    pub fn remove(&mut self, chain_id: u64, name: &str) -> Result<Option<Address>, String> {
+        let previous = match self.chains.get_mut(&chain_id) {
+            Some(names) => names.remove(name),
+            None => None,
+        };
+        if previous.is_some() {
+            if let Err(e) = self.save() {
+                self.restore(chain_id, name, previous);
+                return Err(e.to_string());
+            }
+        }
+        Ok(previous)
+    }
+
+    /// 0x hex as is, anything else is looked up as a name
+    pub fn resolve(&self, chain_id: u64, input: &str) -> Result<Address, String> {
+        if input.starts_with("0x") {
+            return Address::from_str(input);
+        }
+        self.get(chain_id, input).ok_or_else(|| {
+            format!(
+                "{} is neither an address nor a name in the address book",
+                input
+            )
+        })
+    }
+
+    //undoes a change that couldn't be saved, so memory and file don't drift apart
+    fn restore(&mut self, chain_id: u64, name: &str, previous: Option<Address>) {
+        let names = self.chains.entry(chain_id).or_default();
+        match previous {
+            Some(address) => names.insert(name.to_string(), address),
+            None => names.remove(name),
+        };
+    }
+
+    fn save(&self) -> Result<(), StoreError> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let chains: BTreeMap<_, _> = self
+            .chains
+            .iter()
+            .filter(|(_, names)| !names.is_empty())
+            .collect();
+        fs::write(path, serde_json::to_string_pretty(&chains).unwrap())
+            .map_err(|e| StoreError::Io(format!("failed to write {:?}: {}", path, e)))
+    }
+}
+
+/// lowercase letters, digits, '.', '-' and '_', starting with a letter - so a name can never be mistaken for an
+/// address, or the other way round
+pub fn validate_name(name: &str) -> Result<(), String> {
+    let starts_with_letter = name.chars().next().is_some_and(|c| c.is_ascii_lowercase());
+    let allowed = name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '.' || c == '-' || c == '_');
+    if !starts_with_letter || !allowed || name.len() > MAX_NAME_LEN {
+        return Err(format!(
+            "invalid name {:?}: up to {} lowercase letters, digits, '.', '-' or '_', starting with a letter",
+            name, MAX_NAME_LEN
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::gen_address;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_names_per_chain() {
+        let path = std::env::temp_dir().join(format!("addressbook-{}.json", Uuid::new_v4()));
+        let (alice, bob) = (gen_address(), gen_address());
+        let mut book = AddressBook::open(&path).unwrap();
+        assert_eq!(book.set(1, "alice", alice), Ok(None));
+        assert_eq!(book.set(2, "alice", bob), Ok(None));
+        assert_eq!(book.resolve(1, "alice"), Ok(alice));
+        assert_eq!(book.resolve(2, "alice"), Ok(bob));
+        assert!(book.resolve(3, "alice").is_err());
+        assert_eq!(book.resolve(3, &bob.to_string()), Ok(bob));
+        assert!(book.resolve(1, "0xnothex").is_err());
+
+        //survives a restart
+        let mut book = AddressBook::open(&path).unwrap();
+        assert_eq!(
+            book.entries(1),
+            vec![NamedAddress {
+                name: "alice".into(),
+                address: alice
+            }]
+        );
+        assert_eq!(book.set(1, "alice", bob), Ok(Some(alice)));
+        assert_eq!(book.remove(2, "alice"), Ok(Some(bob)));
+        assert_eq!(book.remove(2, "alice"), Ok(None));
+        let book = AddressBook::open(&path).unwrap();
+        assert_eq!(book.get(1, "alice"), Some(bob));
+        assert!(book.entries(2).is_empty());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("alice").is_ok());
+        assert!(validate_name("treasury.v2_old-1").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("Alice").is_err());
+        assert!(validate_name("0xalice").is_err());
+        assert!(validate_name("1alice").is_err());
+        assert!(validate_name("al ice").is_err());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+
+        let mut book = AddressBook::default();
+        assert!(book.set(1, "Alice", gen_address()).is_err());
+        assert!(book.entries(1).is_empty());
+    }
+}
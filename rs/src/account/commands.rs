@@ -0,0 +1,291 @@
+use crate::account::address::Address;
+use crate::account::hd_wallet::{generate_mnemonic, HdWallet};
+use crate::account::keystore::Keystore;
+use crate::account::vanity::{default_threads, find_vanity_account};
+use crate::config::datadir::DataDir;
+use crate::config::next_value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub const USAGE: &str = "usage:
+  rs account import --datadir <dir> --name <name> --key <hex secret key> [--passphrase <pw>]
+  rs account import --datadir <dir> --keyfile <path> [--name <name>] --passphrase <pw>
+  rs account export --datadir <dir> <address|name> --passphrase <pw>
+  rs account export --datadir <dir> <address|name> --out <path>
+  rs account mnemonic [--count <n>]
+  rs account restore --datadir <dir> --mnemonic <phrase> [--count <n>] [--passphrase <pw>]
+  rs account vanity --prefix <hex> [--threads <n>] [--datadir <dir> --name <name> [--passphrase <pw>]]";
+
+/// `rs account <import|export> ...` - works on <datadir>/keystore directly, so the node doesn't have to be running.
+/// Takes everything after "account" and returns what should be printed.
+/// --datadir and --passphrase fall back to NODE_DATADIR and NODE_KEYSTORE_PASSWORD, same as for the node itself
+pub fn run_account_command<F>(args: &[String], lookup: F) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut args = args.iter();
+    let command = args.next().ok_or_else(|| USAGE.to_string())?;
+
+    let mut datadir = lookup("NODE_DATADIR").map(PathBuf::from);
+    let mut passphrase = lookup("NODE_KEYSTORE_PASSWORD");
+    let mut name = None;
+    let mut key = None;
+    let mut keyfile = None;
+    let mut out = None;
+    let mut target = None;
+    let mut mnemonic = None;
+    let mut count = 1;
+    let mut prefix = None;
+    let mut threads = default_threads();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--datadir" => datadir = Some(PathBuf::from(next_value(arg, args.next())?)),
+            "--passphrase" => passphrase = Some(next_value(arg, args.next())?),
+            "--name" => name = Some(next_value(arg, args.next())?),
+            "--key" => key = Some(next_value(arg, args.next())?),
+            "--keyfile" => keyfile = Some(PathBuf::from(next_value(arg, args.next())?)),
+            "--out" => out = Some(PathBuf::from(next_value(arg, args.next())?)),
+            "--mnemonic" => mnemonic = Some(next_value(arg, args.next())?),
+            "--count" => count = parse_count(&next_value(arg, args.next())?)?,
+            "--prefix" => prefix = Some(next_value(arg, args.next())?),
+            "--threads" => threads = parse_count(&next_value(arg, args.next())?)? as usize,
+            flag if flag.starts_with("--") => return Err(format!("unknown flag: {}", flag)),
+            _ => target = Some(arg.clone()),
+        }
+    }
+
+    //the only command that doesn't touch the keystore
+    if command == "mnemonic" {
+        let phrase = generate_mnemonic();
+        let wallet = HdWallet::from_mnemonic(&phrase)?;
+        let mut lines = vec![phrase, String::new()];
+        for index in 0..count {
+            let address = wallet.derive_account(index)?.public_account.address;
+            lines.push(format!("{}: {}", index, address));
+        }
+        return Ok(lines.join("\n"));
+    }
+
+    //only touches the keystore if the found key should be saved there
+    if command == "vanity" {
+        let prefix = prefix.ok_or_else(|| "--prefix is required".to_string())?;
+        let result = find_vanity_account(&prefix, threads)?;
+        let address = result.account.public_account.address;
+        let found = format!("found {} after {} keys", address, result.attempts);
+        return match (datadir, name) {
+            (Some(datadir), Some(name)) => {
+                open_keystore(&datadir)?.add_named(&name, result.account, passphrase.as_deref())?;
+                Ok(format!("{}\nsaved as {}", found, name))
+            }
+            _ => Ok(format!(
+                "{}\nsecret key: {}",
+                found,
+                hex::encode(&result.account.secret_key[..])
+            )),
+        };
+    }
+
+    let datadir = datadir.ok_or_else(|| "--datadir is required".to_string())?;
+    let mut keystore = open_keystore(&datadir)?;
+
+    match command.as_str() {
+        "import" => {
+            let address = match (key, keyfile) {
+                (Some(key), None) => {
+                    let name = name.ok_or_else(|| "--name is required with --key".to_string())?;
+                    keystore.import_key(&name, &key, passphrase.as_deref())?
+                }
+                (None, Some(path)) => {
+                    let passphrase = passphrase
+                        .ok_or_else(|| "--passphrase is required with --keyfile".to_string())?;
+                    let key_file = fs::read_to_string(&path)
+                        .map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+                    keystore.import_key_file(name.as_deref(), &key_file, &passphrase)?
+                }
+                _ => return Err("pass exactly one of --key or --keyfile".into()),
+            };
+            Ok(format!("imported account {}", address))
+        }
+        "export" => {
+            let target =
+                target.ok_or_else(|| "pass the address or name of the account".to_string())?;
+            let address = resolve_account(&keystore, &target)?;
+            match out {
+                Some(path) => {
+                    fs::write(&path, keystore.export_key_file(&address)?)
+                        .map_err(|e| format!("failed to write {:?}: {}", path, e))?;
+                    Ok(format!("wrote key file for {} to {:?}", address, path))
+                }
+                None => {
+                    if keystore.is_locked(&address) {
+                        let passphrase = passphrase.ok_or_else(|| {
+                            "--passphrase is required to export the raw key".to_string()
+                        })?;
+                        keystore.unlock(&address, &passphrase)?;
+                    }
+                    keystore.export_key(&address)
+                }
+            }
+        }
+        //account 0 is the one a node started with the same --mnemonic mines with
+        "restore" => {
+            let phrase = mnemonic.ok_or_else(|| "--mnemonic is required".to_string())?;
+            let wallet = HdWallet::from_mnemonic(&phrase)?;
+            let mut lines = vec![];
+            for index in 0..count {
+                let account = wallet.derive_account(index)?;
+                let address = account.public_account.address;
+                if keystore.get(&address).is_some() || keystore.is_locked(&address) {
+                    lines.push(format!("{} already in the keystore", address));
+                    continue;
+                }
+                keystore.add_named(&format!("hd-{}", index), account, passphrase.as_deref())?;
+                lines.push(format!("restored hd-{}: {}", index, address));
+            }
+            Ok(lines.join("\n"))
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+/// opened without a passphrase - accounts can have passphrases of their own, so only the one we touch gets unlocked
+fn open_keystore(datadir: &Path) -> Result<Keystore, String> {
+    let mut keystore = Keystore::new();
+    keystore
+        .open(DataDir::new(datadir).keystore(), None)
+        .map_err(|e| format!("failed to open keystore: {}", e))?;
+    Ok(keystore)
+}
+
+fn parse_count(count: &str) -> Result<u32, String> {
+    count
+        .parse::<u32>()
+        .map_err(|_| format!("invalid count: {}", count))
+}
+
+/// accepts either the 0x-prefixed address or the name the account was created under
+fn resolve_account(keystore: &Keystore, address_or_name: &str) -> Result<Address, String> {
+    Address::from_str(address_or_name)
+        .ok()
+        .or_else(|| keystore.names.get(address_or_name).copied())
+        .ok_or_else(|| format!("no account {} in the keystore.", address_or_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use uuid::Uuid;
+
+    fn run(args: &[&str]) -> Result<String, String> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        run_account_command(&args, |_| None)
+    }
+
+    #[test]
+    fn test_import_then_export() {
+        let datadir = std::env::temp_dir().join(format!("node-{}", Uuid::new_v4()));
+        let datadir_str = datadir.to_str().unwrap();
+        let account = Account::new(vec![]);
+        let secret_hex = hex::encode(&account.secret_key[..]);
+
+        let res = run(&[
+            "import",
+            "--datadir",
+            datadir_str,
+            "--name",
+            "alice",
+            "--key",
+            &secret_hex,
+            "--passphrase",
+            "pass",
+        ])
+        .unwrap();
+        assert!(res.contains(&account.public_account.address.to_string()));
+
+        //survives a "restart", ie a fresh keystore reading the same dir
+        let exported = run(&[
+            "export",
+            "--datadir",
+            datadir_str,
+            "alice",
+            "--passphrase",
+            "pass",
+        ])
+        .unwrap();
+        assert_eq!(exported, secret_hex);
+
+        assert!(run(&[
+            "export",
+            "--datadir",
+            datadir_str,
+            "alice",
+            "--passphrase",
+            "nope"
+        ])
+        .is_err());
+        assert!(run(&["export", "--datadir", datadir_str, "bob"]).is_err());
+
+        fs::remove_dir_all(datadir).unwrap();
+    }
+
+    #[test]
+    fn test_mnemonic_restore() {
+        let datadir = std::env::temp_dir().join(format!("node-{}", Uuid::new_v4()));
+        let datadir_str = datadir.to_str().unwrap();
+
+        let generated = run(&["mnemonic", "--count", "2"]).unwrap();
+        let phrase = generated.lines().next().unwrap();
+        let first_address = generated.lines().nth(2).unwrap().trim_start_matches("0: ");
+
+        let restored = run(&[
+            "restore",
+            "--datadir",
+            datadir_str,
+            "--mnemonic",
+            phrase,
+            "--count",
+            "2",
+            "--passphrase",
+            "pass",
+        ])
+        .unwrap();
+        assert_eq!(restored.lines().count(), 2);
+        assert!(restored.contains(&format!("restored hd-0: {}", first_address)));
+
+        //restoring again is a no-op
+        let again = run(&[
+            "restore",
+            "--datadir",
+            datadir_str,
+            "--mnemonic",
+            phrase,
+            "--passphrase",
+            "pass",
+        ])
+        .unwrap();
+        assert!(again.contains("already in the keystore"));
+
+        fs::remove_dir_all(datadir).unwrap();
+    }
+
+    #[test]
+    fn test_vanity() {
+        let res = run(&["vanity", "--prefix", "a", "--threads", "1"]).unwrap();
+        let address = res.lines().next().unwrap().split(' ').nth(1).unwrap();
+        assert!(address[2..].starts_with('a'));
+        assert!(res.contains("secret key: "));
+        assert!(run(&["vanity"]).is_err());
+        assert!(run(&["vanity", "--prefix", "xyz"]).is_err());
+    }
+
+    #[test]
+    fn test_bad_input() {
+        assert!(run(&[]).is_err());
+        assert!(run(&["delete"]).is_err());
+        assert!(run(&["import", "--name", "alice"]).is_err()); //no datadir
+        assert!(run(&["import", "--what"]).is_err());
+        assert!(run(&["mnemonic", "--count", "many"]).is_err());
+    }
+}
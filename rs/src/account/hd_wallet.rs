@@ -0,0 +1,186 @@
+use crate::account::Account;
+use bip39::Mnemonic;
+use secp256k1::bitcoin_hashes::{hmac, sha512, Hash, HashEngine};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+/// bip-44 path for ethereum accounts, the account index gets appended as the last level.
+/// Same as metamask/ganache/hardhat, so a phrase from one of them restores the same keys here
+pub const ETH_DERIVATION_PATH: &str = "m/44'/60'/0'/0";
+pub const HARDENED_OFFSET: u32 = 1 << 31;
+
+/// 12 words (128 bits of entropy)
+pub fn generate_mnemonic() -> String {
+    let entropy = rand::random::<[u8; 16]>();
+    Mnemonic::from_entropy(&entropy).unwrap().to_string()
+}
+
+/// a private key + chain code, ie a node in the bip-32 tree
+#[derive(Clone)]
+struct ExtendedKey {
+    secret_key: SecretKey,
+    chain_code: [u8; 32],
+}
+
+impl ExtendedKey {
+    fn from_hmac(key: &[u8], data: &[u8]) -> Result<Self, String> {
+        let mut engine = hmac::HmacEngine::<sha512::Hash>::new(key);
+        engine.input(data);
+        let i = hmac::Hmac::<sha512::Hash>::from_engine(engine).into_inner();
+
+        let secret_key =
+            SecretKey::from_slice(&i[..32]).map_err(|_| "derived an invalid key".to_string())?;
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&i[32..]);
+        Ok(Self {
+            secret_key,
+            chain_code,
+        })
+    }
+
+    /// CKDpriv from bip-32
+    fn derive_child(&self, index: u32) -> Result<Self, String> {
+        let mut data = Vec::with_capacity(37);
+        if index >= HARDENED_OFFSET {
+            data.push(0);
+            data.extend_from_slice(&self.secret_key[..]);
+        } else {
+            let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &self.secret_key);
+            data.extend_from_slice(&public_key.serialize());
+        }
+        data.extend_from_slice(&index.to_be_bytes());
+
+        //child key = IL + parent key (mod n)
+        let mut child = ExtendedKey::from_hmac(&self.chain_code, &data)?;
+        child
+            .secret_key
+            .add_assign(&self.secret_key[..])
+            .map_err(|_| "derived an invalid key".to_string())?;
+        Ok(child)
+    }
+}
+
+/// deterministic keys from a single seed - the same phrase always gives back the same accounts
+pub struct HdWallet {
+    master: ExtendedKey,
+}
+
+impl HdWallet {
+    pub fn from_mnemonic(phrase: &str) -> Result<Self, String> {
+        let mnemonic = Mnemonic::parse(phrase).map_err(|e| format!("invalid mnemonic: {}", e))?;
+        //no bip-39 passphrase, same as the dev tools we want to stay compatible with
+        HdWallet::from_seed(&mnemonic.to_seed(""))
+    }
+
+    pub fn from_seed(seed: &[u8]) -> Result<Self, String> {
+        Ok(Self {
+            master: ExtendedKey::from_hmac(b"Bitcoin seed", seed)?,
+        })
+    }
+
+    /// eg "m/44'/60'/0'/0/1". A trailing ' (or h) means hardened
+    pub fn derive_key(&self, path: &str) -> Result<SecretKey, String> {
+        let mut levels = path.split('/');
+        if levels.next() != Some("m") {
+            return Err(format!("derivation path must start with m: {}", path));
+        }
+        let mut key = self.master.clone();
+        for level in levels {
+            key = key.derive_child(parse_level(level)?)?;
+        }
+        Ok(key.secret_key)
+    }
+
+    /// the index-th account under the standard ethereum path
+    pub fn derive_account(&self, index: u32) -> Result<Account, String> {
+        let path = format!("{}/{}", ETH_DERIVATION_PATH, index);
+        Ok(Account::from_secret_key(self.derive_key(&path)?))
+    }
+}
+
+fn parse_level(level: &str) -> Result<u32, String> {
+    let (number, hardened) = match level.strip_suffix('\'').or_else(|| level.strip_suffix('h')) {
+        Some(number) => (number, true),
+        None => (level, false),
+    };
+    let index = number
+        .parse::<u32>()
+        .ok()
+        .filter(|index| *index < HARDENED_OFFSET)
+        .ok_or_else(|| format!("invalid derivation path level: {}", level))?;
+    Ok(if hardened {
+        index + HARDENED_OFFSET
+    } else {
+        index
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::address::to_checksum_address;
+
+    const TEST_MNEMONIC: &str =
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    /// test vector 1 from https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki
+    #[test]
+    fn test_bip32_vector() {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let wallet = HdWallet::from_seed(&seed).unwrap();
+        assert_eq!(
+            hex::encode(&wallet.derive_key("m").unwrap()[..]),
+            "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35"
+        );
+        assert_eq!(
+            hex::encode(&wallet.derive_key("m/0'").unwrap()[..]),
+            "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea"
+        );
+        assert_eq!(
+            hex::encode(&wallet.derive_key("m/0'/1").unwrap()[..]),
+            "3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368"
+        );
+    }
+
+    /// the first account metamask derives from the "abandon ... about" phrase
+    #[test]
+    fn test_eth_path() {
+        let wallet = HdWallet::from_mnemonic(TEST_MNEMONIC).unwrap();
+        assert_eq!(
+            hex::encode(&wallet.derive_key("m/44'/60'/0'/0/0").unwrap()[..]),
+            "1ab42cc412b618bdea3a599e3c9bae199ebf030895b039e9db1e30dafb12b727"
+        );
+        assert_eq!(
+            to_checksum_address(&wallet.derive_account(0).unwrap().public_account.address),
+            "0x9858EfFD232B4033E47d90003D41EC34EcaEda94"
+        );
+        assert_eq!(
+            wallet.derive_account(0).unwrap().public_account.address,
+            HdWallet::from_mnemonic(TEST_MNEMONIC)
+                .unwrap()
+                .derive_account(0)
+                .unwrap()
+                .public_account
+                .address
+        );
+        assert_ne!(
+            wallet.derive_account(0).unwrap().public_account.address,
+            wallet.derive_account(1).unwrap().public_account.address
+        );
+    }
+
+    #[test]
+    fn test_generated_mnemonic_restores() {
+        let phrase = generate_mnemonic();
+        assert_eq!(phrase.split(' ').count(), 12);
+        assert!(HdWallet::from_mnemonic(&phrase).is_ok());
+    }
+
+    #[test]
+    fn test_bad_input() {
+        assert!(HdWallet::from_mnemonic("not a real phrase").is_err());
+        let wallet = HdWallet::from_mnemonic(TEST_MNEMONIC).unwrap();
+        assert!(wallet.derive_key("44'/60'").is_err());
+        assert!(wallet.derive_key("m/abc").is_err());
+        assert!(wallet.derive_key("m/2147483648").is_err());
+    }
+}
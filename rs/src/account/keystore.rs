@@ -0,0 +1,397 @@
+use crate::account::address::Address;
+use crate::account::secret_storage::{CryptoSection, EncryptedKey, VERSION};
+use crate::account::Account;
+use secp256k1::SecretKey;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// what can be found in the keystore dir
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum KeyFile {
+    Encrypted(EncryptedKey),
+    //written by older versions of the node, with the secret key in the clear. Re-encrypted as soon as we have a passphrase
+    Plaintext { name: String, account: Account },
+}
+
+/// accounts whose secret keys are held by this node (the miner + any created through the api).
+/// Deliberately not serializable - the only way keys leave it is encrypted, as key files
+#[derive(Debug, Clone, Default)]
+pub struct Keystore {
+    pub accounts: HashMap<Address, Account>,
+    pub names: HashMap<String, Address>,
+    //key files found on disk that haven't been unlocked with their passphrase yet
+    pub locked: HashMap<Address, EncryptedKey>,
+    //where named accounts get persisted. None = in memory only
+    pub dir: Option<PathBuf>,
+    //encrypts new key files when the caller doesn't bring a passphrase of its own
+    passphrase: Option<String>,
+}
+
+impl Keystore {
+    pub fn new() -> Self {
+        Self {
+            accounts: HashMap::new(),
+            names: HashMap::new(),
+            locked: HashMap::new(),
+            dir: None,
+            passphrase: None,
+        }
+    }
+    /// loads any previously persisted accounts from dir and persists new named accounts there going forward.
+    /// With a passphrase every key file gets unlocked straight away, without one they stay locked until unlock() is called
+    pub fn open(&mut self, dir: PathBuf, passphrase: Option<String>) -> io::Result<()> {
+        fs::create_dir_all(&dir)?;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match serde_json::from_str(&fs::read_to_string(&path)?)? {
+                KeyFile::Encrypted(key) => {
+                    let address = Address::from_str(&key.address)
+                        .map_err(|_| invalid_data(format!("invalid address in {:?}", path)))?;
+                    if let Some(name) = &key.name {
+                        self.names.insert(name.clone(), address);
+                    }
+                    match &passphrase {
+                        Some(passphrase) => {
+                            let account = decrypt_key(&key, passphrase).map_err(|e| {
+                                invalid_data(format!("failed to unlock {:?}: {}", path, e))
+                            })?;
+                            self.add(account);
+                        }
+                        None => {
+                            self.locked.insert(address, key);
+                        }
+                    }
+                }
+                KeyFile::Plaintext { name, account } => {
+                    match &passphrase {
+                        Some(passphrase) => {
+                            write_key_file(&dir, Some(&name), &account, passphrase)?;
+                            tracing::info!(path = ?path, "encrypted plaintext key file");
+                        }
+                        None => tracing::warn!(
+                            path = ?path,
+                            "plaintext key file - start the node with a keystore password to encrypt it"
+                        ),
+                    }
+                    self.names.insert(name, account.public_account.address);
+                    self.add(account);
+                }
+            }
+        }
+        self.dir = Some(dir);
+        self.passphrase = passphrase;
+        Ok(())
+    }
+    pub fn add(&mut self, account: Account) {
+        self.accounts
+            .insert(account.public_account.address, account);
+    }
+    /// the passphrase is only needed if the keystore persists to disk. Falls back to the one the keystore was opened with
+    pub fn add_named(
+        &mut self,
+        name: &str,
+        account: Account,
+        passphrase: Option<&str>,
+    ) -> Result<(), String> {
+        if self.names.contains_key(name) {
+            return Err(format!("account named {} already exists.", name));
+        }
+        if let Some(dir) = &self.dir {
+            let passphrase = passphrase
+                .or(self.passphrase.as_deref())
+                .ok_or_else(|| "a passphrase is needed to encrypt the key file.".to_string())?;
+            write_key_file(dir, Some(name), &account, passphrase)
+                .map_err(|e| format!("failed to persist account: {}", e))?;
+        }
+        self.names
+            .insert(name.to_owned(), account.public_account.address);
+        self.add(account);
+        Ok(())
+    }
+    /// eg a key exported from another node. Hex, with or without 0x
+    pub fn import_key(
+        &mut self,
+        name: &str,
+        secret_hex: &str,
+        passphrase: Option<&str>,
+    ) -> Result<Address, String> {
+        let secret = hex::decode(secret_hex.trim_start_matches("0x"))
+            .map_err(|_| "secret key isn't valid hex.".to_string())?;
+        let secret_key =
+            SecretKey::from_slice(&secret).map_err(|_| "invalid secret key.".to_string())?;
+        self.import_account(name, Account::from_secret_key(secret_key), passphrase)
+    }
+    /// the file's own name is used unless one is given. The file's passphrase is kept for the re-encrypted copy
+    pub fn import_key_file(
+        &mut self,
+        name: Option<&str>,
+        key_file: &str,
+        passphrase: &str,
+    ) -> Result<Address, String> {
+        let key: EncryptedKey =
+            serde_json::from_str(key_file).map_err(|e| format!("invalid key file: {}", e))?;
+        let name = name
+            .map(String::from)
+            .or_else(|| key.name.clone())
+            .ok_or_else(|| "the key file has no name, please provide one.".to_string())?;
+        let account = decrypt_key(&key, passphrase)?;
+        self.import_account(&name, account, Some(passphrase))
+    }
+    fn import_account(
+        &mut self,
+        name: &str,
+        account: Account,
+        passphrase: Option<&str>,
+    ) -> Result<Address, String> {
+        let address = account.public_account.address;
+        if self.accounts.contains_key(&address) || self.is_locked(&address) {
+            return Err(format!("account {} is already in the keystore.", address));
+        }
+        self.add_named(name, account, passphrase)?;
+        Ok(address)
+    }
+    /// hex encoded secret key of an unlocked account
+    pub fn export_key(&self, address: &Address) -> Result<String, String> {
+        self.get(address)
+            .map(|account| hex::encode(&account.secret_key[..]))
+            .ok_or_else(|| format!("no unlocked account {}.", address))
+    }
+    /// the encrypted key file as it sits on disk, so it can be carried over to another node
+    pub fn export_key_file(&self, address: &Address) -> Result<String, String> {
+        let dir = self
+            .dir
+            .as_ref()
+            .ok_or_else(|| "keystore isn't persisted to disk.".to_string())?;
+        fs::read_to_string(key_file_path(dir, address))
+            .map_err(|_| format!("no key file for {}.", address))
+    }
+    /// decrypts a key file that was left locked at startup
+    pub fn unlock(&mut self, address: &Address, passphrase: &str) -> Result<(), String> {
+        let key = self
+            .locked
+            .get(address)
+            .ok_or_else(|| format!("account {} is not locked.", address))?;
+        let account = decrypt_key(key, passphrase)?;
+        self.locked.remove(address);
+        self.add(account);
+        Ok(())
+    }
+    pub fn is_locked(&self, address: &Address) -> bool {
+        self.locked.contains_key(address)
+    }
+    /// None for locked accounts - their secret key isn't available until they're unlocked
+    pub fn get(&self, address: &Address) -> Option<&Account> {
+        self.accounts.get(address)
+    }
+    pub fn get_by_name(&self, name: &str) -> Option<&Account> {
+        self.names.get(name).and_then(|address| self.get(address))
+    }
+    pub fn name_of(&self, address: &Address) -> Option<&String> {
+        self.names
+            .iter()
+            .find(|(_name, a)| *a == address)
+            .map(|(name, _a)| name)
+    }
+    /// locked accounts included. Sorted so that listings come out in a stable order
+    pub fn addresses(&self) -> Vec<Address> {
+        let mut addresses: Vec<Address> = self
+            .accounts
+            .keys()
+            .chain(self.locked.keys())
+            .copied()
+            .collect();
+        addresses.sort_by_key(|a| a.to_string());
+        addresses
+    }
+}
+
+fn write_key_file(
+    dir: &Path,
+    name: Option<&str>,
+    account: &Account,
+    passphrase: &str,
+) -> io::Result<()> {
+    let key = EncryptedKey {
+        version: VERSION,
+        id: Uuid::new_v4(),
+        address: account.public_account.address.to_string(),
+        name: name.map(String::from),
+        crypto: CryptoSection::encrypt(&account.secret_key[..], passphrase),
+    };
+    fs::write(
+        key_file_path(dir, &account.public_account.address),
+        serde_json::to_string_pretty(&key).unwrap(),
+    )
+}
+
+fn key_file_path(dir: &Path, address: &Address) -> PathBuf {
+    dir.join(format!("{}.json", address))
+}
+
+fn decrypt_key(key: &EncryptedKey, passphrase: &str) -> Result<Account, String> {
+    let secret = key.crypto.decrypt(passphrase)?;
+    let secret_key =
+        SecretKey::from_slice(&secret).map_err(|_| "invalid secret key".to_string())?;
+    let account = Account::from_secret_key(secret_key);
+    if account.public_account.address.to_string() != key.address {
+        return Err("key file address doesn't match its key".into());
+    }
+    Ok(account)
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_and_get() {
+        let mut keystore = Keystore::new();
+        let account = Account::new(vec![]);
+        let address = account.public_account.address;
+        keystore.add(account);
+
+        assert!(keystore.get(&address).is_some());
+        assert_eq!(keystore.addresses(), vec![address]);
+    }
+
+    #[test]
+    fn test_named_accounts_are_unique() {
+        let mut keystore = Keystore::new();
+        keystore
+            .add_named("alice", Account::new(vec![]), None)
+            .unwrap();
+        assert!(keystore
+            .add_named("alice", Account::new(vec![]), None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_named_accounts_persist() {
+        let dir = std::env::temp_dir().join(format!("keystore-{}", Uuid::new_v4()));
+
+        let mut keystore = Keystore::new();
+        keystore
+            .open(dir.clone(), Some("node-pass".into()))
+            .unwrap();
+        let account = Account::new(vec![]);
+        let address = account.public_account.address;
+        keystore.add_named("alice", account, None).unwrap();
+
+        //the secret key never hits the disk in the clear
+        let key_file = fs::read_to_string(dir.join(format!("{}.json", address))).unwrap();
+        let secret_hex = hex::encode(&keystore.get(&address).unwrap().secret_key[..]);
+        assert!(!key_file.contains(&secret_hex));
+
+        //a fresh keystore pointed at the same dir should pick the account back up
+        let mut reopened = Keystore::new();
+        reopened
+            .open(dir.clone(), Some("node-pass".into()))
+            .unwrap();
+        assert_eq!(
+            reopened
+                .get_by_name("alice")
+                .unwrap()
+                .public_account
+                .address,
+            address
+        );
+        assert_eq!(reopened.name_of(&address), Some(&"alice".to_owned()));
+
+        //and refuse to start with the wrong passphrase
+        assert!(Keystore::new()
+            .open(dir.clone(), Some("wrong".into()))
+            .is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_unlock_per_account() {
+        let dir = std::env::temp_dir().join(format!("keystore-{}", Uuid::new_v4()));
+
+        let mut keystore = Keystore::new();
+        keystore.open(dir.clone(), None).unwrap();
+        let account = Account::new(vec![]);
+        let address = account.public_account.address;
+        //no node-wide passphrase, so the caller has to bring one
+        assert!(keystore.add_named("bob", account.clone(), None).is_err());
+        keystore
+            .add_named("bob", account, Some("bob-pass"))
+            .unwrap();
+
+        //opened without a passphrase - the account is known but its key isn't usable yet
+        let mut reopened = Keystore::new();
+        reopened.open(dir.clone(), None).unwrap();
+        assert!(reopened.is_locked(&address));
+        assert!(reopened.get(&address).is_none());
+        assert_eq!(reopened.addresses(), vec![address]);
+
+        assert!(reopened.unlock(&address, "wrong").is_err());
+        reopened.unlock(&address, "bob-pass").unwrap();
+        assert!(!reopened.is_locked(&address));
+        assert_eq!(
+            reopened.get_by_name("bob").unwrap().public_account.address,
+            address
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_export_key() {
+        let mut keystore = Keystore::new();
+        let account = Account::new(vec![]);
+        let address = account.public_account.address;
+        let secret_hex = hex::encode(&account.secret_key[..]);
+
+        let imported = keystore
+            .import_key("alice", &format!("0x{}", secret_hex), None)
+            .unwrap();
+        assert_eq!(imported, address);
+        assert_eq!(keystore.export_key(&address).unwrap(), secret_hex);
+
+        //same key twice, or junk
+        assert!(keystore.import_key("bob", &secret_hex, None).is_err());
+        assert!(keystore.import_key("carol", "nothex", None).is_err());
+    }
+
+    #[test]
+    fn test_import_export_key_file() {
+        let dir_a = std::env::temp_dir().join(format!("keystore-{}", Uuid::new_v4()));
+        let dir_b = std::env::temp_dir().join(format!("keystore-{}", Uuid::new_v4()));
+
+        let mut node_a = Keystore::new();
+        node_a.open(dir_a.clone(), None).unwrap();
+        let account = Account::new(vec![]);
+        let address = account.public_account.address;
+        node_a.add_named("alice", account, Some("pass")).unwrap();
+        let key_file = node_a.export_key_file(&address).unwrap();
+
+        let mut node_b = Keystore::new();
+        node_b.open(dir_b.clone(), None).unwrap();
+        assert!(node_b.import_key_file(None, &key_file, "wrong").is_err());
+        node_b.import_key_file(None, &key_file, "pass").unwrap();
+        assert_eq!(
+            node_b.get_by_name("alice").unwrap().public_account.address,
+            address
+        );
+        //and it's persisted on node b's side too
+        assert!(node_b.export_key_file(&address).is_ok());
+
+        fs::remove_dir_all(dir_a).unwrap();
+        fs::remove_dir_all(dir_b).unwrap();
+    }
+}
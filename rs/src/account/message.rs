@@ -0,0 +1,86 @@
+use crate::account::address::Address;
+use crate::account::Account;
+use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use secp256k1::{Message, Secp256k1};
+use sha3::{Digest, Keccak256};
+
+/// personal_sign / eth_sign style - prefixing the message means a signed message can never double as a signed tx
+pub fn hash_message(message: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+    hasher.update(message);
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&hasher.finalize());
+    hash
+}
+
+/// 0x prefixed r || s || v, with v = 27 + recovery id like everyone else does it
+pub fn sign_message(account: &Account, message: &[u8]) -> String {
+    let msg = Message::from_slice(&hash_message(message)).unwrap();
+    let (recovery_id, rs) = Secp256k1::new()
+        .sign_recoverable(&msg, &account.secret_key)
+        .serialize_compact();
+    let mut signature = rs.to_vec();
+    signature.push(27 + recovery_id.to_i32() as u8);
+    format!("0x{}", hex::encode(signature))
+}
+
+/// recovers who signed the message - the caller compares that against whoever claims to have signed it
+pub fn recover_signer(message: &[u8], signature: &str) -> Result<Address, String> {
+    let signature = hex::decode(signature.trim_start_matches("0x"))
+        .ok()
+        .filter(|s| s.len() == 65)
+        .ok_or_else(|| "signature must be 65 bytes of hex.".to_string())?;
+    //some signers use 0/1 instead of 27/28
+    let v = match signature[64] {
+        v @ 27..=28 => v - 27,
+        v => v,
+    };
+    let recovery_id =
+        RecoveryId::from_i32(v as i32).map_err(|_| "invalid signature recovery id.".to_string())?;
+    let signature = RecoverableSignature::from_compact(&signature[..64], recovery_id)
+        .map_err(|_| "invalid signature.".to_string())?;
+    let msg = Message::from_slice(&hash_message(message)).unwrap();
+    Secp256k1::new()
+        .recover(&msg, &signature)
+        .map(|public_key| Address::from_public_key(&public_key))
+        .map_err(|_| "signature doesn't recover to a valid key.".to_string())
+}
+
+pub fn verify_message(message: &[u8], signature: &str, address: &Address) -> bool {
+    recover_signer(message, signature)
+        .map(|signer| signer == *address)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_message() {
+        //same as ethers' hashMessage("Hello World")
+        assert_eq!(
+            hex::encode(hash_message(b"Hello World")),
+            "a1de988600a42c4b4ab089b619297c17d53cffae5d5120d82d8a92d0bb3b78f2"
+        );
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let account = Account::new(vec![]);
+        let address = account.public_account.address;
+        let signature = sign_message(&account, b"log me in");
+
+        assert_eq!(signature.len(), 2 + 65 * 2);
+        assert_eq!(recover_signer(b"log me in", &signature).unwrap(), address);
+        assert!(verify_message(b"log me in", &signature, &address));
+        assert!(!verify_message(b"log me out", &signature, &address));
+        assert!(!verify_message(
+            b"log me in",
+            &signature,
+            &Account::new(vec![]).public_account.address
+        ));
+        assert!(recover_signer(b"log me in", "0x1234").is_err());
+    }
+}
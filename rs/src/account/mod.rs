@@ -1,26 +1,56 @@
+pub mod address;
+pub mod address_book;
+pub mod commands;
+pub mod hd_wallet;
+pub mod keystore;
+pub mod message;
+pub mod multisig;
+pub mod secret_storage;
+pub mod vanity;
+
+use crate::account::address::Address;
+use crate::account::multisig::MultisigConfig;
+use crate::error::CodecError;
 use crate::interpreter::OPCODE;
+use crate::store::codec::{Decode, Encode, Fields, Record};
+use crate::store::rlp::Rlp;
 use crate::store::state::State;
-use crate::util::keccak_hash;
+use crate::util::bigint::U256;
+use crate::util::{is_zero, keccak_hash};
 
+use lazy_static::lazy_static;
 use secp256k1::bitcoin_hashes::sha256;
 use secp256k1::rand::rngs::OsRng;
+use secp256k1::recovery::{RecoverableSignature, RecoveryId};
 use secp256k1::{Message, PublicKey, Secp256k1, SecretKey, Signature};
 use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::sync::Mutex;
+
+lazy_static! {
+    //set = deterministic test mode, see enable_deterministic_keys()
+    static ref DETERMINISTIC_KEYS: Mutex<Option<DeterministicKeys>> = Mutex::new(None);
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PublicAccount {
-    //NOTE: in real ethereum addresses are hashes of public keys (truncated to 20 least significant bytes).
-    // In this implementation we didn't bother and we simply used the public key itself
-    // because the lib we're using - secp256k1 produces compressed keys (starting with 02 and 03)
-    // hence, all the public keys in this implementation are 66 hex chars (33 bytes) long
-    // to learn more how ethereum actually does it, read this - https://www.oreilly.com/library/view/mastering-ethereum/9781491971932/ch04.html
-    pub address: PublicKey,
-    pub balance: u64,
+    //same as in real ethereum - the last 20 bytes of the hash of the public key, see Address.
+    // to learn more, read this - https://www.oreilly.com/library/view/mastering-ethereum/9781491971932/ch04.html
+    pub address: Address,
+    pub balance: U256,
     pub code: Vec<OPCODE>,
     pub code_hash: Option<String>,
+    //one past the nonce of the last transfer the account sent, see Transaction::check_nonce(). Left out of the json
+    // while it's 0, same as multisig
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub nonce: u64,
+    //only set for multisig accounts. Left out of the json otherwise, so existing state hashes don't change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multisig: Option<MultisigConfig>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+//no Serialize, so the secret key can't end up in some json by accident. Deserialize is only for legacy plaintext key files
+#[derive(Debug, Deserialize, Clone)]
 pub struct Account {
     secret_key: SecretKey,
     pub public_account: PublicAccount,
@@ -28,26 +58,52 @@ pub struct Account {
 
 impl Account {
     //note code can be empty: vec![]
+    //new accounts start out empty - value only comes from genesis allocations, mining rewards and transfers
     pub fn new(code: Vec<OPCODE>) -> Self {
         let (secret_key, public_key) = gen_keypair();
-        //probably shouldn't be printing this to terminal in a real blockchain:)
-        println!(
-            "Created new account with sk, pk: {}, {}",
-            secret_key, public_key
-        );
-        let code_hash = Account::gen_code_hash(&public_key, &code);
+        let address = Address::from_public_key(&public_key);
+        //only the address gets logged - the secret key stays in the keystore
+        tracing::info!(address = %address, "created new account");
+        let code_hash = Account::gen_code_hash(&address, &code);
         Self {
             secret_key,
             public_account: PublicAccount {
-                address: public_key,
-                balance: 1000,
+                address,
+                balance: U256::zero(),
                 code,
                 code_hash,
+                nonce: 0,
+                multisig: None,
+            },
+        }
+    }
+    /// rebuilds an account from a key we already hold, eg one decrypted from a keystore file
+    pub fn from_secret_key(secret_key: SecretKey) -> Self {
+        let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+        Self {
+            secret_key,
+            public_account: PublicAccount {
+                address: Address::from_public_key(&public_key),
+                balance: U256::zero(),
+                code: vec![],
+                code_hash: None,
+                nonce: 0,
+                multisig: None,
             },
         }
     }
-    pub fn gen_code_hash(address: &PublicKey, code: &Vec<OPCODE>) -> Option<String> {
-        if code.len() > 0 {
+    /// the account as it signs its next transfer - create_transaction() takes the tx nonce from here. The node's own
+    /// copy of an account never leaves 0, so get it from GlobalState::next_nonce()
+    pub fn with_nonce(mut self, nonce: u64) -> Self {
+        self.public_account.nonce = nonce;
+        self
+    }
+    /// the key the address is derived from
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey::from_secret_key(&Secp256k1::new(), &self.secret_key)
+    }
+    pub fn gen_code_hash(address: &Address, code: &Vec<OPCODE>) -> Option<String> {
+        if !code.is_empty() {
             //including the address means that 2 SCs with same code but diff addresses will get diff hashes
             Some(keccak_hash(&format!("{}{:?}", address, code)))
         } else {
@@ -60,18 +116,30 @@ impl Account {
         let msg = Message::from_hashed_data::<sha256::Hash>(data.as_bytes());
         secp.sign(&msg, &self.secret_key)
     }
-    pub fn verify_signature(data: &String, sig: &Signature, public_key: &PublicKey) -> bool {
+    /// whether the key behind the address made the signature. A signature doesn't say which key made it, but it
+    /// can only have come from one of a few - each of those gets recovered and checked against the address
+    pub fn verify_signature(data: &String, sig: &Signature, address: &Address) -> bool {
         let msg = Message::from_hashed_data::<sha256::Hash>(data.as_bytes());
         let secp = Secp256k1::new();
-        secp.verify(&msg, sig, public_key).is_ok()
+        let compact = sig.serialize_compact();
+        (0..4).any(|id| {
+            RecoveryId::from_i32(id)
+                .and_then(|id| RecoverableSignature::from_compact(&compact, id))
+                .and_then(|recoverable| secp.recover(&msg, &recoverable))
+                .is_ok_and(|public_key| Address::from_public_key(&public_key) == *address)
+        })
     }
-    pub fn get_balance(address: PublicKey, state: &mut State) -> u64 {
-        let account = state.get_account(address);
-        account.balance
+    /// zero for an address the state has never seen
+    pub fn get_balance(address: Address, state: &State) -> U256 {
+        state.get_account_or_empty(address).balance
     }
 }
 
+/// random keys, unless deterministic test mode is on
 pub fn gen_keypair() -> (SecretKey, PublicKey) {
+    if let Some(keys) = DETERMINISTIC_KEYS.lock().unwrap().as_mut() {
+        return keys.next_keypair();
+    }
     let secp = Secp256k1::new();
     let mut rng = OsRng::new().unwrap();
     let (secret_key, public_key) = secp.generate_keypair(&mut rng);
@@ -79,6 +147,83 @@ pub fn gen_keypair() -> (SecretKey, PublicKey) {
     (secret_key, public_key)
 }
 
+/// an address nobody's going to have the key to, eg to send to in tests
+pub fn gen_address() -> Address {
+    Address::from_public_key(&gen_keypair().1)
+}
+
+/// same seed, same keypair. The secret key is the keccak hash of the seed (rehashed in the astronomically
+/// unlikely case that isn't a valid key). NOT for real funds - anyone who knows the seed has the key
+pub fn gen_keypair_from_seed(seed: &[u8]) -> (SecretKey, PublicKey) {
+    let mut hash = Keccak256::digest(seed);
+    loop {
+        if let Ok(secret_key) = SecretKey::from_slice(&hash) {
+            let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+            return (secret_key, public_key);
+        }
+        hash = Keccak256::digest(&hash);
+    }
+}
+
+/// the n-th key gen_keypair() hands out in deterministic mode comes from "<seed>/<n>"
+pub struct DeterministicKeys {
+    seed: String,
+    counter: u64,
+}
+
+impl DeterministicKeys {
+    pub fn new(seed: &str) -> Self {
+        Self {
+            seed: seed.to_owned(),
+            counter: 0,
+        }
+    }
+    pub fn next_keypair(&mut self) -> (SecretKey, PublicKey) {
+        let keypair = gen_keypair_from_seed(format!("{}/{}", self.seed, self.counter).as_bytes());
+        self.counter += 1;
+        keypair
+    }
+}
+
+/// deterministic test mode - from now on every gen_keypair() (and so every Account::new()) in this process
+/// returns the next key derived from the seed, so a run always produces the same addresses.
+/// Only as stable as the order keys get generated in, eg a multi-threaded run can interleave differently
+pub fn enable_deterministic_keys(seed: &str) {
+    tracing::warn!("deterministic keys enabled - generated keys are NOT secret");
+    *DETERMINISTIC_KEYS.lock().unwrap() = Some(DeterministicKeys::new(seed));
+}
+
+pub fn disable_deterministic_keys() {
+    *DETERMINISTIC_KEYS.lock().unwrap() = None;
+}
+
+impl Encode for PublicAccount {
+    fn to_rlp(&self) -> Rlp {
+        Record::new()
+            .field(1, &self.address)
+            .field(2, &self.balance)
+            .field(3, &self.code)
+            .optional(4, &self.code_hash)
+            .field(5, &self.nonce)
+            .optional(6, &self.multisig)
+            .build()
+    }
+}
+
+impl Decode for PublicAccount {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        let fields = Fields::of(item)?;
+        Ok(Self {
+            address: fields.get(1)?,
+            balance: fields.get(2)?,
+            code: fields.get(3)?,
+            code_hash: fields.optional(4)?,
+            nonce: fields.get(5)?,
+            multisig: fields.optional(6)?,
+        })
+    }
+}
+
 // NOTE USED. Wanted to hash contract data to create an account address, but this creates problems
 // pub fn code_hash_to_public_key(code_hash: &String) -> PublicKey {
 //     let secp = Secp256k1::new();
@@ -96,6 +241,38 @@ mod tests {
         let a = Account::new(vec![]);
         let s = a.sign(&"hello world".to_owned());
         let v = Account::verify_signature(&"hello world".to_owned(), &s, &a.public_account.address);
-        assert!(v)
+        assert!(v);
+        assert!(!Account::verify_signature(
+            &"hello world".to_owned(),
+            &s,
+            &gen_address()
+        ));
+        assert!(!Account::verify_signature(
+            &"hello world!".to_owned(),
+            &s,
+            &a.public_account.address
+        ));
+    }
+
+    #[test]
+    fn test_keypair_from_seed() {
+        let (sk, pk) = gen_keypair_from_seed(b"alice");
+        assert_eq!(
+            hex::encode(&sk[..]),
+            hex::encode(Keccak256::digest(b"alice"))
+        );
+        assert_eq!(gen_keypair_from_seed(b"alice").1, pk);
+        assert_ne!(gen_keypair_from_seed(b"bob").1, pk);
+    }
+
+    #[test]
+    fn test_deterministic_keys_repeat() {
+        let mut a = DeterministicKeys::new("lesson-1");
+        let mut b = DeterministicKeys::new("lesson-1");
+        let first = a.next_keypair().1;
+        assert_eq!(first, b.next_keypair().1);
+        assert_eq!(first, gen_keypair_from_seed(b"lesson-1/0").1);
+        //but every key in the sequence is different
+        assert_ne!(a.next_keypair().1, first);
     }
 }
@@ -0,0 +1,163 @@
+use crate::account::address::Address;
+use crate::account::Account;
+use crate::error::CodecError;
+use crate::store::codec::{Decode, Encode, Fields, Record};
+use crate::store::rlp::Rlp;
+use secp256k1::Signature;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use utoipa::ToSchema;
+
+//keeps validating a tx cheap - every signature gets checked on every node
+pub const MAX_SIGNERS: usize = 16;
+
+/// M-of-N: outgoing tx need signatures from at least `threshold` of `signers`.
+/// Lives in the account's state entry, so every node enforces the same rule
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct MultisigConfig {
+    pub threshold: usize,
+    #[schema(value_type = Vec<String>)]
+    pub signers: Vec<Address>,
+}
+
+/// one signer's signature over a multisig account's outgoing tx
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Cosignature {
+    pub signer: Address,
+    pub signature: Signature,
+}
+
+impl MultisigConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.signers.is_empty() || self.signers.len() > MAX_SIGNERS {
+            return Err(format!("a multisig needs 1 to {} signers.", MAX_SIGNERS));
+        }
+        if self.threshold == 0 || self.threshold > self.signers.len() {
+            return Err(format!(
+                "threshold must be between 1 and the number of signers ({}).",
+                self.signers.len()
+            ));
+        }
+        let unique: HashSet<&Address> = self.signers.iter().collect();
+        if unique.len() != self.signers.len() {
+            return Err("multisig signers must be unique.".into());
+        }
+        Ok(())
+    }
+
+    /// counts distinct signers from the set with a valid signature over the data.
+    /// Signatures from outsiders or repeats of the same signer are ignored, not rejected
+    pub fn count_signatures(&self, data: &String, cosignatures: &[Cosignature]) -> usize {
+        let valid: HashSet<&Address> = cosignatures
+            .iter()
+            .filter(|c| self.signers.contains(&c.signer))
+            .filter(|c| Account::verify_signature(data, &c.signature, &c.signer))
+            .map(|c| &c.signer)
+            .collect();
+        valid.len()
+    }
+
+    pub fn check_signatures(
+        &self,
+        data: &String,
+        cosignatures: &[Cosignature],
+    ) -> Result<(), String> {
+        let count = self.count_signatures(data, cosignatures);
+        if count < self.threshold {
+            return Err(format!(
+                "not enough multisig signatures: {} of {}",
+                count, self.threshold
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Encode for MultisigConfig {
+    fn to_rlp(&self) -> Rlp {
+        Record::new()
+            .field(1, &self.threshold)
+            .field(2, &self.signers)
+            .build()
+    }
+}
+
+impl Decode for MultisigConfig {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        let fields = Fields::of(item)?;
+        Ok(Self {
+            threshold: fields.get(1)?,
+            signers: fields.get(2)?,
+        })
+    }
+}
+
+impl Encode for Cosignature {
+    fn to_rlp(&self) -> Rlp {
+        Record::new()
+            .field(1, &self.signer)
+            .field(2, &self.signature)
+            .build()
+    }
+}
+
+impl Decode for Cosignature {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        let fields = Fields::of(item)?;
+        Ok(Self {
+            signer: fields.get(1)?,
+            signature: fields.get(2)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cosign(account: &Account, data: &String) -> Cosignature {
+        Cosignature {
+            signer: account.public_account.address,
+            signature: account.sign(data),
+        }
+    }
+
+    #[test]
+    fn test_validate_config() {
+        let a = Account::new(vec![]).public_account.address;
+        let b = Account::new(vec![]).public_account.address;
+        let config = |threshold, signers| MultisigConfig { threshold, signers };
+        assert!(config(2, vec![a, b]).validate().is_ok());
+        assert!(config(0, vec![a, b]).validate().is_err());
+        assert!(config(3, vec![a, b]).validate().is_err());
+        assert!(config(1, vec![]).validate().is_err());
+        assert!(config(1, vec![a, a]).validate().is_err());
+    }
+
+    #[test]
+    fn test_threshold() {
+        let signers: Vec<Account> = (0..3).map(|_| Account::new(vec![])).collect();
+        let outsider = Account::new(vec![]);
+        let config = MultisigConfig {
+            threshold: 2,
+            signers: signers.iter().map(|s| s.public_account.address).collect(),
+        };
+        let data = String::from("some tx");
+
+        let mut cosignatures = vec![cosign(&signers[0], &data)];
+        assert_eq!(
+            config.check_signatures(&data, &cosignatures),
+            Err("not enough multisig signatures: 1 of 2".to_string())
+        );
+        //neither a repeat nor an outsider counts towards the threshold
+        cosignatures.push(cosign(&signers[0], &data));
+        cosignatures.push(cosign(&outsider, &data));
+        assert_eq!(config.count_signatures(&data, &cosignatures), 1);
+        //nor does a signature over different data
+        cosignatures.push(cosign(&signers[1], &"another tx".to_string()));
+        assert_eq!(config.count_signatures(&data, &cosignatures), 1);
+
+        cosignatures.push(cosign(&signers[2], &data));
+        assert!(config.check_signatures(&data, &cosignatures).is_ok());
+    }
+}
@@ -0,0 +1,170 @@
+use aes::Aes128;
+use ctr::cipher::{NewCipher, StreamCipher};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use uuid::Uuid;
+
+//web3 secret storage v3 - https://github.com/ethereum/wiki/wiki/Web3-Secret-Storage-Definition
+pub const VERSION: u8 = 3;
+pub const CIPHER: &str = "aes-128-ctr";
+pub const KDF: &str = "scrypt";
+//geth's "light" scrypt params (n = 2^12). The standard n = 2^18 takes about a second per key, which adds up at startup
+pub const SCRYPT_LOG_N: u8 = 12;
+pub const SCRYPT_R: u32 = 8;
+pub const SCRYPT_P: u32 = 1;
+pub const DKLEN: usize = 32;
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CipherParams {
+    pub iv: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KdfParams {
+    pub dklen: usize,
+    pub n: u32,
+    pub r: u32,
+    pub p: u32,
+    pub salt: String,
+}
+
+/// the "crypto" section of a keystore file. All byte strings are hex encoded
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CryptoSection {
+    pub cipher: String,
+    pub cipherparams: CipherParams,
+    pub ciphertext: String,
+    pub kdf: String,
+    pub kdfparams: KdfParams,
+    pub mac: String,
+}
+
+impl CryptoSection {
+    /// encrypts the secret with a fresh random salt and iv
+    pub fn encrypt(secret: &[u8], passphrase: &str) -> Self {
+        let salt = rand::random::<[u8; 32]>();
+        let iv = rand::random::<[u8; 16]>();
+        let kdfparams = KdfParams {
+            dklen: DKLEN,
+            n: 1 << SCRYPT_LOG_N,
+            r: SCRYPT_R,
+            p: SCRYPT_P,
+            salt: hex::encode(salt),
+        };
+        let derived_key = derive_key(passphrase, &kdfparams).unwrap();
+
+        let mut ciphertext = secret.to_vec();
+        apply_cipher(&derived_key, &iv, &mut ciphertext);
+
+        Self {
+            cipher: CIPHER.into(),
+            cipherparams: CipherParams {
+                iv: hex::encode(iv),
+            },
+            mac: hex::encode(mac(&derived_key, &ciphertext)),
+            ciphertext: hex::encode(ciphertext),
+            kdf: KDF.into(),
+            kdfparams,
+        }
+    }
+
+    pub fn decrypt(&self, passphrase: &str) -> Result<Vec<u8>, String> {
+        if self.cipher != CIPHER || self.kdf != KDF {
+            return Err(format!(
+                "unsupported cipher/kdf: {}/{}",
+                self.cipher, self.kdf
+            ));
+        }
+        let derived_key = derive_key(passphrase, &self.kdfparams)?;
+        let mut ciphertext = decode(&self.ciphertext)?;
+        let iv = decode(&self.cipherparams.iv)?;
+        if iv.len() != 16 {
+            return Err("invalid iv".into());
+        }
+
+        //checking the mac first is what tells a wrong passphrase apart from a corrupted key
+        if hex::encode(mac(&derived_key, &ciphertext)) != self.mac {
+            return Err("wrong passphrase".into());
+        }
+        apply_cipher(&derived_key, &iv, &mut ciphertext);
+        Ok(ciphertext)
+    }
+}
+
+/// a keystore file as written to disk, one per account
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EncryptedKey {
+    pub version: u8,
+    pub id: Uuid,
+    //0x-prefixed, lowercase
+    pub address: String,
+    //not part of the spec - the name the account was created under
+    pub name: Option<String>,
+    pub crypto: CryptoSection,
+}
+
+fn derive_key(passphrase: &str, params: &KdfParams) -> Result<Vec<u8>, String> {
+    if !params.n.is_power_of_two() || params.dklen != DKLEN {
+        return Err("invalid kdf params".into());
+    }
+    let scrypt_params = scrypt::Params::new(params.n.trailing_zeros() as u8, params.r, params.p)
+        .map_err(|_| "invalid kdf params".to_string())?;
+    let mut derived_key = vec![0u8; params.dklen];
+    scrypt::scrypt(
+        passphrase.as_bytes(),
+        &decode(&params.salt)?,
+        &scrypt_params,
+        &mut derived_key,
+    )
+    .map_err(|_| "invalid kdf params".to_string())?;
+    Ok(derived_key)
+}
+
+/// first half of the derived key is the aes key, second half goes into the mac.
+/// Both lengths are checked before it gets here (DKLEN, and the iv when decrypting)
+fn apply_cipher(derived_key: &[u8], iv: &[u8], data: &mut [u8]) {
+    let mut cipher =
+        Aes128Ctr::new_from_slices(&derived_key[..16], iv).expect("aes key and iv are 16 bytes");
+    cipher.apply_keystream(data);
+}
+
+fn mac(derived_key: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+    let mut hasher = Keccak256::new();
+    hasher.update(&derived_key[16..32]);
+    hasher.update(ciphertext);
+    hasher.finalize().to_vec()
+}
+
+fn decode(hex_str: &str) -> Result<Vec<u8>, String> {
+    hex::decode(hex_str).map_err(|_| format!("invalid hex: {}", hex_str))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let secret = rand::random::<[u8; 32]>();
+        let crypto = CryptoSection::encrypt(&secret, "correct horse");
+
+        assert_ne!(crypto.ciphertext, hex::encode(secret));
+        assert_eq!(crypto.decrypt("correct horse").unwrap(), secret.to_vec());
+        assert_eq!(
+            crypto.decrypt("battery staple"),
+            Err("wrong passphrase".to_string())
+        );
+    }
+
+    #[test]
+    fn test_same_secret_encrypts_differently() {
+        let secret = rand::random::<[u8; 32]>();
+        let a = CryptoSection::encrypt(&secret, "pass");
+        let b = CryptoSection::encrypt(&secret, "pass");
+        //fresh salt and iv every time
+        assert_ne!(a.ciphertext, b.ciphertext);
+        assert_ne!(a.kdfparams.salt, b.kdfparams.salt);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::account::address::Address;
+use crate::account::Account;
+use secp256k1::rand::rngs::OsRng;
+use secp256k1::Secp256k1;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+//every extra hex char makes the search 16x longer - 8 already takes ~4 billion tries on average
+pub const MAX_PREFIX_LENGTH: usize = 8;
+
+/// what a vanity search found, and how many keys it had to generate to get there
+pub struct VanityResult {
+    pub account: Account,
+    pub attempts: u64,
+}
+
+/// generates keypairs on `threads` threads until one's address matches the prefix, right after the 0x
+pub fn find_vanity_account(prefix: &str, threads: usize) -> Result<VanityResult, String> {
+    let prefix = prefix.to_lowercase();
+    if prefix.is_empty() || prefix.len() > MAX_PREFIX_LENGTH {
+        return Err(format!(
+            "prefix must be 1 to {} hex chars long.",
+            MAX_PREFIX_LENGTH
+        ));
+    }
+    if !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("prefix must be hex: {}", prefix));
+    }
+
+    let found = Arc::new(AtomicBool::new(false));
+    let attempts = Arc::new(AtomicU64::new(0));
+    let (sender, receiver) = mpsc::channel();
+    let workers: Vec<_> = (0..threads.max(1))
+        .map(|_| {
+            let prefix = prefix.clone();
+            let found = found.clone();
+            let attempts = attempts.clone();
+            let sender = sender.clone();
+            thread::spawn(move || {
+                //building a context is the expensive part of gen_keypair(), so each thread reuses its own
+                let secp = Secp256k1::new();
+                let mut rng = OsRng::new().unwrap();
+                while !found.load(Ordering::Relaxed) {
+                    let (secret_key, public_key) = secp.generate_keypair(&mut rng);
+                    attempts.fetch_add(1, Ordering::Relaxed);
+                    if Address::from_public_key(&public_key)
+                        .to_hex()
+                        .starts_with(&prefix)
+                    {
+                        found.store(true, Ordering::Relaxed);
+                        //the receiver only takes the first match, so a second one racing in is fine to drop
+                        let _ = sender.send(secret_key);
+                    }
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+
+    let secret_key = receiver
+        .recv()
+        .map_err(|_| "vanity search stopped without a match.".to_string())?;
+    for worker in workers {
+        worker
+            .join()
+            .map_err(|_| "vanity search thread panicked.".to_string())?;
+    }
+    Ok(VanityResult {
+        account: Account::from_secret_key(secret_key),
+        attempts: attempts.load(Ordering::Relaxed),
+    })
+}
+
+/// all cores, or 1 if we can't tell
+pub fn default_threads() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_vanity_account() {
+        let result = find_vanity_account("Ab", 2).unwrap();
+        let address = result.account.public_account.address.to_string();
+        assert!(address[2..].starts_with("ab"));
+        assert!(result.attempts >= 1);
+    }
+
+    #[test]
+    fn test_bad_prefix() {
+        assert!(find_vanity_account("", 1).is_err());
+        assert!(find_vanity_account("xyz", 1).is_err());
+        assert!(find_vanity_account("123456789", 1).is_err());
+    }
+}
@@ -0,0 +1,79 @@
+use crate::config::NodeConfig;
+use crate::util::keccak_bytes;
+use actix_web::dev::Payload;
+use actix_web::error::ErrorUnauthorized;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{web, Error, FromRequest, HttpRequest};
+use std::future::{ready, Ready};
+
+/// add this as a param to any handler that mutates state or is admin-only.
+/// If the node has an auth token configured, the request must carry "Authorization: Bearer <token>".
+/// If no token is configured, auth is disabled and every request goes through (handy for local dev & tests)
+pub struct AdminAuth;
+
+impl FromRequest for AdminAuth {
+    type Config = ();
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let expected = req
+            .app_data::<web::Data<NodeConfig>>()
+            .and_then(|config| config.auth_token.clone());
+        let header = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|h| h.to_str().ok());
+
+        if is_authorized(expected.as_deref(), header) {
+            ready(Ok(AdminAuth))
+        } else {
+            ready(Err(ErrorUnauthorized("missing or invalid auth token.")))
+        }
+    }
+}
+
+pub fn is_authorized(expected_token: Option<&str>, auth_header: Option<&str>) -> bool {
+    match expected_token {
+        None => true,
+        Some(expected) => auth_header
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .map(|token| tokens_match(token, expected))
+            .unwrap_or(false),
+    }
+}
+
+/// compares the hashes, so the time it takes doesn't depend on the tokens' lengths or on how much of them matches
+fn tokens_match(token: &str, expected: &str) -> bool {
+    let (token, expected) = (
+        keccak_bytes(token.as_bytes()),
+        keccak_bytes(expected.as_bytes()),
+    );
+    token
+        .bytes()
+        .zip(expected.bytes())
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_disabled() {
+        assert!(is_authorized(None, None));
+        assert!(is_authorized(None, Some("Bearer whatever")));
+    }
+
+    #[test]
+    fn test_auth_enabled() {
+        assert!(is_authorized(Some("secret"), Some("Bearer secret")));
+        assert!(!is_authorized(Some("secret"), None));
+        assert!(!is_authorized(Some("secret"), Some("Bearer wrong")));
+        assert!(!is_authorized(Some("secret"), Some("secret")));
+        assert!(!is_authorized(Some("secret"), Some("Bearer secre")));
+        assert!(!is_authorized(Some("secret"), Some("Bearer secrets")));
+        assert!(!is_authorized(Some("secret"), Some("Bearer ")));
+    }
+}
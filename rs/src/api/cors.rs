@@ -0,0 +1,20 @@
+use crate::config::NodeConfig;
+use actix_cors::Cors;
+use actix_web::http::header::{AUTHORIZATION, CONTENT_TYPE};
+
+/// lets browser-based explorers call the api. Preflight (OPTIONS) requests are answered by the middleware itself
+pub fn build_cors(config: &NodeConfig) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(config.cors_methods.iter().map(|m| m.as_str()))
+        .allowed_headers(vec![AUTHORIZATION, CONTENT_TYPE])
+        .max_age(3600);
+
+    for origin in &config.cors_origins {
+        cors = if origin == "*" {
+            cors.allow_any_origin()
+        } else {
+            cors.allowed_origin(origin)
+        };
+    }
+    cors
+}
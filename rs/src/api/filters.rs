@@ -0,0 +1,378 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::account::address::Address;
+use crate::blockchain::blockchain::Blockchain;
+use crate::transaction::tx::Transaction;
+
+/// same as geth - a filter nobody polled for 5 min gets dropped
+pub const FILTER_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// what eth_newFilter takes. All fields optional, block numbers inclusive
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogCriteria {
+    pub address: Option<Address>,
+    pub from_block: Option<usize>,
+    pub to_block: Option<usize>,
+}
+
+impl LogCriteria {
+    fn matches(&self, address: Option<&Address>, block_number: usize) -> bool {
+        self.address.as_ref().is_none_or(|a| Some(a) == address)
+            && self.from_block.is_none_or(|from| block_number >= from)
+            && self.to_block.is_none_or(|to| block_number <= to)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    //the contract that was called
+    pub address: Option<Address>,
+    pub block_number: usize,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub data: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum FilterKind {
+    //last block number we reported
+    Blocks {
+        last_seen: usize,
+    },
+    //hashes of queued tx we've already reported
+    PendingTx {
+        seen: HashSet<String>,
+    },
+    Logs {
+        criteria: LogCriteria,
+        last_seen: usize,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Filter {
+    pub kind: FilterKind,
+    pub last_polled: Instant,
+}
+
+/// server side state for the eth_newFilter family, for clients that poll instead of subscribing.
+/// Every method takes `now` so that tests don't have to actually wait for filters to expire
+#[derive(Debug, Default)]
+pub struct FilterRegistry {
+    filters: HashMap<String, Filter>,
+}
+
+impl FilterRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// filters only ever report what happened after they were installed
+    pub fn install_block_filter(&mut self, blockchain: &Blockchain, now: Instant) -> String {
+        let last_seen = blockchain.chain.len() - 1;
+        self.install(FilterKind::Blocks { last_seen }, now)
+    }
+    pub fn install_pending_tx_filter(&mut self, tx_queue: &[Transaction], now: Instant) -> String {
+        let seen = tx_queue.iter().map(Transaction::hash).collect();
+        self.install(FilterKind::PendingTx { seen }, now)
+    }
+    pub fn install_log_filter(
+        &mut self,
+        criteria: LogCriteria,
+        blockchain: &Blockchain,
+        now: Instant,
+    ) -> String {
+        let last_seen = blockchain.chain.len() - 1;
+        self.install(
+            FilterKind::Logs {
+                criteria,
+                last_seen,
+            },
+            now,
+        )
+    }
+    fn install(&mut self, kind: FilterKind, now: Instant) -> String {
+        self.remove_expired(now);
+        let id = format!("0x{:x}", rand::random::<u64>());
+        self.filters.insert(
+            id.clone(),
+            Filter {
+                kind,
+                last_polled: now,
+            },
+        );
+        id
+    }
+
+    pub fn uninstall(&mut self, id: &str) -> bool {
+        self.filters.remove(id).is_some()
+    }
+
+    /// everything that matched since the last poll - block hashes, tx hashes or LogEntry's depending on the filter.
+    /// None if the filter doesn't exist (or expired)
+    pub fn get_changes(
+        &mut self,
+        id: &str,
+        blockchain: &Blockchain,
+        tx_queue: &[Transaction],
+        now: Instant,
+    ) -> Option<Vec<Value>> {
+        self.remove_expired(now);
+        let filter = self.filters.get_mut(id)?;
+        filter.last_polled = now;
+        let head = blockchain.chain.len() - 1;
+
+        let changes = match &mut filter.kind {
+            FilterKind::Blocks { last_seen } => {
+                let hashes = blockchain
+                    .chain
+                    .iter()
+                    .skip(*last_seen + 1)
+                    .map(|b| Value::String(b.hash()))
+                    .collect();
+                *last_seen = head;
+                hashes
+            }
+            FilterKind::PendingTx { seen } => {
+                let queued: Vec<String> = tx_queue.iter().map(Transaction::hash).collect();
+                let new = queued
+                    .iter()
+                    .filter(|hash| !seen.contains(*hash))
+                    .map(|hash| Value::String(hash.clone()))
+                    .collect();
+                //forget tx that left the queue, so the set doesn't grow forever
+                *seen = queued.into_iter().collect();
+                new
+            }
+            FilterKind::Logs {
+                criteria,
+                last_seen,
+            } => {
+                let logs = collect_logs(blockchain, criteria, *last_seen + 1)
+                    .into_iter()
+                    .map(|log| serde_json::to_value(log).unwrap())
+                    .collect();
+                *last_seen = head;
+                logs
+            }
+        };
+        Some(changes)
+    }
+
+    pub fn remove_expired(&mut self, now: Instant) {
+        self.filters
+            .retain(|_, f| now.duration_since(f.last_polled) < FILTER_TIMEOUT);
+    }
+
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+}
+
+/// logs from every receipt in blocks >= from_block that match the criteria
+pub fn collect_logs(
+    blockchain: &Blockchain,
+    criteria: &LogCriteria,
+    from_block: usize,
+) -> Vec<LogEntry> {
+    let mut logs = vec![];
+    for block in blockchain.chain.iter().skip(from_block) {
+        let number = block.block_headers.truncated_block_headers.number;
+        for tx in block.tx_series.iter() {
+            let address = tx.unsigned_tx.to.as_ref();
+            if !criteria.matches(address, number) {
+                continue;
+            }
+            if let Some(receipt) = blockchain.get_receipt(&tx.hash()) {
+                for data in receipt.logs.iter() {
+                    logs.push(LogEntry {
+                        address: address.copied(),
+                        block_number: number,
+                        block_hash: receipt.block_hash.clone(),
+                        tx_hash: receipt.tx_hash.clone(),
+                        data: data.clone(),
+                    });
+                }
+            }
+        }
+    }
+    logs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::blockchain::block::Block;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::store::state::State;
+    use crate::util::prep_state;
+
+    /// fresh chain + a block 1 that creates the miner's account (mining rewards need it to exist)
+    fn chain_with_miner() -> (Blockchain, Address, Block) {
+        let global_state = prep_state();
+        let miner_addr = global_state.miner_address;
+        let tx_series = global_state.tx_queue.into_inner().unwrap().get_tx_series();
+        let mut blockchain = global_state.blockchain.into_inner().unwrap();
+        let block = mine(&mut blockchain, miner_addr, tx_series);
+        (blockchain, miner_addr, block)
+    }
+
+    fn mine(
+        blockchain: &mut Blockchain,
+        beneficiary: Address,
+        tx_series: Vec<Transaction>,
+    ) -> Block {
+        let last_block = blockchain.chain.last().unwrap().clone();
+        let state_root = blockchain.state.get_state_root().clone();
+        let block = Block::mine_block(
+            &last_block,
+            beneficiary,
+            tx_series,
+            &state_root,
+            &*blockchain.clock,
+            &blockchain.rules,
+        );
+        blockchain.add_block(block.clone()).unwrap();
+        block
+    }
+
+    #[test]
+    fn test_block_filter() {
+        let (mut blockchain, miner_addr, _) = chain_with_miner();
+        let mut filters = FilterRegistry::new();
+        let now = Instant::now();
+
+        let id = filters.install_block_filter(&blockchain, now);
+        assert_eq!(
+            filters.get_changes(&id, &blockchain, &[], now),
+            Some(vec![])
+        );
+
+        let block = mine(&mut blockchain, miner_addr, vec![]);
+        let changes = filters.get_changes(&id, &blockchain, &[], now).unwrap();
+        assert_eq!(changes, vec![Value::String(block.hash())]);
+
+        //already reported
+        assert_eq!(
+            filters.get_changes(&id, &blockchain, &[], now),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn test_pending_tx_filter() {
+        let mut global_state = prep_state();
+        let blockchain = Blockchain::new(State::new(), DEFAULT_CHAIN_ID);
+        let mut tx_queue = global_state.tx_queue.get_mut().unwrap().get_tx_series();
+        let mut filters = FilterRegistry::new();
+        let now = Instant::now();
+
+        //tx queued before the filter was installed don't count
+        let id = filters.install_pending_tx_filter(&tx_queue, now);
+        assert_eq!(
+            filters.get_changes(&id, &blockchain, &tx_queue, now),
+            Some(vec![])
+        );
+
+        let tx = Transaction::create_transaction(
+            Some(Account::new(vec![])),
+            None,
+            0,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        tx_queue.push(tx.clone());
+        let changes = filters
+            .get_changes(&id, &blockchain, &tx_queue, now)
+            .unwrap();
+        assert_eq!(changes, vec![Value::String(tx.hash())]);
+        assert_eq!(
+            filters.get_changes(&id, &blockchain, &tx_queue, now),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn test_log_filter() {
+        let (mut blockchain, miner_addr, _) = chain_with_miner();
+        let mut filters = FilterRegistry::new();
+        let now = Instant::now();
+        let contract = crate::account::gen_address();
+
+        let id = filters.install_log_filter(
+            LogCriteria {
+                address: Some(contract),
+                ..LogCriteria::default()
+            },
+            &blockchain,
+            now,
+        );
+        let block = mine(&mut blockchain, miner_addr, vec![]);
+
+        //nothing emits logs yet, so pretend the block's mining reward tx was a call to our contract that logged something
+        let mut tx = block.tx_series[0].clone();
+        tx.unsigned_tx.to = Some(contract);
+        let mut receipt = blockchain
+            .get_receipt(&block.tx_series[0].hash())
+            .unwrap()
+            .clone();
+        receipt.tx_hash = tx.hash();
+        receipt.logs = vec!["hello".into()];
+        blockchain.store_receipts(vec![receipt]);
+        let mut block = (*blockchain.chain[2]).clone();
+        block.tx_series = vec![tx.clone()];
+        blockchain.chain[2] = std::sync::Arc::new(block);
+
+        let changes = filters.get_changes(&id, &blockchain, &[], now).unwrap();
+        assert_eq!(changes.len(), 1);
+        let log: LogEntry = serde_json::from_value(changes[0].clone()).unwrap();
+        assert_eq!(log.tx_hash, tx.hash());
+        assert_eq!(log.block_number, 2);
+        assert_eq!(log.data, "hello");
+    }
+
+    #[test]
+    fn test_filters_expire() {
+        let blockchain = Blockchain::new(State::new(), DEFAULT_CHAIN_ID);
+        let mut filters = FilterRegistry::new();
+        let now = Instant::now();
+
+        let id = filters.install_block_filter(&blockchain, now);
+        //polling resets the timer
+        let later = now + FILTER_TIMEOUT - Duration::from_secs(1);
+        assert!(filters.get_changes(&id, &blockchain, &[], later).is_some());
+        assert!(filters
+            .get_changes(
+                &id,
+                &blockchain,
+                &[],
+                later + FILTER_TIMEOUT - Duration::from_secs(1)
+            )
+            .is_some());
+
+        let much_later = later + FILTER_TIMEOUT * 3;
+        assert!(filters
+            .get_changes(&id, &blockchain, &[], much_later)
+            .is_none());
+        assert!(filters.is_empty());
+    }
+
+    #[test]
+    fn test_uninstall() {
+        let blockchain = Blockchain::new(State::new(), DEFAULT_CHAIN_ID);
+        let mut filters = FilterRegistry::new();
+        let id = filters.install_block_filter(&blockchain, Instant::now());
+        assert!(filters.uninstall(&id));
+        assert!(!filters.uninstall(&id));
+    }
+}
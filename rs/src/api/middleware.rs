@@ -0,0 +1,55 @@
+use std::future::Future;
+use std::time::Instant;
+
+use actix_service::Service;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{HeaderName, HeaderValue};
+use actix_web::Error;
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// use with App::wrap_fn. Runs every request inside its own span (so anything the handler logs carries the
+/// request id), logs status + latency once it's done and hands the id back to the client in x-request-id
+pub fn trace_request<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = Result<ServiceResponse<B>, Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    let request_id = Uuid::new_v4().to_string();
+    let span = tracing::info_span!(
+        "request",
+        id = %request_id,
+        method = %req.method(),
+        path = %req.path(),
+    );
+    let start = Instant::now();
+    let fut = span.in_scope(|| srv.call(req));
+
+    async move {
+        let res = fut.await;
+        let latency_ms = start.elapsed().as_millis() as u64;
+        match res {
+            Ok(mut res) => {
+                tracing::info!(
+                    status = res.status().as_u16(),
+                    latency_ms,
+                    "request finished"
+                );
+                res.headers_mut().insert(
+                    HeaderName::from_static(REQUEST_ID_HEADER),
+                    HeaderValue::from_str(&request_id).unwrap(),
+                );
+                Ok(res)
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, latency_ms, "request failed");
+                Err(e)
+            }
+        }
+    }
+    .instrument(span)
+}
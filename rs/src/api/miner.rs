@@ -0,0 +1,203 @@
+//the auto miner: mines in a background task instead of waiting for someone to hit /mine. Started with --auto-mine
+// or POST /miner/start, stopped with POST /miner/stop
+
+use crate::api::server::mine_next_block;
+use crate::error::MineError;
+use crate::events::Event;
+use crate::util::GlobalState;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+use utoipa::ToSchema;
+
+/// how often the auto miner checks whether the node is done syncing
+pub const SYNC_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AutoMinerStatus {
+    pub running: bool,
+    /// a block every this many seconds, empty or not. None = a block as soon as there are tx to put in it
+    pub interval_secs: Option<u64>,
+    /// since the node started
+    pub blocks_mined: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StartMinerRequest {
+    /// leave out to mine as soon as there are tx, instead of on a timer
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+}
+
+/// whether the auto miner is running, and how to stop it. Lives in GlobalState
+#[derive(Debug, Default)]
+pub struct AutoMiner {
+    interval_secs: Option<u64>,
+    stop: Option<watch::Sender<bool>>,
+    blocks_mined: u64,
+}
+
+impl AutoMiner {
+    pub fn status(&self) -> AutoMinerStatus {
+        AutoMinerStatus {
+            running: self.stop.is_some(),
+            interval_secs: self.interval_secs,
+            blocks_mined: self.blocks_mined,
+        }
+    }
+    /// the task keeps going until stop() - the block it's on gets finished first
+    pub fn start(
+        &mut self,
+        global_state: Arc<GlobalState>,
+        interval_secs: Option<u64>,
+    ) -> Result<(), String> {
+        if self.stop.is_some() {
+            return Err("the auto miner is already running, stop it first".into());
+        }
+        if interval_secs == Some(0) {
+            return Err("the mining interval must be above 0".into());
+        }
+        let (stop, stopped) = watch::channel(false);
+        //subscribed before the task starts, so no tx that comes in meanwhile is missed
+        let events = global_state.events.subscribe();
+        let interval = interval_secs.map(Duration::from_secs);
+        tokio::spawn(run(global_state, interval, events, stopped));
+        self.interval_secs = interval_secs;
+        self.stop = Some(stop);
+        tracing::info!(interval_secs = ?interval_secs, "auto miner started");
+        Ok(())
+    }
+    pub fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(true);
+            tracing::info!("auto miner stopped");
+        }
+        self.interval_secs = None;
+    }
+}
+
+async fn run(
+    global_state: Arc<GlobalState>,
+    interval: Option<Duration>,
+    mut events: broadcast::Receiver<Event>,
+    mut stopped: watch::Receiver<bool>,
+) {
+    //anything queued before we started counts as new
+    let mut tx_waiting = has_queued_tx(&global_state);
+    loop {
+        //None = stop. A dropped sender means nobody can stop us anymore, so that counts too
+        let due = match interval {
+            Some(interval) => tokio::select! {
+                _ = tokio::time::sleep(interval) => Some(true),
+                changed = stopped.changed() => changed.ok().map(|_| false),
+            },
+            None if tx_waiting => Some(true),
+            None => tokio::select! {
+                //the tx may have made it into the last block already
+                event = events.recv() => Some(matches!(
+                    event,
+                    Ok(Event::NewTx(_)) | Err(broadcast::error::RecvError::Lagged(_))
+                ) && has_queued_tx(&global_state)),
+                changed = stopped.changed() => changed.ok().map(|_| false),
+            },
+        };
+        match due {
+            None => return,
+            Some(_) if *stopped.borrow() => return,
+            Some(false) => continue,
+            Some(true) => {}
+        }
+
+        match mine_next_block(&global_state).await {
+            Ok(block) => {
+                global_state.miner.lock().unwrap().blocks_mined += 1;
+                //more than the reward made it in, so whatever's still queued may fit in the next one (eg the block
+                // was full). Otherwise it's tx that can't go in yet, and only a new one is worth waking up for
+                tx_waiting = block.tx_series.len() > 1 && has_queued_tx(&global_state);
+                tracing::info!(
+                    number = block.block_headers.truncated_block_headers.number,
+                    tx = block.tx_series.len(),
+                    "auto miner mined a block"
+                );
+            }
+            //nothing to do until the sync is done - the tx will still be there
+            Err(MineError::Syncing) => {
+                tracing::debug!("auto miner waiting for the sync");
+                tokio::time::sleep(SYNC_RETRY_INTERVAL).await;
+            }
+            //eg a peer's block came in while we were sealing. The next round mines on top of it
+            Err(e) => {
+                tracing::warn!(error = %e, "auto miner failed to mine a block");
+                tx_waiting = false;
+            }
+        }
+    }
+}
+
+fn has_queued_tx(global_state: &GlobalState) -> bool {
+    !global_state.tx_queue.lock().unwrap().tx_map.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::api::pubsub::queue_transaction;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::store::codec;
+    use crate::transaction::tx::Transaction;
+    use crate::util::prep_state;
+    use std::time::Instant;
+
+    async fn wait_for_height(global_state: &GlobalState, height: usize) {
+        let started = Instant::now();
+        while global_state.blockchain.read().unwrap().chain.len() <= height {
+            assert!(
+                started.elapsed() < Duration::from_secs(30),
+                "no block {}",
+                height
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_mines_as_tx_come_in() {
+        let global_state = Arc::new(prep_state());
+        {
+            let mut miner = global_state.miner.lock().unwrap();
+            miner.start(global_state.clone(), None).unwrap();
+            assert!(miner.start(global_state.clone(), None).is_err());
+        }
+
+        //the 2 account creations prep_state() queued
+        wait_for_height(&global_state, 1).await;
+        let tx = Transaction::create_transaction(
+            Some(Account::new(vec![])),
+            None,
+            0,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        queue_transaction(&codec::encode_hex(&tx), &global_state).unwrap();
+        wait_for_height(&global_state, 2).await;
+        assert!(global_state.tx_queue.lock().unwrap().tx_map.is_empty());
+
+        global_state.miner.lock().unwrap().stop();
+        //nothing queued, so nothing more gets mined
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(global_state.blockchain.read().unwrap().chain.len(), 3);
+        let status = global_state.miner.lock().unwrap().status();
+        assert!(!status.running);
+        assert_eq!(status.blocks_mined, 2);
+    }
+
+    #[test]
+    fn test_interval_has_to_be_above_0() {
+        let mut miner = AutoMiner::default();
+        assert!(miner.start(Arc::new(prep_state()), Some(0)).is_err());
+        assert!(!miner.status().running);
+    }
+}
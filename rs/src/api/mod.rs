@@ -1,2 +1,11 @@
+pub mod auth;
+pub mod cors;
+pub mod filters;
+pub mod middleware;
+pub mod miner;
+pub mod openapi;
 pub mod pubsub;
+pub mod rpc;
 pub mod server;
+pub mod tls;
+pub mod webhooks;
@@ -0,0 +1,284 @@
+use crate::account::address_book::NamedAddress;
+use crate::account::multisig::MultisigConfig;
+use crate::api::miner::{AutoMinerStatus, StartMinerRequest};
+use crate::api::rpc::{RpcError, RpcRequest, RpcResponse};
+use crate::api::server::{
+    AccountInfo, AccountProof, AddressBookEntry, AddressTx, BlockResponse, BlockTxSeries,
+    ChainStats, ConsensusClockInfo, ContractGasRanking, CosignRequest, CreateAccountRequest,
+    CreateAccountResponse, DroppedTx, FaucetRequest, HeadBlock, InclusionStatus, MultisigProposal,
+    MultisigTx, NodeInfo, PendingBlockPreview, PendingTx, PrepareTxRequest, RegisterWebhookRequest,
+    SendSignedTxRequest, SignMessageRequest, SignedMessage, SigningPayload, SponsorTxRequest,
+    StorageSlot, SubmitTxRequest, SubmitWorkRequest, TokenBalance, TokenSupply, TxLookup, TxProof,
+    TxRequest, TxResponse, UnlockAccountRequest, VerifyMessageResponse, WebhookFilterRequest,
+    WebhookRegistration, WorkPackage,
+};
+use crate::api::webhooks::{Webhook, WebhookEvent, WebhookFilter};
+use crate::blockchain::gas_stats::{BlockGasStats, ContractGas};
+use crate::blockchain::sync::{Lifecycle, PeerHealth, SyncStatus};
+use crate::interpreter::profile::{CategoryGas, GasCategory, OpcodeGas, TxGasProfile};
+use crate::network::propagation::{FirstSeen, NodePropagation, PropagationReport};
+use crate::transaction::activity::{Activity, Direction};
+use crate::transaction::receipt::{Receipt, ReceiptStatus};
+use crate::transaction::tx_queue::TxStatus;
+use actix_web::{get, HttpResponse, Responder};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+/// every handler in server.rs is annotated with #[utoipa::path] - to show up in the docs it also has to be listed here
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        crate::api::server::get_blockchain,
+        crate::api::server::get_block,
+        crate::api::server::get_tx_proof,
+        crate::api::server::get_receipt,
+        crate::api::server::get_transaction,
+        crate::api::server::get_stats,
+        crate::api::server::get_latest_blocks,
+        crate::api::server::get_contract_gas,
+        crate::api::server::get_block_range,
+        crate::api::server::get_address_txs,
+        crate::api::server::get_address_history,
+        crate::api::server::mine,
+        crate::api::server::get_pending_block,
+        crate::api::server::get_work,
+        crate::api::server::submit_work,
+        crate::api::server::start_miner,
+        crate::api::server::stop_miner,
+        crate::api::server::transact,
+        crate::api::server::faucet,
+        crate::api::server::propose_multisig_tx,
+        crate::api::server::cosign_multisig_tx,
+        crate::api::server::submit_multisig_tx,
+        crate::api::server::prepare_tx,
+        crate::api::server::send_signed_tx,
+        crate::api::server::sponsor_tx,
+        crate::api::server::get_balance,
+        crate::api::server::get_account_proof,
+        crate::api::server::get_accounts,
+        crate::api::server::create_account,
+        crate::api::server::unlock_account,
+        crate::api::server::sign_message,
+        crate::api::server::verify_message,
+        crate::api::server::get_state,
+        crate::api::server::get_storage_trie,
+        crate::api::server::get_storage_at,
+        crate::api::server::get_token_balance,
+        crate::api::server::get_token_supply,
+        crate::api::server::get_gas_profile,
+        crate::api::server::get_snapshot,
+        crate::api::rpc::rpc,
+        crate::api::server::get_node_info,
+        crate::api::server::get_consensus_clock,
+        crate::api::server::get_sync_status,
+        crate::api::server::get_health,
+        crate::api::server::get_metrics,
+        crate::api::server::get_propagation,
+        crate::api::server::get_propagation_report,
+        crate::api::server::register_webhook,
+        crate::api::server::get_webhooks,
+        crate::api::server::delete_webhook,
+        crate::api::server::get_address_book,
+        crate::api::server::set_address_book_entry,
+        crate::api::server::delete_address_book_entry,
+    ),
+    components(schemas(
+        AccountInfo,
+        AccountProof,
+        AddressTx,
+        Activity,
+        Direction,
+        BlockGasStats,
+        ContractGas,
+        ContractGasRanking,
+        BlockResponse,
+        BlockTxSeries,
+        ChainStats,
+        ConsensusClockInfo,
+        CreateAccountRequest,
+        CreateAccountResponse,
+        DroppedTx,
+        HeadBlock,
+        InclusionStatus,
+        Lifecycle,
+        NodeInfo,
+        PendingBlockPreview,
+        PendingTx,
+        WorkPackage,
+        SubmitWorkRequest,
+        StartMinerRequest,
+        AutoMinerStatus,
+        Receipt,
+        ReceiptStatus,
+        RpcError,
+        RpcRequest,
+        RpcResponse,
+        SignMessageRequest,
+        SignedMessage,
+        StorageSlot,
+        TokenBalance,
+        TokenSupply,
+        TxGasProfile,
+        OpcodeGas,
+        GasCategory,
+        CategoryGas,
+        TxLookup,
+        TxProof,
+        TxRequest,
+        TxResponse,
+        FaucetRequest,
+        MultisigConfig,
+        MultisigProposal,
+        CosignRequest,
+        MultisigTx,
+        SubmitTxRequest,
+        SyncStatus,
+        PeerHealth,
+        FirstSeen,
+        NodePropagation,
+        PropagationReport,
+        PrepareTxRequest,
+        SigningPayload,
+        SendSignedTxRequest,
+        SponsorTxRequest,
+        TxStatus,
+        UnlockAccountRequest,
+        VerifyMessageResponse,
+        RegisterWebhookRequest,
+        WebhookFilterRequest,
+        WebhookRegistration,
+        Webhook,
+        WebhookEvent,
+        WebhookFilter,
+        AddressBookEntry,
+        NamedAddress,
+    )),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "chain", description = "blocks and receipts"),
+        (name = "explorer", description = "summaries for block explorer frontends"),
+        (name = "state", description = "balances and raw tries"),
+        (name = "accounts", description = "accounts managed by this node"),
+        (name = "node", description = "mining and tx submission"),
+        (name = "rpc", description = "ethereum style json-rpc"),
+        (name = "debug", description = "looking into how tx ran"),
+    )
+)]
+pub struct ApiDoc;
+
+/// registers the "Authorization: Bearer <token>" scheme referenced by the protected endpoints
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer_auth",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
+#[get("/openapi.json")]
+pub async fn get_openapi() -> impl Responder {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+/// swagger ui pulled from a cdn and pointed at /openapi.json
+/// (the swagger ui crates all need actix-web 4 stable, so we can't bundle it)
+const SWAGGER_UI_HTML: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+  <title>rs node api</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>"##;
+
+#[get("/docs")]
+pub async fn get_docs() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(SWAGGER_UI_HTML)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_lists_all_routes() {
+        let spec = ApiDoc::openapi();
+        for path in [
+            "/blockchain",
+            "/block/{number_or_hash}",
+            "/block/{number}/tx_proof/{tx_hash}",
+            "/receipt/{tx_hash}",
+            "/stats",
+            "/blocks/latest",
+            "/stats/contracts",
+            "/address/{address}/txs",
+            "/address/{address}/history",
+            "/mine",
+            "/miner/pending_block",
+            "/miner/work",
+            "/miner/start",
+            "/miner/stop",
+            "/transact",
+            "/faucet",
+            "/multisig/propose",
+            "/multisig/cosign",
+            "/multisig/submit",
+            "/tx/prepare",
+            "/tx/send",
+            "/tx/sponsor",
+            "/balance/{address}",
+            "/proof/{address}",
+            "/accounts",
+            "/accounts/{address}/unlock",
+            "/sign",
+            "/verify",
+            "/state",
+            "/storage_trie",
+            "/storage/{address}/{key}",
+            "/token/{address}/balance/{holder}",
+            "/token/{address}/supply",
+            "/debug/gas/{tx_hash}",
+            "/rpc",
+            "/admin/nodeinfo",
+            "/consensus/clock",
+            "/sync",
+            "/health",
+            "/metrics",
+            "/propagation",
+            "/propagation/report",
+            "/webhooks",
+            "/webhooks/{id}",
+            "/addressbook",
+            "/addressbook/{name}",
+        ] {
+            assert!(
+                spec.paths.paths.contains_key(path),
+                "{} missing from the openapi spec",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_spec_has_bearer_auth() {
+        let spec = ApiDoc::openapi();
+        let components = spec.components.unwrap();
+        assert!(components.security_schemes.contains_key("bearer_auth"));
+        assert!(components.schemas.contains_key("TxRequest"));
+    }
+}
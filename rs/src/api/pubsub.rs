@@ -1,139 +1,259 @@
-use crate::blockchain::block::Block;
+//what a node does with the blocks and tx other nodes send it, whichever way they come in - see network::gossip and
+// (with the rabbitmq feature) network::rabbit
 
+use crate::api::server::validate_pooled_tx;
+use crate::blockchain::block::{Block, MAX_BLOCK_BYTES};
+use crate::error::NetError;
+use crate::events::Event;
+use crate::store::codec;
 use crate::transaction::tx::Transaction;
+use crate::util::clock::{Clock, SystemClock};
 use crate::util::GlobalState;
-use futures_util::stream::StreamExt;
-use lapin::{
-    options::*, types::FieldTable, BasicProperties, Channel, Connection, ConnectionProperties,
-    ExchangeKind, Promise, Result,
-};
-use std::ops::DerefMut;
-use std::sync::{Arc, Mutex};
-
-pub async fn rabbit_connect() -> Result<Connection> {
-    let addr = std::env::var("AMQP_ADDR").unwrap_or_else(|_| "amqp://127.0.0.1:5672/%2f".into());
-    let conn = Connection::connect(&addr, ConnectionProperties::default()).await?;
-    println!("connected to RabbitMQ!");
-
-    Ok(conn)
-}
+use std::sync::Arc;
 
-pub fn create_ex_if_doesnt_exist(channel: &Channel, exchange: &str) -> Promise<()> {
-    channel.exchange_declare(
-        exchange,
-        ExchangeKind::Fanout, //important for blockchain to be blockchain
-        ExchangeDeclareOptions::default(),
-        FieldTable::default(),
-    )
-}
+/// only fails if the message isn't a block at all - a block we reject is logged, same as any other peer's
+pub fn process_block(block: String, global_state: Arc<GlobalState>) -> Result<(), NetError> {
+    //no point decoding something check_block is going to throw out anyway
+    if block.len() > MAX_BLOCK_BYTES {
+        return Err(NetError::Decode(format!(
+            "block is {} bytes, over the {} byte limit",
+            block.len(),
+            MAX_BLOCK_BYTES
+        )));
+    }
+    let block_object: Block =
+        codec::decode_hex(&block).map_err(|e| NetError::Decode(e.to_string()))?;
+    tracing::debug!(block = ?block_object, "decoded block");
+
+    //chain lock is released by the time this returns, before we touch the tx queue
+    let added = global_state.import_block(block_object.clone());
 
-pub async fn rabbit_publish(payload: String, exchange: &str) -> Result<()> {
-    let conn = rabbit_connect().await.unwrap();
-    let channel_a = conn.create_channel().await?;
-    let _ex = create_ex_if_doesnt_exist(&channel_a, exchange);
-
-    let _confirm = channel_a
-        .basic_publish(
-            exchange, //subscribe tou our exchange
-            "", //when using fanout, we don't need to specify routing_key -https://www.rabbitmq.com/tutorials/tutorial-three-python.html
-            BasicPublishOptions::default(),
-            payload.as_bytes().to_vec(),
-            BasicProperties::default(),
-        )
-        .await?
-        .await?;
-
-    println!(">>> published payload: {:?}", &payload);
+    match added {
+        Ok(()) => {
+            let now = global_state.blockchain.read().unwrap().clock.now_millis();
+            global_state.sync.lock().unwrap().record_peer_block(now);
+            //clear processed tx from the queue
+            global_state
+                .tx_queue
+                .lock()
+                .unwrap()
+                .clear_block_tx(&block_object.tx_series);
+            global_state.events.publish(Event::NewBlock {
+                block: Arc::new(block_object.clone()),
+                from_peer: true,
+            });
+            tracing::info!(
+                number = block_object.block_headers.truncated_block_headers.number,
+                "inserted block from peer into the blockchain"
+            );
+        }
+        Err(e) => {
+            tracing::warn!(
+                number = block_object.block_headers.truncated_block_headers.number,
+                error = %e,
+                "failed to insert block from peer"
+            );
+        }
+    }
     Ok(())
 }
 
-pub async fn rabbit_consume(
-    processor: fn(String, Arc<Mutex<GlobalState>>),
-    global_state: Arc<Mutex<GlobalState>>,
-    exchange: &str,
-) -> Result<()> {
-    let conn = rabbit_connect().await.unwrap();
-    let channel_b = conn.create_channel().await?;
-    let _ex = create_ex_if_doesnt_exist(&channel_b, exchange); //needed in both, as sometimes this thread will run ahead of producer
-
-    // create a tmp queue
-    let q_opts = QueueDeclareOptions {
-        exclusive: true,
-        ..QueueDeclareOptions::default()
-    };
-    let queue = channel_b
-        .queue_declare(
-            "",     //when a name is not specified, a random name is given
-            q_opts, //exclusive=true means q will be deleted after, which is what we want
-            FieldTable::default(),
-        )
-        .await?;
-    println!("declared a tmp queue: {}", &queue.name().to_string());
-
-    // bind the tmp queue to the exchange, otherwise the exchange won't know to fanout msgs to this q
-    let _ = channel_b.queue_bind(
-        &queue.name().to_string(),
-        exchange,
-        "", //again no need to specify coz using fanout
-        QueueBindOptions::default(),
-        FieldTable::default(),
-    );
-
-    let mut consumer = channel_b
-        .basic_consume(
-            &queue.name().to_string(),
-            "my_consumer",
-            BasicConsumeOptions::default(),
-            FieldTable::default(),
-        )
-        .await?;
-
-    while let Some(delivery) = consumer.next().await {
-        let (_channel, delivery) = delivery.expect("error in consumer");
-        println!("<<< got delivery: {:?}", delivery);
-        delivery.ack(BasicAckOptions::default()).await.expect("ack");
-
-        //restore into string and send for processing
-        let data = String::from_utf8(delivery.data).unwrap();
-        processor(data, global_state.clone());
+pub fn process_transaction(
+    transaction: String,
+    global_state: Arc<GlobalState>,
+) -> Result<(), NetError> {
+    queue_transaction(&transaction, &global_state)
+}
+
+/// process_transaction() for a tx of our own. With gossip nobody sends it back to us, see network::broadcast()
+pub fn queue_transaction(transaction: &str, global_state: &GlobalState) -> Result<(), NetError> {
+    //same cap as for tx submitted to us, and for the same reason as with blocks it's checked before decoding
+    Transaction::check_size(transaction).map_err(|e| NetError::Decode(e.to_string()))?;
+    let tx_object: Transaction =
+        codec::decode_hex(transaction).map_err(|e| NetError::Decode(e.to_string()))?;
+    tracing::debug!(tx = ?tx_object, "decoded tx");
+    //other networks can share a gossip peer or the broker
+    if tx_object.unsigned_tx.chain_id != global_state.blockchain.read().unwrap().chain_id() {
+        tracing::warn!(
+            chain_id = tx_object.unsigned_tx.chain_id,
+            "dropped tx for another chain"
+        );
+        return Ok(());
     }
+    //same as a tx submitted to us - and an error, so gossip doesn't pass it on either
+    validate_pooled_tx(global_state, &tx_object, transaction).map_err(NetError::InvalidTx)?;
+    let tx_hash = tx_object.hash();
+    //wall clock rather than the chain's, it gets compared with other nodes' - and it doesn't need the chain lock
+    global_state
+        .propagation
+        .lock()
+        .unwrap()
+        .record(&tx_hash, SystemClock.now_millis());
+
+    //validation only took a read lock on the chain and this only needs the tx queue's, so incoming tx never wait on
+    // block validation
+    let mut tx_queue = global_state.tx_queue.lock().unwrap();
 
+    //again, another tx from the same sender can have got in since
+    tx_queue.check_nonce(&tx_object)?;
+    tx_queue.add(tx_object.clone());
+    tracing::info!(tx_hash = %tx_hash, "inserted tx into the tx queue");
+    tracing::debug!(queue = ?tx_queue, "tx queue state");
+    drop(tx_queue);
+
+    global_state
+        .events
+        .publish(Event::NewTx(Box::new(tx_object)));
     Ok(())
 }
 
-pub fn process_block(block: String, global_state: Arc<Mutex<GlobalState>>) {
-    let block_object: Block = serde_json::from_str(&block).unwrap();
-    println!("deserialized block: {:?}", block_object);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{gen_address, Account};
+    use crate::config::chain::ChainRules;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::transaction::tx::MAX_TX_BYTES;
+    use crate::util::prep_state;
 
-    let mut guard = global_state.lock().unwrap();
-    let global_state = guard.deref_mut();
-    let tx_queue = &mut global_state.tx_queue;
-    let blockchain = &mut global_state.blockchain;
+    #[test]
+    fn test_oversized_blocks_are_dropped_unparsed() {
+        let global_state = Arc::new(prep_state());
+        let block = " ".repeat(MAX_BLOCK_BYTES + 1);
+        match process_block(block, global_state.clone()) {
+            Err(NetError::Decode(e)) => assert!(e.contains("byte limit")),
+            other => panic!("expected a decode error, got {:?}", other),
+        }
+        assert_eq!(global_state.blockchain.read().unwrap().chain.len(), 1);
+    }
 
-    if blockchain.add_block(block_object.clone(), tx_queue) {
-        println!(
-            "Successfully inserted the new block #{} into the blockchain.",
-            block_object.block_headers.truncated_block_headers.number
+    #[test]
+    fn test_invalid_tx_are_not_queued() {
+        let global_state = Arc::new(prep_state());
+        let sender = Account::new(vec![]);
+        //the sender isn't on chain yet, so the signature is all there is to go on
+        let mut forged = Transaction::create_transaction(
+            Some(sender.clone()),
+            Some(gen_address()),
+            10,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
         );
-    } else {
-        println!(
-            "Failed to insert block #{}",
-            block_object.block_headers.truncated_block_headers.number
+        forged.unsigned_tx.value = 1000.into();
+        //sends nothing to an account with no code
+        let pointless = Transaction::create_transaction(
+            Some(sender),
+            Some(gen_address()),
+            0,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        for (tx, reason) in [(forged, "signature invalid"), (pointless, "pointless")] {
+            match process_transaction(codec::encode_hex(&tx), global_state.clone()) {
+                Err(NetError::InvalidTx(e)) => assert!(e.contains(reason), "{}", e),
+                other => panic!("expected the tx to be rejected, got {:?}", other),
+            }
+        }
+        assert_eq!(
+            global_state.tx_queue.lock().unwrap().get_tx_series().len(),
+            1
         );
     }
-}
 
-pub fn process_transaction(transaction: String, global_state: Arc<Mutex<GlobalState>>) {
-    let tx_object: Transaction = serde_json::from_str(&transaction).unwrap();
-    println!("deserialized tx: {:?}", tx_object);
+    #[test]
+    fn test_oversized_tx_are_dropped_unparsed() {
+        let global_state = Arc::new(prep_state());
+        let tx = " ".repeat(MAX_TX_BYTES + 1);
+        match process_transaction(tx, global_state.clone()) {
+            Err(NetError::Decode(e)) => assert!(e.contains("byte limit")),
+            other => panic!("expected a decode error, got {:?}", other),
+        }
+        //just the miner's account creation from prep_state()
+        assert_eq!(
+            global_state.tx_queue.lock().unwrap().get_tx_series().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_tx_ingestion_does_not_wait_for_chain_lock() {
+        let global_state = Arc::new(prep_state());
+        let tx = Transaction::create_transaction(
+            Some(Account::new(vec![])),
+            None,
+            0,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+
+        //hold the chain lock the whole time, as if a block was being validated - which only takes a read lock,
+        // see GlobalState::import_block()
+        let _chain = global_state.blockchain.read().unwrap();
+        process_transaction(codec::encode_hex(&tx), global_state.clone()).unwrap();
+
+        //the miner's account creation from prep_state() + ours
+        assert_eq!(
+            global_state.tx_queue.lock().unwrap().get_tx_series().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_malformed_messages_are_errors() {
+        let global_state = Arc::new(prep_state());
+        assert!(matches!(
+            process_block("not a block".into(), global_state.clone()),
+            Err(NetError::Decode(_))
+        ));
+        assert!(matches!(
+            process_transaction("{}".into(), global_state),
+            Err(NetError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn test_processed_messages_are_published() {
+        let global_state = Arc::new(prep_state());
+        let mut events = global_state.events.subscribe();
 
-    let mut guard = global_state.lock().unwrap();
-    let global_state = guard.deref_mut();
-    let tx_queue = &mut global_state.tx_queue;
+        let tx = Transaction::create_transaction(
+            Some(Account::new(vec![])),
+            None,
+            0,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        process_transaction(codec::encode_hex(&tx), global_state.clone()).unwrap();
+        assert!(matches!(events.try_recv(), Ok(Event::NewTx(t)) if t.hash() == tx.hash()));
 
-    tx_queue.add(tx_object);
-    println!(
-        "Successfully inserted the tx into global tx queue. Queue state: {:?}",
-        tx_queue
-    );
+        let (last_block, state_root, clock) = {
+            let blockchain = global_state.blockchain.read().unwrap();
+            (
+                blockchain.chain.last().unwrap().clone(),
+                blockchain.state.get_state_root().clone(),
+                blockchain.clock.clone(),
+            )
+        };
+        let block = Block::mine_block(
+            &last_block,
+            global_state.miner_address,
+            vec![tx],
+            &state_root,
+            &*clock,
+            &ChainRules::default(),
+        );
+        process_block(codec::encode_hex(&block), global_state.clone()).unwrap();
+        assert!(matches!(
+            events.try_recv(),
+            Ok(Event::NewBlock { block: b, from_peer: true }) if b.hash() == block.hash()
+        ));
+
+        //a block we reject doesn't make it out
+        process_block(codec::encode_hex(&block), global_state).unwrap();
+        assert!(events.try_recv().is_err());
+    }
 }
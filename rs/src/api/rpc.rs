@@ -0,0 +1,1333 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use actix_web::{post, web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::account::address::Address;
+use crate::api::auth::AdminAuth;
+use crate::api::filters::LogCriteria;
+use crate::api::server::{lookup_tx, validate_submitted_tx};
+use crate::blockchain::block::Block;
+use crate::blockchain::sync::Lifecycle;
+use crate::config::NodeConfig;
+use crate::error::TxError;
+use crate::interpreter::{BlockEnv, EVMRetVal, Interpreter};
+use crate::network::broadcast;
+use crate::store::codec;
+use crate::store::overlay::OverlayState;
+use crate::store::state::StateAccess;
+use crate::transaction::tx::Transaction;
+use crate::util::bigint::{checked_sub, parse_u256, to_hex, U256};
+use crate::util::GlobalState;
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+// standard json-rpc 2.0 error codes - https://www.jsonrpc.org/specification#error_object
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+// implementation defined range, same code geth uses for "filter not found"
+pub const SERVER_ERROR: i64 = -32000;
+
+/// the most calls one eth_callMany runs - they all run under the blockchain's read lock
+pub const MAX_CALL_BUNDLE: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub id: Value,
+    pub method: String,
+    #[serde(default)]
+    #[schema(value_type = Vec<Object>)]
+    pub params: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// exactly one of result / error is set
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    #[schema(value_type = Object)]
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl RpcResponse {
+    pub fn new(id: Value, result: Result<Value, RpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            jsonrpc: JSONRPC_VERSION.into(),
+            id,
+            result,
+            error,
+        }
+    }
+}
+
+/// ethereum style json-rpc. Only a handful of eth_* methods for now, see handle_request() for the list.
+/// eth_sendRawTransaction is the one that needs the auth token, same as /tx/send
+#[utoipa::path(
+    post,
+    path = "/rpc",
+    tag = "rpc",
+    request_body = RpcRequest,
+    responses((status = 200, description = "json-rpc 2.0 response, errors included", body = RpcResponse))
+)]
+#[post("/rpc")]
+pub async fn rpc(
+    auth: Option<AdminAuth>,
+    body: web::Json<RpcRequest>,
+    global_state: web::Data<Arc<GlobalState>>,
+    config: web::Data<NodeConfig>,
+) -> impl Responder {
+    let request = body.into_inner();
+    //broadcasting has to await, so it can't go through handle_request()
+    if request.method == "eth_sendRawTransaction" && request.jsonrpc == JSONRPC_VERSION {
+        let result = match auth {
+            Some(_) => eth_send_raw_transaction(&request.params, &global_state, &config).await,
+            None => Err(RpcError::new(SERVER_ERROR, "missing or invalid auth token")),
+        };
+        return HttpResponse::Ok().json(RpcResponse::new(request.id, result));
+    }
+    HttpResponse::Ok().json(handle_request(request, &global_state, &config))
+}
+
+pub fn handle_request(
+    request: RpcRequest,
+    global_state: &GlobalState,
+    config: &NodeConfig,
+) -> RpcResponse {
+    if request.jsonrpc != JSONRPC_VERSION {
+        return RpcResponse::new(
+            request.id,
+            Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\"")),
+        );
+    }
+    let result = match request.method.as_str() {
+        "eth_chainId" => eth_chain_id(global_state),
+        "eth_blockNumber" => eth_block_number(global_state),
+        "eth_getBalance" => eth_get_balance(&request.params, global_state),
+        "eth_getBlockByNumber" => eth_get_block_by_number(&request.params, global_state),
+        "eth_syncing" => eth_syncing(global_state),
+        "eth_getStorageAt" => eth_get_storage_at(&request.params, global_state),
+        "eth_getTransactionByHash" => eth_get_transaction_by_hash(&request.params, global_state),
+        "eth_call" => eth_call(&request.params, global_state, config),
+        "eth_estimateGas" => eth_estimate_gas(&request.params, global_state, config),
+        "eth_callMany" => eth_call_many(&request.params, global_state, config),
+        "eth_newBlockFilter" => eth_new_block_filter(global_state),
+        "eth_newPendingTransactionFilter" => eth_new_pending_tx_filter(global_state),
+        "eth_newFilter" => eth_new_filter(&request.params, global_state),
+        "eth_getFilterChanges" => eth_get_filter_changes(&request.params, global_state),
+        "eth_uninstallFilter" => eth_uninstall_filter(&request.params, global_state),
+        method => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("method {} not found", method),
+        )),
+    };
+    RpcResponse::new(request.id, result)
+}
+
+/// hex quantity, like every other eth_* number
+fn eth_chain_id(global_state: &GlobalState) -> Result<Value, RpcError> {
+    let chain_id = global_state.blockchain.read().unwrap().chain_id();
+    Ok(Value::String(format!("0x{:x}", chain_id)))
+}
+
+fn eth_block_number(global_state: &GlobalState) -> Result<Value, RpcError> {
+    let height = global_state.blockchain.read().unwrap().chain.len() - 1;
+    Ok(Value::String(format!("0x{:x}", height)))
+}
+
+/// params: [address, block tag (optional, only "latest" / "pending")]. Accounts that don't exist have 0
+fn eth_get_balance(params: &[Value], global_state: &GlobalState) -> Result<Value, RpcError> {
+    let address = parse_address(str_param(params, 0, "address")?)?;
+    check_latest(params, 1)?;
+    let blockchain = global_state.blockchain.read().unwrap();
+    let balance = blockchain.state.get_account_or_empty(address).balance;
+    Ok(Value::String(to_hex(&balance)))
+}
+
+/// params: [block tag, full tx (optional, false by default)]. null if there's no such block.
+/// Transactions are hashes, or objects like eth_getTransactionByHash's when full tx is true.
+/// (!) unlike real ethereum the timestamp is in milliseconds, and nonce is the decimal u128 the proof of work found
+fn eth_get_block_by_number(
+    params: &[Value],
+    global_state: &GlobalState,
+) -> Result<Value, RpcError> {
+    let tag = str_param(params, 0, "block")?;
+    let full_tx = match params.get(1) {
+        Some(full_tx) => full_tx
+            .as_bool()
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "invalid full tx flag"))?,
+        None => false,
+    };
+    let blockchain = global_state.blockchain.read().unwrap();
+    let block = match blockchain
+        .resolve_block_tag(tag)
+        .and_then(|number| blockchain.get_block_by_number(number))
+    {
+        Some(block) => block,
+        None => return Ok(Value::Null),
+    };
+    let headers = &block.block_headers.truncated_block_headers;
+    let quantity = |n: usize| format!("0x{:x}", n);
+    let transactions: Vec<Value> = block
+        .tx_series
+        .iter()
+        .enumerate()
+        .map(|(index, tx)| {
+            if full_tx {
+                Value::Object(tx_object(tx, Some((block, index))))
+            } else {
+                Value::String(tx.hash())
+            }
+        })
+        .collect();
+    let gas_used = blockchain
+        .get_gas_stats(headers.number)
+        .map_or(0, |stats| stats.gas_used);
+    Ok(serde_json::json!({
+        "number": quantity(headers.number),
+        "hash": block.hash(),
+        "parentHash": headers.parent_hash,
+        "miner": headers.beneficiary.to_string(),
+        "difficulty": format!("0x{:x}", headers.difficulty),
+        "timestamp": format!("0x{:x}", headers.timestamp),
+        "transactionsRoot": headers.tx_root,
+        "stateRoot": headers.state_root,
+        "receiptsRoot": headers.receipts_root,
+        "nonce": block.block_headers.nonce.to_string(),
+        "gasUsed": format!("0x{:x}", gas_used),
+        "transactions": transactions,
+    }))
+}
+
+/// false unless the node is catching up with a bootnode, then how far along it is. GET /sync has the eta too
+fn eth_syncing(global_state: &GlobalState) -> Result<Value, RpcError> {
+    let status = global_state.sync_status();
+    if status.state != Lifecycle::Syncing {
+        return Ok(Value::Bool(false));
+    }
+    Ok(serde_json::json!({
+        "startingBlock": format!("0x{:x}", status.starting_block),
+        "currentBlock": format!("0x{:x}", status.current_block),
+        "highestBlock": format!("0x{:x}", status.highest_block),
+    }))
+}
+
+/// params: [address, key, block tag (optional, "latest" by default)]
+/// keys can be decimal (what the STORE opcode writes) or 0x-prefixed hex.
+/// (!) unlike real ethereum the value comes back as the decimal string the interpreter stored, "0" if unset
+fn eth_get_storage_at(params: &[Value], global_state: &GlobalState) -> Result<Value, RpcError> {
+    let address = parse_address(str_param(params, 0, "address")?)?;
+    let key = parse_storage_key(str_param(params, 1, "key")?)?;
+    let tag = match params.get(2) {
+        Some(_) => str_param(params, 2, "block")?,
+        None => "latest",
+    };
+
+    let blockchain = global_state.blockchain.read().unwrap();
+    let block_number = blockchain
+        .resolve_block_tag(tag)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("block {} not found", tag)))?;
+    let value = blockchain
+        .get_storage_at(&address, &key, block_number)
+        .map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))?
+        .unwrap_or_else(|| "0".into());
+    Ok(Value::String(value))
+}
+
+/// params: [tx hash]. null if the tx is neither on chain nor in our queue.
+/// blockNumber / blockHash / transactionIndex are null while it's pending, same as in real ethereum. On top of those,
+/// confirmations and status ("pending", "included" or "finalized") save the client working them out
+fn eth_get_transaction_by_hash(
+    params: &[Value],
+    global_state: &GlobalState,
+) -> Result<Value, RpcError> {
+    let tx_hash = str_param(params, 0, "tx hash")?;
+    let lookup = match lookup_tx(global_state, tx_hash) {
+        Some(lookup) => lookup,
+        None => return Ok(Value::Null),
+    };
+    let quantity = |n: usize| format!("0x{:x}", n);
+    let mut object = tx_object(&lookup.tx, None);
+    object.insert(
+        "blockNumber".into(),
+        serde_json::json!(lookup.block_number.map(quantity)),
+    );
+    object.insert("blockHash".into(), serde_json::json!(lookup.block_hash));
+    object.insert(
+        "transactionIndex".into(),
+        serde_json::json!(lookup.transaction_index.map(quantity)),
+    );
+    object.insert(
+        "confirmations".into(),
+        Value::String(quantity(lookup.confirmations)),
+    );
+    object.insert("status".into(), serde_json::json!(lookup.status));
+    Ok(Value::Object(object))
+}
+
+/// a tx the way the eth_* methods show it. Where it is in the chain, if the block and its index in it are given
+fn tx_object(
+    tx: &Transaction,
+    included: Option<(&Block, usize)>,
+) -> serde_json::Map<String, Value> {
+    let unsigned_tx = &tx.unsigned_tx;
+    let quantity = |n: usize| format!("0x{:x}", n);
+    let mut object = serde_json::Map::new();
+    object.insert("hash".into(), Value::String(tx.hash()));
+    object.insert(
+        "from".into(),
+        serde_json::json!(unsigned_tx.from.map(|from| from.to_string())),
+    );
+    object.insert(
+        "to".into(),
+        serde_json::json!(unsigned_tx.to.map(|to| to.to_string())),
+    );
+    object.insert("value".into(), Value::String(to_hex(&unsigned_tx.value)));
+    object.insert("gas".into(), Value::String(to_hex(&unsigned_tx.gas_limit)));
+    //not in ethereum's, only there for sponsored tx
+    if let Some(fee_payer) = unsigned_tx.fee_payer {
+        object.insert("feePayer".into(), Value::String(fee_payer.to_string()));
+    }
+    if let Some((block, index)) = included {
+        let number = block.block_headers.truncated_block_headers.number;
+        object.insert("blockNumber".into(), Value::String(quantity(number)));
+        object.insert("blockHash".into(), Value::String(block.hash()));
+        object.insert("transactionIndex".into(), Value::String(quantity(index)));
+    }
+    object
+}
+
+/// params: [signed tx]. The tx is hex of its codec::encode() bytes, the way it goes over the wire - same idea as real
+/// ethereum taking RLP. Goes through the same checks as /tx/send and comes back as its hash once it's broadcast
+async fn eth_send_raw_transaction(
+    params: &[Value],
+    global_state: &GlobalState,
+    config: &NodeConfig,
+) -> Result<Value, RpcError> {
+    let raw = str_param(params, 0, "raw tx")?.trim_start_matches("0x");
+    //before decoding, same as with tx from peers
+    Transaction::check_size(raw).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?;
+    let tx: Transaction = codec::decode_hex(raw)
+        .map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid tx: {}", e)))?;
+    //multisig tx carry cosignatures instead, and check_transaction() sees to those
+    if tx.cosignatures.is_empty() {
+        let signature = tx
+            .signature
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "the tx isn't signed"))?;
+        Transaction::from_external_signature(
+            tx.unsigned_tx.clone(),
+            &hex::encode(signature.serialize_compact()),
+        )
+        .map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+    }
+
+    let tx_hash = tx.hash();
+    let str_tx = codec::encode_hex(&tx);
+    validate_submitted_tx(global_state, config, &tx, &str_tx).map_err(|reason| {
+        tracing::warn!(tx_hash = %tx_hash, reason = %reason, "rejected raw tx");
+        RpcError::new(SERVER_ERROR, reason)
+    })?;
+    broadcast(global_state, str_tx, "tx").await.map_err(|e| {
+        tracing::error!(tx_hash = %tx_hash, error = %e, "failed to broadcast tx");
+        RpcError::new(SERVER_ERROR, format!("failed to broadcast tx: {}", e))
+    })?;
+    Ok(Value::String(tx_hash))
+}
+
+/// params: [{to, gas}, block tag (optional, only "latest" / "pending")]
+/// runs the contract's code against the head state, without changing it. null if `to` isn't a contract. The code
+/// stops out of gas once it's used more than `gas` - see call_gas() for when there isn't one.
+/// (!) unlike real ethereum the result is the interpreter's return value as json, eg {"VAL": 15}
+fn eth_call(
+    params: &[Value],
+    global_state: &GlobalState,
+    config: &NodeConfig,
+) -> Result<Value, RpcError> {
+    Ok(match speculative_call(params, global_state, config)? {
+        Some(evm_ret_val) => serde_json::to_value(evm_ret_val.ret_val).unwrap(),
+        None => Value::Null,
+    })
+}
+
+/// params: same as eth_call. Plain transfers don't use any gas here, so it's whatever running the contract's code takes
+fn eth_estimate_gas(
+    params: &[Value],
+    global_state: &GlobalState,
+    config: &NodeConfig,
+) -> Result<Value, RpcError> {
+    let gas_used = speculative_call(params, global_state, config)?.map_or(0, |ret| ret.gas_used);
+    Ok(Value::String(format!("0x{:x}", gas_used)))
+}
+
+/// runs `to`'s code on an overlay of the head state, so whatever it writes to storage is thrown away after
+fn speculative_call(
+    params: &[Value],
+    global_state: &GlobalState,
+    config: &NodeConfig,
+) -> Result<Option<EVMRetVal>, RpcError> {
+    let call = params.first();
+    let to = match call.and_then(|call| call.get("to")) {
+        Some(to) => parse_address(
+            to.as_str()
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, "invalid to"))?,
+        )?,
+        None => return Err(RpcError::new(INVALID_PARAMS, "missing call object or to")),
+    };
+    //there's a call object, or there'd be no "to"
+    let gas = call_gas(call.unwrap(), config)?;
+    check_latest(params, 1)?;
+
+    let blockchain = global_state.blockchain.read().unwrap();
+    let mut state = OverlayState::new(&blockchain.state);
+    let account = state.get_account_or_empty(to);
+    if account.code_hash.is_none() {
+        return Ok(None);
+    }
+    //as if it was going into the next block
+    Interpreter::new()
+        .with_env(blockchain.chain.last().unwrap().next_env(&blockchain.rules))
+        .with_gas_limit(gas)
+        .run_code(account.code, state.storage_trie_mut(to))
+        .map(Some)
+        .map_err(|e| RpcError::new(SERVER_ERROR, format!("execution failed: {}", e)))
+}
+
+/// one of the calls in an eth_callMany bundle
+#[derive(Debug, Clone)]
+struct BundledCall {
+    from: Option<Address>,
+    to: Address,
+    value: U256,
+    gas: u64,
+}
+
+/// params: [[{from, to, value, gas}, ...], block tag (optional, only "latest" / "pending")]
+/// runs the calls one after the other on one overlay of the head state, so each sees what the ones before it did,
+/// and throws the lot away after. from, value and gas are optional - without a from no value moves, gas is the same
+/// as eth_call's. A call that fails leaves nothing behind and the ones after it still run.
+/// For each call: the code's return value (null if `to` isn't a contract), the gas it used, and every balance and
+/// storage slot it changed as {address: {balance: {from, to}, storage: {key: {from, to}}}}. Or just an error.
+/// (!) no gas gets bought, same as eth_call
+fn eth_call_many(
+    params: &[Value],
+    global_state: &GlobalState,
+    config: &NodeConfig,
+) -> Result<Value, RpcError> {
+    let calls = match params.first() {
+        Some(Value::Array(calls)) => calls
+            .iter()
+            .map(|call| parse_bundled_call(call, config))
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => return Err(RpcError::new(INVALID_PARAMS, "missing or invalid calls")),
+    };
+    if calls.len() > MAX_CALL_BUNDLE {
+        return Err(RpcError::new(
+            INVALID_PARAMS,
+            format!("at most {} calls per bundle", MAX_CALL_BUNDLE),
+        ));
+    }
+    check_latest(params, 1)?;
+
+    let blockchain = global_state.blockchain.read().unwrap();
+    //as if they were all going into the next block
+    let env = blockchain.chain.last().unwrap().next_env(&blockchain.rules);
+    let mut state = OverlayState::new(&blockchain.state);
+    let mut results = vec![];
+    for call in calls.iter() {
+        //each call runs on a copy, so one that fails half way doesn't leave writes behind
+        let mut after = state.clone();
+        match run_bundled_call(call, &mut after, env) {
+            Ok(evm_ret_val) => {
+                results.push(serde_json::json!({
+                    "result": evm_ret_val.map(|ret| serde_json::to_value(ret.ret_val).unwrap()),
+                    "gasUsed": format!("0x{:x}", evm_ret_val.map_or(0, |ret| ret.gas_used)),
+                    "stateDiff": state_diff(&mut state, &mut after, call),
+                }));
+                state = after;
+            }
+            Err(e) => results.push(serde_json::json!({ "error": e })),
+        }
+    }
+    Ok(Value::Array(results))
+}
+
+fn parse_bundled_call(call: &Value, config: &NodeConfig) -> Result<BundledCall, RpcError> {
+    let invalid = |name: &str| RpcError::new(INVALID_PARAMS, format!("invalid {}", name));
+    let address = |name: &str| -> Result<Option<Address>, RpcError> {
+        match call.get(name) {
+            Some(address) => {
+                parse_address(address.as_str().ok_or_else(|| invalid(name))?).map(Some)
+            }
+            None => Ok(None),
+        }
+    };
+    let to = address("to")?.ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing to"))?;
+    let from = address("from")?;
+    let value = match call.get("value") {
+        Some(value) => parse_u256(value.as_str().ok_or_else(|| invalid("value"))?)
+            .map_err(|_| invalid("value"))?,
+        None => U256::zero(),
+    };
+    if from.is_none() && !value.is_zero() {
+        return Err(RpcError::new(
+            INVALID_PARAMS,
+            "a call with value needs a from",
+        ));
+    }
+    Ok(BundledCall {
+        from,
+        to,
+        value,
+        gas: call_gas(call, config)?,
+    })
+}
+
+/// a call object's "gas", the most its code may use. Without one it's the most a tx submitted to this node could ask
+/// for, so a call can't do more than the tx it's standing in for
+fn call_gas(call: &Value, config: &NodeConfig) -> Result<u64, RpcError> {
+    match call.get("gas") {
+        Some(gas) => {
+            let gas = gas
+                .as_str()
+                .and_then(|gas| parse_u256(gas).ok())
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, "invalid gas"))?;
+            Ok(std::cmp::min(gas, U256::from(u64::MAX)).low_u64())
+        }
+        None => Ok(config.max_gas_limit),
+    }
+}
+
+//the code runs before the value moves, same as in Transaction::run_standard_tx()
+fn run_bundled_call(
+    call: &BundledCall,
+    state: &mut OverlayState,
+    env: BlockEnv,
+) -> Result<Option<EVMRetVal>, String> {
+    let account = state.get_account_or_empty(call.to);
+    let evm_ret_val = match account.code_hash {
+        Some(_) => Some(
+            Interpreter::new()
+                .with_env(env)
+                .with_gas_limit(call.gas)
+                .run_code(account.code, state.storage_trie_mut(call.to))
+                .map_err(|e| format!("execution failed: {}", e))?,
+        ),
+        None => None,
+    };
+    if let Some(from) = call.from {
+        let mut from_account = state.get_account(from).map_err(|e| e.to_string())?;
+        from_account.balance = checked_sub(from_account.balance, call.value)
+            .map_err(|_| TxError::ExceededBalance.to_string())?;
+        state.put_account(from, from_account);
+        let mut to_account = state.get_account_or_empty(call.to);
+        to_account.balance = to_account.balance.saturating_add(call.value);
+        state.put_account(call.to, to_account);
+    }
+    Ok(evm_ret_val)
+}
+
+/// what a call changed. Only from and to can have been touched
+fn state_diff(before: &mut OverlayState, after: &mut OverlayState, call: &BundledCall) -> Value {
+    let mut diff = serde_json::Map::new();
+    let mut addresses = vec![call.to];
+    addresses.extend(call.from.filter(|from| *from != call.to));
+    for address in addresses {
+        let mut changes = serde_json::Map::new();
+        let (balance_before, balance_after) = (
+            before.get_account_or_empty(address).balance,
+            after.get_account_or_empty(address).balance,
+        );
+        if balance_before != balance_after {
+            changes.insert(
+                "balance".into(),
+                serde_json::json!({"from": to_hex(&balance_before), "to": to_hex(&balance_after)}),
+            );
+        }
+        //only the called code writes storage, and only its own
+        if address == call.to {
+            let storage_before: BTreeMap<String, String> = before
+                .storage_trie_mut(address)
+                .entries()
+                .into_iter()
+                .collect();
+            let storage_after: BTreeMap<String, String> = after
+                .storage_trie_mut(address)
+                .entries()
+                .into_iter()
+                .collect();
+            let mut storage = serde_json::Map::new();
+            for key in storage_before.keys().chain(storage_after.keys()) {
+                let (from, to) = (storage_before.get(key), storage_after.get(key));
+                if from != to {
+                    storage.insert(key.clone(), serde_json::json!({"from": from, "to": to}));
+                }
+            }
+            if !storage.is_empty() {
+                changes.insert("storage".into(), Value::Object(storage));
+            }
+        }
+        if !changes.is_empty() {
+            diff.insert(address.to_string(), Value::Object(changes));
+        }
+    }
+    Value::Object(diff)
+}
+
+fn eth_new_block_filter(global_state: &GlobalState) -> Result<Value, RpcError> {
+    let blockchain = global_state.blockchain.read().unwrap();
+    let id = global_state
+        .filters
+        .lock()
+        .unwrap()
+        .install_block_filter(&blockchain, Instant::now());
+    Ok(Value::String(id))
+}
+
+fn eth_new_pending_tx_filter(global_state: &GlobalState) -> Result<Value, RpcError> {
+    let tx_queue = global_state.tx_queue.lock().unwrap().get_tx_series();
+    let id = global_state
+        .filters
+        .lock()
+        .unwrap()
+        .install_pending_tx_filter(&tx_queue, Instant::now());
+    Ok(Value::String(id))
+}
+
+/// params: [{address, fromBlock, toBlock}], all optional
+fn eth_new_filter(params: &[Value], global_state: &GlobalState) -> Result<Value, RpcError> {
+    let invalid = |name: &str| RpcError::new(INVALID_PARAMS, format!("invalid {}", name));
+    let options = match params.first() {
+        Some(Value::Object(options)) => options.clone(),
+        Some(_) => return Err(invalid("filter options")),
+        None => Default::default(),
+    };
+    let mut criteria = LogCriteria::default();
+    if let Some(address) = options.get("address") {
+        criteria.address = Some(parse_address(
+            address.as_str().ok_or_else(|| invalid("address"))?,
+        )?);
+    }
+    if let Some(from) = options.get("fromBlock") {
+        criteria.from_block =
+            parse_filter_block(from.as_str().ok_or_else(|| invalid("fromBlock"))?)?;
+    }
+    if let Some(to) = options.get("toBlock") {
+        criteria.to_block = parse_filter_block(to.as_str().ok_or_else(|| invalid("toBlock"))?)?;
+    }
+
+    let blockchain = global_state.blockchain.read().unwrap();
+    let id = global_state.filters.lock().unwrap().install_log_filter(
+        criteria,
+        &blockchain,
+        Instant::now(),
+    );
+    Ok(Value::String(id))
+}
+
+/// params: [filter id]
+fn eth_get_filter_changes(params: &[Value], global_state: &GlobalState) -> Result<Value, RpcError> {
+    let id = str_param(params, 0, "filter id")?;
+    //lock order: blockchain -> tx_queue -> filters
+    let blockchain = global_state.blockchain.read().unwrap();
+    let tx_queue = global_state.tx_queue.lock().unwrap().get_tx_series();
+    let changes = global_state
+        .filters
+        .lock()
+        .unwrap()
+        .get_changes(id, &blockchain, &tx_queue, Instant::now())
+        .ok_or_else(|| RpcError::new(SERVER_ERROR, "filter not found"))?;
+    Ok(Value::Array(changes))
+}
+
+/// params: [filter id]
+fn eth_uninstall_filter(params: &[Value], global_state: &GlobalState) -> Result<Value, RpcError> {
+    let id = str_param(params, 0, "filter id")?;
+    let removed = global_state.filters.lock().unwrap().uninstall(id);
+    Ok(Value::Bool(removed))
+}
+
+/// for filter ranges "latest" means no bound, not the current head
+fn parse_filter_block(tag: &str) -> Result<Option<usize>, RpcError> {
+    let invalid = || RpcError::new(INVALID_PARAMS, format!("invalid block {}", tag));
+    match tag {
+        "latest" | "pending" => Ok(None),
+        "earliest" => Ok(Some(0)),
+        _ => match tag.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16)
+                .map(Some)
+                .map_err(|_| invalid()),
+            None => tag.parse::<usize>().map(Some).map_err(|_| invalid()),
+        },
+    }
+}
+
+//calls only ever run against the head state
+fn check_latest(params: &[Value], i: usize) -> Result<(), RpcError> {
+    if params.get(i).is_none() {
+        return Ok(());
+    }
+    match str_param(params, i, "block")? {
+        "latest" | "pending" => Ok(()),
+        tag => Err(RpcError::new(
+            INVALID_PARAMS,
+            format!("only the latest state is available, not {}", tag),
+        )),
+    }
+}
+
+fn str_param<'a>(params: &'a [Value], i: usize, name: &str) -> Result<&'a str, RpcError> {
+    params
+        .get(i)
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("missing or invalid {}", name)))
+}
+
+fn parse_address(address: &str) -> Result<Address, RpcError> {
+    Address::from_str(address)
+        .map_err(|_| RpcError::new(INVALID_PARAMS, format!("invalid address {}", address)))
+}
+
+fn parse_storage_key(key: &str) -> Result<String, RpcError> {
+    let invalid = || RpcError::new(INVALID_PARAMS, format!("invalid key {}", key));
+    match key.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16)
+            .map(|k| k.to_string())
+            .map_err(|_| invalid()),
+        None => key
+            .parse::<i64>()
+            .map(|k| k.to_string())
+            .map_err(|_| invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{gen_address, Account};
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::util::prep_state;
+    use serde_json::json;
+
+    fn request(method: &str, params: Value) -> RpcRequest {
+        serde_json::from_value(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_unknown_method() {
+        let global_state = prep_state();
+        let res = handle_request(
+            request("eth_nope", json!([])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.id, json!(1));
+        assert!(res.result.is_none());
+        assert_eq!(res.error.unwrap().code, METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_chain_id() {
+        let global_state = prep_state();
+        let res = handle_request(
+            request("eth_chainId", json!([])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        //1337
+        assert_eq!(res.result, Some(json!("0x539")));
+    }
+
+    #[test]
+    fn test_syncing() {
+        let global_state = prep_state();
+        let res = handle_request(
+            request("eth_syncing", json!([])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.result, Some(json!(false)));
+
+        global_state.sync.lock().unwrap().start(0, 300, 0);
+        let res = handle_request(
+            request("eth_syncing", json!([])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(
+            res.result,
+            Some(json!({"startingBlock": "0x0", "currentBlock": "0x0", "highestBlock": "0x12c"}))
+        );
+    }
+
+    #[test]
+    fn test_wrong_version() {
+        let global_state = prep_state();
+        let mut req = request("eth_getStorageAt", json!([]));
+        req.jsonrpc = "1.0".into();
+        let res = handle_request(req, &global_state, &NodeConfig::default());
+        assert_eq!(res.error.unwrap().code, INVALID_REQUEST);
+    }
+
+    #[test]
+    fn test_get_storage_at() {
+        let global_state = prep_state();
+        let address = global_state.miner_address;
+        {
+            let mut blockchain = global_state.blockchain.write().unwrap();
+            let mut trie = crate::store::trie::Trie::new();
+            trie.put("16".into(), "42".into());
+            blockchain.storage_history.insert(address, vec![(0, trie)]);
+        }
+
+        //hex key, default block
+        let res = handle_request(
+            request("eth_getStorageAt", json!([address.to_string(), "0x10"])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.result.unwrap(), json!("42"));
+
+        //unset slot
+        let res = handle_request(
+            request(
+                "eth_getStorageAt",
+                json!([address.to_string(), "1", "earliest"]),
+            ),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.result.unwrap(), json!("0"));
+
+        //block that doesn't exist yet
+        let res = handle_request(
+            request("eth_getStorageAt", json!([address.to_string(), "1", "0x5"])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.error.unwrap().code, INVALID_PARAMS);
+
+        //block the node no longer keeps storage for
+        global_state
+            .blockchain
+            .write()
+            .unwrap()
+            .storage_history_from = 1;
+        let res = handle_request(
+            request(
+                "eth_getStorageAt",
+                json!([address.to_string(), "0x10", "earliest"]),
+            ),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        let error = res.error.unwrap();
+        assert_eq!(error.code, SERVER_ERROR);
+        assert!(error.message.contains("pruned"));
+    }
+
+    #[test]
+    fn test_block_number_balance_and_blocks() {
+        let global_state = prep_state();
+        let call = |method: &str, params: Value| {
+            handle_request(
+                request(method, params),
+                &global_state,
+                &NodeConfig::default(),
+            )
+        };
+        assert_eq!(
+            call("eth_blockNumber", json!([])).result,
+            Some(json!("0x0"))
+        );
+
+        let tx_series = global_state.tx_queue.lock().unwrap().get_tx_series();
+        let tx_hash = tx_series[0].hash();
+        {
+            let mut blockchain = global_state.blockchain.write().unwrap();
+            let block = Block::mine_block(
+                blockchain.chain.last().unwrap(),
+                global_state.miner_address,
+                tx_series,
+                &blockchain.state.get_state_root().clone(),
+                &*blockchain.clock,
+                &blockchain.rules,
+            );
+            blockchain.add_block(block).unwrap();
+        }
+        assert_eq!(
+            call("eth_blockNumber", json!([])).result,
+            Some(json!("0x1"))
+        );
+
+        let (block_hash, balance) = {
+            let blockchain = global_state.blockchain.read().unwrap();
+            (
+                blockchain.chain[1].hash(),
+                blockchain
+                    .state
+                    .get_account_or_empty(global_state.miner_address)
+                    .balance,
+            )
+        };
+        let miner = global_state.miner_address.to_string();
+        assert_eq!(
+            call("eth_getBalance", json!([miner, "latest"])).result,
+            Some(json!(to_hex(&balance)))
+        );
+        assert_eq!(
+            call("eth_getBalance", json!([gen_address().to_string()])).result,
+            Some(json!("0x0"))
+        );
+        let res = call("eth_getBalance", json!([miner, "earliest"]));
+        assert_eq!(res.error.unwrap().code, INVALID_PARAMS);
+
+        let block = call("eth_getBlockByNumber", json!(["latest"]))
+            .result
+            .unwrap();
+        assert_eq!(block["number"], json!("0x1"));
+        assert_eq!(block["hash"], json!(block_hash));
+        assert_eq!(block["transactions"][0], json!(tx_hash));
+        let block = call("eth_getBlockByNumber", json!(["0x1", true]))
+            .result
+            .unwrap();
+        assert_eq!(block["transactions"][0]["hash"], json!(tx_hash));
+        assert_eq!(block["transactions"][0]["blockHash"], json!(block_hash));
+        assert_eq!(
+            call("eth_getBlockByNumber", json!(["0x5"])).result,
+            Some(Value::Null)
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_send_raw_transaction() {
+        let global_state = prep_state();
+        let config = NodeConfig {
+            max_gas_limit: 1,
+            ..NodeConfig::default()
+        };
+        let send = |raw: String| {
+            let (global_state, config) = (&global_state, &config);
+            async move {
+                eth_send_raw_transaction(&[json!(raw)], global_state, config)
+                    .await
+                    .unwrap_err()
+            }
+        };
+        assert_eq!(send("nope".into()).await.code, INVALID_PARAMS);
+        assert_eq!(send(hex::encode("nope")).await.code, INVALID_PARAMS);
+
+        let mut tx = Transaction::create_transaction(
+            Some(Account::new(vec![])),
+            Some(gen_address()),
+            0,
+            None,
+            10,
+            DEFAULT_CHAIN_ID,
+        );
+        let raw = |tx: &Transaction| format!("0x{}", codec::encode_hex(tx));
+        //signed, so it gets as far as the node's own limits
+        let err = send(raw(&tx)).await;
+        assert_eq!(err.code, SERVER_ERROR);
+        assert!(err.message.contains("above this node's maximum"));
+
+        tx.signature = None;
+        assert_eq!(
+            send(raw(&tx)).await,
+            RpcError::new(INVALID_PARAMS, "the tx isn't signed")
+        );
+    }
+
+    #[test]
+    fn test_get_transaction_by_hash() {
+        let global_state = prep_state();
+        let tx_series = global_state.tx_queue.lock().unwrap().get_tx_series();
+        let tx_hash = tx_series[0].hash();
+        let lookup = |tx_hash: &str| {
+            handle_request(
+                request("eth_getTransactionByHash", json!([tx_hash])),
+                &global_state,
+                &NodeConfig::default(),
+            )
+            .result
+            .unwrap()
+        };
+
+        let pending = lookup(&tx_hash);
+        assert_eq!(pending["status"], json!("pending"));
+        assert_eq!(pending["blockNumber"], Value::Null);
+        assert_eq!(pending["confirmations"], json!("0x0"));
+        assert_eq!(lookup("unknown"), Value::Null);
+
+        {
+            let mut blockchain = global_state.blockchain.write().unwrap();
+            let block = Block::mine_block(
+                blockchain.chain.last().unwrap(),
+                global_state.miner_address,
+                tx_series,
+                &blockchain.state.get_state_root().clone(),
+                &*blockchain.clock,
+                &blockchain.rules,
+            );
+            blockchain.add_block(block).unwrap();
+        }
+        let included = lookup(&tx_hash);
+        assert_eq!(included["status"], json!("included"));
+        assert_eq!(included["blockNumber"], json!("0x1"));
+        assert_eq!(
+            included["blockHash"],
+            json!(global_state.blockchain.read().unwrap().chain[1].hash())
+        );
+        assert_eq!(included["transactionIndex"], json!("0x0"));
+        assert_eq!(included["confirmations"], json!("0x1"));
+    }
+
+    #[test]
+    fn test_call_and_estimate_gas_leave_storage_alone() {
+        use crate::interpreter::OPCODE;
+        let global_state = prep_state();
+        //stores 7 at key 1, then reads it back
+        let code = vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(7),
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::STORE,
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::LOAD,
+            OPCODE::STOP,
+        ];
+        let contract = Account::new(code.clone()).public_account;
+        let address = contract.address;
+        let state_root = {
+            let mut blockchain = global_state.blockchain.write().unwrap();
+            blockchain.state.put_account(address, contract);
+            blockchain.state.get_state_root().clone()
+        };
+
+        let res = handle_request(
+            request("eth_call", json!([{"to": address.to_string()}, "latest"])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.result, Some(json!({"VAL": 7})));
+
+        let expected_gas = Interpreter::new()
+            .run_code(code, &mut crate::store::trie::Trie::new())
+            .unwrap()
+            .gas_used;
+        let res = handle_request(
+            request("eth_estimateGas", json!([{"to": address.to_string()}])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.result, Some(json!(format!("0x{:x}", expected_gas))));
+        //one short of what it takes
+        let res = handle_request(
+            request(
+                "eth_call",
+                json!([{"to": address.to_string(), "gas": format!("0x{:x}", expected_gas - 1)}]),
+            ),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(
+            res.error.unwrap().message,
+            format!(
+                "execution failed: out of gas, the code only had {}",
+                expected_gas - 1
+            )
+        );
+        //without a gas it gets what a submitted tx could ask for at most
+        let config = NodeConfig {
+            max_gas_limit: expected_gas - 1,
+            ..NodeConfig::default()
+        };
+        let res = handle_request(
+            request("eth_call", json!([{"to": address.to_string()}])),
+            &global_state,
+            &config,
+        );
+        assert!(res.error.unwrap().message.contains("out of gas"));
+
+        let blockchain = global_state.blockchain.read().unwrap();
+        assert_eq!(blockchain.state.get_state_root(), &state_root);
+        assert!(blockchain
+            .state
+            .storage_trie_map
+            .get(&address)
+            .unwrap()
+            .get("1".into())
+            .is_none());
+        drop(blockchain);
+
+        //not a contract
+        let res = handle_request(
+            request(
+                "eth_call",
+                json!([{"to": global_state.miner_address.to_string()}]),
+            ),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.result, Some(Value::Null));
+        let res = handle_request(
+            request("eth_call", json!([{"to": address.to_string()}, "earliest"])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.error.unwrap().code, INVALID_PARAMS);
+        let res = handle_request(
+            request("eth_estimateGas", json!([{}])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.error.unwrap().code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_call_many() {
+        use crate::interpreter::OPCODE;
+        let global_state = prep_state();
+        //stores 7 at key 1 and returns it
+        let code = vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(7),
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::STORE,
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::LOAD,
+            OPCODE::STOP,
+        ];
+        let contract = Account::new(code).public_account;
+        let address = contract.address;
+        let sender = Account::new(vec![]).public_account.address;
+        let state_root = {
+            let mut blockchain = global_state.blockchain.write().unwrap();
+            blockchain.state.put_account(address, contract);
+            blockchain.state.allocate(sender, 100);
+            blockchain.state.get_state_root().clone()
+        };
+
+        let call = |value: &str| json!({"from": sender.to_string(), "to": address.to_string(), "value": value});
+        let res = handle_request(
+            request(
+                "eth_callMany",
+                json!([[call("0x10"), {"to": address.to_string()}, call("1000"), call("0x54")]]),
+            ),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        let results = res.result.unwrap();
+        assert_eq!(results[0]["result"], json!({"VAL": 7}));
+        assert_eq!(
+            results[0]["stateDiff"],
+            json!({
+                address.to_string(): {
+                    "balance": {"from": "0x0", "to": "0x10"},
+                    "storage": {"1": {"from": null, "to": "7"}},
+                },
+                sender.to_string(): {"balance": {"from": "0x64", "to": "0x54"}},
+            })
+        );
+        //sees the first call's write, so it changes nothing
+        assert_eq!(results[1]["result"], json!({"VAL": 7}));
+        assert_eq!(results[1]["stateDiff"], json!({}));
+        assert_eq!(results[2], json!({"error": "exceeded balance"}));
+        //the failed call took nothing, so what's left can still go
+        assert_eq!(
+            results[3]["stateDiff"][sender.to_string()]["balance"]["to"],
+            json!("0x0")
+        );
+        let res = handle_request(
+            request(
+                "eth_callMany",
+                json!([[{"to": address.to_string(), "gas": "0x1"}]]),
+            ),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(
+            res.result.unwrap()[0],
+            json!({"error": "execution failed: out of gas, the code only had 1"})
+        );
+
+        //none of it reached the chain's state
+        let blockchain = global_state.blockchain.read().unwrap();
+        assert_eq!(blockchain.state.get_state_root(), &state_root);
+        drop(blockchain);
+
+        for params in [
+            json!([{"to": address.to_string()}]),
+            json!([[{"to": address.to_string(), "value": "1"}]]),
+            json!([[{"to": address.to_string(), "gas": 5}]]),
+            json!([[{"to": address.to_string()}], "earliest"]),
+            json!([vec![
+                json!({"to": address.to_string()});
+                MAX_CALL_BUNDLE + 1
+            ]]),
+        ] {
+            let res = handle_request(
+                request("eth_callMany", params),
+                &global_state,
+                &NodeConfig::default(),
+            );
+            assert_eq!(res.error.unwrap().code, INVALID_PARAMS);
+        }
+    }
+
+    #[test]
+    fn test_get_storage_at_bad_params() {
+        let global_state = prep_state();
+        let res = handle_request(
+            request("eth_getStorageAt", json!(["nonsense", "1"])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.error.unwrap().code, INVALID_PARAMS);
+        let res = handle_request(
+            request("eth_getStorageAt", json!([])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.error.unwrap().code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_block_filter_lifecycle() {
+        let global_state = prep_state();
+        let res = handle_request(
+            request("eth_newBlockFilter", json!([])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        let id = res.result.unwrap();
+
+        //mine the account creation tx prep_state() queued up
+        {
+            let mut blockchain = global_state.blockchain.write().unwrap();
+            let last_block = blockchain.chain.last().unwrap().clone();
+            let state_root = blockchain.state.get_state_root().clone();
+            let beneficiary = global_state.miner_address;
+            let tx_series = global_state.tx_queue.lock().unwrap().get_tx_series();
+            let block = Block::mine_block(
+                &last_block,
+                beneficiary,
+                tx_series,
+                &state_root,
+                &*blockchain.clock,
+                &blockchain.rules,
+            );
+            blockchain.add_block(block).unwrap();
+        }
+
+        let res = handle_request(
+            request("eth_getFilterChanges", json!([id.clone()])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.result.unwrap().as_array().unwrap().len(), 1);
+
+        let res = handle_request(
+            request("eth_uninstallFilter", json!([id.clone()])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.result.unwrap(), json!(true));
+        let res = handle_request(
+            request("eth_getFilterChanges", json!([id])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.error.unwrap().code, SERVER_ERROR);
+    }
+
+    #[test]
+    fn test_pending_tx_filter() {
+        let global_state = prep_state();
+        let res = handle_request(
+            request("eth_newPendingTransactionFilter", json!([])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        let id = res.result.unwrap();
+
+        let tx = Transaction::create_transaction(
+            Some(Account::new(vec![])),
+            None,
+            0,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        global_state.tx_queue.lock().unwrap().add(tx.clone());
+
+        let res = handle_request(
+            request("eth_getFilterChanges", json!([id])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.result.unwrap(), json!([tx.hash()]));
+    }
+
+    #[test]
+    fn test_new_filter_params() {
+        let global_state = prep_state();
+        let address = global_state.miner_address;
+        let res = handle_request(
+            request(
+                "eth_newFilter",
+                json!([{"address": address.to_string(), "fromBlock": "0x1", "toBlock": "latest"}]),
+            ),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert!(res.result.unwrap().is_string());
+
+        let res = handle_request(
+            request("eth_newFilter", json!([{"fromBlock": "soon"}])),
+            &global_state,
+            &NodeConfig::default(),
+        );
+        assert_eq!(res.error.unwrap().code, INVALID_PARAMS);
+    }
+}
@@ -1,338 +1,5492 @@
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::time::Duration;
 
 use actix_web::dev::Server;
-use actix_web::{get, post, web, App, HttpResponse, HttpServer, Responder};
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, web, App, HttpResponse, HttpServer, Responder};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-use crate::account::Account;
-use crate::api::pubsub::rabbit_publish;
-use crate::blockchain::block::Block;
+use crate::account::address::Address;
+use crate::account::address_book::{self, AddressInput, NamedAddress};
+use crate::account::multisig::MultisigConfig;
+use crate::account::{message, Account};
+use crate::api::auth::AdminAuth;
+use crate::api::cors::build_cors;
+use crate::api::middleware::trace_request;
+use crate::api::miner::StartMinerRequest;
+use crate::api::openapi::{get_docs, get_openapi};
+use crate::api::rpc::rpc;
+use crate::api::tls::load_rustls_config;
+use crate::api::webhooks::{WebhookEvent, WebhookFilter};
+use crate::blockchain::block::{Block, BlockHeaders};
+use crate::blockchain::blockchain::{Blockchain, FINALITY_CONFIRMATIONS};
+use crate::blockchain::consensus::{ConsensusEngine, ProofOfWork};
+use crate::blockchain::gas_stats::BlockGasStats;
+use crate::blockchain::snapshot::Snapshot;
+use crate::blockchain::sync::Lifecycle;
+use crate::config::NodeConfig;
+use crate::error::{MineError, NetError, StoreError, TxError};
+use crate::events::{Event, MinerStatus};
+use crate::network::propagation::{self, FirstSeen};
+use crate::network::{broadcast, peer_count};
+use crate::store::codec;
+use crate::store::trie::Trie;
+use crate::telemetry::metrics;
 
+use crate::interpreter::token;
 use crate::interpreter::OPCODE;
-use crate::transaction::tx::Transaction;
+use crate::transaction::activity::Activity;
+use crate::transaction::tx::{Transaction, TxType, UnsignedTx};
+use crate::transaction::tx_queue::TxStatus;
 
+use crate::util::bigint::U256;
 use crate::util::GlobalState;
-use secp256k1::PublicKey;
 use std::collections::HashMap;
 
-use std::ops::{Deref, DerefMut};
-use std::str::FromStr;
+use std::ops::Deref;
 
-pub fn run_server(addr: &str, global_state: Arc<Mutex<GlobalState>>) -> std::io::Result<Server> {
+pub fn run_server(config: &NodeConfig, global_state: Arc<GlobalState>) -> std::io::Result<Server> {
     let global_state = web::Data::new(global_state);
+    let node_config = web::Data::new(config.clone());
 
     let server = HttpServer::new(move || {
         App::new()
+            .wrap(build_cors(&node_config))
+            //registered last so it's the outermost layer and sees every request, even ones cors rejects
+            .wrap_fn(trace_request)
             .service(get_blockchain)
+            .service(get_block)
+            .service(get_tx_proof)
+            .service(get_receipt)
+            .service(get_transaction)
+            .service(get_stats)
+            .service(get_latest_blocks)
+            .service(get_contract_gas)
+            .service(get_block_range)
+            .service(get_address_txs)
+            .service(get_address_history)
             .service(mine)
+            .service(get_pending_block)
+            .service(get_work)
+            .service(submit_work)
+            .service(start_miner)
+            .service(stop_miner)
             .service(transact)
+            .service(faucet)
+            .service(propose_multisig_tx)
+            .service(cosign_multisig_tx)
+            .service(submit_multisig_tx)
+            .service(prepare_tx)
+            .service(send_signed_tx)
+            .service(sponsor_tx)
             .service(get_balance)
+            .service(get_account_proof)
+            .service(get_accounts)
+            .service(create_account)
+            .service(unlock_account)
+            .service(sign_message)
+            .service(verify_message)
             .service(get_state)
             .service(get_storage_trie)
+            .service(get_storage_at)
+            .service(get_token_balance)
+            .service(get_token_supply)
+            .service(get_gas_profile)
+            .service(get_snapshot)
+            .service(rpc)
+            .service(get_node_info)
+            .service(get_consensus_clock)
+            .service(get_sync_status)
+            .service(get_health)
+            .service(get_metrics)
+            .service(get_propagation)
+            .service(get_propagation_report)
+            .service(register_webhook)
+            .service(get_webhooks)
+            .service(delete_webhook)
+            .service(get_address_book)
+            .service(set_address_book_entry)
+            .service(delete_address_book_entry)
+            .service(get_openapi)
+            .service(get_docs)
             .app_data(global_state.clone())
-    })
-    .bind(addr)?
+            .app_data(node_config.clone())
+    });
+
+    let server = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => {
+            server.bind_rustls(config.bind_addr(), load_rustls_config(cert, key)?)?
+        }
+        _ => server.bind(config.bind_addr())?,
+    }
     .run();
     Ok(server)
 }
 
+#[utoipa::path(
+    get,
+    path = "/blockchain",
+    tag = "chain",
+    responses((status = 200, description = "the entire chain, genesis first", body = [Object]))
+)]
 #[get("/blockchain")]
-pub async fn get_blockchain(global_state: web::Data<Arc<Mutex<GlobalState>>>) -> impl Responder {
-    let guard = global_state.lock().unwrap();
-    let global_state = guard.deref();
-    let blockchain = &global_state.blockchain;
-    HttpResponse::Ok().json(&blockchain.chain)
+pub async fn get_blockchain(global_state: web::Data<Arc<GlobalState>>) -> impl Responder {
+    //only hold the lock for as long as it takes to copy the Arcs, serialization happens after it's released
+    let chain = global_state.blockchain.read().unwrap().chain.clone();
+    HttpResponse::Ok().json(&chain)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockQuery {
+    pub full_tx: Option<bool>,
+}
+
+/// either the full transactions or just their hashes (same hashes used as keys in the tx trie)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum BlockTxSeries {
+    #[schema(value_type = Vec<Object>)]
+    Full(Vec<Transaction>),
+    Hashes(Vec<String>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BlockResponse {
+    pub hash: String,
+    #[schema(value_type = Object)]
+    pub block_headers: BlockHeaders,
+    pub tx_series: BlockTxSeries,
+    /// null for genesis
+    pub gas_stats: Option<BlockGasStats>,
+}
+
+impl BlockResponse {
+    pub fn from_block(block: &Block, full_tx: bool, gas_stats: Option<BlockGasStats>) -> Self {
+        let tx_series = if full_tx {
+            BlockTxSeries::Full(block.tx_series.clone())
+        } else {
+            BlockTxSeries::Hashes(block.tx_series.iter().map(Transaction::hash).collect())
+        };
+        Self {
+            hash: block.hash(),
+            block_headers: block.block_headers.clone(),
+            tx_series,
+            gas_stats,
+        }
+    }
+}
+
+/// accepts "latest", a block number or a block hash. Pass ?full_tx=true to get full transactions instead of hashes
+#[utoipa::path(
+    get,
+    path = "/block/{number_or_hash}",
+    tag = "chain",
+    params(
+        ("number_or_hash" = String, Path, description = "\"latest\", a block number or a block hash"),
+        ("full_tx" = Option<bool>, Query, description = "return full transactions instead of their hashes"),
+    ),
+    responses(
+        (status = 200, description = "the block", body = BlockResponse),
+        (status = 404, description = "no such block"),
+    )
+)]
+#[get("/block/{number_or_hash}")]
+pub async fn get_block(
+    number_or_hash: web::Path<String>,
+    query: web::Query<BlockQuery>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let number_or_hash = number_or_hash.into_inner();
+    let block = {
+        let blockchain = global_state.blockchain.read().unwrap();
+        let block = if number_or_hash == "latest" {
+            blockchain.chain.last().cloned()
+        } else if let Ok(number) = number_or_hash.parse::<usize>() {
+            blockchain.get_block_by_number(number).cloned()
+        } else {
+            blockchain.get_block_by_hash(&number_or_hash).cloned()
+        };
+        block.map(|block| {
+            let number = block.block_headers.truncated_block_headers.number;
+            let gas_stats = blockchain.get_gas_stats(number).cloned();
+            (block, gas_stats)
+        })
+    };
+
+    match block {
+        Some((block, gas_stats)) => HttpResponse::Ok().json(BlockResponse::from_block(
+            &block,
+            query.full_tx.unwrap_or(false),
+            gas_stats,
+        )),
+        None => HttpResponse::NotFound().body(format!("block {} not found.", number_or_hash)),
+    }
+}
+
+/// merkle proof that a tx is part of a block. Verify with Trie::verify_proof(tx_root, tx_hash, proof) -
+/// the value it returns is the tx as codec::canonical() encodes it, hex, and its hash should match tx_hash
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TxProof {
+    pub block_number: usize,
+    pub tx_root: String,
+    pub tx_hash: String,
+    //rlp encoded nodes, hex, root first - see Trie::get_proof()
+    pub proof: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/block/{number}/tx_proof/{tx_hash}",
+    tag = "chain",
+    params(
+        ("number" = usize, Path, description = "block number"),
+        ("tx_hash" = String, Path, description = "hash of a tx included in that block"),
+    ),
+    responses(
+        (status = 200, description = "proof of inclusion against the block's tx_root", body = TxProof),
+        (status = 404, description = "no such block, or the tx isn't in it"),
+    )
+)]
+#[get("/block/{number}/tx_proof/{tx_hash}")]
+pub async fn get_tx_proof(
+    path: web::Path<(usize, String)>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let (block_number, tx_hash) = path.into_inner();
+    let block = global_state
+        .blockchain
+        .read()
+        .unwrap()
+        .get_block_by_number(block_number)
+        .cloned();
+    let block = match block {
+        Some(block) => block,
+        None => return HttpResponse::NotFound().body(format!("block {} not found.", block_number)),
+    };
+
+    //blocks only carry the tx root, so rebuild the trie from the block's tx - same as block validation does
+    let tx_trie = Trie::build_trie(block.tx_series.clone());
+    match tx_trie.get_proof(&tx_hash) {
+        Some(proof) => HttpResponse::Ok().json(TxProof {
+            block_number,
+            tx_root: block.block_headers.truncated_block_headers.tx_root.clone(),
+            tx_hash,
+            proof,
+        }),
+        None => HttpResponse::NotFound().body(format!(
+            "tx {} not found in block {}.",
+            tx_hash, block_number
+        )),
+    }
+}
+
+/// caps ?n on /blocks/latest so one request can't make us serialize the whole chain
+pub const MAX_LATEST_BLOCKS: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ChainStats {
+    /// number of the head block
+    pub height: usize,
+    pub total_txs: usize,
+    /// in ms, null until the first block on top of genesis
+    pub average_block_time: Option<i64>,
+    /// difficulty of the head block
+    pub difficulty: i64,
+    pub total_gas_used: u64,
+    /// the last STATS_GAS_BLOCKS blocks (genesis excluded), newest first
+    pub recent_gas_stats: Vec<BlockGasStats>,
+}
+
+/// how many blocks' gas stats /stats includes
+pub const STATS_GAS_BLOCKS: usize = 10;
+
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "explorer",
+    responses((status = 200, description = "chain summary", body = ChainStats))
+)]
+#[get("/stats")]
+pub async fn get_stats(global_state: web::Data<Arc<GlobalState>>) -> impl Responder {
+    let blockchain = global_state.blockchain.read().unwrap();
+    let head = &blockchain
+        .chain
+        .last()
+        .unwrap()
+        .block_headers
+        .truncated_block_headers;
+    let recent_gas_stats = (0..=head.number)
+        .rev()
+        .take(STATS_GAS_BLOCKS)
+        .filter_map(|number| blockchain.get_gas_stats(number).cloned())
+        .collect();
+    HttpResponse::Ok().json(ChainStats {
+        height: head.number,
+        total_txs: blockchain.get_total_tx_count(),
+        average_block_time: blockchain.get_average_block_time(),
+        difficulty: head.difficulty,
+        total_gas_used: blockchain.get_total_gas_used(),
+        recent_gas_stats,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatestBlocksQuery {
+    pub n: Option<usize>,
+}
+
+/// newest first, tx as hashes
+#[utoipa::path(
+    get,
+    path = "/blocks/latest",
+    tag = "explorer",
+    params(("n" = Option<usize>, Query, description = "how many blocks, default 10, max 100")),
+    responses((status = 200, description = "the latest blocks, newest first", body = [BlockResponse]))
+)]
+#[get("/blocks/latest")]
+pub async fn get_latest_blocks(
+    query: web::Query<LatestBlocksQuery>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let n = query.n.unwrap_or(10).min(MAX_LATEST_BLOCKS);
+    let blocks: Vec<(Arc<Block>, Option<BlockGasStats>)> = {
+        let blockchain = global_state.blockchain.read().unwrap();
+        blockchain
+            .chain
+            .iter()
+            .rev()
+            .take(n)
+            .map(|b| {
+                let number = b.block_headers.truncated_block_headers.number;
+                (b.clone(), blockchain.get_gas_stats(number).cloned())
+            })
+            .collect()
+    };
+
+    let blocks: Vec<BlockResponse> = blocks
+        .into_iter()
+        .map(|(b, gas_stats)| BlockResponse::from_block(&b, false, gas_stats))
+        .collect();
+    HttpResponse::Ok().json(&blocks)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractGasQuery {
+    pub n: Option<usize>,
+    /// "total" (the default) or "average"
+    pub by: Option<String>,
+}
+
+/// one contract's line in /stats/contracts
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ContractGasRanking {
+    pub address: String,
+    pub calls: u64,
+    pub gas_used: u64,
+    /// gas_used / calls, rounded down
+    pub average_gas: u64,
+    /// in opcodes - the other half of what a leaner contract saves on
+    pub code_size: usize,
+}
+
+/// the contracts that have used the most gas over the whole chain. ?by=average ranks by gas per call instead, for
+/// comparing contracts that do the same job with different code
+#[utoipa::path(
+    get,
+    path = "/stats/contracts",
+    tag = "explorer",
+    params(
+        ("n" = Option<usize>, Query, description = "how many contracts, default 10, max 100"),
+        ("by" = Option<String>, Query, description = "total (default) or average"),
+    ),
+    responses(
+        (status = 200, description = "contracts by gas used, most first", body = [ContractGasRanking]),
+        (status = 400, description = "by is neither total nor average"),
+    )
+)]
+#[get("/stats/contracts")]
+pub async fn get_contract_gas(
+    query: web::Query<ContractGasQuery>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let by_average = match query.by.as_deref() {
+        None | Some("total") => false,
+        Some("average") => true,
+        Some(by) => {
+            return HttpResponse::BadRequest()
+                .body(format!("invalid by: {} (expected total or average)", by))
+        }
+    };
+    let n = query.n.unwrap_or(10).min(MAX_LATEST_BLOCKS);
+    let blockchain = global_state.blockchain.read().unwrap();
+    let mut ranking: Vec<ContractGasRanking> = blockchain
+        .contract_gas()
+        .into_iter()
+        .map(|totals| {
+            let account = blockchain.state.get_account_or_empty(totals.address);
+            ContractGasRanking {
+                address: totals.address.to_string(),
+                calls: totals.calls,
+                gas_used: totals.gas_used,
+                average_gas: totals.gas_used / totals.calls.max(1),
+                code_size: account.code.len(),
+            }
+        })
+        .collect();
+    if by_average {
+        //stable, so a tie keeps the order contract_gas() gave it
+        ranking.sort_by_key(|gas| std::cmp::Reverse(gas.average_gas));
+    }
+    ranking.truncate(n);
+    HttpResponse::Ok().json(ranking)
+}
+
+/// caps ?count on /blocks/range - also the page size replace_chain() syncs with
+pub const MAX_BLOCK_RANGE: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockRangeQuery {
+    pub from: usize,
+    pub count: Option<usize>,
+}
+
+/// full blocks in the same format as /blockchain, oldest first - what peers sync from a page at a time
+#[utoipa::path(
+    get,
+    path = "/blocks/range",
+    tag = "chain",
+    params(
+        ("from" = usize, Query, description = "number of the first block"),
+        ("count" = Option<usize>, Query, description = "how many blocks, default and max 100"),
+    ),
+    responses((status = 200, description = "up to count blocks starting at from, oldest first. Empty past the head", body = [Object]))
+)]
+#[get("/blocks/range")]
+pub async fn get_block_range(
+    query: web::Query<BlockRangeQuery>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let count = query.count.unwrap_or(MAX_BLOCK_RANGE).min(MAX_BLOCK_RANGE);
+    let blocks: Vec<Arc<Block>> = global_state
+        .blockchain
+        .read()
+        .unwrap()
+        .chain
+        .iter()
+        .skip(query.from)
+        .take(count)
+        .cloned()
+        .collect();
+    HttpResponse::Ok().json(&blocks)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AddressTx {
+    pub hash: String,
+    pub block_number: usize,
+    #[schema(value_type = Object)]
+    pub tx: Transaction,
+}
+
+/// mined tx the address sent, received or was created by. Newest first
+#[utoipa::path(
+    get,
+    path = "/address/{address}/txs",
+    tag = "explorer",
+    params(("address" = String, Path, description = "0x-prefixed address or address book name")),
+    responses(
+        (status = 200, description = "the address's tx, newest first", body = [AddressTx]),
+        (status = 400, description = "not a valid address"),
+    )
+)]
+#[get("/address/{address}/txs")]
+pub async fn get_address_txs(
+    address: web::Path<String>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let address = match global_state.resolve_address(address.as_str()) {
+        Ok(address) => address,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+
+    let txs: Vec<AddressTx> = global_state
+        .blockchain
+        .read()
+        .unwrap()
+        .get_txs_for_address(&address)
+        .into_iter()
+        .rev()
+        .map(|(block_number, tx)| AddressTx {
+            hash: tx.hash(),
+            block_number,
+            tx: tx.clone(),
+        })
+        .collect();
+    HttpResponse::Ok().json(&txs)
+}
+
+/// what mined tx did to the address - sent, received or created it. Newest first.
+/// Served from an index kept up to date as blocks get added, unlike /address/{address}/txs which scans the chain
+#[utoipa::path(
+    get,
+    path = "/address/{address}/history",
+    tag = "explorer",
+    params(("address" = String, Path, description = "0x-prefixed address or address book name")),
+    responses(
+        (status = 200, description = "the address's activity, newest first", body = [Activity]),
+        (status = 400, description = "not a valid address"),
+    )
+)]
+#[get("/address/{address}/history")]
+pub async fn get_address_history(
+    address: web::Path<String>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let address = match global_state.resolve_address(address.as_str()) {
+        Ok(address) => address,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+
+    let history: Vec<Activity> = global_state
+        .blockchain
+        .read()
+        .unwrap()
+        .get_activity(&address)
+        .iter()
+        .rev()
+        .cloned()
+        .collect();
+    HttpResponse::Ok().json(&history)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageQuery {
+    pub block: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StorageSlot {
+    #[schema(value_type = String)]
+    pub address: Address,
+    pub key: String,
+    pub block_number: usize,
+    /// "0" if the slot was never written to, same as in real ethereum
+    pub value: String,
+}
+
+/// value of a contract storage slot, at the latest block unless ?block is passed
+#[utoipa::path(
+    get,
+    path = "/storage/{address}/{key}",
+    tag = "state",
+    params(
+        ("address" = String, Path, description = "0x-prefixed address of the contract, or its address book name"),
+        ("key" = String, Path, description = "storage slot, as used by the STORE opcode"),
+        ("block" = Option<String>, Query, description = "\"latest\" (default), \"earliest\" or a block number"),
+    ),
+    responses(
+        (status = 200, description = "the slot's value", body = StorageSlot),
+        (status = 400, description = "not a valid address"),
+        (status = 404, description = "no such block"),
+        (status = 410, description = "the block is older than the storage history the node keeps (--storage-history)"),
+    )
+)]
+#[get("/storage/{address}/{key}")]
+pub async fn get_storage_at(
+    path: web::Path<(String, String)>,
+    query: web::Query<StorageQuery>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let (address, key) = path.into_inner();
+    let address = match global_state.resolve_address(&address) {
+        Ok(address) => address,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let tag = query.block.as_deref().unwrap_or("latest");
+
+    let blockchain = global_state.blockchain.read().unwrap();
+    let block_number = match blockchain.resolve_block_tag(tag) {
+        Some(number) => number,
+        None => return HttpResponse::NotFound().body(format!("block {} not found.", tag)),
+    };
+    let value = match blockchain.get_storage_at(&address, &key, block_number) {
+        Ok(value) => value.unwrap_or_else(|| "0".into()),
+        Err(e) => return HttpResponse::Gone().body(e.to_string()),
+    };
+
+    HttpResponse::Ok().json(StorageSlot {
+        address,
+        key,
+        block_number,
+        value,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TokenBalance {
+    #[schema(value_type = String)]
+    pub token: Address,
+    #[schema(value_type = String)]
+    pub holder: Address,
+    /// the holder's Address::short() - the storage slot their balance is in, and what a transfer to them passes as
+    /// its recipient
+    pub slot: i32,
+    pub balance: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TokenSupply {
+    #[schema(value_type = String)]
+    pub token: Address,
+    /// 0 until someone inits the token
+    pub total_supply: i32,
+}
+
+/// a holder's balance of a reference token contract (see interpreter::token), at the latest block
+#[utoipa::path(
+    get,
+    path = "/token/{address}/balance/{holder}",
+    tag = "state",
+    params(
+        ("address" = String, Path, description = "0x-prefixed address of the token contract, or its address book name"),
+        ("holder" = String, Path, description = "0x-prefixed address of the holder, or its address book name"),
+    ),
+    responses(
+        (status = 200, description = "the holder's balance, 0 if they never held any", body = TokenBalance),
+        (status = 400, description = "not a valid address"),
+        (status = 404, description = "no such account, or its code isn't the reference token"),
+    )
+)]
+#[get("/token/{address}/balance/{holder}")]
+pub async fn get_token_balance(
+    path: web::Path<(String, String)>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let (token, holder) = path.into_inner();
+    let (token, holder) = match (
+        global_state.resolve_address(&token),
+        global_state.resolve_address(&holder),
+    ) {
+        (Ok(token), Ok(holder)) => (token, holder),
+        (Err(e), _) | (_, Err(e)) => return HttpResponse::BadRequest().body(e),
+    };
+    match read_token(&global_state, &token, |storage| {
+        token::balance_of(storage, &holder)
+    }) {
+        Ok(balance) => HttpResponse::Ok().json(TokenBalance {
+            token,
+            holder,
+            slot: holder.short(),
+            balance,
+        }),
+        Err(res) => res,
+    }
+}
+
+/// how many of a reference token contract's tokens there are, at the latest block
+#[utoipa::path(
+    get,
+    path = "/token/{address}/supply",
+    tag = "state",
+    params(("address" = String, Path, description = "0x-prefixed address of the token contract, or its address book name")),
+    responses(
+        (status = 200, description = "the token's total supply", body = TokenSupply),
+        (status = 400, description = "not a valid address"),
+        (status = 404, description = "no such account, or its code isn't the reference token"),
+    )
+)]
+#[get("/token/{address}/supply")]
+pub async fn get_token_supply(
+    address: web::Path<String>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let token = match global_state.resolve_address(&address) {
+        Ok(address) => address,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match read_token(&global_state, &token, token::total_supply) {
+        Ok(total_supply) => HttpResponse::Ok().json(TokenSupply {
+            token,
+            total_supply,
+        }),
+        Err(res) => res,
+    }
+}
+
+//reads the token's storage, once it's made sure the account runs the reference token's code - any other contract's
+// slots mean something else
+fn read_token(
+    global_state: &GlobalState,
+    address: &Address,
+    read: impl FnOnce(&Trie) -> i32,
+) -> Result<i32, HttpResponse> {
+    let blockchain = global_state.blockchain.read().unwrap();
+    let is_token = blockchain
+        .state
+        .find_account(*address)
+        .is_some_and(|account| token::is_token(&account.code));
+    if !is_token {
+        return Err(HttpResponse::NotFound().body(format!("{} is not a token contract.", address)));
+    }
+    let storage = blockchain.state.storage_trie_map.get(address);
+    Ok(storage.map_or(0, |storage| read(&storage)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/receipt/{tx_hash}",
+    tag = "chain",
+    params(("tx_hash" = String, Path, description = "hash of a mined transaction")),
+    responses(
+        (status = 200, description = "the tx receipt", body = Receipt),
+        (status = 404, description = "tx unknown or not mined yet"),
+    )
+)]
+#[get("/receipt/{tx_hash}")]
+pub async fn get_receipt(
+    tx_hash: web::Path<String>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let receipt = global_state
+        .blockchain
+        .read()
+        .unwrap()
+        .get_receipt(tx_hash.as_str())
+        .cloned();
+
+    match receipt {
+        Some(receipt) => HttpResponse::Ok().json(receipt),
+        //could be the tx doesn't exist, or simply that it hasn't been mined yet
+        None => HttpResponse::NotFound().body(format!("receipt for tx {} not found.", tx_hash)),
+    }
+}
+
+/// where a mined tx's gas went, opcode by opcode - the tx's contract code gets run again with the gas counted per
+/// opcode. Only the payload's gas for tx that don't run any code
+#[utoipa::path(
+    get,
+    path = "/debug/gas/{tx_hash}",
+    tag = "debug",
+    params(("tx_hash" = String, Path, description = "hash of a mined transaction")),
+    responses(
+        (status = 200, description = "the tx's gas, by opcode and by kind of opcode", body = TxGasProfile),
+        (status = 404, description = "tx unknown or not mined yet"),
+        (status = 410, description = "the block is older than the storage history the node keeps (--storage-history)"),
+        (status = 500, description = "the code failed to run again"),
+    )
+)]
+#[get("/debug/gas/{tx_hash}")]
+pub async fn get_gas_profile(
+    tx_hash: web::Path<String>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let profile = global_state
+        .blockchain
+        .read()
+        .unwrap()
+        .profile_tx(tx_hash.as_str());
+    match profile {
+        Ok(Some(profile)) => HttpResponse::Ok().json(profile),
+        Ok(None) => HttpResponse::NotFound().body(format!("tx {} not mined.", tx_hash)),
+        Err(e @ TxError::Store(StoreError::Pruned { .. })) => {
+            HttpResponse::Gone().body(e.to_string())
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InclusionStatus {
+    /// in our tx queue, not mined yet
+    Pending,
+    /// mined, but fewer than FINALITY_CONFIRMATIONS blocks deep
+    Included,
+    Finalized,
+}
+
+/// a tx plus where it is - everything but tx and status is null while it's pending
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TxLookup {
+    #[schema(value_type = Object)]
+    pub tx: Transaction,
+    pub status: InclusionStatus,
+    pub block_number: Option<usize>,
+    pub block_hash: Option<String>,
+    pub transaction_index: Option<usize>,
+    /// 0 while pending, 1 once it's in the head block
+    pub confirmations: usize,
+}
+
+/// mined tx are looked up on our chain, anything else in our tx queue
+pub fn lookup_tx(global_state: &GlobalState, tx_hash: &str) -> Option<TxLookup> {
+    let mined = {
+        let blockchain = global_state.blockchain.read().unwrap();
+        blockchain.get_transaction(tx_hash).map(|(tx, receipt)| {
+            let confirmations = blockchain.confirmations(receipt.block_number);
+            TxLookup {
+                tx: tx.clone(),
+                status: if confirmations >= FINALITY_CONFIRMATIONS {
+                    InclusionStatus::Finalized
+                } else {
+                    InclusionStatus::Included
+                },
+                block_number: Some(receipt.block_number),
+                block_hash: Some(receipt.block_hash.clone()),
+                transaction_index: Some(receipt.transaction_index),
+                confirmations,
+            }
+        })
+    };
+    mined.or_else(|| {
+        let tx_queue = global_state.tx_queue.lock().unwrap();
+        tx_queue.find_by_hash(tx_hash).map(|tx| TxLookup {
+            tx: tx.clone(),
+            status: InclusionStatus::Pending,
+            block_number: None,
+            block_hash: None,
+            transaction_index: None,
+            confirmations: 0,
+        })
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/tx/{tx_hash}",
+    tag = "chain",
+    params(("tx_hash" = String, Path, description = "hash of a pending or mined transaction")),
+    responses(
+        (status = 200, description = "the tx and where it's been included", body = TxLookup),
+        (status = 404, description = "tx is neither on chain nor in our queue"),
+    )
+)]
+#[get("/tx/{tx_hash}")]
+pub async fn get_transaction(
+    tx_hash: web::Path<String>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    match lookup_tx(&global_state, &tx_hash) {
+        Some(lookup) => HttpResponse::Ok().json(lookup),
+        None => HttpResponse::NotFound().body(format!("tx {} not found.", tx_hash)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/mine",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "block mined and broadcast"),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 403, description = "mining is turned off on this node"),
+        (status = 500, description = "the block failed to run, or the mined block failed validation"),
+        (status = 503, description = "the node is still syncing, or the block could not be broadcast (rabbitmq builds, broker unreachable)"),
+    )
+)]
+#[get("/mine")]
+pub async fn mine(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    config: web::Data<NodeConfig>,
+) -> impl Responder {
+    if !config.mining {
+        return HttpResponse::Forbidden().body("mining is turned off on this node.");
+    }
+    mined_response(mine_next_block(&global_state).await)
+}
+
+/// assembles a block on top of the head, seals it, broadcasts it and adds it to our chain - what /mine and the auto
+/// miner both do
+pub async fn mine_next_block(global_state: &GlobalState) -> Result<Arc<Block>, MineError> {
+    //a block on top of a head we know is stale would only be orphaned
+    if global_state.sync.lock().unwrap().state() == Lifecycle::Syncing {
+        return Err(MineError::Syncing);
+    }
+    let miner = global_state.miner_account();
+    let (last_block, block) = block_template(global_state, &miner).map_err(MineError::Template)?;
+
+    //the proof of work happens without holding any locks, and off the async threads, so the rest of the node keeps
+    // working while we mine
+    let block_number = last_block.block_headers.truncated_block_headers.number + 1;
+    global_state
+        .events
+        .publish(Event::MinerStatus(MinerStatus::Mining { block_number }));
+    global_state.sync.lock().unwrap().set_mining(true);
+    let rules = global_state.chain_rules();
+    let block = tokio::task::spawn_blocking(move || block.seal(&last_block, &rules))
+        .await
+        .expect("sealing the block panicked");
+    global_state.sync.lock().unwrap().set_mining(false);
+    global_state
+        .events
+        .publish(Event::MinerStatus(MinerStatus::Idle));
+
+    broadcast_mined_block(global_state, block).await
+}
+
+fn mined_response(mined: Result<Arc<Block>, MineError>) -> HttpResponse {
+    match mined {
+        Ok(block) => HttpResponse::Ok().body(format!(
+            "block {} mined.",
+            block.block_headers.truncated_block_headers.number
+        )),
+        Err(e @ MineError::Syncing) | Err(e @ MineError::Broadcast(_)) => {
+            HttpResponse::ServiceUnavailable().body(e.to_string())
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// the block that goes on top of the head right now, with everything but the seal: the head it goes on and the
+/// block itself, receipts root included. Throws the queued tx that can never be mined out of the queue
+fn block_template(
+    global_state: &GlobalState,
+    miner: &Account,
+) -> Result<(Arc<Block>, Block), TxError> {
+    let queued = global_state.tx_queue.lock().unwrap().get_tx_series();
+    let (template, replayed) = {
+        let blockchain = global_state.blockchain.read().unwrap();
+        let (tx_series, replayed, dropped) = select_block_tx(&blockchain, queued);
+        for (tx_hash, reason) in dropped {
+            tracing::warn!(tx_hash = %tx_hash, reason = %reason, "left tx out of the block");
+        }
+        (blockchain.candidate(miner, tx_series), replayed)
+    };
+    if !replayed.is_empty() {
+        tracing::warn!(
+            count = replayed.len(),
+            "dropped already mined tx from the queue"
+        );
+        global_state
+            .tx_queue
+            .lock()
+            .unwrap()
+            .clear_block_tx(&replayed);
+    }
+    template
+}
+
+//broadcasts a block we sealed, then adds it to our own chain. If a block from a peer landed in the meantime, ours
+// fails validation and that's what gets reported
+async fn broadcast_mined_block(
+    global_state: &GlobalState,
+    block: Block,
+) -> Result<Arc<Block>, MineError> {
+    if let Err(e) = broadcast(global_state, codec::encode_hex(&block), "blocks").await {
+        tracing::error!(error = %e, "failed to broadcast mined block");
+        return Err(MineError::Broadcast(e));
+    }
+
+    let block = Arc::new(block);
+    if let Err(e) = global_state.import_block((*block).clone()) {
+        tracing::warn!(error = %e, "failed to add mined block");
+        return Err(MineError::Rejected(e));
+    }
+    global_state
+        .tx_queue
+        .lock()
+        .unwrap()
+        .clear_block_tx(&block.tx_series);
+    global_state.events.publish(Event::NewBlock {
+        block: block.clone(),
+        from_peer: false,
+    });
+    Ok(block)
+}
+
+/// which of the queued tx go into a block on top of the head, in the order they'd go in - before Block::fill() cuts
+/// the list down to what fits. Also returns the ones that can never go in (mined already, or their nonce is used),
+/// and the hash of every one that would fail in the block with why. Doesn't touch the queue
+fn select_block_tx(
+    blockchain: &Blockchain,
+    queued: Vec<Transaction>,
+) -> (Vec<Transaction>, Vec<Transaction>, Vec<(String, String)>) {
+    //a tx that's been mined already (eg a peer broadcast it again) would get the whole block rejected. One whose
+    // nonce its sender has moved past never can be - it was mined, or another tx took its nonce
+    let mined = blockchain.recent_tx_ids();
+    let (replayed, tx_series): (Vec<Transaction>, Vec<Transaction>) =
+        queued.into_iter().partition(|tx| {
+            let spent = tx
+                .unsigned_tx
+                .from
+                .and_then(|from| blockchain.state.find_account(from))
+                .is_some_and(|sender| Transaction::check_nonce(tx, &sender).is_err());
+            mined.contains(&tx.unsigned_tx.id) || spent
+        });
+    //a tx that would fail in the block gets left in the queue instead of costing us the whole block
+    let head = blockchain.chain.last().unwrap();
+    let env = head.next_env(&blockchain.rules);
+    let (tx_series, dropped) = Block::preflight(tx_series, &blockchain.state, env);
+    (tx_series, replayed, dropped)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PendingTx {
+    pub tx_hash: String,
+    pub gas_used: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DroppedTx {
+    pub tx_hash: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PendingBlockPreview {
+    pub number: usize,
+    pub parent_hash: String,
+    //in block order, the mining reward last
+    pub tx: Vec<PendingTx>,
+    pub gas_used: u64,
+    //the state root once the block has run
+    pub state_root: String,
+    //would fail in the block, so they stay in the queue
+    pub dropped: Vec<DroppedTx>,
+    //would pass, but the block is full (see Block::fill()). They wait for the next one
+    pub deferred: Vec<String>,
+    //mined already, or their nonce has been used - the next /mine throws them out of the queue
+    pub already_mined: Vec<String>,
+}
+
+/// the block /mine would mine right now, minus the proof of work: which queued tx make it in, which don't and why,
+/// and the state it leaves behind. For working out why a tx isn't getting picked up
+#[utoipa::path(
+    get,
+    path = "/miner/pending_block",
+    tag = "node",
+    responses(
+        (status = 200, description = "the block the miner would mine", body = PendingBlockPreview),
+        (status = 500, description = "the block would fail to run"),
+    )
+)]
+#[get("/miner/pending_block")]
+pub async fn get_pending_block(global_state: web::Data<Arc<GlobalState>>) -> impl Responder {
+    let miner = global_state.miner_account();
+    let queued = global_state.tx_queue.lock().unwrap().get_tx_series();
+    //copy under the lock, run the block after it's released
+    let (last_block, mut state, clock, rules, tx_series, replayed, dropped) = {
+        let blockchain = global_state.blockchain.read().unwrap();
+        let (tx_series, replayed, dropped) = select_block_tx(&blockchain, queued);
+        (
+            blockchain.chain.last().unwrap().clone(),
+            blockchain.state.clone(),
+            blockchain.clock.clone(),
+            blockchain.rules.clone(),
+            tx_series,
+            replayed,
+            dropped,
+        )
+    };
+    let block = Block::candidate(
+        &last_block,
+        &miner,
+        tx_series.clone(),
+        state.get_state_root(),
+        &*clock,
+        &rules,
+    );
+    //fill() keeps a prefix, everything past it waits. -1 for the mining reward
+    let deferred = tx_series
+        .iter()
+        .skip(block.tx_series.len() - 1)
+        .map(|tx| tx.hash())
+        .collect();
+    let receipts = match Block::run_block(&last_block, &block, &mut state, &rules) {
+        Ok(receipts) => receipts,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("the pending block fails to run: {}", e))
+        }
+    };
+    HttpResponse::Ok().json(PendingBlockPreview {
+        number: block.block_headers.truncated_block_headers.number,
+        parent_hash: last_block.hash(),
+        tx: receipts
+            .iter()
+            .map(|receipt| PendingTx {
+                tx_hash: receipt.tx_hash.clone(),
+                gas_used: receipt.gas_used,
+            })
+            .collect(),
+        gas_used: receipts.iter().map(|receipt| receipt.gas_used).sum(),
+        state_root: state.get_state_root().clone(),
+        dropped: dropped
+            .into_iter()
+            .map(|(tx_hash, reason)| DroppedTx { tx_hash, reason })
+            .collect(),
+        deferred,
+        already_mined: replayed.iter().map(|tx| tx.hash()).collect(),
+    })
+}
+
+/// a block for a miner outside the node to find the nonce for. A nonce does when
+/// ProofOfWork::seal_hash(header_hash, nonce) comes in under target, compared as 64 hex chars
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WorkPackage {
+    pub number: usize,
+    pub parent_hash: String,
+    //what the nonce gets hashed onto. Also what the package goes by when the nonce is submitted
+    pub header_hash: String,
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SubmitWorkRequest {
+    pub header_hash: String,
+    //in decimal, as a string - a u128 doesn't fit in a json number
+    pub nonce: String,
+}
+
+/// eth_getWork for this chain - the block /mine would mine, minus the proof of work, so the nonce search can run in
+/// another process or on another machine. Every call hands out a new package, and the last MAX_WORK_PACKAGES stay
+/// open until the head moves. Send the nonce back with POST /miner/work
+#[utoipa::path(
+    get,
+    path = "/miner/work",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "the block to find a nonce for", body = WorkPackage),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 403, description = "mining is turned off on this node"),
+        (status = 500, description = "the block failed to run"),
+        (status = 501, description = "the node's consensus engine doesn't use proof of work"),
+        (status = 503, description = "the node is still syncing"),
+    )
+)]
+#[get("/miner/work")]
+pub async fn get_work(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    config: web::Data<NodeConfig>,
+) -> impl Responder {
+    if !config.mining {
+        return HttpResponse::Forbidden().body("mining is turned off on this node.");
+    }
+    if global_state.chain_rules().consensus.name() != ProofOfWork.name() {
+        return HttpResponse::NotImplemented()
+            .body("the consensus engine doesn't use proof of work.");
+    }
+    if global_state.sync.lock().unwrap().state() == Lifecycle::Syncing {
+        return HttpResponse::ServiceUnavailable().body("the node is still syncing.");
+    }
+    let miner = global_state.miner_account();
+    let (last_block, block) = match block_template(&global_state, &miner) {
+        Ok(template) => template,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("the block fails to run: {}", e))
+        }
+    };
+    let header_hash = block.block_headers.truncated_block_headers.hash();
+    let package = WorkPackage {
+        number: block.block_headers.truncated_block_headers.number,
+        parent_hash: last_block.hash(),
+        header_hash: header_hash.clone(),
+        target: Block::calc_block_target_hash(&last_block),
+    };
+    let mut work = global_state.work.lock().unwrap();
+    work.retain_on(&package.parent_hash);
+    work.insert(header_hash, last_block, block);
+    HttpResponse::Ok().json(package)
+}
+
+/// eth_submitWork - seals the package with the nonce, then broadcasts and adds the block the same way /mine does
+#[utoipa::path(
+    post,
+    path = "/miner/work",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    request_body = SubmitWorkRequest,
+    responses(
+        (status = 200, description = "block mined and broadcast"),
+        (status = 400, description = "the nonce isn't a decimal u128"),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 404, description = "no open package with that header hash, or the head has moved on since"),
+        (status = 422, description = "the nonce doesn't get the hash under the target"),
+        (status = 500, description = "the sealed block failed validation"),
+        (status = 503, description = "the block could not be broadcast (rabbitmq builds, broker unreachable)"),
+    )
+)]
+#[post("/miner/work")]
+pub async fn submit_work(
+    _auth: AdminAuth,
+    body: web::Json<SubmitWorkRequest>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let nonce = match body.nonce.parse::<u128>() {
+        Ok(nonce) => nonce,
+        Err(_) => return HttpResponse::BadRequest().body(format!("invalid nonce {}", body.nonce)),
+    };
+    let package = global_state.work.lock().unwrap().get(&body.header_hash);
+    let (parent, mut block) = match package {
+        Some(package) => package,
+        None => {
+            return HttpResponse::NotFound().body(format!(
+                "no open work package {}, it may be stale.",
+                body.header_hash
+            ))
+        }
+    };
+    block.block_headers.nonce = nonce;
+    if let Err(e) = global_state
+        .chain_rules()
+        .consensus
+        .verify_seal(&parent, &block)
+    {
+        return HttpResponse::UnprocessableEntity().body(e.to_string());
+    }
+    //every package still open is on what's about to be the old head, this one included
+    global_state.work.lock().unwrap().retain_on(&block.hash());
+    mined_response(broadcast_mined_block(&global_state, block).await)
+}
+
+/// starts mining in the background, so blocks keep coming without anyone calling /mine. Runs until POST /miner/stop
+#[utoipa::path(
+    post,
+    path = "/miner/start",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    request_body = StartMinerRequest,
+    responses(
+        (status = 200, description = "the auto miner is running", body = AutoMinerStatus),
+        (status = 400, description = "the interval is 0"),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 403, description = "mining is turned off on this node"),
+        (status = 409, description = "the auto miner is already running"),
+    )
+)]
+#[post("/miner/start")]
+pub async fn start_miner(
+    _auth: AdminAuth,
+    body: web::Json<StartMinerRequest>,
+    global_state: web::Data<Arc<GlobalState>>,
+    config: web::Data<NodeConfig>,
+) -> impl Responder {
+    if !config.mining {
+        return HttpResponse::Forbidden().body("mining is turned off on this node.");
+    }
+    if body.interval_secs == Some(0) {
+        return HttpResponse::BadRequest().body("the mining interval must be above 0.");
+    }
+    let mut miner = global_state.miner.lock().unwrap();
+    if let Err(e) = miner.start(global_state.get_ref().clone(), body.interval_secs) {
+        return HttpResponse::Conflict().body(e);
+    }
+    HttpResponse::Ok().json(miner.status())
+}
+
+/// stops the auto miner. A block it's in the middle of still gets finished
+#[utoipa::path(
+    post,
+    path = "/miner/stop",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "the auto miner isn't running (anymore)", body = AutoMinerStatus),
+        (status = 401, description = "missing or invalid auth token"),
+    )
+)]
+#[post("/miner/stop")]
+pub async fn stop_miner(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let mut miner = global_state.miner.lock().unwrap();
+    miner.stop();
+    HttpResponse::Ok().json(miner.status())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TxRequest {
+    #[schema(value_type = String)]
+    pub value: U256,
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub to: Option<AddressInput>,
+    #[schema(value_type = Vec<Object>)]
+    pub code: Vec<OPCODE>,
+    #[schema(value_type = String)]
+    pub gas_limit: U256,
+    //which of the node's accounts sends the tx. Defaults to the miner
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub from: Option<AddressInput>,
+    //unlocks the "from" account first if its key file is still locked
+    #[serde(default)]
+    pub passphrase: Option<String>,
+    //account creation only - makes the new account a multisig, see /multisig/propose
+    #[serde(default)]
+    pub multisig: Option<MultisigConfig>,
+    //another of the node's accounts that pays the gas, the sender only pays the value. For an account creation it
+    // pays for deploying the code, and defaults to the miner
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub fee_payer: Option<AddressInput>,
+    //unlocks the fee payer first if its key file is still locked
+    #[serde(default)]
+    pub fee_payer_passphrase: Option<String>,
+    //transfers only - what the recipient's code reads with CALLDATALOAD, eg /token/... calls
+    #[serde(default)]
+    pub call_data: Vec<i32>,
+}
+
+/// what /transact hands back, so clients can track the tx without having to compute its hash themselves
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TxResponse {
+    //same hash /receipt/{tx_hash} and the block's tx trie use
+    pub tx_hash: String,
+    pub status: TxStatus,
+    //only present when the tx got rejected
+    pub reason: Option<String>,
+    #[schema(value_type = Object)]
+    pub tx: Transaction,
+}
+
+/// giving the miner (or any other account the node holds keys for) power to a)transact, b)create an account
+#[utoipa::path(
+    post,
+    path = "/transact",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    request_body = TxRequest,
+    responses(
+        (status = 200, description = "the signed tx, its hash and where it stands in the tx pool", body = TxResponse),
+        (status = 400, description = "wrong passphrase, invalid multisig config, a sender or call data was given for an account creation tx, or a fee payer for one without code"),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 404, description = "the node doesn't hold keys for the sender or fee payer"),
+        (status = 422, description = "the tx failed validation (or asked for more gas than the node allows) and was not broadcast", body = TxResponse),
+        (status = 423, description = "the sender or fee payer is locked and no passphrase was given"),
+        (status = 503, description = "the tx could not be broadcast (rabbitmq builds, broker unreachable)"),
+    )
+)]
+#[post("/transact")]
+pub async fn transact(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    config: web::Data<NodeConfig>,
+    body: web::Json<TxRequest>,
+) -> impl Responder {
+    if let Some(multisig) = &body.multisig {
+        if body.to.is_some() || !body.code.is_empty() {
+            return HttpResponse::BadRequest()
+                .body("multisig only applies to creating an account without code.");
+        }
+        if let Err(e) = multisig.validate() {
+            return HttpResponse::BadRequest().body(e);
+        }
+    }
+    if body.fee_payer.is_some() && body.to.is_none() && body.code.is_empty() {
+        return HttpResponse::BadRequest()
+            .body("an account without code has nothing for a fee payer to pay for.");
+    }
+    if !body.call_data.is_empty() && body.to.is_none() {
+        return HttpResponse::BadRequest().body("only transfers can have call data.");
+    }
+    //names are looked up once the body is in, see AddressInput
+    let (to, from, fee_payer) = match (
+        resolve_optional_input(&global_state, &body.to),
+        resolve_optional_input(&global_state, &body.from),
+        resolve_optional_input(&global_state, &body.fee_payer),
+    ) {
+        (Ok(to), Ok(from), Ok(fee_payer)) => (to, from, fee_payer),
+        (Err(res), _, _) | (_, Err(res), _) | (_, _, Err(res)) => return res,
+    };
+    // depending on whether the "to" field is present this will be either a normal tx (present) or an acc creation tx (not present)
+    let account = match (to, from) {
+        (Some(_to), Some(from)) => {
+            match sender_account(&global_state, &from, body.passphrase.as_deref()) {
+                Ok(account) => account,
+                Err(res) => return res,
+            }
+        }
+        (Some(_to), None) => global_state.miner_account(),
+        (None, Some(_from)) => {
+            return HttpResponse::BadRequest()
+                .body("only transfers take a sender - created accounts sign their own tx.")
+        }
+        (None, None) => {
+            //if not present, we're creating a new account. The node keeps its keys so it shows up under /accounts
+            let mut account = Account::new(body.code.clone());
+            account.public_account.multisig = body.multisig.clone();
+            global_state.keystore.write().unwrap().add(account.clone());
+            account
+        }
+    };
+    //a transfer goes out with the sender's next nonce, counting what it has queued already
+    let account = match to {
+        Some(_) => {
+            let nonce = global_state.next_nonce(&account.public_account.address);
+            account.with_nonce(nonce)
+        }
+        None => account,
+    };
+    let fee_payer = match fee_payer {
+        Some(fee_payer) => {
+            let passphrase = body.fee_payer_passphrase.as_deref();
+            match sender_account(&global_state, &fee_payer, passphrase) {
+                Ok(fee_payer) => Some(fee_payer),
+                Err(res) => return res,
+            }
+        }
+        None => None,
+    };
+    let new_tx = match to {
+        Some(to) => Transaction::create_call(
+            account,
+            to,
+            body.value,
+            body.call_data.clone(),
+            body.gas_limit,
+            fee_payer.as_ref(),
+            config.chain_id,
+        ),
+        None if body.code.is_empty() => Transaction::create_transaction(
+            Some(account),
+            None,
+            body.value,
+            None,
+            body.gas_limit,
+            config.chain_id,
+        ),
+        //the new account has nothing to pay for its code with
+        None => {
+            let creator = fee_payer.unwrap_or_else(|| global_state.miner_account());
+            Transaction::create_contract(
+                account,
+                body.value,
+                body.gas_limit,
+                &creator,
+                config.chain_id,
+            )
+        }
+    };
+    submit_tx(&global_state, &config, new_tx).await
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MultisigProposal {
+    #[schema(value_type = String)]
+    pub from: AddressInput,
+    #[schema(value_type = String)]
+    pub to: AddressInput,
+    #[schema(value_type = String)]
+    pub value: U256,
+    #[schema(value_type = String)]
+    pub gas_limit: U256,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CosignRequest {
+    #[schema(value_type = Object)]
+    pub tx: Transaction,
+    #[schema(value_type = String)]
+    pub signer: AddressInput,
+    //unlocks the signer first if its key file is still locked
+    #[serde(default)]
+    pub passphrase: Option<String>,
+}
+
+/// a multisig tx on its way to being valid - pass it from signer to signer, then to /multisig/submit
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MultisigTx {
+    #[schema(value_type = Object)]
+    pub tx: Transaction,
+    //valid signatures from the account's signers so far
+    pub signatures: usize,
+    pub threshold: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SubmitTxRequest {
+    #[schema(value_type = Object)]
+    pub tx: Transaction,
+}
+
+/// the on chain multisig config of the tx's sender
+fn sender_multisig(
+    global_state: &GlobalState,
+    tx: &Transaction,
+) -> Result<MultisigConfig, HttpResponse> {
+    let from = tx
+        .unsigned_tx
+        .from
+        .ok_or_else(|| HttpResponse::BadRequest().body("the tx has no sender."))?;
+    global_state
+        .blockchain
+        .read()
+        .unwrap()
+        .state
+        .find_account(from)
+        .and_then(|account| account.multisig)
+        .ok_or_else(|| {
+            HttpResponse::BadRequest().body(format!("{} is not a multisig account.", from))
+        })
+}
+
+fn multisig_progress(tx: Transaction, multisig: &MultisigConfig) -> MultisigTx {
+    let serialized_tx = serde_json::to_string(&tx.unsigned_tx).unwrap();
+    MultisigTx {
+        signatures: multisig.count_signatures(&serialized_tx, &tx.cosignatures),
+        threshold: multisig.threshold,
+        tx,
+    }
+}
+
+/// starts an outgoing tx from a multisig account. Nobody has signed it yet
+#[utoipa::path(
+    post,
+    path = "/multisig/propose",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    request_body = MultisigProposal,
+    responses(
+        (status = 200, description = "the unsigned tx", body = MultisigTx),
+        (status = 400, description = "the sender isn't a multisig account"),
+        (status = 401, description = "missing or invalid auth token"),
+    )
+)]
+#[post("/multisig/propose")]
+pub async fn propose_multisig_tx(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    body: web::Json<MultisigProposal>,
+) -> impl Responder {
+    let (from, to) = match (
+        resolve_input(&global_state, &body.from),
+        resolve_input(&global_state, &body.to),
+    ) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(res), _) | (_, Err(res)) => return res,
+    };
+    let tx = Transaction::create_multisig_transaction(
+        from,
+        to,
+        body.value,
+        body.gas_limit,
+        global_state.next_nonce(&from),
+        global_state.blockchain.read().unwrap().chain_id(),
+    );
+    match sender_multisig(&global_state, &tx) {
+        Ok(multisig) => HttpResponse::Ok().json(multisig_progress(tx, &multisig)),
+        Err(res) => res,
+    }
+}
+
+/// adds the signature of one of the multisig's signers, if this node holds their key
+#[utoipa::path(
+    post,
+    path = "/multisig/cosign",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    request_body = CosignRequest,
+    responses(
+        (status = 200, description = "the tx with the signature added", body = MultisigTx),
+        (status = 400, description = "not a multisig tx, the signer isn't one of its signers, or wrong passphrase"),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 404, description = "the node doesn't hold keys for the signer"),
+        (status = 423, description = "the signer is locked and no passphrase was given"),
+    )
+)]
+#[post("/multisig/cosign")]
+pub async fn cosign_multisig_tx(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    body: web::Json<CosignRequest>,
+) -> impl Responder {
+    let multisig = match sender_multisig(&global_state, &body.tx) {
+        Ok(multisig) => multisig,
+        Err(res) => return res,
+    };
+    let signer = match resolve_input(&global_state, &body.signer) {
+        Ok(signer) => signer,
+        Err(res) => return res,
+    };
+    if !multisig.signers.contains(&signer) {
+        return HttpResponse::BadRequest()
+            .body(format!("{} is not a signer of this multisig.", signer));
+    }
+    let signer = match sender_account(&global_state, &signer, body.passphrase.as_deref()) {
+        Ok(signer) => signer,
+        Err(res) => return res,
+    };
+    let mut tx = body.tx.clone();
+    tx.cosign(&signer);
+    HttpResponse::Ok().json(multisig_progress(tx, &multisig))
+}
+
+/// validates a multisig tx that went round its signers via /multisig/cosign and broadcasts it
+#[utoipa::path(
+    post,
+    path = "/multisig/submit",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    request_body = SubmitTxRequest,
+    responses(
+        (status = 200, description = "the tx, its hash and where it stands in the tx pool", body = TxResponse),
+        (status = 400, description = "not a transfer from a multisig account"),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 422, description = "eg not enough signatures yet - the tx was not broadcast", body = TxResponse),
+        (status = 503, description = "the tx could not be broadcast (rabbitmq builds, broker unreachable)"),
+    )
+)]
+#[post("/multisig/submit")]
+pub async fn submit_multisig_tx(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    config: web::Data<NodeConfig>,
+    body: web::Json<SubmitTxRequest>,
+) -> impl Responder {
+    let tx = body.into_inner().tx;
+    //anything else could be passed off as signed by this node, eg a mining reward
+    if tx.unsigned_tx.data.tx_type != TxType::Transact {
+        return HttpResponse::BadRequest().body("only transfers from a multisig account.");
+    }
+    if let Err(res) = sender_multisig(&global_state, &tx) {
+        return res;
+    }
+    submit_tx(&global_state, &config, tx).await
 }
 
-#[get("/mine")]
-pub async fn mine(global_state: web::Data<Arc<Mutex<GlobalState>>>) -> impl Responder {
-    // how to access multiple fields on a struct mutex - https://stackoverflow.com/questions/60253791/why-can-i-not-mutably-borrow-separate-fields-from-a-mutex-guard
-    let mut guard = global_state.lock().unwrap();
-    // more on deref_mut - https://dhghomon.github.io/easy_rust/Chapter_56.html
-    let global_state = guard.deref_mut(); //really important that we deref the mutexguard, or we won't be able to have multiple mut refs to diff parts of it
-
-    let beneficiary = global_state.miner_account.public_account.address;
-    let tx_series = global_state.tx_queue.get_tx_series().clone();
-    let mut tx_queue = &mut global_state.tx_queue;
-    let blockchain = &mut global_state.blockchain;
-
-    let last_block = &blockchain.chain[&blockchain.chain.len() - 1];
-    let state_root = blockchain.state.get_state_root();
-    let block = Block::mine_block(&last_block, beneficiary, tx_series, state_root);
-    let block_number = block.block_headers.truncated_block_headers.number;
-
-    let str_block = serde_json::to_string(&block).unwrap();
-    rabbit_publish(str_block, "blocks").await.unwrap();
-
-    if blockchain.add_block(block, &mut tx_queue) {
-        HttpResponse::Ok().body(format!("block {} mined.", block_number))
-    } else {
-        HttpResponse::InternalServerError().body(format!("failed to mine block."))
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PrepareTxRequest {
+    //whoever will sign the tx. For account creation (no "to") this is the account being created
+    #[schema(value_type = String)]
+    pub from: AddressInput,
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub to: Option<AddressInput>,
+    #[schema(value_type = String)]
+    pub value: U256,
+    #[serde(default)]
+    #[schema(value_type = Vec<Object>)]
+    pub code: Vec<OPCODE>,
+    #[schema(value_type = String)]
+    pub gas_limit: U256,
+    //transfers only. Left out = the sender's next nonce, counting what it has queued on this node
+    #[serde(default)]
+    pub nonce: Option<u64>,
+    //who pays the gas. They sign too, see /tx/sponsor. An account creation with code has to have one, to pay for
+    // deploying it
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub fee_payer: Option<AddressInput>,
+    //transfers only, see TxRequest
+    #[serde(default)]
+    pub call_data: Vec<i32>,
+}
+
+/// an unsigned tx and the exact hash its signer has to sign
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SigningPayload {
+    #[schema(value_type = Object)]
+    pub unsigned_tx: UnsignedTx,
+    //hex encoded sha256 of the serialized unsigned tx
+    pub signing_hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SendSignedTxRequest {
+    //exactly as /tx/prepare returned it - any change invalidates the signature
+    #[schema(value_type = Object)]
+    pub unsigned_tx: UnsignedTx,
+    //hex, 64 byte compact or DER
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SponsorTxRequest {
+    //exactly as /tx/prepare returned it, fee payer and all
+    #[schema(value_type = Object)]
+    pub unsigned_tx: UnsignedTx,
+    //the sender's - hex, 64 byte compact or DER
+    pub signature: String,
+    //unlocks the fee payer first if its key file is still locked
+    #[serde(default)]
+    pub passphrase: Option<String>,
+}
+
+/// step 1 of signing off-node (eg on a hardware wallet): builds the tx and says what to sign. Needs no keys, so it's public
+#[utoipa::path(
+    post,
+    path = "/tx/prepare",
+    tag = "node",
+    request_body = PrepareTxRequest,
+    responses(
+        (status = 200, description = "the unsigned tx and its signing hash", body = SigningPayload),
+        (status = 400, description = "call data was given for an account creation tx, or it has code and no fee payer, or a fee payer and no code"),
+    )
+)]
+#[post("/tx/prepare")]
+pub async fn prepare_tx(
+    global_state: web::Data<Arc<GlobalState>>,
+    body: web::Json<PrepareTxRequest>,
+) -> impl Responder {
+    let body = body.into_inner();
+    if body.to.is_none() && body.fee_payer.is_some() == body.code.is_empty() {
+        return HttpResponse::BadRequest()
+            .body("an account creation needs a fee payer exactly when it deploys code.");
+    }
+    if !body.call_data.is_empty() && body.to.is_none() {
+        return HttpResponse::BadRequest().body("only transfers can have call data.");
+    }
+    let (from, to, fee_payer) = match (
+        resolve_input(&global_state, &body.from),
+        resolve_optional_input(&global_state, &body.to),
+        resolve_optional_input(&global_state, &body.fee_payer),
+    ) {
+        (Ok(from), Ok(to), Ok(fee_payer)) => (from, to, fee_payer),
+        (Err(res), _, _) | (_, Err(res), _) | (_, _, Err(res)) => return res,
+    };
+    let nonce = body.nonce.unwrap_or_else(|| global_state.next_nonce(&from));
+    let mut unsigned_tx = Transaction::create_unsigned_transaction(
+        from,
+        to,
+        body.value,
+        body.code,
+        body.gas_limit,
+        nonce,
+        global_state.blockchain.read().unwrap().chain_id(),
+    );
+    unsigned_tx.fee_payer = fee_payer;
+    unsigned_tx.call_data = body.call_data;
+    HttpResponse::Ok().json(SigningPayload {
+        signing_hash: Transaction::signing_hash(&unsigned_tx),
+        unsigned_tx,
+    })
+}
+
+/// step 2: attaches the signature made off-node, then validates and broadcasts the tx like /transact would
+#[utoipa::path(
+    post,
+    path = "/tx/send",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    request_body = SendSignedTxRequest,
+    responses(
+        (status = 200, description = "the signed tx, its hash and where it stands in the tx pool", body = TxResponse),
+        (status = 400, description = "malformed signature, or not signed by the tx's sender"),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 422, description = "the tx failed validation and was not broadcast", body = TxResponse),
+        (status = 503, description = "the tx could not be broadcast (rabbitmq builds, broker unreachable)"),
+    )
+)]
+#[post("/tx/send")]
+pub async fn send_signed_tx(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    config: web::Data<NodeConfig>,
+    body: web::Json<SendSignedTxRequest>,
+) -> impl Responder {
+    let body = body.into_inner();
+    match Transaction::from_external_signature(body.unsigned_tx, &body.signature) {
+        Ok(tx) => submit_tx(&global_state, &config, tx).await,
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
+/// step 2 for a tx with a fee payer the node holds keys for, in place of /tx/send: attaches the sender's signature
+/// and the fee payer's, then validates and broadcasts the tx. The node ends up paying the gas for someone else's tx
+#[utoipa::path(
+    post,
+    path = "/tx/sponsor",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    request_body = SponsorTxRequest,
+    responses(
+        (status = 200, description = "the signed tx, its hash and where it stands in the tx pool", body = TxResponse),
+        (status = 400, description = "no fee payer, malformed signature, not signed by the tx's sender, or wrong passphrase"),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 404, description = "the node doesn't hold keys for the fee payer"),
+        (status = 422, description = "the tx failed validation and was not broadcast", body = TxResponse),
+        (status = 423, description = "the fee payer is locked and no passphrase was given"),
+        (status = 503, description = "the tx could not be broadcast (rabbitmq builds, broker unreachable)"),
+    )
+)]
+#[post("/tx/sponsor")]
+pub async fn sponsor_tx(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    config: web::Data<NodeConfig>,
+    body: web::Json<SponsorTxRequest>,
+) -> impl Responder {
+    let body = body.into_inner();
+    let fee_payer = match body.unsigned_tx.fee_payer {
+        Some(fee_payer) => fee_payer,
+        None => {
+            return HttpResponse::BadRequest()
+                .body("the tx has no fee payer, use /tx/send instead.")
+        }
+    };
+    let mut tx = match Transaction::from_external_signature(body.unsigned_tx, &body.signature) {
+        Ok(tx) => tx,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let fee_payer = match sender_account(&global_state, &fee_payer, body.passphrase.as_deref()) {
+        Ok(fee_payer) => fee_payer,
+        Err(res) => return res,
+    };
+    //can't fail, it's the account the tx names
+    tx.sponsor(&fee_payer).unwrap();
+    submit_tx(&global_state, &config, tx).await
+}
+
+/// a local account to send from, unlocked first if it's still locked and a passphrase came along
+fn sender_account(
+    global_state: &GlobalState,
+    address: &Address,
+    passphrase: Option<&str>,
+) -> Result<Account, HttpResponse> {
+    let mut keystore = global_state.keystore.write().unwrap();
+    if keystore.is_locked(address) {
+        let passphrase = passphrase.ok_or_else(|| {
+            HttpResponse::build(StatusCode::LOCKED).body(format!(
+                "account {} is locked, unlock it first or pass its passphrase.",
+                address
+            ))
+        })?;
+        keystore
+            .unlock(address, passphrase)
+            .map_err(|e| HttpResponse::BadRequest().body(e))?;
+    }
+    keystore
+        .get(address)
+        .cloned()
+        .ok_or_else(|| HttpResponse::NotFound().body(format!("no account {}.", address)))
+}
+
+/// an address from a request body, or the 400 for one that doesn't resolve
+fn resolve_input(
+    global_state: &GlobalState,
+    input: &AddressInput,
+) -> Result<Address, HttpResponse> {
+    global_state
+        .resolve_address(input)
+        .map_err(|e| HttpResponse::BadRequest().body(e))
+}
+
+/// resolve_input(), for optional fields
+fn resolve_optional_input(
+    global_state: &GlobalState,
+    input: &Option<AddressInput>,
+) -> Result<Option<Address>, HttpResponse> {
+    input
+        .as_ref()
+        .map(|input| resolve_input(global_state, input))
+        .transpose()
+}
+
+/// validates a tx we signed ourselves and broadcasts it, unless it's invalid
+async fn submit_tx(
+    global_state: &GlobalState,
+    config: &NodeConfig,
+    new_tx: Transaction,
+) -> HttpResponse {
+    let tx_hash = new_tx.hash();
+    let str_tx = codec::encode_hex(&new_tx);
+    let validation = validate_submitted_tx(global_state, config, &new_tx, &str_tx);
+    let status = match validation {
+        Ok(status) => status,
+        Err(reason) => {
+            tracing::warn!(tx_hash = %tx_hash, reason = %reason, "rejected submitted tx");
+            return HttpResponse::UnprocessableEntity().json(&TxResponse {
+                tx_hash,
+                status: TxStatus::Rejected,
+                reason: Some(reason),
+                tx: new_tx,
+            });
+        }
+    };
+
+    // (!) No longer adding to local queue - instead broadcasting to entire network. Unlike with blocks which we're processing locally, we don't have dedup functionality for tx
+    // let mut tx_queue = &mut global_state.tx_queue;
+    // tx_queue.add(new_tx.clone());
+
+    if let Err(e) = broadcast(global_state, str_tx, "tx").await {
+        tracing::error!(tx_hash = %tx_hash, error = %e, "failed to broadcast tx");
+        return HttpResponse::ServiceUnavailable().body(format!("failed to broadcast tx: {}", e));
+    }
+
+    //already queued by gossip, or our own consumer may have picked it up from the exchange. A Queued tx stays
+    // Queued - it's in the queue, but can't go into a block until its sender exists
+    let status = match status {
+        TxStatus::Validated if global_state.tx_queue.lock().unwrap().contains(&new_tx) => {
+            TxStatus::Pending
+        }
+        status => status,
+    };
+
+    HttpResponse::Ok().json(&TxResponse {
+        tx_hash,
+        status,
+        reason: None,
+        tx: new_tx,
+    })
+}
+
+/// what a tx submitted to this node has to pass before it gets broadcast, and where it stands in the pool if it does.
+/// str_tx is the tx as it goes over the wire
+pub(crate) fn validate_submitted_tx(
+    global_state: &GlobalState,
+    config: &NodeConfig,
+    new_tx: &Transaction,
+    str_tx: &str,
+) -> Result<TxStatus, String> {
+    if new_tx.unsigned_tx.chain_id != config.chain_id {
+        return Err(format!(
+            "tx is for chain id {}, this node is on {}",
+            new_tx.unsigned_tx.chain_id, config.chain_id
+        ));
+    }
+    if new_tx.unsigned_tx.gas_limit > U256::from(config.max_gas_limit) {
+        return Err(format!(
+            "gas limit {} is above this node's maximum of {}",
+            new_tx.unsigned_tx.gas_limit, config.max_gas_limit
+        ));
+    }
+    validate_pooled_tx(global_state, new_tx, str_tx)
+}
+
+/// the part of validate_submitted_tx() that doesn't depend on this node's config - what a tx gossiped to us has to
+/// pass too, see pubsub::queue_transaction()
+pub(crate) fn validate_pooled_tx(
+    global_state: &GlobalState,
+    new_tx: &Transaction,
+    str_tx: &str,
+) -> Result<TxStatus, String> {
+    //the earliest block the tx can go into
+    let env = {
+        let blockchain = global_state.blockchain.read().unwrap();
+        blockchain.chain.last().unwrap().next_env(&blockchain.rules)
+    };
+    Transaction::check_size(str_tx)
+        .and_then(|()| global_state.tx_queue.lock().unwrap().check_nonce(new_tx))
+        .map_err(|e| e.to_string())?;
+    //a tx that calls a contract gets simulated on an overlay of the head state, so a gas limit that doesn't cover
+    // the contract is caught before the tx goes out
+    match new_tx.unsigned_tx.data.tx_type {
+        TxType::Transact => {
+            let blockchain = global_state.blockchain.read().unwrap();
+            let state = &blockchain.state;
+            let sender = new_tx.unsigned_tx.from.map(|from| state.find_account(from));
+            match (sender, Transaction::check_recipient(new_tx, state)) {
+                (None, _) => Err("the tx has no sender".into()),
+                //spam doesn't get to wait in the queue for its sender either, or get passed on by gossip
+                (_, Err(e)) => Err(e.to_string()),
+                (Some(Some(_)), Ok(())) => Transaction::simulate(new_tx, state, env)
+                    .map(|_| TxStatus::Validated)
+                    .map_err(|e| e.to_string()),
+                //nothing to check its balance against yet, but it has to be the sender's
+                (Some(None), Ok(())) => Transaction::check_signature(new_tx)
+                    .map(|()| TxStatus::Queued)
+                    .map_err(|e| e.to_string()),
+            }
+        }
+        TxType::CreateAccount
+            if !Transaction::validate_create_account_transaction(new_tx, env.fork) =>
+        {
+            Err("invalid account creation tx".into())
+        }
+        TxType::CreateAccount => {
+            let blockchain = global_state.blockchain.read().unwrap();
+            Transaction::check_creation_payer(new_tx, &blockchain.state)
+                .map(|()| TxStatus::Validated)
+                .map_err(|e| e.to_string())
+        }
+        TxType::MiningReward => Err("mining rewards only come from miners".into()),
+    }
+}
+
+/// what the faucet hands out when the request doesn't say
+pub const FAUCET_AMOUNT: u64 = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FaucetRequest {
+    #[schema(value_type = String)]
+    pub address: AddressInput,
+    #[schema(value_type = Option<String>)]
+    pub amount: Option<U256>,
+}
+
+/// dev mode only - a plain transfer from the miner, so the faucet can't hand out more than the miner has
+#[utoipa::path(
+    post,
+    path = "/faucet",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    request_body = FaucetRequest,
+    responses(
+        (status = 200, description = "the transfer tx, its hash and where it stands in the tx pool", body = TxResponse),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 404, description = "node isn't running with --dev"),
+        (status = 422, description = "the miner can't cover the transfer", body = TxResponse),
+        (status = 503, description = "the tx could not be broadcast (rabbitmq builds, broker unreachable)"),
+    )
+)]
+#[post("/faucet")]
+pub async fn faucet(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    config: web::Data<NodeConfig>,
+    body: web::Json<FaucetRequest>,
+) -> impl Responder {
+    if !config.dev {
+        return HttpResponse::NotFound().body("the faucet is only available in --dev mode.");
+    }
+    let address = match resolve_input(&global_state, &body.address) {
+        Ok(address) => address,
+        Err(res) => return res,
+    };
+    let miner = global_state
+        .miner_account()
+        .with_nonce(global_state.next_nonce(&global_state.miner_address));
+    let new_tx = Transaction::create_transaction(
+        Some(miner),
+        Some(address),
+        body.amount.unwrap_or_else(|| FAUCET_AMOUNT.into()),
+        None,
+        0,
+        config.chain_id,
+    );
+    submit_tx(&global_state, &config, new_tx).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/balance/{address}",
+    tag = "state",
+    params(("address" = String, Path, description = "0x-prefixed address or address book name")),
+    responses(
+        (status = 200, description = "{\"balance\": <0x hex>}", body = Object),
+        (status = 400, description = "invalid address"),
+    )
+)]
+#[get("/balance/{address}")]
+pub async fn get_balance(
+    address: web::Path<String>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let address = match global_state.resolve_address(address.deref()) {
+        Ok(address) => address,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let balance = global_state
+        .blockchain
+        .read()
+        .unwrap()
+        .state
+        .get_account_or_empty(address)
+        .balance;
+    let mut map = HashMap::new();
+    map.insert("balance", balance);
+    HttpResponse::Ok().json(&map)
+}
+
+/// merkle proof of an account against the state root. Verify with Trie::verify_proof(state_root, address, proof),
+/// the address without its 0x -
+/// the value it returns is the account's json, balance and nonce included.
+/// (!) a block header's state_root is the state before that block, so this root is the one the next block will carry
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AccountProof {
+    /// the head block, whose tx the state includes
+    pub block_number: usize,
+    pub state_root: String,
+    #[schema(value_type = String)]
+    pub address: Address,
+    //rlp encoded nodes, hex, root first - see Trie::get_proof()
+    pub proof: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/proof/{address}",
+    tag = "state",
+    params(("address" = String, Path, description = "0x-prefixed address or address book name")),
+    responses(
+        (status = 200, description = "proof of the account against the state root", body = AccountProof),
+        (status = 400, description = "invalid address"),
+        (status = 404, description = "no account at that address yet"),
+    )
+)]
+#[get("/proof/{address}")]
+pub async fn get_account_proof(
+    address: web::Path<String>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    let address = match global_state.resolve_address(address.deref()) {
+        Ok(address) => address,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    //root, proof and head number all from under the same lock, so they can't straddle a block
+    let blockchain = global_state.blockchain.read().unwrap();
+    let state_trie = &blockchain.state.state_trie;
+    match state_trie.get_proof(&address.to_hex()) {
+        Some(proof) => HttpResponse::Ok().json(AccountProof {
+            block_number: blockchain.chain.len() - 1,
+            state_root: state_trie.root_hash.clone(),
+            address,
+            proof,
+        }),
+        None => HttpResponse::NotFound().body(format!("account {} not found.", address)),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AccountInfo {
+    #[schema(value_type = String)]
+    pub address: Address,
+    #[schema(value_type = String)]
+    pub balance: U256,
+    //on chain - the next transfer needs at least this. Queued ones aren't counted
+    pub nonce: u64,
+    pub is_contract: bool,
+    pub is_miner: bool,
+    pub name: Option<String>,
+    //true if the tx creating the account hasn't been mined yet
+    pub pending: bool,
+    //key file found on disk that hasn't been unlocked yet - see /accounts/{address}/unlock
+    pub locked: bool,
+}
+
+/// accounts managed by this node, ie the ones it holds the keys for
+#[utoipa::path(
+    get,
+    path = "/accounts",
+    tag = "accounts",
+    responses((status = 200, description = "accounts this node holds keys for", body = [AccountInfo]))
+)]
+#[get("/accounts")]
+pub async fn get_accounts(global_state: web::Data<Arc<GlobalState>>) -> impl Responder {
+    let blockchain = global_state.blockchain.read().unwrap();
+    let keystore = global_state.keystore.read().unwrap();
+    let miner_addr = global_state.miner_address;
+
+    let accounts: Vec<AccountInfo> = keystore
+        .addresses()
+        .into_iter()
+        .map(|address| {
+            let on_chain = blockchain.state.find_account(address);
+            //locked accounts are only known by their key file, so fall back to what the chain says
+            let is_contract = match keystore.get(&address) {
+                Some(local) => local.public_account.code_hash.is_some(),
+                None => on_chain.as_ref().is_some_and(|a| a.code_hash.is_some()),
+            };
+            AccountInfo {
+                address,
+                balance: on_chain.as_ref().map(|a| a.balance).unwrap_or_default(),
+                nonce: on_chain.as_ref().map_or(0, |a| a.nonce),
+                is_contract,
+                is_miner: address == miner_addr,
+                name: keystore.name_of(&address).cloned(),
+                pending: on_chain.is_none(),
+                locked: keystore.is_locked(&address),
+            }
+        })
+        .collect();
+    HttpResponse::Ok().json(&accounts)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreateAccountRequest {
+    pub name: String,
+    //encrypts the account's key file. Only needed if the node persists keys and wasn't started with a keystore password
+    pub passphrase: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreateAccountResponse {
+    pub name: String,
+    #[schema(value_type = String)]
+    pub address: Address,
+}
+
+/// generates a keypair and stores it in the node's keystore. No tx is sent -
+/// the account starts existing on chain the first time someone sends value to it
+#[utoipa::path(
+    post,
+    path = "/accounts",
+    tag = "accounts",
+    security(("bearer_auth" = [])),
+    request_body = CreateAccountRequest,
+    responses(
+        (status = 200, description = "account created", body = CreateAccountResponse),
+        (status = 400, description = "no passphrase to encrypt the key file with"),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 409, description = "name already taken"),
+    )
+)]
+#[post("/accounts")]
+pub async fn create_account(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    body: web::Json<CreateAccountRequest>,
+) -> impl Responder {
+    let mut keystore = global_state.keystore.write().unwrap();
+    if keystore.names.contains_key(&body.name) {
+        return HttpResponse::Conflict()
+            .body(format!("account named {} already exists.", body.name));
+    }
+
+    let account = Account::new(vec![]);
+    let address = account.public_account.address;
+    match keystore.add_named(&body.name, account, body.passphrase.as_deref()) {
+        Ok(()) => HttpResponse::Ok().json(CreateAccountResponse {
+            name: body.name.clone(),
+            address,
+        }),
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UnlockAccountRequest {
+    pub passphrase: String,
+}
+
+/// decrypts the key file of an account that stayed locked at startup, so the node can sign with it again
+#[utoipa::path(
+    post,
+    path = "/accounts/{address}/unlock",
+    tag = "accounts",
+    security(("bearer_auth" = [])),
+    params(("address" = String, Path, description = "0x-prefixed address or address book name")),
+    request_body = UnlockAccountRequest,
+    responses(
+        (status = 200, description = "account unlocked"),
+        (status = 400, description = "invalid address or wrong passphrase"),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 404, description = "no locked account with that address"),
+    )
+)]
+#[post("/accounts/{address}/unlock")]
+pub async fn unlock_account(
+    _auth: AdminAuth,
+    address: web::Path<String>,
+    global_state: web::Data<Arc<GlobalState>>,
+    body: web::Json<UnlockAccountRequest>,
+) -> impl Responder {
+    let address = match global_state.resolve_address(address.as_str()) {
+        Ok(address) => address,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let mut keystore = global_state.keystore.write().unwrap();
+    if !keystore.is_locked(&address) {
+        return HttpResponse::NotFound().body(format!("account {} is not locked.", address));
+    }
+    match keystore.unlock(&address, &body.passphrase) {
+        Ok(()) => HttpResponse::Ok().body(format!("account {} unlocked.", address)),
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SignMessageRequest {
+    #[schema(value_type = String)]
+    pub address: AddressInput,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SignedMessage {
+    #[schema(value_type = String)]
+    pub address: AddressInput,
+    pub message: String,
+    //0x prefixed r || s || v
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct VerifyMessageResponse {
+    pub valid: bool,
+    //whoever the signature recovers to, even if that's not the claimed address. None if it doesn't recover at all
+    #[schema(value_type = Option<String>)]
+    pub signer: Option<Address>,
+}
+
+/// personal_sign - signs an arbitrary message with one of the node's accounts, eg to prove who you are off chain
+#[utoipa::path(
+    post,
+    path = "/sign",
+    tag = "accounts",
+    security(("bearer_auth" = [])),
+    request_body = SignMessageRequest,
+    responses(
+        (status = 200, description = "the signed message", body = SignedMessage),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 404, description = "the node doesn't hold keys for that address"),
+        (status = 423, description = "the account is locked"),
+    )
+)]
+#[post("/sign")]
+pub async fn sign_message(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    body: web::Json<SignMessageRequest>,
+) -> impl Responder {
+    let address = match resolve_input(&global_state, &body.address) {
+        Ok(address) => address,
+        Err(res) => return res,
+    };
+    let keystore = global_state.keystore.read().unwrap();
+    let account = match keystore.get(&address) {
+        Some(account) => account,
+        None if keystore.is_locked(&address) => {
+            return HttpResponse::build(StatusCode::LOCKED)
+                .body(format!("account {} is locked, unlock it first.", address))
+        }
+        None => return HttpResponse::NotFound().body(format!("no account {}.", address)),
+    };
+    HttpResponse::Ok().json(SignedMessage {
+        address: address.into(),
+        message: body.message.clone(),
+        signature: message::sign_message(account, body.message.as_bytes()),
+    })
+}
+
+/// checks a signature from /sign (or any personal_sign compatible wallet). Needs no keys, so it's public
+#[utoipa::path(
+    post,
+    path = "/verify",
+    tag = "accounts",
+    request_body = SignedMessage,
+    responses((status = 200, description = "whether the address signed the message", body = VerifyMessageResponse))
+)]
+#[post("/verify")]
+pub async fn verify_message(
+    global_state: web::Data<Arc<GlobalState>>,
+    body: web::Json<SignedMessage>,
+) -> impl Responder {
+    let address = match resolve_input(&global_state, &body.address) {
+        Ok(address) => address,
+        Err(res) => return res,
+    };
+    let signer = message::recover_signer(body.message.as_bytes(), &body.signature).ok();
+    HttpResponse::Ok().json(VerifyMessageResponse {
+        valid: signer == Some(address),
+        signer,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/state",
+    tag = "state",
+    responses((status = 200, description = "the raw state trie", body = Object))
+)]
+#[get("/state")]
+pub async fn get_state(global_state: web::Data<Arc<GlobalState>>) -> impl Responder {
+    //cloning the trie is a lot cheaper than serializing it, so do that under the lock and serialize after
+    let trie = global_state
+        .blockchain
+        .read()
+        .unwrap()
+        .state
+        .state_trie
+        .clone();
+    HttpResponse::Ok().json(&trie)
+}
+
+#[utoipa::path(
+    get,
+    path = "/storage_trie",
+    tag = "state",
+    responses((status = 200, description = "address -> raw storage trie", body = Object))
+)]
+#[get("/storage_trie")]
+pub async fn get_storage_trie(global_state: web::Data<Arc<GlobalState>>) -> impl Responder {
+    let tries = global_state
+        .blockchain
+        .read()
+        .unwrap()
+        .state
+        .storage_trie_map
+        .clone();
+    HttpResponse::Ok().json(&tries)
+}
+
+/// for fast syncing a new node, see sync_from_snapshot(). Only the head block's state is kept, so that's the only
+/// block there's a snapshot of
+#[utoipa::path(
+    get,
+    path = "/snapshot/{block}",
+    tag = "state",
+    params(("block" = String, Path, description = "\"latest\" or the head block's number")),
+    responses(
+        (status = 200, description = "every account with a merkle proof against the state root, plus contract storage", body = Object),
+        (status = 404, description = "no such block"),
+        (status = 410, description = "not the head block - older state isn't kept"),
+    )
+)]
+#[get("/snapshot/{block}")]
+pub async fn get_snapshot(
+    block: web::Path<String>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    //copy under the lock, build the proofs after it's released
+    let (head, state) = {
+        let blockchain = global_state.blockchain.read().unwrap();
+        let head = blockchain.chain.len() - 1;
+        match blockchain.resolve_block_tag(&block) {
+            Some(number) if number == head => {}
+            Some(number) => {
+                return HttpResponse::Gone().body(format!(
+                    "no state kept for block {}, only for the head block ({}).",
+                    number, head
+                ))
+            }
+            None => return HttpResponse::NotFound().body(format!("block {} not found.", block)),
+        }
+        (blockchain.chain[head].clone(), blockchain.state.clone())
+    };
+    HttpResponse::Ok().json(Snapshot::take(&head, &state))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HeadBlock {
+    pub number: usize,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NodeInfo {
+    pub node_id: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub genesis_hash: String,
+    pub head_block: HeadBlock,
+    //connected gossip peers, see network::peer_count()
+    pub peer_count: Option<u64>,
+    //still catching up with a bootnode, see GET /sync for how far along
+    pub syncing: bool,
+    pub features: Vec<String>,
+    //the protocol rules the next block gets mined and validated under, eg "constantinople"
+    pub fork: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/nodeinfo",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "info about this node", body = NodeInfo),
+        (status = 401, description = "missing or invalid auth token"),
+    )
+)]
+#[get("/admin/nodeinfo")]
+pub async fn get_node_info(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    config: web::Data<NodeConfig>,
+) -> impl Responder {
+    //before the chain lock, see GlobalState for the order
+    let syncing = global_state.sync.lock().unwrap().state() == Lifecycle::Syncing;
+    let blockchain = global_state.blockchain.read().unwrap();
+    let chain = &blockchain.chain;
+    let head = chain.last().unwrap();
+
+    HttpResponse::Ok().json(NodeInfo {
+        node_id: global_state.node_id.to_string(),
+        version: env!("CARGO_PKG_VERSION").into(),
+        chain_id: config.chain_id,
+        genesis_hash: chain[0].hash(),
+        head_block: HeadBlock {
+            number: head.block_headers.truncated_block_headers.number,
+            hash: head.hash(),
+        },
+        peer_count: peer_count(&global_state),
+        syncing,
+        features: config.enabled_features(),
+        fork: blockchain
+            .rules
+            .fork_schedule
+            .fork_at(chain.len())
+            .name()
+            .into(),
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ConsensusClockInfo {
+    /// ms since the unix epoch - the genesis block's timestamp, where slot 0 starts
+    pub genesis_time: i64,
+    pub slot_duration_ms: u64,
+    pub slots_per_epoch: u64,
+    pub slot: u64,
+    pub epoch: u64,
+    pub next_slot_in_ms: u64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/consensus/clock",
+    tag = "node",
+    responses((status = 200, description = "the current slot and epoch", body = ConsensusClockInfo))
+)]
+#[get("/consensus/clock")]
+pub async fn get_consensus_clock(
+    global_state: web::Data<Arc<GlobalState>>,
+    config: web::Data<NodeConfig>,
+) -> impl Responder {
+    let clock = global_state
+        .blockchain
+        .read()
+        .unwrap()
+        .consensus_clock(config.slot_duration_ms, config.slots_per_epoch);
+    //one reading, so the slot and the time to the next one can't straddle a boundary
+    let now = clock.now();
+    let slot = clock.slot_at(now);
+    HttpResponse::Ok().json(ConsensusClockInfo {
+        genesis_time: clock.genesis_time,
+        slot_duration_ms: clock.slot_duration_ms,
+        slots_per_epoch: clock.slots_per_epoch,
+        slot,
+        epoch: clock.epoch_of(slot),
+        next_slot_in_ms: clock.next_slot_in(now),
+    })
+}
+
+/// the node's lifecycle, and while it's syncing how far behind it is
+#[utoipa::path(
+    get,
+    path = "/sync",
+    tag = "node",
+    responses((status = 200, description = "sync progress", body = SyncStatus))
+)]
+#[get("/sync")]
+pub async fn get_sync_status(global_state: web::Data<Arc<GlobalState>>) -> impl Responder {
+    HttpResponse::Ok().json(global_state.sync_status())
+}
+
+/// whether blocks from other nodes are still coming in. 503 while the node has peers but hasn't had a block from
+/// them in --partition-threshold block times, so a load balancer can take it out of rotation
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "node",
+    responses(
+        (status = 200, description = "blocks from peers are coming in, or there are no peers", body = PeerHealth),
+        (status = 503, description = "likely cut off from the rest of the network", body = PeerHealth)
+    )
+)]
+#[get("/health")]
+pub async fn get_health(global_state: web::Data<Arc<GlobalState>>) -> impl Responder {
+    let health = global_state.peer_health();
+    if health.partitioned {
+        HttpResponse::ServiceUnavailable().json(health)
+    } else {
+        HttpResponse::Ok().json(health)
+    }
+}
+
+/// stats on the blocks and tx coming in from other nodes, labelled by gossip topic / rabbitmq exchange, plus the
+/// numbers from GET /health and (with a datadir) the storage trie cache, in the prometheus text format
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "node",
+    responses((status = 200, description = "prometheus metrics", body = String, content_type = "text/plain"))
+)]
+#[get("/metrics")]
+pub async fn get_metrics(global_state: web::Data<Arc<GlobalState>>) -> impl Responder {
+    let mut text = metrics::render(&metrics::consumer_metrics());
+    text.push_str(&metrics::render_peer_health(&global_state.peer_health()));
+    let storage_cache = global_state
+        .blockchain
+        .read()
+        .unwrap()
+        .state
+        .storage_trie_map
+        .stats();
+    if let Some(stats) = storage_cache {
+        text.push_str(&metrics::render_storage_cache(&stats));
+    }
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(text)
+}
+
+/// when each tx first reached this node, as unix ms. Only kept with --measure-propagation
+#[utoipa::path(
+    get,
+    path = "/propagation",
+    tag = "node",
+    responses(
+        (status = 200, description = "tx hash -> when this node first saw it", body = FirstSeen),
+        (status = 404, description = "the node isn't measuring propagation"),
+    )
+)]
+#[get("/propagation")]
+pub async fn get_propagation(
+    global_state: web::Data<Arc<GlobalState>>,
+    config: web::Data<NodeConfig>,
+) -> impl Responder {
+    if !config.measure_propagation {
+        return HttpResponse::NotFound().body(NOT_MEASURING_PROPAGATION);
+    }
+    HttpResponse::Ok().json(first_seen(&global_state))
+}
+
+const NOT_MEASURING_PROPAGATION: &str =
+    "propagation isn't being measured, start the node with --measure-propagation.";
+
+fn first_seen(global_state: &GlobalState) -> FirstSeen {
+    FirstSeen {
+        node_id: global_state.node_id.to_string(),
+        first_seen: global_state
+            .propagation
+            .lock()
+            .unwrap()
+            .first_seen()
+            .clone(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PropagationQuery {
+    pub nodes: Option<String>,
+}
+
+/// this node's GET /propagation lined up with the other nodes' - see propagation::aggregate(). They all need to run
+/// with --measure-propagation
+#[utoipa::path(
+    get,
+    path = "/propagation/report",
+    tag = "node",
+    params(("nodes" = Option<String>, Query, description = "comma separated api urls of the other nodes, default the bootnodes")),
+    responses(
+        (status = 200, description = "how long tx took to reach the other nodes", body = PropagationReport),
+        (status = 404, description = "the node isn't measuring propagation"),
+    )
+)]
+#[get("/propagation/report")]
+pub async fn get_propagation_report(
+    query: web::Query<PropagationQuery>,
+    global_state: web::Data<Arc<GlobalState>>,
+    config: web::Data<NodeConfig>,
+) -> impl Responder {
+    if !config.measure_propagation {
+        return HttpResponse::NotFound().body(NOT_MEASURING_PROPAGATION);
+    }
+    let nodes = match &query.nodes {
+        Some(nodes) => nodes
+            .split(',')
+            .map(str::trim)
+            .filter(|node| !node.is_empty())
+            .map(String::from)
+            .collect(),
+        None => config.bootnodes.clone(),
+    };
+
+    let client = reqwest::Client::new();
+    let mut reports = vec![first_seen(&global_state)];
+    let mut unreachable = vec![];
+    for node in nodes {
+        match fetch_first_seen(&client, &node).await {
+            Ok(report) => reports.push(report),
+            Err(e) => unreachable.push(format!("{}: {}", node, e)),
+        }
+    }
+    let mut report = propagation::aggregate(&reports);
+    report.unreachable = unreachable;
+    HttpResponse::Ok().json(report)
+}
+
+async fn fetch_first_seen(client: &reqwest::Client, node: &str) -> Result<FirstSeen, NetError> {
+    let body = client
+        .get(format!("{}/propagation", node.trim_end_matches('/')))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    serde_json::from_str(&body)
+        .map_err(|e| NetError::Decode(format!("invalid first seen times: {}", e)))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RegisterWebhookRequest {
+    pub url: String,
+    /// leave out to get every event
+    #[serde(default)]
+    pub filter: WebhookFilterRequest,
+}
+
+/// a WebhookFilter as /webhooks takes it, with the address still to be resolved
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct WebhookFilterRequest {
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub address: Option<AddressInput>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WebhookRegistration {
+    pub id: String,
+    pub url: String,
+    pub filter: WebhookFilter,
+    /// key for the hmac in the X-Webhook-Signature header of every POST. Only ever shown here
+    pub secret: String,
+}
+
+/// new blocks, reorgs and receipts matching the filter get POSTed to the url as json, signed and retried.
+/// Webhooks only live in memory, so they need registering again after a restart
+#[utoipa::path(
+    post,
+    path = "/webhooks",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    request_body = RegisterWebhookRequest,
+    responses(
+        (status = 200, description = "webhook registered", body = WebhookRegistration),
+        (status = 400, description = "not an http(s) url"),
+        (status = 401, description = "missing or invalid auth token"),
+    )
+)]
+#[post("/webhooks")]
+pub async fn register_webhook(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    body: web::Json<RegisterWebhookRequest>,
+) -> impl Responder {
+    let body = body.into_inner();
+    let filter = match resolve_optional_input(&global_state, &body.filter.address) {
+        Ok(address) => WebhookFilter {
+            events: body.filter.events,
+            address,
+        },
+        Err(res) => return res,
+    };
+    let registered = global_state
+        .webhooks
+        .lock()
+        .unwrap()
+        .register(&body.url, filter);
+    match registered {
+        Ok(hook) => {
+            tracing::info!(webhook = %hook.id, url = %hook.url, "registered webhook");
+            HttpResponse::Ok().json(WebhookRegistration {
+                secret: hook.secret().into(),
+                id: hook.id,
+                url: hook.url,
+                filter: hook.filter,
+            })
+        }
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "registered webhooks, without their secrets", body = [Webhook]),
+        (status = 401, description = "missing or invalid auth token"),
+    )
+)]
+#[get("/webhooks")]
+pub async fn get_webhooks(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    HttpResponse::Ok().json(global_state.webhooks.lock().unwrap().list())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/webhooks/{id}",
+    tag = "node",
+    security(("bearer_auth" = [])),
+    params(("id" = String, Path, description = "id the webhook was registered under")),
+    responses(
+        (status = 200, description = "webhook removed"),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 404, description = "no webhook with that id"),
+    )
+)]
+#[delete("/webhooks/{id}")]
+pub async fn delete_webhook(
+    _auth: AdminAuth,
+    id: web::Path<String>,
+    global_state: web::Data<Arc<GlobalState>>,
+) -> impl Responder {
+    if global_state.webhooks.lock().unwrap().remove(&id) {
+        HttpResponse::Ok().body(format!("webhook {} removed.", id))
+    } else {
+        HttpResponse::NotFound().body(format!("no webhook {}.", id))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AddressBookEntry {
+    pub name: String,
+    //an existing name works too, the new one then points wherever that one does right now
+    #[schema(value_type = String)]
+    pub address: AddressInput,
+}
+
+/// the names the REST api takes in place of an address, for the chain the node is on. Local to this node
+#[utoipa::path(
+    get,
+    path = "/addressbook",
+    tag = "accounts",
+    responses((status = 200, description = "names and their addresses, by name", body = [NamedAddress]))
+)]
+#[get("/addressbook")]
+pub async fn get_address_book(global_state: web::Data<Arc<GlobalState>>) -> impl Responder {
+    let chain_id = global_state.blockchain.read().unwrap().chain_id();
+    HttpResponse::Ok().json(global_state.address_book.read().unwrap().entries(chain_id))
+}
+
+/// adds a name, or points an existing one at another address
+#[utoipa::path(
+    post,
+    path = "/addressbook",
+    tag = "accounts",
+    security(("bearer_auth" = [])),
+    request_body = AddressBookEntry,
+    responses(
+        (status = 200, description = "name saved", body = NamedAddress),
+        (status = 400, description = "invalid name or address"),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 500, description = "the address book couldn't be written to the datadir"),
+    )
+)]
+#[post("/addressbook")]
+pub async fn set_address_book_entry(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    body: web::Json<AddressBookEntry>,
+) -> impl Responder {
+    let body = body.into_inner();
+    if let Err(e) = address_book::validate_name(&body.name) {
+        return HttpResponse::BadRequest().body(e);
+    }
+    let address = match resolve_input(&global_state, &body.address) {
+        Ok(address) => address,
+        Err(res) => return res,
+    };
+    let chain_id = global_state.blockchain.read().unwrap().chain_id();
+    let saved = global_state
+        .address_book
+        .write()
+        .unwrap()
+        .set(chain_id, &body.name, address);
+    match saved {
+        Ok(_) => {
+            tracing::info!(name = %body.name, address = %address, "saved address book name");
+            HttpResponse::Ok().json(NamedAddress {
+                name: body.name,
+                address,
+            })
+        }
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/addressbook/{name}",
+    tag = "accounts",
+    security(("bearer_auth" = [])),
+    params(("name" = String, Path, description = "name to remove from the current chain's address book")),
+    responses(
+        (status = 200, description = "name removed"),
+        (status = 401, description = "missing or invalid auth token"),
+        (status = 404, description = "no such name"),
+        (status = 500, description = "the address book couldn't be written to the datadir"),
+    )
+)]
+#[delete("/addressbook/{name}")]
+pub async fn delete_address_book_entry(
+    _auth: AdminAuth,
+    global_state: web::Data<Arc<GlobalState>>,
+    name: web::Path<String>,
+) -> impl Responder {
+    let chain_id = global_state.blockchain.read().unwrap().chain_id();
+    let removed = global_state
+        .address_book
+        .write()
+        .unwrap()
+        .remove(chain_id, &name);
+    match removed {
+        Ok(Some(_)) => HttpResponse::Ok().body(format!("{} removed.", name)),
+        Ok(None) => HttpResponse::NotFound().body(format!("no name {}.", name)),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
+
+/// how many rounds through its bootnodes the node makes at startup before it gives up, see sync_with_retries()
+pub const SYNC_ATTEMPTS: u32 = 5;
+/// the wait after the first round that fails, doubled after every one after that
+pub const SYNC_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// sync_with_bootnodes() until a round of it works, up to SYNC_ATTEMPTS rounds. Gives bootnodes that are still
+/// coming up (eg the whole network starting at once) the time to. Gossip peers need none of this, they get redialed
+/// for as long as the node runs
+pub async fn sync_with_retries(
+    global_state: Arc<GlobalState>,
+    bootnodes: &[String],
+    fast_sync: bool,
+) -> Result<(), NetError> {
+    let mut wait = SYNC_RETRY_INTERVAL;
+    let mut attempt = 1;
+    loop {
+        match sync_with_bootnodes(global_state.clone(), bootnodes, fast_sync).await {
+            Err(e) if attempt < SYNC_ATTEMPTS => {
+                tracing::warn!(attempt, retry_in = ?wait, error = %e, "no bootnode to sync from, retrying");
+                tokio::time::sleep(wait).await;
+                wait *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// catches the node up with the first of bootnodes that works - the Syncing part of its lifecycle, see SyncTracker.
+/// Err if none of them did, the node doesn't get to Synced then. No bootnodes and there's nothing to catch up with
+pub async fn sync_with_bootnodes(
+    global_state: Arc<GlobalState>,
+    bootnodes: &[String],
+    fast_sync: bool,
+) -> Result<(), NetError> {
+    let mut result = Ok(());
+    for bootnode in bootnodes {
+        result = sync_from(global_state.clone(), bootnode, fast_sync).await;
+        match &result {
+            Ok(()) => {
+                tracing::info!(bootnode = %bootnode, "synced chain from bootnode");
+                break;
+            }
+            Err(e) => {
+                tracing::warn!(bootnode = %bootnode, error = %e, "failed to sync from bootnode")
+            }
+        }
+    }
+    if result.is_ok() {
+        global_state.sync.lock().unwrap().finish();
+    }
+    result
+}
+
+async fn sync_from(
+    global_state: Arc<GlobalState>,
+    bootnode: &str,
+    fast_sync: bool,
+) -> Result<(), NetError> {
+    //their head as of now is what we're aiming for, anything they mine meanwhile comes along with it
+    let body = reqwest::get(format!("{}/stats", bootnode.trim_end_matches('/')))
+        .await?
+        .text()
+        .await?;
+    let stats: ChainStats = serde_json::from_str(&body)
+        .map_err(|e| NetError::Decode(format!("invalid stats: {}", e)))?;
+    let (head, now) = {
+        let blockchain = global_state.blockchain.read().unwrap();
+        (blockchain.chain.len() - 1, blockchain.clock.now_millis())
+    };
+    global_state
+        .sync
+        .lock()
+        .unwrap()
+        .start(head, stats.height, now);
+    if fast_sync {
+        sync_from_snapshot(global_state, bootnode).await
+    } else {
+        replace_chain(global_state, bootnode).await
+    }
+}
+
+/// bootnode is the base url of the node to sync from, eg "http://localhost:8080".
+/// Downloads their chain a page of MAX_BLOCK_RANGE blocks at a time, checking and running each block as it arrives -
+/// so apart from the chain itself, only one page is ever held in memory
+pub async fn replace_chain(global_state: Arc<GlobalState>, bootnode: &str) -> Result<(), NetError> {
+    let bootnode = bootnode.trim_end_matches('/');
+    let client = reqwest::Client::new();
+    let number = |block: &Block| block.block_headers.truncated_block_headers.number;
+    let (mut height, head_hash) = {
+        let blockchain = global_state.blockchain.read().unwrap();
+        (
+            blockchain.chain.len() - 1,
+            blockchain.chain.last().unwrap().hash(),
+        )
+    };
+
+    //the first page starts at our head, to check their chain extends ours
+    let mut page = fetch_block_range(&client, bootnode, height).await?;
+    let extends_ours = match page.first() {
+        //every node has its own genesis, it just has to be for the same chain. Taking theirs doesn't touch the state
+        Some(genesis) if height == 0 => {
+            global_state.replace_chain(vec![genesis.clone()])?;
+            true
+        }
+        Some(block) => block.hash() == head_hash,
+        None => false,
+    };
+    if !extends_ours {
+        //a page at a time only works on top of our head - a fork has to be replayed from genesis, all of it at once
+        tracing::warn!(
+            bootnode,
+            height,
+            "bootnode's chain doesn't extend ours, downloading all of it"
+        );
+        return download_chain(global_state, bootnode).await;
+    }
+
+    loop {
+        let fetched = page.len();
+        //the page can start at or below what we have, eg the first one
+        let have = height;
+        for block in page.into_iter().skip_while(|block| number(block) <= have) {
+            height = number(&block);
+            //locks per block, never across an await
+            global_state.import_block(block)?;
+        }
+        if fetched < MAX_BLOCK_RANGE {
+            return Ok(());
+        }
+        tracing::debug!(height, "synced page of blocks");
+        page = fetch_block_range(&client, bootnode, height + 1).await?;
+    }
+}
+
+async fn fetch_block_range(
+    client: &reqwest::Client,
+    bootnode: &str,
+    from: usize,
+) -> Result<Vec<Block>, NetError> {
+    let body = client
+        .get(format!(
+            "{}/blocks/range?from={}&count={}",
+            bootnode, from, MAX_BLOCK_RANGE
+        ))
+        .send()
+        .await?
+        .text()
+        .await?;
+    serde_json::from_str(&body).map_err(|e| NetError::Decode(format!("invalid blocks: {}", e)))
+}
+
+//the whole chain in one go, for when ours has to be replaced rather than extended. Blockchain::replace_chain()
+// replays it from the genesis state, so nothing our own blocks did carries over
+async fn download_chain(global_state: Arc<GlobalState>, bootnode: &str) -> Result<(), NetError> {
+    //download first, lock after - never hold a lock across an await
+    let body = reqwest::get(format!("{}/blockchain", bootnode))
+        .await?
+        .text()
+        .await?;
+    let chain: Vec<Block> = serde_json::from_str(&body)
+        .map_err(|e| NetError::Decode(format!("invalid chain: {}", e)))?;
+    Ok(global_state.replace_chain(chain)?)
+}
+
+/// fast sync - the bootnode's head state from /snapshot, plus its blocks, which get checked but not run.
+/// Anything mined in between the two requests gets run on top as usual
+pub async fn sync_from_snapshot(
+    global_state: Arc<GlobalState>,
+    bootnode: &str,
+) -> Result<(), NetError> {
+    let bootnode = bootnode.trim_end_matches('/');
+    //snapshot first - by the time we ask for the chain it can only have grown
+    let body = reqwest::get(format!("{}/snapshot/latest", bootnode))
+        .await?
+        .text()
+        .await?;
+    let snapshot: Snapshot = serde_json::from_str(&body)
+        .map_err(|e| NetError::Decode(format!("invalid snapshot: {}", e)))?;
+    let body = reqwest::get(format!("{}/blockchain", bootnode))
+        .await?
+        .text()
+        .await?;
+    let mut chain: Vec<Block> = serde_json::from_str(&body)
+        .map_err(|e| NetError::Decode(format!("invalid chain: {}", e)))?;
+
+    //if a reorg dropped the snapshot's block in between, import_snapshot() rejects it
+    let newer = chain.split_off(std::cmp::min(snapshot.block_number + 1, chain.len()));
+    global_state.import_snapshot(chain.clone(), snapshot)?;
+    if !newer.is_empty() {
+        chain.extend(newer);
+        global_state.replace_chain(chain)?;
+    }
+    Ok(())
+}
+
+//the tests below are unit tests - they don't bother to actually mine blocks as they go. For that see integration tests in tests/ folder
+#[cfg(test)]
+mod tests {
+    use crate::account::address::Address;
+    use crate::account::address_book::NamedAddress;
+    use crate::account::keystore::Keystore;
+    use crate::account::multisig::MultisigConfig;
+    use crate::account::{gen_address, gen_keypair, Account, PublicAccount};
+    use secp256k1::{Message, Secp256k1};
+
+    use crate::api::middleware::REQUEST_ID_HEADER;
+    use crate::api::miner::AutoMinerStatus;
+    use crate::api::pubsub::process_transaction;
+    use crate::api::rpc::RpcResponse;
+    use crate::api::server::{
+        replace_chain, run_server, sync_from_snapshot, sync_with_bootnodes, AccountInfo,
+        AccountProof, AddressTx, BlockResponse, BlockTxSeries, ChainStats, ConsensusClockInfo,
+        ContractGasRanking, CosignRequest, CreateAccountRequest, CreateAccountResponse,
+        FaucetRequest, MultisigProposal, MultisigTx, NodeInfo, PendingBlockPreview,
+        PrepareTxRequest, SendSignedTxRequest, SignMessageRequest, SignedMessage, SigningPayload,
+        SponsorTxRequest, StorageSlot, SubmitTxRequest, TokenBalance, TokenSupply, TxProof,
+        TxRequest, TxResponse, UnlockAccountRequest, VerifyMessageResponse, WebhookRegistration,
+        WorkPackage, FAUCET_AMOUNT,
+    };
+    use crate::api::webhooks::WebhookEvent;
+    use crate::blockchain::block::Block;
+    use crate::blockchain::gas_stats::{BlockGasStats, ContractGas};
+    use crate::blockchain::sync::{Lifecycle, PeerHealth, SyncStatus};
+    use crate::config::{NodeConfig, DEFAULT_CHAIN_ID, DEV_MINER_BALANCE};
+    use crate::network::propagation::PropagationReport;
+    use crate::store::codec;
+    use crate::store::state::StateAccess;
+    use crate::store::trie::Trie;
+    use crate::telemetry::metrics;
+    use crate::transaction::activity::{Activity, Direction};
+    use crate::transaction::receipt::{Receipt, ReceiptStatus};
+
+    use crate::interpreter::{token, OPCODE};
+    use crate::transaction::tx::{Transaction, TxType};
+    use crate::transaction::tx_queue::TxStatus;
+
+    use crate::util::bigint::U256;
+    use crate::util::clock::ManualClock;
+    use crate::util::{prep_state, GlobalState};
+
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    /// mines whatever's in the tx queue on top of the current head, without going through rabbitmq
+    fn mine_local_block(global_state: &mut GlobalState) -> Block {
+        let beneficiary = global_state.miner_address;
+        let tx_series = global_state.tx_queue.get_mut().unwrap().get_tx_series();
+        let blockchain = global_state.blockchain.get_mut().unwrap();
+        let last_block = blockchain.chain.last().unwrap().clone();
+        let state_root = blockchain.state.get_state_root().clone();
+        let block = Block::mine_block(
+            &last_block,
+            beneficiary,
+            tx_series,
+            &state_root,
+            &*blockchain.clock,
+            &blockchain.rules,
+        );
+        blockchain.add_block(block.clone()).unwrap();
+        global_state
+            .tx_queue
+            .get_mut()
+            .unwrap()
+            .clear_block_tx(&block.tx_series);
+        block
+    }
+    use std::sync::Arc;
+
+    #[actix_rt::test]
+    async fn test_transact_endpoint() {
+        let global_state = prep_state();
+        let miner_addr = global_state.miner_address;
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let pk = gen_address();
+        //warning: do NOT try to deserialize with serde_json::to_string(), reqwest does it under the hood. Otherwise you'll fuck up the request body
+        let tx_request = TxRequest {
+            value: 123.into(),
+            to: Some(pk.into()),
+            code: vec![],
+            gas_limit: 100.into(),
+            from: None,
+            passphrase: None,
+            multisig: None,
+            fee_payer: None,
+            fee_payer_passphrase: None,
+            call_data: vec![],
+        };
+
+        let client = reqwest::Client::new();
+        let res = client
+            .post(format!("http://localhost:{}/transact", port))
+            .header("Content-Type", "application/json")
+            .json(&tx_request)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            res.status().as_u16(),
+            200,
+            "the api didn't respond with a 200.",
+        );
+
+        //can only deserialize once (moves the value)
+        let res_json = res.json::<TxResponse>().await.unwrap();
+        assert_eq!(res_json.tx_hash, res_json.tx.hash());
+        //the miner's account creation tx is still sitting in the queue, so there's nothing to validate against yet
+        assert_eq!(res_json.status, TxStatus::Queued);
+        assert_eq!(res_json.reason, None);
+        let res_json = res_json.tx;
+        assert_eq!(res_json.unsigned_tx.value, U256::from(123));
+        assert_eq!(res_json.unsigned_tx.to, Some(pk));
+        assert_eq!(res_json.unsigned_tx.from, Some(miner_addr));
+        assert_ne!(res_json.unsigned_tx.to, res_json.unsigned_tx.from);
+        assert_eq!(res_json.unsigned_tx.data.tx_type, TxType::Transact);
+    }
+
+    #[actix_rt::test]
+    async fn test_transact_endpoint_account_creation() {
+        let global_state = prep_state();
+        let _miner_addr = global_state.miner_address;
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let tx_request = TxRequest {
+            value: 123.into(),
+            to: None,
+            code: vec![],
+            gas_limit: 100.into(),
+            from: None,
+            passphrase: None,
+            multisig: None,
+            fee_payer: None,
+            fee_payer_passphrase: None,
+            call_data: vec![],
+        };
+
+        let client = reqwest::Client::new();
+        let res = client
+            .post(format!("http://localhost:{}/transact", port))
+            .header("Content-Type", "application/json")
+            .json(&tx_request)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            res.status().as_u16(),
+            200,
+            "the api didn't respond with a 200.",
+        );
+
+        let res_json = res.json::<TxResponse>().await.unwrap();
+        assert_eq!(res_json.status, TxStatus::Pending);
+        let res_json = res_json.tx;
+        assert_eq!(res_json.unsigned_tx.value, U256::from(123));
+        assert_eq!(res_json.unsigned_tx.to, None);
+        assert_eq!(res_json.unsigned_tx.from, None);
+        assert_eq!(res_json.unsigned_tx.data.tx_type, TxType::CreateAccount);
+    }
+
+    #[actix_rt::test]
+    async fn test_transact_endpoint_smart_contract_creation() {
+        let mut global_state = prep_state();
+        let miner_addr = global_state.miner_address;
+        //the miner pays to deploy the code
+        global_state
+            .blockchain
+            .get_mut()
+            .unwrap()
+            .state
+            .allocate(miner_addr, 1000);
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let code = vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(10),
+            OPCODE::PUSH,
+            OPCODE::VAL(5),
+            OPCODE::ADD,
+            OPCODE::STOP,
+        ];
+
+        let tx_request = TxRequest {
+            value: 123.into(),
+            to: None,
+            code,
+            //16 gas a byte, 4 bytes an opcode
+            gas_limit: 384.into(),
+            from: None,
+            passphrase: None,
+            multisig: None,
+            fee_payer: None,
+            fee_payer_passphrase: None,
+            call_data: vec![],
+        };
+
+        let client = reqwest::Client::new();
+        let res = client
+            .post(format!("http://localhost:{}/transact", port))
+            .header("Content-Type", "application/json")
+            .json(&tx_request)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            res.status().as_u16(),
+            200,
+            "the api didn't respond with a 200.",
+        );
+
+        let res_json = res.json::<TxResponse>().await.unwrap();
+        assert_eq!(res_json.status, TxStatus::Pending);
+        let res_json = res_json.tx;
+        assert_eq!(res_json.unsigned_tx.value, U256::from(123));
+        assert_eq!(res_json.unsigned_tx.to, None);
+        assert_eq!(res_json.unsigned_tx.from, None);
+        assert_eq!(res_json.unsigned_tx.fee_payer, Some(miner_addr));
+        assert_eq!(res_json.unsigned_tx.data.tx_type, TxType::CreateAccount);
+    }
+
+    #[actix_rt::test]
+    async fn test_transact_endpoint_rejects_invalid_tx() {
+        let mut global_state = prep_state();
+        //puts the miner's account on chain, with the 50 it got for mining the block
+        mine_local_block(&mut global_state);
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs.clone()).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let pk = gen_address();
+        let tx_request = TxRequest {
+            value: 1_000_000.into(),
+            to: Some(pk.into()),
+            code: vec![],
+            gas_limit: 100.into(),
+            from: None,
+            passphrase: None,
+            multisig: None,
+            fee_payer: None,
+            fee_payer_passphrase: None,
+            call_data: vec![],
+        };
+
+        let client = reqwest::Client::new();
+        let res = client
+            .post(format!("http://localhost:{}/transact", port))
+            .header("Content-Type", "application/json")
+            .json(&tx_request)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(res.status().as_u16(), 422);
+        let res_json = res.json::<TxResponse>().await.unwrap();
+        assert_eq!(res_json.status, TxStatus::Rejected);
+        assert_eq!(res_json.reason, Some("exceeded balance".to_string()));
+        assert_eq!(res_json.tx_hash, res_json.tx.hash());
+        //never made it into the pool
+        assert!(wrapped_gs.tx_queue.lock().unwrap().tx_map.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn test_transact_from_another_account() {
+        //bob's key file is locked under his own passphrase
+        let dir = std::env::temp_dir().join(format!("keystore-{}", uuid::Uuid::new_v4()));
+        let bob = Account::new(vec![]);
+        let bob_addr = bob.public_account.address;
+        let mut keystore = Keystore::new();
+        keystore.open(dir.clone(), None).unwrap();
+        keystore.add_named("bob", bob, Some("bob-pass")).unwrap();
+
+        let mut global_state = prep_state();
+        global_state
+            .keystore
+            .get_mut()
+            .unwrap()
+            .open(dir.clone(), None)
+            .unwrap();
+        global_state
+            .blockchain
+            .get_mut()
+            .unwrap()
+            .state
+            .allocate(bob_addr, 500);
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs.clone()).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let pk = gen_address();
+        let mut tx_request = TxRequest {
+            value: 123.into(),
+            to: Some(pk.into()),
+            code: vec![],
+            gas_limit: 100.into(),
+            from: Some(bob_addr.into()),
+            passphrase: None,
+            multisig: None,
+            fee_payer: None,
+            fee_payer_passphrase: None,
+            call_data: vec![],
+        };
+        let client = reqwest::Client::new();
+        let transact_url = format!("http://localhost:{}/transact", port);
+
+        let res = client
+            .post(&transact_url)
+            .json(&tx_request)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 423);
+
+        tx_request.passphrase = Some("bob-pass".into());
+        let res = client
+            .post(&transact_url)
+            .json(&tx_request)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let res_json = res.json::<TxResponse>().await.unwrap();
+        assert_eq!(res_json.status, TxStatus::Pending);
+        assert_eq!(res_json.tx.unsigned_tx.from, Some(bob_addr));
+        assert!(!wrapped_gs.keystore.read().unwrap().is_locked(&bob_addr));
+
+        //the node holds no keys for the receiver
+        tx_request.from = Some(pk.into());
+        let res = client
+            .post(&transact_url)
+            .json(&tx_request)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 404);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_multisig_flow() {
+        //a 2-of-2 multisig whose signers are both held by this node
+        let mut global_state = prep_state();
+        let alice = Account::new(vec![]);
+        let bob = Account::new(vec![]);
+        let mut multisig_account = Account::new(vec![]);
+        multisig_account.public_account.multisig = Some(MultisigConfig {
+            threshold: 2,
+            signers: vec![alice.public_account.address, bob.public_account.address],
+        });
+        let multisig_addr = multisig_account.public_account.address;
+        {
+            let keystore = global_state.keystore.get_mut().unwrap();
+            keystore.add(alice.clone());
+            keystore.add(bob.clone());
+            let state = &mut global_state.blockchain.get_mut().unwrap().state;
+            state.put_account(multisig_addr, multisig_account.public_account.clone());
+            state.allocate(multisig_addr, 100);
+        }
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let url = |path: &str| format!("http://localhost:{}/multisig/{}", port, path);
+        let receiver = gen_address();
+
+        let res = client
+            .post(url("propose"))
+            .json(&MultisigProposal {
+                from: multisig_addr.into(),
+                to: receiver.into(),
+                value: 10.into(),
+                gas_limit: 0.into(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let proposal = res.json::<MultisigTx>().await.unwrap();
+        assert_eq!(proposal.signatures, 0);
+        assert_eq!(proposal.threshold, 2);
+
+        let cosign = |tx: Transaction, signer: &Account| CosignRequest {
+            tx,
+            signer: signer.public_account.address.into(),
+            passphrase: None,
+        };
+        let res = client
+            .post(url("cosign"))
+            .json(&cosign(proposal.tx, &alice))
+            .send()
+            .await
+            .unwrap();
+        let half_signed = res.json::<MultisigTx>().await.unwrap();
+        assert_eq!(half_signed.signatures, 1);
+
+        //not enough signatures yet
+        let res = client
+            .post(url("submit"))
+            .json(&SubmitTxRequest {
+                tx: half_signed.tx.clone(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 422);
+        assert_eq!(
+            res.json::<TxResponse>().await.unwrap().reason,
+            Some("not enough multisig signatures: 1 of 2".to_string())
+        );
+
+        //someone outside the signer set can't help
+        let res = client
+            .post(url("cosign"))
+            .json(&cosign(half_signed.tx.clone(), &multisig_account))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 400);
+
+        let res = client
+            .post(url("cosign"))
+            .json(&cosign(half_signed.tx, &bob))
+            .send()
+            .await
+            .unwrap();
+        let signed = res.json::<MultisigTx>().await.unwrap();
+        assert_eq!(signed.signatures, 2);
+
+        let res = client
+            .post(url("submit"))
+            .json(&SubmitTxRequest { tx: signed.tx })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_eq!(
+            res.json::<TxResponse>().await.unwrap().status,
+            TxStatus::Pending
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_external_signing_flow() {
+        //the key only ever lives in the test, standing in for a hardware wallet
+        let (sk, public_key) = gen_keypair();
+        let address = Address::from_public_key(&public_key);
+        let mut global_state = prep_state();
+        global_state
+            .blockchain
+            .get_mut()
+            .unwrap()
+            .state
+            .allocate(address, 100);
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let receiver = gen_address();
+        let res = client
+            .post(format!("http://localhost:{}/tx/prepare", port))
+            .json(&PrepareTxRequest {
+                from: address.into(),
+                to: Some(receiver.into()),
+                value: 10.into(),
+                code: vec![],
+                gas_limit: 0.into(),
+                nonce: None,
+                fee_payer: None,
+                call_data: vec![],
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let payload = res.json::<SigningPayload>().await.unwrap();
+
+        let msg = Message::from_slice(&hex::decode(&payload.signing_hash).unwrap()).unwrap();
+        let signature = hex::encode(Secp256k1::new().sign(&msg, &sk).serialize_compact());
+
+        //signed by the wrong key
+        let (other_sk, _) = gen_keypair();
+        let wrong_signature =
+            hex::encode(Secp256k1::new().sign(&msg, &other_sk).serialize_compact());
+        let res = client
+            .post(format!("http://localhost:{}/tx/send", port))
+            .json(&SendSignedTxRequest {
+                unsigned_tx: payload.unsigned_tx.clone(),
+                signature: wrong_signature,
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 400);
+
+        let res = client
+            .post(format!("http://localhost:{}/tx/send", port))
+            .json(&SendSignedTxRequest {
+                unsigned_tx: payload.unsigned_tx,
+                signature,
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let res_json = res.json::<TxResponse>().await.unwrap();
+        assert_eq!(res_json.status, TxStatus::Pending);
+        assert_eq!(res_json.tx.unsigned_tx.from, Some(address));
+    }
+
+    #[actix_rt::test]
+    async fn test_sponsored_signing_flow() {
+        let (sk, public_key) = gen_keypair();
+        let address = Address::from_public_key(&public_key);
+        let mut global_state = prep_state();
+        let miner_addr = global_state.miner_address;
+        let state = &mut global_state.blockchain.get_mut().unwrap().state;
+        //covers the value, but not the gas on top
+        state.allocate(address, 10);
+        state.allocate(miner_addr, 1000);
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        tokio::spawn(run_server(&config, wrapped_gs).unwrap());
+
+        let client = reqwest::Client::new();
+        let url = |path: &str| format!("http://localhost:{}/tx/{}", port, path);
+        let prepare = |fee_payer: Option<Address>| PrepareTxRequest {
+            from: address.into(),
+            to: Some(gen_address().into()),
+            value: 10.into(),
+            code: vec![],
+            gas_limit: 50.into(),
+            nonce: None,
+            fee_payer: fee_payer.map(Into::into),
+            call_data: vec![],
+        };
+        let sign = |payload: &SigningPayload| {
+            let msg = Message::from_slice(&hex::decode(&payload.signing_hash).unwrap()).unwrap();
+            hex::encode(Secp256k1::new().sign(&msg, &sk).serialize_compact())
+        };
+
+        let res = client
+            .post(url("prepare"))
+            .json(&prepare(Some(miner_addr)))
+            .send()
+            .await
+            .unwrap();
+        let payload = res.json::<SigningPayload>().await.unwrap();
+        assert_eq!(payload.unsigned_tx.fee_payer, Some(miner_addr));
+
+        //the sender's signature alone doesn't do
+        let res = client
+            .post(url("send"))
+            .json(&SendSignedTxRequest {
+                unsigned_tx: payload.unsigned_tx.clone(),
+                signature: sign(&payload),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 422);
+        assert_eq!(
+            res.json::<TxResponse>().await.unwrap().reason,
+            Some("fee payer signature missing".to_string())
+        );
+
+        let res = client
+            .post(url("sponsor"))
+            .json(&SponsorTxRequest {
+                unsigned_tx: payload.unsigned_tx.clone(),
+                signature: sign(&payload),
+                passphrase: None,
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let res_json = res.json::<TxResponse>().await.unwrap();
+        assert_eq!(res_json.status, TxStatus::Pending);
+        assert!(res_json.tx.fee_payer_signature.is_some());
+
+        //nothing to sponsor
+        let res = client
+            .post(url("prepare"))
+            .json(&prepare(None))
+            .send()
+            .await
+            .unwrap();
+        let payload = res.json::<SigningPayload>().await.unwrap();
+        let res = client
+            .post(url("sponsor"))
+            .json(&SponsorTxRequest {
+                signature: sign(&payload),
+                unsigned_tx: payload.unsigned_tx,
+                passphrase: None,
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 400);
+    }
+
+    #[actix_rt::test]
+    async fn test_faucet_only_in_dev_mode() {
+        let mut global_state = prep_state();
+        let miner_addr = global_state.miner_address;
+        global_state
+            .blockchain
+            .get_mut()
+            .unwrap()
+            .state
+            .allocate(miner_addr, DEV_MINER_BALANCE);
+        mine_local_block(&mut global_state);
+        let wrapped_gs = Arc::new(global_state);
+        let dev_port = rand::random::<u16>();
+        let port = rand::random::<u16>();
+
+        let dev_config = NodeConfig {
+            port: dev_port,
+            dev: true,
+            ..NodeConfig::default()
+        };
+        tokio::spawn(run_server(&dev_config, wrapped_gs.clone()).unwrap());
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        tokio::spawn(run_server(&config, wrapped_gs).unwrap());
+
+        let pk = gen_address();
+        let faucet_request = FaucetRequest {
+            address: pk.into(),
+            amount: None,
+        };
+        let client = reqwest::Client::new();
+        let res = client
+            .post(format!("http://localhost:{}/faucet", port))
+            .json(&faucet_request)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 404);
+
+        let res = client
+            .post(format!("http://localhost:{}/faucet", dev_port))
+            .json(&faucet_request)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let res_json = res.json::<TxResponse>().await.unwrap();
+        assert_eq!(res_json.status, TxStatus::Pending);
+        assert_eq!(res_json.tx.unsigned_tx.from, Some(miner_addr));
+        assert_eq!(res_json.tx.unsigned_tx.to, Some(pk));
+        assert_eq!(res_json.tx.unsigned_tx.value, U256::from(FAUCET_AMOUNT));
+    }
+
+    #[actix_rt::test]
+    async fn test_pending_block() {
+        let wrapped_gs = Arc::new(prep_state());
+        let port = rand::random::<u16>();
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs.clone()).unwrap();
+        tokio::spawn(server);
+
+        let preview = reqwest::get(format!("http://localhost:{}/miner/pending_block", port))
+            .await
+            .unwrap()
+            .json::<PendingBlockPreview>()
+            .await
+            .unwrap();
+        assert_eq!(preview.number, 1);
+        //the account creation tx prep_state() queues, plus the reward
+        assert_eq!(preview.tx.len(), 2);
+        assert!(preview.dropped.is_empty());
+        assert!(preview.deferred.is_empty());
+        assert!(preview.already_mined.is_empty());
+        //nothing got mined or taken out of the queue
+        assert_eq!(wrapped_gs.blockchain.read().unwrap().chain.len(), 1);
+        assert_eq!(wrapped_gs.tx_queue.lock().unwrap().tx_map.len(), 1);
+
+        //mining the block for real lands on the projected state
+        let miner = wrapped_gs.miner_account();
+        let queued = wrapped_gs.tx_queue.lock().unwrap().get_tx_series();
+        let block = {
+            let blockchain = wrapped_gs.blockchain.read().unwrap();
+            Block::mine_block_signed(
+                blockchain.chain.last().unwrap(),
+                &miner,
+                queued,
+                blockchain.state.get_state_root(),
+                &*blockchain.clock,
+                &blockchain.rules,
+            )
+        };
+        assert_eq!(block.tx_series[0].hash(), preview.tx[0].tx_hash);
+        wrapped_gs.import_block(block).unwrap();
+        assert_eq!(
+            wrapped_gs.blockchain.read().unwrap().state.get_state_root(),
+            &preview.state_root
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_get_balance() {
+        let global_state = prep_state();
+        let miner_addr = global_state.miner_address;
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+
+        //need to mine the first block to get the miner address written into the trie
+        //we don't need rabbitmq running for this because we mine the block right in the /mine endpoint function
+        client
+            .get(format!("http://localhost:{}/mine", port))
+            .send()
+            .await
+            .expect("mining failed");
+
+        let res = client
+            .get(format!("http://localhost:{}/balance/{}", port, miner_addr))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            res.status().as_u16(),
+            200,
+            "the api didn't respond with a 200.",
+        );
+        let res_json = res.json::<HashMap<String, U256>>().await.unwrap();
+        assert_eq!(res_json.get("balance").unwrap().to_owned(), U256::from(50));
+    }
+
+    #[actix_rt::test]
+    async fn test_get_block_by_number() {
+        let mut global_state = prep_state();
+
+        //mine a block locally (no rabbitmq needed) so the chain has something other than genesis
+        let block = mine_local_block(&mut global_state);
+
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let res = client
+            .get(format!("http://localhost:{}/block/1", port))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            res.status().as_u16(),
+            200,
+            "the api didn't respond with a 200.",
+        );
+
+        let res_json = res.json::<BlockResponse>().await.unwrap();
+        assert_eq!(res_json.hash, block.hash());
+        assert_eq!(res_json.block_headers.truncated_block_headers.number, 1);
+        match res_json.tx_series {
+            BlockTxSeries::Hashes(hashes) => assert_eq!(hashes.len(), 2), //the miner's account creation + mining reward
+            BlockTxSeries::Full(_) => panic!("expected tx hashes, got full txs"),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_get_tx_proof() {
+        let mut global_state = prep_state();
+        let block = mine_local_block(&mut global_state);
+        let tx_hash = block.tx_series[0].hash();
+
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let res = client
+            .get(format!(
+                "http://localhost:{}/block/1/tx_proof/{}",
+                port, tx_hash
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+
+        //verify it the way a light client would - only trusting the block header's tx_root
+        let res_json = res.json::<TxProof>().await.unwrap();
+        let tx_root = &block.block_headers.truncated_block_headers.tx_root;
+        assert_eq!(&res_json.tx_root, tx_root);
+        let encoded_tx = Trie::verify_proof(tx_root, &tx_hash, &res_json.proof).unwrap();
+        let tx: Transaction = codec::decode_canonical(&hex::decode(encoded_tx).unwrap()).unwrap();
+        assert_eq!(tx.hash(), tx_hash);
+
+        //genesis has no tx
+        let res = client
+            .get(format!(
+                "http://localhost:{}/block/0/tx_proof/{}",
+                port, tx_hash
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 404);
+    }
+
+    #[actix_rt::test]
+    async fn test_get_account_proof() {
+        let mut global_state = prep_state();
+        mine_local_block(&mut global_state);
+        let miner_addr = global_state.miner_address;
+        let miner_balance = global_state
+            .blockchain
+            .read()
+            .unwrap()
+            .state
+            .get_account_or_empty(miner_addr)
+            .balance;
+
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let get = |address: String| {
+            client
+                .get(format!("http://localhost:{}/proof/{}", port, address))
+                .send()
+        };
+        let res = get(miner_addr.to_string()).await.unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+
+        //verify it the way a light client would - only the root and the proof, no state
+        let res_json = res.json::<AccountProof>().await.unwrap();
+        assert_eq!(res_json.block_number, 1);
+        let account_json =
+            Trie::verify_proof(&res_json.state_root, &miner_addr.to_hex(), &res_json.proof)
+                .unwrap();
+        let account: PublicAccount = serde_json::from_str(&account_json).unwrap();
+        assert_eq!(account.balance, miner_balance);
+        //and the proof is only good for the address it was made for
+        let other = gen_address();
+        assert!(
+            Trie::verify_proof(&res_json.state_root, &other.to_hex(), &res_json.proof).is_none()
+        );
+
+        assert_eq!(get(other.to_string()).await.unwrap().status().as_u16(), 404);
+        assert_eq!(get("nope".into()).await.unwrap().status().as_u16(), 400);
+    }
+
+    #[actix_rt::test]
+    async fn test_get_block_by_hash_with_full_tx() {
+        let global_state = prep_state();
+        let genesis_hash = global_state.blockchain.read().unwrap().chain[0].hash();
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let res = client
+            .get(format!(
+                "http://localhost:{}/block/{}?full_tx=true",
+                port, genesis_hash
+            ))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            res.status().as_u16(),
+            200,
+            "the api didn't respond with a 200.",
+        );
+
+        let res_json = res.json::<BlockResponse>().await.unwrap();
+        assert_eq!(res_json.hash, genesis_hash);
+        assert_eq!(res_json.block_headers.truncated_block_headers.number, 0);
+    }
+
+    #[actix_rt::test]
+    async fn test_get_block_not_found() {
+        let global_state = prep_state();
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let res = client
+            .get(format!("http://localhost:{}/block/42", port))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(res.status().as_u16(), 404);
+    }
+
+    #[actix_rt::test]
+    async fn test_get_receipt() {
+        let mut global_state = prep_state();
+
+        //mine a block locally (no rabbitmq needed) so the account creation tx from prep_state() get receipts
+        let block = mine_local_block(&mut global_state);
+        let tx_hash = block.tx_series[0].hash();
+
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let res = client
+            .get(format!("http://localhost:{}/receipt/{}", port, tx_hash))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            res.status().as_u16(),
+            200,
+            "the api didn't respond with a 200.",
+        );
+
+        let res_json = res.json::<Receipt>().await.unwrap();
+        assert_eq!(res_json.tx_hash, tx_hash);
+        assert_eq!(res_json.block_number, 1);
+        assert_eq!(res_json.block_hash, block.hash());
+        assert_eq!(res_json.status, ReceiptStatus::Success);
+
+        let res = client
+            .get(format!("http://localhost:{}/receipt/not-a-real-hash", port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 404);
+    }
+
+    #[actix_rt::test]
+    async fn test_mine_requires_auth_token() {
+        let global_state = prep_state();
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            auth_token: Some("secret".into()),
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let res = client
+            .get(format!("http://localhost:{}/mine", port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 401);
+
+        let res = client
+            .get(format!("http://localhost:{}/mine", port))
+            .header("Authorization", "Bearer wrong")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 401);
+
+        //read endpoints stay public
+        let res = client
+            .get(format!("http://localhost:{}/blockchain", port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+    }
+
+    #[actix_rt::test]
+    async fn test_node_limits_from_config() {
+        let global_state = prep_state();
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            mining: false,
+            max_gas_limit: 10,
+            ..NodeConfig::default()
+        };
+        tokio::spawn(run_server(&config, wrapped_gs).unwrap());
+
+        let client = reqwest::Client::new();
+        let res = client
+            .get(format!("http://localhost:{}/mine", port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 403);
+
+        let pk = gen_address();
+        let tx_request = TxRequest {
+            value: 1.into(),
+            to: Some(pk.into()),
+            code: vec![],
+            gas_limit: 11.into(),
+            from: None,
+            passphrase: None,
+            multisig: None,
+            fee_payer: None,
+            fee_payer_passphrase: None,
+            call_data: vec![],
+        };
+        let res = client
+            .post(format!("http://localhost:{}/transact", port))
+            .json(&tx_request)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 422);
+        let res_json = res.json::<TxResponse>().await.unwrap();
+        assert_eq!(res_json.status, TxStatus::Rejected);
+        assert!(res_json.reason.unwrap().contains("maximum of 10"));
+    }
+
+    #[actix_rt::test]
+    async fn test_cors_preflight() {
+        let global_state = prep_state();
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            cors_origins: vec!["http://explorer.local".into()],
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let res = client
+            .request(
+                reqwest::Method::OPTIONS,
+                format!("http://localhost:{}/blockchain", port),
+            )
+            .header("Origin", "http://explorer.local")
+            .header("Access-Control-Request-Method", "GET")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_eq!(
+            res.headers()
+                .get("access-control-allow-origin")
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            "http://explorer.local"
+        );
+
+        //origins that weren't configured don't get the header
+        let res = client
+            .get(format!("http://localhost:{}/blockchain", port))
+            .header("Origin", "http://evil.local")
+            .send()
+            .await
+            .unwrap();
+        assert!(res.headers().get("access-control-allow-origin").is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_serves_over_tls() {
+        let global_state = prep_state();
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            tls_cert: Some("tests/fixtures/cert.pem".into()),
+            tls_key: Some("tests/fixtures/key.pem".into()),
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        //the fixture cert is self-signed
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap();
+        let res = client
+            .get(format!("https://localhost:{}/blockchain", port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+    }
+
+    #[actix_rt::test]
+    async fn test_get_accounts() {
+        let mut global_state = prep_state();
+        let miner_addr = global_state.miner_address;
+
+        //mine a block locally (no rabbitmq needed) so the accounts from prep_state() get written to the state trie
+        mine_local_block(&mut global_state);
+
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let res = client
+            .get(format!("http://localhost:{}/accounts", port))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            res.status().as_u16(),
+            200,
+            "the api didn't respond with a 200.",
+        );
+
+        //just the miner from prep_state()
+        let res_json = res.json::<Vec<AccountInfo>>().await.unwrap();
+        assert_eq!(res_json.len(), 1);
+
+        let miner = res_json.iter().find(|a| a.is_miner).unwrap();
+        assert_eq!(miner.address, miner_addr);
+        assert_eq!(miner.balance, U256::from(50));
+        assert!(!miner.is_contract);
+        assert!(!miner.pending);
+    }
+
+    #[actix_rt::test]
+    async fn test_create_account() {
+        let global_state = prep_state();
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let req = CreateAccountRequest {
+            name: "alice".into(),
+            passphrase: None,
+        };
+        let res = client
+            .post(format!("http://localhost:{}/accounts", port))
+            .json(&req)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            res.status().as_u16(),
+            200,
+            "the api didn't respond with a 200.",
+        );
+        let created = res.json::<CreateAccountResponse>().await.unwrap();
+        assert_eq!(created.name, "alice");
+
+        //names are unique
+        let res = client
+            .post(format!("http://localhost:{}/accounts", port))
+            .json(&req)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 409);
+
+        let accounts = client
+            .get(format!("http://localhost:{}/accounts", port))
+            .send()
+            .await
+            .unwrap()
+            .json::<Vec<AccountInfo>>()
+            .await
+            .unwrap();
+        let alice = accounts
+            .iter()
+            .find(|a| a.address == created.address)
+            .unwrap();
+        assert_eq!(alice.name, Some("alice".into()));
+        assert!(alice.pending);
+        assert!(!alice.locked);
+    }
+
+    #[actix_rt::test]
+    async fn test_unlock_account() {
+        //persist an account under its own passphrase, then start a node that doesn't know it
+        let dir = std::env::temp_dir().join(format!("keystore-{}", uuid::Uuid::new_v4()));
+        let account = Account::new(vec![]);
+        let address = account.public_account.address;
+        let mut keystore = Keystore::new();
+        keystore.open(dir.clone(), None).unwrap();
+        keystore
+            .add_named("bob", account, Some("bob-pass"))
+            .unwrap();
+
+        let mut global_state = prep_state();
+        global_state
+            .keystore
+            .get_mut()
+            .unwrap()
+            .open(dir.clone(), None)
+            .unwrap();
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs.clone()).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let unlock_url = format!("http://localhost:{}/accounts/{}/unlock", port, address);
+        let res = client
+            .post(&unlock_url)
+            .json(&UnlockAccountRequest {
+                passphrase: "wrong".into(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 400);
+        assert!(wrapped_gs.keystore.read().unwrap().is_locked(&address));
+
+        let res = client
+            .post(&unlock_url)
+            .json(&UnlockAccountRequest {
+                passphrase: "bob-pass".into(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert!(wrapped_gs.keystore.read().unwrap().get(&address).is_some());
+
+        //nothing left to unlock
+        let res = client
+            .post(&unlock_url)
+            .json(&UnlockAccountRequest {
+                passphrase: "bob-pass".into(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 404);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_sign_and_verify_message() {
+        let global_state = prep_state();
+        let miner_addr = global_state.miner_address;
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let signed = client
+            .post(format!("http://localhost:{}/sign", port))
+            .json(&SignMessageRequest {
+                address: miner_addr.into(),
+                message: "log me in".into(),
+            })
+            .send()
+            .await
+            .unwrap()
+            .json::<SignedMessage>()
+            .await
+            .unwrap();
+
+        let verified = client
+            .post(format!("http://localhost:{}/verify", port))
+            .json(&signed)
+            .send()
+            .await
+            .unwrap()
+            .json::<VerifyMessageResponse>()
+            .await
+            .unwrap();
+        assert!(verified.valid);
+        assert_eq!(verified.signer, Some(miner_addr));
+
+        //same signature, different message
+        let tampered = SignedMessage {
+            message: "log me out".into(),
+            ..signed
+        };
+        let verified = client
+            .post(format!("http://localhost:{}/verify", port))
+            .json(&tampered)
+            .send()
+            .await
+            .unwrap()
+            .json::<VerifyMessageResponse>()
+            .await
+            .unwrap();
+        assert!(!verified.valid);
+
+        //only accounts the node holds keys for
+        let stranger = gen_address();
+        let res = client
+            .post(format!("http://localhost:{}/sign", port))
+            .json(&SignMessageRequest {
+                address: stranger.into(),
+                message: "log me in".into(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 404);
+    }
+
+    #[actix_rt::test]
+    async fn test_openapi_and_docs() {
+        let global_state = prep_state();
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let res = client
+            .get(format!("http://localhost:{}/openapi.json", port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let spec = res.json::<serde_json::Value>().await.unwrap();
+        assert!(spec["paths"]["/block/{number_or_hash}"].is_object());
+
+        let res = client
+            .get(format!("http://localhost:{}/docs", port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert!(res.text().await.unwrap().contains("/openapi.json"));
+    }
+
+    #[actix_rt::test]
+    async fn test_consensus_clock() {
+        let mut global_state = prep_state();
+        let blockchain = global_state.blockchain.get_mut().unwrap();
+        let genesis_time = blockchain.chain[0]
+            .block_headers
+            .truncated_block_headers
+            .timestamp;
+        blockchain.clock = Arc::new(ManualClock::new(genesis_time + 2_500));
+        let port = rand::random::<u16>();
+        let config = NodeConfig {
+            port,
+            slot_duration_ms: 1_000,
+            slots_per_epoch: 2,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, Arc::new(global_state)).unwrap();
+        tokio::spawn(server);
+
+        let res = reqwest::get(format!("http://localhost:{}/consensus/clock", port))
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let res_json = res.json::<ConsensusClockInfo>().await.unwrap();
+        assert_eq!(res_json.genesis_time, genesis_time);
+        assert_eq!(res_json.slot, 2);
+        assert_eq!(res_json.epoch, 1);
+        assert_eq!(res_json.next_slot_in_ms, 500);
+    }
+
+    #[actix_rt::test]
+    async fn test_node_info() {
+        let global_state = prep_state();
+        let node_id = global_state.node_id.to_string();
+        let genesis_hash = global_state.blockchain.read().unwrap().chain[0].hash();
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            chain_id: 42,
+            auth_token: Some("secret".into()),
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+
+        let client = reqwest::Client::new();
+        let res = client
+            .get(format!("http://localhost:{}/admin/nodeinfo", port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 401);
+
+        let res = client
+            .get(format!("http://localhost:{}/admin/nodeinfo", port))
+            .header("Authorization", "Bearer secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            res.status().as_u16(),
+            200,
+            "the api didn't respond with a 200.",
+        );
+
+        let res_json = res.json::<NodeInfo>().await.unwrap();
+        assert_eq!(res_json.node_id, node_id);
+        assert_eq!(res_json.chain_id, 42);
+        assert_eq!(res_json.genesis_hash, genesis_hash);
+        assert_eq!(res_json.head_block.number, 0);
+        assert_eq!(res_json.head_block.hash, genesis_hash);
+        assert_eq!(res_json.features, vec!["auth"]);
+        assert_eq!(res_json.fork, "frontier");
+    }
+
+    #[actix_rt::test]
+    async fn test_explorer_endpoints() {
+        let mut global_state = prep_state();
+        let miner_addr = global_state.miner_address;
+        mine_local_block(&mut global_state);
+        let head = mine_local_block(&mut global_state);
+
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server);
+
+        let client = reqwest::Client::new();
+
+        // /stats
+        let res = client
+            .get(format!("http://localhost:{}/stats", port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let stats = res.json::<ChainStats>().await.unwrap();
+        assert_eq!(stats.height, 2);
+        assert_eq!(stats.total_txs, 3); //the miner's account creation + 2 mining rewards
+        assert!(stats.average_block_time.is_some());
+        assert_eq!(
+            stats.difficulty,
+            head.block_headers.truncated_block_headers.difficulty
+        );
+        //blocks 2 and 1, genesis has none
+        let gas_blocks: Vec<usize> = stats
+            .recent_gas_stats
+            .iter()
+            .map(|gas| gas.block_number)
+            .collect();
+        assert_eq!(gas_blocks, vec![2, 1]);
+        assert_eq!(stats.recent_gas_stats[0].tx_count, 1);
+
+        // /blocks/latest
+        let res = client
+            .get(format!("http://localhost:{}/blocks/latest?n=2", port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let blocks = res.json::<Vec<BlockResponse>>().await.unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].hash, head.hash());
+        assert_eq!(blocks[1].block_headers.truncated_block_headers.number, 1);
+        assert_eq!(blocks[1].gas_stats.as_ref().unwrap().tx_count, 2);
+
+        // /address/{address}/txs
+        let res = client
+            .get(format!(
+                "http://localhost:{}/address/{}/txs",
+                port, miner_addr
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let txs = res.json::<Vec<AddressTx>>().await.unwrap();
+        //account creation + 2 mining rewards, newest first
+        assert_eq!(txs.len(), 3);
+        assert_eq!(txs[0].block_number, 2);
+        assert_eq!(txs[0].hash, txs[0].tx.hash());
+
+        let res = client
+            .get(format!("http://localhost:{}/address/nonsense/txs", port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 400);
+
+        // /address/{address}/history
+        let res = client
+            .get(format!(
+                "http://localhost:{}/address/{}/history",
+                port, miner_addr
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let history = res.json::<Vec<Activity>>().await.unwrap();
+        //same tx as above, newest first
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].block_number, 2);
+        assert_eq!(history[0].tx_hash, txs[0].hash);
+        assert_eq!(history[0].direction, Direction::In);
+        assert_eq!(history[0].value, U256::from(50));
+        assert_eq!(history[2].direction, Direction::Created);
+    }
+
+    #[actix_rt::test]
+    async fn test_contract_gas_ranking() {
+        let global_state = prep_state();
+        let busy = Account::new(vec![OPCODE::STOP]);
+        let thrifty = Account::new(vec![OPCODE::PUSH, OPCODE::VAL(1), OPCODE::STOP]);
+        {
+            let mut blockchain = global_state.blockchain.write().unwrap();
+            for contract in [&busy, &thrifty].iter() {
+                let account = contract.public_account.clone();
+                blockchain.state.put_account(account.address, account);
+            }
+            //as if blocks 1 and 2 had called them
+            let called = |block_number, contracts| BlockGasStats {
+                block_number,
+                tx_count: 0,
+                gas_used: 0,
+                gas_limit: 0.into(),
+                average_gas_price: None,
+                contracts,
+            };
+            let contract_gas = |account: &Account, calls, gas_used| ContractGas {
+                address: account.public_account.address,
+                calls,
+                gas_used,
+            };
+            blockchain.gas_stats.insert(
+                1,
+                called(
+                    1,
+                    vec![contract_gas(&busy, 3, 30), contract_gas(&thrifty, 1, 30)],
+                ),
+            );
+            blockchain
+                .gas_stats
+                .insert(2, called(2, vec![contract_gas(&busy, 1, 10)]));
+        }
+        let port = rand::random::<u16>();
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, Arc::new(global_state)).unwrap();
+        tokio::spawn(server);
+
+        let ranking = |query: &'static str| async move {
+            reqwest::get(format!(
+                "http://localhost:{}/stats/contracts{}",
+                port, query
+            ))
+            .await
+            .unwrap()
+        };
+        let by_total = ranking("")
+            .await
+            .json::<Vec<ContractGasRanking>>()
+            .await
+            .unwrap();
+        assert_eq!(by_total.len(), 2);
+        assert_eq!(by_total[0].address, busy.public_account.address.to_string());
+        assert_eq!(by_total[0].calls, 4);
+        assert_eq!(by_total[0].gas_used, 40);
+        assert_eq!(by_total[0].average_gas, 10);
+        assert_eq!(by_total[0].code_size, 1);
+
+        let by_average = ranking("?by=average&n=1")
+            .await
+            .json::<Vec<ContractGasRanking>>()
+            .await
+            .unwrap();
+        assert_eq!(by_average.len(), 1);
+        assert_eq!(
+            by_average[0].address,
+            thrifty.public_account.address.to_string()
+        );
+        assert_eq!(by_average[0].average_gas, 30);
+
+        assert_eq!(ranking("?by=nope").await.status().as_u16(), 400);
     }
-}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TxRequest {
-    pub value: u64,
-    pub to: Option<PublicKey>,
-    pub code: Vec<OPCODE>,
-    pub gas_limit: u64,
-}
+    #[actix_rt::test]
+    async fn test_get_storage_at() {
+        let mut global_state = prep_state();
+        mine_local_block(&mut global_state);
+        //fake a contract whose slot 1 was set in block 1 and changed in block 2
+        let contract = global_state.miner_address;
+        mine_local_block(&mut global_state);
+        {
+            let blockchain = global_state.blockchain.get_mut().unwrap();
+            let mut trie = Trie::new();
+            trie.put("1".into(), "10".into());
+            let old = trie.clone();
+            trie.put("1".into(), "20".into());
+            blockchain
+                .storage_history
+                .insert(contract, vec![(1, old), (2, trie)]);
+        }
 
-/// giving the miner power to a)transact, b)create an account
-#[post("/transact")]
-pub async fn transact(
-    global_state: web::Data<Arc<Mutex<GlobalState>>>,
-    body: web::Json<TxRequest>,
-) -> impl Responder {
-    let guard = global_state.lock().unwrap();
-    let global_state = guard.deref();
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
 
-    // depending on whether the "to" field is present this will be either a normal tx (present) or an acc creation tx (not present)
-    let account = match body.to {
-        Some(_to) => global_state.miner_account.clone(),
-        None => Account::new(body.code.clone()), //if not present, we're creating a new account
-    };
-    let new_tx = Transaction::create_transaction(
-        Some(account.to_owned()),
-        body.to,
-        body.value,
-        None,
-        body.gas_limit,
-    );
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server);
 
-    // (!) No longer adding to local queue - instead broadcasting to entire network. Unlike with blocks which we're processing locally, we don't have dedup functionality for tx
-    // let mut tx_queue = &mut global_state.tx_queue;
-    // tx_queue.add(new_tx.clone());
+        let client = reqwest::Client::new();
+        for (block, expected) in [("", "20"), ("?block=1", "10"), ("?block=0", "0")] {
+            let res = client
+                .get(format!(
+                    "http://localhost:{}/storage/{}/1{}",
+                    port, contract, block
+                ))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(res.status().as_u16(), 200);
+            let slot = res.json::<StorageSlot>().await.unwrap();
+            assert_eq!(slot.value, expected, "wrong value for {}", block);
+        }
 
-    let str_tx = serde_json::to_string(&new_tx).unwrap();
-    rabbit_publish(str_tx, "tx").await.unwrap();
+        let res = client
+            .get(format!(
+                "http://localhost:{}/storage/{}/1?block=9",
+                port, contract
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 404);
 
-    HttpResponse::Ok().json(&new_tx)
-}
+        //same thing over json-rpc
+        let res = client
+            .post(format!("http://localhost:{}/rpc", port))
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 7,
+                "method": "eth_getStorageAt",
+                "params": [contract.to_string(), "0x1", "0x1"],
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let res_json = res.json::<RpcResponse>().await.unwrap();
+        assert_eq!(res_json.id, serde_json::json!(7));
+        assert_eq!(res_json.result, Some(serde_json::json!("10")));
+    }
 
-#[get("/balance/{address}")]
-pub async fn get_balance(
-    address: web::Path<String>,
-    global_state: web::Data<Arc<Mutex<GlobalState>>>,
-) -> impl Responder {
-    let mut lock = global_state.lock().unwrap();
-    let global_state = lock.deref_mut();
-    let address = PublicKey::from_str(address.deref()).unwrap();
-    let balance = Account::get_balance(address, &mut global_state.blockchain.state);
-    let mut map = HashMap::new();
-    map.insert("balance", balance);
-    HttpResponse::Ok().json(&map)
-}
+    #[actix_rt::test]
+    async fn test_token_endpoints() {
+        let mut global_state = prep_state();
+        let holder = global_state.miner_address;
+        let token = Account::new(token::token_code()).public_account;
+        let token_addr = token.address;
+        let other = Account::new(vec![OPCODE::PUSH, OPCODE::VAL(1)]).public_account;
+        let other_addr = other.address;
+        {
+            let state = &mut global_state.blockchain.get_mut().unwrap().state;
+            state.put_account(token_addr, token);
+            state.put_account(other_addr, other);
+            //as if the miner had sent init_call(100) and then passed 40 on
+            let storage = state.storage_trie_mut(token_addr);
+            storage.put("0".into(), "100".into());
+            storage.put(holder.short().to_string(), "60".into());
+            storage.put(gen_address().short().to_string(), "40".into());
+        }
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
 
-#[get("/state")]
-pub async fn get_state(global_state: web::Data<Arc<Mutex<GlobalState>>>) -> impl Responder {
-    let lock = global_state.lock().unwrap();
-    let global_state = lock.deref();
-    let trie = &global_state.blockchain.state.state_trie;
-    HttpResponse::Ok().json(trie)
-}
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        tokio::spawn(run_server(&config, wrapped_gs).unwrap());
 
-#[get("/storage_trie")]
-pub async fn get_storage_trie(global_state: web::Data<Arc<Mutex<GlobalState>>>) -> impl Responder {
-    let lock = global_state.lock().unwrap();
-    let global_state = lock.deref();
-    let trie = &global_state.blockchain.state.storage_trie_map;
-    HttpResponse::Ok().json(trie)
-}
+        let client = reqwest::Client::new();
+        let get = |path: String| {
+            let request = client.get(format!("http://localhost:{}/token/{}", port, path));
+            async move { request.send().await.unwrap() }
+        };
+        let res = get(format!("{}/balance/{}", token_addr, holder)).await;
+        assert_eq!(res.status().as_u16(), 200);
+        let balance = res.json::<TokenBalance>().await.unwrap();
+        assert_eq!((balance.slot, balance.balance), (holder.short(), 60));
+        let res = get(format!("{}/balance/{}", token_addr, gen_address())).await;
+        assert_eq!(res.json::<TokenBalance>().await.unwrap().balance, 0);
+        let res = get(format!("{}/supply", token_addr)).await;
+        assert_eq!(res.json::<TokenSupply>().await.unwrap().total_supply, 100);
 
-pub async fn replace_chain(global_state: Arc<Mutex<GlobalState>>) {
-    let mut guard = global_state.lock().unwrap();
-    let global_state = guard.deref_mut();
-    let blockchain = &mut global_state.blockchain;
+        //code that isn't the token, no code, no such address
+        let res = get(format!("{}/supply", other_addr)).await;
+        assert_eq!(res.status().as_u16(), 404);
+        let res = get(format!("{}/balance/{}", holder, holder)).await;
+        assert_eq!(res.status().as_u16(), 404);
+        let res = get(format!("{}/balance/nobody", token_addr)).await;
+        assert_eq!(res.status().as_u16(), 400);
+    }
 
-    let body = reqwest::get("http://localhost:8080/blockchain")
-        .await
-        .unwrap()
-        .text()
-        .await
-        .unwrap();
-    let chain: Vec<Block> = serde_json::from_str(&body).unwrap();
-    blockchain.replace_chain(chain).unwrap();
-}
+    #[actix_rt::test]
+    async fn test_paged_sync() {
+        let mut global_state = prep_state();
+        for _ in 0..3 {
+            mine_local_block(&mut global_state);
+        }
+        let (head_hash, state_root) = {
+            let blockchain = global_state.blockchain.get_mut().unwrap();
+            (
+                blockchain.chain[3].hash(),
+                blockchain.state.get_state_root().clone(),
+            )
+        };
 
-//the tests below are unit tests - they don't bother to actually mine blocks as they go. For that see integration tests in tests/ folder
-#[cfg(test)]
-mod tests {
-    use crate::account::gen_keypair;
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server);
+        let bootnode = format!("http://localhost:{}", port);
+
+        let client = reqwest::Client::new();
+        for (query, expected) in [("from=1&count=2", vec![1, 2]), ("from=9", vec![])] {
+            let blocks = client
+                .get(format!("{}/blocks/range?{}", bootnode, query))
+                .send()
+                .await
+                .unwrap()
+                .json::<Vec<Block>>()
+                .await
+                .unwrap();
+            let numbers: Vec<usize> = blocks
+                .iter()
+                .map(|b| b.block_headers.truncated_block_headers.number)
+                .collect();
+            assert_eq!(numbers, expected, "wrong blocks for {}", query);
+        }
 
-    use crate::api::server::{run_server, TxRequest};
+        let fresh = Arc::new(prep_state());
+        replace_chain(fresh.clone(), &bootnode).await.unwrap();
+        {
+            let blockchain = fresh.blockchain.read().unwrap();
+            assert_eq!(blockchain.chain.len(), 4);
+            assert_eq!(blockchain.chain[3].hash(), head_hash);
+            assert_eq!(blockchain.state.get_state_root(), &state_root);
+        }
+        //already synced - nothing left to fetch
+        replace_chain(fresh.clone(), &bootnode).await.unwrap();
+        assert_eq!(fresh.blockchain.read().unwrap().chain.len(), 4);
+    }
 
-    use crate::interpreter::OPCODE;
-    use crate::transaction::tx::{Transaction, TxType};
+    #[actix_rt::test]
+    async fn test_sync_from_snapshot() {
+        let mut global_state = prep_state();
+        mine_local_block(&mut global_state);
+        mine_local_block(&mut global_state);
+        let (head_hash, state_root) = {
+            let blockchain = global_state.blockchain.get_mut().unwrap();
+            (
+                blockchain.chain[2].hash(),
+                blockchain.state.get_state_root().clone(),
+            )
+        };
 
-    use crate::util::prep_state;
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server);
+        let bootnode = format!("http://localhost:{}", port);
 
-    use std::collections::HashMap;
-    use std::sync::{Arc, Mutex};
+        let client = reqwest::Client::new();
+        for (block, status) in [("0", 410), ("9", 404), ("2", 200)] {
+            let res = client
+                .get(format!("{}/snapshot/{}", bootnode, block))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(res.status().as_u16(), status, "wrong status for {}", block);
+        }
+
+        let fresh = Arc::new(prep_state());
+        sync_from_snapshot(fresh.clone(), &bootnode).await.unwrap();
+        let blockchain = fresh.blockchain.read().unwrap();
+        assert_eq!(blockchain.chain.len(), 3);
+        assert_eq!(blockchain.chain[2].hash(), head_hash);
+        assert_eq!(blockchain.state.get_state_root(), &state_root);
+    }
 
     #[actix_rt::test]
-    async fn test_transact_endpoint() {
-        let global_state = prep_state();
-        let miner_addr = global_state.miner_account.public_account.address.clone();
-        let wrapped_gs = Arc::new(Mutex::new(global_state));
+    async fn test_webhooks() {
         let port = rand::random::<u16>();
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, Arc::new(prep_state())).unwrap();
+        tokio::spawn(server);
+        let url = format!("http://localhost:{}/webhooks", port);
+        let client = reqwest::Client::new();
 
-        let server = run_server(&format!("localhost:{}", port), wrapped_gs).unwrap();
-        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+        let res = client
+            .post(&url)
+            .json(&serde_json::json!({"url": "not a url"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
 
-        let (_sk, pk) = gen_keypair();
-        //warning: do NOT try to deserialize with serde_json::to_string(), reqwest does it under the hood. Otherwise you'll fuck up the request body
-        let tx_request = TxRequest {
-            value: 123,
-            to: Some(pk),
-            code: vec![],
-            gas_limit: 100,
+        let registration = client
+            .post(&url)
+            .json(&serde_json::json!({
+                "url": "http://localhost:1/hook",
+                "filter": {"events": ["reorg"]}
+            }))
+            .send()
+            .await
+            .unwrap()
+            .json::<WebhookRegistration>()
+            .await
+            .unwrap();
+        assert_eq!(registration.filter.events, vec![WebhookEvent::Reorg]);
+        assert_eq!(registration.secret.len(), 64);
+
+        //listed without the secret
+        let listed = reqwest::get(&url).await.unwrap().text().await.unwrap();
+        assert!(listed.contains(&registration.id));
+        assert!(!listed.contains(&registration.secret));
+
+        let delete = |id: String| {
+            let client = client.clone();
+            let url = format!("{}/{}", url, id);
+            async move { client.delete(&url).send().await.unwrap().status() }
         };
+        assert_eq!(
+            delete(registration.id.clone()).await,
+            reqwest::StatusCode::OK
+        );
+        assert_eq!(
+            delete(registration.id).await,
+            reqwest::StatusCode::NOT_FOUND
+        );
+    }
 
+    #[actix_rt::test]
+    async fn test_address_book() {
+        let global_state = prep_state();
+        let miner_addr = global_state.miner_address;
+        let port = rand::random::<u16>();
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, Arc::new(global_state)).unwrap();
+        tokio::spawn(server);
+        let base = format!("http://localhost:{}", port);
         let client = reqwest::Client::new();
+        //the book is shared by every test in the process
+        let name = format!("miner-{}", rand::random::<u32>());
+
         let res = client
-            .post(format!("http://localhost:{}/transact", port))
-            .header("Content-Type", "application/json")
-            .json(&tx_request)
+            .post(format!("{}/addressbook", base))
+            .json(&serde_json::json!({"name": "Not A Name", "address": miner_addr}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+        let saved = client
+            .post(format!("{}/addressbook", base))
+            .json(&serde_json::json!({"name": name, "address": miner_addr}))
+            .send()
+            .await
+            .unwrap()
+            .json::<NamedAddress>()
+            .await
+            .unwrap();
+        assert_eq!(saved.address, miner_addr);
+        let listed = reqwest::get(format!("{}/addressbook", base))
+            .await
+            .unwrap()
+            .json::<Vec<NamedAddress>>()
+            .await
+            .unwrap();
+        assert!(listed.contains(&saved));
+
+        //in a path
+        client.get(format!("{}/mine", base)).send().await.unwrap();
+        let res = reqwest::get(format!("{}/balance/{}", base, name))
+            .await
+            .unwrap()
+            .json::<HashMap<String, U256>>()
+            .await
+            .unwrap();
+        assert_eq!(res["balance"], U256::from(50));
+        //and in a body
+        let res = client
+            .post(format!("{}/sign", base))
+            .json(&serde_json::json!({"address": name, "message": "hi"}))
             .send()
             .await
+            .unwrap()
+            .json::<SignedMessage>()
+            .await
             .unwrap();
+        assert_eq!(res.address, miner_addr.into());
 
+        let url = format!("{}/addressbook/{}", base, name);
         assert_eq!(
-            res.status().as_u16(),
-            200,
-            "the api didn't respond with a 200.",
+            client.delete(&url).send().await.unwrap().status(),
+            reqwest::StatusCode::OK
         );
-
-        //can only deserialize once (moves the value)
-        let res_json = res.json::<Transaction>().await.unwrap();
-        assert_eq!(res_json.unsigned_tx.value, 123);
-        assert_eq!(res_json.unsigned_tx.to, Some(pk));
-        assert_eq!(res_json.unsigned_tx.from, Some(miner_addr));
-        assert_ne!(res_json.unsigned_tx.to, res_json.unsigned_tx.from);
-        assert_eq!(res_json.unsigned_tx.data.tx_type, TxType::Transact);
+        assert_eq!(
+            client.delete(&url).send().await.unwrap().status(),
+            reqwest::StatusCode::NOT_FOUND
+        );
+        let res = reqwest::get(format!("{}/balance/{}", base, name))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
     }
 
     #[actix_rt::test]
-    async fn test_transact_endpoint_account_creation() {
-        let global_state = prep_state();
-        let _miner_addr = global_state.miner_account.public_account.address.clone();
-        let wrapped_gs = Arc::new(Mutex::new(global_state));
+    async fn test_miner_work() {
+        let global_state = Arc::new(prep_state());
+        let genesis_hash = global_state.blockchain.read().unwrap().chain[0].hash();
         let port = rand::random::<u16>();
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, global_state.clone()).unwrap();
+        tokio::spawn(server);
+        let url = format!("http://localhost:{}/miner/work", port);
 
-        let server = run_server(&format!("localhost:{}", port), wrapped_gs).unwrap();
-        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
+        let package = reqwest::get(&url)
+            .await
+            .unwrap()
+            .json::<WorkPackage>()
+            .await
+            .unwrap();
+        assert_eq!(package.number, 1);
+        assert_eq!(package.parent_hash, genesis_hash);
+        //genesis is at difficulty 1, so any nonce would do
+        assert_eq!(package.target, "f".repeat(64));
+        assert_eq!(global_state.work.lock().unwrap().len(), 1);
 
-        let tx_request = TxRequest {
-            value: 123,
-            to: None,
-            code: vec![],
-            gas_limit: 100,
+        let submit = |header_hash: &str, nonce: &str| {
+            let request = reqwest::Client::new()
+                .post(&url)
+                .json(&serde_json::json!({"header_hash": header_hash, "nonce": nonce}));
+            async move { request.send().await.unwrap().status() }
         };
+        assert_eq!(submit("nope", "1").await, reqwest::StatusCode::NOT_FOUND);
+        assert_eq!(
+            submit(&package.header_hash, "-1").await,
+            reqwest::StatusCode::BAD_REQUEST
+        );
+        //a bad submission leaves the package open
+        assert_eq!(global_state.work.lock().unwrap().len(), 1);
+    }
 
+    #[actix_rt::test]
+    async fn test_auto_miner() {
+        let global_state = Arc::new(prep_state());
+        let port = rand::random::<u16>();
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, global_state.clone()).unwrap();
+        tokio::spawn(server);
         let client = reqwest::Client::new();
-        let res = client
-            .post(format!("http://localhost:{}/transact", port))
-            .header("Content-Type", "application/json")
-            .json(&tx_request)
-            .send()
+        let start = |interval_secs: serde_json::Value| {
+            let request = client
+                .post(format!("http://localhost:{}/miner/start", port))
+                .json(&serde_json::json!({ "interval_secs": interval_secs }));
+            async move { request.send().await.unwrap() }
+        };
+
+        assert_eq!(
+            start(serde_json::json!(0)).await.status(),
+            reqwest::StatusCode::BAD_REQUEST
+        );
+        let status = start(serde_json::json!(1))
+            .await
+            .json::<AutoMinerStatus>()
             .await
             .unwrap();
-
+        assert!(status.running);
+        assert_eq!(status.interval_secs, Some(1));
         assert_eq!(
-            res.status().as_u16(),
-            200,
-            "the api didn't respond with a 200.",
+            start(serde_json::Value::Null).await.status(),
+            reqwest::StatusCode::CONFLICT
         );
 
-        let res_json = res.json::<Transaction>().await.unwrap();
-        assert_eq!(res_json.unsigned_tx.value, 123);
-        assert_eq!(res_json.unsigned_tx.to, None);
-        assert_eq!(res_json.unsigned_tx.from, None);
-        assert_eq!(res_json.unsigned_tx.data.tx_type, TxType::CreateAccount);
+        //a block a second, whether or not there's anything to put in it
+        let started = std::time::Instant::now();
+        while global_state.blockchain.read().unwrap().chain.len() < 3 {
+            assert!(started.elapsed() < Duration::from_secs(30));
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let status = client
+            .post(format!("http://localhost:{}/miner/stop", port))
+            .send()
+            .await
+            .unwrap()
+            .json::<AutoMinerStatus>()
+            .await
+            .unwrap();
+        assert!(!status.running);
+        assert!(status.blocks_mined >= 2);
     }
 
     #[actix_rt::test]
-    async fn test_transact_endpoint_smart_contract_creation() {
-        let global_state = prep_state();
-        let _miner_addr = global_state.miner_account.public_account.address.clone();
-        let wrapped_gs = Arc::new(Mutex::new(global_state));
+    async fn test_metrics() {
+        //the registry is global, so an exchange of our own keeps other tests out of the way
+        let exchange = uuid::Uuid::new_v4().to_string();
+        metrics::record_delivery(&exchange, std::time::Duration::from_millis(2), false);
         let port = rand::random::<u16>();
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, Arc::new(prep_state())).unwrap();
+        tokio::spawn(server);
 
-        let server = run_server(&format!("localhost:{}", port), wrapped_gs).unwrap();
-        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
-
-        let code = vec![
-            OPCODE::PUSH,
-            OPCODE::VAL(10),
-            OPCODE::PUSH,
-            OPCODE::VAL(5),
-            OPCODE::ADD,
-            OPCODE::STOP,
-        ];
+        let res = reqwest::get(format!("http://localhost:{}/metrics", port))
+            .await
+            .unwrap();
+        assert!(res.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"));
+        let text = res.text().await.unwrap();
+        assert!(text.contains(&format!(
+            "rs_consumer_failures_total{{exchange=\"{}\"}} 1",
+            exchange
+        )));
+        assert!(text.contains("rs_partitioned 0\n"));
+    }
 
-        let tx_request = TxRequest {
-            value: 123,
-            to: None,
-            code,
-            gas_limit: 100,
+    #[actix_rt::test]
+    async fn test_health() {
+        let global_state = Arc::new(prep_state());
+        //listening since the epoch, so well past the threshold
+        global_state.sync.lock().unwrap().start_listening(0);
+        let port = rand::random::<u16>();
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
         };
+        let server = run_server(&config, global_state.clone()).unwrap();
+        tokio::spawn(server);
 
-        let client = reqwest::Client::new();
-        let res = client
-            .post(format!("http://localhost:{}/transact", port))
-            .header("Content-Type", "application/json")
-            .json(&tx_request)
-            .send()
+        let res = reqwest::get(format!("http://localhost:{}/health", port))
             .await
             .unwrap();
+        let status = res.status().as_u16();
+        let health = res.json::<PeerHealth>().await.unwrap();
+        assert!(health.quiet_ms.unwrap() > health.threshold_ms);
+        assert_eq!(health.last_peer_block, None);
+        //with gossip and no peers we're a lone node, not a cut off one. rabbitmq has no peer count to go by
+        if cfg!(feature = "rabbitmq") {
+            assert!(health.partitioned);
+            assert_eq!(status, 503);
+        } else {
+            assert_eq!(health.peers, Some(0));
+            assert!(!health.partitioned);
+            assert_eq!(status, 200);
+        }
+    }
 
-        assert_eq!(
-            res.status().as_u16(),
-            200,
-            "the api didn't respond with a 200.",
+    #[actix_rt::test]
+    async fn test_propagation_report() {
+        let start = |measure_propagation: bool| {
+            let global_state = Arc::new(prep_state());
+            global_state.propagation.lock().unwrap().enabled = measure_propagation;
+            let port = rand::random::<u16>();
+            let config = NodeConfig {
+                port,
+                measure_propagation,
+                ..NodeConfig::default()
+            };
+            tokio::spawn(run_server(&config, global_state.clone()).unwrap());
+            (global_state, format!("http://localhost:{}", port))
+        };
+        let (a, a_url) = start(true);
+        let (b, b_url) = start(true);
+        let (_, off_url) = start(false);
+
+        let tx = Transaction::create_transaction(
+            Some(Account::new(vec![])),
+            None,
+            0,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
         );
+        process_transaction(codec::encode_hex(&tx), a.clone()).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        process_transaction(codec::encode_hex(&tx), b.clone()).unwrap();
 
-        let res_json = res.json::<Transaction>().await.unwrap();
-        assert_eq!(res_json.unsigned_tx.value, 123);
-        assert_eq!(res_json.unsigned_tx.to, None);
-        assert_eq!(res_json.unsigned_tx.from, None);
-        assert_eq!(res_json.unsigned_tx.data.tx_type, TxType::CreateAccount);
+        let res = reqwest::get(format!(
+            "{}/propagation/report?nodes={},{}",
+            a_url, b_url, off_url
+        ))
+        .await
+        .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        let report = res.json::<PropagationReport>().await.unwrap();
+        assert_eq!(report.tx_count, 1);
+        assert_eq!(report.fully_propagated, 1);
+        assert!(report.max_delay_ms.unwrap() >= 5);
+        assert_eq!(report.nodes.len(), 2);
+        assert_eq!(report.nodes[0].node_id, a.node_id.to_string());
+        assert_eq!(report.nodes[0].first, 1);
+        //the node that isn't measuring answers 404
+        assert_eq!(report.unreachable.len(), 1);
+        assert!(report.unreachable[0].starts_with(&off_url));
+
+        let res = reqwest::get(format!("{}/propagation", off_url))
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 404);
     }
 
     #[actix_rt::test]
-    async fn test_get_balance() {
-        let global_state = prep_state();
-        let miner_addr = global_state.miner_account.public_account.address.clone();
-        let wrapped_gs = Arc::new(Mutex::new(global_state));
+    async fn test_sync_status() {
+        let mut global_state = prep_state();
+        mine_local_block(&mut global_state);
+        mine_local_block(&mut global_state);
         let port = rand::random::<u16>();
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, Arc::new(global_state)).unwrap();
+        tokio::spawn(server);
+        let bootnode = format!("http://localhost:{}", port);
 
-        let server = run_server(&format!("localhost:{}", port), wrapped_gs).unwrap();
-        tokio::spawn(server); //spawn server on a diff green thread, so we can run the test on main
-
-        let client = reqwest::Client::new();
+        let fresh = Arc::new(prep_state());
+        let fresh_port = rand::random::<u16>();
+        let config = NodeConfig {
+            port: fresh_port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, fresh.clone()).unwrap();
+        tokio::spawn(server);
+        let sync_status = move || async move {
+            reqwest::get(format!("http://localhost:{}/sync", fresh_port))
+                .await
+                .unwrap()
+                .json::<SyncStatus>()
+                .await
+                .unwrap()
+        };
+        assert_eq!(sync_status().await.state, Lifecycle::Initializing);
 
-        //need to mine the first block to get the miner address written into the trie
-        //we don't need rabbitmq running for this because we mine the block right in the /mine endpoint function
-        client
-            .get(format!("http://localhost:{}/mine", port))
-            .send()
+        //behind, so anything it mined would be orphaned
+        fresh.sync.lock().unwrap().start(0, 2, 0);
+        let res = reqwest::get(format!("http://localhost:{}/mine", fresh_port))
             .await
-            .expect("mining failed");
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 503);
 
-        let res = client
-            .get(format!("http://localhost:{}/balance/{}", port, miner_addr))
-            .send()
+        sync_with_bootnodes(fresh.clone(), &[bootnode], false)
             .await
             .unwrap();
+        let status = sync_status().await;
+        assert_eq!(status.state, Lifecycle::Synced);
+        assert_eq!(status.current_block, 2);
+        assert_eq!(status.highest_block, 2);
+        assert_eq!(status.eta_ms, None);
 
-        assert_eq!(
-            res.status().as_u16(),
-            200,
-            "the api didn't respond with a 200.",
-        );
-        let res_json = res.json::<HashMap<String, u64>>().await.unwrap();
-        assert_eq!(res_json.get("balance").unwrap().to_owned(), 1000 + 50);
+        //nowhere to sync from - the node stays where it was
+        let stranded = Arc::new(prep_state());
+        let unreachable = format!("http://localhost:{}", rand::random::<u16>());
+        assert!(sync_with_bootnodes(stranded.clone(), &[unreachable], false)
+            .await
+            .is_err());
+        assert_ne!(stranded.sync.lock().unwrap().state(), Lifecycle::Synced);
+    }
+
+    #[actix_rt::test]
+    async fn test_responses_carry_request_id() {
+        let global_state = prep_state();
+        let wrapped_gs = Arc::new(global_state);
+        let port = rand::random::<u16>();
+
+        let config = NodeConfig {
+            port,
+            ..NodeConfig::default()
+        };
+        let server = run_server(&config, wrapped_gs).unwrap();
+        tokio::spawn(server);
+
+        let client = reqwest::Client::new();
+        let mut ids = vec![];
+        for _ in 0..2 {
+            let res = client
+                .get(format!("http://localhost:{}/stats", port))
+                .send()
+                .await
+                .unwrap();
+            let id = res
+                .headers()
+                .get(REQUEST_ID_HEADER)
+                .unwrap()
+                .to_str()
+                .unwrap();
+            ids.push(id.to_owned());
+        }
+        assert_ne!(ids[0], ids[1]);
     }
 }
@@ -0,0 +1,68 @@
+use rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
+use rustls::{NoClientAuth, ServerConfig};
+use std::fs::File;
+use std::io::{self, BufReader, Error, ErrorKind};
+use std::path::Path;
+
+/// builds a rustls config from a pem certificate chain + pem private key (pkcs8 or rsa)
+pub fn load_rustls_config(cert_path: &Path, key_path: &Path) -> io::Result<ServerConfig> {
+    let mut cert_reader = BufReader::new(File::open(cert_path)?);
+    let cert_chain = certs(&mut cert_reader)
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "failed to parse tls certificate"))?;
+    if cert_chain.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "no certificates found in tls cert file",
+        ));
+    }
+
+    //try pkcs8 first (what openssl generates by default these days), then fall back to rsa
+    let mut key_reader = BufReader::new(File::open(key_path)?);
+    let mut keys = pkcs8_private_keys(&mut key_reader).unwrap_or_default();
+    if keys.is_empty() {
+        let mut key_reader = BufReader::new(File::open(key_path)?);
+        keys = rsa_private_keys(&mut key_reader).unwrap_or_default();
+    }
+    if keys.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "no private key found in tls key file",
+        ));
+    }
+
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    config
+        .set_single_cert(cert_chain, keys.remove(0))
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_rustls_config() {
+        let config = load_rustls_config(
+            Path::new("tests/fixtures/cert.pem"),
+            Path::new("tests/fixtures/key.pem"),
+        );
+        assert!(config.is_ok());
+    }
+
+    #[test]
+    fn test_load_rustls_config_bad_files() {
+        //key and cert swapped
+        let config = load_rustls_config(
+            Path::new("tests/fixtures/key.pem"),
+            Path::new("tests/fixtures/cert.pem"),
+        );
+        assert!(config.is_err());
+
+        let config = load_rustls_config(
+            Path::new("does/not/exist.pem"),
+            Path::new("tests/fixtures/key.pem"),
+        );
+        assert!(config.is_err());
+    }
+}
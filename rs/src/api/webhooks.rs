@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::header::CONTENT_TYPE;
+use secp256k1::bitcoin_hashes::{hmac, sha256, Hash, HashEngine};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::account::address::Address;
+use crate::blockchain::blockchain::Blockchain;
+use crate::events::Event;
+use crate::transaction::receipt::Receipt;
+use crate::transaction::tx::Transaction;
+use crate::util::GlobalState;
+
+/// how many times a delivery is tried before it's given up on
+pub const WEBHOOK_ATTEMPTS: u32 = 4;
+/// wait before the first retry, doubled before each one after it
+pub const WEBHOOK_RETRY_DELAY: Duration = Duration::from_secs(1);
+pub const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+/// "sha256=<hex hmac of the body>", keyed with the secret the webhook was registered with
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    NewBlock,
+    Reorg,
+    Receipt,
+}
+
+impl WebhookEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebhookEvent::NewBlock => "new_block",
+            WebhookEvent::Reorg => "reorg",
+            WebhookEvent::Receipt => "receipt",
+        }
+    }
+}
+
+/// which events a webhook gets. No events = all of them. An address narrows blocks and receipts down to the ones
+/// with a tx it sent, received or created - a reorg concerns everyone, so it goes out regardless
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WebhookFilter {
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub address: Option<Address>,
+}
+
+impl WebhookFilter {
+    fn matches(&self, notification: &Notification) -> bool {
+        let event = notification.payload.event();
+        (self.events.is_empty() || self.events.contains(&event))
+            && match (&self.address, &notification.txs) {
+                (Some(address), Some(txs)) => txs.iter().any(|tx| tx.involves(address)),
+                _ => true,
+            }
+    }
+}
+
+/// the secret is only ever shown once, in the response to registering the webhook
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    pub filter: WebhookFilter,
+    #[serde(skip)]
+    secret: String,
+}
+
+/// what gets POSTed, as json tagged with "event"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookPayload {
+    NewBlock {
+        number: usize,
+        hash: String,
+        tx_count: usize,
+        from_peer: bool,
+    },
+    Reorg {
+        old_head: String,
+        new_head: String,
+        depth: usize,
+    },
+    Receipt(Receipt),
+}
+
+impl WebhookPayload {
+    pub fn event(&self) -> WebhookEvent {
+        match self {
+            WebhookPayload::NewBlock { .. } => WebhookEvent::NewBlock,
+            WebhookPayload::Reorg { .. } => WebhookEvent::Reorg,
+            WebhookPayload::Receipt(_) => WebhookEvent::Receipt,
+        }
+    }
+}
+
+/// a payload plus the tx it's about, for the address filter. txs = None when it's about the whole chain
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub payload: WebhookPayload,
+    txs: Option<Vec<Transaction>>,
+}
+
+impl Notification {
+    /// a new block turns into one notification for the block and one per receipt. New tx and the miner's status
+    /// aren't changes to the chain head, so they don't turn into anything
+    pub fn from_event(event: &Event, blockchain: &Blockchain) -> Vec<Notification> {
+        match event {
+            Event::NewBlock { block, from_peer } => {
+                let mut notifications = vec![Notification {
+                    payload: WebhookPayload::NewBlock {
+                        number: block.block_headers.truncated_block_headers.number,
+                        hash: block.hash(),
+                        tx_count: block.tx_series.len(),
+                        from_peer: *from_peer,
+                    },
+                    txs: Some(block.tx_series.clone()),
+                }];
+                for tx in &block.tx_series {
+                    if let Some(receipt) = blockchain.get_receipt(&tx.hash()) {
+                        notifications.push(Notification {
+                            payload: WebhookPayload::Receipt(receipt.clone()),
+                            txs: Some(vec![tx.clone()]),
+                        });
+                    }
+                }
+                notifications
+            }
+            Event::Reorg {
+                old_head,
+                new_head,
+                depth,
+            } => vec![Notification {
+                payload: WebhookPayload::Reorg {
+                    old_head: old_head.clone(),
+                    new_head: new_head.clone(),
+                    depth: *depth,
+                },
+                txs: None,
+            }],
+            Event::NewTx(_) | Event::MinerStatus(_) => vec![],
+        }
+    }
+}
+
+/// webhooks registered through POST /webhooks. Purely in memory like the rpc filters - they're gone on restart
+#[derive(Debug, Default)]
+pub struct WebhookRegistry {
+    hooks: HashMap<String, Webhook>,
+}
+
+impl WebhookRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// returns the webhook with its freshly generated secret
+    pub fn register(&mut self, url: &str, filter: WebhookFilter) -> Result<Webhook, String> {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(format!(
+                "invalid webhook url: {} (expected an http(s) url)",
+                url
+            ));
+        }
+        let hook = Webhook {
+            id: Uuid::new_v4().to_string(),
+            url: url.into(),
+            filter,
+            secret: hex::encode(rand::random::<[u8; 32]>()),
+        };
+        self.hooks.insert(hook.id.clone(), hook.clone());
+        Ok(hook)
+    }
+
+    pub fn remove(&mut self, id: &str) -> bool {
+        self.hooks.remove(id).is_some()
+    }
+
+    pub fn list(&self) -> Vec<Webhook> {
+        let mut hooks: Vec<Webhook> = self.hooks.values().cloned().collect();
+        hooks.sort_by(|a, b| a.id.cmp(&b.id));
+        hooks
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    pub fn matching(&self, notification: &Notification) -> Vec<Webhook> {
+        self.hooks
+            .values()
+            .filter(|hook| hook.filter.matches(notification))
+            .cloned()
+            .collect()
+    }
+}
+
+impl Webhook {
+    pub fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    /// what goes in SIGNATURE_HEADER. The receiver recomputes it over the raw body to know the POST came from us
+    pub fn sign(&self, body: &[u8]) -> String {
+        sign(&self.secret, body)
+    }
+}
+
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(body);
+    let mac = hmac::Hmac::<sha256::Hash>::from_engine(engine).into_inner();
+    format!("sha256={}", hex::encode(mac))
+}
+
+/// POSTs the payload, retrying with a doubling delay until the endpoint answers with a 2xx or we run out of attempts
+pub async fn deliver(
+    client: &reqwest::Client,
+    hook: &Webhook,
+    payload: &WebhookPayload,
+) -> Result<(), String> {
+    let body = serde_json::to_string(payload).unwrap();
+    let signature = hook.sign(body.as_bytes());
+    let mut delay = WEBHOOK_RETRY_DELAY;
+    let mut last_error = String::new();
+    for attempt in 1..=WEBHOOK_ATTEMPTS {
+        let res = client
+            .post(&hook.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .header(EVENT_HEADER, payload.event().name())
+            .timeout(WEBHOOK_TIMEOUT)
+            .body(body.clone())
+            .send()
+            .await;
+        match res {
+            Ok(res) if res.status().is_success() => return Ok(()),
+            Ok(res) => last_error = format!("answered {}", res.status()),
+            Err(e) => last_error = e.to_string(),
+        }
+        if attempt < WEBHOOK_ATTEMPTS {
+            tracing::debug!(webhook = %hook.id, attempt, error = %last_error, "retrying webhook");
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+    Err(format!(
+        "gave up after {} attempts, last one {}",
+        WEBHOOK_ATTEMPTS, last_error
+    ))
+}
+
+/// delivers chain head changes to the registered webhooks, until the bus goes away. Every delivery runs on a task
+/// of its own, so an endpoint that's down and being retried doesn't hold up anyone else's
+pub async fn dispatch_webhooks(
+    global_state: Arc<GlobalState>,
+    mut events: broadcast::Receiver<Event>,
+) {
+    let client = reqwest::Client::new();
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                tracing::warn!(
+                    missed,
+                    "webhooks fell behind, some events weren't delivered"
+                );
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        //no point digging out receipts nobody is going to get
+        if global_state.webhooks.lock().unwrap().is_empty() {
+            continue;
+        }
+        let notifications =
+            Notification::from_event(&event, &global_state.blockchain.read().unwrap());
+        for notification in notifications {
+            let hooks = global_state
+                .webhooks
+                .lock()
+                .unwrap()
+                .matching(&notification);
+            for hook in hooks {
+                let client = client.clone();
+                let payload = notification.payload.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = deliver(&client, &hook, &payload).await {
+                        tracing::warn!(webhook = %hook.id, url = %hook.url, error = %e, "webhook delivery failed");
+                    }
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::blockchain::block::Block;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::util::prep_state;
+    use actix_web::{web, App, HttpRequest, HttpServer};
+    use std::sync::Mutex;
+
+    /// RFC 4231 test case 2
+    #[test]
+    fn test_hmac_signature() {
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_filters() {
+        let mut registry = WebhookRegistry::new();
+        assert!(registry
+            .register("ftp://example.com", WebhookFilter::default())
+            .is_err());
+
+        let sender = Account::new(vec![]);
+        let address = sender.public_account.address;
+        let everything = registry
+            .register("http://localhost:1/all", WebhookFilter::default())
+            .unwrap();
+        let mine = registry
+            .register(
+                "http://localhost:1/mine",
+                WebhookFilter {
+                    events: vec![WebhookEvent::NewBlock, WebhookEvent::Reorg],
+                    address: Some(address),
+                },
+            )
+            .unwrap();
+        assert_eq!(registry.list().len(), 2);
+        assert!(registry.list().iter().all(|hook| !hook.secret().is_empty()));
+
+        let ids = |registry: &WebhookRegistry, notification: &Notification| {
+            let mut ids: Vec<String> = registry
+                .matching(notification)
+                .into_iter()
+                .map(|hook| hook.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        let block = |txs: Vec<Transaction>| Notification {
+            payload: WebhookPayload::NewBlock {
+                number: 1,
+                hash: "".into(),
+                tx_count: txs.len(),
+                from_peer: false,
+            },
+            txs: Some(txs),
+        };
+        let ours =
+            Transaction::create_transaction(Some(sender), None, 0, None, 0, DEFAULT_CHAIN_ID);
+        let theirs = Transaction::create_transaction(
+            Some(Account::new(vec![])),
+            None,
+            0,
+            None,
+            0,
+            DEFAULT_CHAIN_ID,
+        );
+
+        let mut both = vec![everything.id.clone(), mine.id.clone()];
+        both.sort();
+        assert_eq!(ids(&registry, &block(vec![ours])), both);
+        assert_eq!(
+            ids(&registry, &block(vec![theirs])),
+            vec![everything.id.clone()]
+        );
+        let reorg = Notification {
+            payload: WebhookPayload::Reorg {
+                old_head: "a".into(),
+                new_head: "b".into(),
+                depth: 1,
+            },
+            txs: None,
+        };
+        assert_eq!(ids(&registry, &reorg), both);
+
+        assert!(registry.remove(&mine.id));
+        assert!(!registry.remove(&mine.id));
+        assert_eq!(ids(&registry, &reorg), vec![everything.id]);
+    }
+
+    #[actix_rt::test]
+    async fn test_new_blocks_are_delivered_signed() {
+        //stands in for the integration on the other end
+        let received = web::Data::new(Mutex::new(Vec::<(String, String)>::new()));
+        let port = rand::random::<u16>();
+        let data = received.clone();
+        let receiver = HttpServer::new(move || {
+            App::new().app_data(data.clone()).route(
+                "/hook",
+                web::post().to(
+                    |req: HttpRequest,
+                     body: String,
+                     received: web::Data<Mutex<Vec<(String, String)>>>| async move {
+                        let signature = req
+                            .headers()
+                            .get(SIGNATURE_HEADER)
+                            .unwrap()
+                            .to_str()
+                            .unwrap();
+                        received.lock().unwrap().push((signature.to_string(), body));
+                        "ok"
+                    },
+                ),
+            )
+        })
+        .bind(("localhost", port))
+        .unwrap()
+        .run();
+        tokio::spawn(receiver);
+
+        let global_state = Arc::new(prep_state());
+        let hook = global_state
+            .webhooks
+            .lock()
+            .unwrap()
+            .register(
+                &format!("http://localhost:{}/hook", port),
+                WebhookFilter::default(),
+            )
+            .unwrap();
+        tokio::spawn(dispatch_webhooks(
+            global_state.clone(),
+            global_state.events.subscribe(),
+        ));
+
+        let block = {
+            let blockchain = global_state.blockchain.read().unwrap();
+            Block::mine_block(
+                blockchain.chain.last().unwrap(),
+                global_state.miner_address,
+                global_state.tx_queue.lock().unwrap().get_tx_series(),
+                &blockchain.state.get_state_root().clone(),
+                &*blockchain.clock,
+                &blockchain.rules,
+            )
+        };
+        global_state.import_block(block.clone()).unwrap();
+        global_state.events.publish(Event::NewBlock {
+            block: Arc::new(block.clone()),
+            from_peer: false,
+        });
+
+        //the block, plus a receipt for prep_state()'s account creation and one for the mining reward
+        for _ in 0..50 {
+            if received.lock().unwrap().len() == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        for (signature, body) in received.iter() {
+            assert_eq!(*signature, hook.sign(body.as_bytes()));
+        }
+        assert!(received.iter().any(|(_, body)| {
+            body.contains("\"event\":\"new_block\"") && body.contains(&block.hash())
+        }));
+    }
+}
@@ -1,14 +1,26 @@
-use crate::account::gen_keypair;
-use crate::store::state::State;
+use crate::account::address::Address;
+use crate::account::{gen_address, Account};
+use crate::blockchain::bloom::AddressBloom;
+use crate::blockchain::fork::Fork;
+use crate::config::chain::ChainRules;
+use crate::error::{ChainError, CodecError, TxError};
+use crate::interpreter::BlockEnv;
+use crate::store::codec::{self, Decode, Encode, Fields, Record};
+use crate::store::overlay::OverlayState;
+use crate::store::rlp::Rlp;
+use crate::store::state::{State, StateAccess};
 use crate::store::trie::Trie;
-use crate::transaction::tx::{Transaction, MINING_REWARD};
+use crate::transaction::fee::Payees;
+use crate::transaction::receipt::Receipt;
+use crate::transaction::tx::{Transaction, TxType, MINING_REWARD};
+use crate::util::clock::Clock;
 use crate::util::{base10_to_base16, base16_to_base10, keccak_hash};
-use chrono::{Duration, Utc};
 use lazy_static::lazy_static;
 
-use secp256k1::PublicKey;
+use secp256k1::Signature;
 use serde::{Deserialize, Serialize};
-use uint::construct_uint;
+use std::collections::HashSet;
+use std::sync::OnceLock;
 
 // ----------------------------------------------------------------------------- constants
 
@@ -17,29 +29,64 @@ pub const MILLISECONDS: i64 = 1;
 pub const SECONDS: i64 = 1000 * MILLISECONDS;
 pub const MINE_RATE: i64 = 13 * SECONDS;
 
-//rust only supports ints up to 128 bit and we need 256, so have to use an external crate - https://crates.io/crates/uint
-construct_uint! {
-    #[derive(Serialize, Deserialize)]
-    pub struct U256(4);
-}
+//consensus limits, on top of gas - a block over either is invalid, so no miner can make one too big for peers to take
+pub const MAX_BLOCK_TX: usize = 1024; //incl the mining reward
+pub const MAX_BLOCK_BYTES: usize = 1024 * 1024; //hex encoded, the way blocks go over the wire - see codec::encode_hex()
+                                                //what the miner keeps free for the headers and its reward when filling a block up to MAX_BLOCK_BYTES
+const BLOCK_OVERHEAD_BYTES: usize = 4 * 1024;
+
+//used to live here - kept so existing imports keep working
+pub use crate::util::bigint::U256;
 
 //unfortunately this is needed as currently rust doesn't support functions in consts/statics - https://users.rust-lang.org/t/defining-a-const-variable-with-sqrt/24972
 lazy_static! {
     static ref MAX_HASH_BASE16: String = "f".repeat(HASH_LENGTH);
-    static ref MAX_HASH_BASE10: U256 = base16_to_base10(&*MAX_HASH_BASE16);
+    static ref MAX_HASH_BASE10: U256 = base16_to_base10(&MAX_HASH_BASE16);
 }
 
 // ----------------------------------------------------------------------------- structs
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TruncatedBlockHeaders {
+    //set in the genesis block and inherited by every block after it
+    pub chain_id: u64,
     pub parent_hash: String,
-    pub beneficiary: PublicKey,
+    pub beneficiary: Address,
     pub difficulty: i64,
     pub number: usize,
     pub timestamp: i64,
     pub tx_root: String,
     pub state_root: String,
+    //every address the block's tx touch, see AddressBloom
+    pub address_bloom: AddressBloom,
+    //from the paris fork on. Left out of the json before it, so older blocks hash the same as they always did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub randao: Option<Randao>,
+    //Receipt::root() of what the block's tx did. Blocks mined before it was added don't have one, and keep their hash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipts_root: Option<String>,
+}
+
+/// the randomness beacon: every block mixes the miner's contribution into its parent's mix.
+/// The reveal is the miner's signature over the parent's mix - signatures are deterministic, so all the miner can do
+/// to steer the mix is not publish a block. Check with Block::check_block_structure()
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Randao {
+    pub reveal: Signature,
+    pub mix: String,
+}
+
+impl Randao {
+    pub fn new(miner: &Account, parent_mix: &String) -> Self {
+        let reveal = miner.sign(parent_mix);
+        Self {
+            mix: Randao::mix(parent_mix, &reveal),
+            reveal,
+        }
+    }
+    pub fn mix(parent_mix: &str, reveal: &Signature) -> String {
+        keccak_hash(&(parent_mix, reveal))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,6 +99,10 @@ pub struct BlockHeaders {
 pub struct Block {
     pub block_headers: BlockHeaders,
     pub tx_series: Vec<Transaction>,
+    //see hash(). Not part of the json - a deserialized block computes it again on first use.
+    // (!) filled in once and never invalidated, so don't modify block_headers after calling hash()
+    #[serde(skip)]
+    header_hash: OnceLock<String>,
 }
 
 // ----------------------------------------------------------------------------- impl
@@ -61,17 +112,23 @@ impl Block {
         Self {
             block_headers,
             tx_series: vec![],
+            header_hash: OnceLock::new(),
         }
     }
-    pub fn genesis() -> Self {
+    /// the timestamp is 30s behind the clock, so the first block mined on top of it always drops difficulty
+    pub fn genesis(clock: &dyn Clock, chain_id: u64) -> Self {
         let tbh = TruncatedBlockHeaders {
+            chain_id,
             parent_hash: String::from("NONE"),
-            beneficiary: gen_keypair().1, //random pub key for genesis block
+            beneficiary: gen_address(), //random address for genesis block
             difficulty: 1,
             number: 0,
-            timestamp: (Utc::now() - Duration::seconds(30)).timestamp_millis(), //(!) keep this above 15s for tests
+            timestamp: clock.now_millis() - 30 * SECONDS, //(!) keep this above MINE_RATE for tests
             tx_root: String::from("NONE"),
             state_root: String::from("NONE"),
+            address_bloom: AddressBloom::default(),
+            randao: None,
+            receipts_root: None,
         };
         let bh = BlockHeaders {
             truncated_block_headers: tbh,
@@ -80,6 +137,7 @@ impl Block {
         Self {
             block_headers: bh,
             tx_series: vec![],
+            header_hash: OnceLock::new(),
         }
     }
 
@@ -94,12 +152,11 @@ impl Block {
     pub fn adjust_difficulty(last_block: &Block, timestamp: i64) -> i64 {
         let previous_difficulty = last_block.block_headers.truncated_block_headers.difficulty;
         let previous_timestamp = last_block.block_headers.truncated_block_headers.timestamp;
-        let new_difficulty;
-        if timestamp - previous_timestamp > MINE_RATE {
-            new_difficulty = previous_difficulty - 1;
+        let new_difficulty = if timestamp - previous_timestamp > MINE_RATE {
+            previous_difficulty - 1
         } else {
-            new_difficulty = previous_difficulty + 1;
-        }
+            previous_difficulty + 1
+        };
         //check to make sure doesn't go below 1
         if new_difficulty < 1 {
             return 1;
@@ -107,113 +164,553 @@ impl Block {
         new_difficulty
     }
 
+    /// the longest prefix of tx_series that fits in a block next to the mining reward. The rest has to wait for the next one
+    pub fn fill(tx_series: Vec<Transaction>) -> Vec<Transaction> {
+        let mut bytes = BLOCK_OVERHEAD_BYTES;
+        tx_series
+            .into_iter()
+            .take(MAX_BLOCK_TX - 1)
+            .take_while(|tx| {
+                bytes += 2 * codec::encode(tx).len();
+                bytes <= MAX_BLOCK_BYTES
+            })
+            .collect()
+    }
+
+    /// only takes as many tx as fit, see fill(). Without a randao reveal, so from the paris fork on the block is
+    /// invalid - use mine_block_signed() for those
     pub fn mine_block(
         last_block: &Block,
-        beneficiary: PublicKey,
-        mut tx_series: Vec<Transaction>,
-        state_root: &String,
+        beneficiary: Address,
+        tx_series: Vec<Transaction>,
+        state_root: &str,
+        clock: &dyn Clock,
+        rules: &ChainRules,
+    ) -> Self {
+        Block::mine(
+            last_block,
+            beneficiary,
+            None,
+            tx_series,
+            state_root,
+            clock,
+            rules,
+        )
+    }
+
+    /// mine_block() with the miner as the beneficiary, signing the randao reveal once the paris fork is active
+    pub fn mine_block_signed(
+        last_block: &Block,
+        miner: &Account,
+        tx_series: Vec<Transaction>,
+        state_root: &str,
+        clock: &dyn Clock,
+        rules: &ChainRules,
+    ) -> Self {
+        Block::candidate(last_block, miner, tx_series, state_root, clock, rules)
+            .seal(last_block, rules)
+    }
+
+    /// the block mine_block_signed() would mine, minus the proof of work. Its nonce is 0, so it won't pass
+    /// check_block() until seal() - it's for seeing what would go into the block, see GET /miner/pending_block.
+    /// Without a receipts root, see with_receipts_root()
+    pub fn candidate(
+        last_block: &Block,
+        miner: &Account,
+        tx_series: Vec<Transaction>,
+        state_root: &str,
+        clock: &dyn Clock,
+        rules: &ChainRules,
+    ) -> Self {
+        let number = last_block.block_headers.truncated_block_headers.number + 1;
+        let randao = Some(rules.fork_schedule.fork_at(number))
+            .filter(|fork| *fork >= Fork::Paris)
+            .map(|_| Randao::new(miner, &last_block.randao_mix()));
+        let beneficiary = miner.public_account.address;
+        Block::assemble(
+            last_block,
+            beneficiary,
+            randao,
+            tx_series,
+            state_root,
+            clock,
+            rules,
+        )
+    }
+
+    fn mine(
+        last_block: &Block,
+        beneficiary: Address,
+        randao: Option<Randao>,
+        tx_series: Vec<Transaction>,
+        state_root: &str,
+        clock: &dyn Clock,
+        rules: &ChainRules,
     ) -> Self {
-        let target = Block::calc_block_target_hash(last_block);
-        let timestamp = Utc::now().timestamp_millis(); //in milliseconds specifically
+        Block::assemble(
+            last_block,
+            beneficiary,
+            randao,
+            tx_series,
+            state_root,
+            clock,
+            rules,
+        )
+        .seal(last_block, rules)
+    }
+
+    //everything but the nonce
+    fn assemble(
+        last_block: &Block,
+        beneficiary: Address,
+        randao: Option<Randao>,
+        tx_series: Vec<Transaction>,
+        state_root: &str,
+        clock: &dyn Clock,
+        rules: &ChainRules,
+    ) -> Self {
+        let mut tx_series = Block::fill(tx_series);
+        let timestamp = clock.now_millis(); //in milliseconds specifically
 
         //include mining tx before we build the trie
-        let mining_tx =
-            Transaction::create_transaction(None, None, MINING_REWARD, Some(beneficiary), 10);
+        let chain_id = last_block.block_headers.truncated_block_headers.chain_id;
+        let number = last_block.block_headers.truncated_block_headers.number + 1;
+        let mut mining_tx = Transaction::create_transaction(
+            None,
+            None,
+            MINING_REWARD,
+            Some(beneficiary),
+            10,
+            chain_id,
+        );
+        mining_tx.unsigned_tx.value = rules.reward_schedule.reward_at(number);
         tx_series.push(mining_tx);
 
         let tx_trie = Trie::build_trie(tx_series.clone());
-
-        let mut truncated_block_headers;
-        let mut nonce;
-        loop {
-            truncated_block_headers = TruncatedBlockHeaders {
-                parent_hash: keccak_hash(&last_block.block_headers),
-                beneficiary,
-                difficulty: Block::adjust_difficulty(last_block, timestamp),
-                number: last_block.block_headers.truncated_block_headers.number + 1,
-                timestamp,
-                tx_root: tx_trie.root_hash.clone(),
-                state_root: state_root.clone(),
-            };
-            let truncated_header_hash = keccak_hash(&truncated_block_headers);
-            nonce = rand::random::<u128>();
-
-            let under_target_hash = keccak_hash(&format!("{}{}", truncated_header_hash, nonce));
-            // println!("{}", target);
-            // println!("{}", under_target_hash);
-            if under_target_hash < target {
-                break;
-            }
-        }
-
+        let truncated_block_headers = TruncatedBlockHeaders {
+            chain_id,
+            parent_hash: last_block.hash(),
+            beneficiary,
+            difficulty: rules.consensus.difficulty(last_block, timestamp),
+            number,
+            timestamp,
+            tx_root: tx_trie.root_hash.clone(),
+            state_root: state_root.to_string(),
+            address_bloom: AddressBloom::from_txs(&tx_series),
+            randao,
+            receipts_root: None,
+        };
         Self {
             block_headers: BlockHeaders {
                 truncated_block_headers,
-                nonce,
+                nonce: 0,
             },
             tx_series,
+            header_hash: OnceLock::new(),
+        }
+    }
+
+    /// the proof of work, or whatever the consensus engine does instead. The headers are final after this
+    pub fn seal(self, last_block: &Block, rules: &ChainRules) -> Self {
+        rules.consensus.seal_block(last_block, self)
+    }
+
+    /// runs an unsealed block on top of state (last_block's) to fill in its receipts root. Miners with the state to
+    /// hand call this between candidate() and seal(), so the proof of work covers the root
+    pub fn with_receipts_root(
+        mut self,
+        last_block: &Block,
+        state: &State,
+        rules: &ChainRules,
+    ) -> Result<Self, TxError> {
+        let mut overlay = OverlayState::new(state);
+        //on a copy - run_block() hashes the block, and this one's headers aren't final yet
+        let receipts = Block::run_block(last_block, &self.clone(), &mut overlay, rules)?;
+        self.block_headers.truncated_block_headers.receipts_root = Some(Receipt::root(&receipts));
+        Ok(self)
+    }
+
+    /// the receipts run_block() gave for the block against its header's root, if it has one
+    pub fn check_receipts(&self, receipts: &[Receipt]) -> Result<(), ChainError> {
+        match &self.block_headers.truncated_block_headers.receipts_root {
+            Some(root) if *root != Receipt::root(receipts) => {
+                Err(ChainError::InvalidBlock("receipts root doesn't match"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// what the miner runs before spending any proof of work: every tx gets validated the way check_block() will and
+    /// then run the way run_block() will, on an overlay that's thrown away after. A tx that would get the whole block
+    /// rejected is left out. Returns the tx that passed, plus the hash of every one that didn't and why.
+    /// env = the block they're going into, see next_env()
+    pub fn preflight(
+        tx_series: Vec<Transaction>,
+        state: &State,
+        env: BlockEnv,
+    ) -> (Vec<Transaction>, Vec<(String, String)>) {
+        let mut executed = OverlayState::new(state);
+        let mut passed = vec![];
+        let mut dropped = vec![];
+        for tx in tx_series {
+            //each tx runs on a copy of the overlay, so one that fails half way doesn't leave writes behind
+            let mut execution = executed.clone();
+            let checked = match tx.unsigned_tx.data.tx_type {
+                //check_recipient() against what's run so far, so a contract created earlier in the block counts
+                TxType::Transact => Transaction::check_transaction(&tx, state)
+                    .and_then(|()| Transaction::check_recipient(&tx, &executed))
+                    .map_err(|e| e.to_string()),
+                TxType::CreateAccount
+                    if Transaction::validate_create_account_transaction(&tx, env.fork) =>
+                {
+                    Transaction::check_creation_payer(&tx, state).map_err(|e| e.to_string())
+                }
+                TxType::CreateAccount => Err("invalid account creation tx".into()),
+                //mine_block() adds the one real reward
+                TxType::MiningReward => Err("mining rewards only come from miners".into()),
+            };
+            let outcome = checked.and_then(|()| {
+                //nobody to pay fees to yet - it's the sender's balance that decides whether the tx goes through
+                Transaction::run_transaction(&tx, &mut execution, None, env)
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            });
+            match outcome {
+                Ok(()) => {
+                    executed = execution;
+                    passed.push(tx);
+                }
+                Err(reason) => dropped.push((tx.hash(), reason)),
+            }
+        }
+        (passed, dropped)
+    }
+
+    pub fn validate_block(
+        last_block: &Block,
+        this_block: &Block,
+        state: &State,
+        rules: &ChainRules,
+    ) -> bool {
+        match Block::check_block(last_block, this_block, state, rules) {
+            Ok(()) => true,
+            Err(reason) => {
+                tracing::warn!(reason = %reason, "invalid block");
+                false
+            }
+        }
+    }
+
+    /// same checks as validate_block(), but returns why the block is invalid instead of logging it.
+    /// Contract code doesn't run here - a tx whose contract fails is valid all the same, run_block() gives it a Failure
+    /// receipt
+    pub fn check_block(
+        last_block: &Block,
+        this_block: &Block,
+        state: &State,
+        rules: &ChainRules,
+    ) -> Result<(), ChainError> {
+        Block::check_block_structure(last_block, this_block, rules)?;
+        Block::check_txs(this_block, state, rules)
+    }
+
+    /// the half of check_block() that needs the state before the block - whether its tx are valid against it
+    pub fn check_txs(
+        this_block: &Block,
+        state: &State,
+        rules: &ChainRules,
+    ) -> Result<(), ChainError> {
+        let number = this_block.block_headers.truncated_block_headers.number;
+        if !Transaction::validate_transaction_series(&this_block.tx_series, number, state, rules) {
+            return Err(ChainError::InvalidBlock("contains an invalid tx"));
         }
+        Ok(())
     }
 
-    pub fn validate_block(last_block: &Block, this_block: &Block, state: &mut State) -> bool {
-        // if it's the genesis block, then it's by defn valid
-        if keccak_hash(this_block) == keccak_hash(&Block::genesis()) {
-            return true;
+    /// everything check_block() checks that doesn't need the state before the block - links, proof of work, limits,
+    /// roots. All a node that starts from a snapshot can check the blocks before it against
+    pub fn check_block_structure(
+        last_block: &Block,
+        this_block: &Block,
+        rules: &ChainRules,
+    ) -> Result<(), ChainError> {
+        //first, so an oversized block doesn't get any further
+        if this_block.tx_series.len() > MAX_BLOCK_TX {
+            return Err(ChainError::InvalidBlock("more tx than MAX_BLOCK_TX"));
+        }
+        if 2 * codec::encode(this_block).len() > MAX_BLOCK_BYTES {
+            return Err(ChainError::InvalidBlock("bigger than MAX_BLOCK_BYTES"));
+        }
+
+        if last_block.hash() != this_block.block_headers.truncated_block_headers.parent_hash {
+            return Err(ChainError::InvalidBlock(
+                "parent block header hash doesn't match",
+            ));
         }
 
-        if keccak_hash(&last_block.block_headers)
-            != this_block.block_headers.truncated_block_headers.parent_hash
+        let chain_id = last_block.block_headers.truncated_block_headers.chain_id;
+        if this_block.block_headers.truncated_block_headers.chain_id != chain_id {
+            return Err(ChainError::InvalidBlock(
+                "chain id doesn't match the parent block's",
+            ));
+        }
+        //the signature covers the chain id, so this is what stops tx from another network being replayed here
+        if this_block
+            .tx_series
+            .iter()
+            .any(|tx| tx.unsigned_tx.chain_id != chain_id)
+        {
+            return Err(ChainError::InvalidBlock("contains a tx for another chain"));
+        }
+        //the same transfer twice would credit the recipient twice
+        let mut ids = HashSet::new();
+        if !this_block
+            .tx_series
+            .iter()
+            .all(|tx| ids.insert(tx.unsigned_tx.id))
         {
-            println!("parent block header hash doesn't match");
-            return false;
+            return Err(ChainError::InvalidBlock("contains the same tx twice"));
         }
 
         if this_block.block_headers.truncated_block_headers.number
             != last_block.block_headers.truncated_block_headers.number + 1
         {
-            println!("block number didnt increment by 1 like it should");
-            return false;
+            return Err(ChainError::InvalidBlock(
+                "block number didnt increment by 1 like it should",
+            ));
         }
 
-        if (this_block.block_headers.truncated_block_headers.difficulty
-            - last_block.block_headers.truncated_block_headers.difficulty)
-            .abs()
-            > 1
-        {
-            println!("difficulty difference between two blocks above 1");
-            return false;
+        rules.consensus.verify_seal(last_block, this_block)?;
+
+        //a second reward would mint coins the schedule doesn't allow for
+        let rewards = this_block
+            .tx_series
+            .iter()
+            .filter(|tx| tx.unsigned_tx.data.tx_type == TxType::MiningReward)
+            .count();
+        if rewards > 1 {
+            return Err(ChainError::InvalidBlock("more than one mining reward"));
         }
 
-        let target = Block::calc_block_target_hash(last_block);
-        let rehashed_tbh = keccak_hash(&this_block.block_headers.truncated_block_headers);
-        let rehashed_bh = keccak_hash(&format!(
-            "{}{}",
-            rehashed_tbh, this_block.block_headers.nonce
-        ));
-        if rehashed_bh >= target {
-            println!("nonce check failed");
-            return false;
+        let rebuilt_tx_trie = Trie::build_trie(this_block.tx_series.clone());
+
+        if rebuilt_tx_trie.root_hash != this_block.block_headers.truncated_block_headers.tx_root {
+            return Err(ChainError::InvalidBlock(
+                "transaction root hash doesn't match",
+            ));
         }
 
-        if !Transaction::validate_transaction_series(&this_block.tx_series, state) {
-            return false;
+        let number = this_block.block_headers.truncated_block_headers.number;
+        Block::check_randao(last_block, this_block, rules.fork_schedule.fork_at(number))?;
+
+        //a bloom that leaves out an address would hide the block from that address's history
+        if AddressBloom::from_txs(&this_block.tx_series)
+            != this_block
+                .block_headers
+                .truncated_block_headers
+                .address_bloom
+        {
+            return Err(ChainError::InvalidBlock("address bloom doesn't match"));
         }
 
-        let rebuilt_tx_trie = Trie::build_trie(this_block.tx_series.clone());
+        Ok(())
+    }
 
-        if rebuilt_tx_trie.root_hash != this_block.block_headers.truncated_block_headers.tx_root {
-            println!("transaction root hash doesn't match");
-            return false;
+    /// from the paris fork on every block needs a randao whose reveal the beneficiary signed over the parent's mix,
+    /// before it none may have one
+    fn check_randao(last_block: &Block, this_block: &Block, fork: Fork) -> Result<(), ChainError> {
+        let randao = &this_block.block_headers.truncated_block_headers.randao;
+        match (randao, fork >= Fork::Paris) {
+            (None, false) => {}
+            (Some(_), false) => {
+                return Err(ChainError::InvalidBlock("randao before the paris fork"))
+            }
+            (None, true) => return Err(ChainError::InvalidBlock("missing randao")),
+            (Some(randao), true) => {
+                let parent_mix = last_block.randao_mix();
+                let beneficiary = &this_block.block_headers.truncated_block_headers.beneficiary;
+                if !Account::verify_signature(&parent_mix, &randao.reveal, beneficiary) {
+                    return Err(ChainError::InvalidBlock(
+                        "randao reveal isn't the beneficiary's signature over the parent's mix",
+                    ));
+                }
+                if randao.mix != Randao::mix(&parent_mix, &randao.reveal) {
+                    return Err(ChainError::InvalidBlock("randao mix doesn't match"));
+                }
+            }
         }
+        Ok(())
+    }
 
-        true
+    /// the beacon value as of this block. Blocks from before the paris fork don't have one, their hash stands in
+    pub fn randao_mix(&self) -> String {
+        match &self.block_headers.truncated_block_headers.randao {
+            Some(randao) => randao.mix.clone(),
+            None => self.hash(),
+        }
     }
 
-    pub fn run_block(block: &Block, state: &mut State) {
-        for tx in &block.tx_series {
-            Transaction::run_transaction(&tx, state);
+    /// what contract code sees in the block mined on top of this one - its fork, and this block's mix as
+    /// PREVRANDAO. Doesn't depend on the next block, so preflight and simulations see what the block will
+    pub fn next_env(&self, rules: &ChainRules) -> BlockEnv {
+        let number = self.block_headers.truncated_block_headers.number + 1;
+        //the first 7 hex digits always fit in a positive i32
+        let randao = i32::from_str_radix(&self.randao_mix()[..7], 16).unwrap_or(0);
+        BlockEnv {
+            fork: rules.fork_schedule.fork_at(number),
+            randao,
+            timeout: rules.exec_timeout,
         }
     }
+
+    /// the block's hash is the hash of its full headers (incl nonce) - same value the next block stores as parent_hash.
+    /// Only hashed the first time it's asked for - validation, fork choice and lookups by hash all go through here
+    pub fn hash(&self) -> String {
+        self.header_hash
+            .get_or_init(|| codec::hash(&self.block_headers))
+            .clone()
+    }
+
+    /// runs every tx in the block against the state and returns a receipt for each. A tx whose contract code fails
+    /// gets a Failure receipt and the block carries on. Stops at the first tx that's invalid, with the state part way
+    /// through the block
+    pub fn run_block(
+        last_block: &Block,
+        block: &Block,
+        state: &mut impl StateAccess,
+        rules: &ChainRules,
+    ) -> Result<Vec<Receipt>, TxError> {
+        let block_number = block.block_headers.truncated_block_headers.number;
+        let block_hash = block.hash();
+        let env = last_block.next_env(rules);
+        //gas fees go to whoever mined the block, less the treasury's cut
+        let payees = Payees {
+            beneficiary: block.block_headers.truncated_block_headers.beneficiary,
+            treasury: rules.treasury,
+        };
+        let mut cumulative_gas_used = 0;
+        block
+            .tx_series
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                let outcome = Transaction::run_transaction(tx, state, Some(payees), env)?;
+                cumulative_gas_used += outcome.gas_used;
+                let mut receipt = Receipt::new(
+                    tx,
+                    index,
+                    outcome.gas_used,
+                    cumulative_gas_used,
+                    block_number,
+                    &block_hash,
+                );
+                receipt.status = outcome.status();
+                Ok(receipt)
+            })
+            .collect()
+    }
+}
+
+// ----------------------------------------------------------------------------- encoding
+
+impl TruncatedBlockHeaders {
+    /// what proof of work seals - the nonce goes on top of it, see ProofOfWork::seal_hash()
+    pub fn hash(&self) -> String {
+        codec::hash(self)
+    }
+}
+
+impl Encode for TruncatedBlockHeaders {
+    fn to_rlp(&self) -> Rlp {
+        Record::new()
+            .field(1, &self.chain_id)
+            .field(2, &self.parent_hash)
+            .field(3, &self.beneficiary)
+            .field(4, &self.difficulty)
+            .field(5, &self.number)
+            .field(6, &self.timestamp)
+            .field(7, &self.tx_root)
+            .field(8, &self.state_root)
+            .field(9, &self.address_bloom)
+            .optional(10, &self.randao)
+            .optional(11, &self.receipts_root)
+            .build()
+    }
+}
+
+impl Decode for TruncatedBlockHeaders {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        let fields = Fields::of(item)?;
+        Ok(Self {
+            chain_id: fields.get(1)?,
+            parent_hash: fields.get(2)?,
+            beneficiary: fields.get(3)?,
+            difficulty: fields.get(4)?,
+            number: fields.get(5)?,
+            timestamp: fields.get(6)?,
+            tx_root: fields.get(7)?,
+            state_root: fields.get(8)?,
+            address_bloom: fields.get(9)?,
+            randao: fields.optional(10)?,
+            receipts_root: fields.optional(11)?,
+        })
+    }
+}
+
+impl Encode for Randao {
+    fn to_rlp(&self) -> Rlp {
+        Record::new()
+            .field(1, &self.reveal)
+            .field(2, &self.mix)
+            .build()
+    }
+}
+
+impl Decode for Randao {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        let fields = Fields::of(item)?;
+        Ok(Self {
+            reveal: fields.get(1)?,
+            mix: fields.get(2)?,
+        })
+    }
+}
+
+impl Encode for BlockHeaders {
+    fn to_rlp(&self) -> Rlp {
+        Record::new()
+            .field(1, &self.truncated_block_headers)
+            .field(2, &self.nonce)
+            .build()
+    }
+}
+
+impl Decode for BlockHeaders {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        let fields = Fields::of(item)?;
+        Ok(Self {
+            truncated_block_headers: fields.get(1)?,
+            nonce: fields.get(2)?,
+        })
+    }
+}
+
+impl Encode for Block {
+    fn to_rlp(&self) -> Rlp {
+        Record::new()
+            .field(1, &self.block_headers)
+            .field(2, &self.tx_series)
+            .build()
+    }
+}
+
+impl Decode for Block {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        let fields = Fields::of(item)?;
+        let mut block = Block::new(fields.get(1)?);
+        block.tx_series = fields.get(2)?;
+        Ok(block)
+    }
 }
 
 // ----------------------------------------------------------------------------- tests
@@ -221,32 +718,65 @@ impl Block {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::util::clock::{ManualClock, SystemClock};
     use crate::util::prep_state;
     use ntest::timeout;
 
+    fn mine_at(last_block: &Block, clock: &ManualClock) -> Block {
+        Block::mine_block(
+            last_block,
+            gen_address(),
+            vec![],
+            "",
+            clock,
+            &ChainRules::default(),
+        )
+    }
+
+    #[test]
+    fn test_genesis_timestamp() {
+        let clock = ManualClock::new(1_000 * SECONDS);
+        let genesis = Block::genesis(&clock, DEFAULT_CHAIN_ID);
+        assert_eq!(
+            genesis.block_headers.truncated_block_headers.timestamp,
+            970 * SECONDS
+        );
+    }
+
     #[test]
     fn test_difficulty_down() {
-        let b = Block::mine_block(&Block::genesis(), gen_keypair().1, vec![], &"".into());
+        let clock = ManualClock::new(1_000 * SECONDS);
+        let b = mine_at(&Block::genesis(&clock, DEFAULT_CHAIN_ID), &clock);
         assert_eq!(b.block_headers.truncated_block_headers.difficulty, 1);
+        assert_eq!(
+            b.block_headers.truncated_block_headers.timestamp,
+            1_000 * SECONDS
+        );
     }
 
     #[test]
     fn test_difficulty_up() {
-        let b = Block::mine_block(&Block::genesis(), gen_keypair().1, vec![], &"".into());
-        let b = Block::mine_block(&b, gen_keypair().1, vec![], &"".into());
+        let clock = ManualClock::new(1_000 * SECONDS);
+        let b = mine_at(&Block::genesis(&clock, DEFAULT_CHAIN_ID), &clock);
+        clock.advance(MINE_RATE);
+        let b = mine_at(&b, &clock);
         assert_eq!(b.block_headers.truncated_block_headers.difficulty, 2);
+        clock.advance(MINE_RATE + MILLISECONDS);
+        let b = mine_at(&b, &clock);
+        assert_eq!(b.block_headers.truncated_block_headers.difficulty, 1);
     }
 
     #[test]
     fn test_calc_target_hash_genesis() {
-        let last_block = Block::genesis();
+        let last_block = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
         let target = Block::calc_block_target_hash(&last_block);
         assert_eq!(target, "f".repeat(HASH_LENGTH));
     }
 
     #[test]
     fn test_calc_target_hash() {
-        let mut last_block = Block::genesis();
+        let mut last_block = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
         last_block.block_headers.truncated_block_headers.difficulty = 1000;
         let target = Block::calc_block_target_hash(&last_block);
 
@@ -263,21 +793,74 @@ mod tests {
     #[timeout(10000)]
     #[should_panic]
     fn test_high_difficulty() {
-        let mut last_block = Block::genesis();
-        last_block.block_headers.truncated_block_headers.difficulty = 1000000;
-        let _b = Block::mine_block(&last_block, gen_keypair().1, vec![], &"".into());
+        let mut last_block = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
+        last_block.block_headers.truncated_block_headers.difficulty = 1_000_000_000;
+        let _b = Block::mine_block(
+            &last_block,
+            gen_address(),
+            vec![],
+            "",
+            &SystemClock,
+            &ChainRules::default(),
+        );
     }
 
     #[test]
     fn test_bad_hash() {
         let mut global_state = prep_state();
 
-        let last_block = Block::genesis();
-        let mut b = Block::mine_block(&last_block, gen_keypair().1, vec![], &"".into());
+        let last_block = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
+        let mut b = Block::mine_block(
+            &last_block,
+            gen_address(),
+            vec![],
+            "",
+            &SystemClock,
+            &ChainRules::default(),
+        );
         b.block_headers.truncated_block_headers.parent_hash = "this-is-clearly-wrong".into();
+        assert!(!Block::validate_block(
+            &last_block,
+            &b,
+            &global_state.blockchain.get_mut().unwrap().state,
+            &ChainRules::default()
+        ));
+    }
+
+    #[test]
+    fn test_tx_from_another_chain() {
+        let mut global_state = prep_state();
+
+        let last_block = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
+        let mut tx = Transaction::create_transaction(
+            Some(crate::account::Account::new(vec![])),
+            None,
+            0,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        tx.unsigned_tx.chain_id = DEFAULT_CHAIN_ID + 1;
+        let b = Block::mine_block(
+            &last_block,
+            gen_address(),
+            vec![tx],
+            "",
+            &SystemClock,
+            &ChainRules::default(),
+        );
+        assert_eq!(
+            b.block_headers.truncated_block_headers.chain_id,
+            DEFAULT_CHAIN_ID
+        );
         assert_eq!(
-            false,
-            Block::validate_block(&last_block, &b, &mut global_state.blockchain.state)
+            Block::check_block(
+                &last_block,
+                &b,
+                &global_state.blockchain.get_mut().unwrap().state,
+                &ChainRules::default()
+            ),
+            Err(ChainError::InvalidBlock("contains a tx for another chain"))
         );
     }
 
@@ -285,11 +868,331 @@ mod tests {
     fn test_good_hash() {
         let mut global_state = prep_state();
 
-        let last_block = Block::genesis();
-        let b = Block::mine_block(&last_block, gen_keypair().1, vec![], &"".into());
+        let last_block = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
+        let b = Block::mine_block(
+            &last_block,
+            gen_address(),
+            vec![],
+            "",
+            &SystemClock,
+            &ChainRules::default(),
+        );
+        assert!(Block::validate_block(
+            &last_block,
+            &b,
+            &global_state.blockchain.get_mut().unwrap().state,
+            &ChainRules::default()
+        ));
+    }
+
+    #[test]
+    fn test_address_bloom_has_to_match_the_tx() {
+        let mut global_state = prep_state();
+        let beneficiary = gen_address();
+
+        let last_block = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
+        let mut b = Block::mine_block(
+            &last_block,
+            beneficiary,
+            vec![],
+            "",
+            &SystemClock,
+            &ChainRules::default(),
+        );
+        assert!(b
+            .block_headers
+            .truncated_block_headers
+            .address_bloom
+            .might_contain(&beneficiary));
+
+        //genesis has difficulty 1, so the nonce still passes after the header is changed
+        b.block_headers.truncated_block_headers.address_bloom = AddressBloom::default();
+        assert_eq!(
+            Block::check_block(
+                &last_block,
+                &b,
+                &global_state.blockchain.get_mut().unwrap().state,
+                &ChainRules::default()
+            ),
+            Err(ChainError::InvalidBlock("address bloom doesn't match"))
+        );
+    }
+
+    #[test]
+    fn test_preflight_leaves_out_tx_that_would_fail() {
+        let sender = crate::account::Account::new(vec![]);
+        let receiver = gen_address();
+        let mut state = State::new();
+        state.allocate(sender.public_account.address, 100);
+        let state_root = state.get_state_root().clone();
+
+        let ok = Transaction::create_transaction(
+            Some(sender.clone()),
+            Some(receiver),
+            50,
+            None,
+            10,
+            DEFAULT_CHAIN_ID,
+        );
+        //the sender never had this much
+        let too_much = Transaction::create_transaction(
+            Some(sender.clone()),
+            Some(receiver),
+            500,
+            None,
+            10,
+            DEFAULT_CHAIN_ID,
+        );
+        //nobody created this sender's account
+        let unknown = Transaction::create_transaction(
+            Some(crate::account::Account::new(vec![])),
+            Some(receiver),
+            1,
+            None,
+            10,
+            DEFAULT_CHAIN_ID,
+        );
+        //spam - nothing to send and no code to run
+        let pointless = Transaction::create_transaction(
+            Some(sender.clone()),
+            Some(receiver),
+            0,
+            None,
+            10,
+            DEFAULT_CHAIN_ID,
+        );
+        let (passed, dropped) = Block::preflight(
+            vec![
+                ok.clone(),
+                too_much.clone(),
+                unknown.clone(),
+                pointless.clone(),
+            ],
+            &state,
+            BlockEnv::default(),
+        );
+        assert_eq!(passed.len(), 1);
+        assert_eq!(passed[0].hash(), ok.hash());
+        let dropped_hashes: Vec<String> = dropped.iter().map(|(hash, _)| hash.clone()).collect();
+        assert_eq!(
+            dropped_hashes,
+            vec![too_much.hash(), unknown.hash(), pointless.hash()]
+        );
+        assert_eq!(dropped[0].1, TxError::ExceededBalance.to_string());
+        assert_eq!(state.get_state_root(), &state_root);
+
+        //and the block mined from what's left is valid
+        let genesis = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
+        let b = Block::mine_block(
+            &genesis,
+            receiver,
+            passed,
+            &state_root,
+            &SystemClock,
+            &ChainRules::default(),
+        );
+        assert_eq!(
+            Block::check_block(&genesis, &b, &state, &ChainRules::default()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_block_limits() {
+        //the limits get checked before anything that needs the state
+        let state = &State::new();
+        let genesis = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
+        let reward = || {
+            Transaction::create_transaction(None, None, 0, Some(gen_address()), 0, DEFAULT_CHAIN_ID)
+        };
+
+        //the miner leaves whatever doesn't fit for later
+        let b = Block::mine_block(
+            &genesis,
+            gen_address(),
+            (0..MAX_BLOCK_TX + 5).map(|_| reward()).collect(),
+            "",
+            &SystemClock,
+            &ChainRules::default(),
+        );
+        assert_eq!(b.tx_series.len(), MAX_BLOCK_TX);
+        //a contract this big is over the limit all on its own
+        let huge = Transaction::create_transaction(
+            Some(crate::account::Account::new(vec![
+                crate::interpreter::OPCODE::STOP;
+                MAX_BLOCK_BYTES / 2
+            ])),
+            None,
+            0,
+            None,
+            0,
+            DEFAULT_CHAIN_ID,
+        );
+        assert!(Block::fill(vec![huge.clone()]).is_empty());
+
+        //and a block that ignores the limits doesn't validate
+        let mut too_many = b.clone();
+        too_many.tx_series.push(reward());
+        assert_eq!(
+            Block::check_block(&genesis, &too_many, state, &ChainRules::default()),
+            Err(ChainError::InvalidBlock("more tx than MAX_BLOCK_TX"))
+        );
+        let mut too_big = mine_at(&genesis, &ManualClock::new(0));
+        too_big.tx_series.push(huge);
+        assert_eq!(
+            Block::check_block(&genesis, &too_big, state, &ChainRules::default()),
+            Err(ChainError::InvalidBlock("bigger than MAX_BLOCK_BYTES"))
+        );
+    }
+
+    #[test]
+    fn test_reward_follows_the_schedule() {
+        let genesis = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
+        let state = &State::new();
+        let b = Block::mine_block(
+            &genesis,
+            gen_address(),
+            vec![],
+            "",
+            &SystemClock,
+            &ChainRules::default(),
+        );
+        assert_eq!(
+            b.tx_series[0].unsigned_tx.value,
+            ChainRules::default().reward_schedule.reward_at(1)
+        );
+        assert_eq!(
+            Block::check_block(&genesis, &b, state, &ChainRules::default()),
+            Ok(())
+        );
+
+        //with its headers built over the bigger reward, so it's the reward check that catches it
+        let mut overpaid = Block::assemble(
+            &genesis,
+            gen_address(),
+            None,
+            vec![],
+            "",
+            &SystemClock,
+            &ChainRules::default(),
+        );
+        overpaid.tx_series[0].unsigned_tx.value = ChainRules::default()
+            .reward_schedule
+            .reward_at(1)
+            .saturating_add(1.into());
+        overpaid.block_headers.truncated_block_headers.tx_root =
+            Trie::build_trie(overpaid.tx_series.clone()).root_hash;
+        let overpaid = overpaid.seal(&genesis, &ChainRules::default());
+        assert_eq!(
+            Block::check_block(&genesis, &overpaid, state, &ChainRules::default()),
+            Err(ChainError::InvalidBlock("contains an invalid tx"))
+        );
+        let mut paid_twice = b;
+        let mut second = paid_twice.tx_series[0].clone();
+        second.unsigned_tx.id = uuid::Uuid::new_v4();
+        paid_twice.tx_series.push(second);
+        assert_eq!(
+            Block::check_block(&genesis, &paid_twice, state, &ChainRules::default()),
+            Err(ChainError::InvalidBlock("more than one mining reward"))
+        );
+    }
+
+    #[test]
+    fn test_hash_is_cached_but_not_serialized() {
+        let b = Block::mine_block(
+            &Block::genesis(&SystemClock, DEFAULT_CHAIN_ID),
+            gen_address(),
+            vec![],
+            "",
+            &SystemClock,
+            &ChainRules::default(),
+        );
+        assert_eq!(b.hash(), codec::hash(&b.block_headers));
+        assert_eq!(b.header_hash.get(), Some(&b.hash()));
+
+        let json = serde_json::to_string(&b).unwrap();
+        assert!(!json.contains("header_hash"));
+        let received: Block = serde_json::from_str(&json).unwrap();
+        assert!(received.header_hash.get().is_none());
+        assert_eq!(received.hash(), b.hash());
+    }
+
+    #[test]
+    fn test_encoding_roundtrip() {
+        let mut global_state = prep_state();
+        let tx_series = global_state.tx_queue.get_mut().unwrap().get_tx_series();
+        let genesis = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
+        let miner = Account::new(vec![]);
+        let block = Block::mine(
+            &genesis,
+            miner.public_account.address,
+            Some(Randao::new(&miner, &genesis.randao_mix())),
+            tx_series,
+            "",
+            &SystemClock,
+            &ChainRules::default(),
+        );
+
+        let encoded = codec::encode(&block);
+        assert_eq!(encoded[0], codec::CODEC_VERSION);
+        let decoded: Block = codec::decode(&encoded).unwrap();
+        assert_eq!(decoded.hash(), block.hash());
+        assert_eq!(
+            serde_json::to_string(&decoded).unwrap(),
+            serde_json::to_string(&block).unwrap()
+        );
+        //the same block encodes to the same bytes, every time
+        assert_eq!(codec::encode(&decoded), encoded);
+
+        let mut newer = encoded.clone();
+        newer[0] += 1;
+        assert!(matches!(
+            codec::decode::<Block>(&newer),
+            Err(CodecError::UnsupportedVersion(_))
+        ));
+        assert!(codec::decode::<Block>(&encoded[..encoded.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_randao() {
+        let genesis = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
+        let miner = Account::new(vec![]);
+        let mut block = Block::mine(
+            &genesis,
+            miner.public_account.address,
+            Some(Randao::new(&miner, &genesis.randao_mix())),
+            vec![],
+            "",
+            &SystemClock,
+            &ChainRules::default(),
+        );
+        assert!(Block::check_randao(&genesis, &block, Fork::Paris).is_ok());
+        assert_eq!(
+            Block::check_randao(&genesis, &block, Fork::Constantinople),
+            Err(ChainError::InvalidBlock("randao before the paris fork"))
+        );
+        //the mix carries on from the block, and is what its child's contracts see
+        let mix = block.randao_mix();
+        assert_ne!(mix, block.hash());
+        assert_eq!(
+            block.next_env(&ChainRules::default()).randao,
+            i32::from_str_radix(&mix[..7], 16).unwrap()
+        );
+
+        //someone else's reveal doesn't count, even with a matching mix
+        let other = Account::new(vec![]);
+        let forged = Randao::new(&other, &genesis.randao_mix());
+        block.block_headers.truncated_block_headers.randao = Some(forged);
+        assert_eq!(
+            Block::check_randao(&genesis, &block, Fork::Paris),
+            Err(ChainError::InvalidBlock(
+                "randao reveal isn't the beneficiary's signature over the parent's mix"
+            ))
+        );
+        block.block_headers.truncated_block_headers.randao = None;
         assert_eq!(
-            true,
-            Block::validate_block(&last_block, &b, &mut global_state.blockchain.state)
+            Block::check_randao(&genesis, &block, Fork::Paris),
+            Err(ChainError::InvalidBlock("missing randao"))
         );
     }
 }
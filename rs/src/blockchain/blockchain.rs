@@ -1,57 +1,1404 @@
+use crate::account::address::Address;
+use crate::account::Account;
 use crate::blockchain::block::Block;
+use crate::blockchain::gas_stats::{BlockGasStats, ContractGas};
+use crate::blockchain::snapshot::Snapshot;
+use crate::config::chain::ChainRules;
+use crate::error::{ChainError, StoreError, TxError};
+use crate::interpreter::profile::{GasProfile, TxGasProfile};
+use crate::store::codec;
+use crate::store::overlay::{OverlayState, StateWrites};
+use crate::store::receipts::ReceiptStore;
 use crate::store::state::State;
-use crate::transaction::tx_queue::TransactionQueue;
+use crate::store::trie::Trie;
+use crate::transaction::activity::Activity;
+use crate::transaction::fee::payload_gas;
+use crate::transaction::receipt::Receipt;
+use crate::transaction::tx::{Transaction, TxType};
+use crate::util::clock::{system_clock, Clock, ConsensusClock};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// every block as codec::encode() has it, behind a 4 byte big endian length, genesis first. Appended to as blocks
+/// get added
+pub const BLOCKS_FILE: &str = "blocks.bin";
+/// how many blocks back a new block's tx get checked for having been mined already.
+/// Bounds the cost of the check - every block it adds means hashing another block's worth of tx ids
+pub const TX_REPLAY_LOOKBACK: usize = 256;
+/// how many confirmations (the tx's own block included) before a tx counts as finalized. Proof of work never
+/// really finalizes anything - this is just deep enough that a reorg dropping the tx is very unlikely
+pub const FINALITY_CONFIRMATIONS: usize = 6;
+
+/// a block that's been checked and run, waiting to be committed - see Blockchain::prepare_block()
+#[derive(Debug)]
+pub struct PendingBlock {
+    block: Block,
+    //the head it ran on top of. It can only be committed on top of the same one
+    parent_hash: String,
+    writes: StateWrites,
+    receipts: Vec<Receipt>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Blockchain {
-    pub chain: Vec<Block>,
+    //blocks never change once added, so they're behind an Arc - readers (eg the api) can grab a copy of the chain
+    // under the lock for the price of a few refcount bumps, then serialize it after the lock is released
+    pub chain: Vec<Arc<Block>>,
     pub state: State,
+    //the state block 1 ran on top of, for a fork to be replayed on. None while genesis is still our head - the
+    // genesis allocations go straight into state until then
+    #[serde(skip)]
+    pub genesis_state: Option<State>,
+    //tx hash -> receipt, filled in as blocks get run
+    pub receipts: HashMap<String, Receipt>,
+    //where receipts get persisted (<datadir>/receipts), block by block. None = in memory only
+    #[serde(skip)]
+    pub receipt_store: Option<ReceiptStore>,
+    //how many blocks of receipts to keep, in memory and on disk. None = all of them
+    #[serde(skip)]
+    pub receipt_history: Option<usize>,
+    //block number -> gas figures, filled in as blocks get run. Rebuilt from chaindata on restart, same as receipts
+    pub gas_stats: HashMap<usize, BlockGasStats>,
+    //address -> (block number, storage trie as of that block), only pushed when the trie's root changes.
+    // Lets us answer storage reads at past blocks without snapshotting the whole state every block
+    pub storage_history: HashMap<Address, Vec<(usize, Trie)>>,
+    //how many blocks of storage_history to keep. None = all of them
+    #[serde(skip)]
+    pub storage_history_depth: Option<usize>,
+    //the oldest block storage can still be read at - anything before it has been pruned
+    #[serde(default)]
+    pub storage_history_from: usize,
+    //address -> everything mined that touched it, oldest first. Saves scanning every block for an address's history
+    pub activity: HashMap<Address, Vec<Activity>>,
+    //block hash -> number, for every block on our chain and nothing else. Receipts outlive a reorg - this is what
+    // tells the ones from blocks we've dropped apart, see is_canonical()
+    #[serde(default)]
+    pub canonical: HashMap<String, usize>,
+    //where accepted blocks get persisted (<datadir>/chaindata). None = in memory only
+    #[serde(skip)]
+    pub dir: Option<PathBuf>,
+    //timestamps genesis and every block mined on top of this chain
+    #[serde(skip, default = "system_clock")]
+    pub clock: Arc<dyn Clock>,
+    //what blocks get mined with and checked against. Has to be set before open() replays anything
+    #[serde(skip)]
+    pub rules: ChainRules,
 }
 
 impl Blockchain {
-    pub fn new(state: State) -> Self {
-        Self {
-            chain: vec![Block::genesis()],
+    pub fn new(state: State, chain_id: u64) -> Self {
+        Blockchain::with_clock(state, system_clock(), chain_id)
+    }
+    pub fn with_clock(state: State, clock: Arc<dyn Clock>, chain_id: u64) -> Self {
+        let mut blockchain = Self {
+            chain: vec![Arc::new(Block::genesis(&*clock, chain_id))],
             state,
+            genesis_state: None,
+            receipts: HashMap::new(),
+            receipt_store: None,
+            receipt_history: None,
+            gas_stats: HashMap::new(),
+            storage_history: HashMap::new(),
+            storage_history_depth: None,
+            storage_history_from: 0,
+            activity: HashMap::new(),
+            canonical: HashMap::new(),
+            dir: None,
+            clock,
+            rules: ChainRules::default(),
+        };
+        blockchain.index_canonical();
+        blockchain
+    }
+    /// replays the blocks persisted in dir on top of the genesis state, then persists every block added from here on.
+    /// Has to be called before any blocks are added
+    pub fn open(&mut self, dir: PathBuf) -> Result<(), ChainError> {
+        fs::create_dir_all(&dir)
+            .map_err(|e| StoreError::Io(format!("failed to create {:?}: {}", dir, e)))?;
+        let path = dir.join(BLOCKS_FILE);
+        if path.exists() {
+            let contents = fs::read(&path)
+                .map_err(|e| StoreError::Io(format!("failed to read {:?}: {}", path, e)))?;
+            let chain = decode_records(&contents).map_err(|reason| StoreError::Corrupt {
+                key: format!("{:?}", path),
+                reason,
+            })?;
+            if chain.len() > 1 {
+                self.replace_chain(chain)?;
+            }
         }
+        self.dir = Some(dir);
+        Ok(self.persist_chain()?)
     }
-    pub fn add_block(&mut self, block: Block, tx_queue: &mut TransactionQueue) -> bool {
+    fn persist_chain(&self) -> Result<(), StoreError> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+        let records = self
+            .chain
+            .iter()
+            .flat_map(|block| encode_record(block))
+            .collect::<Vec<u8>>();
+        let path = dir.join(BLOCKS_FILE);
+        fs::write(&path, records)
+            .map_err(|e| StoreError::Io(format!("failed to write {:?}: {}", path, e)))
+    }
+    fn persist_block(&self, block: &Block) -> Result<(), StoreError> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+        let path = dir.join(BLOCKS_FILE);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .map_err(|e| StoreError::Io(format!("failed to open {:?}: {}", path, e)))?;
+        file.write_all(&encode_record(block))
+            .map_err(|e| StoreError::Io(format!("failed to write {:?}: {}", path, e)))
+    }
+    /// the block miner would mine on top of our head, receipts root and all. Only Block::seal() is left, and that
+    /// can run without holding any lock
+    pub fn candidate(
+        &self,
+        miner: &Account,
+        tx_series: Vec<Transaction>,
+    ) -> Result<(Arc<Block>, Block), TxError> {
+        let last_block = self.chain[self.chain.len() - 1].clone();
+        let block = Block::candidate(
+            &last_block,
+            miner,
+            tx_series,
+            self.state.get_state_root(),
+            &*self.clock,
+            &self.rules,
+        )
+        .with_receipts_root(&last_block, &self.state, &self.rules)?;
+        Ok((last_block, block))
+    }
+    /// NOTE: doesn't touch the tx queue - if this returns Ok, it's on the caller to clear the block's tx from it
+    pub fn add_block(&mut self, block: Block) -> Result<(), ChainError> {
+        let pending = self.prepare_block(block)?;
+        self.commit_block(pending)
+    }
+    /// the slow half of add_block() - checks the block and runs it on top of our head, without changing anything.
+    /// Only needs &self, so it can run under a read lock while readers keep seeing the last committed state
+    pub fn prepare_block(&self, block: Block) -> Result<PendingBlock, ChainError> {
         let last_block = &self.chain[self.chain.len() - 1];
-        if Block::validate_block(last_block, &block, &mut self.state) {
-            println!(
-                "block {} is valid, adding to chain...",
-                block.block_headers.truncated_block_headers.number
-            );
-            //clear processed tx from the queue
-            tx_queue.clear_block_tx(&block.tx_series);
-            //run block
-            Block::run_block(&block, &mut self.state);
-            //update the blockchain
-            self.chain.push(block);
-            return true;
+        //replays before the tx checks, whose nonce check would turn them away too, only less helpfully
+        Block::check_block_structure(last_block, &block, &self.rules)?;
+        check_not_replayed(&self.recent_tx_ids(), &block)?;
+        Block::check_txs(&block, &self.state, &self.rules)?;
+        //run on an overlay, so a tx failing half way through leaves nothing half updated
+        let mut overlay = OverlayState::new(&self.state);
+        let receipts = Block::run_block(last_block, &block, &mut overlay, &self.rules)?;
+        block.check_receipts(&receipts)?;
+        Ok(PendingBlock {
+            parent_hash: last_block.hash(),
+            writes: overlay.into_writes(),
+            receipts,
+            block,
+        })
+    }
+    /// the quick half of add_block() - swaps a prepared block and everything it changed in, all in one go
+    pub fn commit_block(&mut self, pending: PendingBlock) -> Result<(), ChainError> {
+        //another block got in since it was prepared
+        if self.chain[self.chain.len() - 1].hash() != pending.parent_hash {
+            return Err(ChainError::InvalidBlock(
+                "parent block header hash doesn't match",
+            ));
+        }
+        let PendingBlock {
+            block,
+            writes,
+            receipts,
+            ..
+        } = pending;
+        tracing::info!(
+            number = block.block_headers.truncated_block_headers.number,
+            "block is valid, adding to chain"
+        );
+        if self.genesis_state.is_none() {
+            self.genesis_state = Some(self.state.clone());
+        }
+        self.state.apply(writes);
+        self.record_gas_stats(&block, &receipts);
+        self.store_receipts(receipts);
+        self.prune_receipts(block.block_headers.truncated_block_headers.number);
+        self.record_storage_history(block.block_headers.truncated_block_headers.number);
+        self.index_activity(&block);
+        //the block stays accepted even if it can't be written - it'll come back from a bootnode after a restart
+        if let Err(e) = self.persist_block(&block) {
+            tracing::error!(error = %e, "failed to persist block");
+        }
+        //update the blockchain
+        self.canonical.insert(
+            block.hash(),
+            block.block_headers.truncated_block_headers.number,
+        );
+        self.chain.push(Arc::new(block));
+        Ok(())
+    }
+    pub fn store_receipts(&mut self, receipts: Vec<Receipt>) {
+        if let (Some(store), Some(first)) = (&self.receipt_store, receipts.first()) {
+            //same as a block that can't be written, the receipts are still served from memory
+            if let Err(e) = store.put(first.block_number, &first.block_hash, &receipts) {
+                tracing::error!(error = %e, "failed to persist receipts");
+            }
+        }
+        for receipt in receipts {
+            self.receipts.insert(receipt.tx_hash.clone(), receipt);
+        }
+    }
+    //drops the receipts of every block more than receipt_history blocks under block_number
+    fn prune_receipts(&mut self, block_number: usize) {
+        let from = match self.receipt_history {
+            Some(depth) => block_number.saturating_sub(depth),
+            None => return,
+        };
+        self.receipts
+            .retain(|_, receipt| receipt.block_number >= from);
+        if let Some(store) = &self.receipt_store {
+            if let Err(e) = store.prune(from) {
+                tracing::error!(error = %e, "failed to prune receipts");
+            }
+        }
+    }
+    fn record_gas_stats(&mut self, block: &Block, receipts: &[Receipt]) {
+        let stats = gas_stats(block, receipts, &self.state);
+        self.gas_stats.insert(stats.block_number, stats);
+    }
+    /// None for genesis and for blocks we don't have
+    pub fn get_gas_stats(&self, block_number: usize) -> Option<&BlockGasStats> {
+        self.gas_stats.get(&block_number)
+    }
+    pub fn get_total_gas_used(&self) -> u64 {
+        self.gas_stats.values().map(|stats| stats.gas_used).sum()
+    }
+    /// every contract called on our chain, with the calls and gas they took between them, most gas first
+    pub fn contract_gas(&self) -> Vec<ContractGas> {
+        ContractGas::total(
+            self.gas_stats
+                .values()
+                .flat_map(|stats| stats.contracts.iter()),
+        )
+    }
+    fn index_activity(&mut self, block: &Block) {
+        let block_number = block.block_headers.truncated_block_headers.number;
+        for tx in block.tx_series.iter() {
+            for (address, activity) in Activity::from_tx(tx, block_number) {
+                self.activity.entry(address).or_default().push(activity);
+            }
+        }
+    }
+    fn index_canonical(&mut self) {
+        self.canonical = self
+            .chain
+            .iter()
+            .map(|block| {
+                (
+                    block.hash(),
+                    block.block_headers.truncated_block_headers.number,
+                )
+            })
+            .collect();
+    }
+    /// whether the block is on our chain, rather than one dropped in a reorg
+    pub fn is_canonical(&self, block_hash: &str) -> bool {
+        self.canonical.contains_key(block_hash)
+    }
+    /// oldest first
+    pub fn get_activity(&self, address: &Address) -> &[Activity] {
+        self.activity
+            .get(address)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+    fn record_storage_history(&mut self, block_number: usize) {
+        push_storage_history(&mut self.state, &mut self.storage_history, block_number);
+        if let Some(depth) = self.storage_history_depth {
+            self.prune_storage_history(block_number.saturating_sub(depth));
+        }
+    }
+    //drops every trie that nothing from `from` onwards can be read from. Per address, the newest one at or
+    // before `from` stays - it's what the slots still looked like at `from`
+    fn prune_storage_history(&mut self, from: usize) {
+        if from <= self.storage_history_from {
+            return;
+        }
+        for history in self.storage_history.values_mut() {
+            let base = history.iter().rposition(|(number, _)| *number <= from);
+            if let Some(base) = base {
+                history.drain(..base);
+            }
+        }
+        self.storage_history_from = from;
+    }
+    /// value of a storage slot as of the given block. None if the slot was never written to by then
+    pub fn get_storage_at(
+        &self,
+        address: &Address,
+        key: &str,
+        block_number: usize,
+    ) -> Result<Option<String>, StoreError> {
+        let trie = self.storage_trie_at(address, block_number)?;
+        //the trie returns "" for keys that are only a prefix of another key
+        Ok(trie.and_then(|trie| {
+            trie.get(key.into())
+                .filter(|value| !value.is_empty())
+                .cloned()
+        }))
+    }
+    /// the address's storage as of the given block. None if nothing was written to it by then
+    fn storage_trie_at(
+        &self,
+        address: &Address,
+        block_number: usize,
+    ) -> Result<Option<&Trie>, StoreError> {
+        if block_number < self.storage_history_from {
+            return Err(StoreError::Pruned {
+                block: block_number,
+                oldest: self.storage_history_from,
+            });
+        }
+        Ok(self.storage_history.get(address).and_then(|history| {
+            history
+                .iter()
+                .rev()
+                .find(|(number, _)| *number <= block_number)
+                .map(|(_, trie)| trie)
+        }))
+    }
+    /// runs a mined tx's contract code again, this time counting gas per opcode. Starts from the contract's storage
+    /// before the block and runs the block's earlier calls to it first, so the code sees the slots it saw when mined.
+    /// None if the tx isn't on our chain. Needs storage history back to the block's parent, see storage_history_depth
+    /// (!) runs the contract's code as it is now - a contract recreated since with different code gets the new code
+    pub fn profile_tx(&self, tx_hash: &str) -> Result<Option<TxGasProfile>, TxError> {
+        let (tx, receipt) = match self.get_transaction(tx_hash) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        //only a transfer runs the recipient's code
+        let calls = |tx: &Transaction, contract: &Address| {
+            tx.unsigned_tx.data.tx_type == TxType::Transact
+                && tx.unsigned_tx.to.as_ref() == Some(contract)
+        };
+        let contract = tx
+            .unsigned_tx
+            .to
+            .filter(|to| calls(tx, to))
+            .and_then(|to| self.state.find_account(to))
+            .filter(|account| account.code_hash.is_some());
+
+        let mut profile = GasProfile::default();
+        if let Some(account) = &contract {
+            let parent = receipt.block_number - 1;
+            let env = self.chain[parent].next_env(&self.rules);
+            let mut storage_trie = self
+                .storage_trie_at(&account.address, parent)?
+                .cloned()
+                .unwrap_or_else(Trie::new);
+            let block = &self.chain[receipt.block_number];
+            //a call that failed left the storage as it was, run_code() undoes it the same way here. So the profile of
+            // a failed tx is what ran up to the failure
+            for earlier in block.tx_series[..receipt.transaction_index]
+                .iter()
+                .filter(|earlier| calls(earlier, &account.address))
+            {
+                let _ = earlier
+                    .interpreter(env)
+                    .run_code(account.code.clone(), &mut storage_trie);
+            }
+            let mut interpreter = tx.interpreter(env).with_profile();
+            let _ = interpreter.run_code(account.code.clone(), &mut storage_trie);
+            profile = interpreter.profile.unwrap_or_default();
+        }
+
+        let payload_gas = payload_gas(tx.payload_bytes());
+        Ok(Some(TxGasProfile {
+            tx_hash: receipt.tx_hash.clone(),
+            block_number: receipt.block_number,
+            contract: contract.map(|account| account.address),
+            gas_used: receipt.gas_used,
+            payload_gas,
+            execution_gas: profile.total(),
+            by_category: profile.by_category(),
+            opcodes: profile.opcodes(),
+        }))
+    }
+    /// "latest" / "pending", "earliest", a decimal or a 0x-prefixed hex block number.
+    /// None if the tag is invalid or the block doesn't exist yet
+    pub fn resolve_block_tag(&self, tag: &str) -> Option<usize> {
+        let head = self.chain.len() - 1;
+        let number = match tag {
+            "latest" | "pending" => head,
+            "earliest" => 0,
+            _ => match tag.strip_prefix("0x") {
+                Some(hex) => usize::from_str_radix(hex, 16).ok()?,
+                None => tag.parse::<usize>().ok()?,
+            },
+        };
+        if number > head {
+            return None;
+        }
+        Some(number)
+    }
+    /// None for a tx whose block got dropped in a reorg, same as for one that was never mined
+    pub fn get_receipt(&self, tx_hash: &str) -> Option<&Receipt> {
+        self.receipts
+            .get(tx_hash)
+            .filter(|receipt| self.is_canonical(&receipt.block_hash))
+    }
+    /// a mined tx and its receipt, found through the receipt's block number and tx index
+    pub fn get_transaction(&self, tx_hash: &str) -> Option<(&Transaction, &Receipt)> {
+        let receipt = self.get_receipt(tx_hash)?;
+        let tx = self
+            .chain
+            .get(receipt.block_number)?
+            .tx_series
+            .get(receipt.transaction_index)?;
+        Some((tx, receipt))
+    }
+    /// 1 for the head block, 2 for the one under it etc
+    pub fn confirmations(&self, block_number: usize) -> usize {
+        self.chain.len().saturating_sub(block_number)
+    }
+    pub fn get_total_tx_count(&self) -> usize {
+        self.chain.iter().map(|b| b.tx_series.len()).sum()
+    }
+    /// in ms, None until there's at least one block on top of genesis
+    pub fn get_average_block_time(&self) -> Option<i64> {
+        let first = self.chain.first()?;
+        let last = self.chain.last()?;
+        let gaps = self.chain.len() as i64 - 1;
+        if gaps == 0 {
+            return None;
+        }
+        Some(
+            (last.block_headers.truncated_block_headers.timestamp
+                - first.block_headers.truncated_block_headers.timestamp)
+                / gaps,
+        )
+    }
+    /// blocks whose address bloom says they might have tx involving the address - a superset of the blocks that do
+    pub fn blocks_touching(&self, address: Address) -> impl Iterator<Item = &Arc<Block>> {
+        self.chain.iter().filter(move |b| {
+            b.block_headers
+                .truncated_block_headers
+                .address_bloom
+                .might_contain(&address)
+        })
+    }
+    /// every mined tx that involves this address (see Transaction::involves), with the number of the block it's in. Oldest first
+    pub fn get_txs_for_address<'a>(&'a self, address: &Address) -> Vec<(usize, &'a Transaction)> {
+        self.blocks_touching(*address)
+            .flat_map(|b| {
+                let number = b.block_headers.truncated_block_headers.number;
+                b.tx_series.iter().map(move |tx| (number, tx))
+            })
+            .filter(|(_, tx)| tx.involves(address))
+            .collect()
+    }
+    pub fn get_block_by_number(&self, number: usize) -> Option<&Arc<Block>> {
+        self.chain
+            .iter()
+            .find(|b| b.block_headers.truncated_block_headers.number == number)
+    }
+    pub fn get_block_by_hash(&self, hash: &str) -> Option<&Arc<Block>> {
+        self.chain.get(*self.canonical.get(hash)?)
+    }
+    /// ids of every tx mined in the last TX_REPLAY_LOOKBACK blocks - none of them can go into the next block again
+    pub fn recent_tx_ids(&self) -> HashSet<Uuid> {
+        tx_ids_in_window(self.chain.iter().map(|block| &**block))
+    }
+    /// from the genesis block, every block and tx after it has to carry the same one
+    /// slots count from our genesis block - which a node that synced took from its bootnode, so nodes on the same
+    /// chain agree on the slot
+    pub fn consensus_clock(&self, slot_duration_ms: u64, slots_per_epoch: u64) -> ConsensusClock {
+        ConsensusClock::new(
+            self.chain[0]
+                .block_headers
+                .truncated_block_headers
+                .timestamp,
+            slot_duration_ms,
+            slots_per_epoch,
+            self.clock.clone(),
+        )
+    }
+    pub fn chain_id(&self) -> u64 {
+        self.chain[0].block_headers.truncated_block_headers.chain_id
+    }
+    fn check_chain_id(&self, chain: &[Block]) -> Result<(), ChainError> {
+        let their_chain_id = chain
+            .first()
+            .ok_or(ChainError::EmptyChain)?
+            .block_headers
+            .truncated_block_headers
+            .chain_id;
+        if their_chain_id != self.chain_id() {
+            return Err(ChainError::ChainIdMismatch {
+                ours: self.chain_id(),
+                theirs: their_chain_id,
+            });
+        }
+        Ok(())
+    }
+    pub fn replace_chain(&mut self, chain: Vec<Block>) -> Result<(), ChainError> {
+        self.check_chain_id(&chain)?;
+        //if the new chain just extends ours (eg we restarted from chaindata and a bootnode is ahead of us),
+        // our state is already where their block n is and only the blocks after it need running
+        let known = self
+            .chain
+            .iter()
+            .zip(chain.iter())
+            .take_while(|(ours, theirs)| ours.hash() == theirs.hash())
+            .count();
+        let first_to_run = if known == self.chain.len() { known } else { 1 };
+        //otherwise it's a fork, and the engine decides which side of it we're on
+        let engine = &self.rules.consensus;
+        if known < self.chain.len() && !engine.fork_choice(&self.chain, &chain) {
+            return Err(ChainError::ForkChoice(engine.name()));
+        }
+        //everything runs on copies, so a block failing half way down their chain leaves ours as it was. A fork
+        // starts over from genesis - our state still has everything our own blocks after the fork point did
+        let mut state = if first_to_run == 1 {
+            self.genesis_state()
         } else {
-            return false;
+            self.state.clone()
+        };
+        //whatever our own blocks from there on wrote is about to be written again, by theirs
+        let mut storage_history = self.storage_history.clone();
+        for history in storage_history.values_mut() {
+            history.retain(|(number, _)| *number < first_to_run);
+        }
+        let mut ran = Vec::new();
+        for i in first_to_run..chain.len() {
+            let (last_block, block) = (&chain[i - 1], &chain[i]);
+            let number = block.block_headers.truncated_block_headers.number;
+            Block::check_block_structure(last_block, block, &self.rules)?;
+            check_not_replayed(&tx_ids_in_window(chain[..i].iter()), block)?;
+            Block::check_txs(block, &state, &self.rules)?;
+            //if block is valid, run block
+            let receipts = Block::run_block(last_block, block, &mut state, &self.rules)?;
+            block.check_receipts(&receipts)?;
+            ran.push((gas_stats(block, &receipts, &state), receipts));
+            push_storage_history(&mut state, &mut storage_history, number);
+            tracing::debug!(number, "validated block");
+        }
+        //every block checked out - swap it all in
+        if self.genesis_state.is_none() {
+            self.genesis_state = Some(self.state.clone());
+        }
+        self.state = state;
+        self.storage_history = storage_history;
+        self.chain = chain.into_iter().map(Arc::new).collect();
+        self.index_canonical();
+        let height = self.chain.len() - 1;
+        //the new chain can be shorter than the one the stats were recorded for
+        self.gas_stats.retain(|number, _| *number < first_to_run);
+        for (stats, receipts) in ran {
+            self.gas_stats.insert(stats.block_number, stats);
+            self.store_receipts(receipts);
         }
+        self.prune_receipts(height);
+        if let Some(depth) = self.storage_history_depth {
+            self.prune_storage_history(height.saturating_sub(depth));
+        }
+        //rebuilt rather than appended to - the new chain can disagree with ours about what got mined
+        self.activity.clear();
+        let chain = self.chain.clone();
+        for block in chain.iter() {
+            self.index_activity(block);
+        }
+        tracing::info!(height = self.chain.len() - 1, "replaced local chain");
+        Ok(self.persist_chain()?)
     }
-    pub fn replace_chain(&mut self, chain: Vec<Block>) -> Result<(), String> {
-        for (i, block) in chain.iter().enumerate() {
-            if i != 0 {
-                let last_block = &chain[i - 1];
-                let is_valid = Block::validate_block(&last_block, block, &mut self.state);
-                if !is_valid {
-                    return Err("failed to replace chain due to validation error.".to_owned());
+    //the state block 1 runs on top of
+    fn genesis_state(&self) -> State {
+        match &self.genesis_state {
+            Some(state) => state.clone(),
+            None => self.state.clone(),
+        }
+    }
+    /// fast sync - takes the state from a snapshot of the chain's head instead of running every block to get there.
+    /// The blocks still get checked against each other, just not run, so there are no receipts (bar what the receipt
+    /// store already had), gas stats or storage history for anything before the snapshot.
+    /// (!) a header's state_root is the state before its block, and nothing validates even that - so the snapshot's
+    /// root is taken on trust from whoever sent it, same as with any fast sync that doesn't start from a checkpoint
+    pub fn import_snapshot(
+        &mut self,
+        chain: Vec<Block>,
+        snapshot: Snapshot,
+    ) -> Result<(), ChainError> {
+        self.check_chain_id(&chain)?;
+        let head = &chain[chain.len() - 1];
+        if head.block_headers.truncated_block_headers.number != snapshot.block_number
+            || head.hash() != snapshot.block_hash
+        {
+            return Err(ChainError::InvalidSnapshot(
+                "not a snapshot of the chain's head",
+            ));
+        }
+        for i in 1..chain.len() {
+            Block::check_block_structure(&chain[i - 1], &chain[i], &self.rules)?;
+            check_not_replayed(&tx_ids_in_window(chain[..i].iter()), &chain[i])?;
+        }
+        //nothing changes until the whole snapshot checks out
+        let mut state = snapshot.into_state()?;
+        state
+            .storage_trie_map
+            .set_store(self.state.storage_trie_map.store().cloned());
+        if self.genesis_state.is_none() {
+            self.genesis_state = Some(self.state.clone());
+        }
+        self.state = state;
+        self.chain = chain.into_iter().map(Arc::new).collect();
+        self.index_canonical();
+        let height = self.chain.len() - 1;
+        self.receipts.clear();
+        //whatever this node ran itself before (eg before a restart) is still on disk
+        if let Some(store) = &self.receipt_store {
+            let from = self
+                .receipt_history
+                .map_or(0, |depth| height.saturating_sub(depth));
+            for receipt in store.load(from)? {
+                if self.is_canonical(&receipt.block_hash) {
+                    self.receipts.insert(receipt.tx_hash.clone(), receipt);
                 }
-                //if block is valid, run block
-                Block::run_block(&block, &mut self.state);
             }
-            println!(
-                "Successfully validated block {}",
-                block.block_headers.truncated_block_headers.number
+        }
+        self.gas_stats.clear();
+        self.storage_history.clear();
+        self.storage_history_from = height;
+        self.record_storage_history(height);
+        self.activity.clear();
+        let chain = self.chain.clone();
+        for block in chain.iter() {
+            self.index_activity(block);
+        }
+        tracing::info!(height, "imported state snapshot");
+        Ok(self.persist_chain()?)
+    }
+}
+
+//after the block has run, so the state knows about contracts it created
+fn gas_stats(block: &Block, receipts: &[Receipt], state: &State) -> BlockGasStats {
+    BlockGasStats::new(block, receipts, |address| {
+        state
+            .find_account(*address)
+            .is_some_and(|account| account.code_hash.is_some())
+    })
+}
+
+//pushes the storage tries the block that just ran on state changed onto their address's history
+fn push_storage_history(
+    state: &mut State,
+    storage_history: &mut HashMap<Address, Vec<(usize, Trie)>>,
+    block_number: usize,
+) {
+    //a trie nothing wrote to can't have changed, and most of them aren't in memory to compare anyway
+    for address in state.storage_trie_map.take_touched() {
+        let trie = match state.storage_trie_map.get(&address) {
+            Some(trie) => trie,
+            None => continue,
+        };
+        let history = storage_history.entry(address).or_default();
+        let changed = history
+            .last()
+            .is_none_or(|(_, last)| last.root_hash != trie.root_hash);
+        if changed {
+            history.push((block_number, trie.into_owned()));
+        }
+    }
+}
+
+//tx ids in the last TX_REPLAY_LOOKBACK of the blocks, which come oldest first
+fn tx_ids_in_window<'a>(chain: impl DoubleEndedIterator<Item = &'a Block>) -> HashSet<Uuid> {
+    chain
+        .rev()
+        .take(TX_REPLAY_LOOKBACK)
+        .flat_map(|block| block.tx_series.iter().map(|tx| tx.unsigned_tx.id))
+        .collect()
+}
+
+//a tx id is a fresh uuid and it's signed over, so seeing one again means the same signed tx is being replayed
+fn check_not_replayed(recent: &HashSet<Uuid>, block: &Block) -> Result<(), ChainError> {
+    if block
+        .tx_series
+        .iter()
+        .any(|tx| recent.contains(&tx.unsigned_tx.id))
+    {
+        return Err(ChainError::InvalidBlock(
+            "contains a tx that was already mined",
+        ));
+    }
+    Ok(())
+}
+
+//a block the way BLOCKS_FILE stores it
+fn encode_record(block: &Block) -> Vec<u8> {
+    let encoded = codec::encode(block);
+    let mut record = (encoded.len() as u32).to_be_bytes().to_vec();
+    record.extend(encoded);
+    record
+}
+
+fn decode_records(mut bytes: &[u8]) -> Result<Vec<Block>, String> {
+    let mut blocks = vec![];
+    while !bytes.is_empty() {
+        if bytes.len() < 4 {
+            return Err("truncated record length".into());
+        }
+        let (len, rest) = bytes.split_at(4);
+        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        let record = rest.get(..len).ok_or("truncated record")?;
+        blocks.push(codec::decode(record).map_err(|e| e.to_string())?);
+        bytes = &rest[len..];
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{gen_address, Account};
+    use crate::blockchain::block::SECONDS;
+    use crate::blockchain::reward::RewardSchedule;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::interpreter::OPCODE;
+    use crate::transaction::activity::Direction;
+    use crate::transaction::tx::MINING_REWARD;
+    use crate::util::bigint::U256;
+    use crate::util::clock::ManualClock;
+    use crate::util::prep_state;
+
+    /// mines the 2 account creation tx that prep_state() queues up into block 1, 30s after genesis
+    fn chain_with_one_block() -> (Blockchain, Address) {
+        let mut global_state = prep_state();
+        let miner_addr = global_state.miner_address;
+        let mut blockchain = Blockchain::with_clock(
+            State::new(),
+            Arc::new(ManualClock::new(1_000 * SECONDS)),
+            DEFAULT_CHAIN_ID,
+        );
+        let tx_series = global_state.tx_queue.get_mut().unwrap().get_tx_series();
+        let state_root = blockchain.state.get_state_root().clone();
+        let block = Block::mine_block(
+            &blockchain.chain[0],
+            miner_addr,
+            tx_series,
+            &state_root,
+            &*blockchain.clock,
+            &blockchain.rules,
+        );
+        blockchain.add_block(block).unwrap();
+        (blockchain, miner_addr)
+    }
+
+    #[test]
+    fn test_chain_survives_restart() {
+        let dir = std::env::temp_dir().join(format!("chaindata-{}", uuid::Uuid::new_v4()));
+        let mut global_state = prep_state();
+        let miner_addr = global_state.miner_address;
+        let mut blockchain = Blockchain::new(State::new(), DEFAULT_CHAIN_ID);
+        blockchain.open(dir.clone()).unwrap();
+        let tx_series = global_state.tx_queue.get_mut().unwrap().get_tx_series();
+        let state_root = blockchain.state.get_state_root().clone();
+        let block = Block::mine_block(
+            &blockchain.chain[0],
+            miner_addr,
+            tx_series,
+            &state_root,
+            &*blockchain.clock,
+            &blockchain.rules,
+        );
+        blockchain.add_block(block).unwrap();
+
+        let mut restarted = Blockchain::new(State::new(), DEFAULT_CHAIN_ID);
+        restarted.open(dir.clone()).unwrap();
+        assert_eq!(restarted.chain.len(), 2);
+        assert_eq!(restarted.chain[1].hash(), blockchain.chain[1].hash());
+        assert_eq!(
+            restarted.state.get_state_root(),
+            blockchain.state.get_state_root()
+        );
+        assert_eq!(
+            restarted.get_activity(&miner_addr),
+            blockchain.get_activity(&miner_addr)
+        );
+        //then syncing from a bootnode only runs what's new
+        let chain = blockchain.chain.iter().map(|b| (**b).clone()).collect();
+        restarted.replace_chain(chain).unwrap();
+        assert_eq!(
+            restarted.state.get_state_root(),
+            blockchain.state.get_state_root()
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_chain_from_another_network() {
+        let (blockchain, _) = chain_with_one_block();
+        let mut chain: Vec<Block> = blockchain.chain.iter().map(|b| (**b).clone()).collect();
+        chain[0].block_headers.truncated_block_headers.chain_id += 1;
+
+        let mut other = Blockchain::new(State::new(), DEFAULT_CHAIN_ID);
+        assert!(matches!(
+            other.replace_chain(chain),
+            Err(ChainError::ChainIdMismatch { .. })
+        ));
+        assert_eq!(other.chain.len(), 1);
+    }
+
+    #[test]
+    fn test_blocks_are_checked_against_the_chains_rules() {
+        let mut blockchain = Blockchain::new(State::new(), DEFAULT_CHAIN_ID);
+        blockchain.rules.reward_schedule = RewardSchedule {
+            initial: MINING_REWARD * 2,
+            halving_interval: None,
+        };
+        let state_root = blockchain.state.get_state_root().clone();
+        let mine = |rules: &ChainRules| {
+            Block::mine_block(
+                &blockchain.chain[0],
+                gen_address(),
+                vec![],
+                &state_root,
+                &*blockchain.clock,
+                rules,
+            )
+        };
+        let elsewhere = mine(&ChainRules::default());
+        let ours = mine(&blockchain.rules);
+        assert_eq!(
+            blockchain.add_block(elsewhere),
+            Err(ChainError::InvalidBlock("contains an invalid tx"))
+        );
+        blockchain.add_block(ours).unwrap();
+    }
+
+    #[test]
+    fn test_lighter_fork_is_ignored() {
+        let (mut blockchain, _) = chain_with_one_block();
+        let state_root = blockchain.state.get_state_root().clone();
+        //our own genesis and nothing after it - a fork with less work behind it
+        let genesis = (*blockchain.chain[0]).clone();
+        assert_eq!(
+            blockchain.replace_chain(vec![genesis]),
+            Err(ChainError::ForkChoice("pow"))
+        );
+        assert_eq!(blockchain.chain.len(), 2);
+        assert_eq!(blockchain.state.get_state_root(), &state_root);
+    }
+
+    #[test]
+    fn test_rejected_fork_leaves_chain_alone() {
+        let (mut blockchain, _) = chain_with_one_block();
+        let state_root = blockchain.state.get_state_root().clone();
+        //a heavier fork, only its last block's tx root doesn't match its tx - the blocks before it run fine
+        let mut fork = vec![(*blockchain.chain[0]).clone()];
+        for _ in 0..3 {
+            let block = Block::mine_block(
+                fork.last().unwrap(),
+                gen_address(),
+                vec![],
+                "",
+                &*blockchain.clock,
+                &blockchain.rules,
             );
+            fork.push(block);
         }
-        self.chain = chain;
-        println!("Successfully replaced local chain.");
-        Ok(())
+        let mut tampered = fork.pop().unwrap();
+        tampered.block_headers.truncated_block_headers.tx_root = "nope".into();
+        fork.push(tampered.seal(&fork[2], &blockchain.rules));
+        assert_eq!(
+            blockchain.replace_chain(fork),
+            Err(ChainError::InvalidBlock(
+                "transaction root hash doesn't match"
+            ))
+        );
+        assert_eq!(blockchain.chain.len(), 2);
+        assert_eq!(blockchain.state.get_state_root(), &state_root);
+        assert!(blockchain.get_gas_stats(2).is_none());
+    }
+
+    #[test]
+    fn test_fork_is_replayed_from_genesis() {
+        let (mut blockchain, miner_addr) = chain_with_one_block();
+        //a heavier fork off genesis, without our block 1 - or the reward it paid our miner
+        let rival = gen_address();
+        let mut fork = vec![(*blockchain.chain[0]).clone()];
+        for _ in 0..2 {
+            let block = Block::mine_block(
+                fork.last().unwrap(),
+                rival,
+                vec![],
+                "",
+                &*blockchain.clock,
+                &blockchain.rules,
+            );
+            fork.push(block);
+        }
+        //a node that only ever saw the fork
+        let mut other = Blockchain::with_clock(
+            State::new(),
+            Arc::new(ManualClock::new(1_000 * SECONDS)),
+            DEFAULT_CHAIN_ID,
+        );
+        other.replace_chain(fork.clone()).unwrap();
+
+        blockchain.replace_chain(fork).unwrap();
+        assert_eq!(blockchain.chain.len(), 3);
+        assert_eq!(
+            blockchain.state.get_state_root(),
+            other.state.get_state_root()
+        );
+        assert!(blockchain.state.find_account(miner_addr).is_none());
+        assert_eq!(
+            blockchain.state.get_account_or_empty(rival).balance,
+            other.state.get_account_or_empty(rival).balance
+        );
+    }
+
+    #[test]
+    fn test_rejected_block_leaves_chain_alone() {
+        let (mut blockchain, _) = chain_with_one_block();
+        let state_root = blockchain.state.get_state_root().clone();
+        //eg our own block coming back from the exchange
+        let block = (*blockchain.chain[1]).clone();
+        assert_eq!(
+            blockchain.add_block(block),
+            Err(ChainError::InvalidBlock(
+                "parent block header hash doesn't match"
+            ))
+        );
+        assert_eq!(blockchain.chain.len(), 2);
+        assert_eq!(blockchain.state.get_state_root(), &state_root);
+    }
+
+    #[test]
+    fn test_receipts_root() {
+        let (mut blockchain, _) = chain_with_one_block();
+        let (last_block, block) = blockchain.candidate(&Account::new(vec![]), vec![]).unwrap();
+        assert!(block
+            .block_headers
+            .truncated_block_headers
+            .receipts_root
+            .is_some());
+
+        let mut tampered = block.clone();
+        tampered.block_headers.truncated_block_headers.receipts_root = Some("nope".into());
+        assert_eq!(
+            blockchain.add_block(tampered.seal(&last_block, &blockchain.rules)),
+            Err(ChainError::InvalidBlock("receipts root doesn't match"))
+        );
+        assert_eq!(blockchain.chain.len(), 2);
+        blockchain
+            .add_block(block.seal(&last_block, &blockchain.rules))
+            .unwrap();
+        assert_eq!(blockchain.chain.len(), 3);
+    }
+
+    #[test]
+    fn test_mined_tx_cant_be_replayed() {
+        let sender = Account::new(vec![]);
+        let mut state = State::new();
+        state.allocate(sender.public_account.address, 1000);
+        let mut blockchain = Blockchain::with_clock(
+            state.clone(),
+            Arc::new(ManualClock::new(0)),
+            DEFAULT_CHAIN_ID,
+        );
+        let transfer = Transaction::create_transaction(
+            Some(sender),
+            Some(gen_address()),
+            10,
+            None,
+            10,
+            DEFAULT_CHAIN_ID,
+        );
+        let mine = |blockchain: &Blockchain, tx_series: Vec<Transaction>| {
+            Block::mine_block(
+                blockchain.chain.last().unwrap(),
+                gen_address(),
+                tx_series,
+                &blockchain.state.get_state_root().clone(),
+                &*blockchain.clock,
+                &blockchain.rules,
+            )
+        };
+
+        let twice = mine(&blockchain, vec![transfer.clone(), transfer.clone()]);
+        assert_eq!(
+            blockchain.add_block(twice),
+            Err(ChainError::InvalidBlock("contains the same tx twice"))
+        );
+        let block = mine(&blockchain, vec![transfer.clone()]);
+        blockchain.add_block(block).unwrap();
+        assert!(blockchain
+            .recent_tx_ids()
+            .contains(&transfer.unsigned_tx.id));
+
+        //its nonce is used up as well, but it's turned away as a replay first
+        let replay = mine(&blockchain, vec![transfer]);
+        assert_eq!(
+            blockchain.add_block(replay.clone()),
+            Err(ChainError::InvalidBlock(
+                "contains a tx that was already mined"
+            ))
+        );
+        assert_eq!(blockchain.chain.len(), 2);
+
+        //same goes for a chain from a peer
+        let mut chain: Vec<Block> = blockchain.chain.iter().map(|b| (**b).clone()).collect();
+        chain.push(replay);
+        let mut other =
+            Blockchain::with_clock(state, Arc::new(ManualClock::new(0)), DEFAULT_CHAIN_ID);
+        assert_eq!(
+            other.replace_chain(chain),
+            Err(ChainError::InvalidBlock(
+                "contains a tx that was already mined"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_profile_tx() {
+        //stores 5 in slot 1, reads it back and adds 2
+        let contract = Account::new(vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(5),
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::STORE,
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::LOAD,
+            OPCODE::PUSH,
+            OPCODE::VAL(2),
+            OPCODE::ADD,
+            OPCODE::STOP,
+        ]);
+        let (alice, bob) = (Account::new(vec![]), Account::new(vec![]));
+        let mut state = State::new();
+        state.put_account(
+            contract.public_account.address,
+            contract.public_account.clone(),
+        );
+        state.allocate(alice.public_account.address, 1000);
+        state.allocate(bob.public_account.address, 1000);
+        let mut blockchain =
+            Blockchain::with_clock(state, Arc::new(ManualClock::new(0)), DEFAULT_CHAIN_ID);
+        let call = |sender: &Account| {
+            Transaction::create_transaction(
+                Some(sender.clone()),
+                Some(contract.public_account.address),
+                0,
+                None,
+                100,
+                DEFAULT_CHAIN_ID,
+            )
+        };
+        let (first, second) = (call(&alice), call(&bob));
+        let block = Block::mine_block(
+            blockchain.chain.last().unwrap(),
+            gen_address(),
+            vec![first, second.clone()],
+            &blockchain.state.get_state_root().clone(),
+            &*blockchain.clock,
+            &blockchain.rules,
+        );
+        blockchain.add_block(block.clone()).unwrap();
+
+        let profile = blockchain.profile_tx(&second.hash()).unwrap().unwrap();
+        assert_eq!(profile.contract, Some(contract.public_account.address));
+        assert_eq!(profile.execution_gas, 11);
+        assert_eq!(
+            profile.payload_gas + profile.execution_gas,
+            profile.gas_used
+        );
+        assert_eq!(profile.by_category.storage, 10);
+        assert_eq!(profile.by_category.arithmetic, 1);
+        assert_eq!(profile.opcodes[0].opcode, "LOAD");
+        let push = profile.opcodes.iter().find(|o| o.opcode == "PUSH").unwrap();
+        assert_eq!(push.count, 4);
+        assert_eq!(push.gas, 0);
+
+        //the reward runs no code
+        let reward = blockchain
+            .profile_tx(&block.tx_series.last().unwrap().hash())
+            .unwrap()
+            .unwrap();
+        assert_eq!(reward.contract, None);
+        assert!(reward.opcodes.is_empty());
+        assert_eq!(blockchain.profile_tx("nope").unwrap(), None);
+    }
+
+    #[test]
+    fn test_chain_snapshot_shares_blocks() {
+        let blockchain = Blockchain::new(State::new(), DEFAULT_CHAIN_ID);
+        let snapshot = blockchain.chain.clone();
+        assert!(Arc::ptr_eq(&snapshot[0], &blockchain.chain[0]));
+    }
+
+    #[test]
+    fn test_explorer_helpers_on_genesis_only() {
+        let blockchain = Blockchain::new(State::new(), DEFAULT_CHAIN_ID);
+        assert_eq!(blockchain.get_total_tx_count(), 0);
+        assert_eq!(blockchain.get_average_block_time(), None);
+    }
+
+    #[test]
+    fn test_explorer_helpers() {
+        let (blockchain, miner_addr) = chain_with_one_block();
+        //the miner's account creation + mining reward
+        assert_eq!(blockchain.get_total_tx_count(), 2);
+        assert_eq!(blockchain.get_average_block_time(), Some(30 * SECONDS));
+
+        //none of them run any code
+        assert_eq!(blockchain.get_gas_stats(0), None);
+        let gas_stats = blockchain.get_gas_stats(1).unwrap();
+        assert_eq!(gas_stats.tx_count, 2);
+        assert_eq!(gas_stats.gas_used, 0);
+        assert_eq!(gas_stats.gas_limit, U256::from(100 + 10));
+        assert_eq!(gas_stats.average_gas_price, None);
+        assert_eq!(blockchain.get_total_gas_used(), 0);
+
+        //the miner's account creation + its reward
+        let txs = blockchain.get_txs_for_address(&miner_addr);
+        assert_eq!(txs.len(), 2);
+        assert!(txs.iter().all(|(number, _)| *number == 1));
+        //genesis has no tx, so its bloom rules it out without looking
+        assert_eq!(blockchain.blocks_touching(miner_addr).count(), 1);
+        assert_eq!(blockchain.blocks_touching(gen_address()).count(), 0);
+
+        //every tx can be found again from its hash
+        for (index, tx) in blockchain.chain[1].tx_series.iter().enumerate() {
+            let (found, receipt) = blockchain.get_transaction(&tx.hash()).unwrap();
+            assert_eq!(found.hash(), tx.hash());
+            assert_eq!(receipt.transaction_index, index);
+        }
+        assert!(blockchain.get_transaction("nope").is_none());
+        assert_eq!(blockchain.confirmations(1), 1);
+        assert_eq!(blockchain.confirmations(0), 2);
+    }
+
+    #[test]
+    fn test_activity_index() {
+        let (blockchain, miner_addr) = chain_with_one_block();
+        let activity = blockchain.get_activity(&miner_addr);
+        //same tx get_txs_for_address() finds by scanning
+        assert_eq!(
+            activity.len(),
+            blockchain.get_txs_for_address(&miner_addr).len()
+        );
+        assert_eq!(activity[0].direction, Direction::Created);
+        assert_eq!(activity[1].direction, Direction::In);
+        assert!(activity.iter().all(|a| a.block_number == 1));
+        assert!(blockchain.get_activity(&gen_address()).is_empty());
+
+        //a node that syncs the chain ends up with the same index
+        let mut synced = Blockchain::new(State::new(), DEFAULT_CHAIN_ID);
+        let chain = blockchain.chain.iter().map(|b| (**b).clone()).collect();
+        synced.replace_chain(chain).unwrap();
+        assert_eq!(synced.get_activity(&miner_addr), activity);
+    }
+
+    #[test]
+    fn test_resolve_block_tag() {
+        let (blockchain, _) = chain_with_one_block();
+        assert_eq!(blockchain.resolve_block_tag("latest"), Some(1));
+        assert_eq!(blockchain.resolve_block_tag("earliest"), Some(0));
+        assert_eq!(blockchain.resolve_block_tag("0x1"), Some(1));
+        assert_eq!(blockchain.resolve_block_tag("0"), Some(0));
+        assert_eq!(blockchain.resolve_block_tag("2"), None);
+        assert_eq!(blockchain.resolve_block_tag("abc"), None);
+    }
+
+    #[test]
+    fn test_get_storage_at_past_blocks() {
+        let mut blockchain = Blockchain::new(State::new(), DEFAULT_CHAIN_ID);
+        let address = gen_address();
+
+        //fake 2 blocks' worth of storage writes
+        blockchain
+            .state
+            .storage_trie_map
+            .insert(address, Trie::new());
+        let trie = blockchain.state.storage_trie_map.get_or_create(address);
+        trie.put("1".into(), "10".into());
+        blockchain.record_storage_history(1);
+        let trie = blockchain.state.storage_trie_map.get_or_create(address);
+        trie.put("1".into(), "20".into());
+        blockchain.record_storage_history(3);
+
+        assert_eq!(blockchain.get_storage_at(&address, "1", 0), Ok(None));
+        assert_eq!(
+            blockchain.get_storage_at(&address, "1", 1),
+            Ok(Some("10".into()))
+        );
+        assert_eq!(
+            blockchain.get_storage_at(&address, "1", 2),
+            Ok(Some("10".into()))
+        );
+        assert_eq!(
+            blockchain.get_storage_at(&address, "1", 3),
+            Ok(Some("20".into()))
+        );
+        assert_eq!(blockchain.get_storage_at(&address, "2", 3), Ok(None));
+    }
+
+    #[test]
+    fn test_pruned_storage_history() {
+        let mut blockchain = Blockchain::new(State::new(), DEFAULT_CHAIN_ID);
+        blockchain.storage_history_depth = Some(2);
+        let address = gen_address();
+        let write = |blockchain: &mut Blockchain, number: usize, value: &str| {
+            let trie = blockchain.state.storage_trie_map.get_or_create(address);
+            trie.put("1".into(), value.into());
+            blockchain.record_storage_history(number);
+        };
+
+        write(&mut blockchain, 1, "10");
+        write(&mut blockchain, 2, "20");
+        assert_eq!(blockchain.storage_history_from, 0);
+        write(&mut blockchain, 5, "50");
+        //blocks 3-5 are kept, and block 3 still needs the trie written in block 2
+        assert_eq!(blockchain.storage_history_from, 3);
+        assert_eq!(blockchain.storage_history[&address].len(), 2);
+        assert_eq!(
+            blockchain.get_storage_at(&address, "1", 3),
+            Ok(Some("20".into()))
+        );
+        assert_eq!(
+            blockchain.get_storage_at(&address, "1", 5),
+            Ok(Some("50".into()))
+        );
+        assert_eq!(
+            blockchain.get_storage_at(&address, "1", 2),
+            Err(StoreError::Pruned {
+                block: 2,
+                oldest: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_orphaned_receipts_are_hidden() {
+        let (mut blockchain, _) = chain_with_one_block();
+        let block = blockchain.chain[1].clone();
+        let tx_hash = block.tx_series[0].hash();
+        assert!(blockchain.is_canonical(&block.hash()));
+        assert!(blockchain.get_transaction(&tx_hash).is_some());
+        assert_eq!(
+            blockchain.get_block_by_hash(&block.hash()).unwrap().hash(),
+            block.hash()
+        );
+
+        //as if the tx's block had been dropped in a reorg, the receipt is still around
+        let mut receipt = blockchain.get_receipt(&tx_hash).unwrap().clone();
+        receipt.block_hash = "orphaned".into();
+        blockchain.store_receipts(vec![receipt]);
+        assert!(!blockchain.is_canonical("orphaned"));
+        assert!(blockchain.receipts.contains_key(&tx_hash));
+        assert!(blockchain.get_receipt(&tx_hash).is_none());
+        assert!(blockchain.get_transaction(&tx_hash).is_none());
+    }
+
+    #[test]
+    fn test_pruned_receipts() {
+        let dir = std::env::temp_dir().join(format!("receipts-{}", uuid::Uuid::new_v4()));
+        let (mut blockchain, _) = chain_with_one_block();
+        let receipt = blockchain.receipts.values().next().unwrap().clone();
+        blockchain.receipt_store = Some(ReceiptStore::open(&dir).unwrap());
+        blockchain.receipt_history = Some(2);
+        for number in 1..=4 {
+            let mut receipt = receipt.clone();
+            receipt.tx_hash = format!("tx{}", number);
+            receipt.block_number = number;
+            receipt.block_hash = format!("block{}", number);
+            blockchain.store_receipts(vec![receipt]);
+            blockchain.prune_receipts(number);
+        }
+        //blocks 2-4 are kept, in memory and on disk
+        assert!(!blockchain.receipts.contains_key("tx1"));
+        assert!(blockchain.receipts.contains_key("tx2"));
+        let store = blockchain.receipt_store.as_ref().unwrap();
+        assert!(store.get(1, "block1").unwrap().is_empty());
+        assert_eq!(store.get(4, "block4").unwrap()[0].tx_hash, "tx4");
+        assert_eq!(store.load(0).unwrap().len(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_prepared_block_is_invisible_until_committed() {
+        let (mut blockchain, miner_addr) = chain_with_one_block();
+        let state_root = blockchain.state.get_state_root().clone();
+        let mine = |blockchain: &Blockchain, beneficiary| {
+            Block::mine_block(
+                &blockchain.chain[1],
+                beneficiary,
+                vec![],
+                &state_root,
+                &*blockchain.clock,
+                &blockchain.rules,
+            )
+        };
+
+        let pending = blockchain
+            .prepare_block(mine(&blockchain, miner_addr))
+            .unwrap();
+        assert_eq!(blockchain.state.get_state_root(), &state_root);
+        assert_eq!(blockchain.chain.len(), 2);
+
+        //a rival block on the same parent gets in first
+        let rival = mine(&blockchain, gen_address());
+        blockchain.add_block(rival).unwrap();
+        assert_eq!(
+            blockchain.commit_block(pending),
+            Err(ChainError::InvalidBlock(
+                "parent block header hash doesn't match"
+            ))
+        );
+        assert_eq!(blockchain.chain.len(), 3);
+    }
+
+    #[test]
+    fn test_import_snapshot() {
+        let (source, _) = chain_with_one_block();
+        let head = source.chain.last().unwrap();
+        let snapshot = Snapshot::take(head, &source.state);
+        let chain = source
+            .chain
+            .iter()
+            .map(|block| (**block).clone())
+            .collect::<Vec<Block>>();
+
+        let mut fresh = Blockchain::new(State::new(), DEFAULT_CHAIN_ID);
+        fresh
+            .import_snapshot(chain.clone(), snapshot.clone())
+            .unwrap();
+        assert_eq!(fresh.chain.last().unwrap().hash(), head.hash());
+        assert_eq!(fresh.state.get_state_root(), source.state.get_state_root());
+        //the blocks before it were never run
+        assert!(fresh.receipts.is_empty());
+        assert_eq!(fresh.storage_history_from, 1);
+
+        //has to be the state of the chain's head
+        let mut fresh = Blockchain::new(State::new(), DEFAULT_CHAIN_ID);
+        assert_eq!(
+            fresh.import_snapshot(chain[..1].to_vec(), snapshot),
+            Err(ChainError::InvalidSnapshot(
+                "not a snapshot of the chain's head"
+            ))
+        );
+        assert_eq!(fresh.chain.len(), 1);
     }
 }
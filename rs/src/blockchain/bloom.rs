@@ -0,0 +1,173 @@
+use crate::account::address::Address;
+use crate::error::CodecError;
+use crate::store::codec::{self, Decode, Encode};
+use crate::store::rlp::Rlp;
+use crate::transaction::tx::Transaction;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha3::{Digest, Keccak256};
+
+/// same size as ethereum's logsBloom - 2048 bits, ie 256 bytes
+pub const BLOOM_BYTES: usize = 256;
+//bits set per address
+const BLOOM_HASHES: usize = 3;
+
+/// every address a block's tx touch (sender, recipient, created account), squashed into a fixed size bloom filter in
+/// the block header. Lets the account history scans skip blocks without looking at their tx - a false positive only
+/// costs a look at a block that turns out not to have the address, but a "no" is always right.
+/// Goes over the wire as hex
+#[derive(Clone, PartialEq, Eq)]
+pub struct AddressBloom(Vec<u8>);
+
+impl Default for AddressBloom {
+    fn default() -> Self {
+        AddressBloom(vec![0; BLOOM_BYTES])
+    }
+}
+
+impl AddressBloom {
+    pub fn from_txs(txs: &[Transaction]) -> Self {
+        let mut bloom = Self::default();
+        for tx in txs {
+            let tx = &tx.unsigned_tx;
+            let created = tx
+                .data
+                .account_data
+                .as_ref()
+                .map(|account| &account.address);
+            for address in tx.from.iter().chain(tx.to.iter()).chain(created) {
+                bloom.insert(address);
+            }
+        }
+        bloom
+    }
+
+    pub fn insert(&mut self, address: &Address) {
+        for bit in Self::bits(address) {
+            self.0[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    /// false = the address definitely isn't in the block
+    pub fn might_contain(&self, address: &Address) -> bool {
+        Self::bits(address)
+            .iter()
+            .all(|bit| self.0[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    //like ethereum: the first 3 pairs of bytes of the address's keccak hash, each taken mod 2048
+    fn bits(address: &Address) -> [usize; BLOOM_HASHES] {
+        let hash = Keccak256::digest(address.as_bytes());
+        let mut bits = [0; BLOOM_HASHES];
+        for (i, bit) in bits.iter_mut().enumerate() {
+            *bit = (((hash[2 * i] as usize) << 8) | hash[2 * i + 1] as usize) % (BLOOM_BYTES * 8);
+        }
+        bits
+    }
+}
+
+//a few hundred zeroes aren't worth printing
+impl std::fmt::Debug for AddressBloom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let set: u32 = self.0.iter().map(|byte| byte.count_ones()).sum();
+        write!(f, "AddressBloom({} bits set)", set)
+    }
+}
+
+impl Serialize for AddressBloom {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for AddressBloom {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes =
+            hex::decode(String::deserialize(deserializer)?).map_err(serde::de::Error::custom)?;
+        if bytes.len() != BLOOM_BYTES {
+            return Err(serde::de::Error::custom(format!(
+                "address bloom has to be {} bytes, got {}",
+                BLOOM_BYTES,
+                bytes.len()
+            )));
+        }
+        Ok(AddressBloom(bytes))
+    }
+}
+
+impl Encode for AddressBloom {
+    fn to_rlp(&self) -> Rlp {
+        Rlp::Bytes(self.0.clone())
+    }
+}
+
+impl Decode for AddressBloom {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        let bytes = codec::bytes(item)?;
+        if bytes.len() != BLOOM_BYTES {
+            return Err(CodecError::Malformed(format!(
+                "address bloom has to be {} bytes, got {}",
+                BLOOM_BYTES,
+                bytes.len()
+            )));
+        }
+        Ok(AddressBloom(bytes.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{gen_address, Account};
+    use crate::config::DEFAULT_CHAIN_ID;
+
+    #[test]
+    fn test_bloom_covers_every_touched_address() {
+        let sender = Account::new(vec![]);
+        let receiver = gen_address();
+        let created = Account::new(vec![]);
+        let beneficiary = gen_address();
+        let txs = vec![
+            Transaction::create_transaction(
+                Some(sender.clone()),
+                Some(receiver),
+                10,
+                None,
+                10,
+                DEFAULT_CHAIN_ID,
+            ),
+            Transaction::create_transaction(
+                Some(created.clone()),
+                None,
+                0,
+                None,
+                10,
+                DEFAULT_CHAIN_ID,
+            ),
+            Transaction::create_transaction(None, None, 0, Some(beneficiary), 10, DEFAULT_CHAIN_ID),
+        ];
+        let bloom = AddressBloom::from_txs(&txs);
+        for address in [
+            sender.public_account.address,
+            receiver,
+            created.public_account.address,
+            beneficiary,
+        ]
+        .iter()
+        {
+            assert!(bloom.might_contain(address));
+        }
+        //at most 12 of 2048 bits are set, so a random address gets a false positive about once in 5 million
+        assert!(!bloom.might_contain(&gen_address()));
+        assert!(!AddressBloom::default().might_contain(&receiver));
+    }
+
+    #[test]
+    fn test_bloom_serializes_as_hex() {
+        let mut bloom = AddressBloom::default();
+        bloom.insert(&gen_address());
+        let json = serde_json::to_string(&bloom).unwrap();
+        assert_eq!(json.len(), 2 * BLOOM_BYTES + 2);
+        assert_eq!(serde_json::from_str::<AddressBloom>(&json).unwrap(), bloom);
+        assert!(serde_json::from_str::<AddressBloom>("\"00ff\"").is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use crate::blockchain::block::Block;
+use crate::error::ChainError;
+use crate::util::keccak_hash;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// the part of the protocol that decides who gets to make the next block and which chain wins. Everything else
+/// about a block - its tx, roots, size limits, forks - is the same whatever the engine, and stays in Block.
+/// Part of the chain's rules, see ChainRules
+pub trait ConsensusEngine: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// the difficulty header of a block on top of parent. Engines with no use for it can keep it at 1
+    fn difficulty(&self, parent: &Block, timestamp: i64) -> i64;
+
+    /// makes an assembled block valid. Block::candidate() is what a block looks like before this
+    fn seal_block(&self, parent: &Block, block: Block) -> Block;
+
+    /// the engine's side of check_block_structure() - the headers seal_block() is responsible for
+    fn verify_seal(&self, parent: &Block, block: &Block) -> Result<(), ChainError>;
+
+    /// whether theirs should replace ours, when it doesn't just extend it. Both start at their genesis and have
+    /// already been checked against their chain id
+    fn fork_choice(&self, ours: &[Arc<Block>], theirs: &[Block]) -> bool;
+}
+
+/// the default engine - a nonce that gets the header hash under a target set by the difficulty, with the
+/// difficulty nudged every block towards MINE_RATE
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProofOfWork;
+
+impl ProofOfWork {
+    /// the work behind a chain. Only ever compared against another chain's
+    pub fn total_difficulty<'a>(chain: impl Iterator<Item = &'a Block>) -> u128 {
+        chain
+            .map(|block| {
+                block
+                    .block_headers
+                    .truncated_block_headers
+                    .difficulty
+                    .max(0) as u128
+            })
+            .sum()
+    }
+
+    /// what has to come in under the target. Only the nonce changes between attempts, so the rest is hashed once and
+    /// passed in - the same truncated header hash GET /miner/work hands out
+    pub fn seal_hash(truncated_header_hash: &str, nonce: u128) -> String {
+        keccak_hash(&format!("{}{}", truncated_header_hash, nonce))
+    }
+}
+
+impl ConsensusEngine for ProofOfWork {
+    fn name(&self) -> &'static str {
+        "pow"
+    }
+
+    fn difficulty(&self, parent: &Block, timestamp: i64) -> i64 {
+        Block::adjust_difficulty(parent, timestamp)
+    }
+
+    fn seal_block(&self, parent: &Block, mut block: Block) -> Block {
+        let target = Block::calc_block_target_hash(parent);
+        let truncated_header_hash = block.block_headers.truncated_block_headers.hash();
+        let mut nonce;
+        loop {
+            nonce = rand::random::<u128>();
+            if ProofOfWork::seal_hash(&truncated_header_hash, nonce) < target {
+                break;
+            }
+        }
+        block.block_headers.nonce = nonce;
+        block
+    }
+
+    fn verify_seal(&self, parent: &Block, block: &Block) -> Result<(), ChainError> {
+        if (block.block_headers.truncated_block_headers.difficulty
+            - parent.block_headers.truncated_block_headers.difficulty)
+            .abs()
+            > 1
+        {
+            return Err(ChainError::InvalidBlock(
+                "difficulty difference between two blocks above 1",
+            ));
+        }
+
+        let target = Block::calc_block_target_hash(parent);
+        let rehashed_tbh = block.block_headers.truncated_block_headers.hash();
+        if ProofOfWork::seal_hash(&rehashed_tbh, block.block_headers.nonce) >= target {
+            return Err(ChainError::InvalidBlock("nonce check failed"));
+        }
+        Ok(())
+    }
+
+    /// the heaviest chain, not the longest. A tie goes to theirs - that's how a fresh node takes a bootnode's genesis
+    fn fork_choice(&self, ours: &[Arc<Block>], theirs: &[Block]) -> bool {
+        ProofOfWork::total_difficulty(theirs.iter())
+            >= ProofOfWork::total_difficulty(ours.iter().map(|block| &**block))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::gen_address;
+    use crate::config::chain::ChainRules;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::util::clock::SystemClock;
+
+    fn mine_on(parent: &Block) -> Block {
+        Block::mine_block(
+            parent,
+            gen_address(),
+            vec![],
+            "",
+            &SystemClock,
+            &ChainRules::default(),
+        )
+    }
+
+    #[test]
+    fn test_seal_verifies() {
+        let mut genesis = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
+        //at difficulty 1 any nonce does
+        genesis.block_headers.truncated_block_headers.difficulty = 16;
+        let block = mine_on(&genesis);
+        assert_eq!(ProofOfWork.verify_seal(&genesis, &block), Ok(()));
+
+        let mut tampered = block.clone();
+        tampered.block_headers.truncated_block_headers.timestamp += 1;
+        //a 1 in 16 chance the old nonce still works, so look for one that doesn't
+        while ProofOfWork.verify_seal(&genesis, &tampered).is_ok() {
+            tampered.block_headers.nonce += 1;
+        }
+        assert_eq!(
+            ProofOfWork.verify_seal(&genesis, &tampered),
+            Err(ChainError::InvalidBlock("nonce check failed"))
+        );
+
+        let mut jumped = block;
+        jumped.block_headers.truncated_block_headers.difficulty = 18;
+        assert_eq!(
+            ProofOfWork.verify_seal(&genesis, &jumped),
+            Err(ChainError::InvalidBlock(
+                "difficulty difference between two blocks above 1"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_fork_choice_goes_by_work() {
+        let genesis = Block::genesis(&SystemClock, DEFAULT_CHAIN_ID);
+        let ours = vec![Arc::new(genesis.clone())];
+        let mut theirs = vec![genesis.clone()];
+        //same genesis, so a tie
+        assert!(ProofOfWork.fork_choice(&ours, &theirs));
+
+        theirs.push(mine_on(&genesis));
+        assert!(ProofOfWork.fork_choice(&ours, &theirs));
+        let ours: Vec<Arc<Block>> = theirs.iter().cloned().map(Arc::new).collect();
+        assert!(!ProofOfWork.fork_choice(&ours, std::slice::from_ref(&genesis)));
+
+        //one heavy block beats two light ones
+        let mut heavy = genesis.clone();
+        heavy.block_headers.truncated_block_headers.difficulty = 10;
+        assert!(ProofOfWork.fork_choice(&ours, &[heavy]));
+    }
+}
@@ -0,0 +1,104 @@
+use crate::interpreter::{GasSchedule, OPCODE};
+
+/// a set of protocol rules. Forks are in the order they activate in and each one builds on the ones before it, so
+/// `fork >= Fork::Constantinople` reads as "constantinople's rules apply"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Fork {
+    /// the rules the chain launched with
+    Frontier,
+    /// adds SHL and SHR, and a STORE that leaves the slot as it was gets cheaper
+    Constantinople,
+    /// blocks carry a randao reveal and mix (see block::Randao), readable from contracts with PREVRANDAO.
+    /// Named after the ethereum fork that brought in PREVRANDAO - blocks here are still proof of work
+    Paris,
+}
+
+impl Fork {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fork::Frontier => "frontier",
+            Fork::Constantinople => "constantinople",
+            Fork::Paris => "paris",
+        }
+    }
+
+    /// whether code running under these rules may use the opcode
+    pub fn has_opcode(&self, opcode: &OPCODE) -> bool {
+        match opcode {
+            OPCODE::SHL | OPCODE::SHR => *self >= Fork::Constantinople,
+            OPCODE::PREVRANDAO => *self >= Fork::Paris,
+            //incl MSTORE/MLOAD/MSIZE, REVERT, CALLER and CALLDATALOAD - they came without a fork, no code deployed before them
+            // could hold them
+            _ => true,
+        }
+    }
+
+    pub fn gas_schedule(&self) -> GasSchedule {
+        let frontier = GasSchedule {
+            arithmetic: 1,
+            jump: 2,
+            store: 5,
+            unchanged_store: 5,
+            load: 5,
+            memory: 3,
+        };
+        match self {
+            Fork::Frontier => frontier,
+            //writing what's already there doesn't change the storage root, so it shouldn't cost like it does
+            Fork::Constantinople | Fork::Paris => GasSchedule {
+                unchanged_store: 1,
+                ..frontier
+            },
+        }
+    }
+}
+
+/// the block each fork activates at. Like the reward schedule it's a consensus rule - a node with a different one
+/// rejects blocks the rest of the chain accepts, so every node has to run with the same schedule
+/// (see ChainRules)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ForkSchedule {
+    /// None = never, the chain stays on frontier rules
+    pub constantinople: Option<u64>,
+    /// can't come before constantinople, see NodeConfig::validate()
+    pub paris: Option<u64>,
+}
+
+impl ForkSchedule {
+    /// the rules block_number gets validated and run under
+    pub fn fork_at(&self, block_number: usize) -> Fork {
+        let active = |fork: Option<u64>| fork.is_some_and(|block| block_number as u64 >= block);
+        if active(self.paris) {
+            Fork::Paris
+        } else if active(self.constantinople) {
+            Fork::Constantinople
+        } else {
+            Fork::Frontier
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_activation() {
+        let schedule = ForkSchedule {
+            constantinople: Some(100),
+            paris: Some(200),
+        };
+        assert_eq!(schedule.fork_at(0), Fork::Frontier);
+        assert_eq!(schedule.fork_at(99), Fork::Frontier);
+        assert_eq!(schedule.fork_at(100), Fork::Constantinople);
+        assert_eq!(schedule.fork_at(200), Fork::Paris);
+        assert_eq!(schedule.fork_at(1_000_000), Fork::Paris);
+        assert_eq!(ForkSchedule::default().fork_at(1_000_000), Fork::Frontier);
+
+        assert!(!Fork::Frontier.has_opcode(&OPCODE::SHL));
+        assert!(Fork::Constantinople.has_opcode(&OPCODE::SHL));
+        assert!(Fork::Frontier.has_opcode(&OPCODE::ADD));
+        assert!(!Fork::Constantinople.has_opcode(&OPCODE::PREVRANDAO));
+        assert!(Fork::Paris.has_opcode(&OPCODE::SHL));
+    }
+}
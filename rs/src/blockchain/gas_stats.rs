@@ -0,0 +1,204 @@
+use crate::account::address::Address;
+use crate::blockchain::block::Block;
+use crate::transaction::receipt::Receipt;
+use crate::transaction::tx::TxType;
+use crate::util::bigint::U256;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// what a unit of gas costs. There's no fee market yet - every tx buys its gas at this price, see GasPurchase
+pub const GAS_PRICE: u64 = 1;
+
+/// gas figures for one block, worked out from its receipts when the block gets run
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct BlockGasStats {
+    pub block_number: usize,
+    pub tx_count: usize,
+    pub gas_used: u64,
+    /// sum of what the block's tx set as their gas limit
+    #[schema(value_type = String)]
+    pub gas_limit: U256,
+    /// fees paid / gas used. Null for blocks that didn't use any gas
+    #[schema(value_type = Option<String>)]
+    pub average_gas_price: Option<U256>,
+    /// what the block's calls to contracts used, per contract, most gas first. Left out when there weren't any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contracts: Vec<ContractGas>,
+}
+
+/// the gas spent calling one contract - in one block, or added up over the chain (see Blockchain::contract_gas()).
+/// Counts the whole tx, payload gas included, so two contracts doing the same job compare like for like
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct ContractGas {
+    #[schema(value_type = String)]
+    pub address: Address,
+    pub calls: u64,
+    pub gas_used: u64,
+}
+
+impl ContractGas {
+    /// adds each contract's calls and gas up, most gas first. Ties go by address, so the order doesn't change
+    /// between calls
+    pub fn total<'a>(entries: impl Iterator<Item = &'a ContractGas>) -> Vec<ContractGas> {
+        let mut totals: HashMap<Address, ContractGas> = HashMap::new();
+        for entry in entries {
+            let total = totals.entry(entry.address).or_insert(ContractGas {
+                address: entry.address,
+                calls: 0,
+                gas_used: 0,
+            });
+            total.calls += entry.calls;
+            total.gas_used = total.gas_used.saturating_add(entry.gas_used);
+        }
+        let mut totals: Vec<ContractGas> = totals.into_values().collect();
+        totals.sort_by(|a, b| {
+            b.gas_used
+                .cmp(&a.gas_used)
+                .then_with(|| a.address.to_string().cmp(&b.address.to_string()))
+        });
+        totals
+    }
+}
+
+impl BlockGasStats {
+    /// is_contract says whether an address has code, as of after the block - so a contract the block itself created
+    /// counts too
+    pub fn new(
+        block: &Block,
+        receipts: &[Receipt],
+        is_contract: impl Fn(&Address) -> bool,
+    ) -> Self {
+        let gas_used: u64 = receipts.iter().map(|receipt| receipt.gas_used).sum();
+        let fees = receipts.iter().fold(U256::zero(), |fees, receipt| {
+            fees.saturating_add(U256::from(receipt.gas_used) * GAS_PRICE)
+        });
+        let gas_limit = block.tx_series.iter().fold(U256::zero(), |total, tx| {
+            total.saturating_add(tx.unsigned_tx.gas_limit)
+        });
+        let calls = block
+            .tx_series
+            .iter()
+            .zip(receipts)
+            .filter(|(tx, _)| tx.unsigned_tx.data.tx_type == TxType::Transact)
+            .filter_map(|(tx, receipt)| tx.unsigned_tx.to.map(|to| (to, receipt.gas_used)))
+            .filter(|(to, _)| is_contract(to))
+            .map(|(address, gas_used)| ContractGas {
+                address,
+                calls: 1,
+                gas_used,
+            })
+            .collect::<Vec<_>>();
+        Self {
+            block_number: block.block_headers.truncated_block_headers.number,
+            tx_count: block.tx_series.len(),
+            gas_used,
+            gas_limit,
+            average_gas_price: if gas_used == 0 {
+                None
+            } else {
+                Some(fees / gas_used)
+            },
+            contracts: ContractGas::total(calls.iter()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{gen_address, Account};
+    use crate::config::chain::ChainRules;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::transaction::tx::Transaction;
+    use crate::util::clock::SystemClock;
+
+    #[test]
+    fn test_gas_stats_from_receipts() {
+        let tx = Transaction::create_transaction(
+            Some(Account::new(vec![])),
+            None,
+            0,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        let block = Block::mine_block(
+            &Block::genesis(&SystemClock, DEFAULT_CHAIN_ID),
+            gen_address(),
+            vec![tx],
+            "",
+            &SystemClock,
+            &ChainRules::default(),
+        );
+        //eg a smart contract call that used 7 gas, then the mining reward
+        let receipts = vec![
+            Receipt::new(&block.tx_series[0], 0, 7, 7, 1, &block.hash()),
+            Receipt::new(&block.tx_series[1], 1, 0, 7, 1, &block.hash()),
+        ];
+        let stats = BlockGasStats::new(&block, &receipts, |_| false);
+        assert_eq!(stats.block_number, 1);
+        assert_eq!(stats.tx_count, 2);
+        assert_eq!(stats.gas_used, 7);
+        assert_eq!(stats.gas_limit, U256::from(100 + 10));
+        assert_eq!(stats.average_gas_price, Some(U256::from(GAS_PRICE)));
+
+        assert!(stats.contracts.is_empty());
+        assert_eq!(
+            BlockGasStats::new(&block, &[], |_| false).average_gas_price,
+            None
+        );
+    }
+
+    #[test]
+    fn test_gas_per_contract() {
+        let sender = Account::new(vec![]);
+        let (cheap, pricey) = (gen_address(), gen_address());
+        let call = |to: Address| {
+            Transaction::create_transaction(
+                Some(sender.clone()),
+                Some(to),
+                0,
+                None,
+                100,
+                DEFAULT_CHAIN_ID,
+            )
+        };
+        //a plain transfer, which doesn't count
+        let block = Block::mine_block(
+            &Block::genesis(&SystemClock, DEFAULT_CHAIN_ID),
+            gen_address(),
+            vec![call(cheap), call(pricey), call(pricey), call(gen_address())],
+            "",
+            &SystemClock,
+            &ChainRules::default(),
+        );
+        let gas = [3, 10, 12, 1, 0];
+        let receipts: Vec<Receipt> = gas
+            .iter()
+            .enumerate()
+            .map(|(i, gas)| Receipt::new(&block.tx_series[i], i, *gas, 0, 1, &block.hash()))
+            .collect();
+        let stats = BlockGasStats::new(&block, &receipts, |address| {
+            *address == cheap || *address == pricey
+        });
+        let expected = vec![
+            ContractGas {
+                address: pricey,
+                calls: 2,
+                gas_used: 22,
+            },
+            ContractGas {
+                address: cheap,
+                calls: 1,
+                gas_used: 3,
+            },
+        ];
+        assert_eq!(stats.contracts, expected);
+        //the same again in another block doubles it
+        let totals = ContractGas::total(stats.contracts.iter().chain(expected.iter()));
+        assert_eq!(totals[0].calls, 4);
+        assert_eq!(totals[0].gas_used, 44);
+        assert_eq!(totals[1].gas_used, 6);
+    }
+}
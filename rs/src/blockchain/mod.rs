@@ -1,2 +1,11 @@
 pub mod block;
+#[allow(clippy::module_inception)]
 pub mod blockchain;
+pub mod bloom;
+pub mod consensus;
+pub mod fork;
+pub mod gas_stats;
+pub mod reward;
+pub mod snapshot;
+pub mod sync;
+pub mod work;
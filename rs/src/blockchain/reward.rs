@@ -0,0 +1,58 @@
+use crate::transaction::tx::MINING_REWARD;
+use crate::util::bigint::U256;
+
+/// the block subsidy as a function of the block number. It's a consensus rule - check_block() rejects a block paying
+/// anything else, so every node on a chain has to run with the same schedule (see ChainRules)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RewardSchedule {
+    /// what block 1 pays
+    pub initial: u64,
+    /// the reward halves every this many blocks. None = it never changes, eg for a proof of authority style chain
+    pub halving_interval: Option<u64>,
+}
+
+impl Default for RewardSchedule {
+    fn default() -> Self {
+        Self {
+            initial: MINING_REWARD,
+            halving_interval: None,
+        }
+    }
+}
+
+impl RewardSchedule {
+    pub fn reward_at(&self, block_number: usize) -> U256 {
+        //blocks 1..=interval pay the initial reward, the next interval half of it, etc
+        let halvings = self
+            .halving_interval
+            .map(|interval| block_number.saturating_sub(1) as u64 / interval)
+            .unwrap_or(0);
+        //shifting a u64 by 64 or more overflows - and the reward has been 0 for a while by then anyway
+        if halvings >= 64 {
+            return U256::zero();
+        }
+        U256::from(self.initial >> halvings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_halvings() {
+        let schedule = RewardSchedule {
+            initial: 50,
+            halving_interval: Some(10),
+        };
+        assert_eq!(schedule.reward_at(1), U256::from(50));
+        assert_eq!(schedule.reward_at(10), U256::from(50));
+        assert_eq!(schedule.reward_at(11), U256::from(25));
+        assert_eq!(schedule.reward_at(21), U256::from(12));
+        assert_eq!(schedule.reward_at(10 * 64 + 1), U256::zero());
+
+        let fixed = RewardSchedule::default();
+        assert_eq!(fixed.reward_at(1), U256::from(MINING_REWARD));
+        assert_eq!(fixed.reward_at(1_000_000), U256::from(MINING_REWARD));
+    }
+}
@@ -0,0 +1,155 @@
+use crate::account::address::Address;
+use crate::account::PublicAccount;
+use crate::blockchain::block::Block;
+use crate::error::ChainError;
+use crate::store::state::State;
+use crate::store::trie::Trie;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// the state as of a block - what a new node needs to start from that block instead of running the chain up to it.
+/// See Blockchain::import_snapshot()
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub block_number: usize,
+    pub block_hash: String,
+    pub state_root: String,
+    pub accounts: Vec<SnapshotAccount>,
+    //only contracts that have stored something. Storage roots aren't part of the account (see State::put_account()),
+    // so unlike the accounts there's nothing to prove these against - each one only gets checked against its own
+    // root_hash, when it's deserialized
+    pub storage: HashMap<Address, Trie>,
+}
+
+/// an account plus its merkle proof against the snapshot's state_root, checkable on its own with Trie::verify_proof()
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotAccount {
+    pub account: PublicAccount,
+    pub proof: Vec<String>,
+}
+
+impl Snapshot {
+    /// state has to be the state after block. Builds a proof per account, so take it from a copy, not under the lock
+    pub fn take(block: &Block, state: &State) -> Self {
+        let accounts = state
+            .state_trie
+            .entries()
+            .into_iter()
+            .map(|(key, value)| SnapshotAccount {
+                //only put_account() writes to the state trie, so an entry that doesn't parse is a bug
+                account: serde_json::from_str(&value).unwrap(),
+                proof: state.state_trie.get_proof(&key).unwrap(),
+            })
+            .collect();
+        let storage = state
+            .storage_trie_map
+            .addresses()
+            .into_iter()
+            .filter_map(|address| {
+                let trie = state.storage_trie_map.get(&address)?.into_owned();
+                Some((address, trie)).filter(|(_, trie)| !trie.is_empty())
+            })
+            .collect();
+        Self {
+            block_number: block.block_headers.truncated_block_headers.number,
+            block_hash: block.hash(),
+            state_root: state.get_state_root().clone(),
+            accounts,
+            storage,
+        }
+    }
+
+    /// checks every account's proof against state_root and rebuilds the state from them. The rebuilt trie has to come
+    /// out with the same root as well - that's what catches accounts that were left out
+    pub fn into_state(self) -> Result<State, ChainError> {
+        let mut state = State::new();
+        for SnapshotAccount { account, proof } in self.accounts {
+            let value = serde_json::to_string(&account).unwrap();
+            let proven = Trie::verify_proof(&self.state_root, &account.address.to_hex(), &proof);
+            if proven.as_ref() != Some(&value) {
+                return Err(ChainError::InvalidSnapshot(
+                    "account proof doesn't match the state root",
+                ));
+            }
+            state.put_account(account.address, account);
+        }
+        if state.get_state_root() != &self.state_root {
+            return Err(ChainError::InvalidSnapshot(
+                "accounts don't add up to the state root",
+            ));
+        }
+        state.storage_trie_map.extend(self.storage);
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::util::clock::SystemClock;
+
+    fn state_with_storage() -> (State, Address) {
+        let mut state = State::new();
+        state.allocate(Account::new(vec![]).public_account.address, 100);
+        state.allocate(Account::new(vec![]).public_account.address, 5);
+        let contract = Account::new(vec![]).public_account.address;
+        state.allocate(contract, 0);
+        state
+            .storage_trie_map
+            .get_or_create(contract)
+            .put("1".into(), "7".into());
+        (state, contract)
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let (state, contract) = state_with_storage();
+        let snapshot = Snapshot::take(&Block::genesis(&SystemClock, DEFAULT_CHAIN_ID), &state);
+        assert_eq!(snapshot.accounts.len(), 3);
+        assert_eq!(snapshot.storage.len(), 1);
+
+        //over the wire and back
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let imported = serde_json::from_str::<Snapshot>(&json)
+            .unwrap()
+            .into_state()
+            .unwrap();
+        assert_eq!(imported.get_state_root(), state.get_state_root());
+        assert_eq!(
+            imported
+                .storage_trie_map
+                .get(&contract)
+                .unwrap()
+                .get("1".into()),
+            Some(&"7".to_string())
+        );
+    }
+
+    #[test]
+    fn test_tampered_snapshot_is_rejected() {
+        let (state, _) = state_with_storage();
+        let snapshot = Snapshot::take(&Block::genesis(&SystemClock, DEFAULT_CHAIN_ID), &state);
+
+        let mut richer = snapshot.clone();
+        richer.accounts[0].account.balance = 1_000_000.into();
+        assert_eq!(
+            richer.into_state().unwrap_err(),
+            ChainError::InvalidSnapshot("account proof doesn't match the state root")
+        );
+
+        let mut missing = snapshot.clone();
+        missing.accounts.pop();
+        assert_eq!(
+            missing.into_state().unwrap_err(),
+            ChainError::InvalidSnapshot("accounts don't add up to the state root")
+        );
+
+        //a storage trie whose entries don't hash to its root doesn't even make it through serde
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let tampered = json.replace("[\"1\",\"7\"]", "[\"1\",\"8\"]");
+        assert_ne!(tampered, json);
+        assert!(serde_json::from_str::<Snapshot>(&tampered).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use crate::blockchain::block::MINE_RATE;
+use crate::config::DEFAULT_PARTITION_THRESHOLD;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// where the node is in its life: Initializing while it loads, Syncing while it catches up with a bootnode,
+/// Synced once it's caught up, and Mining while the miner is working on a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Lifecycle {
+    Initializing,
+    Syncing,
+    Synced,
+    Mining,
+}
+
+/// how far behind the node is, as GET /sync reports it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SyncStatus {
+    pub state: Lifecycle,
+    pub starting_block: usize,
+    pub current_block: usize,
+    /// the bootnode's head when the sync started, or our own head if that's further along
+    pub highest_block: usize,
+    /// None when not syncing, or before the first block comes in to estimate from
+    pub eta_ms: Option<u64>,
+}
+
+/// whether blocks are still coming in from other nodes, as GET /health reports it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PeerHealth {
+    /// we have peers, but none of them has sent us a block in threshold_ms. Either they're on the other side of a
+    /// network split, or the network has stopped making blocks
+    pub partitioned: bool,
+    /// None with rabbitmq, there's no knowing - it counts as connected then
+    pub peers: Option<u64>,
+    /// unix millis of the last block from a peer that made it onto our chain
+    pub last_peer_block: Option<i64>,
+    /// since that block, or since we started listening if there hasn't been one. None before we start listening
+    pub quiet_ms: Option<u64>,
+    /// partition_threshold block times
+    pub threshold_ms: u64,
+}
+
+/// the sync subsystem's record of the lifecycle. The chain itself says where we are, this says where we're headed
+#[derive(Debug)]
+pub struct SyncTracker {
+    state: Lifecycle,
+    starting_block: usize,
+    highest_block: usize,
+    //when the current sync started, in unix millis
+    started_at: i64,
+    /// how many MINE_RATEs without a block from a peer before we count as cut off from the network
+    pub partition_threshold: u32,
+    //when we started listening for peers' blocks, and when the last one came in. Unix millis
+    listening_since: Option<i64>,
+    last_peer_block: Option<i64>,
+    partitioned: bool,
+}
+
+impl Default for SyncTracker {
+    fn default() -> Self {
+        Self {
+            state: Lifecycle::Initializing,
+            starting_block: 0,
+            highest_block: 0,
+            started_at: 0,
+            partition_threshold: DEFAULT_PARTITION_THRESHOLD,
+            listening_since: None,
+            last_peer_block: None,
+            partitioned: false,
+        }
+    }
+}
+
+impl SyncTracker {
+    pub fn state(&self) -> Lifecycle {
+        self.state
+    }
+
+    pub fn start(&mut self, current_block: usize, highest_block: usize, now: i64) {
+        self.state = Lifecycle::Syncing;
+        self.starting_block = current_block;
+        self.highest_block = highest_block;
+        self.started_at = now;
+    }
+
+    pub fn finish(&mut self) {
+        self.state = Lifecycle::Synced;
+    }
+
+    /// the miner only runs on a synced node, so this only moves between Synced and Mining
+    pub fn set_mining(&mut self, mining: bool) {
+        self.state = match (self.state, mining) {
+            (Lifecycle::Synced, true) => Lifecycle::Mining,
+            (Lifecycle::Mining, false) => Lifecycle::Synced,
+            (state, _) => state,
+        };
+    }
+
+    /// once the node starts taking blocks from peers. Until then it can't tell a quiet network from a missing one
+    pub fn start_listening(&mut self, now: i64) {
+        self.listening_since = Some(now);
+    }
+
+    /// a peer's block made it onto our chain
+    pub fn record_peer_block(&mut self, now: i64) {
+        self.last_peer_block = Some(now);
+    }
+
+    /// also returns whether partitioned just changed - so it's warned about once, not on every check
+    pub fn peer_health(&mut self, peers: Option<u64>, now: i64) -> (PeerHealth, bool) {
+        let threshold_ms = self.partition_threshold as u64 * MINE_RATE as u64;
+        let quiet_ms = self
+            .last_peer_block
+            .or(self.listening_since)
+            .map(|since| (now - since).max(0) as u64);
+        let partitioned = peers != Some(0) && quiet_ms.is_some_and(|quiet| quiet > threshold_ms);
+        let changed = partitioned != self.partitioned;
+        self.partitioned = partitioned;
+        let health = PeerHealth {
+            partitioned,
+            peers,
+            last_peer_block: self.last_peer_block,
+            quiet_ms,
+            threshold_ms,
+        };
+        (health, changed)
+    }
+
+    /// the eta assumes the rest of the blocks come in as fast as the ones so far did
+    pub fn status(&self, current_block: usize, now: i64) -> SyncStatus {
+        let highest_block = std::cmp::max(self.highest_block, current_block);
+        let done = current_block.saturating_sub(self.starting_block) as u64;
+        let eta_ms = Some(self.state)
+            .filter(|state| *state == Lifecycle::Syncing && done > 0)
+            .map(|_| {
+                let elapsed = (now - self.started_at).max(0) as u64;
+                (highest_block - current_block) as u64 * elapsed / done
+            });
+        SyncStatus {
+            state: self.state,
+            starting_block: self.starting_block,
+            current_block,
+            highest_block,
+            eta_ms,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lifecycle() {
+        let mut tracker = SyncTracker::default();
+        assert_eq!(tracker.state(), Lifecycle::Initializing);
+        //mining doesn't start before the node has synced
+        tracker.set_mining(true);
+        assert_eq!(tracker.state(), Lifecycle::Initializing);
+
+        tracker.start(10, 110, 1_000);
+        assert_eq!(tracker.status(10, 1_000).eta_ms, None);
+        //20 blocks in 2s, so the other 80 take another 8s
+        let status = tracker.status(30, 3_000);
+        assert_eq!(status.state, Lifecycle::Syncing);
+        assert_eq!(status.highest_block, 110);
+        assert_eq!(status.eta_ms, Some(8_000));
+
+        tracker.finish();
+        tracker.set_mining(true);
+        assert_eq!(tracker.state(), Lifecycle::Mining);
+        tracker.set_mining(false);
+        let status = tracker.status(120, 20_000);
+        assert_eq!(status.state, Lifecycle::Synced);
+        assert_eq!(status.highest_block, 120);
+        assert_eq!(status.eta_ms, None);
+    }
+
+    #[test]
+    fn test_partition() {
+        let mut tracker = SyncTracker {
+            partition_threshold: 2,
+            ..SyncTracker::default()
+        };
+        let threshold = 2 * MINE_RATE;
+        //not listening yet, so no telling
+        let (health, changed) = tracker.peer_health(Some(3), threshold * 5);
+        assert!(!health.partitioned && !changed);
+        assert_eq!(health.quiet_ms, None);
+
+        tracker.start_listening(1_000);
+        assert!(
+            !tracker
+                .peer_health(Some(3), 1_000 + threshold)
+                .0
+                .partitioned
+        );
+        let (health, changed) = tracker.peer_health(Some(3), 1_001 + threshold);
+        assert!(health.partitioned && changed);
+        //only changed the first time
+        assert!(!tracker.peer_health(Some(3), 2_000 + threshold).1);
+        //nobody to hear from is just a lone node
+        assert!(
+            !tracker
+                .peer_health(Some(0), 2_000 + threshold)
+                .0
+                .partitioned
+        );
+        //with rabbitmq there's no peer count
+        assert!(tracker.peer_health(None, 2_000 + threshold).0.partitioned);
+
+        tracker.record_peer_block(3_000 + threshold);
+        let (health, changed) = tracker.peer_health(Some(3), 4_000 + threshold);
+        assert!(!health.partitioned && changed);
+        assert_eq!(health.last_peer_block, Some(3_000 + threshold));
+        assert_eq!(health.quiet_ms, Some(1_000));
+        assert_eq!(health.threshold_ms, threshold as u64);
+    }
+}
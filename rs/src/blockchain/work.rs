@@ -0,0 +1,84 @@
+use crate::blockchain::block::Block;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+/// how many handed out blocks an external miner can still submit a nonce for. Every GET /miner/work makes a new
+/// one, so this is how far a slow miner can fall behind its latest request
+pub const MAX_WORK_PACKAGES: usize = 16;
+
+/// the blocks given out to external miners, waiting for a nonce. Keyed by the truncated header hash, which is what
+/// the miner hashes its nonce onto, together with the block they go on top of
+#[derive(Debug, Default)]
+pub struct WorkPackages {
+    by_header_hash: HashMap<String, (Arc<Block>, Block)>,
+    //oldest first, so the oldest goes when it's full
+    order: VecDeque<String>,
+}
+
+impl WorkPackages {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, header_hash: String, parent: Arc<Block>, block: Block) {
+        if self.by_header_hash.contains_key(&header_hash) {
+            return;
+        }
+        if self.order.len() >= MAX_WORK_PACKAGES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.by_header_hash.remove(&oldest);
+            }
+        }
+        self.order.push_back(header_hash.clone());
+        self.by_header_hash.insert(header_hash, (parent, block));
+    }
+
+    /// the parent and the block, still without its nonce. Left in place, so a miner can try again if its nonce
+    /// doesn't do
+    pub fn get(&self, header_hash: &str) -> Option<(Arc<Block>, Block)> {
+        self.by_header_hash.get(header_hash).cloned()
+    }
+
+    /// once the head moves, every package on the old one would only be orphaned
+    pub fn retain_on(&mut self, head_hash: &str) {
+        let by_header_hash = &mut self.by_header_hash;
+        by_header_hash.retain(|_, (parent, _)| parent.hash() == head_hash);
+        self.order.retain(|hash| by_header_hash.contains_key(hash));
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::util::clock::SystemClock;
+
+    #[test]
+    fn test_work_packages() {
+        let genesis = Arc::new(Block::genesis(&SystemClock, DEFAULT_CHAIN_ID));
+        let mut packages = WorkPackages::new();
+        for i in 0..MAX_WORK_PACKAGES + 1 {
+            packages.insert(i.to_string(), genesis.clone(), (*genesis).clone());
+        }
+        //the first one made room for the last
+        assert_eq!(packages.len(), MAX_WORK_PACKAGES);
+        assert!(packages.get("0").is_none());
+        assert!(packages.get("1").is_some());
+        //still there after a get
+        assert!(packages.get("1").is_some());
+
+        packages.retain_on(&genesis.hash());
+        assert_eq!(packages.len(), MAX_WORK_PACKAGES);
+        packages.retain_on("some other head");
+        assert!(packages.is_empty());
+        assert!(packages.get("1").is_none());
+    }
+}
@@ -0,0 +1,34 @@
+use crate::blockchain::consensus::{ConsensusEngine, ProofOfWork};
+use crate::blockchain::fork::ForkSchedule;
+use crate::blockchain::reward::RewardSchedule;
+use crate::config::DEFAULT_EXEC_TIMEOUT_MS;
+use crate::transaction::fee::Treasury;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// what blocks get mined with and validated against, on top of the chain id. Built from the config at startup
+/// (NodeConfig::chain_rules()) and kept on the Blockchain
+#[derive(Debug, Clone)]
+pub struct ChainRules {
+    pub reward_schedule: RewardSchedule,
+    pub fork_schedule: ForkSchedule,
+    /// None = the miner gets everything
+    pub treasury: Option<Treasury>,
+    /// how long one tx's contract code may run, see NodeConfig::exec_timeout_ms. Handed to the interpreter through
+    /// Block::next_env()
+    pub exec_timeout: Duration,
+    /// how blocks get sealed and chains chosen
+    pub consensus: Arc<dyn ConsensusEngine>,
+}
+
+impl Default for ChainRules {
+    fn default() -> Self {
+        Self {
+            reward_schedule: RewardSchedule::default(),
+            fork_schedule: ForkSchedule::default(),
+            treasury: None,
+            exec_timeout: Duration::from_millis(DEFAULT_EXEC_TIMEOUT_MS),
+            consensus: Arc::new(ProofOfWork),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::account::gen_keypair;
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const KEYSTORE_DIR: &str = "keystore";
+pub const CHAINDATA_DIR: &str = "chaindata";
+pub const RECEIPTS_DIR: &str = "receipts";
+pub const STORAGE_DIR: &str = "storage";
+pub const NODEKEY_FILE: &str = "nodekey";
+pub const CONFIG_FILE: &str = "config.toml";
+pub const STATIC_NODES_FILE: &str = "static-nodes.json";
+pub const ADDRESS_BOOK_FILE: &str = "addressbook.json";
+
+/// everything a node keeps between restarts:
+///   <datadir>/keystore/    one encrypted key file per account
+///   <datadir>/chaindata/   the blocks we've accepted
+///   <datadir>/receipts/    the receipts of the last --receipt-history of them, one file per block
+///   <datadir>/storage/     the nodes of contract storage tries that didn't fit in memory (--storage-cache). Cleared on every start
+///   <datadir>/nodekey      hex secret key the node id is derived from
+///   <datadir>/config.toml  optional, picked up when there's no --config
+///   <datadir>/static-nodes.json  optional, nodes to sync and gossip with on top of the configured ones
+///   <datadir>/addressbook.json   names the api takes in place of addresses, per chain id. Written by POST /addressbook
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataDir {
+    pub root: PathBuf,
+}
+
+impl DataDir {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+        }
+    }
+    pub fn keystore(&self) -> PathBuf {
+        self.root.join(KEYSTORE_DIR)
+    }
+    pub fn chaindata(&self) -> PathBuf {
+        self.root.join(CHAINDATA_DIR)
+    }
+    pub fn receipts(&self) -> PathBuf {
+        self.root.join(RECEIPTS_DIR)
+    }
+    pub fn storage(&self) -> PathBuf {
+        self.root.join(STORAGE_DIR)
+    }
+    pub fn nodekey(&self) -> PathBuf {
+        self.root.join(NODEKEY_FILE)
+    }
+    pub fn config(&self) -> PathBuf {
+        self.root.join(CONFIG_FILE)
+    }
+    pub fn static_nodes(&self) -> PathBuf {
+        self.root.join(STATIC_NODES_FILE)
+    }
+    pub fn address_book(&self) -> PathBuf {
+        self.root.join(ADDRESS_BOOK_FILE)
+    }
+    /// creates whatever is missing, leaves the rest alone
+    pub fn init(&self) -> Result<(), String> {
+        for dir in [self.keystore(), self.chaindata(), self.receipts()].iter() {
+            fs::create_dir_all(dir).map_err(|e| format!("failed to create {:?}: {}", dir, e))?;
+        }
+        Ok(())
+    }
+    /// the node's identity - generated on first run, read back on every run after that
+    pub fn load_or_create_node_key(&self) -> Result<SecretKey, String> {
+        let path = self.nodekey();
+        if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+            let bytes = hex::decode(contents.trim())
+                .map_err(|_| format!("invalid node key in {:?}: not hex", path))?;
+            return SecretKey::from_slice(&bytes)
+                .map_err(|_| format!("invalid node key in {:?}", path));
+        }
+        let (secret_key, _) = gen_keypair();
+        write_private(&path, &hex::encode(&secret_key[..]))
+            .map_err(|e| format!("failed to write {:?}: {}", path, e))?;
+        tracing::info!(path = ?path, "generated a new node key");
+        Ok(secret_key)
+    }
+    /// the json array of node addresses in static-nodes.json, empty if there's no such file. What each one is for is
+    /// up to NodeConfig::apply_static_nodes()
+    pub fn load_static_nodes(&self) -> Result<Vec<String>, String> {
+        let path = self.static_nodes();
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        let contents =
+            fs::read_to_string(&path).map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+        serde_json::from_str(&contents).map_err(|e| {
+            format!(
+                "invalid {:?}: {} (expected a json array of addresses)",
+                path, e
+            )
+        })
+    }
+}
+
+/// what /admin/nodeinfo reports as the node id
+pub fn node_id(node_key: &SecretKey) -> PublicKey {
+    PublicKey::from_secret_key(&Secp256k1::new(), node_key)
+}
+
+/// only readable by the user the node runs as
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    fs::write(path, contents)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_node_key_survives_restart() {
+        let datadir = DataDir::new(&std::env::temp_dir().join(format!("node-{}", Uuid::new_v4())));
+        datadir.init().unwrap();
+        assert!(datadir.keystore().is_dir());
+        assert!(datadir.chaindata().is_dir());
+        assert!(datadir.receipts().is_dir());
+
+        let first = datadir.load_or_create_node_key().unwrap();
+        let second = datadir.load_or_create_node_key().unwrap();
+        assert_eq!(node_id(&first), node_id(&second));
+
+        fs::write(datadir.nodekey(), "not a key").unwrap();
+        assert!(datadir.load_or_create_node_key().is_err());
+
+        assert_eq!(datadir.load_static_nodes(), Ok(vec![]));
+        fs::write(
+            datadir.static_nodes(),
+            r#"["http://node1:8080", "node1:30303"]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            datadir.load_static_nodes().unwrap(),
+            vec!["http://node1:8080", "node1:30303"]
+        );
+        fs::write(datadir.static_nodes(), r#"{"nodes": []}"#).unwrap();
+        assert!(datadir.load_static_nodes().is_err());
+
+        fs::remove_dir_all(datadir.root).unwrap();
+    }
+}
@@ -0,0 +1,118 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// what can go in the toml file passed with --config. Everything is optional - whatever is left out keeps its default,
+/// and env vars / cli flags still override what's in here. Eg:
+///
+/// ```toml
+/// datadir = "./node1"
+/// port = 8081
+/// chain_id = 1337
+/// bootnodes = ["http://localhost:8080"]
+/// fast_sync = true
+/// gossip_port = 30304
+/// gossip_peers = ["localhost:30303"]
+/// amqp_addr = "amqp://127.0.0.1:5672/%2f"
+/// consumer_lag_warn = 1000
+/// partition_threshold = 10
+/// measure_propagation = true
+/// mining = false
+/// auto_mine = 5
+/// max_gas_limit = 10000
+/// initial_reward = 50
+/// halving_interval = 100000
+/// constantinople_block = 1000
+/// paris_block = 2000
+/// treasury = "<address>"
+/// treasury_fee_percent = 100
+/// treasury_reward_percent = 0
+/// exec_timeout_ms = 250
+/// storage_history = 1024
+/// receipt_history = 100000
+/// storage_cache = 4096
+/// slot_duration_ms = 12000
+/// slots_per_epoch = 32
+/// ```
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub datadir: Option<PathBuf>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub chain_id: Option<u64>,
+    pub bootnodes: Option<Vec<String>>,
+    pub fast_sync: Option<bool>,
+    pub gossip_port: Option<u16>,
+    pub gossip_peers: Option<Vec<String>>,
+    pub amqp_addr: Option<String>,
+    pub consumer_lag_warn: Option<u32>,
+    pub partition_threshold: Option<u32>,
+    pub measure_propagation: Option<bool>,
+    pub mining: Option<bool>,
+    pub auto_mine: Option<u64>,
+    pub max_gas_limit: Option<u64>,
+    pub initial_reward: Option<u64>,
+    pub halving_interval: Option<u64>,
+    pub constantinople_block: Option<u64>,
+    pub paris_block: Option<u64>,
+    pub treasury: Option<String>,
+    pub treasury_fee_percent: Option<u8>,
+    pub treasury_reward_percent: Option<u8>,
+    pub exec_timeout_ms: Option<u64>,
+    pub storage_history: Option<usize>,
+    pub receipt_history: Option<usize>,
+    pub storage_cache: Option<usize>,
+    pub slot_duration_ms: Option<u64>,
+    pub slots_per_epoch: Option<u64>,
+    pub dev: Option<bool>,
+    pub log_level: Option<String>,
+    pub log_format: Option<String>,
+}
+
+impl ConfigFile {
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        toml::from_str(contents).map_err(|e| e.to_string())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read config file {:?}: {}", path, e))?;
+        ConfigFile::parse(&contents).map_err(|e| format!("invalid config file {:?}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let file = ConfigFile::parse(
+            r#"
+            port = 9000
+            bootnodes = ["http://a:8080", "http://b:8080"]
+            mining = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.port, Some(9000));
+        assert_eq!(
+            file.bootnodes,
+            Some(vec!["http://a:8080".into(), "http://b:8080".into()])
+        );
+        assert_eq!(file.mining, Some(false));
+        assert_eq!(file.chain_id, None);
+        assert_eq!(ConfigFile::parse("").unwrap(), ConfigFile::default());
+    }
+
+    #[test]
+    fn test_bad_file() {
+        //typos shouldn't be silently ignored
+        assert!(ConfigFile::parse("prot = 9000").is_err());
+        assert!(ConfigFile::parse("port = \"9000\"").is_err());
+        assert!(ConfigFile::parse("port = 70000").is_err());
+        let err = ConfigFile::read(Path::new("/no/such/config.toml")).unwrap_err();
+        assert!(err.starts_with("failed to read config file"));
+    }
+}
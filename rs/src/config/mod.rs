@@ -0,0 +1,1367 @@
+pub mod chain;
+pub mod datadir;
+pub mod file;
+
+use crate::account::address::Address;
+use crate::blockchain::fork::ForkSchedule;
+use crate::blockchain::reward::RewardSchedule;
+use crate::config::chain::ChainRules;
+use crate::config::datadir::DataDir;
+use crate::config::file::ConfigFile;
+use crate::store::storage::DEFAULT_STORAGE_CACHE;
+use crate::transaction::fee::Treasury;
+use crate::util::bigint::{parse_u256, U256};
+use actix_web::http::Method;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const DEFAULT_HOST: &str = "localhost";
+pub const DEFAULT_PORT: u16 = 8080;
+/// same id local dev chains (ganache, hardhat) use
+pub const DEFAULT_CHAIN_ID: u64 = 1337;
+pub const DEFAULT_LOG_LEVEL: &str = "info";
+/// where nodes listen for each other's gossip, see network::gossip
+pub const DEFAULT_GOSSIP_PORT: u16 = 30303;
+/// a local rabbitmq with the default vhost
+pub const DEFAULT_AMQP_ADDR: &str = "amqp://127.0.0.1:5672/%2f";
+/// how many messages can pile up on the broker before a consumer warns it's falling behind
+pub const DEFAULT_CONSUMER_LAG_WARN: u32 = 1_000;
+/// how many block times (MINE_RATE) a node with peers can go without a block from them before it warns it's likely
+/// cut off from the network
+pub const DEFAULT_PARTITION_THRESHOLD: u32 = 10;
+/// the most gas a single tx submitted to this node may ask for
+pub const DEFAULT_MAX_GAS_LIMIT: u64 = 1_000_000;
+/// by default the treasury gets all of the gas fees (like ethereum burning the base fee) and none of the reward
+pub const DEFAULT_TREASURY_FEE_PERCENT: u8 = 100;
+pub const DEFAULT_TREASURY_REWARD_PERCENT: u8 = 0;
+
+/// wall-clock budget for running one tx's contract code. Gas and the interpreter's step limit should stop anything
+/// long before this - it's the backstop for code that's cheap in gas but slow to run
+pub const DEFAULT_EXEC_TIMEOUT_MS: u64 = 250;
+
+/// ethereum's beacon chain timings
+pub const DEFAULT_SLOT_DURATION_MS: u64 = 12_000;
+pub const DEFAULT_SLOTS_PER_EPOCH: u64 = 32;
+
+/// what the miner starts with in --dev mode, so the faucet has something to hand out
+pub const DEV_MINER_BALANCE: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// human readable, for running locally
+    Pretty,
+    /// one json object per line, for log aggregators
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!(
+                "invalid log format: {} (expected pretty or json)",
+                format
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeConfig {
+    pub chain_id: u64,
+    pub host: String,
+    pub port: u16,
+    /// nodes to download the chain from on startup, eg "http://localhost:8080", tried in order until one works.
+    /// If empty, we start from genesis
+    pub bootnodes: Vec<String>,
+    /// take the bootnode's head state from /snapshot instead of running every block since genesis.
+    /// Only used while our own chain is still just genesis
+    pub fast_sync: bool,
+    pub datadir: Option<PathBuf>,
+    /// unlocks every key file in <datadir>/keystore at startup and encrypts new ones.
+    /// If None, persisted accounts stay locked until unlocked through the api
+    pub keystore_password: Option<String>,
+    /// bip-39 phrase. If set the miner account is derived from it (index 0) instead of being random
+    pub mnemonic: Option<String>,
+    /// extra accounts derived from the mnemonic (index 1..=n) and created alongside the miner
+    pub dev_accounts: u32,
+    /// balances credited in the genesis state. Every node on the chain has to be started with the same ones
+    pub genesis_alloc: Vec<(Address, U256)>,
+    /// deterministic test mode - every generated key is derived from this seed, so addresses are the same every run.
+    /// Never for real funds
+    pub key_seed: Option<String>,
+    /// local development: funds the miner with DEV_MINER_BALANCE and turns on POST /faucet
+    pub dev: bool,
+    /// the port other nodes connect to to gossip blocks and txs, on the same host as the api
+    pub gossip_port: u16,
+    /// nodes to gossip with, eg "10.0.0.1:30303". They connect back to us through gossip_port, so only one side of
+    /// each pair needs the other in here
+    pub gossip_peers: Vec<String>,
+    /// the rabbitmq broker blocks and txs get broadcast through. Only used by builds with the rabbitmq feature
+    pub amqp_addr: String,
+    /// a consumer warns once this many messages are waiting for it on the broker
+    pub consumer_lag_warn: u32,
+    /// block times without a block from a peer before the node counts as cut off, see GET /health
+    pub partition_threshold: u32,
+    /// instrumentation: note when each tx is first seen, see GET /propagation
+    pub measure_propagation: bool,
+    /// if false, /mine is turned off and the node only validates and relays
+    pub mining: bool,
+    /// start the auto miner (see api::miner) on boot, mining a block every this many seconds. 0 = as soon as there
+    /// are tx to mine
+    pub auto_mine: Option<u64>,
+    /// txs asking for more gas than this are rejected on submission
+    pub max_gas_limit: u64,
+    /// block subsidy - like the chain id, every node on the chain has to be started with the same one
+    pub reward_schedule: RewardSchedule,
+    /// the blocks protocol upgrades activate at - same on every node, like the reward schedule
+    pub fork_schedule: ForkSchedule,
+    /// the treasury / burn address that gets a cut of every block. Part of the chain's rules like the reward schedule
+    pub treasury: Option<Address>,
+    pub treasury_fee_percent: u8,
+    pub treasury_reward_percent: u8,
+    /// how long one tx's contract code may run, in ms. Every node should use the same value (or leave the default) -
+    /// a node with a lower one rejects blocks that ran fine for the miner
+    pub exec_timeout_ms: u64,
+    /// how many blocks back contract storage can be read at. None = every block since genesis
+    pub storage_history: Option<usize>,
+    /// how many blocks back receipts (and their logs) are kept, in memory and under <datadir>/receipts. None = all
+    pub receipt_history: Option<usize>,
+    /// how many contracts' storage tries stay in memory. Only with a datadir - the rest go under <datadir>/storage
+    pub storage_cache: usize,
+    /// slot and epoch lengths for the consensus clock (see util::clock::ConsensusClock)
+    pub slot_duration_ms: u64,
+    pub slots_per_epoch: u64,
+    /// if set, state-mutating and admin endpoints require "Authorization: Bearer <token>"
+    pub auth_token: Option<String>,
+    /// origins allowed to call the api from a browser. Empty = no cross-origin requests, "*" = any origin
+    pub cors_origins: Vec<String>,
+    pub cors_methods: Vec<String>,
+    /// pem files. If both are set, the api is served over https instead of http
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// a level ("debug") or a full tracing filter ("info,rs::api=debug")
+    pub log_level: String,
+    pub log_format: LogFormat,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        Self {
+            chain_id: DEFAULT_CHAIN_ID,
+            host: DEFAULT_HOST.into(),
+            port: DEFAULT_PORT,
+            bootnodes: vec![],
+            fast_sync: false,
+            datadir: None,
+            keystore_password: None,
+            mnemonic: None,
+            dev_accounts: 0,
+            genesis_alloc: vec![],
+            key_seed: None,
+            dev: false,
+            gossip_port: DEFAULT_GOSSIP_PORT,
+            gossip_peers: vec![],
+            amqp_addr: DEFAULT_AMQP_ADDR.into(),
+            consumer_lag_warn: DEFAULT_CONSUMER_LAG_WARN,
+            partition_threshold: DEFAULT_PARTITION_THRESHOLD,
+            measure_propagation: false,
+            mining: true,
+            auto_mine: None,
+            max_gas_limit: DEFAULT_MAX_GAS_LIMIT,
+            reward_schedule: RewardSchedule::default(),
+            fork_schedule: ForkSchedule::default(),
+            treasury: None,
+            treasury_fee_percent: DEFAULT_TREASURY_FEE_PERCENT,
+            treasury_reward_percent: DEFAULT_TREASURY_REWARD_PERCENT,
+            exec_timeout_ms: DEFAULT_EXEC_TIMEOUT_MS,
+            storage_history: None,
+            receipt_history: None,
+            storage_cache: DEFAULT_STORAGE_CACHE,
+            slot_duration_ms: DEFAULT_SLOT_DURATION_MS,
+            slots_per_epoch: DEFAULT_SLOTS_PER_EPOCH,
+            auth_token: None,
+            cors_origins: vec![],
+            cors_methods: vec!["GET".into(), "POST".into()],
+            tls_cert: None,
+            tls_key: None,
+            log_level: DEFAULT_LOG_LEVEL.into(),
+            log_format: LogFormat::Pretty,
+        }
+    }
+}
+
+impl NodeConfig {
+    /// defaults, overridden by the config file, overridden by env vars, overridden by cli flags
+    pub fn load(args: &[String]) -> Result<Self, String> {
+        NodeConfig::load_with(args, |key| std::env::var(key).ok())
+    }
+
+    pub fn load_with<F>(args: &[String], lookup: F) -> Result<Self, String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut config = NodeConfig::default();
+        if let Some(path) = config_path(args, &lookup)? {
+            config.apply_file(ConfigFile::read(&path)?)?;
+        }
+        config.apply_env(&lookup)?;
+        config.apply_args(args)?;
+        if let Some(datadir) = &config.datadir {
+            let static_nodes = DataDir::new(datadir).load_static_nodes()?;
+            config.apply_static_nodes(&static_nodes)?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// the nodes from <datadir>/static-nodes.json, after the configured ones. An http(s) url gets synced from like a
+    /// --bootnode, a host:port gets gossiped with like a --gossip-peer. Whatever's configured already is skipped
+    pub fn apply_static_nodes(&mut self, nodes: &[String]) -> Result<(), String> {
+        for node in nodes {
+            let list = if node.starts_with("http://") || node.starts_with("https://") {
+                &mut self.bootnodes
+            } else if is_host_and_port(node) {
+                &mut self.gossip_peers
+            } else {
+                return Err(format!(
+                    "invalid static node: {} (expected an http(s) url or host:port)",
+                    node
+                ));
+            };
+            if !list.contains(node) {
+                list.push(node.clone());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn treasury(&self) -> Option<Treasury> {
+        self.treasury.map(|address| Treasury {
+            address,
+            fee_percent: self.treasury_fee_percent,
+            reward_percent: self.treasury_reward_percent,
+        })
+    }
+
+    pub fn chain_rules(&self) -> ChainRules {
+        ChainRules {
+            reward_schedule: self.reward_schedule,
+            fork_schedule: self.fork_schedule,
+            treasury: self.treasury(),
+            exec_timeout: Duration::from_millis(self.exec_timeout_ms),
+            ..ChainRules::default()
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(bootnode) = self
+            .bootnodes
+            .iter()
+            .find(|b| !b.starts_with("http://") && !b.starts_with("https://"))
+        {
+            return Err(format!(
+                "invalid bootnode: {} (expected an http(s) url)",
+                bootnode
+            ));
+        }
+        if let Some(peer) = self
+            .gossip_peers
+            .iter()
+            .find(|peer| !is_host_and_port(peer))
+        {
+            return Err(format!(
+                "invalid gossip peer: {} (expected host:port)",
+                peer
+            ));
+        }
+        if !self.amqp_addr.starts_with("amqp://") && !self.amqp_addr.starts_with("amqps://") {
+            return Err(format!(
+                "invalid amqp address: {} (expected an amqp(s) url)",
+                self.amqp_addr
+            ));
+        }
+        if self.consumer_lag_warn == 0 {
+            return Err("consumer lag warning threshold must be above 0".into());
+        }
+        if self.partition_threshold == 0 {
+            return Err("partition threshold must be above 0".into());
+        }
+        if self.auto_mine.is_some() && !self.mining {
+            return Err("the auto miner can't run with mining turned off".into());
+        }
+        if self.max_gas_limit == 0 {
+            return Err("max gas limit must be above 0".into());
+        }
+        if self.reward_schedule.halving_interval == Some(0) {
+            return Err("halving interval must be above 0".into());
+        }
+        if self.treasury_fee_percent > 100 || self.treasury_reward_percent > 100 {
+            return Err("treasury percentages can't be above 100".into());
+        }
+        if self.exec_timeout_ms == 0 {
+            return Err("exec timeout must be above 0".into());
+        }
+        if self.storage_cache == 0 {
+            return Err("storage cache must be above 0".into());
+        }
+        if self.slot_duration_ms == 0 || self.slots_per_epoch == 0 {
+            return Err("slot duration and slots per epoch must be above 0".into());
+        }
+        //each fork builds on the one before, see Fork
+        if let Some(paris) = self.fork_schedule.paris {
+            if self.fork_schedule.constantinople.is_none_or(|c| c > paris) {
+                return Err("the paris fork can't activate before constantinople".into());
+            }
+        }
+        if self.tls_cert.is_some() != self.tls_key.is_some() {
+            return Err("--tls-cert and --tls-key must be provided together".into());
+        }
+        if self.dev_accounts > 0 && self.mnemonic.is_none() {
+            return Err("--dev-accounts needs a --mnemonic to derive them from".into());
+        }
+        Ok(())
+    }
+
+    /// only what's set in the file is applied
+    pub fn apply_file(&mut self, file: ConfigFile) -> Result<(), String> {
+        if let Some(datadir) = file.datadir {
+            self.datadir = Some(datadir);
+        }
+        if let Some(host) = file.host {
+            self.host = host;
+        }
+        if let Some(port) = file.port {
+            self.port = port;
+        }
+        if let Some(chain_id) = file.chain_id {
+            self.chain_id = chain_id;
+        }
+        if let Some(bootnodes) = file.bootnodes {
+            self.bootnodes = bootnodes;
+        }
+        if let Some(fast_sync) = file.fast_sync {
+            self.fast_sync = fast_sync;
+        }
+        if let Some(gossip_port) = file.gossip_port {
+            self.gossip_port = gossip_port;
+        }
+        if let Some(gossip_peers) = file.gossip_peers {
+            self.gossip_peers = gossip_peers;
+        }
+        if let Some(amqp_addr) = file.amqp_addr {
+            self.amqp_addr = amqp_addr;
+        }
+        if let Some(consumer_lag_warn) = file.consumer_lag_warn {
+            self.consumer_lag_warn = consumer_lag_warn;
+        }
+        if let Some(partition_threshold) = file.partition_threshold {
+            self.partition_threshold = partition_threshold;
+        }
+        if let Some(measure_propagation) = file.measure_propagation {
+            self.measure_propagation = measure_propagation;
+        }
+        if let Some(mining) = file.mining {
+            self.mining = mining;
+        }
+        if let Some(auto_mine) = file.auto_mine {
+            self.auto_mine = Some(auto_mine);
+        }
+        if let Some(max_gas_limit) = file.max_gas_limit {
+            self.max_gas_limit = max_gas_limit;
+        }
+        if let Some(initial_reward) = file.initial_reward {
+            self.reward_schedule.initial = initial_reward;
+        }
+        if let Some(halving_interval) = file.halving_interval {
+            self.reward_schedule.halving_interval = Some(halving_interval);
+        }
+        if let Some(block) = file.constantinople_block {
+            self.fork_schedule.constantinople = Some(block);
+        }
+        if let Some(block) = file.paris_block {
+            self.fork_schedule.paris = Some(block);
+        }
+        if let Some(treasury) = file.treasury {
+            self.treasury = Some(parse_address(&treasury)?);
+        }
+        if let Some(percent) = file.treasury_fee_percent {
+            self.treasury_fee_percent = percent;
+        }
+        if let Some(percent) = file.treasury_reward_percent {
+            self.treasury_reward_percent = percent;
+        }
+        if let Some(exec_timeout_ms) = file.exec_timeout_ms {
+            self.exec_timeout_ms = exec_timeout_ms;
+        }
+        if let Some(storage_history) = file.storage_history {
+            self.storage_history = Some(storage_history);
+        }
+        if let Some(receipt_history) = file.receipt_history {
+            self.receipt_history = Some(receipt_history);
+        }
+        if let Some(storage_cache) = file.storage_cache {
+            self.storage_cache = storage_cache;
+        }
+        if let Some(slot_duration_ms) = file.slot_duration_ms {
+            self.slot_duration_ms = slot_duration_ms;
+        }
+        if let Some(slots_per_epoch) = file.slots_per_epoch {
+            self.slots_per_epoch = slots_per_epoch;
+        }
+        if let Some(dev) = file.dev {
+            self.dev = dev;
+        }
+        if let Some(level) = file.log_level {
+            self.log_level = level;
+        }
+        if let Some(format) = file.log_format {
+            self.log_format = format.parse()?;
+        }
+        Ok(())
+    }
+
+    /// takes the lookup fn as a param so that tests don't have to mess with the real process env
+    pub fn apply_env<F>(&mut self, lookup: F) -> Result<(), String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(chain_id) = lookup("NODE_CHAIN_ID") {
+            self.chain_id = parse_chain_id(&chain_id)?;
+        }
+        if let Some(host) = lookup("NODE_HOST") {
+            self.host = host;
+        }
+        if let Some(port) = lookup("NODE_PORT") {
+            self.port = parse_port(&port)?;
+        }
+        if let Some(bootnodes) = lookup("NODE_BOOTNODE") {
+            self.bootnodes = split_list(&bootnodes);
+        }
+        if let Some(fast_sync) = lookup("NODE_FAST_SYNC") {
+            self.fast_sync = parse_bool(&fast_sync)?;
+        }
+        if let Some(datadir) = lookup("NODE_DATADIR") {
+            self.datadir = Some(PathBuf::from(datadir));
+        }
+        if let Some(password) = lookup("NODE_KEYSTORE_PASSWORD") {
+            self.keystore_password = Some(password);
+        }
+        if let Some(mnemonic) = lookup("NODE_MNEMONIC") {
+            self.mnemonic = Some(mnemonic);
+        }
+        if let Some(dev_accounts) = lookup("NODE_DEV_ACCOUNTS") {
+            self.dev_accounts = parse_dev_accounts(&dev_accounts)?;
+        }
+        if let Some(alloc) = lookup("NODE_GENESIS_ALLOC") {
+            self.genesis_alloc = split_list(&alloc)
+                .iter()
+                .map(|entry| parse_alloc(entry))
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(seed) = lookup("NODE_KEY_SEED") {
+            self.key_seed = Some(seed);
+        }
+        if let Some(dev) = lookup("NODE_DEV") {
+            self.dev = parse_bool(&dev)?;
+        }
+        if let Some(gossip_port) = lookup("NODE_GOSSIP_PORT") {
+            self.gossip_port = parse_port(&gossip_port)?;
+        }
+        if let Some(gossip_peers) = lookup("NODE_GOSSIP_PEERS") {
+            self.gossip_peers = split_list(&gossip_peers);
+        }
+        //AMQP_ADDR is what the node read before it had a config
+        if let Some(amqp_addr) = lookup("NODE_AMQP_ADDR").or_else(|| lookup("AMQP_ADDR")) {
+            self.amqp_addr = amqp_addr;
+        }
+        if let Some(consumer_lag_warn) = lookup("NODE_CONSUMER_LAG_WARN") {
+            self.consumer_lag_warn = parse_lag_warn(&consumer_lag_warn)?;
+        }
+        if let Some(partition_threshold) = lookup("NODE_PARTITION_THRESHOLD") {
+            self.partition_threshold = parse_partition_threshold(&partition_threshold)?;
+        }
+        if let Some(measure_propagation) = lookup("NODE_MEASURE_PROPAGATION") {
+            self.measure_propagation = parse_bool(&measure_propagation)?;
+        }
+        if let Some(mining) = lookup("NODE_MINING") {
+            self.mining = parse_bool(&mining)?;
+        }
+        if let Some(auto_mine) = lookup("NODE_AUTO_MINE") {
+            self.auto_mine = Some(parse_mining_interval(&auto_mine)?);
+        }
+        if let Some(max_gas_limit) = lookup("NODE_MAX_GAS_LIMIT") {
+            self.max_gas_limit = parse_gas_limit(&max_gas_limit)?;
+        }
+        if let Some(initial_reward) = lookup("NODE_INITIAL_REWARD") {
+            self.reward_schedule.initial = parse_reward(&initial_reward)?;
+        }
+        if let Some(halving_interval) = lookup("NODE_HALVING_INTERVAL") {
+            self.reward_schedule.halving_interval =
+                Some(parse_halving_interval(&halving_interval)?);
+        }
+        if let Some(block) = lookup("NODE_CONSTANTINOPLE_BLOCK") {
+            self.fork_schedule.constantinople = Some(parse_fork_block(&block)?);
+        }
+        if let Some(block) = lookup("NODE_PARIS_BLOCK") {
+            self.fork_schedule.paris = Some(parse_fork_block(&block)?);
+        }
+        if let Some(treasury) = lookup("NODE_TREASURY") {
+            self.treasury = Some(parse_address(&treasury)?);
+        }
+        if let Some(percent) = lookup("NODE_TREASURY_FEE_PERCENT") {
+            self.treasury_fee_percent = parse_percent(&percent)?;
+        }
+        if let Some(percent) = lookup("NODE_TREASURY_REWARD_PERCENT") {
+            self.treasury_reward_percent = parse_percent(&percent)?;
+        }
+        if let Some(exec_timeout_ms) = lookup("NODE_EXEC_TIMEOUT_MS") {
+            self.exec_timeout_ms = parse_timeout(&exec_timeout_ms)?;
+        }
+        if let Some(storage_history) = lookup("NODE_STORAGE_HISTORY") {
+            self.storage_history = Some(parse_storage_history(&storage_history)?);
+        }
+        if let Some(receipt_history) = lookup("NODE_RECEIPT_HISTORY") {
+            self.receipt_history = Some(parse_receipt_history(&receipt_history)?);
+        }
+        if let Some(storage_cache) = lookup("NODE_STORAGE_CACHE") {
+            self.storage_cache = parse_storage_cache(&storage_cache)?;
+        }
+        if let Some(slot_duration_ms) = lookup("NODE_SLOT_DURATION_MS") {
+            self.slot_duration_ms = parse_slot_duration(&slot_duration_ms)?;
+        }
+        if let Some(slots_per_epoch) = lookup("NODE_SLOTS_PER_EPOCH") {
+            self.slots_per_epoch = parse_slots_per_epoch(&slots_per_epoch)?;
+        }
+        if let Some(auth_token) = lookup("NODE_AUTH_TOKEN") {
+            self.auth_token = Some(auth_token);
+        }
+        if let Some(origins) = lookup("NODE_CORS_ORIGINS") {
+            self.cors_origins = split_list(&origins);
+        }
+        if let Some(methods) = lookup("NODE_CORS_METHODS") {
+            self.cors_methods = parse_methods(&methods)?;
+        }
+        if let Some(cert) = lookup("NODE_TLS_CERT") {
+            self.tls_cert = Some(PathBuf::from(cert));
+        }
+        if let Some(key) = lookup("NODE_TLS_KEY") {
+            self.tls_key = Some(PathBuf::from(key));
+        }
+        if let Some(level) = lookup("NODE_LOG_LEVEL") {
+            self.log_level = level;
+        }
+        if let Some(format) = lookup("NODE_LOG_FORMAT") {
+            self.log_format = format.parse()?;
+        }
+        Ok(())
+    }
+
+    /// expects the raw output of env::args(), ie the first item is the binary name
+    pub fn apply_args(&mut self, args: &[String]) -> Result<(), String> {
+        let mut args = args.iter().skip(1);
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--chain-id" => self.chain_id = parse_chain_id(&next_value(flag, args.next())?)?,
+                "--host" => self.host = next_value(flag, args.next())?,
+                "--port" => self.port = parse_port(&next_value(flag, args.next())?)?,
+                //already read by load()
+                "--config" => {
+                    next_value(flag, args.next())?;
+                }
+                //can be passed multiple times
+                "--bootnode" => self.bootnodes.push(next_value(flag, args.next())?),
+                "--fast-sync" => self.fast_sync = true,
+                "--datadir" => self.datadir = Some(PathBuf::from(next_value(flag, args.next())?)),
+                "--keystore-password" => {
+                    self.keystore_password = Some(next_value(flag, args.next())?)
+                }
+                "--mnemonic" => self.mnemonic = Some(next_value(flag, args.next())?),
+                "--dev-accounts" => {
+                    self.dev_accounts = parse_dev_accounts(&next_value(flag, args.next())?)?
+                }
+                //can be passed multiple times
+                "--genesis-alloc" => self
+                    .genesis_alloc
+                    .push(parse_alloc(&next_value(flag, args.next())?)?),
+                "--key-seed" => self.key_seed = Some(next_value(flag, args.next())?),
+                "--dev" => self.dev = true,
+                "--gossip-port" => self.gossip_port = parse_port(&next_value(flag, args.next())?)?,
+                //can be passed multiple times
+                "--gossip-peer" => self.gossip_peers.push(next_value(flag, args.next())?),
+                "--amqp-addr" => self.amqp_addr = next_value(flag, args.next())?,
+                "--consumer-lag-warn" => {
+                    self.consumer_lag_warn = parse_lag_warn(&next_value(flag, args.next())?)?
+                }
+                "--partition-threshold" => {
+                    self.partition_threshold =
+                        parse_partition_threshold(&next_value(flag, args.next())?)?
+                }
+                "--measure-propagation" => self.measure_propagation = true,
+                "--no-mining" => self.mining = false,
+                "--auto-mine" => {
+                    self.auto_mine = Some(parse_mining_interval(&next_value(flag, args.next())?)?)
+                }
+                "--max-gas-limit" => {
+                    self.max_gas_limit = parse_gas_limit(&next_value(flag, args.next())?)?
+                }
+                "--initial-reward" => {
+                    self.reward_schedule.initial = parse_reward(&next_value(flag, args.next())?)?
+                }
+                "--halving-interval" => {
+                    self.reward_schedule.halving_interval =
+                        Some(parse_halving_interval(&next_value(flag, args.next())?)?)
+                }
+                "--constantinople-block" => {
+                    self.fork_schedule.constantinople =
+                        Some(parse_fork_block(&next_value(flag, args.next())?)?)
+                }
+                "--paris-block" => {
+                    self.fork_schedule.paris =
+                        Some(parse_fork_block(&next_value(flag, args.next())?)?)
+                }
+                "--treasury" => {
+                    self.treasury = Some(parse_address(&next_value(flag, args.next())?)?)
+                }
+                "--treasury-fee-percent" => {
+                    self.treasury_fee_percent = parse_percent(&next_value(flag, args.next())?)?
+                }
+                "--treasury-reward-percent" => {
+                    self.treasury_reward_percent = parse_percent(&next_value(flag, args.next())?)?
+                }
+                "--exec-timeout-ms" => {
+                    self.exec_timeout_ms = parse_timeout(&next_value(flag, args.next())?)?
+                }
+                "--storage-history" => {
+                    self.storage_history =
+                        Some(parse_storage_history(&next_value(flag, args.next())?)?)
+                }
+                "--receipt-history" => {
+                    self.receipt_history =
+                        Some(parse_receipt_history(&next_value(flag, args.next())?)?)
+                }
+                "--storage-cache" => {
+                    self.storage_cache = parse_storage_cache(&next_value(flag, args.next())?)?
+                }
+                "--slot-duration-ms" => {
+                    self.slot_duration_ms = parse_slot_duration(&next_value(flag, args.next())?)?
+                }
+                "--slots-per-epoch" => {
+                    self.slots_per_epoch = parse_slots_per_epoch(&next_value(flag, args.next())?)?
+                }
+                "--auth-token" => self.auth_token = Some(next_value(flag, args.next())?),
+                //can be passed multiple times
+                "--cors-origin" => self.cors_origins.push(next_value(flag, args.next())?),
+                "--cors-methods" => {
+                    self.cors_methods = parse_methods(&next_value(flag, args.next())?)?
+                }
+                "--tls-cert" => self.tls_cert = Some(PathBuf::from(next_value(flag, args.next())?)),
+                "--tls-key" => self.tls_key = Some(PathBuf::from(next_value(flag, args.next())?)),
+                "--log-level" => self.log_level = next_value(flag, args.next())?,
+                "--log-format" => self.log_format = next_value(flag, args.next())?.parse()?,
+                // kept for backwards compatibility - a peer syncs from the default node and listens one port up
+                "--peer" | "-p" => {
+                    self.bootnodes = vec![format!("http://{}:{}", DEFAULT_HOST, DEFAULT_PORT)];
+                    self.port = DEFAULT_PORT + 1;
+                    self.gossip_peers = vec![format!("{}:{}", DEFAULT_HOST, DEFAULT_GOSSIP_PORT)];
+                    self.gossip_port = DEFAULT_GOSSIP_PORT + 1;
+                }
+                _ => return Err(format!("unknown flag: {}", flag)),
+            }
+        }
+        Ok(())
+    }
+
+    /// optional node features that are switched on, as reported by /admin/nodeinfo
+    pub fn enabled_features(&self) -> Vec<String> {
+        let mut features = vec![];
+        if self.auth_token.is_some() {
+            features.push("auth".into());
+        }
+        if !self.cors_origins.is_empty() {
+            features.push("cors".into());
+        }
+        if self.tls_cert.is_some() {
+            features.push("tls".into());
+        }
+        if self.datadir.is_some() {
+            features.push("persistent_keystore".into());
+        }
+        if self.dev {
+            features.push("dev".into());
+        }
+        if self.treasury.is_some() {
+            features.push("treasury".into());
+        }
+        if self.measure_propagation {
+            features.push("measure_propagation".into());
+        }
+        if self.auto_mine.is_some() {
+            features.push("auto_mine".into());
+        }
+        features
+    }
+
+    pub fn bind_addr(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+    pub fn gossip_addr(&self) -> String {
+        format!("{}:{}", self.host, self.gossip_port)
+    }
+}
+
+/// --config wins over NODE_CONFIG, which wins over <datadir>/config.toml (if there is one).
+/// Has to be known before anything else is applied
+fn config_path<F>(args: &[String], lookup: F) -> Result<Option<PathBuf>, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if let Some(path) = find_arg(args, "--config")?.or_else(|| lookup("NODE_CONFIG")) {
+        return Ok(Some(PathBuf::from(path)));
+    }
+    let datadir = find_arg(args, "--datadir")?.or_else(|| lookup("NODE_DATADIR"));
+    Ok(datadir
+        .map(|datadir| DataDir::new(Path::new(&datadir)).config())
+        .filter(|path| path.exists()))
+}
+
+/// the value of a flag, before the args are properly parsed
+fn find_arg(args: &[String], flag: &str) -> Result<Option<String>, String> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .map(|i| next_value(flag, args.get(i + 1)))
+        .transpose()
+}
+
+pub fn next_value(flag: &str, value: Option<&String>) -> Result<String, String> {
+    value
+        .cloned()
+        .ok_or_else(|| format!("missing value for {}", flag))
+}
+
+//eg "10.0.0.1:30303" or "node1:30303"
+fn is_host_and_port(addr: &str) -> bool {
+    match addr.rsplit_once(':') {
+        Some((host, port)) => {
+            !host.is_empty() && !host.contains('/') && port.parse::<u16>().is_ok()
+        }
+        None => false,
+    }
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|item| item.trim().to_owned())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// eg "GET,POST"
+fn parse_methods(methods: &str) -> Result<Vec<String>, String> {
+    split_list(methods)
+        .into_iter()
+        .map(|m| {
+            let m = m.to_uppercase();
+            Method::from_str(&m)
+                .map(|_| m.clone())
+                .map_err(|_| format!("invalid http method: {}", m))
+        })
+        .collect()
+}
+
+fn parse_chain_id(chain_id: &str) -> Result<u64, String> {
+    chain_id
+        .parse::<u64>()
+        .map_err(|_| format!("invalid chain id: {}", chain_id))
+}
+
+fn parse_dev_accounts(count: &str) -> Result<u32, String> {
+    count
+        .parse::<u32>()
+        .map_err(|_| format!("invalid number of dev accounts: {}", count))
+}
+
+/// eg "<0x address>=1000" or "<0x address>=0x3e8"
+fn parse_alloc(alloc: &str) -> Result<(Address, U256), String> {
+    let invalid = || {
+        format!(
+            "invalid genesis alloc: {} (expected <address>=<amount>)",
+            alloc
+        )
+    };
+    let (address, amount) = alloc.split_once('=').ok_or_else(invalid)?;
+    let address = Address::from_str(address.trim()).map_err(|_| invalid())?;
+    let amount = parse_u256(amount.trim()).map_err(|_| invalid())?;
+    Ok((address, amount))
+}
+
+fn parse_lag_warn(messages: &str) -> Result<u32, String> {
+    messages
+        .parse::<u32>()
+        .map_err(|_| format!("invalid consumer lag warning threshold: {}", messages))
+}
+
+fn parse_partition_threshold(block_times: &str) -> Result<u32, String> {
+    block_times
+        .parse::<u32>()
+        .map_err(|_| format!("invalid partition threshold: {}", block_times))
+}
+
+fn parse_mining_interval(secs: &str) -> Result<u64, String> {
+    secs.parse::<u64>()
+        .map_err(|_| format!("invalid mining interval: {}", secs))
+}
+
+fn parse_gas_limit(gas_limit: &str) -> Result<u64, String> {
+    gas_limit
+        .parse::<u64>()
+        .map_err(|_| format!("invalid gas limit: {}", gas_limit))
+}
+
+fn parse_reward(reward: &str) -> Result<u64, String> {
+    reward
+        .parse::<u64>()
+        .map_err(|_| format!("invalid mining reward: {}", reward))
+}
+
+fn parse_halving_interval(blocks: &str) -> Result<u64, String> {
+    blocks.parse::<u64>().map_err(|_| {
+        format!(
+            "invalid halving interval: {} (expected a number of blocks)",
+            blocks
+        )
+    })
+}
+
+fn parse_fork_block(block: &str) -> Result<u64, String> {
+    block
+        .parse::<u64>()
+        .map_err(|_| format!("invalid fork block: {} (expected a block number)", block))
+}
+
+fn parse_address(address: &str) -> Result<Address, String> {
+    Address::from_str(address.trim()).map_err(|_| format!("invalid address: {}", address))
+}
+
+fn parse_percent(percent: &str) -> Result<u8, String> {
+    percent
+        .parse::<u8>()
+        .map_err(|_| format!("invalid percentage: {} (expected 0-100)", percent))
+}
+
+fn parse_timeout(ms: &str) -> Result<u64, String> {
+    ms.parse::<u64>()
+        .map_err(|_| format!("invalid timeout: {} (expected milliseconds)", ms))
+}
+
+fn parse_storage_history(blocks: &str) -> Result<usize, String> {
+    blocks.parse::<usize>().map_err(|_| {
+        format!(
+            "invalid storage history: {} (expected a number of blocks)",
+            blocks
+        )
+    })
+}
+
+fn parse_receipt_history(blocks: &str) -> Result<usize, String> {
+    blocks.parse::<usize>().map_err(|_| {
+        format!(
+            "invalid receipt history: {} (expected a number of blocks)",
+            blocks
+        )
+    })
+}
+
+fn parse_storage_cache(tries: &str) -> Result<usize, String> {
+    tries.parse::<usize>().map_err(|_| {
+        format!(
+            "invalid storage cache: {} (expected a number of storage tries)",
+            tries
+        )
+    })
+}
+
+fn parse_slot_duration(ms: &str) -> Result<u64, String> {
+    ms.parse::<u64>()
+        .map_err(|_| format!("invalid slot duration: {} (expected milliseconds)", ms))
+}
+
+fn parse_slots_per_epoch(slots: &str) -> Result<u64, String> {
+    slots
+        .parse::<u64>()
+        .map_err(|_| format!("invalid slots per epoch: {}", slots))
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        _ => Err(format!("invalid boolean: {}", value)),
+    }
+}
+
+fn parse_port(port: &str) -> Result<u16, String> {
+    port.parse::<u16>()
+        .map_err(|_| format!("invalid port: {}", port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        std::iter::once("rs")
+            .chain(args.iter().copied())
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let mut config = NodeConfig::default();
+        config.apply_args(&to_args(&[])).unwrap();
+        assert_eq!(config.bind_addr(), "localhost:8080");
+        assert!(config.bootnodes.is_empty());
+        assert!(config.mining);
+    }
+
+    #[test]
+    fn test_args() {
+        let mut config = NodeConfig::default();
+        config
+            .apply_args(&to_args(&[
+                "--host",
+                "0.0.0.0",
+                "--port",
+                "9000",
+                "--bootnode",
+                "http://10.0.0.1:8080",
+                "--datadir",
+                "/tmp/node1",
+                "--auth-token",
+                "secret",
+                "--key-seed",
+                "lesson-1",
+            ]))
+            .unwrap();
+        assert_eq!(config.bind_addr(), "0.0.0.0:9000");
+        assert_eq!(config.bootnodes, vec!["http://10.0.0.1:8080"]);
+        assert_eq!(config.datadir, Some(PathBuf::from("/tmp/node1")));
+        assert_eq!(config.auth_token, Some("secret".into()));
+        assert_eq!(config.key_seed, Some("lesson-1".into()));
+    }
+
+    #[test]
+    fn test_treasury() {
+        let address = crate::account::gen_address();
+        let mut config = NodeConfig::default();
+        config
+            .apply_args(&to_args(&[
+                "--treasury",
+                &address.to_string(),
+                "--treasury-reward-percent",
+                "5",
+            ]))
+            .unwrap();
+        assert_eq!(
+            config.treasury(),
+            Some(Treasury {
+                address,
+                fee_percent: DEFAULT_TREASURY_FEE_PERCENT,
+                reward_percent: 5,
+            })
+        );
+        assert!(config
+            .apply_args(&to_args(&["--treasury", "nobody"]))
+            .is_err());
+        assert!(config
+            .apply_args(&to_args(&["--treasury-fee-percent", "-1"]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_keystore_password() {
+        let mut config = NodeConfig::default();
+        assert_eq!(config.keystore_password, None);
+        config
+            .apply_env(|key| match key {
+                "NODE_KEYSTORE_PASSWORD" => Some("from-env".into()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(config.keystore_password, Some("from-env".into()));
+        config
+            .apply_args(&to_args(&["--keystore-password", "from-args"]))
+            .unwrap();
+        assert_eq!(config.keystore_password, Some("from-args".into()));
+    }
+
+    #[test]
+    fn test_mnemonic() {
+        let mut config = NodeConfig::default();
+        config
+            .apply_args(&to_args(&["--dev-accounts", "3"]))
+            .unwrap();
+        assert!(config.validate().is_err());
+        config
+            .apply_env(|key| match key {
+                "NODE_MNEMONIC" => Some("abandon about".into()),
+                _ => None,
+            })
+            .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.dev_accounts, 3);
+        assert!(config
+            .apply_args(&to_args(&["--dev-accounts", "-1"]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_genesis_alloc() {
+        let a = crate::account::Account::new(vec![]).public_account.address;
+        let b = crate::account::Account::new(vec![]).public_account.address;
+        let mut config = NodeConfig::default();
+        config
+            .apply_env(|key| match key {
+                "NODE_GENESIS_ALLOC" => Some(format!("{}=100", a)),
+                "NODE_DEV" => Some("true".into()),
+                _ => None,
+            })
+            .unwrap();
+        config
+            .apply_args(&to_args(&["--genesis-alloc", &format!("{}=250", b)]))
+            .unwrap();
+        assert_eq!(
+            config.genesis_alloc,
+            vec![(a, U256::from(100)), (b, U256::from(250))]
+        );
+        assert!(config.dev);
+        assert!(config
+            .apply_args(&to_args(&["--genesis-alloc", "nobody=100"]))
+            .is_err());
+        assert!(config
+            .apply_args(&to_args(&["--genesis-alloc", &a.to_string()]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_config_file() {
+        let path = std::env::temp_dir().join(format!("node-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"
+            port = 9000
+            chain_id = 42
+            bootnodes = ["http://a:8080", "http://b:8080"]
+            mining = false
+            fast_sync = true
+            max_gas_limit = 500
+            consumer_lag_warn = 50
+            partition_threshold = 5
+            measure_propagation = true
+            exec_timeout_ms = 100
+            storage_history = 128
+            receipt_history = 64
+            storage_cache = 16
+            slot_duration_ms = 2000
+            initial_reward = 100
+            halving_interval = 1000
+            constantinople_block = 100
+            paris_block = 200
+            treasury_reward_percent = 10
+            "#,
+        )
+        .unwrap();
+        let path_str = path.to_str().unwrap().to_string();
+
+        //file < env < args
+        let config = NodeConfig::load_with(
+            &to_args(&["--config", &path_str, "--port", "9001"]),
+            |key| match key {
+                "NODE_CHAIN_ID" => Some("43".into()),
+                "AMQP_ADDR" => Some("amqp://rabbit:5672/%2f".into()),
+                _ => None,
+            },
+        )
+        .unwrap();
+        assert_eq!(config.port, 9001);
+        assert_eq!(config.chain_id, 43);
+        assert_eq!(config.bootnodes, vec!["http://a:8080", "http://b:8080"]);
+        assert!(!config.mining);
+        assert!(config.fast_sync);
+        assert_eq!(config.max_gas_limit, 500);
+        assert_eq!(config.consumer_lag_warn, 50);
+        assert_eq!(config.partition_threshold, 5);
+        assert!(config.measure_propagation);
+        assert_eq!(config.exec_timeout_ms, 100);
+        assert_eq!(
+            config.chain_rules().exec_timeout,
+            Duration::from_millis(100)
+        );
+        assert_eq!(config.storage_history, Some(128));
+        assert_eq!(config.receipt_history, Some(64));
+        assert_eq!(config.storage_cache, 16);
+        assert_eq!(config.slot_duration_ms, 2000);
+        assert_eq!(config.slots_per_epoch, DEFAULT_SLOTS_PER_EPOCH);
+        assert_eq!(
+            config.reward_schedule,
+            RewardSchedule {
+                initial: 100,
+                halving_interval: Some(1000)
+            }
+        );
+        assert_eq!(
+            config.fork_schedule,
+            ForkSchedule {
+                constantinople: Some(100),
+                paris: Some(200)
+            }
+        );
+        assert_eq!(config.amqp_addr, "amqp://rabbit:5672/%2f");
+        //no address, no treasury - whatever the percentages say
+        assert_eq!(config.treasury_reward_percent, 10);
+        assert_eq!(config.treasury(), None);
+
+        //same file through the env var
+        let config = NodeConfig::load_with(&to_args(&[]), |key| match key {
+            "NODE_CONFIG" => Some(path_str.clone()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(config.port, 9000);
+
+        //picked up from the datadir when there's no --config
+        let datadir = std::env::temp_dir().join(format!("node-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&datadir).unwrap();
+        std::fs::copy(&path, datadir.join("config.toml")).unwrap();
+        let datadir_str = datadir.to_str().unwrap().to_string();
+        let config =
+            NodeConfig::load_with(&to_args(&["--datadir", &datadir_str]), |_| None).unwrap();
+        assert_eq!(config.port, 9000);
+        std::fs::remove_dir_all(datadir).unwrap();
+
+        std::fs::write(&path, "max_gas_limit = 0").unwrap();
+        assert!(NodeConfig::load_with(&to_args(&["--config", &path_str]), |_| None).is_err());
+        std::fs::write(&path, "consumer_lag_warn = 0").unwrap();
+        assert!(NodeConfig::load_with(&to_args(&["--config", &path_str]), |_| None).is_err());
+        std::fs::write(&path, "partition_threshold = 0").unwrap();
+        assert!(NodeConfig::load_with(&to_args(&["--config", &path_str]), |_| None).is_err());
+        std::fs::write(&path, "halving_interval = 0").unwrap();
+        assert!(NodeConfig::load_with(&to_args(&["--config", &path_str]), |_| None).is_err());
+        std::fs::write(&path, "treasury_fee_percent = 101").unwrap();
+        assert!(NodeConfig::load_with(&to_args(&["--config", &path_str]), |_| None).is_err());
+        std::fs::write(&path, "exec_timeout_ms = 0").unwrap();
+        assert!(NodeConfig::load_with(&to_args(&["--config", &path_str]), |_| None).is_err());
+        std::fs::write(&path, "storage_cache = 0").unwrap();
+        assert!(NodeConfig::load_with(&to_args(&["--config", &path_str]), |_| None).is_err());
+        std::fs::write(&path, "slots_per_epoch = 0").unwrap();
+        assert!(NodeConfig::load_with(&to_args(&["--config", &path_str]), |_| None).is_err());
+        std::fs::write(&path, "bootnodes = [\"localhost:8080\"]").unwrap();
+        assert!(NodeConfig::load_with(&to_args(&["--config", &path_str]), |_| None).is_err());
+        std::fs::remove_file(path).unwrap();
+        assert!(NodeConfig::load_with(&to_args(&["--config", &path_str]), |_| None).is_err());
+        assert!(NodeConfig::load_with(&to_args(&["--config"]), |_| None).is_err());
+    }
+
+    #[test]
+    fn test_legacy_peer_flag() {
+        let mut config = NodeConfig::default();
+        config.apply_args(&to_args(&["-p"])).unwrap();
+        assert_eq!(config.port, 8081);
+        assert_eq!(config.bootnodes, vec!["http://localhost:8080"]);
+        assert_eq!(config.gossip_port, 30304);
+        assert_eq!(config.gossip_peers, vec!["localhost:30303"]);
+    }
+
+    #[test]
+    fn test_args_override_env() {
+        let mut config = NodeConfig::default();
+        config
+            .apply_env(|key| match key {
+                "NODE_PORT" => Some("7000".into()),
+                "NODE_BOOTNODE" => Some("http://env-node:8080".into()),
+                _ => None,
+            })
+            .unwrap();
+        config.apply_args(&to_args(&["--port", "7001"])).unwrap();
+        assert_eq!(config.port, 7001);
+        assert_eq!(config.bootnodes, vec!["http://env-node:8080"]);
+    }
+
+    #[test]
+    fn test_cors() {
+        let mut config = NodeConfig::default();
+        config
+            .apply_env(|key| match key {
+                "NODE_CORS_ORIGINS" => Some("http://a.com, http://b.com".into()),
+                _ => None,
+            })
+            .unwrap();
+        config
+            .apply_args(&to_args(&[
+                "--cors-origin",
+                "http://c.com",
+                "--cors-methods",
+                "get,options",
+            ]))
+            .unwrap();
+        assert_eq!(
+            config.cors_origins,
+            vec!["http://a.com", "http://b.com", "http://c.com"]
+        );
+        assert_eq!(config.cors_methods, vec!["GET", "OPTIONS"]);
+    }
+
+    #[test]
+    fn test_tls_needs_cert_and_key() {
+        let mut config = NodeConfig::default();
+        config
+            .apply_args(&to_args(&["--tls-cert", "cert.pem"]))
+            .unwrap();
+        assert!(config.validate().is_err());
+        config
+            .apply_args(&to_args(&["--tls-key", "key.pem"]))
+            .unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_gossip_peers() {
+        let mut config = NodeConfig::default();
+        config
+            .apply_args(&to_args(&[
+                "--gossip-port",
+                "30400",
+                "--gossip-peer",
+                "10.0.0.1:30303",
+                "--gossip-peer",
+                "node2:30303",
+            ]))
+            .unwrap();
+        assert_eq!(config.gossip_port, 30400);
+        assert_eq!(config.gossip_peers, vec!["10.0.0.1:30303", "node2:30303"]);
+        assert!(config.validate().is_ok());
+        for peer in &["10.0.0.1", ":30303", "http://node2:30303", "node2:port"] {
+            config.gossip_peers = vec![peer.to_string()];
+            assert!(config.validate().is_err(), "{}", peer);
+        }
+    }
+
+    #[test]
+    fn test_static_nodes() {
+        let datadir = std::env::temp_dir().join(format!("node-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&datadir).unwrap();
+        std::fs::write(
+            datadir.join("static-nodes.json"),
+            r#"["http://node1:8080", "node1:30303", "http://node2:8080"]"#,
+        )
+        .unwrap();
+        let args = to_args(&[
+            "--datadir",
+            datadir.to_str().unwrap(),
+            "--bootnode",
+            "http://node2:8080",
+        ]);
+        let config = NodeConfig::load_with(&args, |_| None).unwrap();
+        //the flag's first, and only once
+        assert_eq!(
+            config.bootnodes,
+            vec!["http://node2:8080", "http://node1:8080"]
+        );
+        assert_eq!(config.gossip_peers, vec!["node1:30303"]);
+
+        let mut config = NodeConfig::default();
+        assert!(config.apply_static_nodes(&["node1".into()]).is_err());
+        std::fs::remove_dir_all(datadir).unwrap();
+    }
+
+    #[test]
+    fn test_paris_needs_constantinople_first() {
+        let mut config = NodeConfig::default();
+        config
+            .apply_args(&to_args(&["--paris-block", "10"]))
+            .unwrap();
+        assert!(config.validate().is_err());
+        config
+            .apply_args(&to_args(&["--constantinople-block", "20"]))
+            .unwrap();
+        assert!(config.validate().is_err());
+        config
+            .apply_args(&to_args(&["--constantinople-block", "10"]))
+            .unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_auto_mine() {
+        let mut config = NodeConfig::default();
+        config
+            .apply_env(|key| match key {
+                "NODE_AUTO_MINE" => Some("0".into()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(config.auto_mine, Some(0));
+        config.apply_args(&to_args(&["--auto-mine", "5"])).unwrap();
+        assert_eq!(config.auto_mine, Some(5));
+        assert!(config.validate().is_ok());
+        assert!(config
+            .apply_args(&to_args(&["--auto-mine", "soon"]))
+            .is_err());
+        config.apply_args(&to_args(&["--no-mining"])).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_enabled_features() {
+        let mut config = NodeConfig::default();
+        assert!(config.enabled_features().is_empty());
+        config
+            .apply_args(&to_args(&["--auth-token", "x", "--cors-origin", "*"]))
+            .unwrap();
+        assert_eq!(config.enabled_features(), vec!["auth", "cors"]);
+    }
+
+    #[test]
+    fn test_bad_input() {
+        let mut config = NodeConfig::default();
+        assert!(config.apply_args(&to_args(&["--port", "abc"])).is_err());
+        assert!(config.apply_args(&to_args(&["--port"])).is_err());
+        assert!(config.apply_args(&to_args(&["--what"])).is_err());
+        assert!(config.apply_args(&to_args(&["--chain-id", "-1"])).is_err());
+        assert!(config
+            .apply_args(&to_args(&["--max-gas-limit", "lots"]))
+            .is_err());
+        assert!(config
+            .apply_args(&to_args(&["--partition-threshold", "-1"]))
+            .is_err());
+        config
+            .apply_args(&to_args(&["--amqp-addr", "localhost:5672"]))
+            .unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_logging() {
+        let mut config = NodeConfig::default();
+        assert_eq!(config.log_format, LogFormat::Pretty);
+        config
+            .apply_env(|key| match key {
+                "NODE_LOG_FORMAT" => Some("json".into()),
+                _ => None,
+            })
+            .unwrap();
+        config
+            .apply_args(&to_args(&["--log-level", "rs=debug"]))
+            .unwrap();
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(config.log_level, "rs=debug");
+        assert!(config
+            .apply_args(&to_args(&["--log-format", "xml"]))
+            .is_err());
+    }
+}
@@ -0,0 +1,328 @@
+//`rs devnet` - a whole network in one process, for demos and poking at consensus without rabbitmq or a terminal per node.
+// Nodes are plain GlobalStates and "the network" is handing a serialized block / tx to every other node's
+// process_block() / process_transaction() - the same code the rabbitmq consumers run
+
+use crate::api::pubsub::{process_block, process_transaction};
+use crate::blockchain::block::Block;
+use crate::config::{next_value, NodeConfig};
+use crate::events::{Event, MinerStatus};
+use crate::store::codec;
+use crate::telemetry::init_tracing;
+use crate::transaction::tx::Transaction;
+use crate::util::bigint::U256;
+use crate::util::{prep_state, GlobalState};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+pub const USAGE: &str = "usage:
+  rs devnet [--nodes <n>] [--blocks <n, 0 = until stopped>] [--block-time <ms>] [--log-level <level>]";
+
+//sent by the previous round's miner to another node's miner every round, so blocks carry more than the reward
+pub const TRANSFER_VALUE: u64 = 5;
+const TRANSFER_GAS_LIMIT: u64 = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DevnetConfig {
+    pub nodes: usize,
+    //0 = keep going until the process is stopped
+    pub blocks: u64,
+    pub block_time: Duration,
+    //node logs go to stderr, so the default keeps them from drowning out the table
+    pub log_level: String,
+}
+
+impl Default for DevnetConfig {
+    fn default() -> Self {
+        Self {
+            nodes: 3,
+            blocks: 10,
+            block_time: Duration::from_millis(1000),
+            log_level: "warn".into(),
+        }
+    }
+}
+
+impl DevnetConfig {
+    /// everything after "devnet"
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut config = DevnetConfig::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let value = next_value(arg, args.next());
+            match arg.as_str() {
+                "--nodes" => config.nodes = parse_number(arg, &value?)? as usize,
+                "--blocks" => config.blocks = parse_number(arg, &value?)?,
+                "--block-time" => {
+                    config.block_time = Duration::from_millis(parse_number(arg, &value?)?)
+                }
+                "--log-level" => config.log_level = value?,
+                _ => return Err(format!("unknown flag: {}\n{}", arg, USAGE)),
+            }
+        }
+        if config.nodes == 0 {
+            return Err("--nodes has to be at least 1".into());
+        }
+        Ok(config)
+    }
+}
+
+fn parse_number(flag: &str, value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} takes a number, got {}", flag, value))
+}
+
+/// one row of the summary table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Head {
+    pub node: usize,
+    pub miner: String,
+    pub height: usize,
+    pub hash: String,
+    pub difficulty: i64,
+    pub balance: U256,
+    pub queued: usize,
+}
+
+pub struct Devnet {
+    pub nodes: Vec<Arc<GlobalState>>,
+}
+
+impl Devnet {
+    /// node 0's genesis block becomes everyone's, the same way a node started with --bootnode syncs its chain
+    pub fn start(node_count: usize) -> Result<Self, String> {
+        let nodes: Vec<Arc<GlobalState>> =
+            (0..node_count).map(|_| Arc::new(prep_state())).collect();
+        let genesis = (*nodes[0].blockchain.read().unwrap().chain[0]).clone();
+        for node in nodes.iter().skip(1) {
+            node.replace_chain(vec![genesis.clone()])
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(Self { nodes })
+    }
+
+    /// mines whatever's in the node's tx queue, adds it to its chain and relays it to everyone else.
+    /// Same steps as /mine, minus rabbitmq
+    pub fn mine(&self, index: usize) -> Result<Block, String> {
+        let node = &self.nodes[index];
+        let tx_series = node.tx_queue.lock().unwrap().get_tx_series();
+        let (last_block, block) = node
+            .blockchain
+            .read()
+            .unwrap()
+            .candidate(&node.miner_account(), tx_series)
+            .map_err(|e| format!("node {}'s block fails to run: {}", index, e))?;
+        node.events.publish(Event::MinerStatus(MinerStatus::Mining {
+            block_number: last_block.block_headers.truncated_block_headers.number + 1,
+        }));
+        let block = block.seal(&last_block, &node.chain_rules());
+        node.events.publish(Event::MinerStatus(MinerStatus::Idle));
+        node.import_block(block.clone())
+            .map_err(|e| format!("node {} rejected its own block: {}", index, e))?;
+        node.tx_queue
+            .lock()
+            .unwrap()
+            .clear_block_tx(&block.tx_series);
+        node.events.publish(Event::NewBlock {
+            block: Arc::new(block.clone()),
+            from_peer: false,
+        });
+
+        let encoded_block = codec::encode_hex(&block);
+        for peer in self.peers(index) {
+            process_block(encoded_block.clone(), peer.clone()).map_err(|e| e.to_string())?;
+        }
+        Ok(block)
+    }
+
+    /// TRANSFER_VALUE from the node's miner to the first other miner that already has an account, broadcast to
+    /// every node's tx queue. Returns the tx hash, or None if there's no one to send to or nothing to send
+    pub fn send_transfer(&self, index: usize) -> Result<Option<String>, String> {
+        let node = &self.nodes[index];
+        let (recipient, chain_id) = {
+            let blockchain = node.blockchain.read().unwrap();
+            let balance = blockchain
+                .state
+                .get_account_or_empty(node.miner_address)
+                .balance;
+            if balance < U256::from(TRANSFER_VALUE + TRANSFER_GAS_LIMIT) {
+                return Ok(None);
+            }
+            //sending to a miner whose account creation tx isn't mined yet would get its balance reset when it is
+            let recipient = self
+                .peers(index)
+                .map(|peer| peer.miner_address)
+                .find(|address| blockchain.state.find_account(*address).is_some());
+            (recipient, blockchain.chain_id())
+        };
+        let recipient = match recipient {
+            Some(recipient) => recipient,
+            None => return Ok(None),
+        };
+
+        let miner = node
+            .miner_account()
+            .with_nonce(node.next_nonce(&node.miner_address));
+        let tx = Transaction::create_transaction(
+            Some(miner),
+            Some(recipient),
+            TRANSFER_VALUE,
+            None,
+            TRANSFER_GAS_LIMIT,
+            chain_id,
+        );
+        let encoded_tx = codec::encode_hex(&tx);
+        for node in self.nodes.iter() {
+            process_transaction(encoded_tx.clone(), node.clone()).map_err(|e| e.to_string())?;
+        }
+        Ok(Some(tx.hash()))
+    }
+
+    pub fn heads(&self) -> Vec<Head> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| {
+                let blockchain = node.blockchain.read().unwrap();
+                let head = blockchain.chain.last().unwrap();
+                Head {
+                    node: i,
+                    miner: node.miner_address.to_string(),
+                    height: head.block_headers.truncated_block_headers.number,
+                    hash: head.hash(),
+                    difficulty: head.block_headers.truncated_block_headers.difficulty,
+                    balance: blockchain
+                        .state
+                        .get_account_or_empty(node.miner_address)
+                        .balance,
+                    queued: node.tx_queue.lock().unwrap().get_tx_series().len(),
+                }
+            })
+            .collect()
+    }
+
+    /// every node has the same head
+    pub fn in_sync(&self) -> bool {
+        let heads = self.heads();
+        heads.iter().all(|head| head.hash == heads[0].hash)
+    }
+
+    fn peers(&self, index: usize) -> impl Iterator<Item = &Arc<GlobalState>> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(move |(i, _)| *i != index)
+            .map(|(_, node)| node)
+    }
+}
+
+/// addresses and hashes are shortened - enough to tell nodes and forks apart
+pub fn format_heads(heads: &[Head]) -> String {
+    let mut lines = vec![format!(
+        "{:<6}{:<14}{:<8}{:<16}{:<12}{:<10}{}",
+        "node", "miner", "height", "head", "difficulty", "balance", "queued"
+    )];
+    for head in heads {
+        lines.push(format!(
+            "{:<6}{:<14}{:<8}{:<16}{:<12}{:<10}{}",
+            head.node,
+            format!("{}..", &head.miner[..10]),
+            head.height,
+            format!("{}..", &head.hash[..12]),
+            head.difficulty,
+            head.balance,
+            head.queued
+        ));
+    }
+    lines.join("\n")
+}
+
+/// `rs devnet ...` - takes everything after "devnet". Nodes take turns mining, one block every --block-time,
+/// and the heads get printed after every block
+pub fn run_devnet_command(args: &[String]) -> Result<(), String> {
+    let config = DevnetConfig::parse(args)?;
+    init_tracing(&NodeConfig {
+        log_level: config.log_level.clone(),
+        ..NodeConfig::default()
+    })?;
+
+    let devnet = Devnet::start(config.nodes)?;
+    println!("started {} nodes\n", config.nodes);
+    let mut round = 0;
+    while config.blocks == 0 || round < config.blocks {
+        let miner = round as usize % config.nodes;
+        //last round's miner has its reward by now
+        let previous = (miner + config.nodes - 1) % config.nodes;
+        if let Some(tx_hash) = devnet.send_transfer(previous)? {
+            println!(
+                "node {} sent {} (tx {}..)",
+                previous,
+                TRANSFER_VALUE,
+                &tx_hash[..12]
+            );
+        }
+        let block = devnet.mine(miner)?;
+        println!(
+            "node {} mined block {}\n{}",
+            miner,
+            block.block_headers.truncated_block_headers.number,
+            format_heads(&devnet.heads())
+        );
+        if !devnet.in_sync() {
+            println!("(!) nodes disagree about the head");
+        }
+        println!();
+        round += 1;
+        thread::sleep(config.block_time);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::tx::MINING_REWARD;
+
+    #[test]
+    fn test_parse_config() {
+        let args: Vec<String> = vec!["--nodes", "5", "--block-time", "200"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let config = DevnetConfig::parse(&args).unwrap();
+        assert_eq!(config.nodes, 5);
+        assert_eq!(config.block_time, Duration::from_millis(200));
+        assert_eq!(config.blocks, DevnetConfig::default().blocks);
+
+        assert!(DevnetConfig::parse(&["--nodes".into(), "0".into()]).is_err());
+        assert!(DevnetConfig::parse(&["--nodes".into()]).is_err());
+        assert!(DevnetConfig::parse(&["--peers".into(), "2".into()]).is_err());
+    }
+
+    #[test]
+    fn test_nodes_agree_on_the_chain() {
+        let devnet = Devnet::start(3).unwrap();
+        assert!(devnet.in_sync());
+        for round in 0..3 {
+            devnet.send_transfer((round + 2) % 3).unwrap();
+            devnet.mine(round).unwrap();
+        }
+        assert!(devnet.in_sync());
+
+        let heads = devnet.heads();
+        assert!(heads.iter().all(|head| head.height == 3));
+        assert!(heads.iter().all(|head| head.queued == 0));
+        //one reward each. Node 0 had nothing to send yet and node 1 only had node 0 to send to
+        let balances: Vec<U256> = heads.iter().map(|head| head.balance).collect();
+        assert_eq!(
+            balances,
+            vec![
+                U256::from(MINING_REWARD + TRANSFER_VALUE),
+                U256::from(MINING_REWARD - TRANSFER_VALUE),
+                U256::from(MINING_REWARD)
+            ]
+        );
+        assert_eq!(format_heads(&heads).lines().count(), 4);
+    }
+}
@@ -0,0 +1,156 @@
+//one error type per layer. Anything that comes in from outside the node (api requests, blocks and tx from peers,
+// smart contract code) surfaces as one of these instead of a panic. The api still answers with plain text -
+// every variant's Display is the message the client sees
+
+use crate::util::bigint::U256;
+use thiserror::Error;
+
+/// why a block or a whole chain got rejected
+#[derive(Debug, Error, PartialEq)]
+pub enum ChainError {
+    #[error("invalid block: {0}")]
+    InvalidBlock(&'static str),
+    #[error("it's for chain id {theirs}, ours is {ours}")]
+    ChainIdMismatch { ours: u64, theirs: u64 },
+    #[error("the chain is empty")]
+    EmptyChain,
+    #[error("invalid snapshot: {0}")]
+    InvalidSnapshot(&'static str),
+    //the consensus engine's fork choice kept ours, carries its name
+    #[error("{0} fork choice prefers our chain")]
+    ForkChoice(&'static str),
+    #[error("invalid tx in block: {0}")]
+    Tx(#[from] TxError),
+    #[error(transparent)]
+    Store(#[from] StoreError),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TxError {
+    #[error("the tx has no {0}")]
+    MissingField(&'static str),
+    #[error("signature missing")]
+    MissingSignature,
+    #[error("signature invalid")]
+    InvalidSignature,
+    //carries the reason from MultisigConfig::check_signatures()
+    #[error("{0}")]
+    Multisig(String),
+    #[error("exceeded balance")]
+    ExceededBalance,
+    //see Transaction::check_fee_payer()
+    #[error("fee payer {0}")]
+    FeePayer(&'static str),
+    //see Transaction::check_nonce()
+    #[error("nonce {nonce} has been used already, the sender is at {next}")]
+    NonceTooLow { nonce: u64, next: u64 },
+    //the tx queue holds one tx per sender and nonce
+    #[error("a different tx from the same sender with nonce {0} is already queued")]
+    NonceQueued(u64),
+    //a transfer that can't do anything, see Transaction::check_recipient()
+    #[error("pointless tx: {0}")]
+    Pointless(&'static str),
+    #[error("{0}")]
+    Overflow(String),
+    //the payload alone costs more than the gas limit, see fee::payload_gas()
+    #[error("insufficient gas limit for the tx's payload: provided {provided}, needed {needed}")]
+    PayloadGas { provided: U256, needed: u64 },
+    #[error("tx is {0} bytes, over the {1} byte limit")]
+    TooLarge(usize, usize),
+    #[error("smart contract execution failed: {0}")]
+    Exec(#[from] ExecError),
+    #[error(transparent)]
+    Store(#[from] StoreError),
+}
+
+/// smart contract code is user input, so everything the interpreter can trip over ends up here
+#[derive(Debug, Error, PartialEq)]
+pub enum ExecError {
+    #[error("stack underflow at instruction {0}")]
+    StackUnderflow(usize),
+    #[error("expected a value at instruction {0}")]
+    NotAValue(usize),
+    #[error("push instruction cannot be last")]
+    PushAtEnd,
+    #[error("trying to jump to non-existent destination, {0}")]
+    InvalidJump(i32),
+    #[error("execution limit of {0} exceeded")]
+    ExecutionLimit(u64),
+    #[error("execution took longer than the {0}ms time limit")]
+    Timeout(u64),
+    #[error("arithmetic overflow at instruction {0}")]
+    Overflow(usize),
+    #[error("division by zero at instruction {0}")]
+    DivisionByZero(usize),
+    #[error("storage slot {0} doesn't hold a number")]
+    BadSlot(i32),
+    #[error("opcode at instruction {0} isn't active until a later fork")]
+    InactiveOpcode(usize),
+    #[error("memory offset {0} is negative")]
+    BadOffset(i32),
+    #[error("memory limit of {0} bytes exceeded")]
+    MemoryLimit(usize),
+    #[error("out of gas, the code only had {0}")]
+    OutOfGas(u64),
+    //the code's own REVERT. gas_used is the code's, up to the REVERT
+    #[error("reverted with reason {reason} after {gas_used} gas")]
+    Reverted { reason: i32, gas_used: u64 },
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum StoreError {
+    #[error("account {0} doesn't exist yet")]
+    AccountNotFound(String),
+    #[error("corrupt entry for {key}: {reason}")]
+    Corrupt { key: String, reason: String },
+    #[error("block {block} has been pruned, storage history only goes back to block {oldest}")]
+    Pruned { block: usize, oldest: usize },
+    //io::Error isn't PartialEq, so the message (incl the path) is all we keep
+    #[error("{0}")]
+    Io(String),
+}
+
+/// a block or tx that doesn't decode, see store::codec
+#[derive(Debug, Error, PartialEq)]
+pub enum CodecError {
+    #[error("unsupported encoding version {0}")]
+    UnsupportedVersion(u8),
+    #[error("malformed encoding: {0}")]
+    Malformed(String),
+    #[error("field {0} is missing")]
+    MissingField(u64),
+}
+
+/// talking to other nodes, over gossip / rabbitmq and http
+#[derive(Debug, Error)]
+pub enum NetError {
+    #[cfg(feature = "rabbitmq")]
+    #[error("amqp error: {0}")]
+    Amqp(#[from] lapin::Error),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("malformed message: {0}")]
+    Decode(String),
+    #[error("rejected tx: {0}")]
+    Tx(#[from] TxError),
+    //failed the same checks as a tx submitted to us, see server::validate_pooled_tx()
+    #[error("rejected tx: {0}")]
+    InvalidTx(String),
+    #[error("failed to replace chain: {0}")]
+    Chain(#[from] ChainError),
+}
+
+/// why a block couldn't be mined, whether through /mine or by the auto miner
+#[derive(Debug, Error)]
+pub enum MineError {
+    #[error("the node is still syncing")]
+    Syncing,
+    #[error("the block fails to run: {0}")]
+    Template(TxError),
+    #[error("failed to broadcast block: {0}")]
+    Broadcast(NetError),
+    #[error("failed to mine block: {0}")]
+    Rejected(ChainError),
+}
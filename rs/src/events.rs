@@ -0,0 +1,169 @@
+//in-process event bus. Whoever changes the chain or the tx queue publishes what happened, and anything that wants
+// to react (logging, and later metrics / subscriptions) subscribes, instead of each of them being called directly
+// from the code paths that hold the GlobalState locks
+
+use crate::blockchain::block::Block;
+use crate::transaction::tx::Transaction;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// how many events a subscriber can fall behind before it starts missing the oldest ones
+pub const EVENT_BUS_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// a block made it onto our chain, either mined here or received from a peer
+    NewBlock {
+        block: Arc<Block>,
+        from_peer: bool,
+    },
+    /// a tx made it into the tx queue. Boxed, it's by far the biggest
+    NewTx(Box<Transaction>),
+    /// our chain got swapped for one that doesn't just extend it, eg when syncing from a bootnode.
+    /// depth = how many of our blocks got dropped
+    Reorg {
+        old_head: String,
+        new_head: String,
+        depth: usize,
+    },
+    MinerStatus(MinerStatus),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MinerStatus {
+    //proof of work for this block number is underway
+    Mining { block_number: usize },
+    Idle,
+}
+
+impl Event {
+    /// what replacing the `old` chain with `new` looks like to subscribers. None if new just extends old
+    pub fn reorg(old: &[Arc<Block>], new: &[Arc<Block>]) -> Option<Event> {
+        let common = old
+            .iter()
+            .zip(new.iter())
+            .take_while(|(ours, theirs)| ours.hash() == theirs.hash())
+            .count();
+        let depth = old.len() - common;
+        if depth == 0 {
+            return None;
+        }
+        Some(Event::Reorg {
+            old_head: old.last()?.hash(),
+            new_head: new.last()?.hash(),
+            depth,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(EVENT_BUS_CAPACITY)
+    }
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// never blocks and never fails - an event nobody is subscribed to is simply dropped
+    pub fn publish(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+
+    /// only sees events published after this call
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+/// logs every event at debug level (turn on with --log-level rs::events=debug), until the bus goes away
+pub async fn log_events(mut events: broadcast::Receiver<Event>) {
+    loop {
+        match events.recv().await {
+            Ok(Event::NewBlock { block, from_peer }) => tracing::debug!(
+                number = block.block_headers.truncated_block_headers.number,
+                hash = %block.hash(),
+                from_peer,
+                "new block"
+            ),
+            Ok(Event::NewTx(tx)) => tracing::debug!(tx_hash = %tx.hash(), "new tx"),
+            Ok(Event::Reorg {
+                old_head,
+                new_head,
+                depth,
+            }) => tracing::debug!(old_head = %old_head, new_head = %new_head, depth, "reorg"),
+            Ok(Event::MinerStatus(status)) => tracing::debug!(status = ?status, "miner status"),
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                tracing::warn!(missed, "event log fell behind")
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::gen_address;
+    use crate::config::chain::ChainRules;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::util::clock::SystemClock;
+
+    #[test]
+    fn test_publish_without_subscribers() {
+        let bus = EventBus::default();
+        bus.publish(Event::MinerStatus(MinerStatus::Idle));
+
+        //late subscribers don't get replays
+        let mut events = bus.subscribe();
+        assert!(events.try_recv().is_err());
+        bus.publish(Event::MinerStatus(MinerStatus::Mining { block_number: 1 }));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(Event::MinerStatus(MinerStatus::Mining { block_number: 1 }))
+        ));
+    }
+
+    #[test]
+    fn test_reorg_depth() {
+        let genesis = Arc::new(Block::genesis(&SystemClock, DEFAULT_CHAIN_ID));
+        let mine = |last: &Arc<Block>| {
+            Arc::new(Block::mine_block(
+                last,
+                gen_address(),
+                vec![],
+                "",
+                &SystemClock,
+                &ChainRules::default(),
+            ))
+        };
+        let ours = vec![genesis.clone(), mine(&genesis)];
+        let theirs_1 = mine(&genesis);
+        let theirs = vec![genesis.clone(), theirs_1.clone(), mine(&theirs_1)];
+
+        //extending the chain isn't a reorg
+        let extended = vec![ours[0].clone(), ours[1].clone(), mine(&ours[1])];
+        assert!(Event::reorg(&ours, &extended).is_none());
+
+        match Event::reorg(&ours, &theirs) {
+            Some(Event::Reorg {
+                old_head,
+                new_head,
+                depth,
+            }) => {
+                assert_eq!(old_head, ours[1].hash());
+                assert_eq!(new_head, theirs[2].hash());
+                assert_eq!(depth, 1);
+            }
+            other => panic!("expected a reorg, got {:?}", other),
+        }
+    }
+}
@@ -1,17 +1,36 @@
-#![allow(illegal_floating_point_literal_pattern)]
-
+use crate::account::address::Address;
+use crate::blockchain::fork::Fork;
+use crate::config::DEFAULT_EXEC_TIMEOUT_MS;
+use crate::error::{CodecError, ExecError};
+use crate::interpreter::profile::GasProfile;
+use crate::store::codec::{Decode, Encode};
+use crate::store::rlp::Rlp;
 use crate::store::trie::Trie;
 
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::convert::TryFrom;
 
 use std::ops;
+use std::time::{Duration, Instant};
+
+pub mod profile;
+pub mod token;
+#[cfg(feature = "ethtests")]
+pub mod vmtests;
 
 // ----------------------------------------------------------------------------- defn
 
 const EXECUTION_LIMIT: u64 = 10000;
+/// memory is handed out (and paid for) in words this big - one stack value's worth
+pub const MEMORY_WORD_BYTES: usize = 4;
+/// the quadratic part of memory gas: words² / this, on top of GasSchedule::memory per word. Same as ethereum's
+pub const MEMORY_QUAD_DIVISOR: u64 = 512;
+/// the most memory one run_code() call can use. Growing it is paid for before it happens, but this caps the allocation
+/// on its own, whatever gas limit the code runs with
+pub const MEMORY_LIMIT_BYTES: usize = 64 * 1024;
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum OPCODE {
     STOP,
     PUSH,
@@ -29,19 +48,84 @@ pub enum OPCODE {
     JUMPI,
     STORE,
     LOAD,
+    //from constantinople on, see Fork::has_opcode()
+    SHL,
+    SHR,
+    //from paris on, pushes BlockEnv::randao
+    PREVRANDAO,
+    //scratch memory that only lasts for the one run, see Interpreter::memory
+    MSTORE,
+    MLOAD,
+    MSIZE,
+    //stops the run as a failure, with the value on top of the stack as the reason. Storage goes back to how it was,
+    // but the gas used so far is still paid for - see ExecError::Reverted
+    REVERT,
+    //what the tx calling the code brings, see Interpreter::with_call(). CALLER pushes the sender's Address::short(),
+    // CALLDATALOAD pops an index and pushes the call data value at it - 0 past the end, same as in ethereum
+    CALLER,
+    CALLDATALOAD,
 }
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize, Hash)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct EVMRetVal {
     pub ret_val: OPCODE,
     pub gas_used: u64,
 }
 
+/// what each kind of instruction costs. Changes between forks, see Fork::gas_schedule()
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GasSchedule {
+    pub arithmetic: u64,
+    pub jump: u64,
+    pub store: u64,
+    /// a STORE of the value the slot already holds
+    pub unchanged_store: u64,
+    pub load: u64,
+    /// an MSTORE or MLOAD, and every word of memory on top of that - see memory_gas()
+    pub memory: u64,
+}
+
+/// what contract code can see of the block it runs in, and how long it gets to run there
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockEnv {
+    pub fork: Fork,
+    /// the parent block's randao mix, cut down to something that fits on the stack (see Block::next_env()).
+    /// Known before the block is mined, so a simulation sees the same value the block will
+    pub randao: i32,
+    /// the chain's exec timeout (ChainRules::exec_timeout)
+    pub timeout: Duration,
+}
+
+impl Default for BlockEnv {
+    fn default() -> Self {
+        Self {
+            fork: Fork::Frontier,
+            randao: 0,
+            timeout: Duration::from_millis(DEFAULT_EXEC_TIMEOUT_MS),
+        }
+    }
+}
+
 pub struct Interpreter {
     pub program_counter: usize,
     pub stack: Vec<OPCODE>,
     pub code: Vec<OPCODE>,
     pub execution_count: u64,
+    //the run stops with ExecError::OutOfGas as soon as it's used more. Unlimited unless built with_gas_limit()
+    pub gas_limit: u64,
+    //the fork in it decides which opcodes are available and what they cost, and its timeout is the wall-clock budget
+    // for one run_code() call, on top of the gas and EXECUTION_LIMIT
+    pub env: BlockEnv,
+    //who's calling and with what, see with_call(). 0 and nothing outside a tx, eg in eth_call
+    pub caller: i32,
+    pub call_data: Vec<i32>,
+    //gas per opcode, only kept when built with_profile()
+    pub profile: Option<GasProfile>,
+    //byte addressed, starts out empty and grows a word at a time as MSTORE/MLOAD reach past its end. Values go in as
+    // 4 bytes big endian. Unlike storage it's gone once the code is done running
+    pub memory: Vec<u8>,
+    //what each STORE overwrote ("" for a slot that was empty), in order, so run_code() can undo them
+    storage_journal: Vec<(String, String)>,
 }
 
 // ----------------------------------------------------------------------------- impls
@@ -112,8 +196,88 @@ impl Ord for OPCODE {
     }
 }
 
+//an instruction is just its number, in the order they're declared in. A value goes with VAL's number, as [2, value]
+impl Encode for OPCODE {
+    fn to_rlp(&self) -> Rlp {
+        let number: u64 = match self {
+            OPCODE::VAL(value) => return Rlp::List(vec![2u64.to_rlp(), value.to_rlp()]),
+            OPCODE::STOP => 0,
+            OPCODE::PUSH => 1,
+            OPCODE::ADD => 3,
+            OPCODE::SUB => 4,
+            OPCODE::DIV => 5,
+            OPCODE::MUL => 6,
+            OPCODE::EQ => 7,
+            OPCODE::LT => 8,
+            OPCODE::GT => 9,
+            OPCODE::AND => 10,
+            OPCODE::OR => 11,
+            OPCODE::JUMP => 12,
+            OPCODE::JUMPI => 13,
+            OPCODE::STORE => 14,
+            OPCODE::LOAD => 15,
+            OPCODE::SHL => 16,
+            OPCODE::SHR => 17,
+            OPCODE::PREVRANDAO => 18,
+            OPCODE::MSTORE => 19,
+            OPCODE::MLOAD => 20,
+            OPCODE::MSIZE => 21,
+            OPCODE::REVERT => 22,
+            OPCODE::CALLER => 23,
+            OPCODE::CALLDATALOAD => 24,
+        };
+        number.to_rlp()
+    }
+}
+
+impl Decode for OPCODE {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        if let Rlp::List(items) = item {
+            return match items.as_slice() {
+                [number, value] if u64::from_rlp(number)? == 2 => {
+                    Ok(OPCODE::VAL(i32::from_rlp(value)?))
+                }
+                _ => Err(CodecError::Malformed("not a value".into())),
+            };
+        }
+        Ok(match u64::from_rlp(item)? {
+            0 => OPCODE::STOP,
+            1 => OPCODE::PUSH,
+            3 => OPCODE::ADD,
+            4 => OPCODE::SUB,
+            5 => OPCODE::DIV,
+            6 => OPCODE::MUL,
+            7 => OPCODE::EQ,
+            8 => OPCODE::LT,
+            9 => OPCODE::GT,
+            10 => OPCODE::AND,
+            11 => OPCODE::OR,
+            12 => OPCODE::JUMP,
+            13 => OPCODE::JUMPI,
+            14 => OPCODE::STORE,
+            15 => OPCODE::LOAD,
+            16 => OPCODE::SHL,
+            17 => OPCODE::SHR,
+            18 => OPCODE::PREVRANDAO,
+            19 => OPCODE::MSTORE,
+            20 => OPCODE::MLOAD,
+            21 => OPCODE::MSIZE,
+            22 => OPCODE::REVERT,
+            23 => OPCODE::CALLER,
+            24 => OPCODE::CALLDATALOAD,
+            other => return Err(CodecError::Malformed(format!("unknown opcode {}", other))),
+        })
+    }
+}
+
 // ----------------------------------------------------------------------------- interpreter
 
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Interpreter {
     pub fn new() -> Self {
         Self {
@@ -121,36 +285,143 @@ impl Interpreter {
             stack: vec![],
             code: vec![],
             execution_count: 0,
+            gas_limit: u64::MAX,
+            env: BlockEnv::default(),
+            caller: 0,
+            call_data: vec![],
+            profile: None,
+            memory: vec![],
+            storage_journal: vec![],
         }
     }
-    pub fn jump(&mut self) {
-        let destination = self.stack.pop().unwrap();
-        let destination = extract_val_from_opcode(&destination).unwrap() as usize;
-
-        if destination > self.code.len() {
-            panic!(
-                "trying to jump to non-existent destination, {}",
-                destination
-            );
+    /// overrides the env's timeout
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.env.timeout = timeout;
+        self
+    }
+    /// the block the code runs in. BlockEnv::default() if not set
+    pub fn with_env(mut self, env: BlockEnv) -> Self {
+        self.env = env;
+        self
+    }
+    /// with_env(), for when only the rules matter
+    pub fn with_fork(mut self, fork: Fork) -> Self {
+        self.env.fork = fork;
+        self
+    }
+    /// records what each opcode costs as it runs, see profile::GasProfile
+    pub fn with_profile(mut self) -> Self {
+        self.profile = Some(GasProfile::default());
+        self
+    }
+    /// the tx the code runs for: its sender for CALLER, its call data for CALLDATALOAD
+    pub fn with_call(mut self, caller: &Address, call_data: Vec<i32>) -> Self {
+        self.caller = caller.short();
+        self.call_data = call_data;
+        self
+    }
+    /// the gas the code gets - a tx's gas limit, less what its payload costs
+    pub fn with_gas_limit(mut self, gas_limit: u64) -> Self {
+        self.gas_limit = gas_limit;
+        self
+    }
+    fn charge(&mut self, gas_used: &mut u64, opcode: OPCODE, gas: u64) -> Result<(), ExecError> {
+        *gas_used = gas_used.saturating_add(gas);
+        if let Some(profile) = &mut self.profile {
+            profile.record(opcode, gas);
         }
-
-        self.program_counter = destination;
-        self.program_counter -= 1; //need to move 1 back coz we move 1 forward at the end of the loop
+        if *gas_used > self.gas_limit {
+            return Err(ExecError::OutOfGas(self.gas_limit));
+        }
+        Ok(())
+    }
+    fn pop(&mut self) -> Result<OPCODE, ExecError> {
+        self.stack
+            .pop()
+            .ok_or(ExecError::StackUnderflow(self.program_counter))
+    }
+    fn pop_val(&mut self) -> Result<i32, ExecError> {
+        let opcode = self.pop()?;
+        extract_val_from_opcode(&opcode).map_err(|_| ExecError::NotAValue(self.program_counter))
     }
-    pub fn run_code(&mut self, code: Vec<OPCODE>, storage_trie: &mut Trie) -> EVMRetVal {
+    /// what growing memory to cover the value at offset costs, in whole words - 0 if it doesn't have to - and the size
+    /// it grows to. Memory only grows in grow_memory(), once the gas is paid
+    fn memory_expansion(&self, offset: i32, gas_per_word: u64) -> Result<(u64, usize), ExecError> {
+        let end = usize::try_from(offset)
+            .map_err(|_| ExecError::BadOffset(offset))?
+            .saturating_add(MEMORY_WORD_BYTES);
+        if end > MEMORY_LIMIT_BYTES {
+            return Err(ExecError::MemoryLimit(MEMORY_LIMIT_BYTES));
+        }
+        let words = |bytes: usize| bytes.div_ceil(MEMORY_WORD_BYTES) as u64;
+        let (before, after) = (words(self.memory.len()), words(end));
+        if after <= before {
+            return Ok((0, self.memory.len()));
+        }
+        let gas = memory_gas(after, gas_per_word) - memory_gas(before, gas_per_word);
+        Ok((gas, after as usize * MEMORY_WORD_BYTES))
+    }
+    fn grow_memory(&mut self, size: usize) {
+        if size > self.memory.len() {
+            self.memory.resize(size, 0);
+        }
+    }
+    /// moves the program counter straight to the destination - the caller must not advance it after
+    pub fn jump(&mut self) -> Result<(), ExecError> {
+        let destination = self.pop_val()?;
+        if destination < 0 || destination as usize > self.code.len() {
+            return Err(ExecError::InvalidJump(destination));
+        }
+        self.program_counter = destination as usize;
+        Ok(())
+    }
+    /// runs the code against the contract's storage. On any error - REVERT included - its writes are undone, last one
+    /// first, so a failed run never leaves half of them behind
+    pub fn run_code(
+        &mut self,
+        code: Vec<OPCODE>,
+        storage_trie: &mut Trie,
+    ) -> Result<EVMRetVal, ExecError> {
+        let result = self.execute(code, storage_trie);
+        let journal = std::mem::take(&mut self.storage_journal);
+        if result.is_err() {
+            //an empty value deletes the key, so slots that didn't exist before don't after
+            for (key, before) in journal.into_iter().rev() {
+                storage_trie.put(key, before);
+            }
+        }
+        result
+    }
+    /// run_code() without the undo. For the VMTests, where a run that we fail at the very end still has to leave
+    /// its writes behind for the EVM's
+    pub(crate) fn execute(
+        &mut self,
+        code: Vec<OPCODE>,
+        storage_trie: &mut Trie,
+    ) -> Result<EVMRetVal, ExecError> {
         self.code = code;
 
         let mut gas_used: u64 = 0;
+        let gas = self.env.fork.gas_schedule();
+        let deadline = Instant::now() + self.env.timeout;
 
         while self.program_counter < self.code.len() {
             self.execution_count += 1;
 
-            //setting an arbitrary execution limit of 10000
+            //a backstop for code run without a gas limit, eg eth_call without "gas" - a tx's code runs out of gas long
+            // before this unless its limit is huge
             if self.execution_count > EXECUTION_LIMIT {
-                panic!("execution limit of {} exceeded", EXECUTION_LIMIT)
+                return Err(ExecError::ExecutionLimit(EXECUTION_LIMIT));
+            }
+            if Instant::now() >= deadline {
+                return Err(ExecError::Timeout(self.env.timeout.as_millis() as u64));
             }
 
-            let current_opcode = &self.code[self.program_counter];
+            let pc = self.program_counter;
+            let current_opcode = self.code[pc];
+            if !self.env.fork.has_opcode(&current_opcode) {
+                return Err(ExecError::InactiveOpcode(pc));
+            }
 
             match current_opcode {
                 OPCODE::VAL(_) => continue,
@@ -158,112 +429,169 @@ impl Interpreter {
                 OPCODE::PUSH => {
                     self.program_counter += 1;
                     if self.program_counter == self.code.len() {
-                        panic!("push instruction cannot be last")
+                        return Err(ExecError::PushAtEnd);
                     }
-                    let current_opcode = &self.code[self.program_counter];
-                    self.stack.push(*current_opcode);
+                    let current_opcode = self.code[self.program_counter];
+                    self.stack.push(current_opcode);
+                    self.charge(&mut gas_used, OPCODE::PUSH, 0)?;
                 }
                 OPCODE::JUMP => {
-                    self.jump();
-                    gas_used += 2;
+                    self.charge(&mut gas_used, current_opcode, gas.jump)?;
+                    self.jump()?;
+                    continue;
                 }
                 OPCODE::JUMPI => {
-                    let condition = self.stack.pop().unwrap();
-                    match condition {
-                        OPCODE::VAL(1) => self.jump(),
-                        _ => (), //note: NOT continue, or the pointer won't increment at the end of the loop
+                    self.charge(&mut gas_used, current_opcode, gas.jump)?;
+                    let condition = self.pop()?;
+                    if let OPCODE::VAL(1) = condition {
+                        self.jump()?;
+                        continue;
                     }
-                    gas_used += 2;
                 }
                 OPCODE::STORE => {
-                    let key = self.stack.pop().unwrap();
-                    let value = self.stack.pop().unwrap();
-
-                    let key = extract_val_from_opcode(&key).unwrap();
-                    let value = extract_val_from_opcode(&value).unwrap();
+                    let key = self.pop_val()?;
+                    let value = self.pop_val()?;
 
-                    storage_trie.put(format!("{}", key), format!("{}", value));
+                    let (key, value) = (format!("{}", key), format!("{}", value));
+                    let before = storage_trie.get(key.clone()).cloned().unwrap_or_default();
+                    let unchanged = before == value;
+                    self.storage_journal.push((key.clone(), before));
+                    storage_trie.put(key, value);
 
                     // this is a (terrible) workaround -
                     // because the result at the bottom has to pop something off, I'm adding a random (easily recognizable) value
                     self.stack.push(OPCODE::VAL(999));
-                    gas_used += 5;
+                    let cost = if unchanged {
+                        gas.unchanged_store
+                    } else {
+                        gas.store
+                    };
+                    self.charge(&mut gas_used, current_opcode, cost)?;
+                }
+                OPCODE::PREVRANDAO => {
+                    self.stack.push(OPCODE::VAL(self.env.randao));
+                    self.charge(&mut gas_used, current_opcode, gas.arithmetic)?;
+                }
+                //unlike STORE it leaves nothing behind - it's for the middle of the code, not the end
+                OPCODE::MSTORE => {
+                    let offset = self.pop_val()?;
+                    let value = self.pop_val()?;
+
+                    //charged before memory grows, so running out never allocates what it couldn't pay for
+                    let (expansion, size) = self.memory_expansion(offset, gas.memory)?;
+                    self.charge(&mut gas_used, current_opcode, gas.memory + expansion)?;
+                    self.grow_memory(size);
+                    let offset = offset as usize;
+                    self.memory[offset..offset + MEMORY_WORD_BYTES]
+                        .copy_from_slice(&value.to_be_bytes());
+                }
+                //memory nothing was written to yet reads as 0, same as in ethereum
+                OPCODE::MLOAD => {
+                    let offset = self.pop_val()?;
+
+                    let (expansion, size) = self.memory_expansion(offset, gas.memory)?;
+                    self.charge(&mut gas_used, current_opcode, gas.memory + expansion)?;
+                    self.grow_memory(size);
+                    let offset = offset as usize;
+                    let mut bytes = [0; MEMORY_WORD_BYTES];
+                    bytes.copy_from_slice(&self.memory[offset..offset + MEMORY_WORD_BYTES]);
+
+                    self.stack.push(OPCODE::VAL(i32::from_be_bytes(bytes)));
+                }
+                OPCODE::CALLER => {
+                    self.stack.push(OPCODE::VAL(self.caller));
+                    self.charge(&mut gas_used, current_opcode, gas.arithmetic)?;
+                }
+                OPCODE::CALLDATALOAD => {
+                    let index = self.pop_val()?;
+                    let value = usize::try_from(index)
+                        .ok()
+                        .and_then(|index| self.call_data.get(index))
+                        .copied()
+                        .unwrap_or(0);
+                    self.stack.push(OPCODE::VAL(value));
+                    self.charge(&mut gas_used, current_opcode, gas.arithmetic)?;
                 }
+                OPCODE::REVERT => {
+                    let reason = self.pop_val()?;
+                    self.charge(&mut gas_used, current_opcode, 0)?;
+                    return Err(ExecError::Reverted { reason, gas_used });
+                }
+                //in bytes, always a whole number of words
+                OPCODE::MSIZE => {
+                    self.stack.push(OPCODE::VAL(self.memory.len() as i32));
+                    self.charge(&mut gas_used, current_opcode, gas.arithmetic)?;
+                }
+                //a slot nothing was stored in yet reads as 0, same as in ethereum. The trie gives "" for a key that's only
+                // a prefix of another one, eg 1 once 12 is stored
                 OPCODE::LOAD => {
-                    let key = self.stack.pop().unwrap();
-                    let key = extract_val_from_opcode(&key).unwrap();
+                    let key = self.pop_val()?;
 
-                    let value = storage_trie.get(format!("{}", key)).unwrap();
-                    let value = value.parse::<i32>().unwrap();
+                    let stored = storage_trie
+                        .get(format!("{}", key))
+                        .filter(|value| !value.is_empty());
+                    let value = match stored {
+                        Some(value) => value.parse::<i32>().map_err(|_| ExecError::BadSlot(key))?,
+                        None => 0,
+                    };
 
                     self.stack.push(OPCODE::VAL(value));
-                    gas_used += 5;
+                    self.charge(&mut gas_used, current_opcode, gas.load)?;
                 }
                 _ => {
-                    let a = self.stack.pop().unwrap();
-                    let b = self.stack.pop().unwrap();
+                    let a = self.pop_val()?;
+                    let b = self.pop_val()?;
 
+                    //checked, so a contract can't take the node down with an overflow
                     let result = match current_opcode {
-                        OPCODE::ADD => a + b,
-                        OPCODE::SUB => a - b,
-                        OPCODE::DIV => a / b,
-                        OPCODE::MUL => a * b,
-                        OPCODE::EQ => {
-                            if a == b {
-                                OPCODE::VAL(1)
-                            } else {
-                                OPCODE::VAL(0)
-                            }
-                        }
-                        OPCODE::LT => {
-                            if a < b {
-                                OPCODE::VAL(1)
-                            } else {
-                                OPCODE::VAL(0)
-                            }
-                        }
-                        OPCODE::GT => {
-                            if a > b {
-                                OPCODE::VAL(1)
-                            } else {
-                                OPCODE::VAL(0)
-                            }
-                        }
-                        OPCODE::AND => {
-                            let a = extract_val_from_opcode(&a).unwrap();
-                            let b = extract_val_from_opcode(&b).unwrap();
-                            if (a == 0) || (b == 0) {
-                                OPCODE::VAL(0)
-                            } else {
-                                OPCODE::VAL(1)
-                            }
-                        }
-                        OPCODE::OR => {
-                            let a = extract_val_from_opcode(&a).unwrap();
-                            let b = extract_val_from_opcode(&b).unwrap();
-                            if (a != 0) || (b != 0) {
-                                OPCODE::VAL(1)
-                            } else {
-                                OPCODE::VAL(0)
-                            }
-                        }
+                        OPCODE::ADD => a.checked_add(b).ok_or(ExecError::Overflow(pc))?,
+                        OPCODE::SUB => a.checked_sub(b).ok_or(ExecError::Overflow(pc))?,
+                        OPCODE::MUL => a.checked_mul(b).ok_or(ExecError::Overflow(pc))?,
+                        OPCODE::DIV if b == 0 => return Err(ExecError::DivisionByZero(pc)),
+                        OPCODE::DIV => a.checked_div(b).ok_or(ExecError::Overflow(pc))?,
+                        OPCODE::EQ => (a == b) as i32,
+                        OPCODE::LT => (a < b) as i32,
+                        OPCODE::GT => (a > b) as i32,
+                        OPCODE::AND => ((a != 0) && (b != 0)) as i32,
+                        OPCODE::OR => ((a != 0) || (b != 0)) as i32,
+                        //a is the shift, b the value - shifting by 32 or more would lose every bit
+                        OPCODE::SHL => u32::try_from(a)
+                            .ok()
+                            .and_then(|shift| b.checked_shl(shift))
+                            .ok_or(ExecError::Overflow(pc))?,
+                        OPCODE::SHR => u32::try_from(a)
+                            .ok()
+                            .and_then(|shift| (b as u32).checked_shr(shift))
+                            .ok_or(ExecError::Overflow(pc))?
+                            as i32,
                         _ => unreachable!(),
                     };
-                    self.stack.push(result);
-                    gas_used += 1;
+                    self.stack.push(OPCODE::VAL(result));
+                    self.charge(&mut gas_used, current_opcode, gas.arithmetic)?;
                 }
             }
 
-            println!("stack is {:?}", self.stack);
+            tracing::trace!(stack = ?self.stack, "stack");
             self.program_counter += 1;
         }
-        let ret_val = self.stack[self.stack.len() - 1];
-        EVMRetVal { ret_val, gas_used }
+        let ret_val = *self
+            .stack
+            .last()
+            .ok_or(ExecError::StackUnderflow(self.program_counter))?;
+        Ok(EVMRetVal { ret_val, gas_used })
     }
 }
 
 // ----------------------------------------------------------------------------- helpers
 
+/// what `words` of memory cost in all: linear in the words, plus a quadratic part so big memory gets expensive fast.
+/// Expanding memory costs the difference between the sizes before and after
+pub fn memory_gas(words: u64, gas_per_word: u64) -> u64 {
+    words
+        .saturating_mul(gas_per_word)
+        .saturating_add(words.saturating_mul(words) / MEMORY_QUAD_DIVISOR)
+}
+
 pub fn extract_val_from_opcode(parent: &OPCODE) -> Result<i32, String> {
     match parent {
         OPCODE::VAL(value) => Ok(*value),
@@ -278,12 +606,153 @@ mod tests {
     use super::*;
 
     #[test]
-    #[should_panic]
     fn test_bad_push() {
         let mut i = Interpreter::new();
         let mut fake_storage_trie = Trie::new();
         let code = vec![OPCODE::PUSH, OPCODE::VAL(10), OPCODE::PUSH];
-        let _r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie);
+        assert_eq!(r.unwrap_err(), ExecError::PushAtEnd);
+    }
+
+    #[test]
+    fn test_shifts_need_constantinople() {
+        let code = vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(-16),
+            OPCODE::PUSH,
+            OPCODE::VAL(2),
+            OPCODE::SHL,
+            OPCODE::STOP,
+        ];
+        let r = Interpreter::new().run_code(code.clone(), &mut Trie::new());
+        assert_eq!(r.unwrap_err(), ExecError::InactiveOpcode(4));
+
+        let run = |code| {
+            Interpreter::new()
+                .with_fork(Fork::Constantinople)
+                .run_code(code, &mut Trie::new())
+                .unwrap()
+                .ret_val
+        };
+        assert_eq!(run(code), OPCODE::VAL(-64));
+        //logical, so the sign bit shifts down like any other
+        let code = vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(-16),
+            OPCODE::PUSH,
+            OPCODE::VAL(28),
+            OPCODE::SHR,
+            OPCODE::STOP,
+        ];
+        assert_eq!(run(code), OPCODE::VAL(15));
+    }
+
+    #[test]
+    fn test_prevrandao() {
+        let code = vec![OPCODE::PREVRANDAO, OPCODE::STOP];
+        let env = BlockEnv {
+            fork: Fork::Paris,
+            randao: 12345,
+            ..BlockEnv::default()
+        };
+        let r = Interpreter::new()
+            .with_env(env)
+            .run_code(code.clone(), &mut Trie::new())
+            .unwrap();
+        assert_eq!(r.ret_val, OPCODE::VAL(12345));
+        let r = Interpreter::new()
+            .with_fork(Fork::Constantinople)
+            .run_code(code, &mut Trie::new());
+        assert_eq!(r.unwrap_err(), ExecError::InactiveOpcode(0));
+    }
+
+    #[test]
+    fn test_unchanged_store_is_cheaper_from_constantinople() {
+        let code = vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(7),
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::STORE,
+            OPCODE::STOP,
+        ];
+        let gas_used = |fork| {
+            let mut storage_trie = Trie::new();
+            storage_trie.put("1".into(), "7".into());
+            Interpreter::new()
+                .with_fork(fork)
+                .run_code(code.clone(), &mut storage_trie)
+                .unwrap()
+                .gas_used
+        };
+        assert_eq!(gas_used(Fork::Frontier), 5);
+        assert_eq!(gas_used(Fork::Constantinople), 1);
+
+        //a store that does change the slot costs the same either way
+        let r = Interpreter::new()
+            .with_fork(Fork::Constantinople)
+            .run_code(code.clone(), &mut Trie::new())
+            .unwrap();
+        assert_eq!(r.gas_used, 5);
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut storage_trie = Trie::new();
+        let code = vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(42), //value
+            OPCODE::PUSH,
+            OPCODE::VAL(8), //offset
+            OPCODE::MSTORE,
+            OPCODE::PUSH,
+            OPCODE::VAL(8),
+            OPCODE::MLOAD,
+            //never written to
+            OPCODE::PUSH,
+            OPCODE::VAL(0),
+            OPCODE::MLOAD,
+            OPCODE::ADD,
+            OPCODE::MSIZE,
+            OPCODE::ADD,
+            OPCODE::STOP,
+        ];
+        let mut i = Interpreter::new();
+        let r = i.run_code(code, &mut storage_trie).unwrap();
+        //42 + 0 + 12 bytes of memory
+        assert_eq!(r.ret_val, OPCODE::VAL(54));
+        assert_eq!(i.memory.len(), 12);
+        //MSTORE: 3 + 3 words, the MLOADs 3 each, the rest 1 each
+        assert_eq!(r.gas_used, 12 + 3 + 3 + 1 + 1 + 1);
+        assert!(storage_trie.is_empty());
+
+        assert_eq!(memory_gas(1, 3), 3);
+        assert_eq!(memory_gas(512, 3), 1536 + 512);
+
+        let mstore = |offset| {
+            Interpreter::new().run_code(
+                vec![
+                    OPCODE::PUSH,
+                    OPCODE::VAL(1),
+                    OPCODE::PUSH,
+                    OPCODE::VAL(offset),
+                    OPCODE::MSTORE,
+                    OPCODE::MSIZE,
+                    OPCODE::STOP,
+                ],
+                &mut Trie::new(),
+            )
+        };
+        let last_word = (MEMORY_LIMIT_BYTES - MEMORY_WORD_BYTES) as i32;
+        assert_eq!(
+            mstore(last_word).unwrap().ret_val,
+            OPCODE::VAL(MEMORY_LIMIT_BYTES as i32)
+        );
+        assert_eq!(
+            mstore(last_word + 1).unwrap_err(),
+            ExecError::MemoryLimit(MEMORY_LIMIT_BYTES)
+        );
+        assert_eq!(mstore(-4).unwrap_err(), ExecError::BadOffset(-4));
     }
 
     #[test]
@@ -298,7 +767,7 @@ mod tests {
             OPCODE::ADD,
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -318,7 +787,7 @@ mod tests {
             OPCODE::SUB,
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -338,7 +807,7 @@ mod tests {
             OPCODE::MUL,
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -358,7 +827,7 @@ mod tests {
             OPCODE::DIV,
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -384,7 +853,7 @@ mod tests {
             OPCODE::EQ,
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -404,7 +873,7 @@ mod tests {
             OPCODE::EQ,
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -424,7 +893,7 @@ mod tests {
             OPCODE::LT,
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -444,7 +913,7 @@ mod tests {
             OPCODE::GT,
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -464,7 +933,7 @@ mod tests {
             OPCODE::AND,
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -484,7 +953,7 @@ mod tests {
             OPCODE::AND,
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -504,7 +973,7 @@ mod tests {
             OPCODE::OR,
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -524,7 +993,7 @@ mod tests {
             OPCODE::OR,
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -550,7 +1019,7 @@ mod tests {
             OPCODE::VAL(4),
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -559,12 +1028,160 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn test_bad_jump() {
         let mut i = Interpreter::new();
         let mut fake_storage_trie = Trie::new();
         let code = vec![OPCODE::PUSH, OPCODE::VAL(99), OPCODE::JUMP];
-        let _r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie);
+        assert_eq!(r.unwrap_err(), ExecError::InvalidJump(99));
+    }
+
+    #[test]
+    fn test_bad_code_errors_instead_of_panicking() {
+        let run = |code| Interpreter::new().run_code(code, &mut Trie::new());
+        //jumps back to the start forever
+        let r = run(vec![OPCODE::PUSH, OPCODE::VAL(0), OPCODE::JUMP]);
+        assert_eq!(r.unwrap_err(), ExecError::ExecutionLimit(EXECUTION_LIMIT));
+        let r = run(vec![OPCODE::PUSH, OPCODE::VAL(1), OPCODE::ADD]);
+        assert_eq!(r.unwrap_err(), ExecError::StackUnderflow(2));
+        let r = run(vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(0),
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::DIV,
+        ]);
+        assert_eq!(r.unwrap_err(), ExecError::DivisionByZero(4));
+        let r = run(vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(i32::MAX),
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::ADD,
+        ]);
+        assert_eq!(r.unwrap_err(), ExecError::Overflow(4));
+        //PUSH takes whatever comes next, including another instruction
+        let r = run(vec![OPCODE::PUSH, OPCODE::ADD, OPCODE::JUMP]);
+        assert_eq!(r.unwrap_err(), ExecError::NotAValue(2));
+    }
+
+    #[test]
+    fn test_revert_and_out_of_gas_undo_storage() {
+        let mut storage_trie = Trie::new();
+        storage_trie.put("1".into(), "5".into());
+        let store_then = |last: Vec<OPCODE>| {
+            let mut code = vec![
+                OPCODE::PUSH,
+                OPCODE::VAL(7),
+                OPCODE::PUSH,
+                OPCODE::VAL(1),
+                OPCODE::STORE,
+            ];
+            code.extend(last);
+            code
+        };
+
+        let code = store_then(vec![OPCODE::PUSH, OPCODE::VAL(3), OPCODE::REVERT]);
+        let r = Interpreter::new().run_code(code, &mut storage_trie);
+        assert_eq!(
+            r.unwrap_err(),
+            ExecError::Reverted {
+                reason: 3,
+                gas_used: 5
+            }
+        );
+        assert_eq!(storage_trie.get("1".into()), Some(&"5".to_string()));
+        //nothing to give as the reason
+        let r = Interpreter::new().run_code(vec![OPCODE::REVERT], &mut storage_trie);
+        assert_eq!(r.unwrap_err(), ExecError::StackUnderflow(0));
+
+        //the STORE itself takes it over the limit
+        let r = Interpreter::new()
+            .with_gas_limit(4)
+            .run_code(store_then(vec![OPCODE::STOP]), &mut storage_trie);
+        assert_eq!(r.unwrap_err(), ExecError::OutOfGas(4));
+        assert_eq!(storage_trie.get("1".into()), Some(&"5".to_string()));
+        let r = Interpreter::new()
+            .with_gas_limit(5)
+            .run_code(store_then(vec![OPCODE::STOP]), &mut storage_trie);
+        assert_eq!(r.unwrap().gas_used, 5);
+        assert_eq!(storage_trie.get("1".into()), Some(&"7".to_string()));
+    }
+
+    #[test]
+    fn test_out_of_gas_halts_execution() {
+        //jumps back to the start forever, 2 gas a round
+        let mut i = Interpreter::new().with_gas_limit(100);
+        let r = i.run_code(
+            vec![OPCODE::PUSH, OPCODE::VAL(0), OPCODE::JUMP],
+            &mut Trie::new(),
+        );
+        assert_eq!(r.unwrap_err(), ExecError::OutOfGas(100));
+        assert_eq!(i.execution_count, 2 * 51);
+
+        //memory it can't pay for never gets allocated
+        let mut i = Interpreter::new().with_gas_limit(1000);
+        let r = i.run_code(
+            vec![
+                OPCODE::PUSH,
+                OPCODE::VAL(1),
+                OPCODE::PUSH,
+                OPCODE::VAL((MEMORY_LIMIT_BYTES - MEMORY_WORD_BYTES) as i32),
+                OPCODE::MSTORE,
+            ],
+            &mut Trie::new(),
+        );
+        assert_eq!(r.unwrap_err(), ExecError::OutOfGas(1000));
+        assert!(i.memory.is_empty());
+    }
+
+    #[test]
+    fn test_call_opcodes() {
+        let caller = crate::account::gen_address();
+        let run = |code| {
+            Interpreter::new()
+                .with_call(&caller, vec![4, -2])
+                .run_code(code, &mut Trie::new())
+                .unwrap()
+                .ret_val
+        };
+        assert_eq!(run(vec![OPCODE::CALLER]), OPCODE::VAL(caller.short()));
+        let load = |index| vec![OPCODE::PUSH, OPCODE::VAL(index), OPCODE::CALLDATALOAD];
+        assert_eq!(run(load(1)), OPCODE::VAL(-2));
+        assert_eq!(run(load(2)), OPCODE::VAL(0));
+        assert_eq!(run(load(-1)), OPCODE::VAL(0));
+        //an empty slot reads as 0, as does one whose key is a prefix of a stored one
+        assert_eq!(
+            run(vec![OPCODE::PUSH, OPCODE::VAL(7), OPCODE::LOAD]),
+            OPCODE::VAL(0)
+        );
+        let mut storage_trie = Trie::new();
+        storage_trie.put("12".into(), "5".into());
+        let r = Interpreter::new().run_code(
+            vec![OPCODE::PUSH, OPCODE::VAL(1), OPCODE::LOAD],
+            &mut storage_trie,
+        );
+        assert_eq!(r.unwrap().ret_val, OPCODE::VAL(0));
+    }
+
+    #[test]
+    fn test_timeout() {
+        //a zero budget is used up before the first instruction
+        let r = Interpreter::new()
+            .with_timeout(Duration::from_millis(0))
+            .run_code(
+                vec![OPCODE::PUSH, OPCODE::VAL(1), OPCODE::STOP],
+                &mut Trie::new(),
+            );
+        assert_eq!(r.unwrap_err(), ExecError::Timeout(0));
+        //with plenty of time, the step limit still gets there first
+        let r = Interpreter::new()
+            .with_timeout(Duration::from_secs(60))
+            .run_code(
+                vec![OPCODE::PUSH, OPCODE::VAL(0), OPCODE::JUMP],
+                &mut Trie::new(),
+            );
+        assert_eq!(r.unwrap_err(), ExecError::ExecutionLimit(EXECUTION_LIMIT));
     }
 
     #[test]
@@ -587,7 +1204,7 @@ mod tests {
             OPCODE::VAL(4),
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -615,7 +1232,7 @@ mod tests {
             OPCODE::ADD,
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -636,7 +1253,7 @@ mod tests {
             OPCODE::STORE,
             OPCODE::STOP,
         ];
-        let r = i.run_code(code, &mut fake_storage_trie).ret_val;
+        let r = i.run_code(code, &mut fake_storage_trie).unwrap().ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
@@ -667,15 +1284,69 @@ mod tests {
             OPCODE::LOAD,
             OPCODE::STOP,
         ];
-        let _r = i.run_code(code_store, &mut fake_storage_trie).ret_val;
+        let _r = i
+            .run_code(code_store, &mut fake_storage_trie)
+            .unwrap()
+            .ret_val;
         let mut i = Interpreter::new();
-        let r = i.run_code(code_load, &mut fake_storage_trie).ret_val;
+        let r = i
+            .run_code(code_load, &mut fake_storage_trie)
+            .unwrap()
+            .ret_val;
         let r_val = match r {
             OPCODE::VAL(v) => v,
             _ => panic!("cant get val"),
         };
         assert_eq!(r_val, 456);
     }
+
+    #[test]
+    fn test_revert_undoes_stores() {
+        let mut storage_trie = Trie::new();
+        storage_trie.put("1".into(), "7".into());
+        let before = storage_trie.clone();
+        //overwrites 1 twice and adds 12 - which 1 is a prefix of - before reverting
+        let code = vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(8),
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::STORE,
+            OPCODE::PUSH,
+            OPCODE::VAL(9),
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::STORE,
+            OPCODE::PUSH,
+            OPCODE::VAL(5),
+            OPCODE::PUSH,
+            OPCODE::VAL(12),
+            OPCODE::STORE,
+            OPCODE::PUSH,
+            OPCODE::VAL(3),
+            OPCODE::REVERT,
+        ];
+        let mut i = Interpreter::new();
+        assert!(matches!(
+            i.run_code(code, &mut storage_trie),
+            Err(ExecError::Reverted { reason: 3, .. })
+        ));
+        assert_eq!(storage_trie.entries(), before.entries());
+        assert_eq!(storage_trie.root_hash, before.root_hash);
+        //and it takes writes as usual after
+        let code = vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(4),
+            OPCODE::PUSH,
+            OPCODE::VAL(2),
+            OPCODE::STORE,
+            OPCODE::STOP,
+        ];
+        let mut i = Interpreter::new();
+        i.run_code(code, &mut storage_trie).unwrap();
+        assert_eq!(storage_trie.get("1".into()), Some(&"7".to_string()));
+        assert_eq!(storage_trie.get("2".into()), Some(&"4".to_string()));
+    }
 }
 
 // -----------------------------------------------------------------------------
@@ -0,0 +1,149 @@
+//gas accounting by opcode, to see where a contract's gas goes. The interpreter fills one in when it's built
+// with_profile() - Blockchain::profile_tx() replays a mined tx that way for GET /debug/gas/{tx_hash}
+
+use crate::account::address::Address;
+use crate::interpreter::OPCODE;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// which line of the GasSchedule an opcode gets charged from. Stack is PUSH, STOP and REVERT, which are free
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GasCategory {
+    Arithmetic,
+    Jump,
+    Storage,
+    Memory,
+    Stack,
+}
+
+impl GasCategory {
+    pub fn of(opcode: &OPCODE) -> Self {
+        match opcode {
+            OPCODE::JUMP | OPCODE::JUMPI => GasCategory::Jump,
+            OPCODE::STORE | OPCODE::LOAD => GasCategory::Storage,
+            OPCODE::MSTORE | OPCODE::MLOAD | OPCODE::MSIZE => GasCategory::Memory,
+            OPCODE::STOP | OPCODE::REVERT | OPCODE::PUSH | OPCODE::VAL(_) => GasCategory::Stack,
+            //PREVRANDAO included, it's charged like one
+            _ => GasCategory::Arithmetic,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct OpcodeGas {
+    pub opcode: String,
+    pub category: GasCategory,
+    /// how many times it ran
+    pub count: u64,
+    pub gas: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CategoryGas {
+    pub arithmetic: u64,
+    pub jump: u64,
+    pub storage: u64,
+    /// memory expansion included
+    pub memory: u64,
+}
+
+/// opcode name -> how often it ran and what that cost, over one run_code() call
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GasProfile {
+    opcodes: HashMap<String, OpcodeGas>,
+}
+
+impl GasProfile {
+    pub fn record(&mut self, opcode: OPCODE, gas: u64) {
+        let entry = self
+            .opcodes
+            .entry(format!("{:?}", opcode))
+            .or_insert_with(|| OpcodeGas {
+                opcode: format!("{:?}", opcode),
+                category: GasCategory::of(&opcode),
+                count: 0,
+                gas: 0,
+            });
+        entry.count += 1;
+        entry.gas = entry.gas.saturating_add(gas);
+    }
+    pub fn total(&self) -> u64 {
+        self.opcodes.values().map(|entry| entry.gas).sum()
+    }
+    pub fn by_category(&self) -> CategoryGas {
+        let mut totals = CategoryGas::default();
+        for entry in self.opcodes.values() {
+            match entry.category {
+                GasCategory::Arithmetic => totals.arithmetic += entry.gas,
+                GasCategory::Jump => totals.jump += entry.gas,
+                GasCategory::Storage => totals.storage += entry.gas,
+                GasCategory::Memory => totals.memory += entry.gas,
+                GasCategory::Stack => {}
+            }
+        }
+        totals
+    }
+    /// most gas first. Ties go by how often the opcode ran, then by name
+    pub fn opcodes(&self) -> Vec<OpcodeGas> {
+        let mut opcodes: Vec<OpcodeGas> = self.opcodes.values().cloned().collect();
+        opcodes.sort_by(|a, b| {
+            b.gas
+                .cmp(&a.gas)
+                .then_with(|| b.count.cmp(&a.count))
+                .then_with(|| a.opcode.cmp(&b.opcode))
+        });
+        opcodes
+    }
+}
+
+/// where a mined tx's gas went. payload_gas + execution_gas = gas_used, same as in its receipt - unless its code failed
+/// other than by REVERT, which costs the whole gas limit however much of it ran
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TxGasProfile {
+    pub tx_hash: String,
+    pub block_number: usize,
+    /// the contract whose code ran. Null for tx that don't run any code
+    #[schema(value_type = Option<String>)]
+    pub contract: Option<Address>,
+    pub gas_used: u64,
+    pub payload_gas: u64,
+    pub execution_gas: u64,
+    pub by_category: CategoryGas,
+    pub opcodes: Vec<OpcodeGas>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile() {
+        let mut profile = GasProfile::default();
+        profile.record(OPCODE::PUSH, 0);
+        profile.record(OPCODE::PUSH, 0);
+        profile.record(OPCODE::ADD, 1);
+        profile.record(OPCODE::STORE, 5);
+        profile.record(OPCODE::JUMP, 2);
+        profile.record(OPCODE::LOAD, 5);
+        profile.record(OPCODE::MSTORE, 6);
+        profile.record(OPCODE::MSIZE, 1);
+        assert_eq!(profile.total(), 20);
+        assert_eq!(
+            profile.by_category(),
+            CategoryGas {
+                arithmetic: 1,
+                jump: 2,
+                storage: 10,
+                memory: 7,
+            }
+        );
+        let opcodes: Vec<String> = profile.opcodes().into_iter().map(|o| o.opcode).collect();
+        assert_eq!(
+            opcodes,
+            vec!["MSTORE", "LOAD", "STORE", "JUMP", "ADD", "MSIZE", "PUSH"]
+        );
+        assert_eq!(profile.opcodes()[6].count, 2);
+    }
+}
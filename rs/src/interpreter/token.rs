@@ -0,0 +1,311 @@
+//a reference ERC-20 style token, written in the toy instruction set. Every call to it carries call data
+// [selector, ..arguments] and the code dispatches on the selector:
+//   [INIT, supply]          mints supply to the caller. Only once, while the total supply is still 0
+//   [TRANSFER, to, amount]  moves amount from the caller's balance to that of `to` (an Address::short())
+// Storage: slot 0 holds the total supply, slot Address::short() of a holder its balance. Anything that doesn't add up
+// REVERTs with one of the reasons below, so the tx gets mined as failed and nothing moves.
+// GET /token/{address}/... decodes that layout, see balance_of() and total_supply()
+
+use crate::account::address::Address;
+use crate::interpreter::OPCODE;
+use crate::store::trie::Trie;
+
+pub const INIT: i32 = 1;
+pub const TRANSFER: i32 = 2;
+
+//REVERT reasons
+/// incl no call data at all - the token doesn't take plain transfers
+pub const UNKNOWN_SELECTOR: i32 = 1;
+pub const ALREADY_INITIALIZED: i32 = 2;
+/// a supply below 1, or a transfer below 0
+pub const INVALID_AMOUNT: i32 = 3;
+pub const INSUFFICIENT_BALANCE: i32 = 4;
+/// slot 0 is the total supply, no holder can have it
+pub const INVALID_RECIPIENT: i32 = 5;
+
+const TOTAL_SUPPLY_SLOT: i32 = 0;
+
+//where the jumps go, as indexes into token_code()
+const INIT_AT: i32 = 21;
+const TRANSFER_AT: i32 = 51;
+const ALREADY_INITIALIZED_AT: i32 = 99;
+const INVALID_AMOUNT_AT: i32 = 102;
+const INVALID_RECIPIENT_AT: i32 = 105;
+const INSUFFICIENT_BALANCE_AT: i32 = 108;
+
+/// deploy it as an account's code, then init_call() once from whoever gets the supply
+pub fn token_code() -> Vec<OPCODE> {
+    use OPCODE::*;
+    vec![
+        //0: dispatch. A JUMPI that doesn't jump leaves its destination on the stack, which does no harm
+        PUSH,
+        VAL(TRANSFER_AT),
+        PUSH,
+        VAL(0),
+        CALLDATALOAD,
+        PUSH,
+        VAL(TRANSFER),
+        EQ,
+        JUMPI,
+        PUSH,
+        VAL(INIT_AT),
+        PUSH,
+        VAL(0),
+        CALLDATALOAD,
+        PUSH,
+        VAL(INIT),
+        EQ,
+        JUMPI,
+        PUSH,
+        VAL(UNKNOWN_SELECTOR),
+        REVERT,
+        //21: init - supply > 0 means it's been done already
+        PUSH,
+        VAL(ALREADY_INITIALIZED_AT),
+        PUSH,
+        VAL(TOTAL_SUPPLY_SLOT),
+        LOAD,
+        PUSH,
+        VAL(0),
+        LT,
+        JUMPI,
+        //1 > amount
+        PUSH,
+        VAL(INVALID_AMOUNT_AT),
+        PUSH,
+        VAL(1),
+        CALLDATALOAD,
+        PUSH,
+        VAL(1),
+        GT,
+        JUMPI,
+        PUSH,
+        VAL(1),
+        CALLDATALOAD,
+        PUSH,
+        VAL(TOTAL_SUPPLY_SLOT),
+        STORE,
+        PUSH,
+        VAL(1),
+        CALLDATALOAD,
+        CALLER,
+        STORE,
+        STOP,
+        //51: transfer - amount < 0
+        PUSH,
+        VAL(INVALID_AMOUNT_AT),
+        PUSH,
+        VAL(0),
+        PUSH,
+        VAL(2),
+        CALLDATALOAD,
+        LT,
+        JUMPI,
+        //to < 1
+        PUSH,
+        VAL(INVALID_RECIPIENT_AT),
+        PUSH,
+        VAL(1),
+        PUSH,
+        VAL(1),
+        CALLDATALOAD,
+        LT,
+        JUMPI,
+        //amount > the caller's balance
+        PUSH,
+        VAL(INSUFFICIENT_BALANCE_AT),
+        CALLER,
+        LOAD,
+        PUSH,
+        VAL(2),
+        CALLDATALOAD,
+        GT,
+        JUMPI,
+        //the caller's balance - amount
+        PUSH,
+        VAL(2),
+        CALLDATALOAD,
+        CALLER,
+        LOAD,
+        SUB,
+        CALLER,
+        STORE,
+        //read after the debit, so sending to yourself comes out even. An overflow fails the call
+        PUSH,
+        VAL(2),
+        CALLDATALOAD,
+        PUSH,
+        VAL(1),
+        CALLDATALOAD,
+        LOAD,
+        ADD,
+        PUSH,
+        VAL(1),
+        CALLDATALOAD,
+        STORE,
+        STOP,
+        //99: failures
+        PUSH,
+        VAL(ALREADY_INITIALIZED),
+        REVERT,
+        PUSH,
+        VAL(INVALID_AMOUNT),
+        REVERT,
+        PUSH,
+        VAL(INVALID_RECIPIENT),
+        REVERT,
+        PUSH,
+        VAL(INSUFFICIENT_BALANCE),
+        REVERT,
+    ]
+}
+
+/// whether code is token_code(). OPCODE's == only works on values, so it's compared serialized
+pub fn is_token(code: &[OPCODE]) -> bool {
+    serde_json::to_string(code).unwrap() == serde_json::to_string(&token_code()).unwrap()
+}
+
+pub fn init_call(supply: i32) -> Vec<i32> {
+    vec![INIT, supply]
+}
+
+pub fn transfer_call(to: &Address, amount: i32) -> Vec<i32> {
+    vec![TRANSFER, to.short(), amount]
+}
+
+/// 0 for anyone who never held any
+pub fn balance_of(storage: &Trie, holder: &Address) -> i32 {
+    read_slot(storage, holder.short())
+}
+
+/// 0 until init
+pub fn total_supply(storage: &Trie) -> i32 {
+    read_slot(storage, TOTAL_SUPPLY_SLOT)
+}
+
+//same as LOAD does it
+fn read_slot(storage: &Trie, slot: i32) -> i32 {
+    storage
+        .get(slot.to_string())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::error::{ExecError, TxError};
+    use crate::interpreter::BlockEnv;
+    use crate::store::state::{State, StateAccess};
+    use crate::transaction::tx::Transaction;
+
+    #[test]
+    fn test_jump_targets() {
+        let code = token_code();
+        for (at, reason) in [
+            (ALREADY_INITIALIZED_AT, ALREADY_INITIALIZED),
+            (INVALID_AMOUNT_AT, INVALID_AMOUNT),
+            (INVALID_RECIPIENT_AT, INVALID_RECIPIENT),
+            (INSUFFICIENT_BALANCE_AT, INSUFFICIENT_BALANCE),
+        ] {
+            let at = at as usize;
+            assert_eq!(
+                serde_json::to_string(&code[at..at + 3]).unwrap(),
+                serde_json::to_string(&[OPCODE::PUSH, OPCODE::VAL(reason), OPCODE::REVERT])
+                    .unwrap()
+            );
+        }
+        assert_eq!(code.len(), INSUFFICIENT_BALANCE_AT as usize + 3);
+        assert!(is_token(&code));
+        assert!(!is_token(&code[1..]));
+    }
+
+    #[test]
+    fn test_token() {
+        let (alice, bob) = (Account::new(vec![]), Account::new(vec![]));
+        let (alice_addr, bob_addr) = (alice.public_account.address, bob.public_account.address);
+        let token = Account::new(token_code()).public_account;
+        let token_addr = token.address;
+        let mut state = State::new();
+        state.allocate(alice_addr, 10_000);
+        state.allocate(bob_addr, 10_000);
+        state.put_account(token_addr, token);
+
+        let mut nonces = std::collections::HashMap::new();
+        let mut call = |state: &mut State, from: &Account, call_data| {
+            let nonce = nonces.entry(from.public_account.address).or_insert(0);
+            let tx = Transaction::create_call(
+                from.clone().with_nonce(*nonce),
+                token_addr,
+                0,
+                call_data,
+                1000,
+                None,
+                DEFAULT_CHAIN_ID,
+            );
+            *nonce += 1;
+            Transaction::run_standard_tx(&tx, state, None, BlockEnv::default())
+                .unwrap()
+                .error
+        };
+        let reverted = |error: Option<ExecError>| match error {
+            Some(ExecError::Reverted { reason, .. }) => reason,
+            other => panic!("expected a revert, got {:?}", other),
+        };
+
+        assert_eq!(
+            reverted(call(&mut state, &alice, vec![7])),
+            UNKNOWN_SELECTOR
+        );
+        assert_eq!(
+            reverted(call(&mut state, &alice, init_call(0))),
+            INVALID_AMOUNT
+        );
+        assert_eq!(call(&mut state, &alice, init_call(100)), None);
+        assert_eq!(
+            reverted(call(&mut state, &bob, init_call(100))),
+            ALREADY_INITIALIZED
+        );
+
+        assert_eq!(call(&mut state, &alice, transfer_call(&bob_addr, 30)), None);
+        //to yourself comes out even
+        assert_eq!(call(&mut state, &bob, transfer_call(&bob_addr, 10)), None);
+        assert_eq!(
+            reverted(call(&mut state, &bob, transfer_call(&alice_addr, 31))),
+            INSUFFICIENT_BALANCE
+        );
+        assert_eq!(
+            reverted(call(&mut state, &bob, transfer_call(&alice_addr, -1))),
+            INVALID_AMOUNT
+        );
+        assert_eq!(
+            reverted(call(&mut state, &bob, vec![TRANSFER, 0, 1])),
+            INVALID_RECIPIENT
+        );
+
+        let storage = state.storage_trie_mut(token_addr);
+        assert_eq!(total_supply(storage), 100);
+        assert_eq!(balance_of(storage, &alice_addr), 70);
+        assert_eq!(balance_of(storage, &bob_addr), 30);
+        assert_eq!(balance_of(storage, &token_addr), 0);
+
+        //no call data, no selector
+        let tx = Transaction::create_transaction(
+            Some(alice.with_nonce(9)),
+            Some(token_addr),
+            5,
+            None,
+            1000,
+            DEFAULT_CHAIN_ID,
+        );
+        assert!(matches!(
+            Transaction::simulate(&tx, &state, BlockEnv::default()),
+            Err(TxError::Exec(ExecError::Reverted {
+                reason: UNKNOWN_SELECTOR,
+                ..
+            }))
+        ));
+    }
+}
@@ -0,0 +1,298 @@
+//runs the VMTests fixtures from https://github.com/ethereum/tests through the interpreter and the state.
+// Only built with `--features ethtests`, see tests/ethtests/main.rs.
+//
+// Our interpreter isn't an EVM yet, so a fixture only runs if it sticks to what we can express:
+//   - opcodes we have (plus JUMPDEST, which just gets dropped)
+//   - numbers that fit in an i32
+//   - jumps whose destination is pushed right before them - EVM jump targets are byte offsets,
+//     ours are instruction indexes, so they get rewritten
+// Anything else is skipped with the reason, and opcodes we have but that behave differently are listed in
+// KNOWN_DEVIATIONS so they're skipped too. Gas isn't compared - our schedule is made up
+
+use crate::account::address::Address;
+use crate::account::PublicAccount;
+use crate::error::ExecError;
+use crate::interpreter::{Interpreter, OPCODE};
+use crate::store::state::State;
+use crate::util::bigint::{parse_u256, to_hex, U256};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// the ethereum/tests directory these fixtures live in
+pub const VMTESTS_DIR: &str = "VMTests";
+
+/// opcodes we have that don't do what the EVM does yet. Remove an entry once it's fixed
+pub const KNOWN_DEVIATIONS: [(u8, &str); 4] = [
+    (0x04, "DIV errors on division by zero, the EVM returns 0"),
+    (0x16, "AND is logical, the EVM's is bitwise"),
+    (0x17, "OR is logical, the EVM's is bitwise"),
+    (
+        0x57,
+        "JUMPI pops the condition first and only jumps on 1, the EVM pops the destination first and jumps on anything but 0",
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Passed,
+    Skipped(String),
+    Failed(String),
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub passed: usize,
+    //reason -> how many fixtures it skipped
+    pub skipped: HashMap<String, usize>,
+    //fixture name -> what went wrong
+    pub failed: Vec<(String, String)>,
+}
+
+impl Report {
+    fn record(&mut self, name: String, outcome: Outcome) {
+        match outcome {
+            Outcome::Passed => self.passed += 1,
+            Outcome::Skipped(reason) => *self.skipped.entry(reason).or_default() += 1,
+            Outcome::Failed(reason) => self.failed.push((name, reason)),
+        }
+    }
+    pub fn skipped_count(&self) -> usize {
+        self.skipped.values().sum()
+    }
+}
+
+/// every .json file under dir, recursively
+pub fn run_dir(dir: &Path) -> Result<Report, String> {
+    let mut report = Report::default();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = fs::read_dir(&dir).map_err(|e| format!("failed to read {:?}: {}", dir, e))?;
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().map_or(false, |ext| ext == "json") {
+                let contents = fs::read_to_string(&path)
+                    .map_err(|e| format!("failed to read {:?}: {}", path, e))?;
+                let fixtures: Map<String, Value> = serde_json::from_str(&contents)
+                    .map_err(|e| format!("invalid fixture file {:?}: {}", path, e))?;
+                for (name, fixture) in fixtures {
+                    let outcome = run_fixture(&fixture);
+                    report.record(name, outcome);
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// a single test case, ie one of the top level values in a fixture file
+pub fn run_fixture(fixture: &Value) -> Outcome {
+    match try_run_fixture(fixture) {
+        Ok(outcome) => outcome,
+        Err(reason) => Outcome::Skipped(reason),
+    }
+}
+
+//Err = can't run this fixture
+fn try_run_fixture(fixture: &Value) -> Result<Outcome, String> {
+    let exec = &fixture["exec"];
+    let address = exec["address"].as_str().ok_or("no exec.address")?;
+    let code = translate(&decode_hex(exec["code"].as_str().ok_or("no exec.code")?)?)?;
+
+    let account_address = Address::from_bytes(&decode_hex(address)?)?;
+    let mut state = State::new();
+    state.put_account(
+        account_address,
+        PublicAccount {
+            address: account_address,
+            balance: U256::zero(),
+            code: code.clone(),
+            code_hash: None,
+            nonce: 0,
+            multisig: None,
+        },
+    );
+    let storage_trie = state.storage_trie_map.get_or_create(account_address);
+    if let Some(storage) = fixture["pre"][address]["storage"].as_object() {
+        for (key, value) in storage {
+            storage_trie.put(
+                to_i32(key)?.to_string(),
+                to_i32(value.as_str().ok_or("bad pre storage")?)?.to_string(),
+            );
+        }
+    }
+
+    //execute() rather than run_code(), which would undo the storage of the StackUnderflow below
+    let result = match Interpreter::new().execute(code.clone(), storage_trie) {
+        //run_code() wants something left on the stack at the end, the EVM doesn't care
+        Err(ExecError::StackUnderflow(pc))
+            if pc == code.len() || matches!(code[pc], OPCODE::STOP) =>
+        {
+            Ok(())
+        }
+        //the EVM wraps around at 2^256, we stop at i32 - nothing to compare against
+        Err(ExecError::Overflow(_)) => return Err("result doesn't fit in an i32".into()),
+        result => result.map(|_| ()),
+    };
+
+    //VMTests leave "post" out when the code is meant to fail
+    let expected_storage = match fixture.get("post") {
+        None => {
+            return Ok(match result {
+                Err(_) => Outcome::Passed,
+                Ok(_) => Outcome::Failed("expected an exception, ran fine".into()),
+            })
+        }
+        Some(post) => post[address]["storage"]
+            .as_object()
+            .cloned()
+            .unwrap_or_default(),
+    };
+    if let Err(e) = result {
+        return Ok(Outcome::Failed(format!("unexpected exception: {}", e)));
+    }
+
+    let storage_trie = state.storage_trie_map.get(&account_address).unwrap();
+    let mut expected_keys = vec![];
+    for (key, value) in expected_storage.iter() {
+        let key = to_i32(key)?.to_string();
+        let expected = parse_u256(value.as_str().ok_or("bad post storage")?)?;
+        let actual = storage_trie
+            .get(key.clone())
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse::<i32>().map(to_word))
+            .transpose()
+            .map_err(|_| format!("slot {} doesn't hold a number", key))?
+            .unwrap_or_else(U256::zero);
+        if actual != expected {
+            return Ok(Outcome::Failed(format!(
+                "slot {}: expected {}, got {}",
+                key,
+                to_hex(&expected),
+                to_hex(&actual)
+            )));
+        }
+        expected_keys.push(key);
+    }
+    //and nothing got written that the fixture doesn't expect (a slot set to 0 is the same as an empty one)
+    for (key, value) in storage_trie.entries() {
+        if !expected_keys.contains(&key) && value != "0" {
+            return Ok(Outcome::Failed(format!("unexpected write to slot {}", key)));
+        }
+    }
+    Ok(Outcome::Passed)
+}
+
+/// EVM bytecode -> our opcodes
+pub fn translate(bytecode: &[u8]) -> Result<Vec<OPCODE>, String> {
+    let mut code = vec![];
+    //byte offset of every JUMPDEST -> index of the instruction that follows it
+    let mut jumpdests = HashMap::new();
+    let mut i = 0;
+    while i < bytecode.len() {
+        let byte = bytecode[i];
+        if let Some((_, reason)) = KNOWN_DEVIATIONS.iter().find(|(op, _)| *op == byte) {
+            return Err(format!("known deviation: {}", reason));
+        }
+        let opcode = match byte {
+            0x00 => OPCODE::STOP,
+            0x01 => OPCODE::ADD,
+            0x02 => OPCODE::MUL,
+            0x03 => OPCODE::SUB,
+            0x04 => OPCODE::DIV,
+            0x10 => OPCODE::LT,
+            0x11 => OPCODE::GT,
+            0x14 => OPCODE::EQ,
+            0x16 => OPCODE::AND,
+            0x17 => OPCODE::OR,
+            0x54 => OPCODE::LOAD,
+            0x55 => OPCODE::STORE,
+            0x56 => OPCODE::JUMP,
+            0x57 => OPCODE::JUMPI,
+            0x5b => {
+                jumpdests.insert(i, code.len());
+                i += 1;
+                continue;
+            }
+            0x60..=0x7f => {
+                let size = (byte - 0x5f) as usize;
+                let bytes = bytecode
+                    .get(i + 1..i + 1 + size)
+                    .ok_or("push runs past the end of the code")?;
+                code.push(OPCODE::PUSH);
+                code.push(OPCODE::VAL(to_i32(&format!("0x{}", hex::encode(bytes)))?));
+                i += 1 + size;
+                continue;
+            }
+            _ => return Err(format!("unsupported opcode 0x{:02x}", byte)),
+        };
+        code.push(opcode);
+        i += 1;
+    }
+
+    //second pass, now that every JUMPDEST's index is known
+    for index in 0..code.len() {
+        if let OPCODE::JUMP | OPCODE::JUMPI = code[index] {
+            let destination = match index
+                .checked_sub(2)
+                .map(|push| (code[push], code[push + 1]))
+            {
+                Some((OPCODE::PUSH, OPCODE::VAL(destination))) => destination as usize,
+                _ => return Err("computed jump destination".into()),
+            };
+            //not a JUMPDEST is an exception in the EVM - -1 is one for us too
+            let destination = jumpdests.get(&destination).map_or(-1, |i| *i as i32);
+            code[index - 1] = OPCODE::VAL(destination);
+        }
+    }
+    Ok(code)
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    hex::decode(hex.trim_start_matches("0x")).map_err(|_| format!("invalid hex: {}", hex))
+}
+
+fn to_i32(number: &str) -> Result<i32, String> {
+    let number = parse_u256(number)?;
+    if number > U256::from(i32::MAX as u64) {
+        return Err("number doesn't fit in an i32".into());
+    }
+    Ok(number.as_u32() as i32)
+}
+
+/// our negative numbers as the EVM's 256 bit two's complement
+fn to_word(value: i32) -> U256 {
+    if value >= 0 {
+        U256::from(value as u64)
+    } else {
+        U256::max_value() - U256::from((-(value as i64) - 1) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_rewrites_jump_targets() {
+        //PUSH2 0x0008 JUMP PUSH1 1 PUSH1 0 JUMPDEST PUSH1 42 ...
+        let code = translate(&decode_hex("0x61000856600160005b602a").unwrap()).unwrap();
+        assert_eq!(code.len(), 9);
+        //the JUMPDEST at byte 8 is instruction 7
+        assert!(matches!(code[1], OPCODE::VAL(7)));
+        assert!(translate(&[0x56]).is_err());
+        assert!(translate(&[0x16])
+            .unwrap_err()
+            .starts_with("known deviation"));
+    }
+
+    #[test]
+    fn test_to_word() {
+        assert_eq!(to_word(5), U256::from(5));
+        assert_eq!(to_word(-1), U256::max_value());
+        assert_eq!(to_word(-2), U256::max_value() - 1);
+    }
+}
@@ -1,7 +1,14 @@
 pub mod account;
 pub mod api;
 pub mod blockchain;
+pub mod config;
+pub mod devnet;
+pub mod error;
+pub mod events;
 pub mod interpreter;
+pub mod network;
 pub mod store;
+pub mod stress;
+pub mod telemetry;
 pub mod transaction;
 pub mod util;
@@ -6,45 +6,234 @@
 
 use std::env;
 
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use rs::api::pubsub::{process_block, process_transaction, rabbit_consume};
-use rs::api::server::{replace_chain, run_server};
+use rs::account::address_book::AddressBook;
+use rs::account::commands::run_account_command;
+use rs::account::{enable_deterministic_keys, Account};
+#[cfg(feature = "rabbitmq")]
+use rs::api::pubsub::{process_block, process_transaction};
+use rs::api::server::{run_server, sync_with_retries};
+use rs::api::webhooks::dispatch_webhooks;
 
-use rs::util::prep_state;
+use rs::config::datadir::{node_id, DataDir};
+use rs::config::{NodeConfig, DEV_MINER_BALANCE};
+use rs::devnet::run_devnet_command;
+use rs::events::log_events;
+#[cfg(feature = "rabbitmq")]
+use rs::network::rabbit::{rabbit_consume, set_amqp_addr, set_consumer_lag_warn};
+use rs::network::watch_for_partition;
+use rs::store::receipts::ReceiptStore;
+use rs::store::storage::StorageStore;
+use rs::stress::run_stress_command;
+use rs::telemetry::init_tracing;
+use rs::util::{prep_state_from_mnemonic, prep_state_with_accounts};
 
 #[actix_web::main]
 async fn main() {
-    let global_state = prep_state();
-    let wrapped_gs = Arc::new(Mutex::new(global_state));
-    let mut port = 8080;
-
-    // ----------------------------------------------------------------------------- peer nodes
+    // ----------------------------------------------------------------------------- config
+    // eg: cargo run -- --port 8082 --bootnode http://localhost:8080 --datadir ./node2
+    // add --fast-sync to start from the bootnode's head state (GET /snapshot/latest) instead of running every block since genesis
+    // with a --datadir the node keeps its identity (nodekey), accounts (keystore/) and blocks (chaindata/) across restarts
+    // or put the same settings in a toml file and pass --config node2.toml (see rs::config::file::ConfigFile) - env vars and flags still override it
+    // blocks and tx get gossiped straight between nodes: add --gossip-peer <host:port> (repeatable) to connect to another node's
+    // --gossip-port (default 30303). Peers connect back, so only one side of each pair needs the other's address
+    // or list them in <datadir>/static-nodes.json, eg ["http://node1:8080", "node1:30303"] - urls get synced from like a --bootnode,
+    // host:port gets gossiped with like a --gossip-peer. Bootnodes get a few rounds of retries at startup, gossip peers get redialed until they answer
+    // built with --features rabbitmq they go through a broker instead: add --amqp-addr <url> to use a rabbitmq other than the local one
+    // and --consumer-lag-warn <n> to change how many messages can wait on the broker before the node warns it's falling behind (default 1000)
+    // add --partition-threshold <n> to change how many block times (13s) a node with peers can go without a block from them before
+    // it warns it's likely cut off from the network and GET /health starts answering 503 (default 10)
+    // add --measure-propagation to note when each tx first reaches the node - GET /propagation/report lines that up across nodes
+    // add --no-mining for a node that only validates and relays, and --max-gas-limit <n> to cap the gas a submitted tx may ask for
+    // add --auto-mine <secs> to mine a block every secs seconds once synced (0 = as soon as there are tx) - or POST /miner/start later
+    // add --initial-reward <n> and --halving-interval <blocks> to change the block subsidy (50, never halving, by default) - same on every node
+    // add --constantinople-block <n> to activate the constantinople fork (SHL/SHR, cheaper no-op STORE) at block n - same on every node
+    // add --paris-block <n> to activate the paris fork (randao in block headers, PREVRANDAO) at block n, no earlier than constantinople
+    // add --treasury <address> to send a cut of every block to a treasury (or, with an address nobody has the key for, burn it):
+    // --treasury-fee-percent of the gas fees (default 100) and --treasury-reward-percent of the reward (default 0) - same on every node
+    // add --storage-history <n> to only keep contract storage readable (?block= / eth_getStorageAt) for the last n blocks
+    // add --receipt-history <n> to only keep receipts and logs (in memory and under <datadir>/receipts) for the last n blocks
+    // add --storage-cache <n> to change how many contracts' storage stays in memory with a --datadir (default 1024) - the rest waits under <datadir>/storage
+    // give addresses names with POST /addressbook - the rest api then takes the name anywhere it takes an address. Kept per
+    // chain id, in <datadir>/addressbook.json with a --datadir and in memory otherwise
+    // add --slot-duration-ms <ms> and --slots-per-epoch <n> to change the consensus clock's timings (12s slots, 32 per epoch by default)
+    // add --exec-timeout-ms <ms> to change how long one tx's contract code may run (default 250) - keep it the same on every node
+    // add --auth-token <token> to require "Authorization: Bearer <token>" on state-mutating endpoints
+    // add --cors-origin <origin> (repeatable, "*" for any) to let browser-based explorers call the api
+    // add --tls-cert cert.pem --tls-key key.pem to serve the api over https
+    // add --keystore-password <pw> to unlock (and encrypt) the key files under <datadir>/keystore - prefer the env var, args show up in ps
+    // add --mnemonic "<12 words>" (and --dev-accounts <n>) to derive the miner and n more accounts from a seed phrase - see `cargo run -- account mnemonic`
+    // add --genesis-alloc <address>=<amount> (repeatable) to fund accounts in the genesis state - accounts start out empty otherwise
+    // add --dev to fund the miner and turn on POST /faucet for local development
+    // add --key-seed <seed> for deterministic test mode - the same seed gives the same miner/account addresses every run (keys aren't secret!)
+    // add --log-level debug (or a filter like "info,rs::api=debug") and --log-format json to change what gets logged and how
+    // (or the same via NODE_PORT / NODE_BOOTNODE / NODE_FAST_SYNC / NODE_DATADIR / NODE_KEYSTORE_PASSWORD / NODE_MNEMONIC / NODE_DEV_ACCOUNTS / NODE_GENESIS_ALLOC / NODE_DEV / NODE_KEY_SEED / NODE_CONFIG / NODE_GOSSIP_PORT / NODE_GOSSIP_PEERS / NODE_AMQP_ADDR / NODE_CONSUMER_LAG_WARN / NODE_PARTITION_THRESHOLD / NODE_MEASURE_PROPAGATION / NODE_MINING / NODE_AUTO_MINE / NODE_MAX_GAS_LIMIT / NODE_INITIAL_REWARD / NODE_HALVING_INTERVAL / NODE_CONSTANTINOPLE_BLOCK / NODE_PARIS_BLOCK / NODE_TREASURY / NODE_TREASURY_FEE_PERCENT / NODE_TREASURY_REWARD_PERCENT / NODE_EXEC_TIMEOUT_MS / NODE_STORAGE_HISTORY / NODE_RECEIPT_HISTORY / NODE_STORAGE_CACHE / NODE_SLOT_DURATION_MS / NODE_SLOTS_PER_EPOCH / NODE_HOST / NODE_AUTH_TOKEN / NODE_CORS_ORIGINS / NODE_LOG_LEVEL / NODE_LOG_FORMAT env vars)
     let args: Vec<String> = env::args().collect();
-    if args.len() > 1 && (args[1] == "--peer" || args[1] == "-p") {
-        replace_chain(wrapped_gs.clone()).await;
-        // port = rand::random::<u16>();
-        port = 8081; //easier for debugging
+    // key management runs instead of the node, eg: cargo run -- account export --datadir ./node1 alice --passphrase <pw>
+    if args.get(1).map(String::as_str) == Some("account") {
+        match run_account_command(&args[2..], |key| env::var(key).ok()) {
+            Ok(output) => println!("{}", output),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    // a local network of --nodes in-process nodes taking turns mining, no rabbitmq needed, eg: cargo run -- devnet --nodes 4 --blocks 20
+    if args.get(1).map(String::as_str) == Some("devnet") {
+        if let Err(e) = run_devnet_command(&args[2..]) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    // load test of a single node from a funded genesis state, eg: cargo run --release -- stress --accounts 1000 --txs 5000 --block-size 500
+    if args.get(1).map(String::as_str) == Some("stress") {
+        match run_stress_command(&args[2..]) {
+            Ok(report) => println!("{}", report),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    let config = NodeConfig::load(&args).expect("invalid node config");
+    init_tracing(&config).expect("failed to set up logging");
+    #[cfg(feature = "rabbitmq")]
+    {
+        set_amqp_addr(&config.amqp_addr);
+        set_consumer_lag_warn(config.consumer_lag_warn);
+    }
+    // <datadir>/keystore, chaindata, nodekey and (optionally) config.toml - see DataDir
+    let datadir = config.datadir.as_deref().map(DataDir::new);
+    if let Some(datadir) = &datadir {
+        datadir.init().expect("failed to create datadir");
     }
 
-    // ----------------------------------------------------------------------------- listen for blocks & txs
+    if let Some(seed) = &config.key_seed {
+        enable_deterministic_keys(seed);
+    }
+    let mut global_state = match &config.mnemonic {
+        Some(phrase) => prep_state_from_mnemonic(phrase, config.dev_accounts, config.chain_id)
+            .expect("invalid mnemonic"),
+        None => prep_state_with_accounts(Account::new(vec![]), vec![], config.chain_id),
+    };
+    // ----------------------------------------------------------------------------- genesis state
+    let genesis_state = &mut global_state.blockchain.get_mut().unwrap().state;
+    if config.dev {
+        genesis_state.allocate(global_state.miner_address, DEV_MINER_BALANCE);
+    }
+    for (address, balance) in &config.genesis_alloc {
+        genesis_state.allocate(*address, *balance);
+    }
+    //before any blocks get replayed from chaindata, so they're checked against the chain's rules and pruned as they go
+    let blockchain = global_state.blockchain.get_mut().unwrap();
+    blockchain.rules = config.chain_rules();
+    blockchain.storage_history_depth = config.storage_history;
+    blockchain.receipt_history = config.receipt_history;
+    global_state.sync.get_mut().unwrap().partition_threshold = config.partition_threshold;
+    global_state.propagation.get_mut().unwrap().enabled = config.measure_propagation;
+
+    if let Some(datadir) = &datadir {
+        let node_key = datadir
+            .load_or_create_node_key()
+            .expect("failed to load node key");
+        global_state.node_id = node_id(&node_key);
+        global_state
+            .keystore
+            .get_mut()
+            .unwrap()
+            .open(datadir.keystore(), config.keystore_password.clone())
+            .expect("failed to open keystore");
+        *global_state.address_book.get_mut().unwrap() =
+            AddressBook::open(&datadir.address_book()).expect("failed to open address book");
+        //after the genesis allocations - the persisted blocks get replayed on top of them
+        let blockchain = global_state.blockchain.get_mut().unwrap();
+        blockchain.receipt_store =
+            Some(ReceiptStore::open(&datadir.receipts()).expect("failed to open receipt store"));
+        //before the replay, so the tries it builds can already be spilled
+        let storage_store = StorageStore::open(&datadir.storage(), config.storage_cache)
+            .expect("failed to open storage store");
+        blockchain
+            .state
+            .storage_trie_map
+            .set_store(Some(Arc::new(storage_store)));
+        blockchain
+            .open(datadir.chaindata())
+            .expect("failed to load chaindata");
+    }
+    tracing::info!(node_id = %global_state.node_id, "node identity");
+    let wrapped_gs = Arc::new(global_state);
+
+    // ----------------------------------------------------------------------------- events
+    //subscribed before syncing, so the sync's reorg gets logged (and POSTed to webhooks) too
+    tokio::spawn(log_events(wrapped_gs.events.subscribe()));
+    tokio::spawn(dispatch_webhooks(
+        wrapped_gs.clone(),
+        wrapped_gs.events.subscribe(),
+    ));
+
+    // ----------------------------------------------------------------------------- peer nodes
+    //runs alongside the server, which reports how far along it is on GET /sync and eth_syncing
+    //a node restarted from chaindata is most of the way there already, a full sync only runs the blocks it's missing
+    let fast_sync = config.fast_sync && wrapped_gs.blockchain.read().unwrap().chain.len() == 1;
+    let bootnodes = config.bootnodes.clone();
+    let auto_mine = config.auto_mine;
+    #[cfg(not(feature = "rabbitmq"))]
+    let (gossip_addr, gossip_peers) = (config.gossip_addr(), config.gossip_peers.clone());
     let gs_clone = wrapped_gs.clone();
-    let gs_clone2 = wrapped_gs.clone();
-    tokio::spawn(async move {
-        rabbit_consume(process_block, gs_clone, "blocks")
-            .await
-            .unwrap();
-    });
     tokio::spawn(async move {
-        rabbit_consume(process_transaction, gs_clone2, "tx")
-            .await
-            .unwrap();
+        if let Err(e) = sync_with_retries(gs_clone.clone(), &bootnodes, fast_sync).await {
+            //starting from genesis instead would put us on a fork of our own
+            tracing::error!(error = %e, "failed to sync from any bootnode");
+            std::process::exit(1);
+        }
+
+        // ------------------------------------------------------------------------- listen for blocks & txs
+        //only once synced - until then a peer's new block doesn't fit on top of our chain
+        #[cfg(not(feature = "rabbitmq"))]
+        {
+            let gossip = gs_clone.gossip.clone();
+            if let Err(e) = gossip
+                .start(gs_clone.clone(), &gossip_addr, &gossip_peers)
+                .await
+            {
+                tracing::error!(addr = %gossip_addr, error = %e, "failed to start gossip");
+                std::process::exit(1);
+            }
+        }
+        #[cfg(feature = "rabbitmq")]
+        {
+            let (gs_clone1, gs_clone2) = (gs_clone.clone(), gs_clone.clone());
+            tokio::spawn(async move {
+                if let Err(e) = rabbit_consume(process_block, gs_clone1, "blocks").await {
+                    tracing::error!(error = %e, "stopped listening for blocks");
+                }
+            });
+            tokio::spawn(async move {
+                if let Err(e) = rabbit_consume(process_transaction, gs_clone2, "tx").await {
+                    tracing::error!(error = %e, "stopped listening for tx");
+                }
+            });
+        }
+        //on top of the synced head, like a /mine would be
+        if let Some(secs) = auto_mine {
+            let interval = Some(secs).filter(|&secs| secs > 0);
+            gs_clone
+                .miner
+                .lock()
+                .unwrap()
+                .start(gs_clone.clone(), interval)
+                .expect("failed to start the auto miner");
+        }
+        //from here on, peers going quiet for too long means something is wrong
+        tokio::spawn(watch_for_partition(gs_clone));
     });
 
     // ----------------------------------------------------------------------------- server
-    println!("listening on port {}", &port);
-    run_server(&format!("localhost:{}", port), wrapped_gs)
-        .unwrap()
-        .await
-        .unwrap();
+    tracing::info!(addr = %config.bind_addr(), tls = config.tls_cert.is_some(), "listening");
+    run_server(&config, wrapped_gs).unwrap().await.unwrap();
 }
@@ -0,0 +1,341 @@
+use crate::api::pubsub::{process_block, process_transaction};
+use crate::blockchain::block::MAX_BLOCK_BYTES;
+use crate::error::NetError;
+use crate::telemetry::metrics;
+use crate::util::{keccak_bytes, GlobalState};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+/// how many message ids a node remembers. One that comes back after that gets processed (and rejected) again,
+/// which is all the dedup saves
+pub const SEEN_MESSAGES: usize = 10_000;
+/// the most one message can take up on the wire - a full block plus the envelope around it
+pub const MAX_MESSAGE_BYTES: usize = MAX_BLOCK_BYTES + 1024;
+/// messages waiting to be written to one peer. A peer that falls further behind than this misses some - it'll
+/// usually get them from another peer, and a missed block is picked up on the next one anyway
+pub const PEER_BACKLOG: usize = 256;
+/// how long before a peer that dropped (or never answered) gets dialed again
+pub const REDIAL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// one line of json on the wire
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GossipMessage {
+    /// "blocks" or "tx", same as the rabbitmq exchanges
+    pub topic: String,
+    pub payload: String,
+}
+
+impl GossipMessage {
+    //not keccak_hash() - that sorts the characters first, and two different messages can sort the same
+    pub fn id(&self) -> String {
+        keccak_bytes(format!("{}\n{}", self.topic, self.payload).as_bytes())
+    }
+}
+
+/// bounded set of message ids, the oldest goes first when it's full
+#[derive(Debug)]
+pub struct SeenMessages {
+    capacity: usize,
+    ids: HashSet<String>,
+    //oldest first
+    order: VecDeque<String>,
+}
+
+impl SeenMessages {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ids: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// false if it was already there
+    pub fn insert(&mut self, id: String) -> bool {
+        if self.ids.contains(&id) {
+            return false;
+        }
+        if self.order.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        self.order.push_back(id.clone());
+        self.ids.insert(id);
+        true
+    }
+}
+
+/// flood gossip over plain tcp. Every node passes what it hasn't seen before on to all its other peers, so a
+/// message reaches everyone connected to the network through anyone, no broker in the middle. Peers come from
+/// --gossip-peer and from whoever connects to us. Nothing comes in or goes out until start()
+#[derive(Debug)]
+pub struct Gossip {
+    //peer id -> its outbox
+    peers: Mutex<HashMap<usize, mpsc::Sender<String>>>,
+    next_peer_id: AtomicUsize,
+    seen: Mutex<SeenMessages>,
+}
+
+impl Default for Gossip {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gossip {
+    pub fn new() -> Self {
+        Self {
+            peers: Mutex::new(HashMap::new()),
+            next_peer_id: AtomicUsize::new(0),
+            seen: Mutex::new(SeenMessages::new(SEEN_MESSAGES)),
+        }
+    }
+
+    /// listens on addr and dials every peer, each one redialed whenever its connection drops. What comes in from
+    /// then on goes to process_block() / process_transaction(). Returns the address it ended up listening on
+    pub async fn start(
+        self: &Arc<Self>,
+        global_state: Arc<GlobalState>,
+        addr: &str,
+        peers: &[String],
+    ) -> Result<SocketAddr, NetError> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        tokio::spawn(self.clone().accept(listener, global_state.clone()));
+        for peer in peers {
+            tokio::spawn(self.clone().dial(peer.clone(), global_state.clone()));
+        }
+        tracing::info!(addr = %local_addr, peers = peers.len(), "gossip started");
+        Ok(local_addr)
+    }
+
+    /// sends to every connected peer, returns how many that was. Doesn't process it ourselves, see
+    /// network::broadcast()
+    pub fn publish(&self, topic: &str, payload: String) -> usize {
+        let message = GossipMessage {
+            topic: topic.into(),
+            payload,
+        };
+        self.seen.lock().unwrap().insert(message.id());
+        let sent = self.send(&message, None);
+        tracing::debug!(topic, peers = sent, ">>> gossiped message");
+        sent
+    }
+
+    pub fn peer_count(&self) -> usize {
+        self.peers.lock().unwrap().len()
+    }
+
+    fn send(&self, message: &GossipMessage, except: Option<usize>) -> usize {
+        let line = serde_json::to_string(message).unwrap();
+        let peers = self.peers.lock().unwrap();
+        peers
+            .iter()
+            .filter(|(id, _)| Some(**id) != except)
+            .filter(|(_, outbox)| outbox.try_send(line.clone()).is_ok())
+            .count()
+    }
+
+    //a message we've seen before stops here, that's what keeps it from going round in circles. Only what we could
+    // make sense of gets passed on - a block we reject still does, we might just be behind
+    fn receive(
+        &self,
+        from: usize,
+        line: &str,
+        global_state: Arc<GlobalState>,
+    ) -> Result<(), NetError> {
+        let message: GossipMessage =
+            serde_json::from_str(line).map_err(|e| NetError::Decode(e.to_string()))?;
+        let processor: fn(String, Arc<GlobalState>) -> Result<(), NetError> =
+            match message.topic.as_str() {
+                "blocks" => process_block,
+                "tx" => process_transaction,
+                topic => return Err(NetError::Decode(format!("unknown topic: {}", topic))),
+            };
+        if !self.seen.lock().unwrap().insert(message.id()) {
+            return Ok(());
+        }
+        tracing::debug!(topic = %message.topic, peer = from, "<<< got gossip");
+        let started = Instant::now();
+        let processed = processor(message.payload.clone(), global_state);
+        metrics::record_delivery(&message.topic, started.elapsed(), processed.is_ok());
+        processed?;
+        self.send(&message, Some(from));
+        Ok(())
+    }
+
+    async fn accept(self: Arc<Self>, listener: TcpListener, global_state: Arc<GlobalState>) {
+        loop {
+            match listener.accept().await {
+                Ok((stream, addr)) => {
+                    let peer =
+                        self.clone()
+                            .run_peer(stream, addr.to_string(), global_state.clone());
+                    tokio::spawn(peer);
+                }
+                Err(e) => tracing::warn!(error = %e, "failed to accept gossip peer"),
+            }
+        }
+    }
+
+    async fn dial(self: Arc<Self>, addr: String, global_state: Arc<GlobalState>) {
+        loop {
+            match TcpStream::connect(&addr).await {
+                Ok(stream) => {
+                    self.clone()
+                        .run_peer(stream, addr.clone(), global_state.clone())
+                        .await
+                }
+                Err(e) => tracing::debug!(peer = %addr, error = %e, "failed to dial gossip peer"),
+            }
+            tokio::time::sleep(REDIAL_INTERVAL).await;
+        }
+    }
+
+    //until the connection drops or the peer sends a line over MAX_MESSAGE_BYTES. A line that isn't gossip only
+    // gets that one line dropped
+    async fn run_peer(
+        self: Arc<Self>,
+        stream: TcpStream,
+        addr: String,
+        global_state: Arc<GlobalState>,
+    ) {
+        let id = self.next_peer_id.fetch_add(1, Ordering::Relaxed);
+        let (reader, mut writer) = stream.into_split();
+        let (outbox, mut queued) = mpsc::channel::<String>(PEER_BACKLOG);
+        self.peers.lock().unwrap().insert(id, outbox);
+        tracing::info!(peer = %addr, "gossip peer connected");
+
+        let writing = tokio::spawn(async move {
+            while let Some(mut line) = queued.recv().await {
+                line.push('\n');
+                if writer.write_all(line.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let mut reader = BufReader::new(reader);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = (&mut reader)
+                .take(MAX_MESSAGE_BYTES as u64 + 1)
+                .read_line(&mut line)
+                .await;
+            match read {
+                Ok(0) => break,
+                //cut off by the limit, or by the peer going away half way through
+                Ok(_) if !line.ends_with('\n') => {
+                    if line.len() > MAX_MESSAGE_BYTES {
+                        tracing::warn!(peer = %addr, "gossip message over the size limit, disconnecting");
+                    }
+                    break;
+                }
+                Ok(_) => {
+                    if let Err(e) = self.receive(id, line.trim_end(), global_state.clone()) {
+                        tracing::warn!(peer = %addr, error = %e, "dropped gossip message");
+                    }
+                }
+                Err(e) => {
+                    tracing::debug!(peer = %addr, error = %e, "failed to read from gossip peer");
+                    break;
+                }
+            }
+        }
+        self.peers.lock().unwrap().remove(&id);
+        writing.abort();
+        tracing::info!(peer = %addr, "gossip peer disconnected");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::network::broadcast;
+    use crate::store::codec;
+    use crate::transaction::tx::Transaction;
+    use crate::util::prep_state;
+
+    #[test]
+    fn test_seen_messages() {
+        let mut seen = SeenMessages::new(2);
+        assert!(seen.insert("a".into()));
+        assert!(!seen.insert("a".into()));
+        assert!(seen.insert("b".into()));
+        //a goes to make room
+        assert!(seen.insert("c".into()));
+        assert!(seen.insert("a".into()));
+        assert!(!seen.insert("c".into()));
+    }
+
+    #[test]
+    fn test_message_ids_keep_order() {
+        let message = |payload: &str| GossipMessage {
+            topic: "tx".into(),
+            payload: payload.into(),
+        };
+        assert_eq!(message("ab").id(), message("ab").id());
+        assert_ne!(message("ab").id(), message("ba").id());
+    }
+
+    //rabbitmq builds broadcast through the broker instead
+    #[cfg(not(feature = "rabbitmq"))]
+    #[actix_rt::test]
+    async fn test_tx_reaches_peer() {
+        let (a, b) = (Arc::new(prep_state()), Arc::new(prep_state()));
+        let b_addr = b.gossip.start(b.clone(), "127.0.0.1:0", &[]).await.unwrap();
+        a.gossip
+            .start(a.clone(), "127.0.0.1:0", &[b_addr.to_string()])
+            .await
+            .unwrap();
+        while a.gossip.peer_count() == 0 || b.gossip.peer_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let tx = Transaction::create_transaction(
+            Some(Account::new(vec![])),
+            None,
+            0,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        let payload = codec::encode_hex(&tx);
+        broadcast(&a, payload.clone(), "tx").await.unwrap();
+        //the miner's account creation from prep_state() + ours, on both sides
+        let queued = |state: &GlobalState| state.tx_queue.lock().unwrap().get_tx_series().len();
+        assert_eq!(queued(&a), 2);
+        let started = Instant::now();
+        while queued(&b) < 2 {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "tx never arrived"
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        //already seen, so it isn't processed (or passed on) a second time
+        let line = serde_json::to_string(&GossipMessage {
+            topic: "tx".into(),
+            payload,
+        })
+        .unwrap();
+        assert!(a.gossip.receive(0, &line, a.clone()).is_ok());
+        assert_eq!(queued(&a), 2);
+        assert!(matches!(
+            a.gossip
+                .receive(0, "{\"topic\":\"nope\",\"payload\":\"\"}", a.clone()),
+            Err(NetError::Decode(_))
+        ));
+    }
+}
@@ -0,0 +1,62 @@
+pub mod gossip;
+pub mod propagation;
+#[cfg(feature = "rabbitmq")]
+pub mod rabbit;
+
+use crate::error::NetError;
+use crate::util::GlobalState;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// how often watch_for_partition() checks on the blocks coming in from peers
+pub const PARTITION_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// sends a block ("blocks") or a tx ("tx") to every other node, over the built-in gossip. With rabbitmq our own tx
+/// came back to us through the fanout like everyone else's - here it goes into our queue directly. A block we mined
+/// gets added by the miner
+#[cfg(not(feature = "rabbitmq"))]
+pub async fn broadcast(
+    global_state: &GlobalState,
+    payload: String,
+    topic: &str,
+) -> Result<(), NetError> {
+    if topic == "tx" {
+        crate::api::pubsub::queue_transaction(&payload, global_state)?;
+    }
+    global_state.gossip.publish(topic, payload);
+    Ok(())
+}
+
+/// sends a block ("blocks") or a tx ("tx") to every other node, through the rabbitmq broker
+#[cfg(feature = "rabbitmq")]
+pub async fn broadcast(
+    _global_state: &GlobalState,
+    payload: String,
+    topic: &str,
+) -> Result<(), NetError> {
+    rabbit::rabbit_publish(payload, topic).await
+}
+
+/// how many nodes we're gossiping with
+#[cfg(not(feature = "rabbitmq"))]
+pub fn peer_count(global_state: &GlobalState) -> Option<u64> {
+    Some(global_state.gossip.peer_count() as u64)
+}
+
+/// None - every node just talks to the broker, so there's no knowing
+#[cfg(feature = "rabbitmq")]
+pub fn peer_count(_global_state: &GlobalState) -> Option<u64> {
+    None
+}
+
+/// runs for as long as the node does, from when it starts taking blocks from peers. GlobalState::peer_health() warns
+/// when they stop coming in - this makes sure it gets checked with nobody calling GET /health
+pub async fn watch_for_partition(global_state: Arc<GlobalState>) {
+    let now = global_state.blockchain.read().unwrap().clock.now_millis();
+    global_state.sync.lock().unwrap().start_listening(now);
+    let mut interval = tokio::time::interval(PARTITION_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        global_state.peer_health();
+    }
+}
@@ -0,0 +1,194 @@
+//instrumentation for --measure-propagation: every node notes when it first saw each tx, and GET /propagation/report
+// lines those up across nodes to see how long gossip (or the broker) takes to get a tx everywhere
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use utoipa::ToSchema;
+
+/// how many tx a node remembers first-seen times for. The oldest are dropped first
+pub const MAX_TRACKED_TX: usize = 10_000;
+
+/// tx hash -> when this node first saw the tx, as unix ms. Records nothing unless the node runs with
+/// --measure-propagation
+#[derive(Debug, Default)]
+pub struct PropagationLog {
+    pub enabled: bool,
+    first_seen: HashMap<String, i64>,
+    //insertion order, for dropping the oldest
+    order: VecDeque<String>,
+}
+
+impl PropagationLog {
+    /// a tx seen before keeps its first time
+    pub fn record(&mut self, tx_hash: &str, now: i64) {
+        if !self.enabled || self.first_seen.contains_key(tx_hash) {
+            return;
+        }
+        if self.order.len() == MAX_TRACKED_TX {
+            if let Some(oldest) = self.order.pop_front() {
+                self.first_seen.remove(&oldest);
+            }
+        }
+        self.first_seen.insert(tx_hash.to_string(), now);
+        self.order.push_back(tx_hash.to_string());
+    }
+    pub fn first_seen(&self) -> &HashMap<String, i64> {
+        &self.first_seen
+    }
+}
+
+/// one node's side of the experiment, as GET /propagation reports it
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FirstSeen {
+    pub node_id: String,
+    /// tx hash -> unix ms
+    pub first_seen: HashMap<String, i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct NodePropagation {
+    pub node_id: String,
+    /// how many of the tx it saw
+    pub seen: usize,
+    /// how many of those it saw before anyone else - most likely it's where they were submitted
+    pub first: usize,
+    /// how far behind the first node it was, over the tx it wasn't first for
+    pub average_delay_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PropagationReport {
+    pub tx_count: usize,
+    /// seen by every node that answered
+    pub fully_propagated: usize,
+    /// over every sighting but the first of each tx - how long after the first node the others saw it
+    pub median_delay_ms: Option<i64>,
+    pub p90_delay_ms: Option<i64>,
+    pub max_delay_ms: Option<i64>,
+    pub nodes: Vec<NodePropagation>,
+    /// the nodes that couldn't be asked, and why
+    pub unreachable: Vec<String>,
+}
+
+/// lines up what each node saw. Only as good as the nodes' clocks agree - run them on one machine, or with ntp
+pub fn aggregate(reports: &[FirstSeen]) -> PropagationReport {
+    let mut sightings: HashMap<&str, Vec<(usize, i64)>> = HashMap::new();
+    for (node, report) in reports.iter().enumerate() {
+        for (tx_hash, at) in &report.first_seen {
+            sightings
+                .entry(tx_hash.as_str())
+                .or_default()
+                .push((node, *at));
+        }
+    }
+
+    let mut first = vec![0; reports.len()];
+    let mut node_delays = vec![vec![]; reports.len()];
+    let mut delays = vec![];
+    for seen in sightings.values() {
+        let (origin, start) = *seen.iter().min_by_key(|(_, at)| *at).unwrap();
+        first[origin] += 1;
+        for &(node, at) in seen.iter().filter(|(node, _)| *node != origin) {
+            node_delays[node].push(at - start);
+            delays.push(at - start);
+        }
+    }
+    delays.sort_unstable();
+
+    let nodes = reports
+        .iter()
+        .zip(first)
+        .zip(node_delays)
+        .map(|((report, first), node_delays)| NodePropagation {
+            node_id: report.node_id.clone(),
+            seen: report.first_seen.len(),
+            first,
+            average_delay_ms: Some(node_delays.len())
+                .filter(|&count| count > 0)
+                .map(|count| node_delays.iter().sum::<i64>() / count as i64),
+        })
+        .collect();
+    PropagationReport {
+        tx_count: sightings.len(),
+        fully_propagated: sightings
+            .values()
+            .filter(|seen| seen.len() == reports.len())
+            .count(),
+        median_delay_ms: percentile(&delays, 50),
+        p90_delay_ms: percentile(&delays, 90),
+        max_delay_ms: delays.last().copied(),
+        nodes,
+        unreachable: vec![],
+    }
+}
+
+//nearest rank, rounding down
+fn percentile(sorted: &[i64], percent: usize) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    Some(sorted[(sorted.len() - 1) * percent / 100])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first_seen(node_id: &str, seen: &[(&str, i64)]) -> FirstSeen {
+        FirstSeen {
+            node_id: node_id.into(),
+            first_seen: seen
+                .iter()
+                .map(|(tx_hash, at)| (tx_hash.to_string(), *at))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_log_keeps_first_sighting() {
+        let mut log = PropagationLog::default();
+        log.record("a", 1);
+        assert!(log.first_seen().is_empty());
+
+        log.enabled = true;
+        log.record("a", 1);
+        log.record("a", 2);
+        assert_eq!(log.first_seen().get("a"), Some(&1));
+
+        for i in 0..MAX_TRACKED_TX as i64 {
+            log.record(&i.to_string(), i);
+        }
+        assert_eq!(log.first_seen().len(), MAX_TRACKED_TX);
+        assert_eq!(log.first_seen().get("a"), None);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let report = aggregate(&[
+            first_seen("n1", &[("a", 100), ("b", 230)]),
+            first_seen("n2", &[("a", 110), ("b", 200)]),
+            first_seen("n3", &[("a", 140)]),
+        ]);
+        assert_eq!(report.tx_count, 2);
+        assert_eq!(report.fully_propagated, 1);
+        //delays are 10 and 40 for a, 30 for b
+        assert_eq!(report.median_delay_ms, Some(30));
+        assert_eq!(report.max_delay_ms, Some(40));
+        assert_eq!(
+            report.nodes[0],
+            NodePropagation {
+                node_id: "n1".into(),
+                seen: 2,
+                first: 1,
+                average_delay_ms: Some(30),
+            }
+        );
+        assert_eq!(report.nodes[1].first, 1);
+        assert_eq!(report.nodes[1].average_delay_ms, Some(10));
+        assert_eq!(report.nodes[2].average_delay_ms, Some(40));
+
+        let empty = aggregate(&[]);
+        assert_eq!(empty.tx_count, 0);
+        assert_eq!(empty.median_delay_ms, None);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::config::{DEFAULT_AMQP_ADDR, DEFAULT_CONSUMER_LAG_WARN};
+use crate::error::NetError;
+use crate::telemetry::metrics;
+use crate::util::GlobalState;
+use futures_util::stream::StreamExt;
+use lapin::{
+    options::*, types::FieldTable, BasicProperties, Channel, Connection, ConnectionProperties,
+    ExchangeKind, Promise,
+};
+use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+lazy_static! {
+    //set once at startup from the node config, see set_amqp_addr()
+    static ref AMQP_ADDR: RwLock<String> = RwLock::new(DEFAULT_AMQP_ADDR.into());
+}
+
+static CONSUMER_LAG_WARN: AtomicU32 = AtomicU32::new(DEFAULT_CONSUMER_LAG_WARN);
+
+/// how many unacked deliveries the broker hands a consumer at once. Without a limit it pushes the whole backlog
+/// into the client straight away, and the queue always looks empty when we ask how far behind we are
+pub const CONSUMER_PREFETCH: u16 = 64;
+/// how often a consumer asks the broker how many messages are still waiting for it
+pub const LAG_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// every connection opened after this goes to the new broker
+pub fn set_amqp_addr(addr: &str) {
+    *AMQP_ADDR.write().unwrap() = addr.into();
+}
+
+/// how many messages can be waiting on the broker before a consumer warns that it's falling behind
+pub fn set_consumer_lag_warn(messages: u32) {
+    CONSUMER_LAG_WARN.store(messages, Ordering::Relaxed);
+}
+
+pub async fn rabbit_connect() -> Result<Connection, NetError> {
+    let addr = AMQP_ADDR.read().unwrap().clone();
+    let conn = Connection::connect(&addr, ConnectionProperties::default()).await?;
+    tracing::info!(addr = %addr, "connected to RabbitMQ");
+
+    Ok(conn)
+}
+
+pub fn create_ex_if_doesnt_exist(channel: &Channel, exchange: &str) -> Promise<()> {
+    channel.exchange_declare(
+        exchange,
+        ExchangeKind::Fanout, //important for blockchain to be blockchain
+        ExchangeDeclareOptions::default(),
+        FieldTable::default(),
+    )
+}
+
+pub async fn rabbit_publish(payload: String, exchange: &str) -> Result<(), NetError> {
+    let conn = rabbit_connect().await?;
+    let channel_a = conn.create_channel().await?;
+    let _ex = create_ex_if_doesnt_exist(&channel_a, exchange);
+
+    let _confirm = channel_a
+        .basic_publish(
+            exchange, //subscribe tou our exchange
+            "", //when using fanout, we don't need to specify routing_key -https://www.rabbitmq.com/tutorials/tutorial-three-python.html
+            BasicPublishOptions::default(),
+            payload.as_bytes().to_vec(),
+            BasicProperties::default(),
+        )
+        .await?
+        .await?;
+
+    tracing::debug!(exchange, payload = %payload, ">>> published payload");
+    Ok(())
+}
+
+/// a message the processor can't use gets logged and dropped - one bad peer shouldn't stop us listening to the rest.
+/// Deliveries, failures, processing time and how far behind the broker we are go to /metrics, labelled by exchange
+pub async fn rabbit_consume(
+    processor: fn(String, Arc<GlobalState>) -> Result<(), NetError>,
+    global_state: Arc<GlobalState>,
+    exchange: &str,
+) -> Result<(), NetError> {
+    let conn = rabbit_connect().await?;
+    let channel_b = conn.create_channel().await?;
+    let _ex = create_ex_if_doesnt_exist(&channel_b, exchange); //needed in both, as sometimes this thread will run ahead of producer
+    channel_b
+        .basic_qos(CONSUMER_PREFETCH, BasicQosOptions::default())
+        .await?;
+
+    // create a tmp queue
+    let q_opts = QueueDeclareOptions {
+        exclusive: true,
+        ..QueueDeclareOptions::default()
+    };
+    let queue = channel_b
+        .queue_declare(
+            "",     //when a name is not specified, a random name is given
+            q_opts, //exclusive=true means q will be deleted after, which is what we want
+            FieldTable::default(),
+        )
+        .await?;
+    tracing::debug!(queue = %queue.name(), exchange, "declared a tmp queue");
+
+    // bind the tmp queue to the exchange, otherwise the exchange won't know to fanout msgs to this q
+    let _ = channel_b.queue_bind(
+        &queue.name().to_string(),
+        exchange,
+        "", //again no need to specify coz using fanout
+        QueueBindOptions::default(),
+        FieldTable::default(),
+    );
+
+    let mut consumer = channel_b
+        .basic_consume(
+            &queue.name().to_string(),
+            "my_consumer",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+
+    let mut last_lag_check = Instant::now();
+    while let Some(delivery) = consumer.next().await {
+        let (_channel, delivery) = delivery?;
+        tracing::debug!(
+            exchange,
+            delivery_tag = delivery.delivery_tag,
+            "<<< got delivery"
+        );
+        delivery.ack(BasicAckOptions::default()).await?;
+
+        //restore into string and send for processing
+        let started = Instant::now();
+        let processed = String::from_utf8(delivery.data)
+            .map_err(|e| NetError::Decode(e.to_string()))
+            .and_then(|data| processor(data, global_state.clone()));
+        metrics::record_delivery(exchange, started.elapsed(), processed.is_ok());
+        if let Err(e) = processed {
+            tracing::warn!(exchange, error = %e, "dropped message");
+        }
+
+        if last_lag_check.elapsed() >= LAG_CHECK_INTERVAL {
+            last_lag_check = Instant::now();
+            //a passive declare doesn't change the queue, it just reports on it
+            let passive = QueueDeclareOptions {
+                passive: true,
+                ..QueueDeclareOptions::default()
+            };
+            let lag = channel_b
+                .queue_declare(&queue.name().to_string(), passive, FieldTable::default())
+                .await?
+                .message_count();
+            let threshold = CONSUMER_LAG_WARN.load(Ordering::Relaxed);
+            if metrics::record_lag(exchange, lag, threshold) {
+                tracing::warn!(
+                    exchange,
+                    lag,
+                    threshold,
+                    "consumer is falling behind the broker"
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
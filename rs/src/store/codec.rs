@@ -0,0 +1,447 @@
+use crate::account::address::Address;
+use crate::error::CodecError;
+use crate::store::rlp::{self, Rlp};
+use crate::util::bigint::U256;
+use crate::util::keccak_bytes;
+use secp256k1::Signature;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use uuid::Uuid;
+
+/// the first byte of everything encode() puts out. Only needs bumping for a change tags can't express - adding a
+/// field never does, see Record
+pub const CODEC_VERSION: u8 = 1;
+
+/// the binary encoding blocks and tx are hashed, persisted and sent to peers in. Unlike their json it doesn't depend
+/// on how the struct happens to be declared - see Record for how fields get laid out
+pub trait Encode {
+    fn to_rlp(&self) -> Rlp;
+}
+
+pub trait Decode: Sized {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError>;
+}
+
+/// the version byte, then the value. What goes to disk and over the wire
+pub fn encode<T: Encode>(value: &T) -> Vec<u8> {
+    let mut bytes = vec![CODEC_VERSION];
+    bytes.extend(canonical(value));
+    bytes
+}
+
+pub fn decode<T: Decode>(bytes: &[u8]) -> Result<T, CodecError> {
+    match bytes.split_first() {
+        Some((&CODEC_VERSION, body)) => decode_canonical(body),
+        Some((version, _)) => Err(CodecError::UnsupportedVersion(*version)),
+        None => Err(CodecError::Malformed("nothing to decode".into())),
+    }
+}
+
+/// encode(), hex - the payload of a gossip message / rabbitmq delivery
+pub fn encode_hex<T: Encode>(value: &T) -> String {
+    hex::encode(encode(value))
+}
+
+pub fn decode_hex<T: Decode>(payload: &str) -> Result<T, CodecError> {
+    let bytes = hex::decode(payload).map_err(|e| CodecError::Malformed(e.to_string()))?;
+    decode(&bytes)
+}
+
+/// the value without the version byte. What hashes and roots commit to, so they stay put whatever the version
+pub fn canonical<T: Encode>(value: &T) -> Vec<u8> {
+    rlp::encode(&value.to_rlp())
+}
+
+pub fn decode_canonical<T: Decode>(bytes: &[u8]) -> Result<T, CodecError> {
+    let item = rlp::decode(bytes).ok_or_else(|| CodecError::Malformed("not rlp".into()))?;
+    T::from_rlp(&item)
+}
+
+/// keccak256 of canonical()
+pub fn hash<T: Encode>(value: &T) -> String {
+    keccak_bytes(&canonical(value))
+}
+
+/// a struct, as a list of (tag, value) pairs in tag order. A field's tag never changes or gets reused, so:
+///  - a field added later gets a new tag, and has to be left out while it holds its default - then everything
+///    encoded before it existed still decodes (to the default) and still hashes the same
+///  - a tag the decoder doesn't know is skipped, so an older node can still read what a newer one wrote
+#[derive(Debug, Default)]
+pub struct Record(Vec<Rlp>);
+
+impl Record {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// tags have to come in increasing order
+    pub fn field(mut self, tag: u64, value: &impl Encode) -> Self {
+        self.0.push(Rlp::List(vec![tag.to_rlp(), value.to_rlp()]));
+        self
+    }
+
+    /// left out altogether when it's None
+    pub fn optional<T: Encode>(self, tag: u64, value: &Option<T>) -> Self {
+        match value {
+            Some(value) => self.field(tag, value),
+            None => self,
+        }
+    }
+
+    pub fn build(self) -> Rlp {
+        Rlp::List(self.0)
+    }
+}
+
+/// a Record on the way back, by tag
+#[derive(Debug)]
+pub struct Fields<'a>(BTreeMap<u64, &'a Rlp>);
+
+impl<'a> Fields<'a> {
+    pub fn of(item: &'a Rlp) -> Result<Self, CodecError> {
+        let mut fields = BTreeMap::new();
+        let mut last_tag = None;
+        for field in list(item)? {
+            match list(field)? {
+                [tag, value] => {
+                    let tag = u64::from_rlp(tag)?;
+                    //one way to encode a value, so one hash for it
+                    if last_tag.is_some_and(|last| tag <= last) {
+                        return Err(CodecError::Malformed("fields out of order".into()));
+                    }
+                    last_tag = Some(tag);
+                    fields.insert(tag, value);
+                }
+                _ => {
+                    return Err(CodecError::Malformed(
+                        "a field isn't a tag and a value".into(),
+                    ))
+                }
+            }
+        }
+        Ok(Self(fields))
+    }
+
+    pub fn get<T: Decode>(&self, tag: u64) -> Result<T, CodecError> {
+        self.optional(tag)?.ok_or(CodecError::MissingField(tag))
+    }
+
+    pub fn optional<T: Decode>(&self, tag: u64) -> Result<Option<T>, CodecError> {
+        self.0.get(&tag).map(|value| T::from_rlp(value)).transpose()
+    }
+
+    /// for fields that were added later, see Record
+    pub fn or_default<T: Decode + Default>(&self, tag: u64) -> Result<T, CodecError> {
+        Ok(self.optional(tag)?.unwrap_or_default())
+    }
+}
+
+pub fn bytes(item: &Rlp) -> Result<&[u8], CodecError> {
+    match item {
+        Rlp::Bytes(bytes) => Ok(bytes),
+        Rlp::List(_) => Err(CodecError::Malformed("expected bytes, got a list".into())),
+    }
+}
+
+pub fn list(item: &Rlp) -> Result<&[Rlp], CodecError> {
+    match item {
+        Rlp::List(items) => Ok(items),
+        Rlp::Bytes(_) => Err(CodecError::Malformed("expected a list, got bytes".into())),
+    }
+}
+
+//big endian without leading zeroes, so 0 is no bytes at all
+fn uint_bytes(be: &[u8]) -> Rlp {
+    Rlp::Bytes(be.iter().copied().skip_while(|byte| *byte == 0).collect())
+}
+
+fn uint_from_bytes(item: &Rlp, max_len: usize) -> Result<&[u8], CodecError> {
+    let bytes = bytes(item)?;
+    if bytes.len() > max_len {
+        return Err(CodecError::Malformed(format!(
+            "{} bytes is too long for the integer",
+            bytes.len()
+        )));
+    }
+    if bytes.first() == Some(&0) {
+        return Err(CodecError::Malformed("integer with leading zeroes".into()));
+    }
+    Ok(bytes)
+}
+
+impl Encode for u64 {
+    fn to_rlp(&self) -> Rlp {
+        uint_bytes(&self.to_be_bytes())
+    }
+}
+
+impl Decode for u64 {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        let bytes = uint_from_bytes(item, 8)?;
+        Ok(bytes.iter().fold(0, |n, byte| (n << 8) | *byte as u64))
+    }
+}
+
+impl Encode for u128 {
+    fn to_rlp(&self) -> Rlp {
+        uint_bytes(&self.to_be_bytes())
+    }
+}
+
+impl Decode for u128 {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        let bytes = uint_from_bytes(item, 16)?;
+        Ok(bytes.iter().fold(0, |n, byte| (n << 8) | *byte as u128))
+    }
+}
+
+//as a u64, so it doesn't matter what it is on the machine that wrote it
+impl Encode for usize {
+    fn to_rlp(&self) -> Rlp {
+        (*self as u64).to_rlp()
+    }
+}
+
+impl Decode for usize {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        u64::from_rlp(item)?
+            .try_into()
+            .map_err(|_| CodecError::Malformed("integer too big for usize".into()))
+    }
+}
+
+//zigzag - 0, -1, 1, -2... as 0, 1, 2, 3..., so small negatives stay short too
+impl Encode for i64 {
+    fn to_rlp(&self) -> Rlp {
+        (((*self << 1) ^ (*self >> 63)) as u64).to_rlp()
+    }
+}
+
+impl Decode for i64 {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        let zigzag = u64::from_rlp(item)?;
+        Ok((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64))
+    }
+}
+
+impl Encode for i32 {
+    fn to_rlp(&self) -> Rlp {
+        (*self as i64).to_rlp()
+    }
+}
+
+impl Decode for i32 {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        i64::from_rlp(item)?
+            .try_into()
+            .map_err(|_| CodecError::Malformed("integer too big for i32".into()))
+    }
+}
+
+impl Encode for U256 {
+    fn to_rlp(&self) -> Rlp {
+        let mut be = [0; 32];
+        self.to_big_endian(&mut be);
+        uint_bytes(&be)
+    }
+}
+
+impl Decode for U256 {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        Ok(U256::from_big_endian(uint_from_bytes(item, 32)?))
+    }
+}
+
+impl Encode for String {
+    fn to_rlp(&self) -> Rlp {
+        Rlp::Bytes(self.as_bytes().to_vec())
+    }
+}
+
+impl Decode for String {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        String::from_utf8(bytes(item)?.to_vec()).map_err(|e| CodecError::Malformed(e.to_string()))
+    }
+}
+
+//20 bytes
+impl Encode for Address {
+    fn to_rlp(&self) -> Rlp {
+        Rlp::Bytes(self.as_bytes().to_vec())
+    }
+}
+
+impl Decode for Address {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        Address::from_bytes(bytes(item)?).map_err(CodecError::Malformed)
+    }
+}
+
+//compact, 64 bytes
+impl Encode for Signature {
+    fn to_rlp(&self) -> Rlp {
+        Rlp::Bytes(self.serialize_compact().to_vec())
+    }
+}
+
+impl Decode for Signature {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        Signature::from_compact(bytes(item)?).map_err(|e| CodecError::Malformed(e.to_string()))
+    }
+}
+
+impl Encode for Uuid {
+    fn to_rlp(&self) -> Rlp {
+        Rlp::Bytes(self.as_bytes().to_vec())
+    }
+}
+
+impl Decode for Uuid {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        Uuid::from_slice(bytes(item)?).map_err(|e| CodecError::Malformed(e.to_string()))
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn to_rlp(&self) -> Rlp {
+        Rlp::List(self.iter().map(Encode::to_rlp).collect())
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        list(item)?.iter().map(T::from_rlp).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Old {
+        a: u64,
+        b: String,
+    }
+
+    //Old with a field added since
+    #[derive(Debug, PartialEq)]
+    struct New {
+        a: u64,
+        b: String,
+        c: Option<i64>,
+    }
+
+    impl Encode for Old {
+        fn to_rlp(&self) -> Rlp {
+            Record::new().field(1, &self.a).field(2, &self.b).build()
+        }
+    }
+
+    impl Decode for Old {
+        fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+            let fields = Fields::of(item)?;
+            Ok(Self {
+                a: fields.get(1)?,
+                b: fields.get(2)?,
+            })
+        }
+    }
+
+    impl Encode for New {
+        fn to_rlp(&self) -> Rlp {
+            Record::new()
+                .field(1, &self.a)
+                .field(2, &self.b)
+                .optional(3, &self.c)
+                .build()
+        }
+    }
+
+    impl Decode for New {
+        fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+            let fields = Fields::of(item)?;
+            Ok(Self {
+                a: fields.get(1)?,
+                b: fields.get(2)?,
+                c: fields.optional(3)?,
+            })
+        }
+    }
+
+    #[test]
+    fn test_integers_roundtrip() {
+        for n in [0, 1, 127, 128, 255, 256, u64::MAX].iter() {
+            assert_eq!(decode_canonical::<u64>(&canonical(n)), Ok(*n));
+        }
+        for n in [0, -1, 1, i64::MIN, i64::MAX].iter() {
+            assert_eq!(decode_canonical::<i64>(&canonical(n)), Ok(*n));
+        }
+        let big = U256::max_value() - 1;
+        assert_eq!(decode_canonical::<U256>(&canonical(&big)), Ok(big));
+        assert_eq!(canonical(&0u64), vec![0x80]);
+        //the same number with a leading zero would hash differently, so it's not accepted
+        assert!(u64::from_rlp(&Rlp::Bytes(vec![0, 1])).is_err());
+        assert!(u64::from_rlp(&Rlp::Bytes(vec![1; 9])).is_err());
+    }
+
+    #[test]
+    fn test_versions() {
+        let old = Old {
+            a: 7,
+            b: "x".into(),
+        };
+        let bytes = encode(&old);
+        assert_eq!(bytes[0], CODEC_VERSION);
+        assert_eq!(decode::<Old>(&bytes), Ok(old));
+        assert_eq!(
+            decode_hex::<Old>(&hex::encode(&bytes)),
+            decode::<Old>(&bytes)
+        );
+
+        let mut future = bytes;
+        future[0] = CODEC_VERSION + 1;
+        assert_eq!(
+            decode::<Old>(&future),
+            Err(CodecError::UnsupportedVersion(CODEC_VERSION + 1))
+        );
+        assert!(decode::<Old>(&[]).is_err());
+        assert!(decode_hex::<Old>("not hex").is_err());
+    }
+
+    #[test]
+    fn test_fields_come_and_go() {
+        //written before c existed, read after
+        let old = Old {
+            a: 7,
+            b: "x".into(),
+        };
+        let new = decode::<New>(&encode(&old)).unwrap();
+        assert_eq!(new.c, None);
+        //and with c left at its default, it's the same bytes, so the same hash
+        assert_eq!(hash(&new), hash(&old));
+
+        //written after, read by something that doesn't know c
+        let new = New {
+            a: 7,
+            b: "x".into(),
+            c: Some(-3),
+        };
+        assert_eq!(decode::<Old>(&encode(&new)), Ok(old));
+        assert_ne!(
+            hash(&new),
+            hash(&Old {
+                a: 7,
+                b: "x".into()
+            })
+        );
+
+        let missing = Record::new().field(1, &7u64).build();
+        assert_eq!(Old::from_rlp(&missing), Err(CodecError::MissingField(2)));
+        let out_of_order = Record::new()
+            .field(2, &"x".to_string())
+            .field(1, &7u64)
+            .build();
+        assert!(Old::from_rlp(&out_of_order).is_err());
+    }
+}
@@ -1,2 +1,7 @@
+pub mod codec;
+pub mod overlay;
+pub mod receipts;
+pub mod rlp;
 pub mod state;
+pub mod storage;
 pub mod trie;
@@ -0,0 +1,152 @@
+use crate::account::address::Address;
+use crate::account::PublicAccount;
+use crate::error::StoreError;
+use crate::store::state::{State, StateAccess};
+use crate::store::trie::Trie;
+use std::collections::HashMap;
+
+/// a throwaway layer on top of the canonical State for speculative execution - validation, eth_call, gas estimation,
+/// the miner's preflight. Reads fall through to the state underneath, writes stay in the overlay and are gone once
+/// it's dropped. Only the accounts and storage tries that actually get written are copied, never the whole state
+#[derive(Debug, Clone)]
+pub struct OverlayState<'a> {
+    base: &'a State,
+    accounts: HashMap<Address, PublicAccount>,
+    //copied from the base the first time a contract's storage gets touched
+    storage_tries: HashMap<Address, Trie>,
+}
+
+impl<'a> OverlayState<'a> {
+    pub fn new(base: &'a State) -> Self {
+        Self {
+            base,
+            accounts: HashMap::new(),
+            storage_tries: HashMap::new(),
+        }
+    }
+
+    /// how many accounts / storage tries the overlay has written to
+    pub fn touched(&self) -> (usize, usize) {
+        (self.accounts.len(), self.storage_tries.len())
+    }
+
+    /// what the overlay wrote, without the borrow of the base - so it can be applied once the base is writable
+    pub fn into_writes(self) -> StateWrites {
+        StateWrites {
+            accounts: self.accounts,
+            storage_tries: self.storage_tries,
+        }
+    }
+}
+
+/// an overlay's writes, waiting to go into the state it was on top of - see State::apply()
+#[derive(Debug, Clone, Default)]
+pub struct StateWrites {
+    pub accounts: HashMap<Address, PublicAccount>,
+    pub storage_tries: HashMap<Address, Trie>,
+}
+
+impl<'a> StateAccess for OverlayState<'a> {
+    fn get_account(&self, address: Address) -> Result<PublicAccount, StoreError> {
+        match self.accounts.get(&address) {
+            Some(account) => Ok(account.clone()),
+            None => self.base.get_account(address),
+        }
+    }
+    fn find_account(&self, address: Address) -> Option<PublicAccount> {
+        match self.accounts.get(&address) {
+            Some(account) => Some(account.clone()),
+            None => self.base.find_account(address),
+        }
+    }
+    fn put_account(&mut self, address: Address, account_data: PublicAccount) {
+        self.accounts.insert(address, account_data);
+    }
+    fn storage_trie_mut(&mut self, address: Address) -> &mut Trie {
+        let base = self.base;
+        self.storage_tries.entry(address).or_insert_with(|| {
+            base.storage_trie_map
+                .get(&address)
+                .map_or_else(Trie::new, |trie| trie.into_owned())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::interpreter::{Interpreter, OPCODE};
+    use crate::util::bigint::U256;
+
+    #[test]
+    fn test_writes_never_reach_the_base() {
+        let sender = Account::new(vec![]).public_account.address;
+        let mut state = State::new();
+        state.allocate(sender, 100);
+        let state_root = state.get_state_root().clone();
+
+        let mut overlay = OverlayState::new(&state);
+        let mut account = overlay.get_account(sender).unwrap();
+        account.balance = U256::from(1);
+        overlay.put_account(sender, account);
+        assert_eq!(overlay.get_account(sender).unwrap().balance, U256::from(1));
+
+        let contract = Account::new(vec![]).public_account.address;
+        let code = vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(7),
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::STORE,
+            OPCODE::STOP,
+        ];
+        Interpreter::new()
+            .run_code(code, overlay.storage_trie_mut(contract))
+            .unwrap();
+        assert_eq!(
+            overlay.storage_trie_mut(contract).get("1".into()),
+            Some(&"7".to_string())
+        );
+        assert_eq!(overlay.touched(), (1, 1));
+
+        drop(overlay);
+        assert_eq!(state.get_account(sender).unwrap().balance, U256::from(100));
+        assert!(state.storage_trie_map.get(&contract).is_none());
+        assert_eq!(state.get_state_root(), &state_root);
+    }
+
+    #[test]
+    fn test_writes_apply_like_direct_ones() {
+        let sender = Account::new(vec![]).public_account.address;
+        let receiver = Account::new(vec![]).public_account.address;
+        let mut state = State::new();
+        state.allocate(sender, 100);
+        let mut direct = state.clone();
+
+        let mut overlay = OverlayState::new(&state);
+        let mut account = overlay.get_account(sender).unwrap();
+        account.balance = U256::from(60);
+        overlay.put_account(sender, account.clone());
+        overlay.put_account(receiver, overlay.get_account_or_empty(receiver));
+        let writes = overlay.into_writes();
+        state.apply(writes);
+
+        direct.put_account(sender, account);
+        direct.put_account(receiver, direct.get_account_or_empty(receiver));
+        assert_eq!(state.get_state_root(), direct.get_state_root());
+        assert!(state.storage_trie_map.contains(&receiver));
+    }
+
+    #[test]
+    fn test_reads_fall_through() {
+        let address = Account::new(vec![]).public_account.address;
+        let mut state = State::new();
+        assert!(OverlayState::new(&state).find_account(address).is_none());
+
+        state.allocate(address, 5);
+        let overlay = OverlayState::new(&state);
+        assert_eq!(overlay.get_account_or_empty(address).balance, U256::from(5));
+        assert_eq!(overlay.touched(), (0, 0));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::error::StoreError;
+use crate::transaction::receipt::Receipt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// a block's receipts (and so its logs), one json file per block: <number>-<block hash>.json. Kept apart from the
+/// chaindata, so how long they're kept for doesn't depend on how long the blocks are. The number leads the name so
+/// pruning can go by height without opening anything - blocks dropped in a reorg included
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiptStore {
+    dir: PathBuf,
+}
+
+impl ReceiptStore {
+    pub fn open(dir: &Path) -> Result<Self, StoreError> {
+        fs::create_dir_all(dir)
+            .map_err(|e| StoreError::Io(format!("failed to create {:?}: {}", dir, e)))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    fn path(&self, block_number: usize, block_hash: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}-{}.json", block_number, block_hash))
+    }
+
+    /// overwrites whatever was there - running the same block twice gives the same receipts
+    pub fn put(
+        &self,
+        block_number: usize,
+        block_hash: &str,
+        receipts: &[Receipt],
+    ) -> Result<(), StoreError> {
+        let path = self.path(block_number, block_hash);
+        fs::write(&path, serde_json::to_string(receipts).unwrap())
+            .map_err(|e| StoreError::Io(format!("failed to write {:?}: {}", path, e)))
+    }
+
+    /// empty for a block we have no receipts for
+    pub fn get(&self, block_number: usize, block_hash: &str) -> Result<Vec<Receipt>, StoreError> {
+        let path = self.path(block_number, block_hash);
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        read_receipts(&path)
+    }
+
+    /// every receipt from block `from` onwards, orphaned blocks included
+    pub fn load(&self, from: usize) -> Result<Vec<Receipt>, StoreError> {
+        let mut receipts = vec![];
+        for (number, path) in self.entries()? {
+            if number >= from {
+                receipts.extend(read_receipts(&path)?);
+            }
+        }
+        Ok(receipts)
+    }
+
+    /// deletes every block's receipts from before `from`. Returns how many blocks' worth went
+    pub fn prune(&self, from: usize) -> Result<usize, StoreError> {
+        let mut pruned = 0;
+        for (number, path) in self.entries()? {
+            if number < from {
+                fs::remove_file(&path)
+                    .map_err(|e| StoreError::Io(format!("failed to remove {:?}: {}", path, e)))?;
+                pruned += 1;
+            }
+        }
+        Ok(pruned)
+    }
+
+    //block number -> file. Anything not named like one of ours is left alone
+    fn entries(&self) -> Result<Vec<(usize, PathBuf)>, StoreError> {
+        let dir = fs::read_dir(&self.dir)
+            .map_err(|e| StoreError::Io(format!("failed to read {:?}: {}", self.dir, e)))?;
+        let mut entries = vec![];
+        for entry in dir {
+            let path = entry
+                .map_err(|e| StoreError::Io(format!("failed to read {:?}: {}", self.dir, e)))?
+                .path();
+            let number = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.split('-').next())
+                .and_then(|number| number.parse::<usize>().ok());
+            if let Some(number) = number {
+                entries.push((number, path));
+            }
+        }
+        entries.sort();
+        Ok(entries)
+    }
+}
+
+fn read_receipts(path: &Path) -> Result<Vec<Receipt>, StoreError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| StoreError::Io(format!("failed to read {:?}: {}", path, e)))?;
+    serde_json::from_str(&contents).map_err(|e| StoreError::Corrupt {
+        key: format!("{:?}", path),
+        reason: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::receipt::ReceiptStatus;
+
+    fn receipt(block_number: usize, block_hash: &str) -> Receipt {
+        Receipt {
+            tx_hash: uuid::Uuid::new_v4().to_string(),
+            block_number,
+            block_hash: block_hash.into(),
+            transaction_index: 0,
+            status: ReceiptStatus::Success,
+            gas_used: 1,
+            cumulative_gas_used: 1,
+            contract_address: None,
+            logs: vec!["log".into()],
+        }
+    }
+
+    #[test]
+    fn test_receipt_store() {
+        let dir = std::env::temp_dir().join(format!("receipts-{}", uuid::Uuid::new_v4()));
+        let store = ReceiptStore::open(&dir).unwrap();
+        for number in 1..=3 {
+            let hash = format!("hash{}", number);
+            store.put(number, &hash, &[receipt(number, &hash)]).unwrap();
+        }
+        //an orphaned block at the same height
+        store.put(1, "orphan", &[receipt(1, "orphan")]).unwrap();
+
+        let found = store.get(2, "hash2").unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].logs, vec!["log".to_string()]);
+        assert!(store.get(4, "hash4").unwrap().is_empty());
+        assert_eq!(store.load(2).unwrap().len(), 2);
+
+        //reopened from disk, as after a restart
+        let store = ReceiptStore::open(&dir).unwrap();
+        assert_eq!(store.load(0).unwrap().len(), 4);
+        assert_eq!(store.prune(2), Ok(2));
+        assert!(store.get(1, "orphan").unwrap().is_empty());
+        assert_eq!(store.load(0).unwrap().len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
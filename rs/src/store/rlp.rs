@@ -0,0 +1,146 @@
+/// ethereum's recursive length prefix encoding - only byte strings and lists of them, nothing else. The trie encodes
+/// its nodes with it, see Trie
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rlp {
+    Bytes(Vec<u8>),
+    List(Vec<Rlp>),
+}
+
+pub fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    //a single byte under 0x80 is its own encoding
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return vec![bytes[0]];
+    }
+    let mut encoded = encode_length(bytes.len(), 0x80);
+    encoded.extend_from_slice(bytes);
+    encoded
+}
+
+/// items have to be encoded already
+pub fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload = items.concat();
+    let mut encoded = encode_length(payload.len(), 0xc0);
+    encoded.extend(payload);
+    encoded
+}
+
+pub fn encode(item: &Rlp) -> Vec<u8> {
+    match item {
+        Rlp::Bytes(bytes) => encode_bytes(bytes),
+        Rlp::List(items) => encode_list(&items.iter().map(encode).collect::<Vec<_>>()),
+    }
+}
+
+/// None unless bytes is exactly one item, nothing left over
+pub fn decode(bytes: &[u8]) -> Option<Rlp> {
+    match decode_item(bytes)? {
+        (item, []) => Some(item),
+        _ => None,
+    }
+}
+
+//up to 55 bytes the length goes in the prefix itself, past that the prefix says how many bytes the length takes
+fn encode_length(len: usize, offset: u8) -> Vec<u8> {
+    if len < 56 {
+        return vec![offset + len as u8];
+    }
+    let len_bytes = len
+        .to_be_bytes()
+        .iter()
+        .copied()
+        .skip_while(|byte| *byte == 0)
+        .collect::<Vec<u8>>();
+    let mut encoded = vec![offset + 55 + len_bytes.len() as u8];
+    encoded.extend(len_bytes);
+    encoded
+}
+
+//the item at the start of bytes, and whatever comes after it
+fn decode_item(bytes: &[u8]) -> Option<(Rlp, &[u8])> {
+    let prefix = *bytes.first()?;
+    let (is_list, header_len, len) = match prefix {
+        0x00..=0x7f => return Some((Rlp::Bytes(vec![prefix]), &bytes[1..])),
+        0x80..=0xb7 => (false, 1, (prefix - 0x80) as usize),
+        0xb8..=0xbf => {
+            let len_len = (prefix - 0xb7) as usize;
+            (
+                false,
+                1 + len_len,
+                decode_length(bytes.get(1..1 + len_len)?)?,
+            )
+        }
+        0xc0..=0xf7 => (true, 1, (prefix - 0xc0) as usize),
+        0xf8..=0xff => {
+            let len_len = (prefix - 0xf7) as usize;
+            (
+                true,
+                1 + len_len,
+                decode_length(bytes.get(1..1 + len_len)?)?,
+            )
+        }
+    };
+    let end = header_len.checked_add(len)?;
+    let payload = bytes.get(header_len..end)?;
+    let rest = &bytes[end..];
+    if !is_list {
+        return Some((Rlp::Bytes(payload.to_vec()), rest));
+    }
+    let mut items = vec![];
+    let mut payload = payload;
+    while !payload.is_empty() {
+        let (item, left) = decode_item(payload)?;
+        items.push(item);
+        payload = left;
+    }
+    Some((Rlp::List(items), rest))
+}
+
+fn decode_length(bytes: &[u8]) -> Option<usize> {
+    if bytes.len() > std::mem::size_of::<usize>() {
+        return None;
+    }
+    Some(
+        bytes
+            .iter()
+            .fold(0usize, |len, byte| (len << 8) | *byte as usize),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode_bytes(b"dog"), vec![0x83, b'd', b'o', b'g']);
+        assert_eq!(encode_bytes(b""), vec![0x80]);
+        assert_eq!(encode_bytes(&[0x0f]), vec![0x0f]);
+        assert_eq!(encode_bytes(&[0x80]), vec![0x81, 0x80]);
+        assert_eq!(
+            encode_list(&[encode_bytes(b"cat"), encode_bytes(b"dog")]),
+            vec![0xc8, 0x83, b'c', b'a', b't', 0x83, b'd', b'o', b'g']
+        );
+        assert_eq!(encode_list(&[]), vec![0xc0]);
+
+        let long = [b'a'; 56];
+        let encoded = encode_bytes(&long);
+        assert_eq!(encoded[..2], [0xb8, 56]);
+        assert_eq!(encoded.len(), 58);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let item = Rlp::List(vec![
+            Rlp::Bytes(b"cat".to_vec()),
+            Rlp::List(vec![Rlp::Bytes(vec![]), Rlp::Bytes(vec![0x7f])]),
+            Rlp::Bytes(vec![b'x'; 1000]),
+        ]);
+        assert_eq!(decode(&encode(&item)), Some(item));
+
+        //truncated, or with something left over
+        let encoded = encode_bytes(b"dog");
+        assert_eq!(decode(&encoded[..3]), None);
+        assert_eq!(decode(&[encoded.as_slice(), &[0x01]].concat()), None);
+        assert_eq!(decode(&[]), None);
+    }
+}
@@ -1,25 +1,33 @@
+use crate::account::address::Address;
 use crate::account::PublicAccount;
+use crate::error::StoreError;
+use crate::store::overlay::StateWrites;
+use crate::store::storage::StorageTries;
 use crate::store::trie::Trie;
-use secp256k1::bitcoin_hashes::hex::ToHex;
-use secp256k1::PublicKey;
+use crate::util::bigint::U256;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct State {
     pub state_trie: Trie,
-    pub storage_trie_map: HashMap<PublicKey, Trie>,
+    pub storage_trie_map: StorageTries,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl State {
     pub fn new() -> Self {
         Self {
             state_trie: Trie::new(),
-            storage_trie_map: HashMap::new(),
+            storage_trie_map: StorageTries::new(),
         }
     }
-    pub fn put_account(&mut self, address: PublicKey, account_data: PublicAccount) {
-        if self.storage_trie_map.get(&address).is_none() {
+    pub fn put_account(&mut self, address: Address, account_data: PublicAccount) {
+        if !self.storage_trie_map.contains(&address) {
             self.storage_trie_map.insert(address, Trie::new());
         }
 
@@ -32,16 +40,82 @@ impl State {
         self.state_trie
             .put(address.to_hex(), serialized_account_data);
     }
-    pub fn get_account(&mut self, address: PublicKey) -> PublicAccount {
+    /// an error if the account hasn't been written to the trie yet - see find_account() / get_account_or_empty()
+    pub fn get_account(&self, address: Address) -> Result<PublicAccount, StoreError> {
         let account_str = self
             .state_trie
             .get(address.to_hex())
-            .expect("ACCOUNT DOESNT EXIST YET. PLEASE CREATE IT FIRST.");
+            .filter(|account_str| !account_str.is_empty())
+            .ok_or_else(|| StoreError::AccountNotFound(address.to_string()))?;
 
         //account gets deserialized from string here, because trie can be used for other things but Accounts
-        serde_json::from_str::<PublicAccount>(account_str).unwrap()
+        serde_json::from_str::<PublicAccount>(account_str).map_err(|e| StoreError::Corrupt {
+            key: address.to_string(),
+            reason: e.to_string(),
+        })
+    }
+    pub fn find_account(&self, address: Address) -> Option<PublicAccount> {
+        //only put_account() writes to the state trie, so an entry that doesn't parse is a bug, not bad input
+        self.state_trie
+            .get(address.to_hex())
+            .filter(|account_str| !account_str.is_empty())
+            .map(|account_str| serde_json::from_str::<PublicAccount>(account_str).unwrap())
+    }
+    /// same as in real ethereum - an address nobody has written to yet is simply an empty account
+    pub fn get_account_or_empty(&self, address: Address) -> PublicAccount {
+        StateAccess::get_account_or_empty(self, address)
+    }
+    /// genesis allocation - credits the address directly, without a tx. Only meant for building the initial state
+    pub fn allocate(&mut self, address: Address, balance: impl Into<U256>) {
+        let mut account = self.get_account_or_empty(address);
+        account.balance = account.balance.saturating_add(balance.into());
+        self.put_account(address, account);
     }
     pub fn get_state_root(&self) -> &String {
         &self.state_trie.root_hash
     }
+    /// writes an overlay's changes through, see OverlayState::into_writes(). Storage first, so put_account()
+    /// doesn't give a contract that was just written to an empty trie
+    pub fn apply(&mut self, writes: StateWrites) {
+        self.storage_trie_map.extend(writes.storage_tries);
+        for (address, account) in writes.accounts {
+            self.put_account(address, account);
+        }
+    }
+}
+
+/// what running and validating tx needs from the world state. Implemented by the canonical State and by OverlayState,
+/// so the same code can run for real or speculatively
+pub trait StateAccess {
+    fn get_account(&self, address: Address) -> Result<PublicAccount, StoreError>;
+    fn find_account(&self, address: Address) -> Option<PublicAccount>;
+    fn put_account(&mut self, address: Address, account_data: PublicAccount);
+    /// the account's storage trie, created empty if it doesn't have one yet
+    fn storage_trie_mut(&mut self, address: Address) -> &mut Trie;
+
+    fn get_account_or_empty(&self, address: Address) -> PublicAccount {
+        self.find_account(address).unwrap_or(PublicAccount {
+            address,
+            balance: U256::zero(),
+            code: vec![],
+            code_hash: None,
+            nonce: 0,
+            multisig: None,
+        })
+    }
+}
+
+impl StateAccess for State {
+    fn get_account(&self, address: Address) -> Result<PublicAccount, StoreError> {
+        State::get_account(self, address)
+    }
+    fn find_account(&self, address: Address) -> Option<PublicAccount> {
+        State::find_account(self, address)
+    }
+    fn put_account(&mut self, address: Address, account_data: PublicAccount) {
+        State::put_account(self, address, account_data)
+    }
+    fn storage_trie_mut(&mut self, address: Address) -> &mut Trie {
+        self.storage_trie_map.get_or_create(address)
+    }
 }
@@ -0,0 +1,455 @@
+use crate::account::address::Address;
+use crate::error::StoreError;
+use crate::store::trie::{NodeStore, Trie};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// how many contracts' storage tries stay in memory with a datadir, unless --storage-cache says otherwise
+pub const DEFAULT_STORAGE_CACHE: usize = 1024;
+
+/// what the nodes of storage tries that drop out of memory get appended to, under the store's dir
+pub const NODES_FILE: &str = "nodes.bin";
+
+/// where storage tries go once they drop out of memory - node by node, keyed by hash, so a trie gets read back a
+/// path at a time rather than whole (see Trie::stored()). Nothing in it outlives the process - the state gets rebuilt
+/// from chaindata on every start, so open() starts it out empty.
+/// (!) nodes are only ever appended, so the file grows with every change to a trie that gets written out - until the
+/// next start. Shared by every copy of the state, along with the cache's counters
+#[derive(Debug)]
+pub struct StorageStore {
+    capacity: usize,
+    nodes: Mutex<NodeFile>,
+    hits: AtomicU64,
+    loads: AtomicU64,
+    evictions: AtomicU64,
+}
+
+#[derive(Debug)]
+struct NodeFile {
+    path: PathBuf,
+    file: File,
+    //hash -> where its rlp starts in the file, and how long it is
+    index: HashMap<Vec<u8>, (u64, usize)>,
+}
+
+impl StorageStore {
+    /// keeps `capacity` tries in memory, at least 1
+    pub fn open(dir: &Path, capacity: usize) -> Result<Self, StoreError> {
+        if dir.exists() {
+            fs::remove_dir_all(dir)
+                .map_err(|e| StoreError::Io(format!("failed to clear {:?}: {}", dir, e)))?;
+        }
+        fs::create_dir_all(dir)
+            .map_err(|e| StoreError::Io(format!("failed to create {:?}: {}", dir, e)))?;
+        let path = dir.join(NODES_FILE);
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)
+            .map_err(|e| StoreError::Io(format!("failed to open {:?}: {}", path, e)))?;
+        Ok(Self {
+            capacity: capacity.max(1),
+            nodes: Mutex::new(NodeFile {
+                path,
+                file,
+                index: HashMap::new(),
+            }),
+            hits: AtomicU64::new(0),
+            loads: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        })
+    }
+
+    /// how many trie nodes are on disk
+    pub fn node_count(&self) -> usize {
+        self.nodes.lock().unwrap().index.len()
+    }
+}
+
+impl NodeStore for StorageStore {
+    fn read_node(&self, hash: &[u8]) -> Result<Vec<u8>, StoreError> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let (offset, len) = *nodes.index.get(hash).ok_or_else(|| StoreError::Corrupt {
+            key: hex::encode(hash),
+            reason: "no such trie node".into(),
+        })?;
+        let mut encoded = vec![0; len];
+        let NodeFile { path, file, .. } = &mut *nodes;
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut encoded))
+            .map_err(|e| StoreError::Io(format!("failed to read {:?}: {}", path, e)))?;
+        self.loads.fetch_add(1, Ordering::Relaxed);
+        Ok(encoded)
+    }
+
+    fn write_nodes(&self, nodes: Vec<(Vec<u8>, Vec<u8>)>) -> Result<(), StoreError> {
+        let mut node_file = self.nodes.lock().unwrap();
+        let NodeFile { path, file, index } = &mut *node_file;
+        let failed =
+            |e: std::io::Error| StoreError::Io(format!("failed to write {:?}: {}", path, e));
+        //appends go to the end wherever the file is at, this is only to find out where that is
+        let start = file.seek(SeekFrom::End(0)).map_err(failed)?;
+        let mut bytes = vec![];
+        let mut added = vec![];
+        for (hash, encoded) in nodes {
+            if index.contains_key(&hash) {
+                continue;
+            }
+            added.push((hash, (start + bytes.len() as u64, encoded.len())));
+            bytes.extend(encoded);
+        }
+        //the index only points at nodes once they're all in - whatever half a failed write left is never read
+        file.write_all(&bytes).map_err(failed)?;
+        index.extend(added);
+        Ok(())
+    }
+}
+
+/// what GET /metrics reports about the storage cache
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StorageCacheStats {
+    pub capacity: usize,
+    /// in memory right now
+    pub hot: usize,
+    /// only on disk right now
+    pub cold: usize,
+    /// lookups the memory could answer
+    pub hits: u64,
+    /// trie nodes read back from disk
+    pub loads: u64,
+    pub evictions: u64,
+}
+
+/// every contract's storage trie, by address. Without a store they're all kept in memory. With one (ie with a
+/// datadir) only the `capacity` most recently used stay there - the rest are written out and only their root is
+/// kept, the nodes under it get read back as lookups walk into them.
+/// (!) reads through a shared borrow (eg the api, under the read lock) can read a trie back but can't keep it, so only
+/// writes and block execution count as a use
+#[derive(Debug, Clone, Default)]
+pub struct StorageTries {
+    hot: HashMap<Address, Trie>,
+    //root hashes of the tries that are only on disk
+    cold: HashMap<Address, String>,
+    store: Option<Arc<StorageStore>>,
+    //least recently used first, same scheme as SignatureCache
+    tick: u64,
+    last_used: HashMap<Address, u64>,
+    by_use: BTreeMap<u64, Address>,
+    //written to (or possibly written to) since the last take_touched()
+    touched: HashSet<Address>,
+}
+
+impl StorageTries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// None keeps everything in memory. Anything over capacity gets written out straight away
+    pub fn set_store(&mut self, store: Option<Arc<StorageStore>>) {
+        self.store = store;
+        self.evict();
+    }
+
+    pub fn store(&self) -> Option<&Arc<StorageStore>> {
+        self.store.as_ref()
+    }
+
+    pub fn contains(&self, address: &Address) -> bool {
+        self.hot.contains_key(address) || self.cold.contains_key(address)
+    }
+
+    /// borrowed if it's in memory. If not, the nodes it needs get read from disk as it goes
+    pub fn get(&self, address: &Address) -> Option<Cow<'_, Trie>> {
+        if let Some(trie) = self.hot.get(address) {
+            self.count(|store| &store.hits);
+            return Some(Cow::Borrowed(trie));
+        }
+        let root_hash = self.cold.get(address)?;
+        Some(Cow::Owned(self.stored(root_hash)))
+    }
+
+    /// the address's trie, created empty if it doesn't have one yet. Counts as a write
+    pub fn get_or_create(&mut self, address: Address) -> &mut Trie {
+        if self.hot.contains_key(&address) {
+            self.count(|store| &store.hits);
+        } else {
+            let trie = match self.cold.remove(&address) {
+                Some(root_hash) => self.stored(&root_hash),
+                None => Trie::new(),
+            };
+            self.hot.insert(address, trie);
+        }
+        self.used(address);
+        self.evict();
+        self.hot.get_mut(&address).unwrap()
+    }
+
+    pub fn insert(&mut self, address: Address, trie: Trie) {
+        self.cold.remove(&address);
+        self.hot.insert(address, trie);
+        self.used(address);
+        self.evict();
+    }
+
+    pub fn extend(&mut self, tries: impl IntoIterator<Item = (Address, Trie)>) {
+        for (address, trie) in tries {
+            self.insert(address, trie);
+        }
+    }
+
+    /// every address with a trie, in memory or not
+    pub fn addresses(&self) -> Vec<Address> {
+        let mut addresses: Vec<Address> =
+            self.hot.keys().chain(self.cold.keys()).copied().collect();
+        addresses.sort();
+        addresses
+    }
+
+    /// what's been written to since the last call. Only these can have a new root
+    pub fn take_touched(&mut self) -> Vec<Address> {
+        let mut touched: Vec<Address> = self.touched.drain().collect();
+        touched.sort();
+        touched
+    }
+
+    /// None without a store - everything's in memory then
+    pub fn stats(&self) -> Option<StorageCacheStats> {
+        let store = self.store.as_ref()?;
+        Some(StorageCacheStats {
+            capacity: store.capacity,
+            hot: self.hot.len(),
+            cold: self.cold.len(),
+            hits: store.hits.load(Ordering::Relaxed),
+            loads: store.loads.load(Ordering::Relaxed),
+            evictions: store.evictions.load(Ordering::Relaxed),
+        })
+    }
+
+    //only ever called with a store - that's the only way anything goes cold
+    fn stored(&self, root_hash: &str) -> Trie {
+        let store = self
+            .store
+            .clone()
+            .expect("a cold storage trie without a store");
+        Trie::stored(root_hash, store)
+    }
+
+    fn count(&self, counter: impl Fn(&StorageStore) -> &AtomicU64) {
+        if let Some(store) = &self.store {
+            counter(store).fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn used(&mut self, address: Address) {
+        self.tick += 1;
+        if let Some(last) = self.last_used.insert(address, self.tick) {
+            self.by_use.remove(&last);
+        }
+        self.by_use.insert(self.tick, address);
+        self.touched.insert(address);
+    }
+
+    //writes out the least recently used tries until we're back at capacity. Only nodes that aren't on disk yet get
+    // written, so a trie that hasn't changed since it was read back just gets dropped
+    fn evict(&mut self) {
+        let store = match &self.store {
+            Some(store) => store.clone(),
+            None => return,
+        };
+        while self.hot.len() > store.capacity {
+            let (&tick, &address) = match self.by_use.iter().next() {
+                Some(oldest) => oldest,
+                None => return,
+            };
+            let trie = &self.hot[&address];
+            //it stays in memory, over capacity - better than losing it
+            if let Err(e) = trie.write_to(&*store) {
+                tracing::error!(error = %e, address = %address, "failed to write out storage trie");
+                return;
+            }
+            self.cold.insert(address, trie.root_hash.clone());
+            self.hot.remove(&address);
+            self.last_used.remove(&address);
+            self.by_use.remove(&tick);
+            store.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+//the same address -> trie map it always was, whatever's in memory
+impl Serialize for StorageTries {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.addresses()
+                .into_iter()
+                .map(|address| (address, self.get(&address).unwrap().into_owned())),
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for StorageTries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut tries = StorageTries::new();
+        tries.extend(HashMap::<Address, Trie>::deserialize(deserializer)?);
+        Ok(tries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::gen_address;
+
+    fn trie(value: &str) -> Trie {
+        let mut trie = Trie::new();
+        trie.put("1".into(), value.into());
+        trie
+    }
+
+    fn open(capacity: usize) -> (PathBuf, Arc<StorageStore>) {
+        let dir = std::env::temp_dir().join(format!("storage-{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(StorageStore::open(&dir, capacity).unwrap());
+        (dir, store)
+    }
+
+    #[test]
+    fn test_spills_least_recently_used() {
+        let (dir, store) = open(2);
+        let mut tries = StorageTries::new();
+        tries.set_store(Some(store.clone()));
+        let (a, b, c) = (gen_address(), gen_address(), gen_address());
+        tries.insert(a, trie("a"));
+        tries.insert(b, trie("b"));
+        tries.get_or_create(a);
+        tries.insert(c, trie("c"));
+
+        //b was the least recently used. A single short leaf is just the root
+        let stats = tries.stats().unwrap();
+        assert_eq!((stats.hot, stats.cold, stats.evictions), (2, 1, 1));
+        assert_eq!(store.node_count(), 1);
+        assert!(matches!(tries.get(&b), Some(Cow::Owned(_))));
+        assert_eq!(
+            tries.get(&b).unwrap().get("1".into()),
+            Some(&"b".to_string())
+        );
+        assert!(matches!(tries.get(&a), Some(Cow::Borrowed(_))));
+        assert!(tries.get(&gen_address()).is_none());
+        assert_eq!(tries.addresses().len(), 3);
+
+        //reading it back in and changing it writes the new root the next time round
+        tries.get_or_create(b).put("1".into(), "b2".into());
+        assert_eq!(
+            tries.get(&b).unwrap().get("1".into()),
+            Some(&"b2".to_string())
+        );
+        tries.get_or_create(a);
+        tries.get_or_create(c);
+        assert_eq!(
+            tries.get(&b).unwrap().get("1".into()),
+            Some(&"b2".to_string())
+        );
+        assert_eq!(store.node_count(), 4);
+        //pushing out tries that haven't changed since they were taken back writes nothing
+        tries.get_or_create(b);
+        tries.get_or_create(a);
+        assert_eq!(store.node_count(), 4);
+        //only walking into a cold trie reads anything - b's root, for the two get()s and the put()
+        let stats = tries.stats().unwrap();
+        assert_eq!((stats.loads, stats.evictions), (3, 6));
+
+        let mut touched = vec![a, b, c];
+        touched.sort();
+        assert_eq!(tries.take_touched(), touched);
+        assert!(tries.take_touched().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_reads_back_a_path_at_a_time() {
+        let (dir, store) = open(1);
+        let mut tries = StorageTries::new();
+        tries.set_store(Some(store.clone()));
+        let (a, b) = (gen_address(), gen_address());
+        let big = tries.get_or_create(a);
+        for i in 0..200 {
+            big.put(
+                i.to_string(),
+                format!("a value long enough to get its own node {}", i),
+            );
+        }
+        tries.insert(b, trie("b"));
+        let written = store.node_count();
+        assert!(written > 200);
+
+        //one key only reads the nodes on its way down
+        let loads = tries.stats().unwrap().loads;
+        assert_eq!(
+            tries.get(&a).unwrap().get("123".into()),
+            Some(&"a value long enough to get its own node 123".to_string())
+        );
+        let read = tries.stats().unwrap().loads - loads;
+        assert!(read > 1 && read < 10, "read {} nodes", read);
+
+        //taking it back and changing one key only writes that key's path
+        tries
+            .get_or_create(a)
+            .put("123".into(), "something else".into());
+        tries.get_or_create(b);
+        let rewritten = store.node_count() - written;
+        assert!(rewritten > 1 && rewritten < 10, "wrote {} nodes", rewritten);
+        assert_eq!(
+            tries.get(&a).unwrap().get("123".into()),
+            Some(&"something else".to_string())
+        );
+        assert_eq!(
+            tries.get(&a).unwrap().get("45".into()),
+            Some(&"a value long enough to get its own node 45".to_string())
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_copies_keep_reading_their_roots() {
+        let (dir, store) = open(1);
+        let mut tries = StorageTries::new();
+        let (a, b) = (gen_address(), gen_address());
+        tries.insert(a, trie("a"));
+        tries.insert(b, trie("b"));
+        //attaching the store spills whatever doesn't fit
+        tries.set_store(Some(store));
+        assert_eq!(tries.stats().unwrap().cold, 1);
+
+        //a copy still reads the trie as it was when it was taken, whatever the original does to it since
+        let copy = tries.clone();
+        tries.get_or_create(a).put("1".into(), "a2".into());
+        tries.get_or_create(b);
+        assert_eq!(
+            copy.get(&a).unwrap().get("1".into()),
+            Some(&"a".to_string())
+        );
+        assert_eq!(
+            tries.get(&a).unwrap().get("1".into()),
+            Some(&"a2".to_string())
+        );
+
+        let json = serde_json::to_string(&tries).unwrap();
+        let restored: StorageTries = serde_json::from_str(&json).unwrap();
+        assert!(restored.stats().is_none());
+        assert_eq!(
+            restored.get(&a).unwrap().get("1".into()),
+            Some(&"a2".to_string())
+        );
+        assert_eq!(
+            restored.get(&a).unwrap().root_hash,
+            tries.get(&a).unwrap().root_hash
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
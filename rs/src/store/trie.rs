@@ -1,95 +1,715 @@
+use crate::error::StoreError;
+use crate::store::codec;
+use crate::store::rlp::{self, Rlp};
 use crate::transaction::tx::Transaction;
-use crate::util::keccak_hash;
+use crate::util::keccak_bytes;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::sync::{Arc, OnceLock};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Node {
-    pub value: String,
-    pub child_map: HashMap<char, Node>,
+/// one step down the trie is one nibble (half a byte) of the key
+type Nibbles = Vec<u8>;
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+    /// the rest of the key, and the value at the end of it
+    Leaf { path: Nibbles, value: String },
+    /// a run of nibbles every key underneath shares. The child is always a branch
+    Extension { path: Nibbles, child: Box<Node> },
+    /// one child per next nibble, plus the value of a key that ends here ("" for none)
+    Branch {
+        children: Box<[Option<Node>; 16]>,
+        value: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    //only empty for a stored node that hasn't been walked into yet, see kind()
+    kind: OnceLock<NodeKind>,
+    //the node's rlp, worked out the first time it's needed. Nodes get rebuilt rather than changed in place, so a put()
+    // only has to rehash the nodes on its key's path
+    encoded: OnceLock<Vec<u8>>,
+    //set for a node that's in a NodeStore, ie one that was read back from it. Rebuilding it drops this
+    stored: Option<StoredNode>,
+}
+
+#[derive(Debug, Clone)]
+struct StoredNode {
+    hash: Vec<u8>,
+    store: Arc<dyn NodeStore>,
+}
+
+/// where a trie's nodes can be written out to and read back from, by hash, so a big trie doesn't have to be kept in
+/// memory whole. See Trie::stored() and StorageStore
+pub trait NodeStore: Debug + Send + Sync {
+    /// the node's rlp
+    fn read_node(&self, hash: &[u8]) -> Result<Vec<u8>, StoreError>;
+    /// hash -> rlp. Ones the store already has can be skipped
+    fn write_nodes(&self, nodes: Vec<(Vec<u8>, Vec<u8>)>) -> Result<(), StoreError>;
 }
 
 impl Node {
-    pub fn new() -> Self {
+    fn new(kind: NodeKind) -> Self {
+        Self {
+            kind: OnceLock::from(kind),
+            encoded: OnceLock::new(),
+            stored: None,
+        }
+    }
+
+    fn stored(hash: Vec<u8>, store: Arc<dyn NodeStore>) -> Self {
         Self {
-            value: "".into(),
-            child_map: HashMap::new(),
+            kind: OnceLock::new(),
+            encoded: OnceLock::new(),
+            stored: Some(StoredNode { hash, store }),
+        }
+    }
+
+    //reads a stored node back the first time it's needed. Its children stay in the store until they're needed too.
+    // A node we wrote ourselves not reading back means the state is gone - a restart rebuilds it from chaindata
+    fn kind(&self) -> &NodeKind {
+        self.kind.get_or_init(|| {
+            let stored = self.stored.as_ref().unwrap();
+            let lost = |reason: String| -> ! {
+                panic!(
+                    "lost a storage trie node {}: {}",
+                    hex::encode(&stored.hash),
+                    reason
+                )
+            };
+            let encoded = stored
+                .store
+                .read_node(&stored.hash)
+                .unwrap_or_else(|e| lost(e.to_string()));
+            let kind = rlp::decode(&encoded)
+                .and_then(|item| kind_from_rlp(item, &stored.store))
+                .unwrap_or_else(|| lost("doesn't decode".into()));
+            let _ = self.encoded.set(encoded);
+            kind
+        })
+    }
+
+    fn into_kind(self) -> NodeKind {
+        self.kind();
+        self.kind.into_inner().unwrap()
+    }
+
+    fn leaf(path: &[u8], value: String) -> Self {
+        Node::new(NodeKind::Leaf {
+            path: path.to_vec(),
+            value,
+        })
+    }
+
+    fn extension(path: &[u8], child: Node) -> Self {
+        Node::new(NodeKind::Extension {
+            path: path.to_vec(),
+            child: Box::new(child),
+        })
+    }
+
+    fn branch(children: Box<[Option<Node>; 16]>, value: String) -> Self {
+        Node::new(NodeKind::Branch { children, value })
+    }
+
+    //path in front of node. Whatever comes out is still a valid node - two extensions in a row become one
+    fn prefixed(path: &[u8], node: Node) -> Self {
+        if path.is_empty() {
+            return node;
+        }
+        match node.into_kind() {
+            NodeKind::Leaf { path: rest, value } => Node::leaf(&[path, &rest].concat(), value),
+            NodeKind::Extension { path: rest, child } => {
+                Node::extension(&[path, &rest].concat(), *child)
+            }
+            branch @ NodeKind::Branch { .. } => Node::extension(path, Node::new(branch)),
+        }
+    }
+
+    fn encoded(&self) -> &[u8] {
+        //reading a stored node back fills this in along the way
+        let kind = self.kind();
+        self.encoded.get_or_init(|| match kind {
+            NodeKind::Leaf { path, value } => rlp::encode_list(&[
+                rlp::encode_bytes(&hex_prefix(path, true)),
+                rlp::encode_bytes(value.as_bytes()),
+            ]),
+            NodeKind::Extension { path, child } => rlp::encode_list(&[
+                rlp::encode_bytes(&hex_prefix(path, false)),
+                child.reference(),
+            ]),
+            NodeKind::Branch { children, value } => {
+                let mut items = children
+                    .iter()
+                    .map(|child| match child {
+                        Some(child) => child.reference(),
+                        None => rlp::encode_bytes(&[]),
+                    })
+                    .collect::<Vec<_>>();
+                items.push(rlp::encode_bytes(value.as_bytes()));
+                rlp::encode_list(&items)
+            }
+        })
+    }
+
+    //how a parent points at this node - by its hash, unless the rlp is shorter than a hash, then it's just inlined.
+    // A stored node was always pointed at by its hash, so it doesn't have to be read back for this
+    fn reference(&self) -> Vec<u8> {
+        if let Some(stored) = &self.stored {
+            return rlp::encode_bytes(&stored.hash);
+        }
+        let encoded = self.encoded();
+        if encoded.len() < 32 {
+            encoded.to_vec()
+        } else {
+            rlp::encode_bytes(&keccak(encoded))
+        }
+    }
+
+    fn get(&self, path: &[u8]) -> Option<&String> {
+        match self.kind() {
+            NodeKind::Leaf { path: rest, value } if rest.as_slice() == path => Some(value),
+            NodeKind::Leaf { .. } => None,
+            NodeKind::Extension { path: rest, child } => {
+                child.get(path.strip_prefix(rest.as_slice())?)
+            }
+            NodeKind::Branch { value, .. } if path.is_empty() => {
+                Some(value).filter(|value| !value.is_empty())
+            }
+            NodeKind::Branch { children, .. } => {
+                children[path[0] as usize].as_ref()?.get(&path[1..])
+            }
+        }
+    }
+
+    fn insert(node: Option<Node>, path: &[u8], value: String) -> Node {
+        let node = match node {
+            Some(node) => node,
+            None => return Node::leaf(path, value),
+        };
+        match node.into_kind() {
+            NodeKind::Leaf {
+                path: rest,
+                value: old,
+            } => {
+                if rest.as_slice() == path {
+                    return Node::leaf(path, value);
+                }
+                //the two keys part ways after what they have in common, so a branch goes there
+                let common = common_prefix(&rest, path);
+                let mut children: Box<[Option<Node>; 16]> = Default::default();
+                let mut branch_value = String::new();
+                for (key, value) in [(rest.as_slice(), old), (path, value)] {
+                    match key.get(common) {
+                        Some(nibble) => {
+                            children[*nibble as usize] = Some(Node::leaf(&key[common + 1..], value))
+                        }
+                        None => branch_value = value,
+                    }
+                }
+                Node::prefixed(&path[..common], Node::branch(children, branch_value))
+            }
+            NodeKind::Extension { path: rest, child } => {
+                let common = common_prefix(&rest, path);
+                if common == rest.len() {
+                    return Node::extension(
+                        &rest,
+                        Node::insert(Some(*child), &path[common..], value),
+                    );
+                }
+                //the new key leaves the extension part way through, so it gets split around a branch
+                let mut children: Box<[Option<Node>; 16]> = Default::default();
+                children[rest[common] as usize] = Some(Node::prefixed(&rest[common + 1..], *child));
+                let mut branch_value = String::new();
+                match path.get(common) {
+                    Some(nibble) => {
+                        children[*nibble as usize] = Some(Node::leaf(&path[common + 1..], value))
+                    }
+                    None => branch_value = value,
+                }
+                Node::prefixed(&path[..common], Node::branch(children, branch_value))
+            }
+            NodeKind::Branch {
+                mut children,
+                value: old,
+            } => {
+                if path.is_empty() {
+                    return Node::branch(children, value);
+                }
+                let slot = &mut children[path[0] as usize];
+                *slot = Some(Node::insert(slot.take(), &path[1..], value));
+                Node::branch(children, old)
+            }
+        }
+    }
+
+    //the key has to be there - see Trie::put(). None if nothing's left of the node
+    fn remove(self, path: &[u8]) -> Option<Node> {
+        match self.into_kind() {
+            NodeKind::Leaf { .. } => None,
+            NodeKind::Extension { path: rest, child } => child
+                .remove(&path[rest.len()..])
+                .map(|child| Node::prefixed(&rest, child)),
+            NodeKind::Branch {
+                mut children,
+                mut value,
+            } => {
+                if path.is_empty() {
+                    value = String::new();
+                } else {
+                    let slot = &mut children[path[0] as usize];
+                    *slot = slot.take().and_then(|child| child.remove(&path[1..]));
+                }
+                //a branch has to branch - with one thing left in it, it folds into that
+                let left = children
+                    .iter()
+                    .positions(|child| child.is_some())
+                    .collect::<Vec<_>>();
+                match (left.as_slice(), value.is_empty()) {
+                    ([], true) => None,
+                    ([], false) => Some(Node::leaf(&[], value)),
+                    ([nibble], true) => {
+                        let child = children[*nibble].take().unwrap();
+                        Some(Node::prefixed(&[*nibble as u8], child))
+                    }
+                    _ => Some(Node::branch(children, value)),
+                }
+            }
+        }
+    }
+
+    //every hashed node from this one down that isn't in a store yet, children first. A stored node went in with
+    // everything under it, so it's skipped whole - what a put() since has rebuilt is all that's left
+    fn collect_unstored(&self, is_root: bool, nodes: &mut Vec<(Vec<u8>, Vec<u8>)>) {
+        if self.stored.is_some() {
+            return;
+        }
+        match self.kind() {
+            NodeKind::Leaf { .. } => {}
+            NodeKind::Extension { child, .. } => child.collect_unstored(false, nodes),
+            NodeKind::Branch { children, .. } => {
+                for child in children.iter().flatten() {
+                    child.collect_unstored(false, nodes);
+                }
+            }
+        }
+        //inlined nodes go in with their parent. The root is always hashed, however short it is
+        let encoded = self.encoded();
+        if is_root || encoded.len() >= 32 {
+            nodes.push((keccak(encoded), encoded.to_vec()));
+        }
+    }
+
+    fn collect_entries(&self, mut path: Nibbles, entries: &mut Vec<(Nibbles, String)>) {
+        match self.kind() {
+            NodeKind::Leaf { path: rest, value } => {
+                path.extend(rest);
+                entries.push((path, value.clone()));
+            }
+            NodeKind::Extension { path: rest, child } => {
+                path.extend(rest);
+                child.collect_entries(path, entries);
+            }
+            NodeKind::Branch { children, value } => {
+                if !value.is_empty() {
+                    entries.push((path.clone(), value.clone()));
+                }
+                for (nibble, child) in children.iter().enumerate() {
+                    if let Some(child) = child {
+                        let mut child_path = path.clone();
+                        child_path.push(nibble as u8);
+                        child.collect_entries(child_path, entries);
+                    }
+                }
+            }
         }
     }
 }
 
+/// a merkle patricia trie, the same as ethereum's - branch, extension and leaf nodes keyed by the nibbles of the key,
+/// each one rlp encoded and referred to by its keccak256 hash. So the root hash commits to every key and value, and
+/// one key can be proven with just the nodes on its path. Keys are strings, their utf8 bytes are what gets walked
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "TrieEntries", try_from = "TrieEntries")]
 pub struct Trie {
-    pub head: Node,
+    root: Option<Node>,
     pub root_hash: String,
 }
 
+//what goes over the wire and to disk - the entries, not the nodes. The nodes are rebuilt from them on the way back,
+// and have to come out with the same root
+#[derive(Serialize, Deserialize)]
+struct TrieEntries {
+    root_hash: String,
+    entries: Vec<(String, String)>,
+}
+
+impl From<Trie> for TrieEntries {
+    fn from(trie: Trie) -> Self {
+        Self {
+            entries: trie.entries(),
+            root_hash: trie.root_hash,
+        }
+    }
+}
+
+impl TryFrom<TrieEntries> for Trie {
+    type Error = String;
+
+    fn try_from(stored: TrieEntries) -> Result<Self, String> {
+        let mut trie = Trie::new();
+        for (key, value) in stored.entries {
+            trie.put(key, value);
+        }
+        if trie.root_hash != stored.root_hash {
+            return Err(format!(
+                "trie entries hash to {}, not {}",
+                trie.root_hash, stored.root_hash
+            ));
+        }
+        Ok(trie)
+    }
+}
+
+impl Default for Trie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Trie {
     pub fn new() -> Self {
-        let mut s = Self {
-            head: Node::new(),
-            root_hash: "".into(),
-        };
-        s.generate_root_hash();
-        s
+        Self {
+            root: None,
+            //keccak256 of the rlp of an empty string, same as ethereum's empty root
+            root_hash: keccak_bytes(&rlp::encode_bytes(&[])),
+        }
+    }
+    /// a trie that's all in the store bar its root hash, eg one write_to() wrote out. Nodes only get read back as
+    /// they're walked into, so a get() or put() reads the nodes on its key's path and nothing else
+    pub fn stored(root_hash: &str, store: Arc<dyn NodeStore>) -> Self {
+        let empty = Trie::new();
+        if root_hash == empty.root_hash {
+            return empty;
+        }
+        Self {
+            root: Some(Node::stored(hex::decode(root_hash).unwrap(), store)),
+            root_hash: root_hash.to_string(),
+        }
+    }
+    /// writes out every node the store doesn't have yet, so stored() can read the trie back from its root hash
+    pub fn write_to(&self, store: &dyn NodeStore) -> Result<(), StoreError> {
+        let mut nodes = vec![];
+        if let Some(root) = &self.root {
+            root.collect_unstored(true, &mut nodes);
+        }
+        if nodes.is_empty() {
+            return Ok(());
+        }
+        store.write_nodes(nodes)
     }
-    pub fn generate_root_hash(&mut self) {
-        self.root_hash = keccak_hash(&self.head);
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
     }
     pub fn get(&self, key: String) -> Option<&String> {
-        let mut node = &self.head;
-        for c in key.chars() {
-            if node.child_map.get(&c).is_some() {
-                node = &node.child_map.get(&c).unwrap();
-            } else {
-                return None;
+        self.root.as_ref()?.get(&to_nibbles(&key))
+    }
+    /// importantly we want to store ACTUAL values in the trie, not references. Because refs might change and trie must not.
+    /// An empty value deletes the key
+    pub fn put(&mut self, key: String, value: String) {
+        let path = to_nibbles(&key);
+        if value.is_empty() {
+            if self.get(key).is_none() {
+                return;
             }
+            self.root = self.root.take().and_then(|root| root.remove(&path));
+        } else {
+            self.root = Some(Node::insert(self.root.take(), &path, value));
         }
-        Some(&node.value)
+        //the root is always hashed, however short it is
+        self.root_hash = match &self.root {
+            Some(root) => keccak_bytes(root.encoded()),
+            None => Trie::new().root_hash,
+        };
     }
-    /// importantly we want to store ACTUAL values in the trie, not references. Because refs might change and trie must not
-    pub fn put(&mut self, key: String, value: String) {
-        let mut node = &mut self.head;
-        for c in key.chars() {
-            //insert any missing keys
-            if node.child_map.get(&c).is_none() {
-                node.child_map.insert(c, Node::new());
+    /// the rlp of every hashed node on the way down to the key, root first, each one hex - the same as the proofs of
+    /// ethereum's eth_getProof. Nodes short enough to be inlined come inside their parent. None if the key isn't in
+    /// the trie
+    pub fn get_proof(&self, key: &str) -> Option<Vec<String>> {
+        let path = to_nibbles(key);
+        let mut at = 0;
+        let mut node = self.root.as_ref()?;
+        let mut proof = vec![hex::encode(node.encoded())];
+        loop {
+            let next = match node.kind() {
+                NodeKind::Leaf { path: rest, .. } if rest.as_slice() == &path[at..] => {
+                    return Some(proof)
+                }
+                NodeKind::Leaf { .. } => return None,
+                NodeKind::Extension { path: rest, child } => {
+                    if !path[at..].starts_with(rest) {
+                        return None;
+                    }
+                    at += rest.len();
+                    &**child
+                }
+                NodeKind::Branch { value, .. } if at == path.len() => {
+                    return Some(proof).filter(|_| !value.is_empty())
+                }
+                NodeKind::Branch { children, .. } => {
+                    at += 1;
+                    children[path[at - 1] as usize].as_ref()?
+                }
+            };
+            if next.encoded().len() >= 32 {
+                proof.push(hex::encode(next.encoded()));
+            }
+            node = next;
+        }
+    }
+    /// what a light client does - only needs the root hash (eg a block's tx_root) and the proof, not the trie.
+    /// Returns the proven value if the proof checks out
+    pub fn verify_proof(root_hash: &str, key: &str, proof: &[String]) -> Option<String> {
+        let path = to_nibbles(key);
+        let mut at = 0;
+        let mut proof = proof.iter();
+        let mut reference = Rlp::Bytes(hex::decode(root_hash).ok()?);
+        loop {
+            //a hash has to be followed by a node that hashes to it, an inlined node is already here
+            let node = match reference {
+                Rlp::Bytes(hash) if hash.len() == 32 => {
+                    let encoded = hex::decode(proof.next()?).ok()?;
+                    if keccak(&encoded) != hash {
+                        return None;
+                    }
+                    rlp::decode(&encoded)?
+                }
+                Rlp::Bytes(_) => return None,
+                inlined => inlined,
+            };
+            let mut items = match node {
+                Rlp::List(items) => items,
+                Rlp::Bytes(_) => return None,
+            };
+            match items.len() {
+                17 if at == path.len() => {
+                    return match items.pop()? {
+                        Rlp::Bytes(value) if !value.is_empty() => String::from_utf8(value).ok(),
+                        _ => None,
+                    }
+                }
+                17 => {
+                    reference = items.swap_remove(path[at] as usize);
+                    at += 1;
+                }
+                2 => {
+                    let (rest, is_leaf) = match &items[0] {
+                        Rlp::Bytes(encoded) => from_hex_prefix(encoded)?,
+                        Rlp::List(_) => return None,
+                    };
+                    if !path[at..].starts_with(&rest) {
+                        return None;
+                    }
+                    at += rest.len();
+                    let next = items.pop()?;
+                    if !is_leaf {
+                        reference = next;
+                        continue;
+                    }
+                    return match next {
+                        Rlp::Bytes(value) if at == path.len() => String::from_utf8(value).ok(),
+                        _ => None,
+                    };
+                }
+                _ => return None,
             }
-            //continue down trie
-            node = node.child_map.get_mut(&c).unwrap();
         }
-        //now that we're at the bottom, insert the value
-        node.value = value;
-        //regenerate the root hash for the trie
-        self.generate_root_hash();
+    }
+    /// every key that holds a value, with the value, sorted by key
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let mut entries = vec![];
+        if let Some(root) = &self.root {
+            root.collect_entries(vec![], &mut entries);
+        }
+        //only put() builds paths, always from a whole string, so they always make one back
+        let mut entries = entries
+            .into_iter()
+            .map(|(path, value)| (from_nibbles(&path).unwrap(), value))
+            .collect::<Vec<_>>();
+        entries.sort();
+        entries
     }
     pub fn build_trie(items: Vec<Transaction>) -> Trie {
         let mut t = Trie::new();
 
         for tx in items.into_iter().sorted_by_key(|t| t.unsigned_tx.id) {
-            let serialized_tx = serde_json::to_string(&tx).unwrap();
-            t.put(keccak_hash(&tx), serialized_tx);
+            t.put(tx.hash(), hex::encode(codec::canonical(&tx)));
         }
 
         t
     }
 }
 
+//a node as write_to() stored it. Children pointed at by hash stay in the store, inlined ones come along
+fn kind_from_rlp(item: Rlp, store: &Arc<dyn NodeStore>) -> Option<NodeKind> {
+    let mut items = match item {
+        Rlp::List(items) => items,
+        Rlp::Bytes(_) => return None,
+    };
+    let text = |item: Rlp| match item {
+        Rlp::Bytes(bytes) => String::from_utf8(bytes).ok(),
+        Rlp::List(_) => None,
+    };
+    match items.len() {
+        17 => {
+            let value = text(items.pop()?)?;
+            let mut children: Box<[Option<Node>; 16]> = Default::default();
+            for (slot, item) in children.iter_mut().zip(items) {
+                *slot = child_from_rlp(item, store)?;
+            }
+            Some(NodeKind::Branch { children, value })
+        }
+        2 => {
+            let next = items.pop()?;
+            let (path, is_leaf) = match &items[0] {
+                Rlp::Bytes(encoded) => from_hex_prefix(encoded)?,
+                Rlp::List(_) => return None,
+            };
+            if is_leaf {
+                return Some(NodeKind::Leaf {
+                    path,
+                    value: text(next)?,
+                });
+            }
+            let child = child_from_rlp(next, store)??;
+            Some(NodeKind::Extension {
+                path,
+                child: Box::new(child),
+            })
+        }
+        _ => None,
+    }
+}
+
+//Some(None) for an empty slot, None if it doesn't decode
+fn child_from_rlp(item: Rlp, store: &Arc<dyn NodeStore>) -> Option<Option<Node>> {
+    match item {
+        Rlp::Bytes(bytes) if bytes.is_empty() => Some(None),
+        Rlp::Bytes(hash) if hash.len() == 32 => Some(Some(Node::stored(hash, store.clone()))),
+        Rlp::Bytes(_) => None,
+        inlined => Some(Some(Node::new(kind_from_rlp(inlined, store)?))),
+    }
+}
+
+fn keccak(bytes: &[u8]) -> Vec<u8> {
+    hex::decode(keccak_bytes(bytes)).unwrap()
+}
+
+fn to_nibbles(key: &str) -> Nibbles {
+    key.bytes()
+        .flat_map(|byte| vec![byte >> 4, byte & 0x0f])
+        .collect()
+}
+
+fn from_nibbles(nibbles: &[u8]) -> Option<String> {
+    if !nibbles.len().is_multiple_of(2) {
+        return None;
+    }
+    let bytes = nibbles
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect();
+    String::from_utf8(bytes).ok()
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+//a node's path packed back into bytes. The first nibble says whether it's a leaf and whether the path has an odd
+// number of nibbles - if it does the path starts right after it, if not a padding nibble comes first
+fn hex_prefix(path: &[u8], is_leaf: bool) -> Vec<u8> {
+    let leaf_flag = if is_leaf { 2 } else { 0 };
+    let flag = leaf_flag + (path.len() % 2) as u8;
+    let mut nibbles = vec![flag];
+    if path.len().is_multiple_of(2) {
+        nibbles.push(0);
+    }
+    nibbles.extend(path);
+    nibbles
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect()
+}
+
+fn from_hex_prefix(encoded: &[u8]) -> Option<(Nibbles, bool)> {
+    let flag = encoded.first()? >> 4;
+    if flag > 3 {
+        return None;
+    }
+    let mut nibbles = encoded
+        .iter()
+        .flat_map(|byte| vec![byte >> 4, byte & 0x0f])
+        .collect::<Vec<u8>>();
+    //the flag, and the padding if the path is even
+    nibbles.drain(..if flag % 2 == 1 { 1 } else { 2 });
+    Some((nibbles, flag >= 2))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::keccak_hash;
+    use std::collections::HashMap;
 
     #[test]
     fn test_put() {
         let mut t = Trie::new();
-        // println!("t1: {:?}", t.root_hash);
-        t.put("foo".into(), "bar".into());
-        t.put("food".into(), "protbar".into());
-        let left = format!("{:?}", t.head);
-        let right = "Node { value: \"\", child_map: {'f': Node { value: \"\", child_map: {'o': Node { value: \"\", child_map: {'o': Node { value: \"bar\", child_map: {'d': Node { value: \"protbar\", child_map: {} }} }} }} }} }";
-        // println!("t2: {:?}", t.root_hash);
-        assert_eq!(left, right);
+        assert_eq!(
+            t.root_hash,
+            "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"
+        );
+        //ethereum's "puppy" trie test
+        t.put("do".into(), "verb".into());
+        t.put("horse".into(), "stallion".into());
+        t.put("doge".into(), "coin".into());
+        t.put("dog".into(), "puppy".into());
+        assert_eq!(
+            t.root_hash,
+            "5991bb8c6514148a29db676a14ac506cd2cd5775ace63c30a4fe457715e9ac84"
+        );
+
+        //the order things went in doesn't matter, only what's there
+        let mut reversed = Trie::new();
+        for (key, value) in t.entries().into_iter().rev() {
+            reversed.put(key, value);
+        }
+        assert_eq!(reversed.root_hash, t.root_hash);
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut t = Trie::new();
+        t.put("do".into(), "verb".into());
+        t.put("horse".into(), "stallion".into());
+        let root = t.root_hash.clone();
+
+        t.put("doge".into(), "coin".into());
+        t.put("dog".into(), "puppy".into());
+        t.put("dog".into(), "".into());
+        t.put("doge".into(), "".into());
+        //and one that was never there
+        t.put("cat".into(), "".into());
+        assert_eq!(t.root_hash, root);
+        assert_eq!(t.get("dog".into()), None);
+
+        t.put("do".into(), "".into());
+        t.put("horse".into(), "".into());
+        assert!(t.is_empty());
+        assert_eq!(t.root_hash, Trie::new().root_hash);
     }
 
     #[test]
@@ -99,6 +719,9 @@ mod tests {
         t.put("food".into(), "protbar".into());
         let left = t.get("food".into()).unwrap();
         assert_eq!(left, "protbar");
+        //only a prefix of a key isn't a key
+        assert_eq!(t.get("fo".into()), None);
+        assert_eq!(t.get("fooda".into()), None);
     }
 
     /// tests to make sure that if the original value changes, the hash is still valid
@@ -116,4 +739,79 @@ mod tests {
 
         assert_eq!(pre_update, post_update);
     }
+
+    #[test]
+    fn test_proof_roundtrip() {
+        let mut t = Trie::new();
+        t.put("foo".into(), "bar".into());
+        t.put("fig".into(), "tree".into());
+        //long enough to get hashed instead of inlined
+        t.put("food".into(), "protbar".repeat(10));
+        t.put("fizz".into(), "buzz".repeat(10));
+
+        for (key, value) in t.entries() {
+            let proof = t.get_proof(&key).unwrap();
+            assert_eq!(Trie::verify_proof(&t.root_hash, &key, &proof), Some(value));
+        }
+        let proof = t.get_proof("fizz").unwrap();
+        assert_eq!(proof.len(), 4);
+        //fig's leaf is inlined in a node on fizz's path, so the same proof does for it
+        assert_eq!(
+            Trie::verify_proof(&t.root_hash, "fig", &proof),
+            Some("tree".to_string())
+        );
+
+        //but not for a key down another path, or against a different root
+        assert_eq!(Trie::verify_proof(&t.root_hash, "foo", &proof), None);
+        assert_eq!(Trie::verify_proof("nonsense", "fizz", &proof), None);
+        assert_eq!(
+            Trie::verify_proof(&Trie::new().root_hash, "fizz", &proof),
+            None
+        );
+        assert!(t.get_proof("bar").is_none());
+        assert!(t.get_proof("fo").is_none());
+    }
+
+    #[test]
+    fn test_tampered_proof_fails() {
+        let mut t = Trie::new();
+        t.put("foo".into(), "bar".into());
+        t.put("fig".into(), "tree".into());
+
+        let mut proof = t.get_proof("foo").unwrap();
+        let last = proof.last_mut().unwrap();
+        *last = last.replace(&hex::encode("bar"), &hex::encode("baz"));
+        assert_eq!(Trie::verify_proof(&t.root_hash, "foo", &proof), None);
+        assert_eq!(Trie::verify_proof(&t.root_hash, "foo", &[]), None);
+    }
+
+    #[test]
+    fn test_entries() {
+        let mut t = Trie::new();
+        t.put("foo".into(), "bar".into());
+        t.put("food".into(), "protbar".into());
+        t.put("fig".into(), "tree".into());
+        assert_eq!(
+            t.entries(),
+            vec![
+                ("fig".to_string(), "tree".to_string()),
+                ("foo".to_string(), "bar".to_string()),
+                ("food".to_string(), "protbar".to_string()),
+            ]
+        );
+        assert!(Trie::new().entries().is_empty());
+    }
+
+    #[test]
+    fn test_serde_checks_the_root() {
+        let mut t = Trie::new();
+        t.put("foo".into(), "bar".into());
+        let json = serde_json::to_string(&t).unwrap();
+        let back = serde_json::from_str::<Trie>(&json).unwrap();
+        assert_eq!(back.root_hash, t.root_hash);
+        assert_eq!(back.get("foo".into()), Some(&"bar".to_string()));
+
+        let tampered = json.replace("\"bar\"", "\"baz\"");
+        assert!(serde_json::from_str::<Trie>(&tampered).is_err());
+    }
 }
@@ -0,0 +1,273 @@
+//`rs stress` - load test of a single node, end to end and without the network: funded accounts go straight into the
+// genesis state (the "snapshot"), random transfers between them go through process_transaction() into the tx queue,
+// and the miner works through the queue block by block. Every phase is timed on its own so it's clear whether
+// signing, ingestion, proof of work or validation + execution is the bottleneck
+
+use crate::account::Account;
+use crate::api::pubsub::process_transaction;
+use crate::config::{next_value, NodeConfig};
+use crate::store::codec;
+use crate::telemetry::init_tracing;
+use crate::transaction::tx::Transaction;
+use crate::util::{prep_state, GlobalState};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub const USAGE: &str = "usage:
+  rs stress [--accounts <n>] [--txs <n>] [--block-size <n>] [--log-level <level>]";
+
+//way more than --txs transfers of at most MAX_TRANSFER can spend, so no tx ever gets rejected for the balance
+pub const INITIAL_BALANCE: u64 = 1_000_000_000;
+pub const MAX_TRANSFER: u64 = 100;
+const GAS_LIMIT: u64 = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StressConfig {
+    pub accounts: usize,
+    pub txs: usize,
+    //max tx per block, not counting the mining reward
+    pub block_size: usize,
+    pub log_level: String,
+}
+
+impl Default for StressConfig {
+    fn default() -> Self {
+        Self {
+            accounts: 1000,
+            txs: 5000,
+            block_size: 500,
+            log_level: "warn".into(),
+        }
+    }
+}
+
+impl StressConfig {
+    /// everything after "stress"
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut config = StressConfig::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let value = next_value(arg, args.next());
+            match arg.as_str() {
+                "--accounts" => config.accounts = parse_count(arg, &value?)?,
+                "--txs" => config.txs = parse_count(arg, &value?)?,
+                "--block-size" => config.block_size = parse_count(arg, &value?)?,
+                "--log-level" => config.log_level = value?,
+                _ => return Err(format!("unknown flag: {}\n{}", arg, USAGE)),
+            }
+        }
+        //a transfer needs 2 different accounts
+        if config.accounts < 2 {
+            return Err("--accounts has to be at least 2".into());
+        }
+        Ok(config)
+    }
+}
+
+fn parse_count(flag: &str, value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("{} takes a number above 0, got {}", flag, value)),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StressReport {
+    pub accounts: usize,
+    pub txs: usize,
+    pub blocks: usize,
+    //key generation + funding the accounts in the genesis state
+    pub setup: Duration,
+    //creating and signing the transfers
+    pub signing: Duration,
+    //process_transaction(), ie decoding + adding to the tx queue
+    pub ingestion: Duration,
+    //proof of work, incl building the tx trie
+    pub mining: Duration,
+    //import_block(), ie validating + running every tx
+    pub validation: Duration,
+}
+
+impl StressReport {
+    /// mined tx (excl mining rewards) per second of mining + validation
+    pub fn tx_per_sec(&self) -> f64 {
+        per_sec(self.txs, self.mining + self.validation)
+    }
+    pub fn blocks_per_sec(&self) -> f64 {
+        per_sec(self.blocks, self.mining + self.validation)
+    }
+}
+
+fn per_sec(count: usize, duration: Duration) -> f64 {
+    count as f64 / duration.as_secs_f64().max(f64::EPSILON)
+}
+
+impl std::fmt::Display for StressReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} txs between {} accounts in {} blocks",
+            self.txs, self.accounts, self.blocks
+        )?;
+        let phases = [
+            ("setup", self.setup, self.accounts),
+            ("signing", self.signing, self.txs),
+            ("ingestion", self.ingestion, self.txs),
+            ("mining", self.mining, self.blocks),
+            ("validation", self.validation, self.blocks),
+        ];
+        for (name, duration, count) in phases.iter() {
+            writeln!(
+                f,
+                "  {:<12}{:>10.3}s {:>12.1}/s",
+                name,
+                duration.as_secs_f64(),
+                per_sec(*count, *duration)
+            )?;
+        }
+        write!(
+            f,
+            "throughput: {:.1} tx/s, {:.2} blocks/s",
+            self.tx_per_sec(),
+            self.blocks_per_sec()
+        )
+    }
+}
+
+/// a node whose genesis state already has config.accounts funded accounts
+fn funded_node(config: &StressConfig) -> (GlobalState, Vec<Account>) {
+    let mut global_state = prep_state();
+    let accounts: Vec<Account> = (0..config.accounts).map(|_| Account::new(vec![])).collect();
+    let state = &mut global_state.blockchain.get_mut().unwrap().state;
+    for account in accounts.iter() {
+        state.allocate(account.public_account.address, INITIAL_BALANCE);
+    }
+    (global_state, accounts)
+}
+
+//moves the sender on to its next nonce
+fn random_transfer(accounts: &mut [Account], chain_id: u64) -> Transaction {
+    let from = rand::random::<usize>() % accounts.len();
+    //anyone but the sender
+    let to = (from + 1 + rand::random::<usize>() % (accounts.len() - 1)) % accounts.len();
+    let tx = Transaction::create_transaction(
+        Some(accounts[from].clone()),
+        Some(accounts[to].public_account.address),
+        1 + rand::random::<u64>() % MAX_TRANSFER,
+        None,
+        GAS_LIMIT,
+        chain_id,
+    );
+    accounts[from].public_account.nonce += 1;
+    tx
+}
+
+pub fn run_stress(config: &StressConfig) -> Result<StressReport, String> {
+    let mut report = StressReport {
+        accounts: config.accounts,
+        txs: config.txs,
+        ..StressReport::default()
+    };
+
+    let start = Instant::now();
+    let (global_state, mut accounts) = funded_node(config);
+    let global_state = Arc::new(global_state);
+    report.setup = start.elapsed();
+
+    let start = Instant::now();
+    let chain_id = global_state.blockchain.read().unwrap().chain_id();
+    let txs: Vec<String> = (0..config.txs)
+        .map(|_| codec::encode_hex(&random_transfer(&mut accounts, chain_id)))
+        .collect();
+    report.signing = start.elapsed();
+
+    let start = Instant::now();
+    for tx in txs {
+        process_transaction(tx, global_state.clone()).map_err(|e| e.to_string())?;
+    }
+    report.ingestion = start.elapsed();
+
+    //the queue also holds the account creation tx prep_state() queued up - they get mined like any other
+    loop {
+        let tx_series: Vec<Transaction> = global_state
+            .tx_queue
+            .lock()
+            .unwrap()
+            .get_tx_series()
+            .into_iter()
+            .take(config.block_size)
+            .collect();
+        if tx_series.is_empty() {
+            break;
+        }
+        let start = Instant::now();
+        let (last_block, block) = global_state
+            .blockchain
+            .read()
+            .unwrap()
+            .candidate(&global_state.miner_account(), tx_series)
+            .map_err(|e| format!("block {} fails to run: {}", report.blocks + 1, e))?;
+        let block = block.seal(&last_block, &global_state.chain_rules());
+        report.mining += start.elapsed();
+
+        let start = Instant::now();
+        global_state
+            .import_block(block.clone())
+            .map_err(|e| format!("block {} rejected: {}", report.blocks + 1, e))?;
+        report.validation += start.elapsed();
+
+        global_state
+            .tx_queue
+            .lock()
+            .unwrap()
+            .clear_block_tx(&block.tx_series);
+        report.blocks += 1;
+    }
+    Ok(report)
+}
+
+/// `rs stress ...` - takes everything after "stress" and returns the report to print
+pub fn run_stress_command(args: &[String]) -> Result<String, String> {
+    let config = StressConfig::parse(args)?;
+    init_tracing(&NodeConfig {
+        log_level: config.log_level.clone(),
+        ..NodeConfig::default()
+    })?;
+    Ok(run_stress(&config)?.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let args: Vec<String> = vec!["--accounts", "50", "--block-size", "10"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let config = StressConfig::parse(&args).unwrap();
+        assert_eq!(config.accounts, 50);
+        assert_eq!(config.block_size, 10);
+        assert_eq!(config.txs, StressConfig::default().txs);
+
+        assert!(StressConfig::parse(&["--accounts".into(), "1".into()]).is_err());
+        assert!(StressConfig::parse(&["--txs".into(), "0".into()]).is_err());
+        assert!(StressConfig::parse(&["--txs".into(), "many".into()]).is_err());
+    }
+
+    #[test]
+    fn test_every_tx_gets_mined() {
+        let config = StressConfig {
+            accounts: 5,
+            txs: 20,
+            block_size: 8,
+            ..StressConfig::default()
+        };
+        let report = run_stress(&config).unwrap();
+        //20 transfers + prep_state()'s 2 account creations, 8 per block
+        assert_eq!(report.blocks, 3);
+        assert!(report.tx_per_sec() > 0.0);
+        assert!(report.to_string().contains("in 3 blocks"));
+    }
+}
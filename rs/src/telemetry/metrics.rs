@@ -0,0 +1,282 @@
+use crate::blockchain::sync::PeerHealth;
+use crate::store::storage::StorageCacheStats;
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+lazy_static! {
+    //keyed by gossip topic / rabbitmq exchange (they have the same names). A BTreeMap so /metrics lists them in the same order every time
+    static ref CONSUMERS: Mutex<BTreeMap<String, ConsumerMetrics>> = Mutex::new(BTreeMap::new());
+}
+
+/// what one exchange's consumer loop has done since the node started
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConsumerMetrics {
+    pub deliveries: u64,
+    /// deliveries the processor couldn't use, see Gossip::receive() / rabbit_consume()
+    pub failures: u64,
+    /// time spent in the processor, summed over every delivery
+    pub processing_us: u64,
+    pub max_processing_us: u64,
+    /// messages waiting for us on the broker the last time we asked. Always 0 with gossip
+    pub lag: u32,
+}
+
+impl ConsumerMetrics {
+    pub fn record_delivery(&mut self, took: Duration, ok: bool) {
+        let took = took.as_micros() as u64;
+        self.deliveries += 1;
+        self.processing_us += took;
+        self.max_processing_us = self.max_processing_us.max(took);
+        if !ok {
+            self.failures += 1;
+        }
+    }
+
+    /// true if this is the reading that put us over the threshold - so it's warned about once, not on every check
+    pub fn record_lag(&mut self, lag: u32, threshold: u32) -> bool {
+        let fell_behind = lag > threshold && self.lag <= threshold;
+        self.lag = lag;
+        fell_behind
+    }
+}
+
+pub fn record_delivery(exchange: &str, took: Duration, ok: bool) {
+    CONSUMERS
+        .lock()
+        .unwrap()
+        .entry(exchange.into())
+        .or_default()
+        .record_delivery(took, ok);
+}
+
+pub fn record_lag(exchange: &str, lag: u32, threshold: u32) -> bool {
+    CONSUMERS
+        .lock()
+        .unwrap()
+        .entry(exchange.into())
+        .or_default()
+        .record_lag(lag, threshold)
+}
+
+pub fn consumer_metrics() -> BTreeMap<String, ConsumerMetrics> {
+    CONSUMERS.lock().unwrap().clone()
+}
+
+//name, type, help, and how to read it off one exchange's metrics
+type ConsumerFamily = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&ConsumerMetrics) -> String,
+);
+
+/// the prometheus text format, labelled by exchange
+pub fn render(consumers: &BTreeMap<String, ConsumerMetrics>) -> String {
+    let families: [ConsumerFamily; 6] = [
+        (
+            "rs_consumer_deliveries_total",
+            "counter",
+            "messages delivered by rabbitmq",
+            |m| m.deliveries.to_string(),
+        ),
+        (
+            "rs_consumer_failures_total",
+            "counter",
+            "delivered messages that were dropped",
+            |m| m.failures.to_string(),
+        ),
+        (
+            "rs_consumer_processing_seconds_sum",
+            "counter",
+            "time spent processing deliveries",
+            |m| seconds(m.processing_us),
+        ),
+        (
+            "rs_consumer_processing_seconds_count",
+            "counter",
+            "deliveries the processing time is summed over",
+            |m| m.deliveries.to_string(),
+        ),
+        (
+            "rs_consumer_processing_seconds_max",
+            "gauge",
+            "slowest delivery so far",
+            |m| seconds(m.max_processing_us),
+        ),
+        (
+            "rs_consumer_lag_messages",
+            "gauge",
+            "messages waiting on the broker at the last check",
+            |m| m.lag.to_string(),
+        ),
+    ];
+    let mut out = String::new();
+    for (name, kind, help, value) in families.iter() {
+        writeln!(out, "# HELP {} {}", name, help).unwrap();
+        writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+        for (exchange, metrics) in consumers {
+            writeln!(
+                out,
+                "{}{{exchange=\"{}\"}} {}",
+                name,
+                exchange,
+                value(metrics)
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+/// the prometheus text format, for GET /health's numbers. Left out where there's nothing to report yet
+pub fn render_peer_health(health: &PeerHealth) -> String {
+    let families = [
+        (
+            "rs_partitioned",
+            "peers but no blocks from them in too long, 1 or 0",
+            Some((health.partitioned as u8).to_string()),
+        ),
+        (
+            "rs_peers",
+            "nodes we're gossiping with",
+            health.peers.map(|peers| peers.to_string()),
+        ),
+        (
+            "rs_peer_block_quiet_seconds",
+            "time since a peer's block last made it onto our chain",
+            health.quiet_ms.map(|ms| seconds(ms * 1_000)),
+        ),
+    ];
+    let mut out = String::new();
+    for (name, help, value) in families.iter() {
+        if let Some(value) = value {
+            writeln!(out, "# HELP {} {}", name, help).unwrap();
+            writeln!(out, "# TYPE {} gauge", name).unwrap();
+            writeln!(out, "{} {}", name, value).unwrap();
+        }
+    }
+    out
+}
+
+/// the prometheus text format, for the storage tries kept in memory vs spilled under <datadir>/storage
+pub fn render_storage_cache(stats: &StorageCacheStats) -> String {
+    let families = [
+        (
+            "rs_storage_tries_capacity",
+            "gauge",
+            "how many storage tries may stay in memory",
+            stats.capacity as u64,
+        ),
+        (
+            "rs_storage_tries_hot",
+            "gauge",
+            "contract storage tries held in memory",
+            stats.hot as u64,
+        ),
+        (
+            "rs_storage_tries_cold",
+            "gauge",
+            "contract storage tries only on disk",
+            stats.cold as u64,
+        ),
+        (
+            "rs_storage_trie_hits_total",
+            "counter",
+            "storage trie lookups served from memory",
+            stats.hits,
+        ),
+        (
+            "rs_storage_trie_loads_total",
+            "counter",
+            "storage trie nodes read back from disk",
+            stats.loads,
+        ),
+        (
+            "rs_storage_trie_evictions_total",
+            "counter",
+            "storage tries dropped from memory to make room",
+            stats.evictions,
+        ),
+    ];
+    let mut out = String::new();
+    for (name, kind, help, value) in families.iter() {
+        writeln!(out, "# HELP {} {}", name, help).unwrap();
+        writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+        writeln!(out, "{} {}", name, value).unwrap();
+    }
+    out
+}
+
+fn seconds(us: u64) -> String {
+    format!("{:.6}", us as f64 / 1_000_000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consumer_metrics() {
+        let mut metrics = ConsumerMetrics::default();
+        metrics.record_delivery(Duration::from_millis(3), true);
+        metrics.record_delivery(Duration::from_millis(1), false);
+        assert_eq!(metrics.deliveries, 2);
+        assert_eq!(metrics.failures, 1);
+        assert_eq!(metrics.processing_us, 4_000);
+        assert_eq!(metrics.max_processing_us, 3_000);
+
+        //only warned about on the way over the threshold
+        assert!(!metrics.record_lag(10, 100));
+        assert!(metrics.record_lag(150, 100));
+        assert!(!metrics.record_lag(200, 100));
+        assert!(!metrics.record_lag(0, 100));
+        assert!(metrics.record_lag(101, 100));
+
+        let mut consumers = BTreeMap::new();
+        consumers.insert("blocks".to_string(), metrics);
+        let text = render(&consumers);
+        assert!(text.contains("# TYPE rs_consumer_deliveries_total counter\n"));
+        assert!(text.contains("rs_consumer_failures_total{exchange=\"blocks\"} 1\n"));
+        assert!(text.contains("rs_consumer_processing_seconds_sum{exchange=\"blocks\"} 0.004000\n"));
+        assert!(text.contains("rs_consumer_lag_messages{exchange=\"blocks\"} 101\n"));
+    }
+
+    #[test]
+    fn test_render_peer_health() {
+        let mut health = PeerHealth {
+            partitioned: true,
+            peers: None,
+            last_peer_block: None,
+            quiet_ms: Some(1_500),
+            threshold_ms: 1_000,
+        };
+        let text = render_peer_health(&health);
+        assert!(text.contains("# TYPE rs_partitioned gauge\nrs_partitioned 1\n"));
+        assert!(text.contains("rs_peer_block_quiet_seconds 1.500000\n"));
+        assert!(!text.contains("rs_peers"));
+
+        health.peers = Some(2);
+        health.quiet_ms = None;
+        let text = render_peer_health(&health);
+        assert!(text.contains("rs_peers 2\n"));
+        assert!(!text.contains("rs_peer_block_quiet_seconds"));
+    }
+
+    #[test]
+    fn test_render_storage_cache() {
+        let text = render_storage_cache(&StorageCacheStats {
+            capacity: 2,
+            hot: 2,
+            cold: 1,
+            hits: 10,
+            loads: 3,
+            evictions: 4,
+        });
+        assert!(text.contains("# TYPE rs_storage_tries_cold gauge\nrs_storage_tries_cold 1\n"));
+        assert!(text.contains("# TYPE rs_storage_trie_evictions_total counter\n"));
+        assert!(text.contains("rs_storage_trie_loads_total 3\n"));
+    }
+}
@@ -0,0 +1,31 @@
+pub mod metrics;
+
+use crate::config::{LogFormat, NodeConfig};
+use tracing_subscriber::EnvFilter;
+
+/// installs the global tracing subscriber. Has to run before anything logs, ie first thing in main
+pub fn init_tracing(config: &NodeConfig) -> Result<(), String> {
+    let filter = EnvFilter::try_new(&config.log_level)
+        .map_err(|e| format!("invalid log level {}: {}", config.log_level, e))?;
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    match config.log_format {
+        LogFormat::Pretty => builder.try_init(),
+        //include the request span (id, method, path) on every line so logs can be grepped per request
+        LogFormat::Json => builder.json().with_current_span(true).try_init(),
+    }
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_log_level() {
+        let config = NodeConfig {
+            log_level: "rs=loud".into(),
+            ..NodeConfig::default()
+        };
+        assert!(init_tracing(&config).is_err());
+    }
+}
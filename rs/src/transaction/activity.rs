@@ -0,0 +1,103 @@
+use crate::account::address::Address;
+use crate::transaction::tx::{Transaction, TxType};
+use crate::util::bigint::U256;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    In,
+    Out,
+    //the tx created the account
+    Created,
+}
+
+/// one entry in an address's history - what a mined tx did to it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct Activity {
+    pub block_number: usize,
+    pub tx_hash: String,
+    pub direction: Direction,
+    #[schema(value_type = String)]
+    pub value: U256,
+}
+
+impl Activity {
+    /// every address the tx touches, with what it did to each of them.
+    /// A tx sent to yourself shows up twice, once in each direction
+    pub fn from_tx(tx: &Transaction, block_number: usize) -> Vec<(Address, Activity)> {
+        let tx_hash = tx.hash();
+        let entry = |direction, value| Activity {
+            block_number,
+            tx_hash: tx_hash.clone(),
+            direction,
+            value,
+        };
+        let unsigned_tx = &tx.unsigned_tx;
+        match unsigned_tx.data.tx_type {
+            TxType::CreateAccount => unsigned_tx
+                .data
+                .account_data
+                .iter()
+                .map(|account| (account.address, entry(Direction::Created, U256::zero())))
+                .collect(),
+            //mining rewards have no sender
+            TxType::MiningReward | TxType::Transact => {
+                let mut touched = vec![];
+                if let Some(from) = unsigned_tx.from {
+                    touched.push((from, entry(Direction::Out, unsigned_tx.value)));
+                }
+                if let Some(to) = unsigned_tx.to {
+                    touched.push((to, entry(Direction::In, unsigned_tx.value)));
+                }
+                touched
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::transaction::tx::MINING_REWARD;
+
+    #[test]
+    fn test_activity_from_tx() {
+        let sender = Account::new(vec![]);
+        let sender_addr = sender.public_account.address;
+        let receiver = Account::new(vec![]).public_account.address;
+
+        let tx = Transaction::create_transaction(
+            Some(sender.clone()),
+            Some(receiver),
+            10,
+            None,
+            0,
+            DEFAULT_CHAIN_ID,
+        );
+        let activity = Activity::from_tx(&tx, 3);
+        assert_eq!(activity.len(), 2);
+        assert_eq!(activity[0].0, sender_addr);
+        assert_eq!(activity[0].1.direction, Direction::Out);
+        assert_eq!(activity[1].0, receiver);
+        assert_eq!(activity[1].1.direction, Direction::In);
+        assert_eq!(activity[1].1.value, U256::from(10));
+        assert_eq!(activity[1].1.tx_hash, tx.hash());
+        assert_eq!(activity[1].1.block_number, 3);
+
+        let tx = Transaction::create_transaction(Some(sender), None, 0, None, 0, DEFAULT_CHAIN_ID);
+        let activity = Activity::from_tx(&tx, 3);
+        assert_eq!(activity.len(), 1);
+        assert_eq!(activity[0].0, sender_addr);
+        assert_eq!(activity[0].1.direction, Direction::Created);
+
+        let tx =
+            Transaction::create_transaction(None, None, 0, Some(receiver), 0, DEFAULT_CHAIN_ID);
+        let activity = Activity::from_tx(&tx, 3);
+        assert_eq!(activity.len(), 1);
+        assert_eq!(activity[0].1.value, U256::from(MINING_REWARD));
+    }
+}
@@ -0,0 +1,205 @@
+use crate::account::address::Address;
+use crate::blockchain::gas_stats::GAS_PRICE;
+use crate::error::TxError;
+use crate::store::state::StateAccess;
+use crate::util::bigint::{checked_sub, saturating_sub, U256};
+
+//how a tx pays for gas, the same whether it runs in a block, in the miner's preflight or in a simulation:
+// 1. buy: before anything runs, the sender (or the tx's fee payer) pays for the whole gas limit
+// 2. the tx runs and uses some of that gas
+// 3. settle: whoever bought it gets back what wasn't used, the block's payees get paid for what was
+
+/// an account that gets a cut of every block's gas fees and reward. Point it at an address nobody holds the key for
+/// and it's a burn address, otherwise it's a treasury. Either way /balance shows what it's accumulated.
+/// Part of the chain's rules like the reward schedule, see ChainRules
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Treasury {
+    pub address: Address,
+    /// percent of every tx's gas fee. Plays the part of ethereum's burned base fee
+    pub fee_percent: u8,
+    /// percent of the block reward
+    pub reward_percent: u8,
+}
+
+impl Treasury {
+    pub fn cut(amount: U256, percent: u8) -> U256 {
+        amount.saturating_mul(U256::from(percent)) / 100
+    }
+}
+
+/// who a block pays: the miner, and the treasury's cut if the chain has one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Payees {
+    pub beneficiary: Address,
+    pub treasury: Option<Treasury>,
+}
+
+impl Payees {
+    /// splits amount between the treasury (its `percent`) and the beneficiary (the rest). Returns the treasury's part
+    pub fn pay(
+        &self,
+        state: &mut impl StateAccess,
+        amount: U256,
+        percent: impl Fn(&Treasury) -> u8,
+    ) -> U256 {
+        let cut = self
+            .treasury
+            .map(|treasury| Treasury::cut(amount, percent(&treasury)))
+            .unwrap_or_else(U256::zero);
+        if let Some(treasury) = &self.treasury {
+            credit(state, treasury.address, cut);
+        }
+        credit(state, self.beneficiary, saturating_sub(amount, cut));
+        cut
+    }
+}
+
+fn credit(state: &mut impl StateAccess, address: Address, amount: U256) {
+    let mut account = state.get_account_or_empty(address);
+    account.balance = account.balance.saturating_add(amount);
+    state.put_account(address, account);
+}
+
+/// gas per byte of payload a transfer carries, see Transaction::payload_bytes(). Same as ethereum's calldata
+pub const GAS_PER_PAYLOAD_BYTE: u64 = 16;
+
+/// what a transfer pays for its payload before any code runs
+pub fn payload_gas(payload_bytes: usize) -> u64 {
+    (payload_bytes as u64).saturating_mul(GAS_PER_PAYLOAD_BYTE)
+}
+
+/// what `gas` costs at GAS_PRICE
+pub fn gas_cost(gas: U256) -> Result<U256, String> {
+    gas.checked_mul(U256::from(GAS_PRICE))
+        .ok_or_else(|| format!("{} gas at {} each overflows", gas, GAS_PRICE))
+}
+
+/// gas a sender paid for upfront, waiting on the tx to run to find out how much of it gets used
+#[derive(Debug)]
+pub struct GasPurchase {
+    payer: Address,
+    gas_limit: U256,
+    paid: U256,
+}
+
+/// where the gas money ended up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settlement {
+    pub refund: U256,
+    pub fee: U256,
+    /// the part of fee that went to the treasury
+    pub treasury_cut: U256,
+}
+
+impl GasPurchase {
+    pub fn buy(
+        state: &mut impl StateAccess,
+        payer: Address,
+        gas_limit: U256,
+    ) -> Result<Self, TxError> {
+        let paid = gas_cost(gas_limit).map_err(TxError::Overflow)?;
+        let mut account = state.get_account(payer)?;
+        account.balance =
+            checked_sub(account.balance, paid).map_err(|_| TxError::ExceededBalance)?;
+        state.put_account(payer, account);
+        Ok(Self {
+            payer,
+            gas_limit,
+            paid,
+        })
+    }
+
+    /// payees = None when there's no block to pay, eg a simulation - the fee just isn't credited anywhere
+    pub fn settle(
+        self,
+        state: &mut impl StateAccess,
+        gas_used: u64,
+        payees: Option<Payees>,
+    ) -> Settlement {
+        //the interpreter stops the code at the limit, this is just belt and braces
+        let used = std::cmp::min(U256::from(gas_used), self.gas_limit);
+        //can't overflow - it's at most what was paid
+        let fee = used.saturating_mul(U256::from(GAS_PRICE));
+        let refund = saturating_sub(self.paid, fee);
+
+        credit(state, self.payer, refund);
+        let treasury_cut = payees
+            .map(|payees| payees.pay(state, fee, |treasury| treasury.fee_percent))
+            .unwrap_or_else(U256::zero);
+        Settlement {
+            refund,
+            fee,
+            treasury_cut,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{gen_address, Account};
+    use crate::store::state::State;
+
+    #[test]
+    fn test_fee_flow() {
+        let payer = Account::new(vec![]).public_account.address;
+        let beneficiary = gen_address();
+        let mut state = State::new();
+        state.allocate(payer, 100);
+
+        let purchase = GasPurchase::buy(&mut state, payer, 30.into()).unwrap();
+        assert_eq!(state.get_account(payer).unwrap().balance, U256::from(70));
+        let payees = Payees {
+            beneficiary,
+            treasury: None,
+        };
+        let settlement = purchase.settle(&mut state, 12, Some(payees));
+        assert_eq!(
+            settlement,
+            Settlement {
+                refund: 18.into(),
+                fee: 12.into(),
+                treasury_cut: 0.into(),
+            }
+        );
+        assert_eq!(state.get_account(payer).unwrap().balance, U256::from(88));
+        assert_eq!(
+            state.get_account(beneficiary).unwrap().balance,
+            U256::from(12)
+        );
+
+        //no more gas than the balance covers
+        assert_eq!(
+            GasPurchase::buy(&mut state, payer, 89.into()).unwrap_err(),
+            TxError::ExceededBalance
+        );
+        assert_eq!(state.get_account(payer).unwrap().balance, U256::from(88));
+    }
+
+    #[test]
+    fn test_treasury_cut() {
+        let payer = Account::new(vec![]).public_account.address;
+        let treasury = Treasury {
+            address: gen_address(),
+            fee_percent: 25,
+            reward_percent: 10,
+        };
+        let payees = Payees {
+            beneficiary: gen_address(),
+            treasury: Some(treasury),
+        };
+        let mut state = State::new();
+        state.allocate(payer, 100);
+
+        let purchase = GasPurchase::buy(&mut state, payer, 50.into()).unwrap();
+        let settlement = purchase.settle(&mut state, 40, Some(payees));
+        assert_eq!(settlement.treasury_cut, U256::from(10));
+        //the reward split works the same way
+        payees.pay(&mut state, 50.into(), |treasury| treasury.reward_percent);
+
+        let balance = |address| state.get_account(address).unwrap().balance;
+        assert_eq!(balance(payer), U256::from(60));
+        assert_eq!(balance(treasury.address), U256::from(10 + 5));
+        assert_eq!(balance(payees.beneficiary), U256::from(30 + 45));
+    }
+}
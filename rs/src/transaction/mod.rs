@@ -1,2 +1,6 @@
+pub mod activity;
+pub mod fee;
+pub mod receipt;
+pub mod sig_cache;
 pub mod tx;
 pub mod tx_queue;
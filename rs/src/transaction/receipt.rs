@@ -0,0 +1,138 @@
+use crate::account::address::Address;
+use crate::transaction::tx::{Transaction, TxType};
+use crate::util::keccak_bytes;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub enum ReceiptStatus {
+    Success,
+    Failure,
+}
+
+/// record of what happened when a tx got executed as part of a block
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct Receipt {
+    pub tx_hash: String,
+    pub block_number: usize,
+    pub block_hash: String,
+    //position in the block's tx_series
+    pub transaction_index: usize,
+    pub status: ReceiptStatus,
+    pub gas_used: u64,
+    /// gas used by this tx and every one before it in the block. 0 in receipts stored before it was recorded
+    #[serde(default)]
+    pub cumulative_gas_used: u64,
+    //only present for tx that create a smart contract account
+    #[schema(value_type = Option<String>)]
+    pub contract_address: Option<Address>,
+    //NOTE: the interpreter has no LOG opcode yet, so this is always empty for now
+    pub logs: Vec<String>,
+}
+
+impl Receipt {
+    pub fn new(
+        tx: &Transaction,
+        transaction_index: usize,
+        gas_used: u64,
+        cumulative_gas_used: u64,
+        block_number: usize,
+        block_hash: &str,
+    ) -> Self {
+        let contract_address = match tx.unsigned_tx.data.tx_type {
+            TxType::CreateAccount => tx
+                .unsigned_tx
+                .data
+                .account_data
+                .as_ref()
+                .filter(|acc| acc.code_hash.is_some())
+                .map(|acc| acc.address),
+            _ => None,
+        };
+        Self {
+            tx_hash: tx.hash(),
+            block_number,
+            block_hash: block_hash.to_owned(),
+            transaction_index,
+            //an invalid tx gets the whole block rejected, so the only way to fail is contract code that does - see
+            // Block::run_block()
+            status: ReceiptStatus::Success,
+            gas_used,
+            cumulative_gas_used,
+            contract_address,
+            logs: vec![],
+        }
+    }
+
+    /// what a block header's receipts_root commits to: every receipt's outcome, in block order. Leaves out where the
+    /// receipt sits (block hash, number, index) - the block's hash covers the root, so it can't go the other way round.
+    /// keccak_bytes() rather than a trie - keccak_hash() sorts the characters first, so gas used 12 and 21 would
+    /// hash the same
+    pub fn root(receipts: &[Receipt]) -> String {
+        let outcomes: Vec<_> = receipts
+            .iter()
+            .map(|receipt| {
+                (
+                    &receipt.tx_hash,
+                    &receipt.status,
+                    receipt.gas_used,
+                    receipt.cumulative_gas_used,
+                    &receipt.contract_address,
+                    &receipt.logs,
+                )
+            })
+            .collect();
+        keccak_bytes(serde_json::to_string(&outcomes).unwrap().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::Account;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::interpreter::OPCODE;
+
+    #[test]
+    fn test_receipt_contract_address() {
+        let code = vec![OPCODE::PUSH, OPCODE::VAL(1), OPCODE::STOP];
+        let sc_account = Account::new(code);
+        let sc_address = sc_account.public_account.address;
+        let tx =
+            Transaction::create_transaction(Some(sc_account), None, 0, None, 100, DEFAULT_CHAIN_ID);
+
+        let receipt = Receipt::new(&tx, 0, 0, 0, 1, "some-hash");
+        assert_eq!(receipt.tx_hash, tx.hash());
+        assert_eq!(receipt.status, ReceiptStatus::Success);
+        assert_eq!(receipt.contract_address, Some(sc_address));
+    }
+
+    #[test]
+    fn test_receipt_no_contract_address() {
+        let account = Account::new(vec![]);
+        let tx =
+            Transaction::create_transaction(Some(account), None, 0, None, 100, DEFAULT_CHAIN_ID);
+
+        let receipt = Receipt::new(&tx, 0, 0, 0, 1, "some-hash");
+        assert_eq!(receipt.contract_address, None);
+    }
+
+    #[test]
+    fn test_receipts_root() {
+        let tx = Transaction::create_transaction(
+            Some(Account::new(vec![])),
+            None,
+            0,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        let receipts = vec![Receipt::new(&tx, 0, 12, 12, 1, "some-hash")];
+        //where the receipt ended up doesn't come into it
+        let elsewhere = vec![Receipt::new(&tx, 3, 12, 12, 7, "other-hash")];
+        assert_eq!(Receipt::root(&receipts), Receipt::root(&elsewhere));
+        let more_gas = vec![Receipt::new(&tx, 0, 21, 21, 1, "some-hash")];
+        assert_ne!(Receipt::root(&receipts), Receipt::root(&more_gas));
+        assert_ne!(Receipt::root(&receipts), Receipt::root(&[]));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::account::address::Address;
+use crate::util::keccak_bytes;
+use lazy_static::lazy_static;
+use secp256k1::Signature;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// how many verified signatures the cache remembers. Enough for a few full blocks plus the queue
+pub const SIGNATURE_CACHE_SIZE: usize = 10_000;
+
+lazy_static! {
+    static ref VERIFIED: Mutex<SignatureCache> =
+        Mutex::new(SignatureCache::new(SIGNATURE_CACHE_SIZE));
+}
+
+/// the same tx gets its signature checked on submission, in the miner's preflight and again in every node's
+/// check_block(). The key has to come from cache_key(), so a hit stands for the exact check that passed before.
+/// Only successes are kept - a bad signature is rare and never gets that far anyway
+pub fn verify_cached(key: &str, verify: impl FnOnce() -> bool) -> bool {
+    if VERIFIED.lock().unwrap().hit(key) {
+        return true;
+    }
+    //not under the lock, so other threads' lookups don't wait on the secp256k1 work
+    let valid = verify();
+    if valid {
+        VERIFIED.lock().unwrap().insert(key.to_string());
+    }
+    valid
+}
+
+/// keccak of the exact message that was signed, the signature and the signer - everything the secp256k1 check looks
+/// at, byte for byte. Once a key has verified it always will
+pub fn cache_key(message: &str, signature: &Signature, signer: &Address) -> String {
+    let mut bytes = message.as_bytes().to_vec();
+    bytes.extend_from_slice(&signature.serialize_compact());
+    bytes.extend_from_slice(signer.as_bytes());
+    keccak_bytes(&bytes)
+}
+
+/// bounded LRU set of keys, each cache_key(message, signature, signer) of a check that passed. Each key carries the
+/// tick it was last used at, and `by_use` orders the ticks, so the least recently used one is always first
+#[derive(Debug)]
+pub struct SignatureCache {
+    capacity: usize,
+    tick: u64,
+    last_used: HashMap<String, u64>,
+    by_use: BTreeMap<u64, String>,
+}
+
+impl SignatureCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            last_used: HashMap::new(),
+            by_use: BTreeMap::new(),
+        }
+    }
+
+    /// whether the key is in the cache. Counts as a use
+    pub fn hit(&mut self, key: &str) -> bool {
+        match self.last_used.get(key).copied() {
+            Some(tick) => {
+                self.by_use.remove(&tick);
+                self.touch(key.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn insert(&mut self, key: String) {
+        if let Some(tick) = self.last_used.get(&key).copied() {
+            self.by_use.remove(&tick);
+        } else if self.last_used.len() >= self.capacity {
+            if let Some((_, oldest)) = self.by_use.pop_first() {
+                self.last_used.remove(&oldest);
+            }
+        }
+        self.touch(key);
+    }
+
+    fn touch(&mut self, key: String) {
+        self.tick += 1;
+        self.by_use.insert(self.tick, key.clone());
+        self.last_used.insert(key, self.tick);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_least_recently_used_goes_first() {
+        let mut cache = SignatureCache::new(2);
+        cache.insert("a".into());
+        cache.insert("b".into());
+        //a is now the more recently used one
+        assert!(cache.hit("a"));
+        cache.insert("c".into());
+        assert_eq!(cache.last_used.len(), 2);
+        assert!(!cache.hit("b"));
+        assert!(cache.hit("a"));
+        assert!(cache.hit("c"));
+    }
+
+    #[test]
+    fn test_key_covers_the_exact_check() {
+        let account = crate::account::Account::new(vec![]);
+        let signer = account.public_account.address;
+        let message = r#"{"value":"0x12"}"#.to_string();
+        let signature = account.sign(&message);
+        let key = cache_key(&message, &signature, &signer);
+        //same characters, different tx
+        assert_ne!(key, cache_key(r#"{"value":"0x21"}"#, &signature, &signer));
+        let someone_else = crate::account::gen_address();
+        assert_ne!(key, cache_key(&message, &signature, &someone_else));
+        let other = account.sign(&r#"{"value":"0x13"}"#.to_string());
+        assert_ne!(key, cache_key(&message, &other, &signer));
+    }
+
+    #[test]
+    fn test_only_successes_are_cached() {
+        let hash = uuid::Uuid::new_v4().to_string();
+        assert!(!verify_cached(&hash, || false));
+        assert!(verify_cached(&hash, || true));
+        //answered from the cache this time
+        assert!(verify_cached(&hash, || panic!("verified twice")));
+    }
+}
@@ -1,12 +1,30 @@
-use secp256k1::{PublicKey, Signature};
+use secp256k1::bitcoin_hashes::{sha256, Hash};
+use secp256k1::Signature;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::account::address::Address;
+use crate::account::multisig::Cosignature;
 use crate::account::{Account, PublicAccount};
-use crate::interpreter::{extract_val_from_opcode, Interpreter};
-use crate::store::state::State;
+use crate::blockchain::fork::Fork;
+use crate::config::chain::ChainRules;
+use crate::error::{CodecError, ExecError, TxError};
+use crate::interpreter::{BlockEnv, Interpreter, OPCODE};
+use crate::store::codec::{self, Decode, Encode, Fields, Record};
+use crate::store::overlay::OverlayState;
+use crate::store::rlp::Rlp;
+use crate::store::state::{State, StateAccess};
+use crate::transaction::fee::{gas_cost, payload_gas, GasPurchase, Payees};
+use crate::transaction::receipt::ReceiptStatus;
+use crate::transaction::sig_cache::{cache_key, verify_cached};
+use crate::util::bigint::{checked_add, checked_sub, U256};
+use crate::util::is_zero;
 
+/// what the default reward schedule pays every block, see RewardSchedule
 pub const MINING_REWARD: u64 = 50;
+/// as it comes in - json over the api, hex encoded over the wire (see codec::encode_hex()). Biggest tx a node lets into
+/// its queue. Payload gas makes big tx expensive, this keeps any one of them from getting too big to pass around
+pub const MAX_TX_BYTES: usize = 32 * 1024;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum TxType {
@@ -24,54 +42,183 @@ pub struct TxData {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UnsignedTx {
     pub id: Uuid,
-    pub from: Option<PublicKey>,
-    pub to: Option<PublicKey>,
-    pub value: u64,
+    //the network the tx is meant for. It's part of what gets signed, so a tx can't be replayed on another chain
+    pub chain_id: u64,
+    pub from: Option<Address>,
+    pub to: Option<Address>,
+    pub value: U256,
     pub data: TxData,
-    pub gas_limit: u64,
+    pub gas_limit: U256,
+    //transfers only. Has to be at least the sender's account nonce, which then moves past it - so once the tx is
+    // mined it can never run again, however long after. Left out of the json while it's 0, so hashes and
+    // signatures of tx without one don't change
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub nonce: u64,
+    //the account that pays for the gas instead of the sender, see Transaction::sponsor(). For an account creation
+    // it's whoever pays to deploy the code - the new account starts out with nothing. The sender
+    // signs it along with everything else, so nobody can pin a fee payer on a tx that wasn't meant to have one.
+    // Left out of the json while unset, same as the nonce
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_payer: Option<Address>,
+    //transfers only - what the recipient's code reads with CALLDATALOAD, eg which function to run and its arguments.
+    // Left out of the json while empty, same as the nonce
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub call_data: Vec<i32>,
+}
+
+/// what running a tx came to. Contract code that fails doesn't make the tx invalid - it still gets mined, uses up the
+/// sender's nonce and pays for its gas, only the value and the code's storage writes don't go through
+#[derive(Debug, PartialEq)]
+pub struct TxOutcome {
+    pub gas_used: u64,
+    /// why the contract's code failed. None if it ran fine, or there wasn't any to run
+    pub error: Option<ExecError>,
+}
+
+impl TxOutcome {
+    pub fn success(gas_used: u64) -> Self {
+        Self {
+            gas_used,
+            error: None,
+        }
+    }
+    pub fn status(&self) -> ReceiptStatus {
+        match self.error {
+            None => ReceiptStatus::Success,
+            Some(_) => ReceiptStatus::Failure,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Transaction {
     pub unsigned_tx: UnsignedTx,
     pub signature: Option<Signature>,
+    //signatures from a multisig account's signers, in place of `signature`.
+    // Left out of the json when empty, so hashes of ordinary tx don't change
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cosignatures: Vec<Cosignature>,
+    //the fee payer's, over sponsorship_message(). Left out of the json when there's none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_payer_signature: Option<Signature>,
 }
 
 impl Transaction {
+    /// same hash that's used as the tx's key in the block's tx trie
+    pub fn hash(&self) -> String {
+        codec::hash(self)
+    }
+
+    /// bytes of data a transfer carries on top of the transfer itself: its call data, 4 bytes a word. Plus any account
+    /// data - the ones built here never carry any, but nothing stops a transfer from being sent with it attached.
+    /// For an account creation it's the code it deploys, 4 bytes an opcode
+    pub fn payload_bytes(&self) -> usize {
+        match self.unsigned_tx.data.tx_type {
+            TxType::Transact => {
+                4 * self.unsigned_tx.call_data.len()
+                    + self
+                        .unsigned_tx
+                        .data
+                        .account_data
+                        .as_ref()
+                        .map_or(0, |account_data| {
+                            serde_json::to_vec(account_data).unwrap().len()
+                        })
+            }
+            TxType::CreateAccount => self
+                .unsigned_tx
+                .data
+                .account_data
+                .as_ref()
+                .map_or(0, |account_data| 4 * account_data.code.len()),
+            TxType::MiningReward => 0,
+        }
+    }
+
+    /// the gas limit, capped to what fits in a u64
+    fn gas_limit_u64(&self) -> u64 {
+        std::cmp::min(self.unsigned_tx.gas_limit, U256::from(u64::MAX)).low_u64()
+    }
+
+    /// what's left of the gas limit for the recipient's code once the payload is paid for
+    pub fn execution_gas_limit(&self) -> u64 {
+        self.gas_limit_u64()
+            .saturating_sub(payload_gas(self.payload_bytes()))
+    }
+
+    /// set up to run the recipient's code for this tx, in the block that env describes
+    pub fn interpreter(&self, env: BlockEnv) -> Interpreter {
+        let interpreter = Interpreter::new()
+            .with_env(env)
+            .with_gas_limit(self.execution_gas_limit());
+        match &self.unsigned_tx.from {
+            Some(from) => interpreter.with_call(from, self.unsigned_tx.call_data.clone()),
+            None => interpreter,
+        }
+    }
+
+    /// mempool admission, for tx submitted to this node and tx coming in from peers alike
+    pub fn check_size(serialized_tx: &str) -> Result<(), TxError> {
+        if serialized_tx.len() > MAX_TX_BYTES {
+            return Err(TxError::TooLarge(serialized_tx.len(), MAX_TX_BYTES));
+        }
+        Ok(())
+    }
+
+    /// whether the address sent, received, paid the gas or (for account creation tx) is the account being created
+    pub fn involves(&self, address: &Address) -> bool {
+        let tx = &self.unsigned_tx;
+        tx.from.as_ref() == Some(address)
+            || tx.to.as_ref() == Some(address)
+            || tx.fee_payer.as_ref() == Some(address)
+            || tx.data.account_data.as_ref().map(|a| &a.address) == Some(address)
+    }
+
     pub fn create_transaction(
         account: Option<Account>,
-        to: Option<PublicKey>,
-        value: u64, //note can be 0
-        beneficiary: Option<PublicKey>,
-        gas_limit: u64,
+        to: Option<Address>,
+        value: impl Into<U256>, //note can be 0
+        beneficiary: Option<Address>,
+        gas_limit: impl Into<U256>,
+        chain_id: u64,
     ) -> Self {
         let id = Uuid::new_v4();
+        let value = value.into();
+        let gas_limit = gas_limit.into();
         //case 1 - mining tx (signified through the presence of the beneficiary)
         if let Some(beneficiary) = beneficiary {
             return Self {
                 //don't need a signature, so simply return
                 unsigned_tx: UnsignedTx {
                     id,
+                    chain_id,
                     from: None,
                     to: Some(beneficiary),
-                    value: MINING_REWARD,
+                    value: MINING_REWARD.into(),
                     data: TxData {
                         tx_type: TxType::MiningReward,
                         account_data: None,
                     },
                     gas_limit,
+                    nonce: 0,
+                    fee_payer: None,
+                    call_data: vec![],
                 },
                 signature: None,
+                cosignatures: vec![],
+                fee_payer_signature: None,
             };
         }
         let unsigned_tx;
         let acc;
-        //case 2 - normal tx (signified through the presence of the "to" field)
+        //case 2 - normal tx (signified through the presence of the "to" field). Goes out with the account's nonce,
+        // see Account::with_nonce()
         if let Some(to) = to {
             acc = account.unwrap();
             unsigned_tx = UnsignedTx {
                 id,
-                from: Some(acc.public_account.address.clone()),
+                chain_id,
+                from: Some(acc.public_account.address),
                 to: Some(to),
                 value,
                 data: TxData {
@@ -79,12 +226,16 @@ impl Transaction {
                     account_data: None,
                 },
                 gas_limit,
+                nonce: acc.public_account.nonce,
+                fee_payer: None,
+                call_data: vec![],
             };
         //case 3 - account creation tx (if both beneficiary and to are absent)
         } else {
             acc = account.unwrap();
             unsigned_tx = UnsignedTx {
                 id,
+                chain_id,
                 from: None,
                 to: None,
                 value,
@@ -93,68 +244,515 @@ impl Transaction {
                     account_data: Some(acc.public_account.clone()), //will have smart contract code in there if it's included in address defn
                 },
                 gas_limit,
+                nonce: 0,
+                fee_payer: None,
+                call_data: vec![],
             };
         }
         let serialized_tx = serde_json::to_string(&unsigned_tx).unwrap();
         Self {
             unsigned_tx,
             signature: Some(acc.sign(&serialized_tx)),
+            cosignatures: vec![],
+            fee_payer_signature: None,
         }
     }
 
-    pub fn validate_transaction(tx: &Transaction, state: &mut State) -> bool {
-        let serialized_tx = serde_json::to_string(&tx.unsigned_tx).unwrap();
-        let public_key = &tx.unsigned_tx.from.unwrap();
-        let sig = &tx.signature.unwrap();
+    /// a transfer whose gas fee_payer pays, signed by both. The sender still pays the value
+    pub fn create_sponsored_transaction(
+        account: Account,
+        to: Address,
+        value: impl Into<U256>,
+        gas_limit: impl Into<U256>,
+        fee_payer: &Account,
+        chain_id: u64,
+    ) -> Self {
+        Transaction::create_call(
+            account,
+            to,
+            value,
+            vec![],
+            gas_limit,
+            Some(fee_payer),
+            chain_id,
+        )
+    }
 
-        if !Account::verify_signature(&serialized_tx, sig, public_key) {
-            println!("transaction signature invalid.");
-            return false;
+    /// a transfer that hands the recipient's code call_data to read. Sponsored if there's a fee_payer, see
+    /// create_sponsored_transaction()
+    pub fn create_call(
+        account: Account,
+        to: Address,
+        value: impl Into<U256>,
+        call_data: Vec<i32>,
+        gas_limit: impl Into<U256>,
+        fee_payer: Option<&Account>,
+        chain_id: u64,
+    ) -> Self {
+        let mut unsigned_tx = Transaction::create_unsigned_transaction(
+            account.public_account.address,
+            Some(to),
+            value,
+            vec![],
+            gas_limit,
+            account.public_account.nonce,
+            chain_id,
+        );
+        unsigned_tx.call_data = call_data;
+        unsigned_tx.fee_payer = fee_payer.map(|payer| payer.public_account.address);
+        let serialized_tx = serde_json::to_string(&unsigned_tx).unwrap();
+        let mut tx = Self {
+            unsigned_tx,
+            signature: Some(account.sign(&serialized_tx)),
+            cosignatures: vec![],
+            fee_payer_signature: None,
         };
+        if let Some(fee_payer) = fee_payer {
+            //can't fail, the tx names them
+            tx.sponsor(fee_payer).unwrap();
+        }
+        tx
+    }
 
-        let from_account = state.get_account(tx.unsigned_tx.from.unwrap());
-        let to_account = state.get_account(tx.unsigned_tx.to.unwrap());
-        //important to include both the tx value and the gas limit
-        if (tx.unsigned_tx.value + tx.unsigned_tx.gas_limit) > from_account.balance {
-            println!("exceeded balance");
-            return false;
+    /// the account creation tx that deploys account's code, with creator paying for the code's payload gas
+    pub fn create_contract(
+        account: Account,
+        value: impl Into<U256>,
+        gas_limit: impl Into<U256>,
+        creator: &Account,
+        chain_id: u64,
+    ) -> Self {
+        let mut unsigned_tx = Transaction::create_unsigned_transaction(
+            account.public_account.address,
+            None,
+            value,
+            account.public_account.code.clone(),
+            gas_limit,
+            0,
+            chain_id,
+        );
+        unsigned_tx.fee_payer = Some(creator.public_account.address);
+        let serialized_tx = serde_json::to_string(&unsigned_tx).unwrap();
+        let mut tx = Self {
+            unsigned_tx,
+            signature: Some(account.sign(&serialized_tx)),
+            cosignatures: vec![],
+            fee_payer_signature: None,
+        };
+        //can't fail, the tx names them
+        tx.sponsor(creator).unwrap();
+        tx
+    }
+
+    /// a tx for someone else to sign, eg a hardware wallet - the node never sees the key.
+    /// Same shapes create_transaction() builds: a transfer if "to" is set, otherwise "from" creates its own account.
+    /// The nonce only goes on transfers
+    pub fn create_unsigned_transaction(
+        from: Address,
+        to: Option<Address>,
+        value: impl Into<U256>,
+        code: Vec<OPCODE>,
+        gas_limit: impl Into<U256>,
+        nonce: u64,
+        chain_id: u64,
+    ) -> UnsignedTx {
+        let data = match to {
+            Some(_) => TxData {
+                tx_type: TxType::Transact,
+                account_data: None,
+            },
+            None => TxData {
+                tx_type: TxType::CreateAccount,
+                account_data: Some(PublicAccount {
+                    address: from,
+                    balance: U256::zero(),
+                    code_hash: Account::gen_code_hash(&from, &code),
+                    code,
+                    nonce: 0,
+                    multisig: None,
+                }),
+            },
+        };
+        UnsignedTx {
+            id: Uuid::new_v4(),
+            chain_id,
+            //account creation tx carry their address in account_data instead
+            from: to.map(|_| from),
+            to,
+            value: value.into(),
+            data,
+            gas_limit: gas_limit.into(),
+            nonce: to.map_or(0, |_| nonce),
+            fee_payer: None,
+            call_data: vec![],
         }
+    }
 
-        //when hitting a SC
-        if to_account.code_hash.is_some() {
-            let storage_trie = state.storage_trie_map.get_mut(&to_account.address).unwrap();
-            let mut interpreter = Interpreter::new();
-            let gas_used = interpreter.run_code(to_account.code, storage_trie).gas_used;
-            if tx.unsigned_tx.gas_limit < gas_used {
-                println!("insufficient gas limit to execute the samrt contract. Provided: {}, Needed: {}",
-                tx.unsigned_tx.gas_limit, gas_used);
-                return false;
+    /// what gets signed - the sha256 hash of the serialized unsigned tx (see Account::sign), hex encoded
+    pub fn signing_hash(unsigned_tx: &UnsignedTx) -> String {
+        let serialized_tx = serde_json::to_string(unsigned_tx).unwrap();
+        hex::encode(sha256::Hash::hash(serialized_tx.as_bytes()).into_inner())
+    }
+
+    /// puts an externally made signature (hex, compact or DER) on the tx, as long as it's from the right key
+    pub fn from_external_signature(
+        unsigned_tx: UnsignedTx,
+        signature: &str,
+    ) -> Result<Self, String> {
+        let bytes = hex::decode(signature.trim_start_matches("0x"))
+            .map_err(|_| "signature must be hex.".to_string())?;
+        let mut signature = Signature::from_compact(&bytes)
+            .or_else(|_| Signature::from_der(&bytes))
+            .map_err(|_| "signature must be 64 bytes compact or DER.".to_string())?;
+        //some signers don't produce low-s signatures, which secp256k1 refuses to verify
+        signature.normalize_s();
+
+        let signer = match unsigned_tx.data.tx_type {
+            TxType::Transact => unsigned_tx.from,
+            TxType::CreateAccount => unsigned_tx.data.account_data.as_ref().map(|a| a.address),
+            TxType::MiningReward => None,
+        }
+        .ok_or_else(|| "the tx has no signer.".to_string())?;
+        let serialized_tx = serde_json::to_string(&unsigned_tx).unwrap();
+        if !Account::verify_signature(&serialized_tx, &signature, &signer) {
+            return Err(format!("signature isn't from {}.", signer));
+        }
+        Ok(Self {
+            unsigned_tx,
+            signature: Some(signature),
+            cosignatures: vec![],
+            fee_payer_signature: None,
+        })
+    }
+
+    /// an outgoing tx from a multisig account. Goes out unsigned - it's valid once enough of the signers cosign() it
+    pub fn create_multisig_transaction(
+        from: Address,
+        to: Address,
+        value: impl Into<U256>,
+        gas_limit: impl Into<U256>,
+        nonce: u64,
+        chain_id: u64,
+    ) -> Self {
+        Self {
+            unsigned_tx: Transaction::create_unsigned_transaction(
+                from,
+                Some(to),
+                value,
+                vec![],
+                gas_limit,
+                nonce,
+                chain_id,
+            ),
+            signature: None,
+            cosignatures: vec![],
+            fee_payer_signature: None,
+        }
+    }
+
+    /// adds the signer's signature, replacing any earlier one of theirs
+    pub fn cosign(&mut self, signer: &Account) {
+        let serialized_tx = serde_json::to_string(&self.unsigned_tx).unwrap();
+        let address = signer.public_account.address;
+        self.cosignatures.retain(|c| c.signer != address);
+        self.cosignatures.push(Cosignature {
+            signer: address,
+            signature: signer.sign(&serialized_tx),
+        });
+    }
+
+    /// what a fee payer signs: the serialized unsigned tx, same as the sender, but prefixed - so the payer's signature
+    /// can't pass for the sender's or for a multisig cosignature if their key happens to be one of those too
+    fn sponsorship_message(unsigned_tx: &UnsignedTx) -> String {
+        format!("fee payer:{}", serde_json::to_string(unsigned_tx).unwrap())
+    }
+
+    /// adds the fee payer's signature, agreeing to pay for the tx's gas. The sender has to have named them already
+    pub fn sponsor(&mut self, fee_payer: &Account) -> Result<(), TxError> {
+        if self.unsigned_tx.fee_payer != Some(fee_payer.public_account.address) {
+            return Err(TxError::FeePayer("isn't the one the tx names"));
+        }
+        let message = Transaction::sponsorship_message(&self.unsigned_tx);
+        self.fee_payer_signature = Some(fee_payer.sign(&message));
+        Ok(())
+    }
+
+    pub fn validate_transaction(tx: &Transaction, state: &impl StateAccess) -> bool {
+        match Transaction::check_transaction(tx, state) {
+            Ok(()) => true,
+            Err(reason) => {
+                tracing::warn!(reason = %reason, "invalid tx");
+                false
             }
         }
+    }
 
-        true
+    /// same checks as validate_transaction(), but returns the reason the tx is invalid instead of logging it.
+    /// Doesn't run contract code - whether it goes through is only known once the tx runs, see run_standard_tx().
+    /// To find out up front, use simulate()
+    pub fn check_transaction(tx: &Transaction, state: &impl StateAccess) -> Result<(), TxError> {
+        let (from, _) = Transaction::transfer_parties(tx)?;
+        let from_account = state.get_account(from)?;
+
+        //a multisig account's own key can't move its funds - only its signers can
+        match &from_account.multisig {
+            Some(multisig) => {
+                let serialized_tx = serde_json::to_string(&tx.unsigned_tx).unwrap();
+                multisig
+                    .check_signatures(&serialized_tx, &tx.cosignatures)
+                    .map_err(TxError::Multisig)?
+            }
+            None => Transaction::check_signature(tx)?,
+        }
+        //against the state before the block, so a sender's later tx in the same block pass too. Running them is what
+        // checks they come in order
+        Transaction::check_nonce(tx, &from_account)?;
+
+        //known without running anything, unlike what contract code uses
+        let needed = payload_gas(tx.payload_bytes());
+        if tx.unsigned_tx.gas_limit < U256::from(needed) {
+            return Err(TxError::PayloadGas {
+                provided: tx.unsigned_tx.gas_limit,
+                needed,
+            });
+        }
+
+        //important to include both the tx value and all the gas the sender has to buy upfront - unless someone else
+        // buys the gas, then each side only has to cover its own part
+        let gas = gas_cost(tx.unsigned_tx.gas_limit).map_err(TxError::Overflow)?;
+        match tx.unsigned_tx.fee_payer {
+            Some(fee_payer) => {
+                let payer_account = Transaction::check_fee_payer(tx, fee_payer, state)?;
+                if gas > payer_account.balance {
+                    return Err(TxError::FeePayer("can't cover the gas"));
+                }
+                if tx.unsigned_tx.value > from_account.balance {
+                    return Err(TxError::ExceededBalance);
+                }
+            }
+            None => {
+                let cost = checked_add(tx.unsigned_tx.value, gas).map_err(TxError::Overflow)?;
+                if cost > from_account.balance {
+                    return Err(TxError::ExceededBalance);
+                }
+            }
+        }
+        Ok(())
     }
 
-    pub fn validate_create_account_transaction(_tx: &Transaction) -> bool {
+    /// that the sender signed the tx with its own key. The part of check_transaction() that doesn't need the state,
+    /// and all there is to check for a sender that isn't on chain yet
+    pub fn check_signature(tx: &Transaction) -> Result<(), TxError> {
+        let (from, _) = Transaction::transfer_parties(tx)?;
+        let serialized_tx = serde_json::to_string(&tx.unsigned_tx).unwrap();
+        let sig = tx.signature.as_ref().ok_or(TxError::MissingSignature)?;
+        let key = cache_key(&serialized_tx, sig, &from);
+        let verified = verify_cached(&key, || {
+            Account::verify_signature(&serialized_tx, sig, &from)
+        });
+        if !verified {
+            return Err(TxError::InvalidSignature);
+        }
+        Ok(())
+    }
+
+    /// the fee payer's side of check_transaction(): an account other than the sender, that signed off on the tx
+    fn check_fee_payer(
+        tx: &Transaction,
+        fee_payer: Address,
+        state: &impl StateAccess,
+    ) -> Result<PublicAccount, TxError> {
+        //would let a sender get around paying value + gas out of the one balance
+        if tx.unsigned_tx.from == Some(fee_payer) {
+            return Err(TxError::FeePayer("is the sender"));
+        }
+        let payer_account = state.get_account(fee_payer)?;
+        //same as sending from one - its own key can't spend its funds
+        if payer_account.multisig.is_some() {
+            return Err(TxError::FeePayer("can't be a multisig account"));
+        }
+        let sig = tx
+            .fee_payer_signature
+            .as_ref()
+            .ok_or(TxError::FeePayer("signature missing"))?;
+        let message = Transaction::sponsorship_message(&tx.unsigned_tx);
+        let key = cache_key(&message, sig, &fee_payer);
+        let verified = verify_cached(&key, || {
+            Account::verify_signature(&message, sig, &fee_payer)
+        });
+        if !verified {
+            return Err(TxError::FeePayer("signature invalid"));
+        }
+        Ok(payer_account)
+    }
+
+    /// spam filter for transfers that can't do anything: zero value to its own sender, or zero value to an account
+    /// with no code to run. Keeps them out of the queue and out of new blocks, but isn't part of block validity, so
+    /// blocks that already hold such tx stay valid. The recipient itself needs no checking - any 20 bytes are an
+    /// address
+    pub fn check_recipient(tx: &Transaction, state: &impl StateAccess) -> Result<(), TxError> {
+        let (from, to) = Transaction::transfer_parties(tx)?;
+        if !tx.unsigned_tx.value.is_zero() {
+            return Ok(());
+        }
+        if from == to {
+            return Err(TxError::Pointless("sends nothing to its own sender"));
+        }
+        let has_code = state
+            .find_account(to)
+            .is_some_and(|account| account.code_hash.is_some());
+        if !has_code {
+            return Err(TxError::Pointless(
+                "sends nothing to an account with no code to run",
+            ));
+        }
+        Ok(())
+    }
+
+    /// check_transaction(), then runs the tx on an overlay of the state to see whether it'd go through, incl
+    /// running a contract's code against the gas limit. Returns the gas it used. The state itself is never touched.
+    /// Code that fails is an error here - mined, the tx would only pay for gas and do nothing else.
+    /// env = the block the tx would go into
+    pub fn simulate(tx: &Transaction, state: &State, env: BlockEnv) -> Result<u64, TxError> {
+        Transaction::check_transaction(tx, state)?;
+        let outcome = Transaction::run_transaction(tx, &mut OverlayState::new(state), None, env)?;
+        match outcome.error {
+            Some(e) => Err(TxError::Exec(e)),
+            None => Ok(outcome.gas_used),
+        }
+    }
+
+    /// a nonce below the account's has been used already - by this tx, or by another one from the same sender. Gaps are
+    /// fine, it only has to go up
+    pub fn check_nonce(tx: &Transaction, sender: &PublicAccount) -> Result<(), TxError> {
+        if tx.unsigned_tx.nonce < sender.nonce {
+            return Err(TxError::NonceTooLow {
+                nonce: tx.unsigned_tx.nonce,
+                next: sender.nonce,
+            });
+        }
+        Ok(())
+    }
+
+    /// sender and recipient of a transfer - both are just Options on the wire
+    fn transfer_parties(tx: &Transaction) -> Result<(Address, Address), TxError> {
+        let from = tx.unsigned_tx.from.ok_or(TxError::MissingField("sender"))?;
+        let to = tx
+            .unsigned_tx
+            .to
+            .ok_or(TxError::MissingField("recipient"))?;
+        Ok((from, to))
+    }
+
+    /// fork = the rules of the block the tx goes into
+    pub fn validate_create_account_transaction(tx: &Transaction, fork: Fork) -> bool {
         //NOTE1: the tests written in js are not necessary in rust due to static typing
         //NOTE2: can't run signature verification because "from" field is empty
+        let balance = tx
+            .unsigned_tx
+            .data
+            .account_data
+            .as_ref()
+            .map(|account_data| account_data.balance);
+        //otherwise anyone could mint themselves money just by creating an account
+        if balance != Some(U256::zero()) {
+            tracing::warn!("invalid tx: created account must start with 0 balance");
+            return false;
+        }
+        //no code runs, there's nothing to read it
+        if !tx.unsigned_tx.call_data.is_empty() {
+            tracing::warn!("invalid tx: only transfers can have call data");
+            return false;
+        }
+        //checked right above - balance is only Some if there's account data
+        let account_data = tx.unsigned_tx.data.account_data.as_ref().unwrap();
+        //the code's payload gas has to come from somewhere, and the account starts out with nothing. Without code
+        // there's nothing to pay for
+        match (
+            account_data.code.is_empty(),
+            tx.unsigned_tx.fee_payer.is_some(),
+        ) {
+            (false, false) => {
+                tracing::warn!("invalid tx: deploying code takes a fee payer to pay for it");
+                return false;
+            }
+            (true, true) => {
+                tracing::warn!(
+                    "invalid tx: an account without code has nothing for a fee payer to pay for"
+                );
+                return false;
+            }
+            _ => {}
+        }
+        let needed = payload_gas(tx.payload_bytes());
+        if tx.unsigned_tx.gas_limit < U256::from(needed) {
+            tracing::warn!(needed, gas_limit = %tx.unsigned_tx.gas_limit, "invalid tx: gas limit doesn't cover the code");
+            return false;
+        }
+        if let Some(multisig) = &account_data.multisig {
+            if let Err(reason) = multisig.validate() {
+                tracing::warn!(reason = %reason, "invalid tx: bad multisig config");
+                return false;
+            }
+            //a contract would run its code for anyone who sends to it, there's nothing for signers to approve
+            if !account_data.code.is_empty() {
+                tracing::warn!("invalid tx: a smart contract can't be a multisig");
+                return false;
+            }
+        }
+        //would deploy fine and then fail every call until the fork activates
+        if let Some(opcode) = account_data.code.iter().find(|op| !fork.has_opcode(op)) {
+            tracing::warn!(opcode = ?opcode, fork = fork.name(), "invalid tx: contract code uses an opcode that isn't active yet");
+            return false;
+        }
         true
     }
 
-    pub fn validate_mining_reward_transaction(tx: &Transaction) -> bool {
-        if tx.unsigned_tx.value != MINING_REWARD {
-            println!("value doesn't equal mining reward.");
+    /// the part of an account creation's checks that needs the state: that whoever pays for its code signed off on
+    /// it and can cover the gas limit. Nothing to check for an account without code, it has no fee payer
+    pub fn check_creation_payer(tx: &Transaction, state: &impl StateAccess) -> Result<(), TxError> {
+        if let Some(fee_payer) = tx.unsigned_tx.fee_payer {
+            let payer_account = Transaction::check_fee_payer(tx, fee_payer, state)?;
+            let gas = gas_cost(tx.unsigned_tx.gas_limit).map_err(TxError::Overflow)?;
+            if gas > payer_account.balance {
+                return Err(TxError::FeePayer("can't cover the gas"));
+            }
+        }
+        Ok(())
+    }
+
+    /// reward = what the reward schedule pays for the block the tx is in
+    pub fn validate_mining_reward_transaction(tx: &Transaction, reward: U256) -> bool {
+        if tx.unsigned_tx.value != reward {
+            tracing::warn!(value = %tx.unsigned_tx.value, reward = %reward, "invalid tx: value doesn't equal mining reward");
             return false;
         }
         true
     }
 
-    pub fn validate_transaction_series(tx_series: &Vec<Transaction>, state: &mut State) -> bool {
+    /// block_number = the block the series is in, which decides the mining reward and the fork's rules
+    pub fn validate_transaction_series(
+        tx_series: &Vec<Transaction>,
+        block_number: usize,
+        state: &impl StateAccess,
+        rules: &ChainRules,
+    ) -> bool {
+        let reward = rules.reward_schedule.reward_at(block_number);
+        let fork = rules.fork_schedule.fork_at(block_number);
         for tx in tx_series {
             let is_valid = match tx.unsigned_tx.data.tx_type {
-                TxType::MiningReward => Transaction::validate_mining_reward_transaction(tx),
+                TxType::MiningReward => Transaction::validate_mining_reward_transaction(tx, reward),
                 TxType::Transact => Transaction::validate_transaction(tx, state),
-                TxType::CreateAccount => Transaction::validate_create_account_transaction(tx),
+                TxType::CreateAccount => {
+                    Transaction::validate_create_account_transaction(tx, fork)
+                        && match Transaction::check_creation_payer(tx, state) {
+                            Ok(()) => true,
+                            Err(reason) => {
+                                tracing::warn!(reason = %reason, "invalid tx");
+                                false
+                            }
+                        }
+                }
             };
             //if at least 1 tx fails, then the entire series fails and we return false
             if !is_valid {
@@ -164,83 +762,314 @@ impl Transaction {
         true
     }
 
-    pub fn run_transaction(tx: &Transaction, state: &mut State) {
+    /// returns the amount of gas used, and whether the contract's code failed. Only meant for tx that passed
+    /// validation - on an error the state may be partially updated, so run on a copy you can throw away.
+    /// The gas fee goes to payees - the block's, or None outside a block (see GasPurchase::settle()).
+    /// Contract code runs in env, the block's
+    pub fn run_transaction(
+        tx: &Transaction,
+        state: &mut impl StateAccess,
+        payees: Option<Payees>,
+        env: BlockEnv,
+    ) -> Result<TxOutcome, TxError> {
         match tx.unsigned_tx.data.tx_type {
-            TxType::MiningReward => Transaction::run_mining_tx(tx, state),
-            TxType::Transact => Transaction::run_standard_tx(tx, state),
-            TxType::CreateAccount => Transaction::run_create_account_tx(tx, state),
+            TxType::MiningReward => Transaction::run_mining_tx(tx, state, payees),
+            TxType::Transact => Transaction::run_standard_tx(tx, state, payees, env),
+            TxType::CreateAccount => Transaction::run_create_account_tx(tx, state, payees),
         }
     }
 
-    pub fn run_mining_tx(tx: &Transaction, state: &mut State) {
-        let to = tx.unsigned_tx.to.unwrap();
-        let value = tx.unsigned_tx.value;
-        let mut account = state.get_account(to);
-
-        account.balance += value;
-
-        state.put_account(account.address, account);
+    /// the reward goes to the tx's recipient, less the treasury's cut if the block's payees have a treasury
+    pub fn run_mining_tx(
+        tx: &Transaction,
+        state: &mut impl StateAccess,
+        payees: Option<Payees>,
+    ) -> Result<TxOutcome, TxError> {
+        let to = tx
+            .unsigned_tx
+            .to
+            .ok_or(TxError::MissingField("beneficiary"))?;
+        let payees = Payees {
+            beneficiary: to,
+            treasury: payees.and_then(|payees| payees.treasury),
+        };
+        payees.pay(state, tx.unsigned_tx.value, |treasury| {
+            treasury.reward_percent
+        });
+        Ok(TxOutcome::success(0))
     }
 
-    pub fn run_standard_tx(tx: &Transaction, state: &mut State) {
-        let mut from_account = state.get_account(tx.unsigned_tx.from.unwrap());
-        let mut to_account = state.get_account(tx.unsigned_tx.to.unwrap());
-        let mut refund = tx.unsigned_tx.gas_limit;
+    /// gas gets bought and settled through GasPurchase - by the fee payer if the tx has one, otherwise the sender - and
+    /// the value moves in between. Gas used is the payload's plus whatever the recipient's code uses.
+    /// If the code fails the value stays put and its storage writes are undone, but the nonce is used up and the gas
+    /// paid for, same as in ethereum: what it used up to a REVERT, the whole gas limit for anything else
+    pub fn run_standard_tx(
+        tx: &Transaction,
+        state: &mut impl StateAccess,
+        payees: Option<Payees>,
+        env: BlockEnv,
+    ) -> Result<TxOutcome, TxError> {
+        let (from, to) = Transaction::transfer_parties(tx)?;
+        //checked again here, against the state as it is by this tx - the one before it in the block may have used it
+        Transaction::check_nonce(tx, &state.get_account(from)?)?;
+        let next_nonce = tx
+            .unsigned_tx
+            .nonce
+            .checked_add(1)
+            .ok_or_else(|| TxError::Overflow("nonce overflow".into()))?;
+        let payer = tx.unsigned_tx.fee_payer.unwrap_or(from);
+        let gas = GasPurchase::buy(state, payer, tx.unsigned_tx.gas_limit)?;
+        let mut gas_used = payload_gas(tx.payload_bytes());
+        let mut error = None;
 
         //if true, then we're interacting with a smart contract
+        let to_account = state.get_account_or_empty(to);
         if to_account.code_hash.is_some() {
-            let mut interpreter = Interpreter::new();
-            let storage_trie = state.storage_trie_map.get_mut(&to_account.address).unwrap();
-            let evm_ret_val = interpreter.run_code(to_account.code.clone(), storage_trie);
-            println!(
-                "SMART CONTRACT EXECUTION AT ADDRESS: {}. RESULT: {}, GAS USED: {}",
-                &to_account.address,
-                extract_val_from_opcode(&evm_ret_val.ret_val).unwrap(),
-                evm_ret_val.gas_used,
-            );
-            //decrease the refund by the amount of gas used
-            refund -= evm_ret_val.gas_used;
-
-            // NOTE: in current implementation interpreter doesn't actually decrement gas of the SC, so we're simply not gonna add it
-            // if we're hitting a SC we're gonna want to give it the gas to run
-            // to_account.balance += evm_ret_val.gas_used;
+            let mut interpreter = tx.interpreter(env);
+            let storage_trie = state.storage_trie_mut(to_account.address);
+            match interpreter.run_code(to_account.code.clone(), storage_trie) {
+                Ok(evm_ret_val) => {
+                    tracing::info!(
+                        address = %to_account.address,
+                        result = ?evm_ret_val.ret_val,
+                        gas_used = evm_ret_val.gas_used,
+                        "smart contract executed"
+                    );
+                    gas_used = gas_used.saturating_add(evm_ret_val.gas_used);
+                }
+                //run_code() has already put the storage back
+                Err(e) => {
+                    tracing::info!(address = %to_account.address, error = %e, "smart contract failed");
+                    gas_used = match e {
+                        ExecError::Reverted {
+                            gas_used: code_gas, ..
+                        } => gas_used.saturating_add(code_gas),
+                        _ => tx.gas_limit_u64(),
+                    };
+                    error = Some(e);
+                }
+            }
         }
 
-        from_account.balance -= tx.unsigned_tx.value;
-        from_account.balance -= tx.unsigned_tx.gas_limit;
-        from_account.balance += refund;
-        to_account.balance += tx.unsigned_tx.value;
+        //read back after every put, so sending to yourself doesn't lose one of the updates
+        let mut from_account = state.get_account(from)?;
+        if error.is_none() {
+            from_account.balance = checked_sub(from_account.balance, tx.unsigned_tx.value)
+                .map_err(|_| TxError::ExceededBalance)?;
+        }
+        from_account.nonce = next_nonce;
+        state.put_account(from, from_account);
+        if error.is_none() {
+            let mut to_account = state.get_account_or_empty(to);
+            to_account.balance = to_account.balance.saturating_add(tx.unsigned_tx.value);
+            state.put_account(to, to_account);
+        }
 
-        state.put_account(from_account.address, from_account);
-        state.put_account(to_account.address, to_account);
+        gas.settle(state, gas_used, payees);
+        Ok(TxOutcome { gas_used, error })
     }
 
-    pub fn run_create_account_tx(tx: &Transaction, state: &mut State) {
-        let account_data = tx.unsigned_tx.data.account_data.clone().unwrap();
+    /// code comes with a fee payer, who buys the gas and pays for the code's payload out of it, same as a transfer's
+    pub fn run_create_account_tx(
+        tx: &Transaction,
+        state: &mut impl StateAccess,
+        payees: Option<Payees>,
+    ) -> Result<TxOutcome, TxError> {
+        let mut account_data = tx
+            .unsigned_tx
+            .data
+            .account_data
+            .clone()
+            .ok_or(TxError::MissingField("account data"))?;
+        //the address might have been sent value (or allocated some at genesis) before this tx got mined - keep it.
+        // Same for its nonce, or recreating an account would let its old transfers run again
+        let existing = state.get_account_or_empty(account_data.address);
+        account_data.balance = existing.balance;
+        account_data.nonce = existing.nonce;
+        let gas = tx
+            .unsigned_tx
+            .fee_payer
+            .map(|payer| GasPurchase::buy(state, payer, tx.unsigned_tx.gas_limit))
+            .transpose()?;
+        let gas_used = payload_gas(tx.payload_bytes());
 
         //in real ethereum SC's address is the hash of the sender's account + nonce - https://github.com/ethereumbook/ethereumbook/blob/develop/07smart-contracts-solidity.asciidoc
-        //in our implementation, because we're using PublicKey struct we can't simply use a hash
-        //so we just specify a SC address manually, exactly like we would for a normal account
+        //in our implementation there's no separate scheme for contracts - the creator's key comes with an address
+        //like any other account's, and the contract simply lives there
         state.put_account(account_data.address, account_data);
+        if let Some(gas) = gas {
+            gas.settle(state, gas_used, payees);
+        }
+        Ok(TxOutcome::success(gas_used))
+    }
+}
+
+impl Encode for TxType {
+    fn to_rlp(&self) -> Rlp {
+        let discriminant: u64 = match self {
+            TxType::CreateAccount => 0,
+            TxType::Transact => 1,
+            TxType::MiningReward => 2,
+        };
+        discriminant.to_rlp()
+    }
+}
+
+impl Decode for TxType {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        match u64::from_rlp(item)? {
+            0 => Ok(TxType::CreateAccount),
+            1 => Ok(TxType::Transact),
+            2 => Ok(TxType::MiningReward),
+            other => Err(CodecError::Malformed(format!("unknown tx type {}", other))),
+        }
+    }
+}
+
+impl Encode for TxData {
+    fn to_rlp(&self) -> Rlp {
+        Record::new()
+            .field(1, &self.tx_type)
+            .optional(2, &self.account_data)
+            .build()
+    }
+}
+
+impl Decode for TxData {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        let fields = Fields::of(item)?;
+        Ok(Self {
+            tx_type: fields.get(1)?,
+            account_data: fields.optional(2)?,
+        })
+    }
+}
+
+impl Encode for UnsignedTx {
+    fn to_rlp(&self) -> Rlp {
+        Record::new()
+            .field(1, &self.id)
+            .field(2, &self.chain_id)
+            .optional(3, &self.from)
+            .optional(4, &self.to)
+            .field(5, &self.value)
+            .field(6, &self.data)
+            .field(7, &self.gas_limit)
+            .field(8, &self.nonce)
+            .optional(9, &self.fee_payer)
+            //left out while empty, so tx without any keep their hash
+            .optional(10, &Some(self.call_data.clone()).filter(|d| !d.is_empty()))
+            .build()
+    }
+}
+
+impl Decode for UnsignedTx {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        let fields = Fields::of(item)?;
+        Ok(Self {
+            id: fields.get(1)?,
+            chain_id: fields.get(2)?,
+            from: fields.optional(3)?,
+            to: fields.optional(4)?,
+            value: fields.get(5)?,
+            data: fields.get(6)?,
+            gas_limit: fields.get(7)?,
+            nonce: fields.get(8)?,
+            fee_payer: fields.optional(9)?,
+            call_data: fields.or_default(10)?,
+        })
+    }
+}
+
+impl Encode for Transaction {
+    fn to_rlp(&self) -> Rlp {
+        Record::new()
+            .field(1, &self.unsigned_tx)
+            .optional(2, &self.signature)
+            .field(3, &self.cosignatures)
+            .optional(4, &self.fee_payer_signature)
+            .build()
+    }
+}
+
+impl Decode for Transaction {
+    fn from_rlp(item: &Rlp) -> Result<Self, CodecError> {
+        let fields = Fields::of(item)?;
+        Ok(Self {
+            unsigned_tx: fields.get(1)?,
+            signature: fields.optional(2)?,
+            cosignatures: fields.get(3)?,
+            fee_payer_signature: fields.optional(4)?,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::interpreter::OPCODE;
+    use crate::account::multisig::MultisigConfig;
+    use crate::account::{gen_address, gen_keypair};
+    use crate::blockchain::gas_stats::GAS_PRICE;
+    use crate::config::DEFAULT_CHAIN_ID;
+    use crate::error::StoreError;
+    use crate::transaction::fee::GAS_PER_PAYLOAD_BYTE;
+    use secp256k1::{Message, Secp256k1};
 
     #[test]
     fn test_normal_account_creation() {
         let miner_account = Account::new(vec![]);
-        let tx = Transaction::create_transaction(Some(miner_account.clone()), None, 0, None, 100);
+        let tx = Transaction::create_transaction(
+            Some(miner_account.clone()),
+            None,
+            0,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
 
         let mut state = State::new();
         let state_before = state.clone();
 
-        Transaction::run_create_account_tx(&tx, &mut state);
+        Transaction::run_create_account_tx(&tx, &mut state, None).unwrap();
 
         assert_ne!(state_before.get_state_root(), state.get_state_root());
+        assert_eq!(
+            state
+                .get_account(miner_account.public_account.address)
+                .unwrap()
+                .balance,
+            U256::zero()
+        );
+    }
+
+    #[test]
+    fn test_account_creation_keeps_allocated_balance() {
+        let account = Account::new(vec![]);
+        let address = account.public_account.address;
+        let mut state = State::new();
+        state.allocate(address, 500);
+
+        let tx = Transaction::create_transaction(Some(account), None, 0, None, 0, DEFAULT_CHAIN_ID);
+        assert!(Transaction::validate_create_account_transaction(
+            &tx,
+            Fork::Frontier
+        ));
+        Transaction::run_create_account_tx(&tx, &mut state, None).unwrap();
+        assert_eq!(state.get_account(address).unwrap().balance, U256::from(500));
+
+        //a create account tx claiming a balance of its own is rejected
+        let mut minting_tx = tx.clone();
+        minting_tx
+            .unsigned_tx
+            .data
+            .account_data
+            .as_mut()
+            .unwrap()
+            .balance = 1000.into();
+        assert!(!Transaction::validate_create_account_transaction(
+            &minting_tx,
+            Fork::Frontier
+        ));
     }
 
     #[test]
@@ -254,7 +1083,8 @@ mod tests {
             OPCODE::STOP,
         ];
         let sc_account = Account::new(code);
-        let tx = Transaction::create_transaction(Some(sc_account), None, 0, None, 100);
+        let tx =
+            Transaction::create_transaction(Some(sc_account), None, 0, None, 100, DEFAULT_CHAIN_ID);
 
         //check to make sure we actually have coded embedded in tx's data, which will trigger the creation of SC account rather than normal account
         let code_hash = tx.unsigned_tx.data.account_data.clone().unwrap().code_hash;
@@ -263,8 +1093,839 @@ mod tests {
         let mut state = State::new();
         let state_before = state.clone();
 
-        Transaction::run_create_account_tx(&tx, &mut state);
+        Transaction::run_create_account_tx(&tx, &mut state, None).unwrap();
 
         assert_ne!(state_before.get_state_root(), state.get_state_root());
     }
+
+    #[test]
+    fn test_transfer_to_address_not_yet_in_state() {
+        let sender = Account::new(vec![]);
+        let mut state = State::new();
+        state.allocate(sender.public_account.address, 1000);
+
+        //only the keypair exists, no account creation tx was ever run for it
+        let receiver = Account::new(vec![]).public_account.address;
+        let tx = Transaction::create_transaction(
+            Some(sender.clone()),
+            Some(receiver),
+            10,
+            None,
+            0,
+            DEFAULT_CHAIN_ID,
+        );
+
+        assert!(Transaction::validate_transaction(&tx, &state));
+        Transaction::run_standard_tx(&tx, &mut state, None, BlockEnv::default()).unwrap();
+        assert_eq!(state.get_account(receiver).unwrap().balance, U256::from(10));
+        assert_eq!(
+            state
+                .get_account(sender.public_account.address)
+                .unwrap()
+                .balance,
+            U256::from(1000 - 10)
+        );
+    }
+
+    #[test]
+    fn test_nonces_only_go_up() {
+        let sender = Account::new(vec![]);
+        let sender_addr = sender.public_account.address;
+        let mut state = State::new();
+        state.allocate(sender_addr, 1000);
+        let receiver = gen_address();
+        let transfer = |nonce: u64| {
+            Transaction::create_transaction(
+                Some(sender.clone().with_nonce(nonce)),
+                Some(receiver),
+                10,
+                None,
+                0,
+                DEFAULT_CHAIN_ID,
+            )
+        };
+
+        let tx = transfer(0);
+        Transaction::run_standard_tx(&tx, &mut state, None, BlockEnv::default()).unwrap();
+        assert_eq!(state.get_account(sender_addr).unwrap().nonce, 1);
+        //the same signed tx again
+        let used = || TxError::NonceTooLow { nonce: 0, next: 1 };
+        assert_eq!(Transaction::check_transaction(&tx, &state), Err(used()));
+        assert_eq!(
+            Transaction::run_standard_tx(&tx, &mut state, None, BlockEnv::default()),
+            Err(used())
+        );
+        assert_eq!(state.get_account(receiver).unwrap().balance, U256::from(10));
+
+        //gaps are fine, it only has to go up
+        let tx = transfer(5);
+        assert!(Transaction::check_transaction(&tx, &state).is_ok());
+        Transaction::run_standard_tx(&tx, &mut state, None, BlockEnv::default()).unwrap();
+        assert_eq!(state.get_account(sender_addr).unwrap().nonce, 6);
+        assert!(Transaction::check_transaction(&transfer(4), &state).is_err());
+
+        //signed at nonce 12, can't be passed off as 21 - both serialize to the same characters
+        let mut tx = transfer(12);
+        assert!(Transaction::check_transaction(&tx, &state).is_ok());
+        tx.unsigned_tx.nonce = 21;
+        assert_eq!(
+            Transaction::check_transaction(&tx, &state),
+            Err(TxError::InvalidSignature)
+        );
+
+        //recreating the account doesn't reset it
+        let create_tx = Transaction::create_transaction(
+            Some(sender.clone()),
+            None,
+            0,
+            None,
+            0,
+            DEFAULT_CHAIN_ID,
+        );
+        Transaction::run_create_account_tx(&create_tx, &mut state, None).unwrap();
+        assert_eq!(state.get_account(sender_addr).unwrap().nonce, 6);
+    }
+
+    #[test]
+    fn test_multisig_transaction() {
+        let signers: Vec<Account> = (0..3).map(|_| Account::new(vec![])).collect();
+        let mut multisig_account = Account::new(vec![]);
+        multisig_account.public_account.multisig = Some(MultisigConfig {
+            threshold: 2,
+            signers: signers.iter().map(|s| s.public_account.address).collect(),
+        });
+        let multisig_addr = multisig_account.public_account.address;
+        let mut state = State::new();
+
+        let create_tx = Transaction::create_transaction(
+            Some(multisig_account.clone()),
+            None,
+            0,
+            None,
+            0,
+            DEFAULT_CHAIN_ID,
+        );
+        assert!(Transaction::validate_create_account_transaction(
+            &create_tx,
+            Fork::Frontier
+        ));
+        Transaction::run_create_account_tx(&create_tx, &mut state, None).unwrap();
+        state.allocate(multisig_addr, 100);
+
+        let receiver = Account::new(vec![]).public_account.address;
+        //the account's own key doesn't count
+        let tx = Transaction::create_transaction(
+            Some(multisig_account),
+            Some(receiver),
+            10,
+            None,
+            0,
+            DEFAULT_CHAIN_ID,
+        );
+        assert_eq!(
+            Transaction::check_transaction(&tx, &state),
+            Err(TxError::Multisig(
+                "not enough multisig signatures: 0 of 2".to_string()
+            ))
+        );
+
+        let mut tx = Transaction::create_multisig_transaction(
+            multisig_addr,
+            receiver,
+            10,
+            0,
+            0,
+            DEFAULT_CHAIN_ID,
+        );
+        tx.cosign(&signers[0]);
+        tx.cosign(&signers[0]);
+        assert_eq!(tx.cosignatures.len(), 1);
+        assert!(!Transaction::validate_transaction(&tx, &state));
+        tx.cosign(&signers[2]);
+        assert!(Transaction::validate_transaction(&tx, &state));
+
+        Transaction::run_standard_tx(&tx, &mut state, None, BlockEnv::default()).unwrap();
+        assert_eq!(state.get_account(receiver).unwrap().balance, U256::from(10));
+        assert_eq!(
+            state.get_account(multisig_addr).unwrap().balance,
+            U256::from(90)
+        );
+        //the config survives the balance update
+        assert!(state.get_account(multisig_addr).unwrap().multisig.is_some());
+    }
+
+    #[test]
+    fn test_invalid_multisig_config() {
+        let mut account = Account::new(vec![OPCODE::STOP]);
+        account.public_account.multisig = Some(MultisigConfig {
+            threshold: 1,
+            signers: vec![Account::new(vec![]).public_account.address],
+        });
+        //contracts can't be multisigs
+        let tx = Transaction::create_transaction(
+            Some(account.clone()),
+            None,
+            0,
+            None,
+            0,
+            DEFAULT_CHAIN_ID,
+        );
+        assert!(!Transaction::validate_create_account_transaction(
+            &tx,
+            Fork::Frontier
+        ));
+
+        account.public_account.code = vec![];
+        account.public_account.multisig.as_mut().unwrap().threshold = 2;
+        let tx = Transaction::create_transaction(Some(account), None, 0, None, 0, DEFAULT_CHAIN_ID);
+        assert!(!Transaction::validate_create_account_transaction(
+            &tx,
+            Fork::Frontier
+        ));
+    }
+
+    #[test]
+    fn test_external_signature() {
+        //stands in for a hardware wallet - all it gets is the hash
+        let (sk, pk) = gen_keypair();
+        let address = Address::from_public_key(&pk);
+        let sign_externally = |unsigned_tx: &UnsignedTx| {
+            let hash = hex::decode(Transaction::signing_hash(unsigned_tx)).unwrap();
+            let msg = Message::from_slice(&hash).unwrap();
+            hex::encode(Secp256k1::new().sign(&msg, &sk).serialize_compact())
+        };
+        let mut state = State::new();
+        state.allocate(address, 100);
+
+        let receiver = gen_address();
+        let unsigned_tx = Transaction::create_unsigned_transaction(
+            address,
+            Some(receiver),
+            10,
+            vec![],
+            0,
+            0,
+            DEFAULT_CHAIN_ID,
+        );
+        let signature = sign_externally(&unsigned_tx);
+        let tx = Transaction::from_external_signature(unsigned_tx.clone(), &signature).unwrap();
+        assert!(Transaction::validate_transaction(&tx, &state));
+
+        //tampering with the tx after signing breaks it
+        let mut tampered = unsigned_tx;
+        tampered.value = 99.into();
+        assert!(Transaction::from_external_signature(tampered, &signature).is_err());
+
+        let create_tx = Transaction::create_unsigned_transaction(
+            address,
+            None,
+            0,
+            vec![],
+            0,
+            0,
+            DEFAULT_CHAIN_ID,
+        );
+        let signature = sign_externally(&create_tx);
+        let tx = Transaction::from_external_signature(create_tx, &signature).unwrap();
+        assert!(Transaction::validate_create_account_transaction(
+            &tx,
+            Fork::Frontier
+        ));
+        assert!(Transaction::from_external_signature(tx.unsigned_tx, "nothex").is_err());
+    }
+
+    #[test]
+    fn test_contract_code_needs_its_fork() {
+        let code = vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::PUSH,
+            OPCODE::VAL(3),
+            OPCODE::SHL,
+            OPCODE::STOP,
+        ];
+        let tx = Transaction::create_contract(
+            Account::new(code),
+            0,
+            1000,
+            &Account::new(vec![]),
+            DEFAULT_CHAIN_ID,
+        );
+        assert!(!Transaction::validate_create_account_transaction(
+            &tx,
+            Fork::Frontier
+        ));
+        assert!(Transaction::validate_create_account_transaction(
+            &tx,
+            Fork::Constantinople
+        ));
+    }
+
+    #[test]
+    fn test_check_transaction_reports_reason() {
+        let sender = Account::new(vec![]);
+        let mut state = State::new();
+        state.put_account(sender.public_account.address, sender.public_account.clone());
+
+        let receiver = Account::new(vec![]).public_account.address;
+        let tx = Transaction::create_transaction(
+            Some(sender),
+            Some(receiver),
+            1000,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+
+        assert_eq!(
+            Transaction::check_transaction(&tx, &state),
+            Err(TxError::ExceededBalance)
+        );
+        assert!(!Transaction::validate_transaction(&tx, &state));
+
+        //bad input is an error, not a panic
+        let stranger = Account::new(vec![]);
+        let stranger_addr = stranger.public_account.address;
+        let mut tx = Transaction::create_transaction(
+            Some(stranger),
+            Some(receiver),
+            1,
+            None,
+            0,
+            DEFAULT_CHAIN_ID,
+        );
+        assert_eq!(
+            Transaction::check_transaction(&tx, &state),
+            Err(TxError::Store(StoreError::AccountNotFound(
+                stranger_addr.to_string()
+            )))
+        );
+        tx.unsigned_tx.to = None;
+        assert_eq!(
+            Transaction::run_standard_tx(&tx, &mut state, None, BlockEnv::default()),
+            Err(TxError::MissingField("recipient"))
+        );
+    }
+
+    #[test]
+    fn test_pointless_transfers() {
+        let sender = Account::new(vec![]);
+        let sender_addr = sender.public_account.address;
+        let contract = Account::new(vec![OPCODE::STOP]).public_account;
+        let contract_addr = contract.address;
+        let mut state = State::new();
+        state.allocate(sender_addr, 1000);
+        state.put_account(contract_addr, contract);
+        let check = |to, value: u64| {
+            let tx = Transaction::create_transaction(
+                Some(sender.clone()),
+                Some(to),
+                value,
+                None,
+                10,
+                DEFAULT_CHAIN_ID,
+            );
+            Transaction::check_recipient(&tx, &state)
+        };
+
+        assert_eq!(
+            check(sender_addr, 0),
+            Err(TxError::Pointless("sends nothing to its own sender"))
+        );
+        assert_eq!(
+            check(gen_address(), 0),
+            Err(TxError::Pointless(
+                "sends nothing to an account with no code to run"
+            ))
+        );
+        //calling a contract is what a zero value tx is for
+        assert_eq!(check(contract_addr, 0), Ok(()));
+        //and anything with value is a real transfer, even to yourself
+        assert_eq!(check(sender_addr, 1), Ok(()));
+        assert_eq!(check(gen_address(), 1), Ok(()));
+    }
+
+    #[test]
+    fn test_payload_pays_gas() {
+        let sender = Account::new(vec![]);
+        let from = sender.public_account.address;
+        let to = gen_address();
+        let mut state = State::new();
+        state.allocate(from, 100_000);
+        let with_payload = |gas_limit: u64| {
+            let mut unsigned_tx = Transaction::create_unsigned_transaction(
+                from,
+                Some(to),
+                5,
+                vec![],
+                gas_limit,
+                0,
+                DEFAULT_CHAIN_ID,
+            );
+            unsigned_tx.data.account_data =
+                Some(Account::new(vec![OPCODE::STOP; 100]).public_account);
+            let signature = sender.sign(&serde_json::to_string(&unsigned_tx).unwrap());
+            Transaction {
+                unsigned_tx,
+                signature: Some(signature),
+                cosignatures: vec![],
+                fee_payer_signature: None,
+            }
+        };
+        //an ordinary transfer carries nothing
+        let plain = Transaction::create_transaction(
+            Some(sender.clone()),
+            Some(to),
+            5,
+            None,
+            0,
+            DEFAULT_CHAIN_ID,
+        );
+        assert_eq!(plain.payload_bytes(), 0);
+
+        let tx = with_payload(0);
+        let needed = payload_gas(tx.payload_bytes());
+        assert!(needed >= 100 * GAS_PER_PAYLOAD_BYTE);
+        assert_eq!(
+            Transaction::check_transaction(&tx, &state),
+            Err(TxError::PayloadGas {
+                provided: 0.into(),
+                needed
+            })
+        );
+        let tx = with_payload(needed);
+        assert_eq!(
+            Transaction::simulate(&tx, &state, BlockEnv::default()),
+            Ok(needed)
+        );
+        assert!(Transaction::check_size(&"x".repeat(MAX_TX_BYTES)).is_ok());
+        assert_eq!(
+            Transaction::check_size(&"x".repeat(MAX_TX_BYTES + 1)),
+            Err(TxError::TooLarge(MAX_TX_BYTES + 1, MAX_TX_BYTES))
+        );
+    }
+
+    #[test]
+    fn test_deploying_code_pays_gas() {
+        let code = vec![OPCODE::PUSH, OPCODE::VAL(1), OPCODE::STOP];
+        let creator = Account::new(vec![]);
+        let creator_addr = creator.public_account.address;
+        let beneficiary = gen_address();
+        let mut state = State::new();
+        state.allocate(creator_addr, 1000);
+        let fork = Fork::Constantinople;
+
+        let contract = Account::new(code.clone());
+        let contract_addr = contract.public_account.address;
+        let tx = Transaction::create_contract(contract, 0, 1000, &creator, DEFAULT_CHAIN_ID);
+        let needed = payload_gas(tx.payload_bytes());
+        assert_eq!(needed, 3 * 4 * GAS_PER_PAYLOAD_BYTE);
+        assert!(tx.involves(&creator_addr));
+        assert!(Transaction::validate_create_account_transaction(&tx, fork));
+        assert_eq!(Transaction::check_creation_payer(&tx, &state), Ok(()));
+
+        let payees = Payees {
+            beneficiary,
+            treasury: None,
+        };
+        let outcome =
+            Transaction::run_transaction(&tx, &mut state, Some(payees), BlockEnv::default())
+                .unwrap();
+        assert_eq!(outcome, TxOutcome::success(needed));
+        assert_eq!(
+            state.get_account(creator_addr).unwrap().balance,
+            U256::from(1000 - needed * GAS_PRICE)
+        );
+        assert_eq!(
+            state.get_account(beneficiary).unwrap().balance,
+            U256::from(needed * GAS_PRICE)
+        );
+        //the contract itself still starts out with nothing
+        let deployed = state.get_account(contract_addr).unwrap();
+        assert_eq!(deployed.balance, U256::zero());
+        assert_eq!(deployed.code.len(), 3);
+
+        //nobody to pay for the code
+        let unpaid = Transaction::create_transaction(
+            Some(Account::new(code.clone())),
+            None,
+            0,
+            None,
+            1000,
+            DEFAULT_CHAIN_ID,
+        );
+        assert!(!Transaction::validate_create_account_transaction(
+            &unpaid, fork
+        ));
+        //a gas limit that doesn't cover it
+        let short = Transaction::create_contract(
+            Account::new(code.clone()),
+            0,
+            needed - 1,
+            &creator,
+            DEFAULT_CHAIN_ID,
+        );
+        assert!(!Transaction::validate_create_account_transaction(
+            &short, fork
+        ));
+        //nothing to pay for
+        let codeless =
+            Transaction::create_contract(Account::new(vec![]), 0, 1000, &creator, DEFAULT_CHAIN_ID);
+        assert!(!Transaction::validate_create_account_transaction(
+            &codeless, fork
+        ));
+        //a creator that can't buy the gas
+        let broke = Transaction::create_contract(
+            Account::new(code),
+            0,
+            1000,
+            &Account::new(vec![]),
+            DEFAULT_CHAIN_ID,
+        );
+        assert!(Transaction::validate_create_account_transaction(
+            &broke, fork
+        ));
+        assert!(Transaction::check_creation_payer(&broke, &state).is_err());
+    }
+
+    #[test]
+    fn test_contract_code_only_runs_with_the_tx() {
+        let sender = Account::new(vec![]);
+        let contract = Account::new(vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(7),
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::STORE,
+            OPCODE::STOP,
+        ])
+        .public_account;
+        let contract_addr = contract.address;
+        let mut state = State::new();
+        state.allocate(sender.public_account.address, 1000);
+        state.put_account(contract_addr, contract);
+        let stored = |state: &State| {
+            state
+                .storage_trie_map
+                .get(&contract_addr)
+                .unwrap()
+                .get("1".into())
+                .cloned()
+        };
+
+        //validation alone never runs the code, so it can't tell the gas limit is too low
+        let tx = Transaction::create_transaction(
+            Some(sender.clone()),
+            Some(contract_addr),
+            0,
+            None,
+            1,
+            DEFAULT_CHAIN_ID,
+        );
+        assert!(Transaction::check_transaction(&tx, &state).is_ok());
+        assert_eq!(
+            Transaction::simulate(&tx, &state, BlockEnv::default()),
+            Err(TxError::Exec(ExecError::OutOfGas(1)))
+        );
+        assert_eq!(stored(&state), None);
+
+        let tx = Transaction::create_transaction(
+            Some(sender),
+            Some(contract_addr),
+            0,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        let simulated_gas = Transaction::simulate(&tx, &state, BlockEnv::default()).unwrap();
+        assert!(simulated_gas > 0);
+        assert_eq!(stored(&state), None);
+
+        assert_eq!(
+            Transaction::run_standard_tx(&tx, &mut state, None, BlockEnv::default()),
+            Ok(TxOutcome::success(simulated_gas))
+        );
+        assert_eq!(stored(&state), Some("7".to_string()));
+    }
+
+    #[test]
+    fn test_failed_contract_still_pays_for_gas() {
+        let sender = Account::new(vec![]);
+        let sender_addr = sender.public_account.address;
+        let contract = Account::new(vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(7),
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::STORE,
+            OPCODE::PUSH,
+            OPCODE::VAL(3),
+            OPCODE::REVERT,
+        ])
+        .public_account;
+        let contract_addr = contract.address;
+        let mut state = State::new();
+        state.allocate(sender_addr, 1000);
+        state.put_account(contract_addr, contract);
+
+        let tx = Transaction::create_transaction(
+            Some(sender.clone()),
+            Some(contract_addr),
+            5,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        assert_eq!(
+            Transaction::simulate(&tx, &state, BlockEnv::default()),
+            Err(TxError::Exec(ExecError::Reverted {
+                reason: 3,
+                gas_used: 5
+            }))
+        );
+        //mined all the same: a revert pays for what ran, the value and the STORE don't go through
+        let outcome =
+            Transaction::run_standard_tx(&tx, &mut state, None, BlockEnv::default()).unwrap();
+        assert_eq!(outcome.gas_used, 5);
+        assert_eq!(outcome.status(), ReceiptStatus::Failure);
+        assert_eq!(
+            state.get_account(sender_addr).unwrap().balance,
+            U256::from(1000 - 5 * GAS_PRICE)
+        );
+        assert_eq!(state.get_account(sender_addr).unwrap().nonce, 1);
+        assert!(state.get_account(contract_addr).unwrap().balance.is_zero());
+        assert_eq!(state.storage_trie_mut(contract_addr).get("1".into()), None);
+
+        //running out pays for the whole limit
+        let tx = Transaction::create_transaction(
+            Some(sender.with_nonce(1)),
+            Some(contract_addr),
+            5,
+            None,
+            3,
+            DEFAULT_CHAIN_ID,
+        );
+        let outcome =
+            Transaction::run_standard_tx(&tx, &mut state, None, BlockEnv::default()).unwrap();
+        assert_eq!(outcome.error, Some(ExecError::OutOfGas(3)));
+        assert_eq!(outcome.gas_used, 3);
+        assert_eq!(
+            state.get_account(sender_addr).unwrap().balance,
+            U256::from(1000 - 8 * GAS_PRICE)
+        );
+        assert_eq!(state.get_account(sender_addr).unwrap().nonce, 2);
+    }
+
+    #[test]
+    fn test_gas_fee_goes_to_beneficiary() {
+        let sender = Account::new(vec![]);
+        let sender_addr = sender.public_account.address;
+        //PUSH is free, the ADD is what costs gas
+        let contract = Account::new(vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::ADD,
+            OPCODE::STOP,
+        ])
+        .public_account;
+        let contract_addr = contract.address;
+        let beneficiary = gen_address();
+        let mut state = State::new();
+        state.allocate(sender_addr, 1000);
+        state.put_account(contract_addr, contract);
+
+        let tx = Transaction::create_transaction(
+            Some(sender),
+            Some(contract_addr),
+            5,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        let payees = Payees {
+            beneficiary,
+            treasury: None,
+        };
+        let gas_used =
+            Transaction::run_transaction(&tx, &mut state, Some(payees), BlockEnv::default())
+                .unwrap()
+                .gas_used;
+        assert!(gas_used > 0);
+        //the unused part of the 100 came back, the used part went to the beneficiary
+        assert_eq!(
+            state.get_account(sender_addr).unwrap().balance,
+            U256::from(1000 - 5 - gas_used)
+        );
+        assert_eq!(
+            state.get_account(contract_addr).unwrap().balance,
+            U256::from(5)
+        );
+        assert_eq!(
+            state.get_account(beneficiary).unwrap().balance,
+            U256::from(gas_used)
+        );
+    }
+
+    #[test]
+    fn test_chain_id_is_signed() {
+        let sender = Account::new(vec![]);
+        let mut state = State::new();
+        state.allocate(sender.public_account.address, 1000);
+
+        let receiver = Account::new(vec![]).public_account.address;
+        let mut tx = Transaction::create_transaction(
+            Some(sender),
+            Some(receiver),
+            10,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        assert_eq!(tx.unsigned_tx.chain_id, DEFAULT_CHAIN_ID);
+        assert!(Transaction::check_transaction(&tx, &state).is_ok());
+
+        //can't just be relabelled for another network
+        tx.unsigned_tx.chain_id += 1;
+        assert_eq!(
+            Transaction::check_transaction(&tx, &state),
+            Err(TxError::InvalidSignature)
+        );
+    }
+    #[test]
+    fn test_fee_payer_pays_the_gas() {
+        let sender = Account::new(vec![]);
+        let sender_addr = sender.public_account.address;
+        let payer = Account::new(vec![]);
+        let payer_addr = payer.public_account.address;
+        let contract = Account::new(vec![
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::PUSH,
+            OPCODE::VAL(1),
+            OPCODE::ADD,
+            OPCODE::STOP,
+        ])
+        .public_account;
+        let contract_addr = contract.address;
+        let mut state = State::new();
+        //enough for the value, not for the gas on top
+        state.allocate(sender_addr, 5);
+        state.allocate(payer_addr, 1000);
+        state.put_account(contract_addr, contract);
+
+        let unsponsored = Transaction::create_transaction(
+            Some(sender.clone()),
+            Some(contract_addr),
+            5,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        assert_eq!(
+            Transaction::check_transaction(&unsponsored, &state),
+            Err(TxError::ExceededBalance)
+        );
+        let tx = Transaction::create_sponsored_transaction(
+            sender.clone(),
+            contract_addr,
+            5,
+            100,
+            &payer,
+            DEFAULT_CHAIN_ID,
+        );
+        assert!(tx.involves(&payer_addr));
+        assert!(Transaction::check_transaction(&tx, &state).is_ok());
+        //survives the wire
+        let decoded: Transaction = codec::decode_hex(&codec::encode_hex(&tx)).unwrap();
+        assert_eq!(decoded.hash(), tx.hash());
+
+        let gas_used = Transaction::run_transaction(&tx, &mut state, None, BlockEnv::default())
+            .unwrap()
+            .gas_used;
+        assert!(gas_used > 0);
+        assert_eq!(
+            state.get_account(sender_addr).unwrap().balance,
+            U256::zero()
+        );
+        assert_eq!(
+            state.get_account(payer_addr).unwrap().balance,
+            U256::from(1000 - gas_used * GAS_PRICE)
+        );
+        assert_eq!(
+            state.get_account(contract_addr).unwrap().balance,
+            U256::from(5)
+        );
+    }
+
+    #[test]
+    fn test_fee_payer_has_to_sign() {
+        let sender = Account::new(vec![]);
+        let payer = Account::new(vec![]);
+        let receiver = gen_address();
+        let mut state = State::new();
+        state.allocate(sender.public_account.address, 10);
+        state.allocate(payer.public_account.address, 1000);
+        let tx = Transaction::create_sponsored_transaction(
+            sender.clone(),
+            receiver,
+            10,
+            100,
+            &payer,
+            DEFAULT_CHAIN_ID,
+        );
+
+        let mut unsigned = tx.clone();
+        unsigned.fee_payer_signature = None;
+        assert_eq!(
+            Transaction::check_transaction(&unsigned, &state),
+            Err(TxError::FeePayer("signature missing"))
+        );
+        //the sender's signature over the same tx doesn't count
+        let mut forged = tx.clone();
+        forged.fee_payer_signature = forged.signature;
+        assert_eq!(
+            Transaction::check_transaction(&forged, &state),
+            Err(TxError::FeePayer("signature invalid"))
+        );
+        assert!(tx.clone().sponsor(&sender).is_err());
+        //nor can the fee payer be swapped out after the sender signed
+        let other = Account::new(vec![]);
+        state.allocate(other.public_account.address, 1000);
+        let mut swapped = tx.clone();
+        swapped.unsigned_tx.fee_payer = Some(other.public_account.address);
+        swapped.sponsor(&other).unwrap();
+        assert_eq!(
+            Transaction::check_transaction(&swapped, &state),
+            Err(TxError::InvalidSignature)
+        );
+
+        let own = Transaction::create_sponsored_transaction(
+            sender.clone(),
+            receiver,
+            10,
+            100,
+            &sender,
+            DEFAULT_CHAIN_ID,
+        );
+        assert_eq!(
+            Transaction::check_transaction(&own, &state),
+            Err(TxError::FeePayer("is the sender"))
+        );
+        let broke = Account::new(vec![]);
+        state.allocate(broke.public_account.address, 1);
+        let tx = Transaction::create_sponsored_transaction(
+            sender,
+            receiver,
+            10,
+            100,
+            &broke,
+            DEFAULT_CHAIN_ID,
+        );
+        assert_eq!(
+            Transaction::check_transaction(&tx, &state),
+            Err(TxError::FeePayer("can't cover the gas"))
+        );
+    }
 }
@@ -1,13 +1,35 @@
-use crate::transaction::tx::Transaction;
+use crate::account::address::Address;
+use crate::error::TxError;
+use crate::transaction::tx::{Transaction, TxType};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+/// where a freshly submitted tx stands with respect to the tx pool
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub enum TxStatus {
+    //passed validation against the current head state and got broadcast, but hasn't reached our own queue yet
+    Validated,
+    //sitting in our own queue, will be included in the next mined block
+    Pending,
+    //can't be validated yet because the sender's account isn't on chain (eg its creation tx hasn't been mined). Broadcast anyway
+    Queued,
+    //failed validation, never broadcast
+    Rejected,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TransactionQueue {
     pub tx_map: HashMap<Uuid, Transaction>,
 }
 
+impl Default for TransactionQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TransactionQueue {
     pub fn new() -> Self {
         Self {
@@ -18,8 +40,43 @@ impl TransactionQueue {
     pub fn add(&mut self, tx: Transaction) {
         self.tx_map.insert(tx.unsigned_tx.id, tx);
     }
+    /// a transfer can't go in next to a different one from the same sender with the same nonce - only one of them
+    /// could ever be mined. The same tx again is fine, add() just replaces it
+    pub fn check_nonce(&self, tx: &Transaction) -> Result<(), TxError> {
+        let from = match (&tx.unsigned_tx.data.tx_type, tx.unsigned_tx.from) {
+            (TxType::Transact, Some(from)) => from,
+            _ => return Ok(()),
+        };
+        let taken = self.tx_map.values().any(|queued| {
+            queued.unsigned_tx.id != tx.unsigned_tx.id
+                && queued.unsigned_tx.from == Some(from)
+                && queued.unsigned_tx.nonce == tx.unsigned_tx.nonce
+        });
+        if taken {
+            return Err(TxError::NonceQueued(tx.unsigned_tx.nonce));
+        }
+        Ok(())
+    }
+    /// one past the highest nonce the sender has queued, None if it has nothing queued
+    pub fn next_nonce(&self, from: &Address) -> Option<u64> {
+        self.tx_map
+            .values()
+            .filter(|tx| tx.unsigned_tx.from.as_ref() == Some(from))
+            .map(|tx| tx.unsigned_tx.nonce.saturating_add(1))
+            .max()
+    }
+    pub fn contains(&self, tx: &Transaction) -> bool {
+        self.tx_map.contains_key(&tx.unsigned_tx.id)
+    }
+    /// the queue is keyed by tx id, so this is a scan
+    pub fn find_by_hash(&self, tx_hash: &str) -> Option<&Transaction> {
+        self.tx_map.values().find(|tx| tx.hash() == tx_hash)
+    }
+    /// lowest nonce first, so a sender's tx go into a block in the order they have to run in
     pub fn get_tx_series(&self) -> Vec<Transaction> {
-        self.tx_map.clone().into_iter().map(|(_k, v)| v).collect()
+        let mut tx_series: Vec<Transaction> = self.tx_map.clone().into_values().collect();
+        tx_series.sort_by_key(|tx| tx.unsigned_tx.nonce);
+        tx_series
     }
     pub fn clear_block_tx(&mut self, tx_series: &Vec<Transaction>) {
         for tx in tx_series {
@@ -27,3 +84,56 @@ impl TransactionQueue {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{gen_address, Account};
+    use crate::config::DEFAULT_CHAIN_ID;
+
+    #[test]
+    fn test_one_tx_per_sender_and_nonce() {
+        let sender = Account::new(vec![]);
+        let from = sender.public_account.address;
+        let transfer = |nonce: u64| {
+            Transaction::create_transaction(
+                Some(sender.clone().with_nonce(nonce)),
+                Some(gen_address()),
+                10,
+                None,
+                10,
+                DEFAULT_CHAIN_ID,
+            )
+        };
+        let mut tx_queue = TransactionQueue::new();
+        assert_eq!(tx_queue.next_nonce(&from), None);
+        let (second, first) = (transfer(1), transfer(0));
+        tx_queue.add(second.clone());
+        tx_queue.add(first.clone());
+        assert_eq!(tx_queue.next_nonce(&from), Some(2));
+
+        assert_eq!(tx_queue.check_nonce(&first), Ok(()));
+        assert_eq!(
+            tx_queue.check_nonce(&transfer(1)),
+            Err(TxError::NonceQueued(1))
+        );
+        assert_eq!(tx_queue.check_nonce(&transfer(2)), Ok(()));
+        //account creation tx have no sender to clash with
+        let create = Transaction::create_transaction(
+            Some(sender.clone()),
+            None,
+            0,
+            None,
+            100,
+            DEFAULT_CHAIN_ID,
+        );
+        assert_eq!(tx_queue.check_nonce(&create), Ok(()));
+
+        let hashes: Vec<String> = tx_queue
+            .get_tx_series()
+            .iter()
+            .map(|tx| tx.hash())
+            .collect();
+        assert_eq!(hashes, vec![first.hash(), second.hash()]);
+    }
+}
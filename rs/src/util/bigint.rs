@@ -0,0 +1,115 @@
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+//rust only supports ints up to 128 bit and we need 256, so have to use an external crate - https://crates.io/crates/uint.
+// Its own module, so the lints the macro's code trips over don't have to be allowed for the whole file
+mod uint_type {
+    #![allow(clippy::manual_div_ceil, clippy::assign_op_pattern)]
+    uint::construct_uint! {
+        /// the one big integer type in the crate - balances, tx values, gas and the pow target are all U256
+        pub struct U256(4);
+    }
+}
+pub use uint_type::U256;
+
+/// "0x" prefixed lowercase hex without leading zeros, same as ethereum's json-rpc quantities. 0 is "0x0"
+impl Serialize for U256 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&to_hex(self))
+    }
+}
+
+/// takes the hex strings we write, but also plain json numbers and decimal strings - clients that send
+/// {"value": 100} keep working
+impl<'de> Deserialize<'de> for U256 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(U256Visitor)
+    }
+}
+
+struct U256Visitor;
+
+impl<'de> Visitor<'de> for U256Visitor {
+    type Value = U256;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a non-negative integer, as a number, a decimal string or a 0x hex string")
+    }
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<U256, E> {
+        Ok(U256::from(value))
+    }
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<U256, E> {
+        if value < 0 {
+            return Err(E::custom(format!("negative value: {}", value)));
+        }
+        Ok(U256::from(value as u64))
+    }
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<U256, E> {
+        parse_u256(value).map_err(E::custom)
+    }
+}
+
+pub fn to_hex(value: &U256) -> String {
+    format!("0x{:x}", value)
+}
+
+/// "0x" prefixed hex or plain decimal
+pub fn parse_u256(value: &str) -> Result<U256, String> {
+    let invalid = || format!("invalid number: {}", value);
+    match value.strip_prefix("0x") {
+        Some("") => Err(invalid()),
+        Some(hex) => U256::from_str_radix(hex, 16).map_err(|_| invalid()),
+        None => U256::from_dec_str(value).map_err(|_| invalid()),
+    }
+}
+
+/// for amounts that come from users - overflowing has to be an error, not a panic
+pub fn checked_add(a: U256, b: U256) -> Result<U256, String> {
+    a.checked_add(b)
+        .ok_or_else(|| format!("{} + {} overflows", a, b))
+}
+
+pub fn checked_sub(a: U256, b: U256) -> Result<U256, String> {
+    a.checked_sub(b)
+        .ok_or_else(|| format!("{} - {} underflows", a, b))
+}
+
+/// for amounts that were already validated, eg applying a tx that passed check_transaction().
+/// Clamping beats taking down the node if that ever turns out to be wrong
+pub fn saturating_sub(a: U256, b: U256) -> U256 {
+    a.saturating_sub(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_serde() {
+        assert_eq!(serde_json::to_string(&U256::from(0)).unwrap(), "\"0x0\"");
+        assert_eq!(serde_json::to_string(&U256::from(255)).unwrap(), "\"0xff\"");
+        assert_eq!(
+            serde_json::to_string(&U256::max_value()).unwrap().len(),
+            2 + 2 + 64
+        );
+        for json in ["\"0xff\"", "\"255\"", "255"].iter() {
+            assert_eq!(serde_json::from_str::<U256>(json).unwrap(), U256::from(255));
+        }
+        let max: U256 =
+            serde_json::from_str(&serde_json::to_string(&U256::max_value()).unwrap()).unwrap();
+        assert_eq!(max, U256::max_value());
+        assert!(serde_json::from_str::<U256>("-1").is_err());
+        assert!(serde_json::from_str::<U256>("\"0x\"").is_err());
+        assert!(serde_json::from_str::<U256>("\"12ab\"").is_err());
+        assert!(serde_json::from_str::<U256>("1.5").is_err());
+    }
+
+    #[test]
+    fn test_checked_math() {
+        assert_eq!(checked_add(U256::from(1), U256::from(2)), Ok(U256::from(3)));
+        assert!(checked_add(U256::max_value(), U256::from(1)).is_err());
+        assert!(checked_sub(U256::from(1), U256::from(2)).is_err());
+        assert_eq!(saturating_sub(U256::from(1), U256::from(2)), U256::zero());
+    }
+}
@@ -0,0 +1,137 @@
+use chrono::Utc;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+/// where block timestamps come from. The node runs on SystemClock, tests use a ManualClock so that mining,
+/// difficulty adjustment and genesis don't depend on how long things took (or on sleeping)
+pub trait Clock: Debug + Send + Sync {
+    /// milliseconds since the unix epoch
+    fn now_millis(&self) -> i64;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> i64 {
+        Utc::now().timestamp_millis()
+    }
+}
+
+/// only moves when told to
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    millis: AtomicI64,
+}
+
+impl ManualClock {
+    pub fn new(millis: i64) -> Self {
+        Self {
+            millis: AtomicI64::new(millis),
+        }
+    }
+    pub fn set(&self, millis: i64) {
+        self.millis.store(millis, Ordering::SeqCst);
+    }
+    pub fn advance(&self, millis: i64) {
+        self.millis.fetch_add(millis, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_millis(&self) -> i64 {
+        self.millis.load(Ordering::SeqCst)
+    }
+}
+
+/// the default for anything that holds on to a clock, eg Blockchain
+pub fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// splits the time since genesis into fixed length slots, grouped into epochs - the schedule a proof of stake engine
+/// would propose and attest on (blocks are still proof of work, nothing waits for a slot yet).
+/// Reads the time from a Clock, so tests can step through slots with a ManualClock
+#[derive(Debug, Clone)]
+pub struct ConsensusClock {
+    /// ms since the unix epoch, slot 0 starts here
+    pub genesis_time: i64,
+    pub slot_duration_ms: u64,
+    pub slots_per_epoch: u64,
+    clock: Arc<dyn Clock>,
+}
+
+impl ConsensusClock {
+    /// slot_duration_ms and slots_per_epoch have to be above 0 (NodeConfig::validate() makes sure of it)
+    pub fn new(
+        genesis_time: i64,
+        slot_duration_ms: u64,
+        slots_per_epoch: u64,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            genesis_time,
+            slot_duration_ms,
+            slots_per_epoch,
+            clock,
+        }
+    }
+    /// the slot `millis` falls in. Anything before genesis counts as slot 0
+    pub fn slot_at(&self, millis: i64) -> u64 {
+        (millis - self.genesis_time).max(0) as u64 / self.slot_duration_ms
+    }
+    pub fn current_slot(&self) -> u64 {
+        self.slot_at(self.clock.now_millis())
+    }
+    pub fn epoch_of(&self, slot: u64) -> u64 {
+        slot / self.slots_per_epoch
+    }
+    pub fn current_epoch(&self) -> u64 {
+        self.epoch_of(self.current_slot())
+    }
+    /// ms since the unix epoch
+    pub fn slot_start(&self, slot: u64) -> i64 {
+        self.genesis_time + (slot * self.slot_duration_ms) as i64
+    }
+    pub fn now(&self) -> i64 {
+        self.clock.now_millis()
+    }
+    /// ms from `millis` until the next slot starts - slot 0, if it's before genesis
+    pub fn next_slot_in(&self, millis: i64) -> u64 {
+        let next = if millis < self.genesis_time {
+            0
+        } else {
+            self.slot_at(millis) + 1
+        };
+        (self.slot_start(next) - millis) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consensus_clock() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let consensus = ConsensusClock::new(1_000, 100, 4, clock.clone());
+        assert_eq!(consensus.current_slot(), 0);
+        assert_eq!(consensus.next_slot_in(consensus.now()), 100);
+
+        clock.advance(450);
+        assert_eq!(consensus.current_slot(), 4);
+        assert_eq!(consensus.current_epoch(), 1);
+        assert_eq!(consensus.next_slot_in(consensus.now()), 50);
+        assert_eq!(consensus.slot_start(4), 1_400);
+
+        //exactly on a boundary is the start of the next slot, not the end of this one
+        clock.set(1_500);
+        assert_eq!(consensus.current_slot(), 5);
+        assert_eq!(consensus.next_slot_in(consensus.now()), 100);
+
+        clock.set(900);
+        assert_eq!(consensus.current_slot(), 0);
+        assert_eq!(consensus.next_slot_in(consensus.now()), 100);
+    }
+}
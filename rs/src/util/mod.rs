@@ -1,49 +1,250 @@
+pub mod bigint;
+pub mod clock;
+
+use crate::account::address::Address;
+use crate::account::address_book::AddressBook;
+use crate::account::hd_wallet::HdWallet;
+use crate::account::keystore::Keystore;
 use crate::account::Account;
-use crate::blockchain::block::U256;
+use crate::api::filters::FilterRegistry;
+use crate::api::miner::AutoMiner;
+use crate::api::webhooks::WebhookRegistry;
+use crate::blockchain::block::Block;
 use crate::blockchain::blockchain::Blockchain;
-use crate::interpreter::OPCODE;
+use crate::blockchain::snapshot::Snapshot;
+use crate::blockchain::sync::{PeerHealth, SyncStatus, SyncTracker};
+use crate::blockchain::work::WorkPackages;
+use crate::config::chain::ChainRules;
+use crate::config::DEFAULT_CHAIN_ID;
+use crate::error::ChainError;
+use crate::events::{Event, EventBus};
+use crate::network::gossip::Gossip;
+use crate::network::peer_count;
+use crate::network::propagation::PropagationLog;
 use crate::store::state::State;
 use crate::transaction::tx::Transaction;
 use crate::transaction::tx_queue::TransactionQueue;
+use crate::util::bigint::U256;
 use itertools::Itertools;
+use secp256k1::rand::rngs::OsRng;
+use secp256k1::{PublicKey, Secp256k1};
 
 use serde::{Deserialize, Serialize};
 use sha3::{Digest, Keccak256};
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, RwLock};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// shared between the server, the miner and the pubsub consumers as an Arc<GlobalState>.
+/// Each component has its own lock so that eg tx ingestion doesn't have to wait for block validation.
+/// (!) if you ever need more than one lock at a time, take them in field order (blockchain -> tx_queue -> keystore -> address_book -> filters -> webhooks -> work -> sync -> propagation -> miner) to avoid deadlocks
+/// Serializing it only ever writes out public data - secret keys live in the keystore, which is skipped
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GlobalState {
-    pub blockchain: Blockchain,
-    pub tx_queue: TransactionQueue,
-    pub miner_account: Account,
+    //identifies the node in /admin/nodeinfo. Random per process unless there's a <datadir>/nodekey to derive it from
+    pub node_id: PublicKey,
+    //never changes after startup, so no lock needed. Its key is in the keystore like everyone else's
+    pub miner_address: Address,
+    pub blockchain: RwLock<Blockchain>,
+    pub tx_queue: Mutex<TransactionQueue>,
+    #[serde(skip)]
+    pub keystore: RwLock<Keystore>,
+    //names the api takes in place of addresses, see AddressInput. Backed by a file only if there's a datadir
+    #[serde(skip)]
+    pub address_book: RwLock<AddressBook>,
+    //json-rpc polling filters, purely in memory
+    #[serde(skip)]
+    pub filters: Mutex<FilterRegistry>,
+    //where chain head changes get POSTed, see api::webhooks. Also only in memory
+    #[serde(skip)]
+    pub webhooks: Mutex<WebhookRegistry>,
+    //blocks handed out to external miners by GET /miner/work, waiting for a nonce
+    #[serde(skip)]
+    pub work: Mutex<WorkPackages>,
+    //new blocks, tx, reorgs and miner status go out here, see events.rs. Not a lock, publishing never waits
+    #[serde(skip)]
+    pub events: EventBus,
+    //the node's lifecycle, driven by the bootnode sync and the miner. See sync_status()
+    #[serde(skip)]
+    pub sync: Mutex<SyncTracker>,
+    //the peers blocks and tx get gossiped to, see network::gossip. Only ever takes its own locks
+    #[serde(skip)]
+    pub gossip: Arc<Gossip>,
+    //when each tx was first seen here, for --measure-propagation
+    #[serde(skip)]
+    pub propagation: Mutex<PropagationLog>,
+    //the background miner started by --auto-mine or POST /miner/start, see api::miner
+    #[serde(skip)]
+    pub miner: Mutex<AutoMiner>,
 }
 
+impl GlobalState {
+    /// the miner's signing account, for the tx the node sends on its own behalf
+    pub fn miner_account(&self) -> Account {
+        self.keystore
+            .read()
+            .unwrap()
+            .get(&self.miner_address)
+            .cloned()
+            .expect("the miner's key is always in the keystore")
+    }
+
+    /// a copy of the chain's rules, for sealing a block or checking a seal without holding the blockchain lock
+    pub fn chain_rules(&self) -> ChainRules {
+        self.blockchain.read().unwrap().rules.clone()
+    }
+
+    /// the nonce the address' next transfer goes out with - one past its last mined or queued one
+    pub fn next_nonce(&self, address: &Address) -> u64 {
+        let on_chain = self
+            .blockchain
+            .read()
+            .unwrap()
+            .state
+            .find_account(*address)
+            .map_or(0, |account| account.nonce);
+        let queued = self.tx_queue.lock().unwrap().next_nonce(address);
+        queued.map_or(on_chain, |queued| queued.max(on_chain))
+    }
+
+    /// an address the api was given, see AddressInput. Names are looked up for the chain we're on
+    pub fn resolve_address(&self, input: &str) -> Result<Address, String> {
+        let chain_id = self.blockchain.read().unwrap().chain_id();
+        self.address_book.read().unwrap().resolve(chain_id, input)
+    }
+
+    /// Blockchain::add_block(), without holding the write lock while the block runs. It's checked and run under a
+    /// read lock, so api reads carry on against the last committed state, and only the swap waits for readers
+    pub fn import_block(&self, block: Block) -> Result<(), ChainError> {
+        let pending = self.blockchain.read().unwrap().prepare_block(block)?;
+        self.blockchain.write().unwrap().commit_block(pending)
+    }
+
+    /// the lifecycle plus how far along the sync is, measured against our head
+    pub fn sync_status(&self) -> SyncStatus {
+        let (head, now) = {
+            let blockchain = self.blockchain.read().unwrap();
+            (blockchain.chain.len() - 1, blockchain.clock.now_millis())
+        };
+        self.sync.lock().unwrap().status(head, now)
+    }
+
+    /// whether peers' blocks are still coming in. Logs the moment that changes, whoever happens to be asking
+    pub fn peer_health(&self) -> PeerHealth {
+        let now = self.blockchain.read().unwrap().clock.now_millis();
+        let peers = peer_count(self);
+        let (health, changed) = self.sync.lock().unwrap().peer_health(peers, now);
+        match (changed, health.partitioned) {
+            (true, true) => tracing::warn!(
+                peers = ?health.peers,
+                quiet_ms = ?health.quiet_ms,
+                "no blocks from peers in a while, the node may be cut off from the network"
+            ),
+            (true, false) => {
+                tracing::info!(peers = ?health.peers, "blocks from peers coming in again")
+            }
+            _ => {}
+        }
+        health
+    }
+
+    /// Blockchain::replace_chain(), plus a Reorg event if any of our blocks got dropped
+    pub fn replace_chain(&self, chain: Vec<Block>) -> Result<(), ChainError> {
+        let reorg = {
+            let mut blockchain = self.blockchain.write().unwrap();
+            let old_chain = blockchain.chain.clone();
+            blockchain.replace_chain(chain)?;
+            Event::reorg(&old_chain, &blockchain.chain)
+        };
+        if let Some(reorg) = reorg {
+            self.events.publish(reorg);
+        }
+        Ok(())
+    }
+
+    /// Blockchain::import_snapshot(), plus a Reorg event if any of our blocks got dropped
+    pub fn import_snapshot(&self, chain: Vec<Block>, snapshot: Snapshot) -> Result<(), ChainError> {
+        let reorg = {
+            let mut blockchain = self.blockchain.write().unwrap();
+            let old_chain = blockchain.chain.clone();
+            blockchain.import_snapshot(chain, snapshot)?;
+            Event::reorg(&old_chain, &blockchain.chain)
+        };
+        if let Some(reorg) = reorg {
+            self.events.publish(reorg);
+        }
+        Ok(())
+    }
+}
+
+/// on DEFAULT_CHAIN_ID, with a fresh miner account
 pub fn prep_state() -> GlobalState {
-    let code = vec![
-        OPCODE::PUSH,
-        OPCODE::VAL(10),
-        OPCODE::PUSH,
-        OPCODE::VAL(5),
-        OPCODE::ADD,
-        OPCODE::STOP,
-    ];
-
-    println!("MINER ACCOUNT: ");
-    let miner_account = Account::new(vec![]);
-    println!("SMART CONTRACT ACCOUNT: ");
-    let sc_account = Account::new(code);
-
-    let tx = Transaction::create_transaction(Some(miner_account.clone()), None, 0, None, 100);
-    let tx2 = Transaction::create_transaction(Some(sc_account), None, 0, None, 100);
-
-    let mut global_state = GlobalState {
-        blockchain: Blockchain::new(State::new()),
-        tx_queue: TransactionQueue::new(),
-        miner_account,
-    };
-    global_state.tx_queue.add(tx);
-    global_state.tx_queue.add(tx2);
-
-    global_state
+    prep_state_with_accounts(Account::new(vec![]), vec![], DEFAULT_CHAIN_ID)
+}
+
+/// derives the miner (index 0) and dev_accounts more from the phrase, so the node comes up with the same keys every time
+pub fn prep_state_from_mnemonic(
+    phrase: &str,
+    dev_accounts: u32,
+    chain_id: u64,
+) -> Result<GlobalState, String> {
+    let wallet = HdWallet::from_mnemonic(phrase)?;
+    let miner_account = wallet.derive_account(0)?;
+    let accounts = (1..=dev_accounts)
+        .map(|index| wallet.derive_account(index))
+        .collect::<Result<Vec<Account>, String>>()?;
+    Ok(prep_state_with_accounts(miner_account, accounts, chain_id))
+}
+
+/// the account creation tx for the miner and any extra accounts get queued up for the first block. No contracts -
+/// deploying code costs gas, and at genesis nobody has any to pay with
+pub fn prep_state_with_accounts(
+    miner_account: Account,
+    accounts: Vec<Account>,
+    chain_id: u64,
+) -> GlobalState {
+    tracing::info!(address = %miner_account.public_account.address, "miner account");
+    let tx =
+        Transaction::create_transaction(Some(miner_account.clone()), None, 0, None, 100, chain_id);
+
+    let mut keystore = Keystore::new();
+    keystore.add(miner_account.clone());
+
+    let mut tx_queue = TransactionQueue::new();
+    tx_queue.add(tx);
+
+    for account in accounts {
+        tracing::info!(address = %account.public_account.address, "dev account");
+        tx_queue.add(Transaction::create_transaction(
+            Some(account.clone()),
+            None,
+            0,
+            None,
+            100,
+            chain_id,
+        ));
+        keystore.add(account);
+    }
+
+    GlobalState {
+        //not gen_keypair() - in deterministic mode that would shift every account key generated after this
+        node_id: Secp256k1::new()
+            .generate_keypair(&mut OsRng::new().unwrap())
+            .1,
+        miner_address: miner_account.public_account.address,
+        blockchain: RwLock::new(Blockchain::new(State::new(), chain_id)),
+        tx_queue: Mutex::new(tx_queue),
+        keystore: RwLock::new(keystore),
+        address_book: RwLock::new(AddressBook::default()),
+        filters: Mutex::new(FilterRegistry::new()),
+        webhooks: Mutex::new(WebhookRegistry::new()),
+        work: Mutex::new(WorkPackages::new()),
+        events: EventBus::default(),
+        sync: Mutex::new(SyncTracker::default()),
+        gossip: Arc::new(Gossip::new()),
+        propagation: Mutex::new(PropagationLog::default()),
+        miner: Mutex::new(AutoMiner::default()),
+    }
 }
 
 pub fn sort_characters<T>(data: &T) -> String
@@ -55,23 +256,69 @@ where
     s.chars().sorted().rev().collect::<String>()
 }
 
+thread_local! {
+    //finalize_reset() leaves it ready for the next hash, so each thread only ever builds one
+    static HASHER: RefCell<Keccak256> = RefCell::new(Keccak256::new());
+}
+
+/// io::Write sink that only counts how often each byte comes through. Sorting the characters of an ascii string
+/// loses everything but those counts, so the serialized value never has to be kept around
+struct ByteCounts([u64; 256]);
+
+impl Write for ByteCounts {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf {
+            self.0[*byte as usize] += 1;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 /// Note we're specifically using keccak256 not sha3
 /// read about the difference here - https://www.oreilly.com/library/view/mastering-ethereum/9781491971932/ch04.html (under cryptographic hash functions header)
+///
+/// The input is the serialized value with its characters sorted in reverse (see sort_characters()). For ascii json
+/// that's just every byte repeated as many times as it occurs, highest first, so it gets fed to the hasher straight
+/// from the counts without allocating either string. Anything with multibyte characters takes the slow path, which
+/// hashes the same thing
 pub fn keccak_hash<T>(data: &T) -> String
 where
     T: ?Sized + Serialize,
 {
-    let s = sort_characters(data);
-    // println!("{:?}", s);
-    let mut hasher = Keccak256::new();
-    hasher.update(s);
-    let result = hasher.finalize();
-    let hex_r = hex::encode(result);
-    // println!("{}", hex_r);
-    hex_r
+    let mut counts = ByteCounts([0; 256]);
+    serde_json::to_writer(&mut counts, data).unwrap();
+    if counts.0[128..].iter().any(|count| *count > 0) {
+        return keccak_bytes(sort_characters(data).as_bytes());
+    }
+    HASHER.with(|hasher| {
+        let mut hasher = hasher.borrow_mut();
+        for byte in (0..128u8).rev() {
+            let run = [byte; 64];
+            let mut left = counts.0[byte as usize] as usize;
+            while left > 0 {
+                let n = left.min(run.len());
+                hasher.update(&run[..n]);
+                left -= n;
+            }
+        }
+        hex::encode(hasher.finalize_reset())
+    })
 }
 
-pub fn base16_to_base10(base16: &String) -> U256 {
+/// keccak256 of the bytes as they are, hex encoded
+pub fn keccak_bytes(bytes: &[u8]) -> String {
+    HASHER.with(|hasher| {
+        let mut hasher = hasher.borrow_mut();
+        hasher.update(bytes);
+        hex::encode(hasher.finalize_reset())
+    })
+}
+
+pub fn base16_to_base10(base16: &str) -> U256 {
     U256::from_str_radix(base16, 16).unwrap()
 }
 
@@ -79,6 +326,12 @@ pub fn base10_to_base16(base10: U256) -> String {
     format!("{:x}", base10)
 }
 
+/// for skip_serializing_if on counters that default to 0, so adding one doesn't change the hash of anything that
+/// never moved it
+pub fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -100,6 +353,64 @@ mod tests {
         );
     }
 
+    /// the fast path has to hash exactly what hashing the sorted string would
+    #[test]
+    fn test_keccak_hash_matches_sorted_characters() {
+        fn reference<T: ?Sized + Serialize>(data: &T) -> String {
+            let mut hasher = Keccak256::new();
+            hasher.update(sort_characters(data));
+            hex::encode(hasher.finalize())
+        }
+        let long = "ab".repeat(1_000);
+        let header = Headers {
+            header: "some header".into(),
+        };
+        let non_ascii = Headers {
+            header: "żółw €".into(),
+        };
+        assert_eq!(keccak_hash(&long), reference(&long));
+        assert_eq!(keccak_hash(&header), reference(&header));
+        assert_eq!(keccak_hash(&non_ascii), reference(&non_ascii));
+        assert_eq!(keccak_hash(&vec![1u64, 2, 3]), reference(&vec![1u64, 2, 3]));
+        //and twice in a row on the same thread's hasher
+        assert_eq!(keccak_hash(&header), reference(&header));
+        assert_eq!(
+            keccak_bytes(b""),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+    }
+
+    #[test]
+    fn test_prep_state_from_mnemonic_is_deterministic() {
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let a = prep_state_from_mnemonic(phrase, 2, DEFAULT_CHAIN_ID).unwrap();
+        let b = prep_state_from_mnemonic(phrase, 2, DEFAULT_CHAIN_ID).unwrap();
+        assert_eq!(a.miner_address, b.miner_address);
+        //miner + 2 dev accounts
+        assert_eq!(a.keystore.read().unwrap().addresses().len(), 3);
+        assert_eq!(a.tx_queue.lock().unwrap().get_tx_series().len(), 3);
+        assert!(prep_state_from_mnemonic("nonsense", 0, DEFAULT_CHAIN_ID).is_err());
+    }
+
+    #[test]
+    fn test_serialized_state_has_no_secret_keys() {
+        let global_state = prep_state();
+        let miner_secret = global_state
+            .keystore
+            .read()
+            .unwrap()
+            .export_key(&global_state.miner_address)
+            .unwrap();
+        let serialized = serde_json::to_string(&global_state).unwrap();
+        assert!(serialized.contains(&global_state.miner_address.to_string()));
+        assert!(!serialized.contains(&miner_secret));
+        //but the node can still sign as the miner
+        assert_eq!(
+            global_state.miner_account().public_account.address,
+            global_state.miner_address
+        );
+    }
+
     #[test]
     fn test_keccak_works() {
         let data = Headers {
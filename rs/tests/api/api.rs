@@ -1,8 +1,11 @@
-use crate::helpers::{get_balance_call, mine_call, pause_execution, spawn_app, transact_call};
+use crate::helpers::{
+    get_balance_call, mine_call, pause_execution, spawn_app, transact_call, transact_request,
+    DEPLOY_GAS_LIMIT, MINER_ALLOCATION,
+};
 
+use rs::api::server::TxResponse;
 use rs::interpreter::OPCODE;
-
-use std::ops::Deref;
+use rs::transaction::tx_queue::TxStatus;
 
 #[actix_rt::test]
 async fn test_transaction_moves_value() {
@@ -29,15 +32,16 @@ async fn test_transaction_moves_value() {
     // ----------------------------------------------------------------------------- confirm balance change
 
     let balance_sender = get_balance_call(miner_addr, port).await;
-    assert_eq!(balance_sender, 1000 + 50 + 50 - 123);
+    assert_eq!(balance_sender, MINER_ALLOCATION + 50 + 50 - 123);
 
+    //created accounts start out empty - all of it came from the transfer
     let balance_receiver = get_balance_call(created_addr, port).await;
-    assert_eq!(balance_receiver, 1000 + 123);
+    assert_eq!(balance_receiver, 123);
 }
 
 #[actix_rt::test]
 pub async fn test_executes_smart_contract() {
-    let (port, miner_addr, _global_state) = spawn_app().await;
+    let (port, miner_addr, global_state) = spawn_app().await;
 
     //give enough time for workers to boot up
     pause_execution(1).await;
@@ -54,7 +58,7 @@ pub async fn test_executes_smart_contract() {
         OPCODE::ADD,
         OPCODE::STOP,
     ];
-    let tx = transact_call(None, code, 0, 100, port).await;
+    let tx = transact_call(None, code, 0, DEPLOY_GAS_LIMIT, port).await;
     let created_addr = tx.unsigned_tx.data.account_data.unwrap().address;
 
     //give enough time for workers to receive the tx and add it to the q, before mining a block
@@ -62,21 +66,30 @@ pub async fn test_executes_smart_contract() {
     mine_call(port).await;
 
     // ----------------------------------------------------------------------------- interact with sc
-    let _tx = transact_call(Some(created_addr), vec![], 0, 100, port).await;
+    let tx = transact_call(Some(created_addr), vec![], 0, 100, port).await;
 
     //give enough time for workers to receive the tx and add it to the q, before mining a block
     pause_execution(1).await;
     mine_call(port).await;
 
-    // ----------------------------------------------------------------------------- confirm balance change
+    // ----------------------------------------------------------------------------- confirm gas used
     // a little bit indirect - but because SC execution doesn't return anything to the caller
     // we have to check gas expenditure and make sure it matches what we'd expect if the SC executed
-
+    let gas_used = global_state
+        .blockchain
+        .read()
+        .unwrap()
+        .get_receipt(&tx.hash())
+        .unwrap()
+        .gas_used;
+    assert_eq!(gas_used, 2);
+
+    //the miner is the beneficiary as well, so the fee it paid came straight back
     let balance_sender = get_balance_call(miner_addr, port).await;
-    assert_eq!(balance_sender, 1000 + 50 + 50 - 2);
+    assert_eq!(balance_sender, MINER_ALLOCATION + 50 + 50);
 
     let balance_receiver = get_balance_call(created_addr, port).await;
-    assert_eq!(balance_receiver, 1000); //note that we're not giving the SC any gas
+    assert_eq!(balance_receiver, 0); //note that we're not giving the SC any gas
 }
 
 #[actix_rt::test]
@@ -98,7 +111,7 @@ pub async fn test_fails_smart_contract_execution_due_to_low_gas_limit() {
         OPCODE::ADD,
         OPCODE::STOP,
     ];
-    let tx = transact_call(None, code, 0, 100, port).await;
+    let tx = transact_call(None, code, 0, DEPLOY_GAS_LIMIT, port).await;
     let created_addr = tx.unsigned_tx.data.account_data.unwrap().address;
 
     //give enough time for workers to receive the tx and add it to the q, before mining a block
@@ -106,7 +119,13 @@ pub async fn test_fails_smart_contract_execution_due_to_low_gas_limit() {
     mine_call(port).await;
 
     // ----------------------------------------------------------------------------- interact with sc
-    let _tx = transact_call(Some(created_addr), vec![], 0, 1, port).await;
+    //the tx is invalid due to insufficient gas limit, so the node refuses to broadcast it
+    let res = transact_request(Some(created_addr), vec![], 0, 1, port).await;
+    assert_eq!(res.status().as_u16(), 422);
+    assert_eq!(
+        res.json::<TxResponse>().await.unwrap().status,
+        TxStatus::Rejected
+    );
 
     //give enough time for workers to receive the tx and add it to the q, before mining a block
     pause_execution(1).await;
@@ -117,10 +136,10 @@ pub async fn test_fails_smart_contract_execution_due_to_low_gas_limit() {
     // we have to check gas expenditure and make sure it matches what we'd expect if the SC executed
 
     let balance_sender = get_balance_call(miner_addr, port).await;
-    assert_eq!(balance_sender, 1000 + 50); //second block won't mine due to invalid tx. And tx is invalid due to insufficient gas limit.
+    assert_eq!(balance_sender, MINER_ALLOCATION + 50 + 50); //second block only has the mining reward in it - no gas was spent
 
     let balance_receiver = get_balance_call(created_addr, port).await;
-    assert_eq!(balance_receiver, 1000);
+    assert_eq!(balance_receiver, 0);
 }
 
 #[actix_rt::test]
@@ -145,7 +164,7 @@ pub async fn test_sc_stores_values_in_storage_trie() {
         OPCODE::STORE,
         OPCODE::STOP,
     ];
-    let tx = transact_call(None, code, 0, 100, port).await;
+    let tx = transact_call(None, code, 0, DEPLOY_GAS_LIMIT, port).await;
     let created_addr = tx.unsigned_tx.data.account_data.unwrap().address;
 
     //give enough time for workers to receive the tx and add it to the q, before mining a block
@@ -153,26 +172,33 @@ pub async fn test_sc_stores_values_in_storage_trie() {
     mine_call(port).await;
 
     // ----------------------------------------------------------------------------- interact with sc
-    let _tx = transact_call(Some(created_addr), vec![], 0, 100, port).await;
+    let tx = transact_call(Some(created_addr), vec![], 0, 100, port).await;
 
     //give enough time for workers to receive the tx and add it to the q, before mining a block
     pause_execution(1).await;
     mine_call(port).await;
 
-    // ----------------------------------------------------------------------------- confirm balance change
+    // ----------------------------------------------------------------------------- confirm gas used
     // a little bit indirect - but because SC execution doesn't return anything to the caller
     // we have to check gas expenditure and make sure it matches what we'd expect if the SC executed
-
+    let gas_used = global_state
+        .blockchain
+        .read()
+        .unwrap()
+        .get_receipt(&tx.hash())
+        .unwrap()
+        .gas_used;
+    assert_eq!(gas_used, 7);
+
+    //the miner is the beneficiary as well, so the fee it paid came straight back
     let balance_sender = get_balance_call(miner_addr, port).await;
-    assert_eq!(balance_sender, 1000 + 50 + 50 - 7);
+    assert_eq!(balance_sender, MINER_ALLOCATION + 50 + 50);
 
     let balance_receiver = get_balance_call(created_addr, port).await;
-    assert_eq!(balance_receiver, 1000); //note that we're not giving the SC any gas
+    assert_eq!(balance_receiver, 0); //note that we're not giving the SC any gas
 
-    let global_state = global_state.lock().unwrap();
-    let storage_trie = global_state
-        .deref()
-        .blockchain
+    let blockchain = global_state.blockchain.read().unwrap();
+    let storage_trie = blockchain
         .state
         .storage_trie_map
         .get(&created_addr)
@@ -1,75 +1,122 @@
-use rs::api::pubsub::{process_block, process_transaction, rabbit_consume};
-use rs::api::server::{run_server, TxRequest};
+use rs::account::address::Address;
+#[cfg(feature = "rabbitmq")]
+use rs::api::pubsub::{process_block, process_transaction};
+use rs::api::server::{run_server, TxRequest, TxResponse};
+use rs::config::NodeConfig;
 use rs::interpreter::OPCODE;
+#[cfg(feature = "rabbitmq")]
+use rs::network::rabbit::rabbit_consume;
 use rs::transaction::tx::Transaction;
+use rs::util::bigint::U256;
 use rs::util::{prep_state, GlobalState};
-use secp256k1::PublicKey;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-pub async fn spawn_app() -> (u16, PublicKey, Arc<Mutex<GlobalState>>) {
-    let global_state = prep_state();
-    let miner_addr = global_state.miner_account.public_account.address.clone();
+/// accounts start out empty, so the miner gets this much in the genesis state to have something to send
+pub const MINER_ALLOCATION: u64 = 1000;
+/// covers deploying the tests' contracts, whose code the miner pays for
+pub const DEPLOY_GAS_LIMIT: u64 = 1000;
 
-    let wrapped_gs = Arc::new(Mutex::new(global_state));
+pub async fn spawn_app() -> (u16, Address, Arc<GlobalState>) {
+    let mut global_state = prep_state();
+    let miner_addr = global_state.miner_address;
+    global_state
+        .blockchain
+        .get_mut()
+        .unwrap()
+        .state
+        .allocate(miner_addr, MINER_ALLOCATION);
+
+    let wrapped_gs = Arc::new(global_state);
     let port = rand::random::<u16>();
 
     let gs_clone = wrapped_gs.clone();
-    let gs_clone2 = wrapped_gs.clone();
-    let gs_clone3 = wrapped_gs.clone();
-    tokio::spawn(async move {
-        rabbit_consume(process_block, gs_clone, "blocks")
-            .await
-            .unwrap();
-    });
-    tokio::spawn(async move {
-        rabbit_consume(process_transaction, gs_clone2, "tx")
-            .await
-            .unwrap();
-    });
+    //no peers - a tx the node broadcasts still goes into its own queue, see network::broadcast()
+    #[cfg(not(feature = "rabbitmq"))]
+    wrapped_gs
+        .gossip
+        .start(wrapped_gs.clone(), "127.0.0.1:0", &[])
+        .await
+        .unwrap();
+    //with rabbitmq it only gets there through our own consumer
+    #[cfg(feature = "rabbitmq")]
+    {
+        let (gs_clone, gs_clone2) = (wrapped_gs.clone(), wrapped_gs.clone());
+        tokio::spawn(async move {
+            rabbit_consume(process_block, gs_clone, "blocks")
+                .await
+                .unwrap();
+        });
+        tokio::spawn(async move {
+            rabbit_consume(process_transaction, gs_clone2, "tx")
+                .await
+                .unwrap();
+        });
+    }
 
     println!("listening on port {}", &port);
-    let server = run_server(&format!("localhost:{}", port), wrapped_gs).unwrap();
+    let config = NodeConfig {
+        port,
+        ..NodeConfig::default()
+    };
+    let server = run_server(&config, wrapped_gs).unwrap();
     tokio::spawn(server);
 
-    (port, miner_addr, gs_clone3)
+    (port, miner_addr, gs_clone)
 }
 
 pub async fn transact_call(
-    to: Option<PublicKey>,
+    to: Option<Address>,
     code: Vec<OPCODE>,
     value: u64,
     gas_limit: u64,
     port: u16,
 ) -> Transaction {
+    let res = transact_request(to, code, value, gas_limit, port).await;
+
+    // check response & extract addr
+    assert_eq!(
+        res.status().as_u16(),
+        200,
+        "the api didn't respond with a 200.",
+    );
+    res.json::<TxResponse>().await.unwrap().tx
+}
+
+/// same as transact_call(), but leaves checking the response to the caller
+pub async fn transact_request(
+    to: Option<Address>,
+    code: Vec<OPCODE>,
+    value: u64,
+    gas_limit: u64,
+    port: u16,
+) -> reqwest::Response {
     // prep the tx
     let tx_request = TxRequest {
-        value,
-        to,
+        value: value.into(),
+        to: to.map(Into::into),
         code,
-        gas_limit,
+        gas_limit: gas_limit.into(),
+        from: None,
+        passphrase: None,
+        multisig: None,
+        fee_payer: None,
+        fee_payer_passphrase: None,
+        call_data: vec![],
     };
 
     // send the tx
     let client = reqwest::Client::new();
-    let res = client
+    client
         .post(format!("http://localhost:{}/transact", port))
         .header("Content-Type", "application/json")
         .json(&tx_request)
         .send()
         .await
-        .unwrap();
-
-    // check response & extract addr
-    assert_eq!(
-        res.status().as_u16(),
-        200,
-        "the api didn't respond with a 200.",
-    );
-    res.json::<Transaction>().await.unwrap()
+        .unwrap()
 }
 
-pub async fn get_balance_call(addr: PublicKey, port: u16) -> u64 {
+pub async fn get_balance_call(addr: Address, port: u16) -> u64 {
     let client = reqwest::Client::new();
     let res = client
         .get(format!("http://localhost:{}/balance/{}", port, addr))
@@ -83,8 +130,9 @@ pub async fn get_balance_call(addr: PublicKey, port: u16) -> u64 {
         "the api didn't respond with a 200.",
     );
 
-    let res_json = res.json::<HashMap<String, u64>>().await.unwrap();
-    res_json.get("balance").unwrap().to_owned()
+    //comes back as 0x hex, the tests only ever deal in small amounts
+    let res_json = res.json::<HashMap<String, U256>>().await.unwrap();
+    res_json.get("balance").unwrap().as_u64()
 }
 
 pub async fn mine_call(port: u16) {
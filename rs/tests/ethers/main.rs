@@ -0,0 +1,65 @@
+#![cfg(feature = "ethers")]
+
+//drives a node through /rpc with ethers-rs, to catch the json-rpc drifting from what ethereum clients expect:
+//   cargo test --features ethers --test ethers
+// (!) only the read side so far. Our tx aren't ethereum's RLP and contract code is our own opcodes, so ethers can't
+// sign a transfer or deploy anything - the transfer below goes through /transact. There's no
+// eth_getTransactionReceipt either, and get_block() can't read our blocks: the nonce is the decimal u128 the proof
+// of work found, not 8 bytes of hex
+
+#[allow(dead_code)]
+#[path = "../api/helpers.rs"]
+mod helpers;
+
+use ethers_core::types::{Address, BlockNumber, SyncingStatus};
+use ethers_providers::{Http, Middleware, Provider};
+use helpers::{mine_call, pause_execution, spawn_app, transact_call, MINER_ALLOCATION};
+use rs::config::DEFAULT_CHAIN_ID;
+use std::convert::TryFrom;
+
+fn address(address: &rs::account::address::Address) -> Address {
+    address.to_string().parse().unwrap()
+}
+
+#[actix_rt::test]
+async fn test_reads_the_chain() {
+    let (port, miner_addr, _global_state) = spawn_app().await;
+    let provider = Provider::<Http>::try_from(format!("http://localhost:{}/rpc", port)).unwrap();
+
+    //give enough time for workers to boot up
+    pause_execution(1).await;
+
+    assert_eq!(
+        provider.get_chainid().await.unwrap().as_u64(),
+        DEFAULT_CHAIN_ID
+    );
+    assert_eq!(provider.get_block_number().await.unwrap().as_u64(), 0);
+    assert_eq!(
+        provider
+            .get_balance(address(&miner_addr), None)
+            .await
+            .unwrap()
+            .as_u64(),
+        MINER_ALLOCATION
+    );
+
+    // ----------------------------------------------------------------------------- fund an account
+    let tx = transact_call(None, vec![], 0, 100, port).await;
+    let created_addr = tx.unsigned_tx.data.account_data.unwrap().address;
+    pause_execution(1).await;
+    mine_call(port).await;
+    transact_call(Some(created_addr), vec![], 123, 100, port).await;
+    pause_execution(1).await;
+    mine_call(port).await;
+
+    assert_eq!(provider.get_block_number().await.unwrap().as_u64(), 2);
+    assert_eq!(
+        provider
+            .get_balance(address(&created_addr), Some(BlockNumber::Latest.into()))
+            .await
+            .unwrap()
+            .as_u64(),
+        123
+    );
+    assert_eq!(provider.syncing().await.unwrap(), SyncingStatus::IsFalse);
+}
@@ -0,0 +1,42 @@
+#![cfg(feature = "ethtests")]
+
+//runs VMTests fixtures through the interpreter:
+//   cargo test --features ethtests --test ethtests -- --nocapture
+// By default that's tests/fixtures/vmtests/ - a handful of hand-written cases in the same format, not official vectors. To run the real thing point
+// ETHEREUM_TESTS_DIR at a checkout of https://github.com/ethereum/tests, eg
+//   ETHEREUM_TESTS_DIR=../../tests/LegacyTests/Constantinople cargo test --features ethtests --test ethtests
+
+use rs::interpreter::vmtests::{run_dir, VMTESTS_DIR};
+use std::path::PathBuf;
+
+fn fixtures_dir() -> PathBuf {
+    match std::env::var("ETHEREUM_TESTS_DIR") {
+        Ok(dir) => PathBuf::from(dir).join(VMTESTS_DIR),
+        Err(_) => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/vmtests"),
+    }
+}
+
+#[test]
+fn vmtests() {
+    let dir = fixtures_dir();
+    let report = run_dir(&dir).unwrap();
+
+    println!(
+        "{:?}: {} passed, {} skipped, {} failed",
+        dir,
+        report.passed,
+        report.skipped_count(),
+        report.failed.len()
+    );
+    let mut skipped: Vec<_> = report.skipped.iter().collect();
+    skipped.sort_by(|a, b| b.1.cmp(a.1));
+    for (reason, count) in skipped {
+        println!("  skipped {}: {}", count, reason);
+    }
+    for (name, reason) in report.failed.iter() {
+        println!("  FAILED {}: {}", name, reason);
+    }
+
+    assert!(report.passed > 0, "nothing ran");
+    assert!(report.failed.is_empty());
+}